@@ -0,0 +1,75 @@
+//! Parses 100k synthetic frames, a fifth of them truncated inside the TCP header, and reports the time per
+//! packet for the valid frames, the truncated ones, and the mix. Failures are reported as `ParseError`, which
+//! doesn't allocate, so a truncated frame should cost no more than a valid one.
+//!
+//! This measures the current tree only. The `format!` based errors it replaced can't be built next to it, so
+//! a before and after comparison means running this against each revision.
+//!
+//! Run with `cargo bench --bench parse`.
+
+#![allow(unused)]
+
+use std::hint::black_box;
+use std::time::{Duration, Instant};
+use packet_analyser::{ethernet, ipv4};
+use packet_analyser::error::ParseError;
+use packet_analyser::ethernet::{Ethernet, Fcs};
+
+
+const PACKETS: usize = 100_000;
+const ROUNDS: usize = 10;
+
+
+/// An Ethernet/IPv4/TCP frame with a 20 byte header and 16 bytes of payload.
+fn frame() -> Vec<u8> {
+    let mut data = vec![0u8; 14 + 20 + 20 + 16];
+    data[12..14].copy_from_slice(&[0x08, 0x00]);
+    data[14] = 0x45;
+    data[16..18].copy_from_slice(&((20 + 20 + 16) as u16).to_be_bytes());
+    data[22] = 64;
+    data[23] = 6;
+    data[26..30].copy_from_slice(&[10, 0, 0, 1]);
+    data[30..34].copy_from_slice(&[10, 0, 0, 2]);
+    data[34..36].copy_from_slice(&443u16.to_be_bytes());
+    data[36..38].copy_from_slice(&51000u16.to_be_bytes());
+    data[46] = 0x50;
+    data[47] = 0x18;
+    data
+}
+
+fn parse(data: &[u8]) -> Result<usize, ParseError> {
+    let ethernet = Ethernet::from_bytes_with_fcs(data, Fcs::Absent)?;
+    match ethernet.payload()? {
+        ethernet::Payload::IPv4(ip) => match ip.payload()? {
+            ipv4::Payload::Tcp(tcp) => Ok(tcp.raw_payload().len()),
+            _ => Ok(0),
+        },
+        _ => Ok(0),
+    }
+}
+
+fn measure(name: &str, frames: &[Vec<u8>]) {
+    let mut best = Duration::MAX;
+    let mut errors = 0;
+    for _ in 0..ROUNDS {
+        errors = 0;
+        let start = Instant::now();
+        for frame in frames {
+            if black_box(parse(black_box(frame))).is_err() { errors += 1 }
+        }
+        best = best.min(start.elapsed());
+    }
+    println!("{:<12} {:>10.1} ns/packet, {} errors per {} packets", name, best.as_nanos() as f64 / frames.len() as f64, errors, frames.len());
+}
+
+fn main() {
+    let valid = frame();
+    let truncated = valid[..14 + 20 + 12].to_vec();
+    let frames: Vec<Vec<u8>> = (0..PACKETS)
+        .map(|i| if i % 5 == 0 { truncated.clone() } else { valid.clone() })
+        .collect();
+
+    measure("valid", &vec![valid; PACKETS]);
+    measure("truncated", &vec![truncated; PACKETS]);
+    measure("mixed", &frames);
+}
@@ -0,0 +1,361 @@
+use std::collections::{HashMap, VecDeque};
+use std::io::Error;
+use crate::reassembly::{is_before, StreamKey};
+use crate::tcp::Tcp;
+use crate::time_window;
+use crate::visitor::{Context, Visitor};
+
+
+#[derive(Debug, Copy, Clone, Default, Eq, PartialEq)]
+pub struct Anomalies {
+    /// Segments whose data was all sent before.
+    pub retransmissions: usize,
+    /// Segments that arrived after data with higher sequence numbers and weren't sent before.
+    pub out_of_order: usize,
+    /// Pure ACKs repeating the previous acknowledgment number and window.
+    pub duplicate_acks: usize,
+    /// Probes repeating the byte before the next sequence number, with at most that one byte of data.
+    pub keep_alives: usize,
+    /// Segments advertising a window of zero.
+    pub zero_windows: usize,
+    /// Segments with a non-zero urgent pointer but without URG.
+    pub stray_urgent_pointers: usize,
+}
+
+impl Anomalies {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+}
+
+
+/// What a segment was recognized as.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Event {
+    Retransmission,
+    OutOfOrder,
+    DuplicateAck,
+    KeepAlive,
+    ZeroWindow,
+    /// The first non-zero window after a zero window.
+    WindowOpened,
+    StrayUrgentPointer,
+}
+
+impl Event {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Event::Retransmission     => "retransmission",
+            Event::OutOfOrder         => "out of order",
+            Event::DuplicateAck       => "duplicate ack",
+            Event::KeepAlive          => "keep-alive",
+            Event::ZeroWindow         => "zero window",
+            Event::WindowOpened       => "window opened",
+            Event::StrayUrgentPointer => "urgent pointer without URG",
+        }
+    }
+}
+
+
+/// What one direction of a connection has sent recently.
+#[derive(Debug, Default)]
+struct Direction {
+    /// Sequence number following the highest byte sent.
+    end: Option<u32>,
+    /// The most recent segments as (sequence, length).
+    ranges: VecDeque<(u32, u32)>,
+    /// Acknowledgment number and window of the last pure ACK.
+    last_ack: Option<(u32, u16)>,
+    /// Whether the last segment advertised a window of zero.
+    zero_window: bool,
+    anomalies: Anomalies,
+}
+
+impl Direction {
+    fn was_sent(&self, sequence: u32, length: u32) -> bool {
+        let end = sequence.wrapping_add(length);
+        self.ranges.iter().any(|(start, size)| !is_before(sequence, *start) && !is_before(start.wrapping_add(*size), end))
+    }
+}
+
+
+/// Counts retransmissions, out of order segments, duplicate ACKs, keep-alives, zero windows and urgent pointers
+/// without URG per direction of each TCP connection.
+#[derive(Debug, Default)]
+pub struct AnomalyDetector {
+    directions: HashMap<StreamKey, Direction>,
+}
+
+impl AnomalyDetector {
+    /// Number of recent segments remembered per direction to recognize retransmissions.
+    pub const MAX_RANGES: usize = 64;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records a segment and returns what it was recognized as, if anything.
+    pub fn push(&mut self, key: StreamKey, tcp: &Tcp) -> Vec<Event> {
+        let direction = self.directions.entry(key).or_default();
+        let mut events = Vec::new();
+
+        if tcp.has_stray_urgent_pointer() {
+            direction.anomalies.stray_urgent_pointers += 1;
+            events.push(Event::StrayUrgentPointer);
+        }
+
+        if tcp.rst() == 0 && tcp.syn() == 0 {
+            let zero_window = tcp.window_size() == 0;
+            if zero_window {
+                direction.anomalies.zero_windows += 1;
+                events.push(Event::ZeroWindow);
+            } else if direction.zero_window {
+                events.push(Event::WindowOpened);
+            }
+            direction.zero_window = zero_window;
+        }
+
+        // SYN and FIN take up one sequence number each.
+        let length = tcp.raw_payload().len() as u32 + tcp.syn() as u32 + tcp.fin() as u32;
+        let sequence = tcp.sequence_number();
+
+        // A keep-alive is sent one below the next sequence number so the peer must answer with an ACK. Checked
+        // before anything else since it would otherwise look like a retransmission or a duplicate ACK.
+        let is_keep_alive = tcp.ack() != 0 && tcp.rst() == 0 && tcp.syn() == 0 && tcp.fin() == 0
+            && tcp.raw_payload().len() <= 1
+            && direction.end == Some(sequence.wrapping_add(1));
+        if is_keep_alive {
+            direction.anomalies.keep_alives += 1;
+            events.push(Event::KeepAlive);
+            return events;
+        }
+
+        if length == 0 {
+            if tcp.ack() != 0 && tcp.rst() == 0 {
+                let ack = (tcp.acknowledgment_number(), tcp.window_size());
+                if direction.last_ack == Some(ack) {
+                    direction.anomalies.duplicate_acks += 1;
+                    events.push(Event::DuplicateAck);
+                }
+                direction.last_ack = Some(ack);
+            }
+            return events;
+        }
+        direction.last_ack = None;
+
+        let end = sequence.wrapping_add(length);
+        match direction.end {
+            Some(highest) if is_before(sequence, highest) => {
+                if direction.was_sent(sequence, length) {
+                    direction.anomalies.retransmissions += 1;
+                    events.push(Event::Retransmission);
+                } else {
+                    direction.anomalies.out_of_order += 1;
+                    events.push(Event::OutOfOrder);
+                }
+            },
+            _ => {},
+        }
+        if direction.end.is_none_or(|highest| is_before(highest, end)) {
+            direction.end = Some(end);
+        }
+
+        direction.ranges.push_back((sequence, length));
+        if direction.ranges.len() > Self::MAX_RANGES {
+            direction.ranges.pop_front();
+        }
+        events
+    }
+
+    pub fn anomalies(&self, key: &StreamKey) -> Anomalies {
+        self.directions.get(key).map(|x| x.anomalies).unwrap_or_default()
+    }
+
+    /// The directions with at least one anomaly, in address order.
+    pub fn report(&self) -> Vec<(StreamKey, Anomalies)> {
+        let mut report: Vec<(StreamKey, Anomalies)> = self.directions.iter()
+            .filter(|(_, direction)| !direction.anomalies.is_empty())
+            .map(|(key, direction)| (*key, direction.anomalies))
+            .collect();
+        report.sort_by_key(|(key, _)| (key.source, key.source_port, key.destination, key.destination_port));
+        report
+    }
+}
+
+
+/// Prints the TCP anomalies of each connection at the end of the capture.
+pub struct AnomalyVisitor {
+    detector: AnomalyDetector,
+}
+
+impl AnomalyVisitor {
+    pub fn new() -> Self {
+        Self { detector: AnomalyDetector::new() }
+    }
+}
+
+impl Default for AnomalyVisitor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+impl<'a> Visitor<'a, ()> for AnomalyVisitor {
+    fn visit_tcp(&mut self, packet: &Tcp<'a>, context: &Context<'a>) -> Result<(), Error> {
+        if let Some(ip) = &context.ipv4 {
+            self.detector.push(StreamKey::new(ip, packet), packet);
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        let report = self.detector.report();
+        if report.is_empty() {
+            println!("No TCP anomalies");
+        }
+        for (key, anomalies) in report {
+            println!(
+                "{}: {} retransmissions, {} out of order, {} duplicate acks, {} keep-alives, {} zero windows, {} urgent pointers without URG",
+                key, anomalies.retransmissions, anomalies.out_of_order, anomalies.duplicate_acks, anomalies.keep_alives, anomalies.zero_windows,
+                anomalies.stray_urgent_pointers
+            );
+        }
+        Ok(())
+    }
+}
+
+
+/// Prints keep-alives and zero windows as they happen, with the time and connection, to diagnose stalls.
+pub struct StallVisitor {
+    detector: AnomalyDetector,
+}
+
+impl StallVisitor {
+    pub fn new() -> Self {
+        Self { detector: AnomalyDetector::new() }
+    }
+}
+
+impl Default for StallVisitor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+impl<'a> Visitor<'a, ()> for StallVisitor {
+    fn visit_tcp(&mut self, packet: &Tcp<'a>, context: &Context<'a>) -> Result<(), Error> {
+        let (Some(ip), Some(header)) = (&context.ipv4, context.header) else { return Ok(()) };
+        let key = StreamKey::new(ip, packet);
+        let time = time_window::timestamp(header);
+
+        for event in self.detector.push(key, packet) {
+            if matches!(event, Event::KeepAlive | Event::ZeroWindow | Event::WindowOpened) {
+                println!("{}.{:06} {}: {}", time.as_secs(), time.subsec_micros(), key, event.name());
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        for (key, anomalies) in self.detector.report() {
+            if anomalies.keep_alives > 0 || anomalies.zero_windows > 0 {
+                println!("{}: {} keep-alives, {} zero windows", key, anomalies.keep_alives, anomalies.zero_windows);
+            }
+        }
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::{TcpBuilder, TCP_ACK, TCP_PSH, TCP_URG};
+    use crate::testing::ip;
+
+    fn key() -> StreamKey {
+        StreamKey { source: ip(10, 0, 0, 1), source_port: 51000, destination: ip(10, 0, 0, 2), destination_port: 80 }
+    }
+
+    /// The events of a segment from the client carrying `length` bytes from `sequence`.
+    fn data(detector: &mut AnomalyDetector, sequence: u32, length: usize) -> Vec<Event> {
+        let segment = TcpBuilder::new(51000, 80).with_flags(TCP_ACK | TCP_PSH).with_sequence_number(sequence).with_acknowledgment_number(1).with_window_size(1024).with_payload(&vec![b'x'; length]).build(ip(10, 0, 0, 1), ip(10, 0, 0, 2));
+        detector.push(key(), &Tcp::from_bytes(&segment).unwrap())
+    }
+
+    /// The events of a pure ACK from the client.
+    fn ack(detector: &mut AnomalyDetector, acknowledgment: u32) -> Vec<Event> {
+        let segment = TcpBuilder::new(51000, 80).with_flags(TCP_ACK).with_sequence_number(1000).with_acknowledgment_number(acknowledgment).with_window_size(1024).build(ip(10, 0, 0, 1), ip(10, 0, 0, 2));
+        detector.push(key(), &Tcp::from_bytes(&segment).unwrap())
+    }
+
+    #[test]
+    fn counts_a_retransmitted_segment() {
+        let mut detector = AnomalyDetector::new();
+        assert_eq!(data(&mut detector, 1000, 100), []);
+        assert_eq!(data(&mut detector, 1100, 100), []);
+        assert_eq!(data(&mut detector, 1000, 100), [Event::Retransmission]);
+        assert_eq!(detector.anomalies(&key()), Anomalies { retransmissions: 1, ..Anomalies::default() });
+    }
+
+    #[test]
+    fn counts_a_segment_arriving_after_later_data() {
+        let mut detector = AnomalyDetector::new();
+        assert_eq!(data(&mut detector, 1000, 100), []);
+        assert_eq!(data(&mut detector, 1200, 100), []);
+        assert_eq!(data(&mut detector, 1100, 100), [Event::OutOfOrder]);
+        assert_eq!(detector.anomalies(&key()).out_of_order, 1);
+        assert_eq!(detector.anomalies(&key()).retransmissions, 0);
+    }
+
+    #[test]
+    fn counts_duplicate_acks() {
+        let mut detector = AnomalyDetector::new();
+        assert_eq!(ack(&mut detector, 5000), []);
+        assert_eq!(ack(&mut detector, 5000), [Event::DuplicateAck]);
+        assert_eq!(ack(&mut detector, 5000), [Event::DuplicateAck]);
+        assert_eq!(ack(&mut detector, 6000), []);
+        assert_eq!(detector.anomalies(&key()).duplicate_acks, 2);
+        assert_eq!(detector.report(), [(key(), Anomalies { duplicate_acks: 2, ..Anomalies::default() })]);
+    }
+
+    /// The events of a segment from the client with `payload` at `sequence` advertising `window`.
+    fn segment(detector: &mut AnomalyDetector, sequence: u32, payload: &[u8], window: u16) -> Vec<Event> {
+        let segment = TcpBuilder::new(51000, 80).with_flags(TCP_ACK).with_sequence_number(sequence).with_acknowledgment_number(1).with_window_size(window).with_payload(payload).build(ip(10, 0, 0, 1), ip(10, 0, 0, 2));
+        detector.push(key(), &Tcp::from_bytes(&segment).unwrap())
+    }
+
+    #[test]
+    fn recognizes_keep_alives_instead_of_retransmissions() {
+        let mut detector = AnomalyDetector::new();
+        assert_eq!(data(&mut detector, 1000, 100), []);
+        // Probes one below the next sequence number, without data and with the byte repeated.
+        assert_eq!(segment(&mut detector, 1099, b"", 1024), [Event::KeepAlive]);
+        assert_eq!(segment(&mut detector, 1099, b"x", 1024), [Event::KeepAlive]);
+        // Resending more than that byte is a retransmission.
+        assert_eq!(data(&mut detector, 1050, 50), [Event::Retransmission]);
+        assert_eq!(detector.anomalies(&key()), Anomalies { keep_alives: 2, retransmissions: 1, ..Anomalies::default() });
+    }
+
+    #[test]
+    fn recognizes_a_zero_window_and_its_reopening() {
+        let mut detector = AnomalyDetector::new();
+        assert_eq!(segment(&mut detector, 1000, b"", 1024), []);
+        assert_eq!(segment(&mut detector, 1000, b"", 0), [Event::ZeroWindow]);
+        assert_eq!(segment(&mut detector, 1000, b"", 0), [Event::ZeroWindow, Event::DuplicateAck]);
+        assert_eq!(segment(&mut detector, 1000, b"", 4096), [Event::WindowOpened]);
+        assert_eq!(detector.anomalies(&key()).zero_windows, 2);
+    }
+    #[test]
+    fn counts_an_urgent_pointer_without_urg() {
+        let mut detector = AnomalyDetector::new();
+        let segment = TcpBuilder::new(51000, 80).with_flags(TCP_ACK | TCP_PSH).with_sequence_number(1000).with_acknowledgment_number(1).with_window_size(1024).with_urgent_pointer(1).with_payload(b"x").build(ip(10, 0, 0, 1), ip(10, 0, 0, 2));
+        assert_eq!(detector.push(key(), &Tcp::from_bytes(&segment).unwrap()), [Event::StrayUrgentPointer]);
+
+        let segment = TcpBuilder::new(51000, 80).with_flags(TCP_ACK | TCP_PSH | TCP_URG).with_sequence_number(1001).with_acknowledgment_number(1).with_window_size(1024).with_urgent_pointer(1).with_payload(b"!").build(ip(10, 0, 0, 1), ip(10, 0, 0, 2));
+        assert_eq!(detector.push(key(), &Tcp::from_bytes(&segment).unwrap()), []);
+        assert_eq!(detector.anomalies(&key()), Anomalies { stray_urgent_pointers: 1, ..Anomalies::default() });
+    }
+}
@@ -0,0 +1,170 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::OnceLock;
+use crate::dns;
+use crate::http;
+
+
+/// The transport an application protocol runs over.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Transport {
+    Tcp,
+    Udp,
+}
+
+
+/// An application protocol recognized by its port.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum AppProtocol {
+    Dns,
+    Http,
+    Tls,
+    Dhcp,
+}
+
+impl AppProtocol {
+    pub fn name(&self) -> &'static str {
+        match self {
+            AppProtocol::Dns  => "dns",
+            AppProtocol::Http => "http",
+            AppProtocol::Tls  => "tls",
+            AppProtocol::Dhcp => "dhcp",
+        }
+    }
+
+    /// The transports the protocol runs over.
+    pub fn transports(&self) -> &'static [Transport] {
+        match self {
+            AppProtocol::Dns  => &[Transport::Tcp, Transport::Udp],
+            AppProtocol::Http => &[Transport::Tcp],
+            AppProtocol::Tls  => &[Transport::Tcp],
+            AppProtocol::Dhcp => &[Transport::Udp],
+        }
+    }
+}
+
+impl FromStr for AppProtocol {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "dns"  => Ok(AppProtocol::Dns),
+            "http" => Ok(AppProtocol::Http),
+            "tls"  => Ok(AppProtocol::Tls),
+            "dhcp" => Ok(AppProtocol::Dhcp),
+            _ => Err(format!("Unknown application protocol '{}', expected dns, http, tls or dhcp", text)),
+        }
+    }
+}
+
+
+/// Parses `PROTOCOL:PORT`, e.g. `http:8000`.
+pub fn parse_port(text: &str) -> Result<(AppProtocol, u16), String> {
+    let (protocol, port) = text.split_once(':').ok_or(format!("Expected PROTOCOL:PORT, got '{}'", text))?;
+    let port = port.parse().map_err(|_| format!("Invalid port '{}'", port))?;
+    Ok((protocol.parse()?, port))
+}
+
+
+/// Which application protocol each TCP and UDP port carries, so payloads are handed to the right dissector in
+/// one place instead of by port checks spread over the visitors. A segment matches by either of its ports, the
+/// destination port first.
+#[derive(Debug, Clone)]
+pub struct AppDispatch {
+    ports: HashMap<(Transport, u16), AppProtocol>,
+}
+
+impl AppDispatch {
+    /// The well-known ports of the protocols.
+    pub fn new() -> Self {
+        let mut dispatch = Self::empty().with_port(AppProtocol::Dns, dns::PORT);
+        for port in http::PORTS {
+            dispatch = dispatch.with_port(AppProtocol::Http, port);
+        }
+        dispatch
+            .with_port(AppProtocol::Tls, 443)
+            .with_port(AppProtocol::Dhcp, 67)
+            .with_port(AppProtocol::Dhcp, 68)
+    }
+
+    pub fn empty() -> Self {
+        Self { ports: HashMap::new() }
+    }
+
+    /// The table of `new`, shared by visitors that don't have their own.
+    pub fn builtin() -> &'static AppDispatch {
+        static BUILTIN: OnceLock<AppDispatch> = OnceLock::new();
+        BUILTIN.get_or_init(AppDispatch::new)
+    }
+
+    /// Maps `port` to `protocol` on each of its transports, replacing what the port was mapped to.
+    pub fn with_port(mut self, protocol: AppProtocol, port: u16) -> Self {
+        for transport in protocol.transports() {
+            self.ports.insert((*transport, port), protocol);
+        }
+        self
+    }
+
+    /// Removes the mapping of `port`, e.g. to stop decoding it as HTTP.
+    pub fn without_port(mut self, transport: Transport, port: u16) -> Self {
+        self.ports.remove(&(transport, port));
+        self
+    }
+
+    pub fn get(&self, transport: Transport, port: u16) -> Option<AppProtocol> {
+        self.ports.get(&(transport, port)).copied()
+    }
+
+    /// The protocol of a segment or datagram between `source_port` and `destination_port`.
+    pub fn protocol(&self, transport: Transport, source_port: u16, destination_port: u16) -> Option<AppProtocol> {
+        self.get(transport, destination_port).or_else(|| self.get(transport, source_port))
+    }
+}
+
+impl Default for AppDispatch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dispatches_the_well_known_ports_on_either_side() {
+        let apps = AppDispatch::new();
+        assert_eq!(apps.protocol(Transport::Udp, 53000, 53), Some(AppProtocol::Dns));
+        assert_eq!(apps.protocol(Transport::Tcp, 443, 51000), Some(AppProtocol::Tls));
+        assert_eq!(apps.protocol(Transport::Udp, 68, 67), Some(AppProtocol::Dhcp));
+        assert_eq!(apps.protocol(Transport::Tcp, 51000, 80), Some(AppProtocol::Http));
+        // DHCP and HTTP only run over one transport.
+        assert_eq!(apps.protocol(Transport::Tcp, 68, 67), None);
+        assert_eq!(apps.protocol(Transport::Udp, 51000, 80), None);
+        assert_eq!(apps.protocol(Transport::Tcp, 51000, 51001), None);
+    }
+
+    #[test]
+    fn dispatches_http_on_8080_by_an_override() {
+        let apps = AppDispatch::new().without_port(Transport::Tcp, 8080);
+        assert_eq!(apps.protocol(Transport::Tcp, 51000, 8080), None);
+
+        let apps = apps.with_port(AppProtocol::Http, 8080);
+        assert_eq!(apps.protocol(Transport::Tcp, 51000, 8080), Some(AppProtocol::Http));
+        assert_eq!(apps.protocol(Transport::Tcp, 8080, 51000), Some(AppProtocol::Http));
+
+        // A later mapping replaces the earlier one, and the destination port wins over the source port.
+        let apps = apps.with_port(AppProtocol::Tls, 8080);
+        assert_eq!(apps.get(Transport::Tcp, 8080), Some(AppProtocol::Tls));
+        assert_eq!(apps.protocol(Transport::Tcp, 80, 8080), Some(AppProtocol::Tls));
+    }
+
+    #[test]
+    fn parses_protocol_ports() {
+        assert_eq!(parse_port("http:8080"), Ok((AppProtocol::Http, 8080)));
+        assert!(parse_port("http").is_err());
+        assert!(parse_port("http:80800").is_err());
+        assert!(parse_port("gopher:70").is_err());
+    }
+}
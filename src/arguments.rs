@@ -0,0 +1,408 @@
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+use packet_analyser::reassembly::{OverlapPolicy, StreamKey};
+use packet_analyser::ring_buffer::RingBuffer;
+use packet_analyser::count::CountBy;
+use packet_analyser::checksum::ChecksumPolicy;
+use packet_analyser::ipv4::Ipv4Address;
+use packet_analyser::time_window::{TimeBound, TimeWindow};
+use packet_analyser::replay::Pacer;
+use packet_analyser::ethernet::{self, Fcs};
+use packet_analyser::shared::MacAddress;
+use packet_analyser::rewrite;
+use packet_analyser::protocol_filter::ProtocolSet;
+use packet_analyser::scan::Thresholds;
+use packet_analyser::sample::Sampling;
+use packet_analyser::limits::ResourceLimits;
+use packet_analyser::flow_export::FlowFormat;
+use packet_analyser::digest::HashOptions;
+use packet_analyser::app::{self, AppDispatch, AppProtocol};
+use packet_analyser::payload_dump::PayloadDirection;
+
+
+/// Command line options.
+///
+/// Usage: `packet-analyser --list`
+///        `packet-analyser [--summary] [--count N] [--duration SECS]
+///                         [--follow SRC:PORT-DST:PORT [--separator TEXT]] [--overlap-policy first|last|linux|bsd]
+///                         [--tcp-payload-only [--payload-out PATH] [--payload-flow SRC:PORT-DST:PORT
+///                                                                   [--payload-direction both|forward|reverse]]]
+///                         [--ring-buffer COUNT:SIZE_MB] [--debug-out PATH] [--dedup [--dedup-window N]]
+///                         [--sample 1/N [--sample-seed SEED]] [--interval SECS]
+///                         [--timing] [--no-defrag] [--count-by srcip|dstip|srcport|dstport|proto|ethertype]
+///                         [--dns] [--anomalies] [--connections] [--stalls] [--rtt] [--mss] [--ecn] [--qos]
+///                         [--ndjson] [--xml] [-T pdml|json]
+///                         [--verbose 0|1|2] [--ip-prefixes PATH] [--since TIME] [--until TIME] [--log]
+///                         [--checksums verify|skip|inbound [--local-ip ADDRESS]... [--local-mac ADDRESS]...]
+///                         [--assemble-files DIR] [--headers-only] [--replay [--speed FACTOR]] [--max-payload-bytes N]
+///                         [--show-drops] [--max-frame-size BYTES] [--only PROTOCOLS] [--exclude PROTOCOLS]
+///                         [--scans [--scan-ports N] [--syn-flood N] [--scan-window SECS]]
+///                         [--relative-seq] [--pretty] [--mtu BYTES] [--fcs auto|present|absent]
+///                         [--rewrite-out PATH [--rewrite-mac OLD=NEW]... [--rewrite-ip OLD=NEW]...]
+///                         [--max-flows N] [--max-reassembly-bytes N] [--flow-idle-timeout SECS]
+///                         [--max-decompressed-bytes N]
+///                         [--flow-export PATH [--flow-format csv|ipfix]]
+///                         [--app-port PROTOCOL:PORT]...
+///                         [--hash [--hash-from link|network|transport] [--hash-ignore ttl,checksums] [--hash-flows]]
+///                         [--diff OTHER_FILE]
+///                         [interface | file | fifo | -]`
+///
+/// `TIME` is either `HH:MM:SS` in UTC or seconds since the epoch.
+/// `PROTOCOLS` is a comma separated list of decoded protocols, e.g. `tcp,dns`, see `protocol_filter::PROTOCOLS`.
+/// `-` reads Ethernet frames from stdin, each prefixed by its length as a 32 bit big endian integer. A named
+/// pipe is read the same way, e.g. one a hardware tap writes to.
+#[derive(Debug, Default)]
+pub struct Arguments {
+    /// Print the available interfaces and exit.
+    pub list: bool,
+    /// Network interface to capture on by name or `--list` index, a capture file to read, or a named pipe or
+    /// `-` for raw frames on stdin.
+    pub interface: Option<String>,
+    /// Print a one-line summary per packet instead of the full dump.
+    pub summary: bool,
+    /// Stop after this many packets.
+    pub count: Option<usize>,
+    /// Stop after this much wall-clock time.
+    pub duration: Option<Duration>,
+    /// Print the reassembled payload of this TCP connection instead of decoding packets.
+    pub follow: Option<StreamKey>,
+    /// Line printed between the two directions of a followed stream.
+    pub separator: Option<String>,
+    /// Write the payload of each TCP segment as is, without reassembly, instead of decoding packets.
+    pub tcp_payload_only: bool,
+    /// Write the payload to this file instead of stdout.
+    pub payload_out: Option<PathBuf>,
+    /// Only write the payload of this TCP connection.
+    pub payload_flow: Option<StreamKey>,
+    /// Which direction of `payload_flow` to write.
+    pub payload_direction: Option<PayloadDirection>,
+    /// Which copy of overlapping TCP data wins when reassembling for `--follow` and `--assemble-files`.
+    pub overlap_policy: Option<OverlapPolicy>,
+    /// Write packets to this many rotating pcap files of at most this many bytes.
+    pub ring_buffer: Option<(usize, usize)>,
+    /// Write the `Debug` rendering of each packet to this file instead of printing it.
+    pub debug_out: Option<PathBuf>,
+    /// Write the frames to this pcap file with the addresses of `rewrite_macs` and `rewrite_ips` replaced.
+    pub rewrite_out: Option<PathBuf>,
+    pub rewrite_macs: Vec<(MacAddress, MacAddress)>,
+    pub rewrite_ips: Vec<(Ipv4Address, Ipv4Address)>,
+    /// Write a record of each flow to this file as it ends, in `flow_format`.
+    pub flow_export: Option<PathBuf>,
+    pub flow_format: Option<FlowFormat>,
+    /// Decode these ports as the application protocol, e.g. `http:8000`, in addition to the well-known ports.
+    pub app_ports: Vec<(AppProtocol, u16)>,
+    /// Print the SHA-256 of each packet, covering what `hash_options` selects, instead of decoding it.
+    pub hash: bool,
+    pub hash_options: HashOptions,
+    /// Also print a hash of the payload of each flow.
+    pub hash_flows: bool,
+    /// Compare the flows of the capture file with this one instead of decoding it.
+    pub diff: Option<String>,
+    /// Drop packets identical to a recently seen one.
+    pub dedup: bool,
+    /// Number of recent distinct packets remembered for deduplication.
+    pub dedup_window: Option<usize>,
+    /// Only decode one in this many packets.
+    pub sample: Option<u64>,
+    /// Sample packets at random from a generator with this seed instead of every Nth.
+    pub sample_seed: Option<u64>,
+    /// Only pass on packets whose innermost decoded protocol is one of these.
+    pub only: Option<ProtocolSet>,
+    /// Drop packets whose innermost decoded protocol is one of these.
+    pub exclude: Option<ProtocolSet>,
+    /// Print the capture's time span and inter-packet gaps at the end.
+    pub timing: bool,
+    /// Print the packets, bytes and top talkers of every interval of this much capture time.
+    pub interval: Option<Duration>,
+    /// Show IPv4 fragments as they arrive instead of reassembling them.
+    pub no_defrag: bool,
+    /// Print how many packets have each value of this field instead of decoding them.
+    pub count_by: Option<CountBy>,
+    /// Print DNS lookups with their answers and response times instead of decoding packets.
+    pub dns: bool,
+    /// Print TCP retransmissions, out of order segments and duplicate ACKs per connection at the end.
+    pub anomalies: bool,
+    /// Print the state changes of each TCP connection and their handshake round trip times.
+    pub connections: bool,
+    /// Print TCP keep-alives and zero window advertisements as they happen.
+    pub stalls: bool,
+    /// Print the round trip time of each TCP direction from the timestamp option, and PAWS violations.
+    pub rtt: bool,
+    /// Print the MSS each TCP direction advertised, and segments larger than the receiver's MSS.
+    pub mss: bool,
+    /// Print whether each TCP connection negotiated ECN, and its CE marks and ECE and CWR responses.
+    pub ecn: bool,
+    /// Print alerts for port scans and SYN floods.
+    pub scans: bool,
+    /// Distinct destination ports within the scan window that count as a port scan.
+    pub scan_ports: Option<usize>,
+    /// Uncompleted handshakes within the scan window that count as a SYN flood.
+    pub syn_flood: Option<usize>,
+    /// The sliding window port scans and SYN floods are detected over.
+    pub scan_window: Option<Duration>,
+    /// Connections and streams tracked at a time before the least recently used is dropped.
+    pub max_flows: Option<usize>,
+    /// Bytes buffered out of order by TCP and fragment reassembly before the oldest flows are dropped.
+    pub max_reassembly_bytes: Option<usize>,
+    /// Capture time after which connections and streams without packets are dropped.
+    pub flow_idle_timeout: Option<Duration>,
+    /// Bytes a gzip or deflate encoded HTTP body may inflate to when carving files.
+    pub max_decompressed_bytes: Option<usize>,
+    /// Print packet and byte totals per DSCP class and ECN marking at the end.
+    pub qos: bool,
+    /// Print one JSON object per packet and line instead of the full dump.
+    pub ndjson: bool,
+    /// Print a PDML document like Wireshark's XML export instead of the full dump.
+    pub xml: bool,
+    /// Print a JSON array like `tshark -T json` instead of the full dump.
+    pub json: bool,
+    /// Print TCP sequence and acknowledgment numbers relative to the initial sequence number of each direction.
+    pub relative_seq: bool,
+    /// Warn about IP packets longer than this, e.g. segments coalesced by the NIC.
+    pub mtu: Option<usize>,
+    /// Draw each packet as a tree of its layers instead of flat lines.
+    pub pretty: bool,
+    /// How much of each packet to print: 0 is one line, 1 the key fields of each layer and 2 everything.
+    pub verbose: Option<u8>,
+    /// Annotate IPv4 addresses with the country and ASN from this `prefix,country,asn` file. It is a plain
+    /// prefix table, not a MaxMind or other GeoIP database.
+    pub ip_prefixes: Option<PathBuf>,
+    /// Skip packets captured before this time.
+    pub since: Option<TimeBound>,
+    /// Skip packets captured after this time.
+    pub until: Option<TimeBound>,
+    /// Decode packets into records of the `log` facade instead of printing them. Needs the `log` feature.
+    pub log: bool,
+    /// Which checksums to verify, see `ChecksumPolicy`.
+    pub checksums: Option<ChecksumPolicy>,
+    /// Addresses of this host, whose packets aren't verified with `--checksums inbound`.
+    pub local_ips: Vec<Ipv4Address>,
+    /// MAC addresses of this host, whose frames aren't verified with `--checksums inbound`.
+    pub local_macs: Vec<MacAddress>,
+    /// Save the bodies of HTTP responses as files in this directory instead of decoding packets.
+    pub assemble_files: Option<PathBuf>,
+    /// Capture only the first bytes of each packet live, enough for the headers, and don't dump payloads.
+    pub headers_only: bool,
+    /// Visit the packets of a capture file with the gaps they were captured with.
+    pub replay: bool,
+    /// How many times faster than captured to replay, e.g. `2` or `0.5`.
+    pub speed: Option<f64>,
+    /// Hex dump at most this many bytes of each payload.
+    pub max_payload_bytes: Option<usize>,
+    /// Print the kernel's received and dropped packet counters periodically while capturing live.
+    pub show_drops: bool,
+    /// Capture at most this many bytes of each frame live. Defaults to libpcap's 65535, which already fits
+    /// jumbo frames; frames above it are truncated, never dropped.
+    pub max_frame_size: Option<u32>,
+    /// Whether captured frames end with the Ethernet frame check sequence, guessed from its CRC by default.
+    pub fcs: Option<Fcs>,
+}
+
+impl Arguments {
+    pub fn time_window(&self) -> TimeWindow {
+        TimeWindow { since: self.since, until: self.until }
+    }
+
+    /// The sampling for `--sample`, if given.
+    pub fn sampling(&self) -> Option<Sampling> {
+        let one_in = self.sample?;
+        Some(match self.sample_seed {
+            Some(seed) => Sampling::Random { one_in, seed },
+            None => Sampling::Every { one_in },
+        })
+    }
+
+    /// The thresholds for `--scans`.
+    pub fn scan_thresholds(&self) -> Thresholds {
+        let default = Thresholds::default();
+        Thresholds {
+            ports: self.scan_ports.unwrap_or(default.ports),
+            half_open: self.syn_flood.unwrap_or(default.half_open),
+            window: self.scan_window.unwrap_or(default.window),
+        }
+    }
+
+    /// The limits on flow and reassembly state, and on the size of decompressed bodies.
+    pub fn resource_limits(&self) -> ResourceLimits {
+        let default = ResourceLimits::default();
+        ResourceLimits {
+            max_flows: self.max_flows.unwrap_or(default.max_flows),
+            max_reassembly_bytes: self.max_reassembly_bytes.unwrap_or(default.max_reassembly_bytes),
+            flow_idle_timeout: self.flow_idle_timeout.unwrap_or(default.flow_idle_timeout),
+            max_decompressed_bytes: self.max_decompressed_bytes.unwrap_or(default.max_decompressed_bytes),
+        }
+    }
+
+    /// The well-known ports with those of `--app-port`.
+    pub fn app_dispatch(&self) -> AppDispatch {
+        self.app_ports.iter().fold(AppDispatch::new(), |dispatch, (protocol, port)| dispatch.with_port(*protocol, *port))
+    }
+
+    /// The pacer for `--replay`, if given.
+    pub fn pacer(&self) -> Option<Pacer> {
+        self.replay.then(|| Pacer::new(self.speed.unwrap_or(1.0)))
+    }
+}
+
+impl Arguments {
+    pub fn from_env() -> Result<Self, String> {
+        Self::parse(std::env::args().skip(1))
+    }
+
+    pub fn parse(args: impl IntoIterator<Item=String>) -> Result<Self, String> {
+        let mut arguments = Self::default();
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--list"     => arguments.list = true,
+                "--summary"  => arguments.summary = true,
+                "--count"    => arguments.count = Some(value(&mut args, &arg)?),
+                "--duration" => arguments.duration = Some(Duration::from_secs_f64(value(&mut args, &arg)?)),
+                "--follow"    => arguments.follow = Some(value(&mut args, &arg)?),
+                "--separator" => arguments.separator = Some(value(&mut args, &arg)?),
+                "--tcp-payload-only"  => arguments.tcp_payload_only = true,
+                "--payload-out"       => arguments.payload_out = Some(value(&mut args, &arg)?),
+                "--payload-flow"      => arguments.payload_flow = Some(value(&mut args, &arg)?),
+                "--payload-direction" => arguments.payload_direction = Some(value(&mut args, &arg)?),
+                "--overlap-policy" => arguments.overlap_policy = Some(value(&mut args, &arg)?),
+                "--timing" => arguments.timing = true,
+                "--interval" => {
+                    let seconds: f64 = value(&mut args, &arg)?;
+                    if !(seconds > 0.0 && seconds.is_finite()) { return Err(format!("Invalid value '{}' for option '{}', expected a positive number of seconds", seconds, arg)) }
+                    arguments.interval = Some(Duration::from_secs_f64(seconds));
+                },
+                "--no-defrag" => arguments.no_defrag = true,
+                "--dns" => arguments.dns = true,
+                "--anomalies" => arguments.anomalies = true,
+                "--connections" => arguments.connections = true,
+                "--stalls" => arguments.stalls = true,
+                "--rtt" => arguments.rtt = true,
+                "--mss" => arguments.mss = true,
+                "--ecn" => arguments.ecn = true,
+                "--scans" => arguments.scans = true,
+                "--scan-ports" => arguments.scan_ports = Some(value(&mut args, &arg)?),
+                "--syn-flood" => arguments.syn_flood = Some(value(&mut args, &arg)?),
+                "--scan-window" => arguments.scan_window = Some(Duration::from_secs_f64(value(&mut args, &arg)?)),
+                "--max-flows" => {
+                    let flows: usize = value(&mut args, &arg)?;
+                    if flows == 0 { return Err(format!("Invalid value '{}' for option '{}', expected at least 1", flows, arg)) }
+                    arguments.max_flows = Some(flows);
+                },
+                "--max-reassembly-bytes" => arguments.max_reassembly_bytes = Some(value(&mut args, &arg)?),
+                "--max-decompressed-bytes" => arguments.max_decompressed_bytes = Some(value(&mut args, &arg)?),
+                "--flow-idle-timeout" => {
+                    let seconds: f64 = value(&mut args, &arg)?;
+                    if !(seconds > 0.0 && seconds.is_finite()) { return Err(format!("Invalid value '{}' for option '{}', expected a positive number of seconds", seconds, arg)) }
+                    arguments.flow_idle_timeout = Some(Duration::from_secs_f64(seconds));
+                },
+                "--qos" => arguments.qos = true,
+                "--ndjson" => arguments.ndjson = true,
+                "--xml" => arguments.xml = true,
+                "-T" => match value::<String>(&mut args, &arg)?.as_str() {
+                    "pdml" => arguments.xml = true,
+                    "json" => arguments.json = true,
+                    format => return Err(format!("Invalid value '{}' for option '{}', expected pdml or json", format, arg)),
+                },
+                "--log" => arguments.log = true,
+                "--headers-only" => arguments.headers_only = true,
+                "--relative-seq" => arguments.relative_seq = true,
+                "--pretty" => arguments.pretty = true,
+                "--mtu" => arguments.mtu = Some(value(&mut args, &arg)?),
+                "--replay" => arguments.replay = true,
+                "--show-drops" => arguments.show_drops = true,
+                "--max-frame-size" => {
+                    let size: u32 = value(&mut args, &arg)?;
+                    if size < ethernet::MIN_TOTAL_SIZE as u32 || size > i32::MAX as u32 {
+                        return Err(format!("Invalid value '{}' for option '{}', expected at least {} bytes", size, arg, ethernet::MIN_TOTAL_SIZE));
+                    }
+                    arguments.max_frame_size = Some(size);
+                },
+                "--fcs" => arguments.fcs = Some(value(&mut args, &arg)?),
+                "--max-payload-bytes" => arguments.max_payload_bytes = Some(value(&mut args, &arg)?),
+                "--speed" => {
+                    let speed: f64 = value(&mut args, &arg)?;
+                    if !(speed > 0.0 && speed.is_finite()) { return Err(format!("Invalid value '{}' for option '{}', expected a positive factor", speed, arg)) }
+                    arguments.speed = Some(speed);
+                },
+                "--checksums" => arguments.checksums = Some(value(&mut args, &arg)?),
+                "--local-ip" => arguments.local_ips.push(value(&mut args, &arg)?),
+                "--local-mac" => arguments.local_macs.push(value(&mut args, &arg)?),
+                "--verbose" => {
+                    let level: u8 = value(&mut args, &arg)?;
+                    if level > 2 { return Err(format!("Invalid value '{}' for option '{}', expected 0, 1 or 2", level, arg)) }
+                    arguments.verbose = Some(level);
+                },
+                "--count-by" => arguments.count_by = Some(value(&mut args, &arg)?),
+                "--dedup" => arguments.dedup = true,
+                "--dedup-window" => arguments.dedup_window = Some(value(&mut args, &arg)?),
+                "--sample" => {
+                    let rate: String = value(&mut args, &arg)?;
+                    arguments.sample = Some(Sampling::parse_rate(&rate)?);
+                },
+                "--sample-seed" => arguments.sample_seed = Some(value(&mut args, &arg)?),
+                "--only" => arguments.only = Some(protocols(&mut args, &arg)?),
+                "--exclude" => arguments.exclude = Some(protocols(&mut args, &arg)?),
+                "--since" => arguments.since = Some(value(&mut args, &arg)?),
+                "--until" => arguments.until = Some(value(&mut args, &arg)?),
+                "--ip-prefixes" => arguments.ip_prefixes = Some(value(&mut args, &arg)?),
+                "--assemble-files" => arguments.assemble_files = Some(value(&mut args, &arg)?),
+                "--debug-out" => arguments.debug_out = Some(value(&mut args, &arg)?),
+                "--rewrite-out" => arguments.rewrite_out = Some(value(&mut args, &arg)?),
+                "--rewrite-mac" => arguments.rewrite_macs.push(rewrite::parse_mapping(&value::<String>(&mut args, &arg)?)?),
+                "--rewrite-ip" => arguments.rewrite_ips.push(rewrite::parse_mapping(&value::<String>(&mut args, &arg)?)?),
+                "--flow-export" => arguments.flow_export = Some(value(&mut args, &arg)?),
+                "--flow-format" => arguments.flow_format = Some(value(&mut args, &arg)?),
+                "--app-port" => arguments.app_ports.push(app::parse_port(&value::<String>(&mut args, &arg)?)?),
+                "--hash" => arguments.hash = true,
+                "--hash-from" => arguments.hash_options.from = value(&mut args, &arg)?,
+                "--hash-ignore" => arguments.hash_options = arguments.hash_options.with_ignored(&value::<String>(&mut args, &arg)?)?,
+                "--hash-flows" => arguments.hash_flows = true,
+                "--diff" => arguments.diff = Some(value(&mut args, &arg)?),
+                "--ring-buffer" => {
+                    let spec: String = value(&mut args, &arg)?;
+                    arguments.ring_buffer = Some(RingBuffer::parse_spec(&spec)?);
+                },
+                _ if arg.starts_with("--") => return Err(format!("Unknown option '{}'", arg)),
+                _ if arguments.interface.is_none() => arguments.interface = Some(arg),
+                _ => return Err(format!("Unexpected argument '{}'", arg)),
+            }
+        }
+
+        if (!arguments.rewrite_macs.is_empty() || !arguments.rewrite_ips.is_empty()) && arguments.rewrite_out.is_none() {
+            return Err("--rewrite-mac and --rewrite-ip need --rewrite-out PATH".to_string());
+        }
+        if arguments.flow_format.is_some() && arguments.flow_export.is_none() {
+            return Err("--flow-format needs --flow-export PATH".to_string());
+        }
+        if (arguments.hash_options != HashOptions::default() || arguments.hash_flows) && !arguments.hash {
+            return Err("--hash-from, --hash-ignore and --hash-flows need --hash".to_string());
+        }
+        if (arguments.payload_out.is_some() || arguments.payload_flow.is_some()) && !arguments.tcp_payload_only {
+            return Err("--payload-out and --payload-flow need --tcp-payload-only".to_string());
+        }
+        if arguments.payload_direction.is_some() && arguments.payload_flow.is_none() {
+            return Err("--payload-direction needs --payload-flow SRC:PORT-DST:PORT".to_string());
+        }
+        if arguments.diff.is_some() && arguments.interface.is_none() {
+            return Err("--diff needs a capture file to compare with".to_string());
+        }
+
+        Ok(arguments)
+    }
+}
+
+
+/// Parses the value following the option `name`.
+fn value<T: FromStr>(args: &mut impl Iterator<Item=String>, name: &str) -> Result<T, String> {
+    let value = args.next().ok_or(format!("Option '{}' expects a value", name))?;
+    value.parse().map_err(|_| format!("Invalid value '{}' for option '{}'", value, name))
+}
+
+
+/// Parses the protocol list following the option `name`, keeping the message that names the unknown protocol.
+fn protocols(args: &mut impl Iterator<Item=String>, name: &str) -> Result<ProtocolSet, String> {
+    let value = args.next().ok_or(format!("Option '{}' expects a value", name))?;
+    value.parse().map_err(|error| format!("Invalid value '{}' for option '{}': {}", value, name, error))
+}
@@ -0,0 +1,210 @@
+use alloc::vec::Vec;
+use crate::ipv4::{Ipv4Address, Protocol};
+use crate::shared::{checksum_add, checksum_finish};
+
+
+pub const TCP_FIN: u8 = 0b0000_0001;
+pub const TCP_SYN: u8 = 0b0000_0010;
+pub const TCP_RST: u8 = 0b0000_0100;
+pub const TCP_PSH: u8 = 0b0000_1000;
+pub const TCP_ACK: u8 = 0b0001_0000;
+pub const TCP_URG: u8 = 0b0010_0000;
+
+
+/// Crafts a TCP segment without options. The checksum covers the IP pseudo-header, so the addresses are only
+/// needed when building.
+#[derive(Debug, Clone)]
+pub struct TcpBuilder {
+    source_port: u16,
+    destination_port: u16,
+    sequence_number: u32,
+    acknowledgment_number: u32,
+    flags: u8,
+    window_size: u16,
+    urgent_pointer: u16,
+    payload: Vec<u8>,
+}
+
+impl TcpBuilder {
+    pub const HEADER_SIZE: usize = 20;
+
+    pub fn new(source_port: u16, destination_port: u16) -> Self {
+        Self { source_port, destination_port, sequence_number: 0, acknowledgment_number: 0, flags: 0, window_size: 65535, urgent_pointer: 0, payload: Vec::new() }
+    }
+
+    pub fn with_sequence_number(self, sequence_number: u32) -> Self {
+        Self { sequence_number, ..self }
+    }
+
+    pub fn with_acknowledgment_number(self, acknowledgment_number: u32) -> Self {
+        Self { acknowledgment_number, ..self }
+    }
+
+    /// The `TCP_*` flags to set, e.g. `TCP_SYN | TCP_ACK`.
+    pub fn with_flags(self, flags: u8) -> Self {
+        Self { flags, ..self }
+    }
+
+    pub fn with_window_size(self, window_size: u16) -> Self {
+        Self { window_size, ..self }
+    }
+
+    /// Bytes of urgent data from the start of the payload, which only count with `TCP_URG`.
+    pub fn with_urgent_pointer(self, urgent_pointer: u16) -> Self {
+        Self { urgent_pointer, ..self }
+    }
+
+    pub fn with_payload(self, payload: &[u8]) -> Self {
+        Self { payload: payload.to_vec(), ..self }
+    }
+
+    /// The segment with its checksum computed for the given IPv4 addresses.
+    pub fn build(&self, source: Ipv4Address, destination: Ipv4Address) -> Vec<u8> {
+        let mut data = Vec::with_capacity(Self::HEADER_SIZE + self.payload.len());
+        data.extend_from_slice(&self.source_port.to_be_bytes());
+        data.extend_from_slice(&self.destination_port.to_be_bytes());
+        data.extend_from_slice(&self.sequence_number.to_be_bytes());
+        data.extend_from_slice(&self.acknowledgment_number.to_be_bytes());
+        data.push(((Self::HEADER_SIZE / 4) as u8) << 4);
+        data.push(self.flags);
+        data.extend_from_slice(&self.window_size.to_be_bytes());
+        data.extend_from_slice(&[0, 0]);  // Check sum, filled in below.
+        data.extend_from_slice(&self.urgent_pointer.to_be_bytes());
+        data.extend_from_slice(&self.payload);
+
+        let mut sum = checksum_add(0, source.bytes());
+        sum = checksum_add(sum, destination.bytes());
+        sum = checksum_add(sum, &[0, Protocol::TCP.value()]);
+        sum = checksum_add(sum, &(data.len() as u16).to_be_bytes());
+        sum = checksum_add(sum, &data);
+        data[16..18].copy_from_slice(&checksum_finish(sum).to_be_bytes());
+        data
+    }
+}
+
+
+/// Crafts an IPv4 packet without options around a payload, with a valid header checksum.
+#[derive(Debug, Clone)]
+pub struct Ipv4Builder {
+    source: Ipv4Address,
+    destination: Ipv4Address,
+    protocol: Protocol,
+    time_to_live: u8,
+    identification: u16,
+    dont_fragment: bool,
+    payload: Vec<u8>,
+}
+
+impl Ipv4Builder {
+    pub const HEADER_SIZE: usize = 20;
+
+    pub fn new(source: Ipv4Address, destination: Ipv4Address) -> Self {
+        Self { source, destination, protocol: Protocol::Other(255), time_to_live: 64, identification: 0, dont_fragment: true, payload: Vec::new() }
+    }
+
+    pub fn with_time_to_live(self, time_to_live: u8) -> Self {
+        Self { time_to_live, ..self }
+    }
+
+    pub fn with_identification(self, identification: u16) -> Self {
+        Self { identification, ..self }
+    }
+
+    pub fn with_dont_fragment(self, dont_fragment: bool) -> Self {
+        Self { dont_fragment, ..self }
+    }
+
+    pub fn with_payload(self, protocol: Protocol, payload: &[u8]) -> Self {
+        Self { protocol, payload: payload.to_vec(), ..self }
+    }
+
+    /// Carries `tcp`, with its checksum computed for this packet's addresses.
+    pub fn with_tcp(self, tcp: &TcpBuilder) -> Self {
+        let payload = tcp.build(self.source, self.destination);
+        Self { protocol: Protocol::TCP, payload, ..self }
+    }
+
+    /// The packet. Panics if it's larger than the 65535 bytes the total length can express.
+    pub fn build(&self) -> Vec<u8> {
+        let total_length = u16::try_from(Self::HEADER_SIZE + self.payload.len()).expect("Ipv4 packet too big");
+
+        let mut data = Vec::with_capacity(total_length as usize);
+        data.push(0x40 | (Self::HEADER_SIZE / 4) as u8);
+        data.push(0);
+        data.extend_from_slice(&total_length.to_be_bytes());
+        data.extend_from_slice(&self.identification.to_be_bytes());
+        data.extend_from_slice(&(if self.dont_fragment { 0x4000u16 } else { 0 }).to_be_bytes());
+        data.push(self.time_to_live);
+        data.push(self.protocol.value());
+        data.extend_from_slice(&[0, 0]);  // Header checksum, filled in below.
+        data.extend_from_slice(self.source.bytes());
+        data.extend_from_slice(self.destination.bytes());
+
+        let checksum = checksum_finish(checksum_add(0, &data));
+        data[10..12].copy_from_slice(&checksum.to_be_bytes());
+        data.extend_from_slice(&self.payload);
+        data
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ipv4::{self, IPv4};
+
+    /// Whether the TCP segment of `ip` sums to zero with its pseudo-header.
+    fn tcp_checksum_is_valid(ip: &IPv4) -> bool {
+        let segment = ip.raw_payload();
+        let mut sum = checksum_add(0, ip.source_address().bytes());
+        sum = checksum_add(sum, ip.destination_address().bytes());
+        sum = checksum_add(sum, &[0, Protocol::TCP.value()]);
+        sum = checksum_add(sum, &(segment.len() as u16).to_be_bytes());
+        checksum_finish(checksum_add(sum, segment)) == 0
+    }
+
+    #[test]
+    fn builds_a_syn_that_parses_back_with_valid_checksums() {
+        let source = Ipv4Address::from_bytes([192, 168, 1, 10]);
+        let destination = Ipv4Address::from_bytes([93, 184, 216, 34]);
+        let data = Ipv4Builder::new(source, destination)
+            .with_time_to_live(128)
+            .with_identification(0x1234)
+            .with_tcp(&TcpBuilder::new(51000, 443).with_flags(TCP_SYN).with_sequence_number(0xDEADBEEF).with_window_size(64240))
+            .build();
+
+        let ip = IPv4::from_bytes(&data).unwrap();
+        assert_eq!(ip.source_address(), source);
+        assert_eq!(ip.destination_address(), destination);
+        assert_eq!(ip.protocol(), Protocol::TCP);
+        assert_eq!(ip.time_to_live(), 128);
+        assert_eq!(ip.identification(), 0x1234);
+        assert_eq!(ip.df(), 1);
+        assert_eq!(ip.total_length() as usize, Ipv4Builder::HEADER_SIZE + TcpBuilder::HEADER_SIZE);
+        assert_eq!(checksum_finish(checksum_add(0, &data[..ip.header_size()])), 0);
+        assert!(tcp_checksum_is_valid(&ip));
+
+        let Ok(ipv4::Payload::Tcp(tcp)) = ip.payload() else { panic!("the packet doesn't carry TCP") };
+        assert_eq!((tcp.source_port(), tcp.destination_port()), (51000, 443));
+        assert_eq!(tcp.sequence_number(), 0xDEADBEEF);
+        assert_eq!(tcp.acknowledgment_number(), 0);
+        assert_eq!(tcp.flags(), TCP_SYN);
+        assert_eq!(tcp.window_size(), 64240);
+        assert_eq!(tcp.header_size(), TcpBuilder::HEADER_SIZE);
+        assert!(tcp.raw_payload().is_empty());
+    }
+
+    #[test]
+    fn checksums_cover_the_payload_and_addresses() {
+        let source = Ipv4Address::from_bytes([10, 0, 0, 1]);
+        let destination = Ipv4Address::from_bytes([10, 0, 0, 2]);
+        let mut data = Ipv4Builder::new(source, destination).with_tcp(&TcpBuilder::new(51000, 80).with_payload(b"odd")).build();
+        assert!(tcp_checksum_is_valid(&IPv4::from_bytes(&data).unwrap()));
+
+        // The same segment between other addresses fails the pseudo-header.
+        data[19] = 3;
+        let ip = IPv4::from_bytes(&data).unwrap();
+        assert!(!tcp_checksum_is_valid(&ip));
+        assert_ne!(checksum_finish(checksum_add(0, &data[..ip.header_size()])), 0);
+    }
+}
@@ -0,0 +1,272 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io::Error;
+use std::path::PathBuf;
+use crate::app::{AppDispatch, AppProtocol, Transport};
+use crate::http::{self, BodyLength, Message};
+use crate::limits::ResourceLimits;
+use crate::reassembly::{OverlapPolicy, Reassembler, StreamKey};
+use crate::tcp::Tcp;
+use crate::time_window;
+use crate::visitor::{Context, Visitor};
+
+
+/// Writes the body of every HTTP response to a file in `directory`, named after the path of the request.
+/// Chunked, gzip and deflate encoded bodies are decoded first.
+pub struct CarveVisitor {
+    directory: PathBuf,
+    reassembler: Reassembler,
+    /// Reassembled bytes of each direction that don't form a complete message yet.
+    buffers: HashMap<StreamKey, Vec<u8>>,
+    /// Targets of the requests sent in each direction, oldest first, waiting for their responses.
+    targets: HashMap<StreamKey, VecDeque<String>>,
+    /// Responses whose body ends when the connection closes, with the target they belong to.
+    until_close: HashMap<StreamKey, (String, Message)>,
+    names: HashSet<String>,
+    carved: usize,
+    apps: AppDispatch,
+    /// Bytes a compressed body is inflated to at most.
+    max_decompressed_bytes: usize,
+}
+
+impl CarveVisitor {
+    /// Bytes buffered per direction before the message is given up on.
+    pub const MAX_BUFFER_SIZE: usize = 64 << 20;
+
+    pub fn new(directory: PathBuf) -> Self {
+        Self {
+            directory,
+            reassembler: Reassembler::new(),
+            buffers: HashMap::new(),
+            targets: HashMap::new(),
+            until_close: HashMap::new(),
+            names: HashSet::new(),
+            carved: 0,
+            apps: AppDispatch::new(),
+            max_decompressed_bytes: ResourceLimits::default().max_decompressed_bytes,
+        }
+    }
+
+    pub fn with_overlap_policy(self, policy: OverlapPolicy) -> Self {
+        Self { reassembler: self.reassembler.with_policy(policy), ..self }
+    }
+
+    pub fn with_limits(self, limits: ResourceLimits) -> Self {
+        Self { reassembler: self.reassembler.with_limits(limits), max_decompressed_bytes: limits.max_decompressed_bytes, ..self }
+    }
+
+    /// Carves HTTP on the ports `apps` maps to it.
+    pub fn with_app_dispatch(self, apps: AppDispatch) -> Self {
+        Self { apps, ..self }
+    }
+
+    /// A file name from the last segment of the target's path that is unique among the carved files,
+    /// e.g. `logo.png`, `logo-1.png` and `index` for `/`.
+    pub fn file_name(&mut self, target: &str) -> String {
+        let path = target.split(['?', '#']).next().unwrap_or("");
+        let name: String = path.rsplit('/').next().unwrap_or("").chars()
+            .map(|x| if x.is_ascii_alphanumeric() || x == '.' || x == '-' || x == '_' { x } else { '_' })
+            .collect();
+        let name = if name.is_empty() || name.chars().all(|x| x == '.') { "index".to_string() } else { name };
+
+        let (stem, extension) = match name.rfind('.') {
+            Some(dot) if dot > 0 => (&name[..dot], &name[dot..]),
+            _ => (name.as_str(), ""),
+        };
+        let mut unique = name.clone();
+        let mut counter = 0;
+        while self.names.contains(&unique) {
+            counter += 1;
+            unique = format!("{}-{}{}", stem, counter, extension);
+        }
+        self.names.insert(unique.clone());
+        unique
+    }
+
+    fn save(&mut self, target: &str, message: &Message, body: &[u8]) -> Result<(), Error> {
+        let body = http::decode_content(message, body, self.max_decompressed_bytes).unwrap_or_else(|error| {
+            eprintln!("[WARNING]: Saving the encoded body of {}: {}", target, error);
+            body.to_vec()
+        });
+
+        let name = self.file_name(target);
+        let path = self.directory.join(name);
+        std::fs::write(&path, &body)?;
+        println!("{} -> {} ({} bytes)", target, path.display(), body.len());
+        self.carved += 1;
+        Ok(())
+    }
+
+    /// Parses and handles every complete message at the start of the buffer of `key`.
+    fn drain(&mut self, key: StreamKey) -> Result<(), Error> {
+        loop {
+            if self.until_close.contains_key(&key) { return Ok(()) }
+            let Some(buffer) = self.buffers.get(&key) else { return Ok(()) };
+
+            let (message, header_size) = match http::parse(buffer) {
+                Ok(http::Parse::Complete { message, header_size }) => (message, header_size),
+                Ok(http::Parse::Incomplete) => return Ok(()),
+                // Not HTTP, or lost track of the message boundaries.
+                Err(_) => {
+                    self.buffers.remove(&key);
+                    return Ok(());
+                },
+            };
+
+            let body = &buffer[header_size..];
+            let (content, size) = match message.body_length() {
+                BodyLength::Length(length) => {
+                    if body.len() < length { return Ok(()) }
+                    (body[..length].to_vec(), header_size + length)
+                },
+                BodyLength::Chunked => match http::decode_chunked(body)? {
+                    Some((content, size)) => (content, header_size + size),
+                    None => return Ok(()),
+                },
+                BodyLength::UntilClose => {
+                    let target = self.targets.get_mut(&key.reversed()).and_then(|x| x.pop_front()).unwrap_or_default();
+                    self.buffers.get_mut(&key).unwrap().drain(..header_size);
+                    self.until_close.insert(key, (target, message));
+                    return Ok(());
+                },
+            };
+            self.buffers.get_mut(&key).unwrap().drain(..size);
+
+            match &message {
+                Message::Request(request) => self.targets.entry(key).or_default().push_back(request.target.clone()),
+                Message::Response(_) => {
+                    let target = self.targets.get_mut(&key.reversed()).and_then(|x| x.pop_front()).unwrap_or_default();
+                    if !content.is_empty() {
+                        self.save(&target, &message, &content)?;
+                    }
+                },
+            }
+        }
+    }
+
+    /// Saves the body of a response that ended with its connection.
+    fn close(&mut self, key: StreamKey) -> Result<(), Error> {
+        let Some((target, message)) = self.until_close.remove(&key) else { return Ok(()) };
+        let body = self.buffers.remove(&key).unwrap_or_default();
+        if !body.is_empty() {
+            self.save(&target, &message, &body)?;
+        }
+        Ok(())
+    }
+}
+
+
+impl<'a> Visitor<'a, ()> for CarveVisitor {
+    fn visit_tcp(&mut self, packet: &Tcp<'a>, context: &Context<'a>) -> Result<(), Error> {
+        let Some(ip) = &context.ipv4 else { return Ok(()) };
+        if self.apps.protocol(Transport::Tcp, packet.source_port(), packet.destination_port()) != Some(AppProtocol::Http) {
+            return Ok(());
+        }
+
+        let key = StreamKey::new(ip, packet);
+        let now = context.header.map(time_window::timestamp).unwrap_or_default();
+        let data = self.reassembler.push(ip, packet, now);
+        if !data.is_empty() {
+            let buffer = self.buffers.entry(key).or_default();
+            buffer.extend_from_slice(&data);
+            if buffer.len() > Self::MAX_BUFFER_SIZE {
+                self.buffers.remove(&key);
+                self.until_close.remove(&key);
+                return Ok(());
+            }
+            self.drain(key)?;
+        }
+
+        if packet.fin() != 0 || packet.rst() != 0 {
+            self.close(key)?;
+        }
+        Ok(())
+    }
+
+    fn app_dispatch(&self) -> &AppDispatch {
+        &self.apps
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        let keys: Vec<StreamKey> = self.until_close.keys().copied().collect();
+        for key in keys {
+            self.close(key)?;
+        }
+        println!("Carved {} files into {}", self.carved, self.directory.display());
+        if self.reassembler.conflicts() > 0 {
+            println!("[WARNING] {} segments overlapped earlier data with different bytes", self.reassembler.conflicts());
+        }
+        if self.reassembler.evicted() + self.reassembler.expired() > 0 {
+            println!("[WARNING] {} streams were dropped to stay within the limits and {} after being idle, their files may be incomplete",
+                self.reassembler.evicted(), self.reassembler.expired());
+        }
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use crate::builder::{TcpBuilder, TCP_ACK, TCP_FIN, TCP_PSH};
+    use crate::raw::visit_frame;
+    use crate::testing::{self, ip};
+
+    /// Sends `payload` from the client (10.0.0.1:51000) or the server (10.0.0.2:80) at `sequence`.
+    fn send(visitor: &mut CarveVisitor, from_client: bool, sequence: u32, flags: u8, payload: &[u8]) {
+        let (source, destination, ports) = if from_client {
+            (ip(10, 0, 0, 1), ip(10, 0, 0, 2), (51000, 80))
+        } else {
+            (ip(10, 0, 0, 2), ip(10, 0, 0, 1), (80, 51000))
+        };
+        let tcp = TcpBuilder::new(ports.0, ports.1).with_flags(flags).with_sequence_number(sequence).with_payload(payload);
+        visit_frame(visitor, &testing::tcp_frame(source, destination, &tcp), Duration::ZERO).unwrap();
+    }
+
+    #[test]
+    fn carves_a_download_split_across_segments() {
+        let directory = testing::temp_dir("carve-download");
+        let mut visitor = CarveVisitor::new(directory.clone());
+        let file: Vec<u8> = (0..3000u32).map(|x| (x * 7 % 251) as u8).collect();
+
+        let request = b"GET /images/logo.png?size=large HTTP/1.1\r\nHost: example.com\r\n\r\n";
+        send(&mut visitor, true, 1000, TCP_ACK | TCP_PSH, request);
+        let mut response = format!("HTTP/1.1 200 OK\r\nContent-Type: image/png\r\nContent-Length: {}\r\n\r\n", file.len()).into_bytes();
+        response.extend_from_slice(&file);
+        let (first, second) = response.split_at(1460);
+        send(&mut visitor, false, 5000, TCP_ACK, first);
+        send(&mut visitor, false, 5000 + first.len() as u32, TCP_ACK | TCP_PSH, second);
+        visitor.finish().unwrap();
+
+        assert_eq!(std::fs::read(directory.join("logo.png")).unwrap(), file);
+        assert_eq!(visitor.carved, 1);
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn carves_chunked_and_until_close_bodies() {
+        let directory = testing::temp_dir("carve-chunked");
+        let mut visitor = CarveVisitor::new(directory.clone());
+
+        let requests = b"GET / HTTP/1.1\r\n\r\nGET /notes.txt HTTP/1.1\r\n\r\n";
+        send(&mut visitor, true, 1000, TCP_ACK | TCP_PSH, requests);
+        let chunked = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n6\r\nHello \r\n6\r\nworld!\r\n0\r\n\r\n";
+        send(&mut visitor, false, 5000, TCP_ACK | TCP_PSH, chunked);
+        let until_close = b"HTTP/1.0 200 OK\r\n\r\nwritten until the end";
+        send(&mut visitor, false, 5000 + chunked.len() as u32, TCP_ACK | TCP_PSH | TCP_FIN, until_close);
+
+        assert_eq!(std::fs::read(directory.join("index")).unwrap(), b"Hello world!");
+        assert_eq!(std::fs::read(directory.join("notes.txt")).unwrap(), b"written until the end");
+        std::fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn names_files_uniquely() {
+        let mut visitor = CarveVisitor::new(PathBuf::new());
+        assert_eq!(visitor.file_name("/a/logo.png"), "logo.png");
+        assert_eq!(visitor.file_name("/b/logo.png"), "logo-1.png");
+        assert_eq!(visitor.file_name("/"), "index");
+        assert_eq!(visitor.file_name("/..?q"), "index-1");
+        assert_eq!(visitor.file_name("/a b"), "a_b");
+    }
+}
@@ -0,0 +1,209 @@
+use std::str::FromStr;
+use crate::ipv4::{Ipv4Address, IPv4, Protocol};
+use crate::shared::{checksum_add, checksum_finish, MacAddress};
+use crate::ipsec::Ah;
+use crate::visitor::Context;
+
+
+/// Which checksums are verified. With checksum offload the network card fills in the checksums of
+/// transmitted packets after they're captured, so they look invalid even though they're fine.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub enum ChecksumPolicy {
+    #[default]
+    Verify,
+    Skip,
+    /// Only verify packets that weren't sent from one of the local addresses.
+    VerifyInbound,
+}
+
+impl FromStr for ChecksumPolicy {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "verify"  => Ok(Self::Verify),
+            "skip"    => Ok(Self::Skip),
+            "inbound" => Ok(Self::VerifyInbound),
+            _ => Err(format!("Invalid checksum policy '{}', expected verify, skip or inbound", text)),
+        }
+    }
+}
+
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ChecksumStatus {
+    Valid,
+    Invalid,
+    /// Not verified because of the policy, or because the packet wasn't fully captured.
+    Unverified,
+}
+
+impl ChecksumStatus {
+    fn from_valid(valid: bool) -> Self {
+        if valid { Self::Valid } else { Self::Invalid }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Valid      => "valid",
+            Self::Invalid    => "invalid",
+            Self::Unverified => "not verified",
+        }
+    }
+}
+
+
+/// Verifies IPv4, TCP and UDP checksums according to a `ChecksumPolicy`.
+#[derive(Debug, Default, Clone)]
+pub struct ChecksumVerifier {
+    policy: ChecksumPolicy,
+    local_macs: Vec<MacAddress>,
+    local_ips: Vec<Ipv4Address>,
+}
+
+impl ChecksumVerifier {
+    pub fn new(policy: ChecksumPolicy) -> Self {
+        Self { policy, ..Self::default() }
+    }
+
+    /// Treats packets from this address as transmitted by this host.
+    pub fn add_local_mac(&mut self, address: MacAddress) {
+        self.local_macs.push(address);
+    }
+
+    /// Treats packets from this address as transmitted by this host.
+    pub fn add_local_ip(&mut self, address: Ipv4Address) {
+        self.local_ips.push(address);
+    }
+
+    /// Whether the packet was sent by this host, judging by the enclosing layers.
+    pub fn is_outbound(&self, context: &Context) -> bool {
+        context.ethernet.as_ref().is_some_and(|x| self.local_macs.contains(&x.source()))
+            || context.ipv4.as_ref().is_some_and(|x| self.local_ips.contains(&x.source_address()))
+    }
+
+    fn should_verify(&self, context: &Context) -> bool {
+        match self.policy {
+            ChecksumPolicy::Verify        => true,
+            ChecksumPolicy::Skip          => false,
+            ChecksumPolicy::VerifyInbound => !self.is_outbound(context),
+        }
+    }
+
+    /// The header checksum of `packet`, whose enclosing layers are in `context`.
+    pub fn ipv4(&self, packet: &IPv4, context: &Context) -> ChecksumStatus {
+        let context = context.with_ipv4(packet);
+        if !self.should_verify(&context) { return ChecksumStatus::Unverified }
+        ChecksumStatus::from_valid(packet.verify_header_checksum())
+    }
+
+    /// The checksum of the TCP segment in the innermost IP layer of `context`.
+    pub fn tcp(&self, context: &Context) -> ChecksumStatus {
+        if !self.should_verify(context) { return ChecksumStatus::Unverified }
+        match transport_sum(context, Protocol::TCP.value()) {
+            Some(sum) => ChecksumStatus::from_valid(checksum_finish(sum) == 0),
+            None      => ChecksumStatus::Unverified,
+        }
+    }
+
+    /// The checksum of the UDP datagram in the innermost IP layer of `context`. A zero checksum over IPv4
+    /// means the sender didn't compute one.
+    pub fn udp(&self, check_sum: u16, context: &Context) -> ChecksumStatus {
+        if !self.should_verify(context) { return ChecksumStatus::Unverified }
+        if check_sum == 0 && context.ipv4.is_some() { return ChecksumStatus::Unverified }
+        match transport_sum(context, Protocol::UDP.value()) {
+            Some(sum) => ChecksumStatus::from_valid(checksum_finish(sum) == 0),
+            None      => ChecksumStatus::Unverified,
+        }
+    }
+}
+
+
+/// The one's complement sum over the pseudo-header and the whole transport segment, or `None` if the segment
+/// wasn't fully captured.
+fn transport_sum(context: &Context, protocol: u8) -> Option<u32> {
+    if let Some(ip) = &context.ipv4 {
+        let mut length = (ip.total_length() as usize).checked_sub(ip.header_size())?;
+        let mut segment = ip.raw_payload();
+        // The pseudo-header covers the segment behind an Authentication Header, not the header itself.
+        if ip.protocol() == Protocol::AH {
+            let ah = Ah::from_bytes(segment).ok()?;
+            length = length.checked_sub(ah.header_size())?;
+            segment = ah.raw_payload();
+        }
+        let segment = segment.get(..length)?;
+        let mut sum = checksum_add(0, ip.source_address().bytes());
+        sum = checksum_add(sum, ip.destination_address().bytes());
+        sum = checksum_add(sum, &[0, protocol]);
+        sum = checksum_add(sum, &(length as u16).to_be_bytes());
+        return Some(checksum_add(sum, segment));
+    }
+    if let Some(ip) = &context.ipv6 {
+        let (_, upper_layer) = ip.upper_layer().ok()?;
+        let length = (ip.payload_length() as usize).checked_sub(ip.raw_payload().len() - upper_layer.len())?;
+        let segment = upper_layer.get(..length)?;
+        let mut sum = checksum_add(0, ip.source_address().bytes());
+        sum = checksum_add(sum, ip.destination_address().bytes());
+        sum = checksum_add(sum, &(length as u32).to_be_bytes());
+        sum = checksum_add(sum, &[0, 0, 0, protocol]);
+        return Some(checksum_add(sum, segment));
+    }
+    None
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::TcpBuilder;
+    use crate::ethernet::{self, Ethernet};
+    use crate::testing::{self, ip, MAC_A, MAC_B};
+
+    /// A TCP frame from `MAC_A` and 10.0.0.1 whose checksum is zero, as with checksum offload.
+    fn offloaded() -> Vec<u8> {
+        let mut frame = testing::tcp_frame(ip(10, 0, 0, 1), ip(10, 0, 0, 2), &TcpBuilder::new(51000, 80));
+        frame[ethernet::HEADER_SIZE + 20 + 16..ethernet::HEADER_SIZE + 20 + 18].fill(0);
+        frame
+    }
+
+    /// The status of the TCP checksum of `frame` under `verifier`.
+    fn tcp_status(verifier: &ChecksumVerifier, frame: &[u8]) -> ChecksumStatus {
+        let frame = Ethernet::from_bytes(frame).unwrap();
+        let Ok(ethernet::Payload::IPv4(packet)) = frame.payload() else { panic!("not IPv4") };
+        let context = Context::default().with_ethernet(&frame).with_ipv4(&packet);
+        verifier.tcp(&context)
+    }
+
+    #[test]
+    fn flags_a_zero_tcp_checksum_only_when_verifying() {
+        assert_eq!(tcp_status(&ChecksumVerifier::new(ChecksumPolicy::Verify), &offloaded()), ChecksumStatus::Invalid);
+        assert_eq!(tcp_status(&ChecksumVerifier::new(ChecksumPolicy::Skip), &offloaded()), ChecksumStatus::Unverified);
+
+        let valid = testing::tcp_frame(ip(10, 0, 0, 1), ip(10, 0, 0, 2), &TcpBuilder::new(51000, 80));
+        assert_eq!(tcp_status(&ChecksumVerifier::new(ChecksumPolicy::Verify), &valid), ChecksumStatus::Valid);
+    }
+
+    #[test]
+    fn skips_packets_from_local_addresses_when_verifying_inbound() {
+        let mut by_mac = ChecksumVerifier::new(ChecksumPolicy::VerifyInbound);
+        by_mac.add_local_mac(MacAddress::from_bytes(&MAC_A).unwrap());
+        assert_eq!(tcp_status(&by_mac, &offloaded()), ChecksumStatus::Unverified);
+
+        let mut by_ip = ChecksumVerifier::new(ChecksumPolicy::VerifyInbound);
+        by_ip.add_local_ip(ip(10, 0, 0, 1));
+        assert_eq!(tcp_status(&by_ip, &offloaded()), ChecksumStatus::Unverified);
+
+        let mut other = ChecksumVerifier::new(ChecksumPolicy::VerifyInbound);
+        other.add_local_mac(MacAddress::from_bytes(&MAC_B).unwrap());
+        other.add_local_ip(ip(10, 0, 0, 2));
+        assert_eq!(tcp_status(&other, &offloaded()), ChecksumStatus::Invalid);
+    }
+
+    #[test]
+    fn parses_policies() {
+        assert_eq!("verify".parse(), Ok(ChecksumPolicy::Verify));
+        assert_eq!("skip".parse(), Ok(ChecksumPolicy::Skip));
+        assert_eq!("inbound".parse(), Ok(ChecksumPolicy::VerifyInbound));
+        assert!("outbound".parse::<ChecksumPolicy>().is_err());
+    }
+}
@@ -0,0 +1,315 @@
+use std::io::Error;
+use std::time::Duration;
+use crate::flow::FiveTuple;
+use crate::ipv4::IPv4;
+use crate::limits::{FlowTable, ResourceLimits};
+use crate::tcp::Tcp;
+use crate::time_window;
+use crate::visitor::{Context, Visitor};
+
+
+/// The state of a TCP connection as seen from the wire, between both endpoints.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum TcpState {
+    /// The client sent a SYN.
+    SynSent,
+    /// The server answered with a SYN-ACK.
+    SynReceived,
+    /// The handshake completed, or data was seen on a connection whose handshake wasn't captured.
+    Established,
+    /// One side sent a FIN.
+    FinWait,
+    /// Both sides sent a FIN, waiting for the last one to be acknowledged.
+    Closing,
+    /// Both FINs were acknowledged, or either side sent a RST.
+    Closed,
+}
+
+impl TcpState {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::SynSent     => "SYN_SENT",
+            Self::SynReceived => "SYN_RECEIVED",
+            Self::Established => "ESTABLISHED",
+            Self::FinWait     => "FIN_WAIT",
+            Self::Closing     => "CLOSING",
+            Self::Closed      => "CLOSED",
+        }
+    }
+}
+
+
+/// The lifecycle of one connection.
+#[derive(Debug, Clone)]
+pub struct Connection {
+    /// The direction from the side that opened the connection, or from the first packet seen if the handshake
+    /// wasn't captured.
+    pub key: FiveTuple,
+    pub state: TcpState,
+    /// Whether the connection ended with a RST.
+    pub reset: bool,
+    syn: Option<Duration>,
+    syn_ack: Option<Duration>,
+    /// Sequence number of the FIN sent by the client and the server.
+    client_fin: Option<u32>,
+    server_fin: Option<u32>,
+}
+
+impl Connection {
+    fn new(key: FiveTuple, state: TcpState) -> Self {
+        Self { key, state, reset: false, syn: None, syn_ack: None, client_fin: None, server_fin: None }
+    }
+
+    /// Time from the SYN to the SYN-ACK, if both were captured.
+    pub fn handshake_rtt(&self) -> Option<Duration> {
+        self.syn_ack?.checked_sub(self.syn?)
+    }
+
+    /// Whether the FIN sent by the other direction than `from_client` is acknowledged by `tcp`.
+    fn acknowledges_fin(&self, tcp: &Tcp, from_client: bool) -> bool {
+        let fin = if from_client { self.server_fin } else { self.client_fin };
+        tcp.ack() != 0 && fin.is_some_and(|x| tcp.acknowledgment_number() == x.wrapping_add(1))
+    }
+}
+
+
+/// Follows the state of every TCP connection from the flags and sequence numbers of its segments.
+#[derive(Debug, Default)]
+pub struct ConnectionTracker {
+    /// Keyed by the normalized tuple so both directions find the same connection.
+    connections: FlowTable<FiveTuple, Connection>,
+}
+
+impl ConnectionTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_limits(self, limits: ResourceLimits) -> Self {
+        Self { connections: FlowTable::new(limits) }
+    }
+
+    /// Number of connections evicted to stay within `ResourceLimits::max_flows`.
+    pub fn evicted(&self) -> usize {
+        self.connections.evicted()
+    }
+
+    /// Number of connections dropped after `ResourceLimits::flow_idle_timeout`.
+    pub fn expired(&self) -> usize {
+        self.connections.expired()
+    }
+
+    /// Updates the connection of the segment and returns its state before and after, if it changed.
+    pub fn push(&mut self, ip: &IPv4, tcp: &Tcp, timestamp: Duration) -> Option<(Option<TcpState>, TcpState)> {
+        let key = FiveTuple::from_tcp(ip, tcp);
+        let is_syn = tcp.syn() != 0 && tcp.ack() == 0;
+
+        // A SYN on a closed connection reuses the ports for a new one.
+        let connection = match self.connections.get_mut(&key.normalized(), timestamp) {
+            Some(connection) if !(is_syn && connection.state == TcpState::Closed) => connection,
+            _ => {
+                let state = if is_syn { TcpState::SynSent } else { TcpState::Established };
+                let mut connection = Connection::new(key, state);
+                if is_syn { connection.syn = Some(timestamp) }
+                self.connections.insert(key.normalized(), timestamp, connection);
+                return Some((None, state));
+            },
+        };
+
+        let previous = connection.state;
+        let from_client = key == connection.key;
+
+        if tcp.rst() != 0 {
+            connection.reset = true;
+            connection.state = TcpState::Closed;
+        } else {
+            if tcp.fin() != 0 {
+                let fin = tcp.sequence_number().wrapping_add(tcp.raw_payload().len() as u32);
+                if from_client { connection.client_fin = Some(fin) } else { connection.server_fin = Some(fin) }
+            }
+
+            connection.state = match connection.state {
+                TcpState::SynSent if tcp.syn() != 0 && tcp.ack() != 0 && !from_client => {
+                    connection.syn_ack = Some(timestamp);
+                    TcpState::SynReceived
+                },
+                TcpState::SynReceived if tcp.ack() != 0 && tcp.syn() == 0 && from_client => TcpState::Established,
+                state => state,
+            };
+
+            let fins = connection.client_fin.is_some() as u8 + connection.server_fin.is_some() as u8;
+            connection.state = match (connection.state, fins) {
+                (TcpState::Established | TcpState::SynReceived, 1) => TcpState::FinWait,
+                (TcpState::Established | TcpState::SynReceived | TcpState::FinWait, 2) => TcpState::Closing,
+                (TcpState::Closing, _) if connection.acknowledges_fin(tcp, from_client) && tcp.fin() == 0 => TcpState::Closed,
+                (state, _) => state,
+            };
+        }
+
+        (connection.state != previous).then_some((Some(previous), connection.state))
+    }
+
+    pub fn connection(&self, key: &FiveTuple) -> Option<&Connection> {
+        self.connections.get(&key.normalized())
+    }
+
+    /// All connections in address order.
+    pub fn connections(&self) -> Vec<&Connection> {
+        let mut connections: Vec<&Connection> = self.connections.values().collect();
+        connections.sort_by_key(|x| x.key.normalized());
+        connections
+    }
+}
+
+
+/// Prints every state change of each TCP connection, and the final state and handshake RTT of all
+/// connections at the end.
+pub struct ConnectionVisitor {
+    tracker: ConnectionTracker,
+}
+
+impl ConnectionVisitor {
+    pub fn new() -> Self {
+        Self { tracker: ConnectionTracker::new() }
+    }
+
+    pub fn with_limits(self, limits: ResourceLimits) -> Self {
+        Self { tracker: self.tracker.with_limits(limits) }
+    }
+}
+
+impl Default for ConnectionVisitor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+impl<'a> Visitor<'a, ()> for ConnectionVisitor {
+    fn visit_tcp(&mut self, packet: &Tcp<'a>, context: &Context<'a>) -> Result<(), Error> {
+        let (Some(ip), Some(header)) = (&context.ipv4, context.header) else { return Ok(()) };
+        let timestamp = time_window::timestamp(header);
+
+        if let Some((previous, state)) = self.tracker.push(ip, packet, timestamp) {
+            let connection = self.tracker.connection(&FiveTuple::from_tcp(ip, packet)).unwrap();
+            let previous = previous.map_or("NEW", |x| x.name());
+            match connection.handshake_rtt() {
+                Some(rtt) if state == TcpState::SynReceived => println!("{}: {} -> {} (rtt {:.3} ms)", connection.key, previous, state.name(), rtt.as_secs_f64() * 1000.0),
+                _ if connection.reset => println!("{}: {} -> {} (reset)", connection.key, previous, state.name()),
+                _ => println!("{}: {} -> {}", connection.key, previous, state.name()),
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        for connection in self.tracker.connections() {
+            let rtt = match connection.handshake_rtt() {
+                Some(rtt) => format!("{:.3} ms", rtt.as_secs_f64() * 1000.0),
+                None      => "unknown".to_string(),
+            };
+            let note = match connection.state {
+                TcpState::SynSent | TcpState::SynReceived => " (half-open)",
+                TcpState::Closed if connection.reset       => " (reset)",
+                _ => "",
+            };
+            println!("{}: {}{}, handshake rtt {}", connection.key, connection.state.name(), note, rtt);
+        }
+        if self.tracker.evicted() > 0 || self.tracker.expired() > 0 {
+            println!("[WARNING] {} connections were evicted to stay within the flow limit and {} expired after being idle", self.tracker.evicted(), self.tracker.expired());
+        }
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::{Ipv4Builder, TcpBuilder, TCP_ACK, TCP_FIN, TCP_RST, TCP_SYN};
+    use crate::testing::ip;
+
+    /// Pushes a segment from the client (10.0.0.1:51000) or the server (10.0.0.2:80) at `millis`.
+    fn push(tracker: &mut ConnectionTracker, from_client: bool, flags: u8, sequence: u32, acknowledgment: u32, millis: u64) -> Option<(Option<TcpState>, TcpState)> {
+        let (source, destination, ports) = if from_client {
+            (ip(10, 0, 0, 1), ip(10, 0, 0, 2), (51000, 80))
+        } else {
+            (ip(10, 0, 0, 2), ip(10, 0, 0, 1), (80, 51000))
+        };
+        let tcp = TcpBuilder::new(ports.0, ports.1).with_flags(flags).with_sequence_number(sequence).with_acknowledgment_number(acknowledgment);
+        let data = Ipv4Builder::new(source, destination).with_tcp(&tcp).build();
+        let ip = IPv4::from_bytes(&data).unwrap();
+        let Ok(crate::ipv4::Payload::Tcp(tcp)) = ip.payload() else { unreachable!() };
+        tracker.push(&ip, &tcp, Duration::from_millis(millis))
+    }
+
+    fn only(tracker: &ConnectionTracker) -> &Connection {
+        let connections = tracker.connections();
+        assert_eq!(connections.len(), 1);
+        connections[0]
+    }
+
+    #[test]
+    fn follows_a_handshake_and_teardown() {
+        let mut tracker = ConnectionTracker::new();
+        assert_eq!(push(&mut tracker, true,  TCP_SYN,           100, 0,   1000), Some((None, TcpState::SynSent)));
+        assert_eq!(push(&mut tracker, false, TCP_SYN | TCP_ACK, 300, 101, 1012), Some((Some(TcpState::SynSent), TcpState::SynReceived)));
+        assert_eq!(push(&mut tracker, true,  TCP_ACK,           101, 301, 1013), Some((Some(TcpState::SynReceived), TcpState::Established)));
+        assert_eq!(push(&mut tracker, true,  TCP_FIN | TCP_ACK, 101, 301, 2000), Some((Some(TcpState::Established), TcpState::FinWait)));
+        assert_eq!(push(&mut tracker, false, TCP_ACK,           301, 102, 2010), None);
+        assert_eq!(push(&mut tracker, false, TCP_FIN | TCP_ACK, 301, 102, 2020), Some((Some(TcpState::FinWait), TcpState::Closing)));
+        assert_eq!(push(&mut tracker, true,  TCP_ACK,           102, 302, 2030), Some((Some(TcpState::Closing), TcpState::Closed)));
+
+        let connection = only(&tracker);
+        assert_eq!(connection.state, TcpState::Closed);
+        assert!(!connection.reset);
+        assert_eq!(connection.handshake_rtt(), Some(Duration::from_millis(12)));
+        assert_eq!(connection.key.to_string(), "TCP 10.0.0.1:51000 > 10.0.0.2:80");
+    }
+
+    #[test]
+    fn closes_a_reset_connection_and_reopens_it_on_a_new_syn() {
+        let mut tracker = ConnectionTracker::new();
+        push(&mut tracker, true, TCP_SYN, 100, 0, 0);
+        assert_eq!(push(&mut tracker, false, TCP_RST | TCP_ACK, 0, 101, 1), Some((Some(TcpState::SynSent), TcpState::Closed)));
+        assert!(only(&tracker).reset);
+        assert_eq!(only(&tracker).handshake_rtt(), None);
+
+        assert_eq!(push(&mut tracker, true, TCP_SYN, 500, 0, 10), Some((None, TcpState::SynSent)));
+        assert!(!only(&tracker).reset);
+    }
+
+    #[test]
+    fn leaves_an_unanswered_syn_half_open() {
+        let mut tracker = ConnectionTracker::new();
+        push(&mut tracker, true, TCP_SYN, 100, 0, 0);
+        assert_eq!(push(&mut tracker, true, TCP_SYN, 100, 0, 1000), None);
+        assert_eq!(only(&tracker).state, TcpState::SynSent);
+        assert_eq!(only(&tracker).handshake_rtt(), None);
+    }
+
+    #[test]
+    fn joins_a_connection_whose_handshake_was_not_captured() {
+        let mut tracker = ConnectionTracker::new();
+        assert_eq!(push(&mut tracker, false, TCP_ACK, 301, 102, 0), Some((None, TcpState::Established)));
+        // The first packet seen decides the direction.
+        assert_eq!(only(&tracker).key.to_string(), "TCP 10.0.0.2:80 > 10.0.0.1:51000");
+    }
+    #[test]
+    fn evicts_connections_beyond_max_flows() {
+        let limits = ResourceLimits { max_flows: 2, ..ResourceLimits::default() };
+        let mut tracker = ConnectionTracker::new().with_limits(limits);
+        for port in [51000, 51001, 51002] {
+            let tcp = TcpBuilder::new(port, 80).with_flags(TCP_SYN);
+            let data = Ipv4Builder::new(ip(10, 0, 0, 1), ip(10, 0, 0, 2)).with_tcp(&tcp).build();
+            let ip = IPv4::from_bytes(&data).unwrap();
+            let Ok(crate::ipv4::Payload::Tcp(tcp)) = ip.payload() else { unreachable!() };
+            assert_eq!(tracker.push(&ip, &tcp, Duration::ZERO), Some((None, TcpState::SynSent)));
+        }
+
+        let mut ports: Vec<_> = tracker.connections().iter().map(|x| x.key.source_port).collect();
+        ports.sort();
+        assert_eq!(ports, [51001, 51002]);
+        assert_eq!((tracker.evicted(), tracker.expired()), (1, 0));
+    }
+}
@@ -0,0 +1,207 @@
+use std::collections::HashMap;
+use std::io::Error;
+use pcap::Packet;
+use crate::ethernet::{self, Ethernet};
+use crate::ipv4;
+use crate::ipv6;
+use crate::pppoe;
+use crate::names;
+use crate::mpls;
+use crate::visitor::Visitor;
+
+
+/// Bucket for packets that don't have the field being counted.
+pub const NONE: &str = "<none>";
+
+
+/// Extracts the value to count by from a frame, or `None` if the frame doesn't have it.
+pub type KeyFn = Box<dyn Fn(&Ethernet) -> Option<String>>;
+
+
+/// The fields `--count-by` accepts.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum CountBy {
+    SourceIp,
+    DestinationIp,
+    SourcePort,
+    DestinationPort,
+    Protocol,
+    EtherType,
+}
+
+impl CountBy {
+    pub fn key(&self) -> KeyFn {
+        match self {
+            CountBy::SourceIp        => Box::new(|frame| addresses(frame).map(|(source, _)| source)),
+            CountBy::DestinationIp   => Box::new(|frame| addresses(frame).map(|(_, destination)| destination)),
+            CountBy::SourcePort      => Box::new(|frame| ports(frame).map(|(source, _)| source.to_string())),
+            CountBy::DestinationPort => Box::new(|frame| ports(frame).map(|(_, destination)| destination.to_string())),
+            CountBy::Protocol        => Box::new(|frame| protocol(frame).map(|protocol| format!("{} ({})", names::ip_protocol_name(protocol), protocol))),
+            CountBy::EtherType       => Box::new(|frame| Some(format!("{} (0x{:04X})", frame.ether_type().name(), frame.ether_type().as_u16()))),
+        }
+    }
+}
+
+impl std::str::FromStr for CountBy {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "srcip"     => Ok(CountBy::SourceIp),
+            "dstip"     => Ok(CountBy::DestinationIp),
+            "srcport"   => Ok(CountBy::SourcePort),
+            "dstport"   => Ok(CountBy::DestinationPort),
+            "proto"     => Ok(CountBy::Protocol),
+            "ethertype" => Ok(CountBy::EtherType),
+            _ => Err(format!("Unknown field '{}', expected one of srcip, dstip, srcport, dstport, proto, ethertype", text)),
+        }
+    }
+}
+
+
+enum Ip<'a> {
+    V4(ipv4::IPv4<'a>),
+    V6(ipv6::IPv6<'a>),
+}
+
+fn ip<'a>(frame: &Ethernet<'a>) -> Option<Ip<'a>> {
+    match frame.payload().ok()? {
+        ethernet::Payload::IPv4(packet) => Some(Ip::V4(packet)),
+        ethernet::Payload::IPv6(packet) => Some(Ip::V6(packet)),
+        ethernet::Payload::PPPoE(packet) => match packet.payload().ok()? {
+            pppoe::Payload::IPv4(packet) => Some(Ip::V4(packet)),
+            pppoe::Payload::IPv6(packet) => Some(Ip::V6(packet)),
+            pppoe::Payload::Unknown(_) => None,
+        },
+        ethernet::Payload::Mpls(packet) => match packet.payload().ok()? {
+            mpls::Payload::IPv4(packet) => Some(Ip::V4(packet)),
+            mpls::Payload::IPv6(packet) => Some(Ip::V6(packet)),
+            mpls::Payload::Unknown(_) => None,
+        },
+        ethernet::Payload::Lldp(_) | ethernet::Payload::Eapol(_) | ethernet::Payload::Unknown(_) => None,
+    }
+}
+
+/// The source and destination address of the IP packet in `frame`.
+pub fn addresses(frame: &Ethernet) -> Option<(String, String)> {
+    match ip(frame)? {
+        Ip::V4(packet) => Some((format!("{:?}", packet.source_address()), format!("{:?}", packet.destination_address()))),
+        Ip::V6(packet) => Some((format!("{:?}", packet.source_address()), format!("{:?}", packet.destination_address()))),
+    }
+}
+
+fn ports(frame: &Ethernet) -> Option<(u16, u16)> {
+    match ip(frame)? {
+        Ip::V4(packet) => match packet.payload().ok()? {
+            ipv4::Payload::Tcp(tcp)   => Some((tcp.source_port(), tcp.destination_port())),
+            ipv4::Payload::Udp(udp)   => Some((udp.source_port(), udp.destination_port())),
+            ipv4::Payload::Sctp(sctp) => Some((sctp.source_port(), sctp.destination_port())),
+            ipv4::Payload::Gre(_) | ipv4::Payload::Ah(_) | ipv4::Payload::Esp(_) | ipv4::Payload::Icmp(_) | ipv4::Payload::Unknown(_) => None,
+        },
+        Ip::V6(packet) => match packet.payload().ok()? {
+            ipv6::Payload::Tcp(tcp)   => Some((tcp.source_port(), tcp.destination_port())),
+            ipv6::Payload::Udp(udp)   => Some((udp.source_port(), udp.destination_port())),
+            ipv6::Payload::Sctp(sctp) => Some((sctp.source_port(), sctp.destination_port())),
+            ipv6::Payload::Icmpv6(_) | ipv6::Payload::Gre(_) | ipv6::Payload::Esp(_) | ipv6::Payload::Unknown(_) => None,
+        },
+    }
+}
+
+fn protocol(frame: &Ethernet) -> Option<u8> {
+    match ip(frame)? {
+        Ip::V4(packet) => Some(packet.protocol_raw()),
+        Ip::V6(packet) => Some(packet.upper_layer_protocol()),
+    }
+}
+
+
+/// Tallies packets by the value `key` extracts and prints a frequency table at the end.
+pub struct CountVisitor {
+    key: KeyFn,
+    counts: HashMap<String, usize>,
+}
+
+impl CountVisitor {
+    pub fn new(key: KeyFn) -> Self {
+        Self { key, counts: HashMap::new() }
+    }
+
+    /// The counts, most frequent first and ties in key order.
+    pub fn table(&self) -> Vec<(&str, usize)> {
+        let mut table: Vec<(&str, usize)> = self.counts.iter().map(|(key, count)| (key.as_str(), *count)).collect();
+        table.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+        table
+    }
+}
+
+
+impl<'a> Visitor<'a, ()> for CountVisitor {
+    fn visit_packet(&mut self, packet: &'a Packet) -> Result<(), Error> {
+        let key = Ethernet::from_bytes(packet.data).ok()
+            .and_then(|frame| (self.key)(&frame))
+            .unwrap_or(NONE.to_string());
+        *self.counts.entry(key).or_insert(0) += 1;
+        Ok(())
+    }
+
+    /// The keys are taken from Ethernet frames, so wireless frames are counted as `<none>`.
+    fn visit_radiotap_packet(&mut self, packet: &'a Packet) -> Result<(), Error> {
+        *self.counts.entry(NONE.to_string()).or_insert(0) += 1;
+        Ok(())
+    }
+
+    fn visit_sll2_packet(&mut self, packet: &'a Packet) -> Result<(), Error> {
+        *self.counts.entry(NONE.to_string()).or_insert(0) += 1;
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        let table = self.table();
+        let width = table.iter().map(|(key, _)| key.len()).max().unwrap_or(0);
+        for (key, count) in table {
+            println!("{:<width$}  {}", key, count, width = width);
+        }
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use crate::builder::TcpBuilder;
+    use crate::raw::visit_frame;
+    use crate::testing::{self, ip};
+
+    fn count(count_by: CountBy) -> Vec<(String, usize)> {
+        let (a, b) = (ip(10, 0, 0, 1), ip(10, 0, 0, 2));
+        let frames = [
+            testing::tcp_frame(a, b, &TcpBuilder::new(51000, 443)),
+            testing::tcp_frame(a, b, &TcpBuilder::new(51001, 443)),
+            testing::udp_frame(a, 53000, b, 53, b"query"),
+            testing::tcp_frame(a, b, &TcpBuilder::new(51002, 80)),
+            testing::tcp_frame(a, b, &TcpBuilder::new(51003, 443)),
+            testing::ethernet(0x0806, &[0; 28]),
+        ];
+        let mut visitor = CountVisitor::new(count_by.key());
+        for frame in &frames {
+            visit_frame(&mut visitor, frame, Duration::ZERO).unwrap();
+        }
+        visitor.table().into_iter().map(|(key, count)| (key.to_string(), count)).collect()
+    }
+
+    #[test]
+    fn counts_by_destination_port() {
+        assert_eq!(count(CountBy::DestinationPort), [
+            ("443".to_string(), 3), ("53".to_string(), 1), ("80".to_string(), 1), (NONE.to_string(), 1),
+        ]);
+    }
+
+    #[test]
+    fn counts_by_protocol() {
+        assert_eq!(count(CountBy::Protocol), [
+            ("TCP (6)".to_string(), 4), (NONE.to_string(), 1), ("UDP (17)".to_string(), 1),
+        ]);
+    }
+}
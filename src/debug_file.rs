@@ -0,0 +1,63 @@
+use std::io::{Error, Write};
+use crate::ethernet::Ethernet;
+use crate::visitor::{Context, Visitor};
+
+
+/// Writes the `Debug` rendering of each parsed frame to a writer instead of stdout, one record per packet.
+pub struct DebugFileVisitor<W: Write> {
+    writer: W,
+    count: usize,
+}
+
+impl<W: Write> DebugFileVisitor<W> {
+    pub const SEPARATOR: &'static str = "--------------------------------------------------------------------------------";
+
+    pub fn new(writer: W) -> Self {
+        Self { writer, count: 0 }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+
+impl<'a, W: Write> Visitor<'a, ()> for DebugFileVisitor<W> {
+    fn visit_ethernet(&mut self, packet: &Ethernet<'a>, context: &Context<'a>) -> Result<(), Error> {
+        writeln!(self.writer, "{} packet {}", Self::SEPARATOR, self.count)?;
+        write!(self.writer, "{:#?}", packet)?;
+        self.writer.flush()?;
+        self.count += 1;
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::time::Duration;
+    use crate::builder::TcpBuilder;
+    use crate::raw::visit_frame;
+    use crate::testing::{self, ip};
+
+    #[test]
+    fn writes_a_record_per_packet() {
+        let directory = testing::temp_dir("debug-file");
+        let path = directory.join("packets.txt");
+
+        let mut visitor = DebugFileVisitor::new(File::create(&path).unwrap());
+        visit_frame(&mut visitor, &testing::tcp_frame(ip(10, 0, 0, 1), ip(10, 0, 0, 2), &TcpBuilder::new(51000, 80)), Duration::ZERO).unwrap();
+        visit_frame(&mut visitor, &testing::udp_frame(ip(10, 0, 0, 3), 53000, ip(10, 0, 0, 4), 53, b"query"), Duration::ZERO).unwrap();
+        drop(visitor);
+
+        let text = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        let records: Vec<_> = text.split(DebugFileVisitor::<File>::SEPARATOR).skip(1).collect();
+        assert_eq!(records.len(), 2, "{}", text);
+        assert!(records[0].starts_with(" packet 0\n") && records[0].contains("10.0.0.1"), "{}", records[0]);
+        assert!(records[1].starts_with(" packet 1\n") && records[1].contains("10.0.0.3"), "{}", records[1]);
+    }
+}
@@ -0,0 +1,121 @@
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::io::Error;
+use pcap::Packet;
+use crate::visitor::Visitor;
+
+
+/// Drops frames that are byte-for-byte identical to one of the `window` most recently seen distinct frames,
+/// and passes the rest on to the inner visitor.
+pub struct DedupVisitor {
+    inner: Box<dyn for<'a> Visitor<'a, ()>>,
+    window: usize,
+    /// Frame hash to the index of the packet it was last seen in.
+    seen: HashMap<u64, u64>,
+    /// Hashes in the order they were last seen. Entries whose index no longer matches `seen` are stale.
+    order: VecDeque<(u64, u64)>,
+    index: u64,
+    dropped: usize,
+}
+
+impl DedupVisitor {
+    pub const DEFAULT_WINDOW: usize = 1024;
+
+    pub fn new(inner: Box<dyn for<'a> Visitor<'a, ()>>, window: usize) -> Self {
+        Self { inner, window: window.max(1), seen: HashMap::new(), order: VecDeque::new(), index: 0, dropped: 0 }
+    }
+
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+
+    /// Records the frame and returns whether it's a duplicate of a recently seen one.
+    pub fn is_duplicate(&mut self, data: &[u8]) -> bool {
+        let mut hasher = DefaultHasher::new();
+        data.hash(&mut hasher);
+        let hash = hasher.finish();
+
+        self.index += 1;
+        let duplicate = self.seen.insert(hash, self.index).is_some();
+        self.order.push_back((hash, self.index));
+
+        // Evict the least recently seen hashes, skipping entries that were refreshed since.
+        while self.seen.len() > self.window || self.order.len() > 2 * self.window {
+            let Some((hash, index)) = self.order.pop_front() else { break };
+            if self.seen.get(&hash) == Some(&index) {
+                self.seen.remove(&hash);
+            }
+        }
+
+        duplicate
+    }
+}
+
+
+impl<'a> Visitor<'a, ()> for DedupVisitor {
+    fn visit_packet(&mut self, packet: &'a Packet) -> Result<(), Error> {
+        if self.is_duplicate(packet.data) {
+            self.dropped += 1;
+            return Ok(());
+        }
+        self.inner.visit_packet(packet)
+    }
+
+    fn visit_radiotap_packet(&mut self, packet: &'a Packet) -> Result<(), Error> {
+        if self.is_duplicate(packet.data) {
+            self.dropped += 1;
+            return Ok(());
+        }
+        self.inner.visit_radiotap_packet(packet)
+    }
+
+    fn visit_sll2_packet(&mut self, packet: &'a Packet) -> Result<(), Error> {
+        if self.is_duplicate(packet.data) {
+            self.dropped += 1;
+            return Ok(());
+        }
+        self.inner.visit_sll2_packet(packet)
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        self.inner.finish()?;
+        println!("Dropped {} duplicate packets", self.dropped);
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use crate::raw::visit_frame;
+    use crate::testing::{self, ip, Recorder};
+
+    #[test]
+    fn forwards_only_distinct_packets() {
+        let first = testing::udp_frame(ip(10, 0, 0, 1), 53000, ip(10, 0, 0, 2), 53, b"first");
+        let third = testing::udp_frame(ip(10, 0, 0, 1), 53000, ip(10, 0, 0, 2), 53, b"third");
+        let recorder = Recorder::default();
+        let mut dedup = DedupVisitor::new(Box::new(recorder.clone()), DedupVisitor::DEFAULT_WINDOW);
+
+        for frame in [&first, &first, &third] {
+            visit_frame(&mut dedup, frame, Duration::ZERO).unwrap();
+        }
+
+        assert_eq!(recorder.packets(), vec![first, third]);
+        assert_eq!(dedup.dropped(), 1);
+    }
+
+    #[test]
+    fn forgets_packets_outside_the_window() {
+        let mut dedup = DedupVisitor::new(Box::new(Recorder::default()), 2);
+        assert!(!dedup.is_duplicate(b"a"));
+        assert!(!dedup.is_duplicate(b"b"));
+        assert!(dedup.is_duplicate(b"a"));
+        assert!(!dedup.is_duplicate(b"c"));
+        // "b" was the least recently seen when "c" came in.
+        assert!(!dedup.is_duplicate(b"b"));
+    }
+}
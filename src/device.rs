@@ -0,0 +1,164 @@
+use pcap::Device;
+
+
+/// Picks the interface called `name` out of `devices`. A name that is all digits is the index shown by
+/// `--list`, unless an interface has exactly that name.
+pub fn select(devices: Vec<Device>, name: &str) -> Result<Device, String> {
+    if !name.is_empty() && name.bytes().all(|x| x.is_ascii_digit()) && !devices.iter().any(|x| x.name == name) {
+        let count = devices.len();
+        let index: usize = name.parse().map_err(|_| format!("Invalid interface index '{}'", name))?;
+        return devices.into_iter()
+            .nth(index)
+            .ok_or(format!("No interface with index {}, there are {}, run with --list to see them", index, count));
+    }
+
+    devices.into_iter()
+        .find(|x| x.name == name)
+        .ok_or(format!("No interface named '{}', run with --list to see the available ones", name))
+}
+
+
+/// Where the capture devices come from, so selection doesn't depend on the machine it runs on.
+pub trait DeviceLister {
+    fn list(&self) -> Result<Vec<Device>, pcap::Error>;
+    /// The device to use when none is named.
+    fn lookup(&self) -> Result<Option<Device>, pcap::Error>;
+}
+
+/// The devices libpcap finds.
+pub struct PcapLister;
+
+impl DeviceLister for PcapLister {
+    fn list(&self) -> Result<Vec<Device>, pcap::Error> {
+        Device::list()
+    }
+
+    fn lookup(&self) -> Result<Option<Device>, pcap::Error> {
+        Device::lookup()
+    }
+}
+
+
+/// Picks the device named `interface`, see `select`, or the default device if `None`. The error is meant for
+/// the user and suggests `--list` where that helps.
+pub fn choose(lister: &dyn DeviceLister, interface: Option<&str>) -> Result<Device, String> {
+    match interface {
+        Some(name) => {
+            let devices = lister.list().map_err(|error| format!("Failed to list the capture devices: {}", error))?;
+            if devices.is_empty() {
+                return Err(format!("No capture devices found, so there is no interface named '{}'. Capturing may need more privileges", name));
+            }
+            select(devices, name)
+        },
+        None => match lister.lookup() {
+            Ok(Some(device)) => Ok(device),
+            Ok(None) => Err("No capture devices found. Capturing may need more privileges, run with --list to see the available interfaces".to_string()),
+            Err(error) => Err(format!("Failed to find a default capture device: {}. Name one, run with --list to see them", error)),
+        },
+    }
+}
+
+
+/// Prints each device with its description, addresses and flags, one block per device.
+pub fn print_list(devices: &[Device]) {
+    for (i, device) in devices.iter().enumerate() {
+        println!("{}: {}", i, device.name);
+        if let Some(description) = &device.desc {
+            println!("    Description : {}", description);
+        }
+        for address in &device.addresses {
+            match address.netmask {
+                Some(netmask) => println!("    Address     : {} netmask {}", address.addr, netmask),
+                None          => println!("    Address     : {}", address.addr),
+            }
+        }
+        println!("    Flags       : {:?}, {:?}", device.flags.if_flags, device.flags.connection_status);
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Lists the devices it was made with, the first being the default.
+    struct FakeLister {
+        devices: Vec<&'static str>,
+    }
+
+    impl DeviceLister for FakeLister {
+        fn list(&self) -> Result<Vec<Device>, pcap::Error> {
+            Ok(self.devices.iter().map(|x| Device::from(*x)).collect())
+        }
+
+        fn lookup(&self) -> Result<Option<Device>, pcap::Error> {
+            Ok(self.devices.first().map(|x| Device::from(*x)))
+        }
+    }
+
+    fn lister() -> FakeLister {
+        FakeLister { devices: vec!["eth0", "wlan0", "lo"] }
+    }
+
+    #[test]
+    fn chooses_the_named_interface() {
+        assert_eq!(choose(&lister(), Some("wlan0")).unwrap().name, "wlan0");
+    }
+
+    #[test]
+    fn chooses_the_default_without_a_name() {
+        assert_eq!(choose(&lister(), None).unwrap().name, "eth0");
+    }
+
+    #[test]
+    fn rejects_an_unknown_name_instead_of_the_default() {
+        let error = choose(&lister(), Some("wlan1")).unwrap_err();
+        assert!(error.contains("No interface named 'wlan1'"), "{}", error);
+    }
+
+    #[test]
+    fn chooses_an_interface_by_index() {
+        assert_eq!(choose(&lister(), Some("1")).unwrap().name, "wlan0");
+        assert_eq!(choose(&lister(), Some("0")).unwrap().name, "eth0");
+    }
+
+    #[test]
+    fn rejects_an_index_out_of_range() {
+        let error = choose(&lister(), Some("3")).unwrap_err();
+        assert!(error.contains("No interface with index 3, there are 3"), "{}", error);
+    }
+
+    /// Fails like libpcap without the privileges to look at the interfaces.
+    struct FailingLister;
+
+    impl DeviceLister for FailingLister {
+        fn list(&self) -> Result<Vec<Device>, pcap::Error> {
+            Err(pcap::Error::PcapError("permission denied".to_string()))
+        }
+
+        fn lookup(&self) -> Result<Option<Device>, pcap::Error> {
+            Err(pcap::Error::PcapError("permission denied".to_string()))
+        }
+    }
+
+    #[test]
+    fn explains_that_there_are_no_devices() {
+        let empty = FakeLister { devices: vec![] };
+        let error = choose(&empty, None).unwrap_err();
+        assert!(error.starts_with("No capture devices found"), "{}", error);
+        assert!(error.contains("--list"), "{}", error);
+
+        let error = choose(&empty, Some("eth0")).unwrap_err();
+        assert!(error.starts_with("No capture devices found, so there is no interface named 'eth0'"), "{}", error);
+    }
+
+    #[test]
+    fn reports_a_failing_lister_instead_of_panicking() {
+        let error = choose(&FailingLister, None).unwrap_err();
+        assert!(error.starts_with("Failed to find a default capture device: "), "{}", error);
+        assert!(error.contains("permission denied"), "{}", error);
+
+        let error = choose(&FailingLister, Some("eth0")).unwrap_err();
+        assert!(error.starts_with("Failed to list the capture devices: "), "{}", error);
+    }
+}
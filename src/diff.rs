@@ -0,0 +1,265 @@
+use std::collections::HashMap;
+use std::io::Error;
+use std::time::Duration;
+use crate::flow::FiveTuple;
+use crate::ipv4::{self, IPv4};
+use crate::time_window;
+use crate::visitor::{Context, Visitor};
+
+
+/// The traffic of one connection in a capture, both directions together.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct FlowStats {
+    pub packets: u64,
+    /// Bytes of the IP packets, headers included.
+    pub bytes: u64,
+    /// Capture time of the first packet, since the epoch.
+    pub first: Duration,
+}
+
+
+/// The flows of a capture by normalized 5-tuple, and the time span it covers.
+#[derive(Debug, Default)]
+pub struct CaptureFlows {
+    pub flows: HashMap<FiveTuple, FlowStats>,
+    pub start: Option<Duration>,
+    pub end: Option<Duration>,
+}
+
+impl CaptureFlows {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, key: FiveTuple, now: Duration, bytes: u64) {
+        self.start = Some(self.start.map_or(now, |x| x.min(now)));
+        self.end = Some(self.end.map_or(now, |x| x.max(now)));
+        let flow = self.flows.entry(key.normalized()).or_insert(FlowStats { packets: 0, bytes: 0, first: now });
+        flow.packets += 1;
+        flow.bytes += bytes;
+        flow.first = flow.first.min(now);
+    }
+
+    pub fn duration(&self) -> Duration {
+        match (self.start, self.end) {
+            (Some(start), Some(end)) => end - start,
+            _ => Duration::ZERO,
+        }
+    }
+
+    pub fn packets(&self) -> u64 {
+        self.flows.values().map(|x| x.packets).sum()
+    }
+
+    pub fn bytes(&self) -> u64 {
+        self.flows.values().map(|x| x.bytes).sum()
+    }
+
+    /// When `flow` started, relative to the start of the capture.
+    fn offset(&self, flow: &FlowStats) -> Duration {
+        flow.first.saturating_sub(self.start.unwrap_or_default())
+    }
+}
+
+
+/// How a flow differs between two captures.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum FlowChange {
+    /// Only in the second capture.
+    Added(FlowStats),
+    /// Only in the first capture.
+    Removed(FlowStats),
+    /// Only in one capture, starting later into it than the other capture lasted, so the other capture may
+    /// have ended before the flow began.
+    Uncovered { first: bool, stats: FlowStats },
+    Changed { before: FlowStats, after: FlowStats },
+}
+
+
+/// The flows that differ between captures `a` and `b`, in address order, and the number of flows that are the
+/// same in both. Flows are matched by 5-tuple, so captures of the same traffic at different times compare
+/// equal. Captures of different durations are compared relative to their start.
+pub fn diff(a: &CaptureFlows, b: &CaptureFlows) -> (Vec<(FiveTuple, FlowChange)>, usize) {
+    let mut changes = Vec::new();
+    let mut unchanged = 0;
+
+    for (key, before) in &a.flows {
+        match b.flows.get(key) {
+            Some(after) if after.packets == before.packets && after.bytes == before.bytes => unchanged += 1,
+            Some(after) => changes.push((*key, FlowChange::Changed { before: *before, after: *after })),
+            None if a.offset(before) > b.duration() => changes.push((*key, FlowChange::Uncovered { first: true, stats: *before })),
+            None => changes.push((*key, FlowChange::Removed(*before))),
+        }
+    }
+    for (key, after) in &b.flows {
+        if a.flows.contains_key(key) { continue }
+        if b.offset(after) > a.duration() {
+            changes.push((*key, FlowChange::Uncovered { first: false, stats: *after }));
+        } else {
+            changes.push((*key, FlowChange::Added(*after)));
+        }
+    }
+
+    changes.sort_by_key(|(key, _)| *key);
+    (changes, unchanged)
+}
+
+
+fn delta(before: u64, after: u64) -> String {
+    format!("{} -> {} ({:+})", before, after, after as i64 - before as i64)
+}
+
+/// Prints the flows that differ between `a` and `b`, one per line, and a summary.
+pub fn print_diff(names: (&str, &str), a: &CaptureFlows, b: &CaptureFlows) {
+    for (name, flows) in [(names.0, a), (names.1, b)] {
+        println!("{}: {} flows, {} packets, {} bytes over {:.3} seconds", name, flows.flows.len(), flows.packets(), flows.bytes(), flows.duration().as_secs_f64());
+    }
+
+    let (changes, unchanged) = diff(a, b);
+    let (mut added, mut removed, mut uncovered, mut changed) = (0, 0, 0, 0);
+    for (key, change) in &changes {
+        match change {
+            FlowChange::Added(stats) => {
+                added += 1;
+                println!("+ {}: {} packets, {} bytes", key, stats.packets, stats.bytes);
+            },
+            FlowChange::Removed(stats) => {
+                removed += 1;
+                println!("- {}: {} packets, {} bytes", key, stats.packets, stats.bytes);
+            },
+            FlowChange::Uncovered { first, stats } => {
+                uncovered += 1;
+                let (name, other) = if *first { names } else { (names.1, names.0) };
+                println!("? {}: {} packets, {} bytes, only in {} after {} ended", key, stats.packets, stats.bytes, name, other);
+            },
+            FlowChange::Changed { before, after } => {
+                changed += 1;
+                println!("~ {}: packets {}, bytes {}", key, delta(before.packets, after.packets), delta(before.bytes, after.bytes));
+            },
+        }
+    }
+    println!("{} added, {} removed, {} changed, {} unchanged, {} outside the other capture", added, removed, changed, unchanged, uncovered);
+}
+
+
+/// Collects the IPv4 flows of a capture for `diff`.
+#[derive(Default)]
+pub struct FlowCollector {
+    pub flows: CaptureFlows,
+}
+
+impl FlowCollector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+
+impl<'a> Visitor<'a, ()> for FlowCollector {
+    fn visit_ipv4(&mut self, packet: &IPv4<'a>, context: &Context<'a>) -> Result<(), Error> {
+        let now = context.header.map(time_window::timestamp).unwrap_or_default();
+        // Later fragments don't hold the ports, so they count towards a flow without them.
+        let payload = if packet.fragment_offset() == 0 { packet.payload().ok() } else { None };
+        let key = match payload {
+            Some(ipv4::Payload::Tcp(tcp)) => FiveTuple::from_tcp(packet, &tcp),
+            Some(ipv4::Payload::Udp(udp)) => FiveTuple::from_udp(packet, &udp),
+            _ => FiveTuple {
+                protocol: packet.protocol_raw(),
+                source: packet.source_address(),
+                source_port: 0,
+                destination: packet.destination_address(),
+                destination_port: 0,
+            },
+        };
+        self.flows.push(key, now, packet.total_length() as u64);
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::File;
+    use std::path::Path;
+    use crate::builder::{TcpBuilder, TCP_ACK};
+    use crate::pcapng::PcapngReader;
+    use crate::source::{PacketSource, PcapngSource};
+    use crate::testing::{self, ip};
+
+    fn http() -> FiveTuple {
+        FiveTuple { protocol: 6, source: ip(10, 0, 0, 1), source_port: 51000, destination: ip(10, 0, 0, 2), destination_port: 80 }
+    }
+
+    fn dns() -> FiveTuple {
+        FiveTuple { protocol: 17, source: ip(10, 0, 0, 1), source_port: 53000, destination: ip(10, 0, 0, 53), destination_port: 53 }
+    }
+
+    fn syslog() -> FiveTuple {
+        FiveTuple { protocol: 17, source: ip(10, 0, 0, 3), source_port: 5000, destination: ip(10, 0, 0, 4), destination_port: 514 }
+    }
+
+    /// A capture of an HTTP exchange whose response has `body` bytes and a DNS query, and of a syslog
+    /// message during the exchange if `with_syslog`.
+    fn capture(body: usize, with_syslog: bool) -> Vec<u8> {
+        let request = TcpBuilder::new(51000, 80).with_flags(TCP_ACK).with_payload(b"GET / HTTP/1.1\r\n\r\n");
+        let response = TcpBuilder::new(80, 51000).with_flags(TCP_ACK).with_payload(&vec![b'x'; body]);
+        let mut frames = vec![
+            (0, testing::tcp_frame(ip(10, 0, 0, 1), ip(10, 0, 0, 2), &request)),
+            (1000, testing::udp_frame(ip(10, 0, 0, 1), 53000, ip(10, 0, 0, 53), 53, b"query")),
+            (2000, testing::tcp_frame(ip(10, 0, 0, 2), ip(10, 0, 0, 1), &response)),
+        ];
+        if with_syslog {
+            frames.push((1500, testing::udp_frame(ip(10, 0, 0, 3), 5000, ip(10, 0, 0, 4), 514, b"<13>hello")));
+        }
+        testing::pcapng(&frames)
+    }
+
+    fn collect(path: &Path) -> CaptureFlows {
+        let mut source = PcapngSource::new(PcapngReader::new(File::open(path).unwrap()));
+        let mut collector = FlowCollector::new();
+        while let Some(packet) = source.next() {
+            collector.visit_packet(&packet.unwrap().as_packet()).unwrap();
+        }
+        collector.flows
+    }
+
+    #[test]
+    fn compares_the_flows_of_two_files() {
+        let directory = testing::temp_dir("diff");
+        std::fs::write(directory.join("before.pcapng"), capture(100, false)).unwrap();
+        std::fs::write(directory.join("after.pcapng"), capture(300, true)).unwrap();
+        let before = collect(&directory.join("before.pcapng"));
+        let after = collect(&directory.join("after.pcapng"));
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!((before.flows.len(), before.packets(), before.duration()), (2, 3, Duration::from_millis(2)));
+        let (changes, unchanged) = diff(&before, &after);
+        assert_eq!(unchanged, 1);
+        assert_eq!(changes, [
+            (http(), FlowChange::Changed {
+                before: FlowStats { packets: 2, bytes: 58 + 140, first: Duration::ZERO },
+                after: FlowStats { packets: 2, bytes: 58 + 340, first: Duration::ZERO },
+            }),
+            (syslog(), FlowChange::Added(FlowStats { packets: 1, bytes: 37, first: Duration::from_micros(1500) })),
+        ]);
+        assert_eq!(after.flows[&dns()], before.flows[&dns()]);
+    }
+
+    #[test]
+    fn sets_apart_flows_starting_after_the_other_capture_ended() {
+        let (mut a, mut b) = (CaptureFlows::new(), CaptureFlows::new());
+        a.push(http(), Duration::from_secs(100), 60);
+        a.push(dns(), Duration::from_secs(105), 40);
+        // The second capture starts later and only lasts a second, too short to have seen the DNS query.
+        b.push(http().reversed(), Duration::from_secs(200), 60);
+        b.push(syslog(), Duration::from_secs(201), 50);
+
+        let (changes, unchanged) = diff(&a, &b);
+        assert_eq!(unchanged, 1);
+        assert_eq!(changes, [
+            (dns(), FlowChange::Uncovered { first: true, stats: FlowStats { packets: 1, bytes: 40, first: Duration::from_secs(105) } }),
+            (syslog(), FlowChange::Added(FlowStats { packets: 1, bytes: 50, first: Duration::from_secs(201) })),
+        ]);
+    }
+}
@@ -0,0 +1,451 @@
+/*
+https://www.rfc-editor.org/rfc/rfc6234
+*/
+
+use std::io::Error;
+use std::str::FromStr;
+use std::time::Duration;
+use pcap::Packet;
+use crate::endian::be_u32;
+use crate::ethernet::{EtherType, Ethernet};
+use crate::flow::FiveTuple;
+use crate::ipv4::IPv4;
+use crate::ipv6::IPv6;
+use crate::limits::{FlowTable, ResourceLimits};
+use crate::sll::Sll2;
+use crate::tcp::Tcp;
+use crate::time_window;
+use crate::udp::Udp;
+use crate::visitor::{Context, Visitor};
+
+
+const K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+
+/// SHA-256, fed incrementally.
+#[derive(Debug, Clone)]
+pub struct Sha256 {
+    state: [u32; 8],
+    block: [u8; 64],
+    /// Bytes in `block`.
+    used: usize,
+    length: u64,
+}
+
+impl Sha256 {
+    pub fn new() -> Self {
+        Self {
+            state: [0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19],
+            block: [0; 64],
+            used: 0,
+            length: 0,
+        }
+    }
+
+    pub fn update(&mut self, mut data: &[u8]) {
+        self.length += data.len() as u64;
+        while !data.is_empty() {
+            let count = (64 - self.used).min(data.len());
+            self.block[self.used..self.used + count].copy_from_slice(&data[..count]);
+            self.used += count;
+            data = &data[count..];
+            if self.used == 64 {
+                self.compress();
+                self.used = 0;
+            }
+        }
+    }
+
+    pub fn finish(mut self) -> [u8; 32] {
+        let bits = self.length.wrapping_mul(8);
+        self.update(&[0x80]);
+        while self.used != 56 {
+            self.update(&[0]);
+        }
+        self.update(&bits.to_be_bytes());
+
+        let mut digest = [0; 32];
+        for (chunk, word) in digest.chunks_mut(4).zip(self.state) {
+            chunk.copy_from_slice(&word.to_be_bytes());
+        }
+        digest
+    }
+
+    fn compress(&mut self) {
+        let mut w = [0u32; 64];
+        for (i, chunk) in self.block.chunks(4).enumerate() {
+            w[i] = be_u32(chunk, 0);
+        }
+        for i in 16..64 {
+            let s0 = w[i-15].rotate_right(7) ^ w[i-15].rotate_right(18) ^ (w[i-15] >> 3);
+            let s1 = w[i-2].rotate_right(17) ^ w[i-2].rotate_right(19) ^ (w[i-2] >> 10);
+            w[i] = w[i-16].wrapping_add(s0).wrapping_add(w[i-7]).wrapping_add(s1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = self.state;
+        for i in 0..64 {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let choice = (e & f) ^ (!e & g);
+            let t1 = h.wrapping_add(s1).wrapping_add(choice).wrapping_add(K[i]).wrapping_add(w[i]);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let majority = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(majority);
+            h = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
+        }
+        for (word, value) in self.state.iter_mut().zip([a, b, c, d, e, f, g, h]) {
+            *word = word.wrapping_add(value);
+        }
+    }
+}
+
+impl Default for Sha256 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+pub fn sha256(data: &[u8]) -> [u8; 32] {
+    let mut hasher = Sha256::new();
+    hasher.update(data);
+    hasher.finish()
+}
+
+pub fn to_hex(digest: &[u8]) -> String {
+    digest.iter().map(|x| format!("{:02x}", x)).collect()
+}
+
+
+/// The first layer included in a packet's hash.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub enum HashLayer {
+    /// The whole frame as captured.
+    #[default]
+    Link,
+    /// The IP packet, without link layer addresses that change at every hop or trailing padding.
+    Network,
+    /// The TCP or UDP segment and everything else after the IP header.
+    Transport,
+}
+
+impl FromStr for HashLayer {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "link"      => Ok(Self::Link),
+            "network"   => Ok(Self::Network),
+            "transport" => Ok(Self::Transport),
+            _ => Err(format!("Unknown layer '{}', expected link, network or transport", text)),
+        }
+    }
+}
+
+
+/// What a packet's hash covers. Ignored fields are zeroed before hashing, so the same packet hashes equal at
+/// capture points on either side of a router.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub struct HashOptions {
+    pub from: HashLayer,
+    /// Ignore the IPv4 TTL or IPv6 hop limit, and the IPv4 header checksum that covers it.
+    pub ignore_ttl: bool,
+    /// Ignore the IPv4 header checksum and the TCP and UDP checksums, e.g. when NAT rewrote them.
+    pub ignore_checksums: bool,
+}
+
+impl HashOptions {
+    /// Parses the comma separated fields to ignore, e.g. `ttl,checksums`.
+    pub fn with_ignored(mut self, fields: &str) -> Result<Self, String> {
+        for field in fields.split(',').filter(|x| !x.is_empty()) {
+            match field {
+                "ttl"       => self.ignore_ttl = true,
+                "checksums" => self.ignore_checksums = true,
+                _ => return Err(format!("Unknown field '{}', expected ttl or checksums", field)),
+            }
+        }
+        Ok(self)
+    }
+
+    /// The bytes of the IP packet `network` with EtherType `ether_type` that its hash covers. Other protocols
+    /// are covered as they are, from the network layer on.
+    pub fn network_bytes(&self, ether_type: u16, network: &[u8]) -> Vec<u8> {
+        let ether_type = EtherType::from_code(ether_type);
+        let (header_size, total, ttl, protocol, first_fragment) = match ether_type {
+            EtherType::IPv4 => match IPv4::from_bytes(network) {
+                Ok(ip) => (ip.header_size(), ip.header_size() + ip.raw_payload().len(), IPv4::TTL_BITS.start / 8, ip.protocol_raw(), ip.fragment_offset() == 0),
+                Err(_) => return network.to_vec(),
+            },
+            EtherType::IPv6 => match IPv6::from_bytes(network) {
+                Ok(ip) => (IPv6::HEADER_SIZE, IPv6::HEADER_SIZE + ip.raw_payload().len(), IPv6::HOP_LIMIT_BITS.start / 8, ip.next_header(), true),
+                Err(_) => return network.to_vec(),
+            },
+            _ => return network.to_vec(),
+        };
+
+        let mut bytes = network[..total].to_vec();
+        let ipv4 = ether_type == EtherType::IPv4;
+        let checksum = IPv4::HEADER_CHECKSUM_BITS.start / 8..IPv4::HEADER_CHECKSUM_BITS.end / 8;
+        if self.ignore_ttl {
+            bytes[ttl] = 0;
+            if ipv4 { bytes[checksum.clone()].fill(0) }
+        }
+        if self.ignore_checksums {
+            if ipv4 { bytes[checksum].fill(0) }
+            let offset = match protocol {
+                6  => Some(header_size + 16),
+                17 => Some(header_size + 6),
+                _  => None,
+            };
+            if let Some(offset) = offset.filter(|x| first_fragment && x + 2 <= bytes.len()) {
+                bytes[offset..offset + 2].fill(0);
+            }
+        }
+
+        if self.from == HashLayer::Transport { bytes.drain(..header_size); }
+        bytes
+    }
+
+    /// The bytes of a frame with the network layer `network`, a slice of `frame`, that its hash covers.
+    pub fn frame_bytes(&self, frame: &[u8], network: Option<(u16, &[u8])>) -> Vec<u8> {
+        let Some((ether_type, data)) = network else { return frame.to_vec() };
+        if self.from != HashLayer::Link {
+            return self.network_bytes(ether_type, data);
+        }
+        if !self.ignore_ttl && !self.ignore_checksums {
+            return frame.to_vec();
+        }
+
+        // Keep the link layer and trailing bytes, and mask the fields in place.
+        let start = data.as_ptr() as usize - frame.as_ptr() as usize;
+        let masked = self.network_bytes(ether_type, data);
+        let mut bytes = frame.to_vec();
+        bytes[start..start + masked.len()].copy_from_slice(&masked);
+        bytes
+    }
+}
+
+
+/// The EtherType and bytes of the network layer of an Ethernet frame, after any VLAN tags and without the
+/// padding or frame check sequence.
+fn ethernet_network(data: &[u8]) -> Option<(u16, &[u8])> {
+    Ethernet::from_bytes(data).ok().map(|frame| frame.untagged())
+}
+
+
+/// The running hash of the payload of one direction of a connection.
+#[derive(Debug, Clone, Default)]
+pub struct FlowDigest {
+    pub hasher: Sha256,
+    pub packets: usize,
+    pub bytes: usize,
+}
+
+
+/// Prints the SHA-256 of each packet, so the same packet can be matched across capture points, and with
+/// `with_flows` a hash of each flow's payload in the order it was captured.
+pub struct HashVisitor {
+    options: HashOptions,
+    /// The payload hashes of TCP and UDP over IPv4, when they're printed.
+    flows: Option<FlowTable<FiveTuple, FlowDigest>>,
+}
+
+impl HashVisitor {
+    pub fn new(options: HashOptions) -> Self {
+        Self { options, flows: None }
+    }
+
+    pub fn with_flows(self, limits: ResourceLimits) -> Self {
+        Self { flows: Some(FlowTable::new(limits)), ..self }
+    }
+
+    fn print(&self, packet: &Packet, network: Option<(u16, &[u8])>) {
+        let bytes = self.options.frame_bytes(packet.data, network);
+        let time = time_window::timestamp(packet.header);
+        println!("{}.{:06} {} {}", time.as_secs(), time.subsec_micros(), to_hex(&sha256(&bytes)), bytes.len());
+    }
+
+    fn push(&mut self, key: FiveTuple, payload: &[u8], context: &Context) {
+        let Some(flows) = &mut self.flows else { return };
+        let now = context.header.map(time_window::timestamp).unwrap_or_default();
+        let mut ended = flows.expire(now);
+        if flows.get(&key).is_none() {
+            while flows.len() >= flows.limits().max_flows.max(1) {
+                let Some(flow) = flows.evict_oldest() else { break };
+                ended.push(flow);
+            }
+        }
+        print_flows(ended);
+
+        let flow = flows.get_or_insert_with(key, now, FlowDigest::default);
+        flow.hasher.update(payload);
+        flow.packets += 1;
+        flow.bytes += payload.len();
+    }
+}
+
+fn print_flows(flows: Vec<(FiveTuple, FlowDigest)>) {
+    for (key, flow) in flows {
+        println!("{}: {} {} packets, {} bytes", key, to_hex(&flow.hasher.finish()), flow.packets, flow.bytes);
+    }
+}
+
+
+impl<'a> Visitor<'a, ()> for HashVisitor {
+    fn visit_packet(&mut self, packet: &'a Packet) -> Result<(), Error> {
+        self.print(packet, ethernet_network(packet.data));
+        if self.flows.is_some() {
+            let _ = self.visit_packet_payload(packet);
+        }
+        Ok(())
+    }
+
+    /// The network layer isn't located in wireless frames, so they're always hashed whole.
+    fn visit_radiotap_packet(&mut self, packet: &'a Packet) -> Result<(), Error> {
+        self.print(packet, None);
+        Ok(())
+    }
+
+    fn visit_sll2_packet(&mut self, packet: &'a Packet) -> Result<(), Error> {
+        let frame = Sll2::from_bytes(packet.data).ok();
+        self.print(packet, frame.as_ref().map(|x| (x.protocol_type(), x.raw_payload())));
+        if let (true, Some(frame)) = (self.flows.is_some(), frame) {
+            let _ = self.visit_sll2(&frame, &Context::new(packet.header));
+        }
+        Ok(())
+    }
+
+    fn visit_tcp(&mut self, packet: &Tcp<'a>, context: &Context<'a>) -> Result<(), Error> {
+        let Some(ip) = &context.ipv4 else { return Ok(()) };
+        self.push(FiveTuple::from_tcp(ip, packet), packet.raw_payload(), context);
+        Ok(())
+    }
+
+    fn visit_udp(&mut self, packet: &Udp<'a>, context: &Context<'a>) -> Result<(), Error> {
+        let Some(ip) = &context.ipv4 else { return Ok(()) };
+        self.push(FiveTuple::from_udp(ip, packet), packet.raw_payload(), context);
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        if let Some(flows) = &mut self.flows {
+            print_flows(flows.drain());
+        }
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::{Ipv4Builder, TcpBuilder, TCP_ACK};
+    use crate::testing::{self, ip};
+
+    /// A TCP frame carrying `payload` with a TTL of `ttl`.
+    fn frame(ttl: u8, payload: &[u8]) -> Vec<u8> {
+        let tcp = TcpBuilder::new(51000, 80).with_flags(TCP_ACK).with_payload(payload);
+        let packet = Ipv4Builder::new(ip(10, 0, 0, 1), ip(10, 0, 0, 2)).with_time_to_live(ttl).with_tcp(&tcp).build();
+        testing::ethernet(testing::ETHER_TYPE_IPV4, &packet)
+    }
+
+    fn hash(options: HashOptions, frame: &[u8]) -> [u8; 32] {
+        sha256(&options.frame_bytes(frame, ethernet_network(frame)))
+    }
+
+    #[test]
+    fn computes_sha256_test_vectors() {
+        assert_eq!(to_hex(&sha256(b"abc")), "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad");
+        assert_eq!(to_hex(&sha256(b"")), "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+
+        let mut hasher = Sha256::new();
+        for chunk in b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq".chunks(7) {
+            hasher.update(chunk);
+        }
+        assert_eq!(to_hex(&hasher.finish()), "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1");
+    }
+
+    #[test]
+    fn hashes_identical_packets_equal() {
+        let options = HashOptions::default();
+        assert_eq!(hash(options, &frame(64, b"hello")), hash(options, &frame(64, b"hello")));
+        assert_ne!(hash(options, &frame(64, b"hello")), hash(options, &frame(64, b"world")));
+    }
+
+    #[test]
+    fn hashes_a_ttl_difference_equal_when_ignoring_the_ttl() {
+        let (before, after) = (frame(64, b"hello"), frame(63, b"hello"));
+        assert_ne!(hash(HashOptions::default(), &before), hash(HashOptions::default(), &after));
+
+        for from in [HashLayer::Link, HashLayer::Network] {
+            let options = HashOptions { from, ..HashOptions::default() }.with_ignored("ttl").unwrap();
+            assert_eq!(hash(options, &before), hash(options, &after));
+            assert_ne!(hash(options, &before), hash(options, &frame(64, b"world")));
+        }
+    }
+
+    #[test]
+    fn hashes_from_the_chosen_layer() {
+        let frame = frame(64, b"hello");
+        let network = ethernet_network(&frame);
+        assert_eq!(HashOptions::default().frame_bytes(&frame, network), frame);
+
+        let options = HashOptions { from: HashLayer::Network, ..HashOptions::default() };
+        assert_eq!(options.frame_bytes(&frame, network), &frame[Ethernet::PAYLOAD_OFFSET..]);
+
+        let options = HashOptions { from: HashLayer::Transport, ..HashOptions::default() }.with_ignored("checksums").unwrap();
+        let segment = options.frame_bytes(&frame, network);
+        assert_eq!(segment.len(), 20 + 5);
+        assert_eq!((&segment[16..18], &segment[20..]), (&[0, 0][..], &b"hello"[..]));
+    }
+
+    #[test]
+    fn locates_the_network_layer_behind_a_vlan_tag() {
+        let packet = Ipv4Builder::new(ip(10, 0, 0, 1), ip(10, 0, 0, 2)).with_tcp(&TcpBuilder::new(51000, 80)).build();
+        let mut tagged = vec![0x00, 0x64, 0x08, 0x00];
+        tagged.extend_from_slice(&packet);
+        let frame = testing::ethernet(0x8100, &tagged);
+        assert_eq!(ethernet_network(&frame), Some((0x0800, &packet[..])));
+    }
+
+    #[test]
+    fn leaves_the_padding_and_frame_check_sequence_out_of_the_network_layer() {
+        let plain = frame(64, b"");
+        let mut padded = plain.clone();
+        padded.resize(60, 0);
+        padded.extend_from_slice(&crate::shared::crc32(&padded).to_le_bytes());
+
+        assert_eq!(ethernet_network(&padded), Some((0x0800, &plain[Ethernet::PAYLOAD_OFFSET..])));
+        for from in [HashLayer::Network, HashLayer::Transport] {
+            let options = HashOptions { from, ..HashOptions::default() };
+            assert_eq!(hash(options, &padded), hash(options, &plain));
+        }
+        // The link layer keeps them, with the masked fields in place.
+        let options = HashOptions::default().with_ignored("ttl").unwrap();
+        let bytes = options.frame_bytes(&padded, ethernet_network(&padded));
+        assert_eq!((bytes.len(), bytes[Ethernet::PAYLOAD_OFFSET + 8]), (64, 0));
+    }
+
+    #[test]
+    fn parses_the_ignored_fields() {
+        let options = HashOptions::default().with_ignored("ttl,checksums").unwrap();
+        assert!(options.ignore_ttl && options.ignore_checksums);
+        assert!(HashOptions::default().with_ignored("ttl,flags").is_err());
+        assert_eq!("transport".parse::<HashLayer>(), Ok(HashLayer::Transport));
+    }
+}
@@ -0,0 +1,142 @@
+/*
+https://www.rfc-editor.org/rfc/rfc1035
+*/
+
+use crate::error::ParseError;
+use crate::ipv4::Ipv4Address;
+use crate::ipv6::Ipv6Address;
+use crate::endian::{be_u16, be_u32};
+
+
+pub const PORT: u16 = 53;
+
+pub const TYPE_A     : u16 = 1;
+pub const TYPE_CNAME : u16 = 5;
+pub const TYPE_AAAA  : u16 = 28;
+
+/// Maximum number of compression pointers followed in one name, which stops pointer loops.
+const MAX_POINTERS: usize = 16;
+
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Question {
+    pub name: String,
+    pub kind: u16,
+    pub class: u16,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Data {
+    A(Ipv4Address),
+    Aaaa(Ipv6Address),
+    Cname(String),
+    Other(Vec<u8>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Record {
+    pub name: String,
+    pub kind: u16,
+    pub class: u16,
+    pub ttl: u32,
+    pub data: Data,
+}
+
+impl std::fmt::Display for Record {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match &self.data {
+            Data::A(address)    => write!(f, "A {:?}", address),
+            Data::Aaaa(address) => write!(f, "AAAA {:?}", address),
+            Data::Cname(name)   => write!(f, "CNAME {}", name),
+            Data::Other(data)   => write!(f, "TYPE{} ({} bytes)", self.kind, data.len()),
+        }
+    }
+}
+
+
+/// A query or response. Only the question and answer sections are decoded.
+#[derive(Debug, Clone)]
+pub struct Message {
+    pub id: u16,
+    pub flags: u16,
+    pub questions: Vec<Question>,
+    pub answers: Vec<Record>,
+}
+
+impl Message {
+    pub const HEADER_SIZE: usize = 12;
+
+    pub fn is_response(&self)  -> bool { self.flags & 0x8000 != 0 }
+    pub fn response_code(&self) -> u8  { (self.flags & 0x000F) as u8 }
+
+    pub fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
+        if data.len() < Self::HEADER_SIZE {
+            return Err(ParseError::TooSmall { layer: "Dns", field: "data", expected: Self::HEADER_SIZE, got: data.len() });
+        }
+
+        let question_count = be_u16(data, 4);
+        let answer_count   = be_u16(data, 6);
+
+        let mut index = Self::HEADER_SIZE;
+        let mut questions = Vec::new();
+        for _ in 0..question_count {
+            let name = read_name(data, &mut index)?;
+            let fixed = data.get(index..index + 4).ok_or(ParseError::Invalid("Dns question truncated"))?;
+            questions.push(Question { name, kind: be_u16(fixed, 0), class: be_u16(fixed, 2) });
+            index += 4;
+        }
+
+        let mut answers = Vec::new();
+        for _ in 0..answer_count {
+            let name = read_name(data, &mut index)?;
+            let fixed = data.get(index..index + 10).ok_or(ParseError::Invalid("Dns record truncated"))?;
+            let (kind, class, ttl, length) = (be_u16(fixed, 0), be_u16(fixed, 2), be_u32(fixed, 4), be_u16(fixed, 8) as usize);
+            index += 10;
+
+            let raw = data.get(index..index + length).ok_or(ParseError::Invalid("Dns record data truncated"))?;
+            let record_data = match (kind, length) {
+                (TYPE_A, 4)     => Data::A(Ipv4Address::from_bytes(raw.try_into().unwrap())),
+                (TYPE_AAAA, 16) => Data::Aaaa(Ipv6Address::from_bytes(raw.try_into().unwrap())),
+                (TYPE_CNAME, _) => Data::Cname(read_name(data, &mut index.clone())?),
+                _               => Data::Other(raw.to_vec()),
+            };
+            index += length;
+            answers.push(Record { name, kind, class, ttl, data: record_data });
+        }
+
+        Ok(Self { id: be_u16(data, 0), flags: be_u16(data, 2), questions, answers })
+    }
+}
+
+
+/// Reads a possibly compressed domain name at `index` and moves `index` past it.
+fn read_name(data: &[u8], index: &mut usize) -> Result<String, ParseError> {
+    let mut labels: Vec<String> = Vec::new();
+    let mut position = *index;
+    let mut pointers = 0;
+
+    loop {
+        let length = *data.get(position).ok_or(ParseError::Invalid("Dns name truncated"))? as usize;
+        match length & 0xC0 {
+            0x00 if length == 0 => {
+                if pointers == 0 { *index = position + 1 }
+                break;
+            },
+            0x00 => {
+                let label = data.get(position + 1..position + 1 + length).ok_or(ParseError::Invalid("Dns label truncated"))?;
+                labels.push(String::from_utf8_lossy(label).into_owned());
+                position += 1 + length;
+            },
+            0xC0 => {
+                let pointer = data.get(position..position + 2).ok_or(ParseError::Invalid("Dns name pointer truncated"))?;
+                if pointers == 0 { *index = position + 2 }
+                pointers += 1;
+                if pointers > MAX_POINTERS { return Err(ParseError::Invalid("Dns name has too many pointers")) }
+                position = (be_u16(pointer, 0) & 0x3FFF) as usize;
+            },
+            _ => return Err(ParseError::Invalid("Dns label type is not supported")),
+        }
+    }
+
+    Ok(if labels.is_empty() { ".".to_string() } else { labels.join(".") })
+}
@@ -0,0 +1,144 @@
+use std::collections::HashMap;
+use std::io::Error;
+use std::time::Duration;
+use pcap::PacketHeader;
+use crate::dns;
+use crate::udp::Udp;
+use crate::visitor::{Context, Visitor};
+
+
+/// One side of a DNS exchange, rendered as `address:port`.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+pub struct QueryKey {
+    pub id: u16,
+    pub client: String,
+    pub server: String,
+}
+
+
+#[derive(Debug, Clone)]
+struct Pending {
+    name: String,
+    sent: Duration,
+}
+
+
+/// Pairs DNS queries over UDP with their responses and prints the answers and the response time. Queries
+/// without a response within `timeout` are reported as unanswered.
+pub struct DnsVisitor {
+    timeout: Duration,
+    pending: HashMap<QueryKey, Pending>,
+}
+
+impl DnsVisitor {
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(5);
+
+    pub fn new(timeout: Duration) -> Self {
+        Self { timeout, pending: HashMap::new() }
+    }
+
+    /// Records a message seen at `now` and returns the report line for a response that matches a query.
+    pub fn push(&mut self, message: &dns::Message, source: String, destination: String, now: Duration) -> Option<String> {
+        let mut lines = self.expire(now);
+
+        if !message.is_response() {
+            let name = message.questions.first().map(|x| x.name.clone()).unwrap_or_default();
+            self.pending.insert(QueryKey { id: message.id, client: source, server: destination }, Pending { name, sent: now });
+        } else if let Some(query) = self.pending.remove(&QueryKey { id: message.id, client: destination, server: source }) {
+            let answers: Vec<String> = message.answers.iter().map(|x| x.to_string()).collect();
+            let answers = if answers.is_empty() { format!("no answers, rcode {}", message.response_code()) } else { answers.join(", ") };
+            lines.push(format!("{} in {:.3} ms: {}", query.name, now.saturating_sub(query.sent).as_secs_f64() * 1000.0, answers));
+        }
+
+        if lines.is_empty() { None } else { Some(lines.join("\n")) }
+    }
+
+    /// Removes the queries that have waited longer than the timeout and returns their report lines.
+    pub fn expire(&mut self, now: Duration) -> Vec<String> {
+        let expired: Vec<QueryKey> = self.pending.iter()
+            .filter(|(_, query)| now.saturating_sub(query.sent) > self.timeout)
+            .map(|(key, _)| key.clone())
+            .collect();
+        let mut lines: Vec<String> = expired.iter()
+            .map(|key| format!("{} unanswered (id {:#06x}, {} > {})", self.pending.remove(key).unwrap().name, key.id, key.client, key.server))
+            .collect();
+        lines.sort();
+        lines
+    }
+}
+
+
+fn timestamp(header: &PacketHeader) -> Duration {
+    Duration::new(header.ts.tv_sec.max(0) as u64, (header.ts.tv_usec.max(0) as u32).min(999_999) * 1000)
+}
+
+
+impl<'a> Visitor<'a, ()> for DnsVisitor {
+    fn visit_udp(&mut self, packet: &Udp<'a>, context: &Context<'a>) -> Result<(), Error> {
+        if packet.source_port() != dns::PORT && packet.destination_port() != dns::PORT {
+            return Ok(());
+        }
+
+        let (source, destination) = match (&context.ipv4, &context.ipv6) {
+            (Some(ip), _) => (format!("{:?}:{}", ip.source_address(), packet.source_port()), format!("{:?}:{}", ip.destination_address(), packet.destination_port())),
+            (_, Some(ip)) => (format!("[{:?}]:{}", ip.source_address(), packet.source_port()), format!("[{:?}]:{}", ip.destination_address(), packet.destination_port())),
+            _ => return Ok(()),
+        };
+        let now = context.header.map(timestamp).unwrap_or_default();
+
+        let message = dns::Message::from_bytes(packet.raw_payload())?;
+        if let Some(line) = self.push(&message, source, destination, now) {
+            println!("{}", line);
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        for line in self.expire(Duration::MAX) {
+            println!("{}", line);
+        }
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::dns::{Data, Message, Question, Record, TYPE_A};
+    use crate::testing::ip;
+
+    fn query(id: u16, name: &str) -> Message {
+        Message { id, flags: 0x0100, questions: vec![Question { name: name.to_string(), kind: TYPE_A, class: 1 }], answers: vec![] }
+    }
+
+    fn response(id: u16, name: &str, addresses: &[[u8; 4]]) -> Message {
+        let answers = addresses.iter()
+            .map(|x| Record { name: name.to_string(), kind: TYPE_A, class: 1, ttl: 300, data: Data::A(ip(x[0], x[1], x[2], x[3])) })
+            .collect();
+        Message { flags: 0x8180, answers, ..query(id, name) }
+    }
+
+    #[test]
+    fn pairs_a_query_with_its_response() {
+        let mut visitor = DnsVisitor::new(DnsVisitor::DEFAULT_TIMEOUT);
+        let (client, server) = ("10.0.0.1:53000".to_string(), "10.0.0.53:53".to_string());
+
+        assert_eq!(visitor.push(&query(0x1234, "example.com"), client.clone(), server.clone(), Duration::from_millis(1000)), None);
+        // A response to another transaction doesn't match.
+        assert_eq!(visitor.push(&response(0x4321, "example.com", &[[1, 1, 1, 1]]), server.clone(), client.clone(), Duration::from_millis(1005)), None);
+
+        let line = visitor.push(&response(0x1234, "example.com", &[[93, 184, 216, 34], [93, 184, 216, 35]]), server, client, Duration::from_micros(1_012_500));
+        assert_eq!(line.as_deref(), Some("example.com in 12.500 ms: A 93.184.216.34, A 93.184.216.35"));
+        assert!(visitor.expire(Duration::MAX).is_empty());
+    }
+
+    #[test]
+    fn reports_queries_without_a_response_after_the_timeout() {
+        let mut visitor = DnsVisitor::new(Duration::from_secs(2));
+        visitor.push(&query(0x0001, "slow.example"), "10.0.0.1:53000".to_string(), "10.0.0.53:53".to_string(), Duration::from_secs(10));
+
+        assert!(visitor.expire(Duration::from_secs(12)).is_empty());
+        assert_eq!(visitor.expire(Duration::from_secs(13)), ["slow.example unanswered (id 0x0001, 10.0.0.1:53000 > 10.0.0.53:53)"]);
+    }
+}
@@ -0,0 +1,94 @@
+use std::time::{Duration, Instant};
+use pcap::{Activated, Capture, Stat};
+
+
+/// Where the kernel's packet counters come from, a live capture in practice.
+pub trait StatsSource {
+    /// The counters since the capture started, or `None` if the source doesn't keep any.
+    fn stats(&mut self) -> Option<Stat>;
+}
+
+impl<T: Activated + ?Sized> StatsSource for Capture<T> {
+    fn stats(&mut self) -> Option<Stat> {
+        Capture::stats(self).ok()
+    }
+}
+
+
+/// Prints how many packets the kernel received and dropped every `interval`, so losses from buffer overruns
+/// aren't silent.
+pub struct DropMonitor {
+    interval: Duration,
+    last: Instant,
+}
+
+impl DropMonitor {
+    pub const DEFAULT_INTERVAL: Duration = Duration::from_secs(10);
+
+    pub fn new(interval: Duration) -> Self {
+        Self { interval, last: Instant::now() }
+    }
+
+    /// Formats the counters, e.g. `recv=1200 dropped=3 if_dropped=0`.
+    pub fn format(stat: &Stat) -> String {
+        format!("recv={} dropped={} if_dropped={}", stat.received, stat.dropped, stat.if_dropped)
+    }
+
+    /// Prints the counters if `interval` passed since they were last printed.
+    pub fn poll(&mut self, source: &mut (impl StatsSource + ?Sized)) {
+        if self.last.elapsed() >= self.interval {
+            self.report(source);
+        }
+    }
+
+    /// Prints the counters now.
+    pub fn report(&mut self, source: &mut (impl StatsSource + ?Sized)) {
+        self.last = Instant::now();
+        if let Some(stat) = source.stats() {
+            eprintln!("[STATS]: {}", Self::format(&stat));
+        }
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Counts how often it's asked, and drops one more packet each time.
+    #[derive(Default)]
+    struct Stub {
+        calls: u32,
+    }
+
+    impl StatsSource for Stub {
+        fn stats(&mut self) -> Option<Stat> {
+            self.calls += 1;
+            Some(Stat { received: 100 * self.calls, dropped: self.calls, if_dropped: 0 })
+        }
+    }
+
+    #[test]
+    fn polls_the_source_once_per_interval() {
+        let mut stub = Stub::default();
+        let mut monitor = DropMonitor::new(Duration::from_secs(3600));
+        monitor.poll(&mut stub);
+        monitor.poll(&mut stub);
+        assert_eq!(stub.calls, 0);
+
+        // The report at the end of a capture doesn't wait for the interval.
+        monitor.report(&mut stub);
+        assert_eq!(stub.calls, 1);
+
+        let mut monitor = DropMonitor::new(Duration::ZERO);
+        monitor.poll(&mut stub);
+        monitor.poll(&mut stub);
+        assert_eq!(stub.calls, 3);
+    }
+
+    #[test]
+    fn formats_the_counters() {
+        let stat = Stub { calls: 2 }.stats().unwrap();
+        assert_eq!(DropMonitor::format(&stat), "recv=300 dropped=3 if_dropped=0");
+    }
+}
@@ -0,0 +1,191 @@
+/*
+https://standards.ieee.org/ieee/802.1X/7345/
+https://www.rfc-editor.org/rfc/rfc3748
+*/
+
+use alloc::string::String;
+use crate::error::ParseError;
+use crate::endian::be_u16;
+
+
+pub const TYPE_EAP_PACKET : u8 = 0;
+pub const TYPE_START      : u8 = 1;
+pub const TYPE_LOGOFF     : u8 = 2;
+pub const TYPE_KEY        : u8 = 3;
+
+pub const CODE_REQUEST  : u8 = 1;
+pub const CODE_RESPONSE : u8 = 2;
+pub const CODE_SUCCESS  : u8 = 3;
+pub const CODE_FAILURE  : u8 = 4;
+
+pub const EAP_TYPE_IDENTITY : u8 = 1;
+
+
+/// An EAP over LAN frame, exchanged while a port or WPA association is being authenticated.
+#[derive(Clone)]
+pub struct Eapol<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Eapol<'a> {
+    pub const HEADER_SIZE: usize = 4;
+
+    pub fn version(&self) -> u8  { self.data[0] }
+    pub fn kind(&self)    -> u8  { self.data[1] }
+    pub fn length(&self)  -> u16 { be_u16(self.data, 2) }
+
+    pub fn kind_name(&self) -> &'static str {
+        match self.kind() {
+            TYPE_EAP_PACKET => "EAP-Packet",
+            TYPE_START      => "EAPOL-Start",
+            TYPE_LOGOFF     => "EAPOL-Logoff",
+            TYPE_KEY        => "EAPOL-Key",
+            _               => "Unknown",
+        }
+    }
+
+    /// The body, without any Ethernet padding.
+    pub fn raw_payload(&self) -> &'a [u8] {
+        &self.data[Self::HEADER_SIZE..Self::HEADER_SIZE + self.length() as usize]
+    }
+
+    /// The EAP packet carried by an EAP-Packet frame.
+    pub fn eap(&self) -> Option<Result<Eap<'a>, ParseError>> {
+        if self.kind() != TYPE_EAP_PACKET { return None }
+        Some(Eap::from_bytes(self.raw_payload()))
+    }
+
+    pub fn from_bytes(data: &'a [u8]) -> Result<Self, ParseError> {
+        if data.len() < Self::HEADER_SIZE {
+            return Err(ParseError::TooSmall { layer: "Eapol", field: "data", expected: Self::HEADER_SIZE, got: data.len() });
+        }
+
+        let me = Self { data };
+        if Self::HEADER_SIZE + me.length() as usize > data.len() {
+            return Err(ParseError::TooBig { layer: "Eapol", field: "length", expected: data.len() - Self::HEADER_SIZE, got: me.length() as usize });
+        }
+
+        Ok(me)
+    }
+}
+
+
+impl<'a> core::fmt::Debug for Eapol<'a> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "    Eapol\n")?;
+        write!(f, "        version: {:?}\n", self.version())?;
+        write!(f, "        kind:    {} ({})\n", self.kind_name(), self.kind())?;
+        write!(f, "        length:  {:?}\n", self.length())?;
+        write!(f, "        eap:     {:?}\n", self.eap())?;
+        Ok(())
+    }
+}
+
+
+#[derive(Clone)]
+pub struct Eap<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Eap<'a> {
+    pub const HEADER_SIZE: usize = 4;
+
+    pub fn code(&self)       -> u8  { self.data[0] }
+    pub fn identifier(&self) -> u8  { self.data[1] }
+    pub fn length(&self)     -> u16 { be_u16(self.data, 2) }
+
+    pub fn code_name(&self) -> &'static str {
+        match self.code() {
+            CODE_REQUEST  => "Request",
+            CODE_RESPONSE => "Response",
+            CODE_SUCCESS  => "Success",
+            CODE_FAILURE  => "Failure",
+            _             => "Unknown",
+        }
+    }
+
+    /// The method of a request or response, e.g. 1 for Identity.
+    pub fn kind(&self) -> Option<u8> {
+        match self.code() {
+            CODE_REQUEST | CODE_RESPONSE => self.data.get(Self::HEADER_SIZE).copied(),
+            _ => None,
+        }
+    }
+
+    /// The data following the type of a request or response.
+    pub fn type_data(&self) -> &'a [u8] {
+        let end = self.length() as usize;
+        &self.data[(Self::HEADER_SIZE + 1).min(end)..end]
+    }
+
+    /// The identity of an Identity response, or the prompt of an Identity request.
+    pub fn identity(&self) -> Option<String> {
+        if self.kind() != Some(EAP_TYPE_IDENTITY) { return None }
+        Some(String::from_utf8_lossy(self.type_data()).into_owned())
+    }
+
+    pub fn from_bytes(data: &'a [u8]) -> Result<Self, ParseError> {
+        if data.len() < Self::HEADER_SIZE {
+            return Err(ParseError::TooSmall { layer: "Eap", field: "data", expected: Self::HEADER_SIZE, got: data.len() });
+        }
+
+        let me = Self { data };
+        let length = me.length() as usize;
+        if length < Self::HEADER_SIZE {
+            return Err(ParseError::TooSmall { layer: "Eap", field: "length", expected: Self::HEADER_SIZE, got: length });
+        }
+        if length > data.len() {
+            return Err(ParseError::TooBig { layer: "Eap", field: "length", expected: data.len(), got: length });
+        }
+
+        Ok(me)
+    }
+}
+
+
+impl<'a> core::fmt::Debug for Eap<'a> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "Eap {{ code: {} ({}), identifier: {}, length: {}, kind: {:?} }}", self.code_name(), self.code(), self.identifier(), self.length(), self.kind())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ethernet::{self, Ethernet};
+    use crate::testing;
+
+    #[test]
+    fn decodes_an_eapol_start() {
+        // Padded to the Ethernet minimum.
+        let mut body = [0u8; 46];
+        body[..4].copy_from_slice(&[2, TYPE_START, 0, 0]);
+        let frame = testing::ethernet(0x888E, &body);
+
+        let Ok(ethernet::Payload::Eapol(eapol)) = Ethernet::from_bytes(&frame).unwrap().payload() else { panic!("not an EAPOL frame") };
+        assert_eq!(eapol.version(), 2);
+        assert_eq!(eapol.kind_name(), "EAPOL-Start");
+        assert_eq!(eapol.raw_payload(), []);
+        assert!(eapol.eap().is_none());
+    }
+
+    #[test]
+    fn decodes_an_eap_request_identity() {
+        let data = [1, TYPE_EAP_PACKET, 0, 10, CODE_REQUEST, 7, 0, 10, EAP_TYPE_IDENTITY, b'h', b'e', b'l', b'l', b'o', 0, 0];
+        let eapol = Eapol::from_bytes(&data).unwrap();
+        assert_eq!(eapol.kind_name(), "EAP-Packet");
+
+        let eap = eapol.eap().unwrap().unwrap();
+        assert_eq!(eap.code_name(), "Request");
+        assert_eq!(eap.identifier(), 7);
+        assert_eq!(eap.kind(), Some(EAP_TYPE_IDENTITY));
+        assert_eq!(eap.identity().as_deref(), Some("hello"));
+    }
+
+    #[test]
+    fn rejects_a_length_past_the_frame() {
+        assert!(matches!(Eapol::from_bytes(&[2, TYPE_KEY, 0, 95, 0]), Err(ParseError::TooBig { layer: "Eapol", .. })));
+        assert!(matches!(Eap::from_bytes(&[CODE_SUCCESS, 1, 0, 2]), Err(ParseError::TooSmall { layer: "Eap", field: "length", .. })));
+    }
+}
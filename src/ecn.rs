@@ -0,0 +1,215 @@
+/*
+https://www.rfc-editor.org/rfc/rfc3168
+*/
+
+use std::collections::HashMap;
+use std::io::Error;
+use crate::qos::ECN_CE;
+use crate::reassembly::StreamKey;
+use crate::tcp::Tcp;
+use crate::time_window;
+use crate::visitor::{Context, Visitor};
+
+
+/// What a segment revealed about the ECN behaviour of its connection.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum EcnEvent {
+    /// The SYN-ACK accepted the ECN the SYN asked for.
+    Negotiated,
+    /// The client asked for ECN but the server didn't accept it.
+    Declined,
+    /// A router marked the segment Congestion Experienced instead of dropping it.
+    CongestionExperienced,
+    /// The receiver echoed a CE mark back with ECE.
+    Echo,
+    /// The sender confirmed it reduced its congestion window with CWR.
+    WindowReduced,
+}
+
+
+/// The ECN state of one connection, kept under the direction of the client.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub struct FlowEcn {
+    /// The SYN set both ECE and CWR.
+    pub requested: bool,
+    /// The SYN-ACK set ECE but not CWR. `None` until it's seen.
+    pub accepted: Option<bool>,
+    pub ce_marks: usize,
+    pub echoes: usize,
+    pub reductions: usize,
+}
+
+impl FlowEcn {
+    pub fn negotiated(&self) -> bool {
+        self.requested && self.accepted == Some(true)
+    }
+}
+
+
+/// Correlates the ECN field of the IP header with the ECE and CWR flags of TCP per connection. Only IPv4 is
+/// tracked, like the other per-connection reports.
+#[derive(Debug, Default)]
+pub struct EcnTracker {
+    flows: HashMap<StreamKey, FlowEcn>,
+}
+
+impl EcnTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records a segment sent in the direction `key` with the IP header's ECN code point `ecn`.
+    pub fn push(&mut self, key: StreamKey, ecn: u8, tcp: &Tcp) -> Vec<EcnEvent> {
+        let mut events = Vec::new();
+        let syn = tcp.syn() != 0;
+        let ack = tcp.ack() != 0;
+        let ece = tcp.ece() != 0;
+        let cwr = tcp.cwr() != 0;
+
+        if syn && !ack {
+            self.flows.insert(key, FlowEcn { requested: ece && cwr, ..FlowEcn::default() });
+            return events;
+        }
+
+        let client = if self.flows.contains_key(&key.reversed()) { key.reversed() } else { key };
+        let flow = self.flows.entry(client).or_default();
+
+        // ECE and CWR mean something else during the handshake.
+        if syn {
+            let accepted = ece && !cwr;
+            flow.accepted = Some(accepted);
+            if flow.requested {
+                events.push(if accepted { EcnEvent::Negotiated } else { EcnEvent::Declined });
+            }
+            return events;
+        }
+
+        if ecn & 0b11 == ECN_CE {
+            flow.ce_marks += 1;
+            events.push(EcnEvent::CongestionExperienced);
+        }
+        if ece {
+            flow.echoes += 1;
+            events.push(EcnEvent::Echo);
+        }
+        if cwr {
+            flow.reductions += 1;
+            events.push(EcnEvent::WindowReduced);
+        }
+        events
+    }
+
+    /// The connections seen, in address order.
+    pub fn report(&self) -> Vec<(StreamKey, FlowEcn)> {
+        let mut report: Vec<(StreamKey, FlowEcn)> = self.flows.iter().map(|(key, ecn)| (*key, *ecn)).collect();
+        report.sort_by_key(|(key, _)| (key.source, key.source_port, key.destination, key.destination_port));
+        report
+    }
+}
+
+
+/// Prints ECN negotiation and congestion signals as they happen and a summary per connection at the end.
+pub struct EcnVisitor {
+    tracker: EcnTracker,
+}
+
+impl EcnVisitor {
+    pub fn new() -> Self {
+        Self { tracker: EcnTracker::new() }
+    }
+}
+
+impl Default for EcnVisitor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+impl<'a> Visitor<'a, ()> for EcnVisitor {
+    fn visit_tcp(&mut self, packet: &Tcp<'a>, context: &Context<'a>) -> Result<(), Error> {
+        let Some(ip) = &context.ipv4 else { return Ok(()) };
+        let key = StreamKey::new(ip, packet);
+        let time = context.header.map(time_window::timestamp).unwrap_or_default();
+
+        for event in self.tracker.push(key, ip.ecn(), packet) {
+            let text = match event {
+                EcnEvent::Negotiated            => "ECN negotiated",
+                EcnEvent::Declined              => "ECN requested but not accepted",
+                EcnEvent::CongestionExperienced => "congestion experienced (CE)",
+                EcnEvent::Echo                  => "congestion echoed (ECE)",
+                EcnEvent::WindowReduced         => "congestion window reduced (CWR)",
+            };
+            println!("{}.{:06} {}: {}", time.as_secs(), time.subsec_micros(), key, text);
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        for (key, ecn) in self.tracker.report() {
+            let negotiation = match (ecn.requested, ecn.accepted) {
+                (false, _)          => "not requested",
+                (true, None)        => "requested",
+                (true, Some(false)) => "declined",
+                (true, Some(true))  => "negotiated",
+            };
+            println!("{}: ECN {}, {} CE marks, {} ECE, {} CWR", key, negotiation, ecn.ce_marks, ecn.echoes, ecn.reductions);
+        }
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::{Ipv4Builder, TcpBuilder, TCP_ACK, TCP_SYN};
+    use crate::ipv4::{self, IPv4};
+    use crate::qos::ECN_ECT0;
+    use crate::testing::ip;
+
+    const TCP_ECE: u8 = 0b0100_0000;
+    const TCP_CWR: u8 = 0b1000_0000;
+
+    /// Records a segment with `flags` from the client, or from the server if `reply`, in an IPv4 packet whose
+    /// ECN field is `ecn`, decoded the way the visitor sees it.
+    fn push(tracker: &mut EcnTracker, reply: bool, flags: u8, ecn: u8) -> Vec<EcnEvent> {
+        let (source, destination) = if reply { (ip(10, 0, 0, 2), ip(10, 0, 0, 1)) } else { (ip(10, 0, 0, 1), ip(10, 0, 0, 2)) };
+        let tcp = if reply { TcpBuilder::new(80, 51000) } else { TcpBuilder::new(51000, 80) };
+        let mut data = Ipv4Builder::new(source, destination).with_tcp(&tcp.with_flags(flags)).build();
+        data[1] = ecn;
+        let packet = IPv4::from_bytes(&data).unwrap();
+        let Ok(ipv4::Payload::Tcp(segment)) = packet.payload() else { panic!("the packet isn't TCP") };
+        tracker.push(StreamKey::new(&packet, &segment), packet.ecn(), &segment)
+    }
+
+    #[test]
+    fn follows_a_ce_mark_through_the_echo_and_the_reduction() {
+        let mut tracker = EcnTracker::new();
+        assert_eq!(push(&mut tracker, false, TCP_SYN | TCP_ECE | TCP_CWR, 0), []);
+        assert_eq!(push(&mut tracker, true, TCP_SYN | TCP_ACK | TCP_ECE, 0), [EcnEvent::Negotiated]);
+        assert_eq!(push(&mut tracker, false, TCP_ACK, ECN_ECT0), []);
+
+        assert_eq!(push(&mut tracker, false, TCP_ACK, ECN_CE), [EcnEvent::CongestionExperienced]);
+        assert_eq!(push(&mut tracker, true, TCP_ACK | TCP_ECE, ECN_ECT0), [EcnEvent::Echo]);
+        assert_eq!(push(&mut tracker, false, TCP_ACK | TCP_CWR, ECN_ECT0), [EcnEvent::WindowReduced]);
+
+        let [(key, ecn)] = tracker.report()[..] else { panic!("not one connection") };
+        assert_eq!(key.source, ip(10, 0, 0, 1));
+        assert!(ecn.negotiated());
+        assert_eq!((ecn.ce_marks, ecn.echoes, ecn.reductions), (1, 1, 1));
+    }
+
+    #[test]
+    fn reports_a_declined_request() {
+        let mut tracker = EcnTracker::new();
+        assert_eq!(push(&mut tracker, false, TCP_SYN | TCP_ECE | TCP_CWR, 0), []);
+        assert_eq!(push(&mut tracker, true, TCP_SYN | TCP_ACK, 0), [EcnEvent::Declined]);
+        assert!(!tracker.report()[0].1.negotiated());
+
+        // A SYN without ECE and CWR doesn't ask for ECN, whatever the SYN-ACK says.
+        let mut tracker = EcnTracker::new();
+        assert_eq!(push(&mut tracker, false, TCP_SYN, 0), []);
+        assert_eq!(push(&mut tracker, true, TCP_SYN | TCP_ACK | TCP_ECE, 0), []);
+    }
+}
@@ -1,8 +1,81 @@
-pub fn be_to_fe<const C: usize>(mut bytes: [u8; C]) -> [u8; C] {
-    let middle = C / 2;
-    let (first, second) = bytes.split_at_mut(middle);
-    for i in 0..middle {
-        std::mem::swap(&mut first[i], &mut second[middle-i-1])
-    };
-    return bytes;
+/*
+Reads of multi-byte fields. Network order is big endian, so the most significant byte comes first, and the value
+is the same whatever the byte order of the host. The few little endian fields, e.g. of Radiotap and the Ethernet
+frame check sequence, have their own functions so every byte order conversion lives here.
+*/
+
+
+/// The big endian `u16` at `data[i..i+2]`.
+pub fn be_u16(data: &[u8], i: usize) -> u16 {
+    u16::from_be_bytes([data[i], data[i+1]])
+}
+
+/// The big endian 24-bit value at `data[i..i+3]`, e.g. the VXLAN network identifier.
+pub fn be_u24(data: &[u8], i: usize) -> u32 {
+    u32::from_be_bytes([0, data[i], data[i+1], data[i+2]])
+}
+
+/// The big endian `u32` at `data[i..i+4]`.
+pub fn be_u32(data: &[u8], i: usize) -> u32 {
+    u32::from_be_bytes([data[i], data[i+1], data[i+2], data[i+3]])
+}
+
+/// The big endian `u64` at `data[i..i+8]`.
+pub fn be_u64(data: &[u8], i: usize) -> u64 {
+    (be_u32(data, i) as u64) << 32 | be_u32(data, i + 4) as u64
+}
+
+
+/// The little endian `u16` at `data[i..i+2]`.
+pub fn le_u16(data: &[u8], i: usize) -> u16 {
+    u16::from_le_bytes([data[i], data[i+1]])
+}
+
+/// The little endian `u32` at `data[i..i+4]`.
+pub fn le_u32(data: &[u8], i: usize) -> u32 {
+    u32::from_le_bytes([data[i], data[i+1], data[i+2], data[i+3]])
+}
+
+/// The little endian `u64` at `data[i..i+8]`.
+pub fn le_u64(data: &[u8], i: usize) -> u64 {
+    (le_u32(data, i + 4) as u64) << 32 | le_u32(data, i) as u64
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BYTES: [u8; 9] = [0xFF, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06, 0x07, 0x08];
+
+    #[test]
+    fn reads_big_endian_values() {
+        assert_eq!(be_u16(&BYTES, 1), 0x0102);
+        assert_eq!(be_u24(&BYTES, 1), 0x010203);
+        assert_eq!(be_u32(&BYTES, 1), 0x01020304);
+        assert_eq!(be_u64(&BYTES, 1), 0x0102030405060708);
+        assert_eq!(be_u16(&BYTES, 0), 0xFF01);
+        assert_eq!(be_u24(&BYTES, 0), 0xFF0102);
+    }
+
+    #[test]
+    fn reads_little_endian_values() {
+        assert_eq!(le_u16(&BYTES, 1), 0x0201);
+        assert_eq!(le_u32(&BYTES, 1), 0x04030201);
+        assert_eq!(le_u64(&BYTES, 1), 0x0807060504030201);
+        assert_eq!(le_u16(&BYTES, 0), 0x01FF);
+    }
+
+    #[test]
+    fn reads_the_last_bytes() {
+        assert_eq!(be_u16(&BYTES, 7), 0x0708);
+        assert_eq!(be_u24(&BYTES, 6), 0x060708);
+        assert_eq!(le_u32(&BYTES, 5), 0x08070605);
+    }
+
+    #[test]
+    #[should_panic]
+    fn panics_reading_past_the_end() {
+        be_u32(&BYTES, 6);
+    }
 }
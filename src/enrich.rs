@@ -0,0 +1,145 @@
+use std::io::{BufRead, BufReader, Error, ErrorKind};
+use std::path::Path;
+use std::fs::File;
+use crate::endian::be_u32;
+use crate::ipv4::Ipv4Address;
+
+
+/// What is known about where an address is located.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct IpInfo {
+    /// ISO 3166 country code, e.g. `US`.
+    pub country: Option<String>,
+    /// Autonomous system number.
+    pub asn: Option<u32>,
+}
+
+impl std::fmt::Display for IpInfo {
+    /// Formats as e.g. `US, AS64500`, leaving out the unknown parts.
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match (&self.country, self.asn) {
+            (Some(country), Some(asn)) => write!(f, "{}, AS{}", country, asn),
+            (Some(country), None)      => write!(f, "{}", country),
+            (None, Some(asn))          => write!(f, "AS{}", asn),
+            (None, None)               => Ok(()),
+        }
+    }
+}
+
+
+/// Looks up extra information about addresses, e.g. from a `PrefixTable`.
+pub trait IpEnricher {
+    fn lookup(&self, ip: Ipv4Address) -> Option<IpInfo>;
+}
+
+
+/// Knows nothing about any address.
+pub struct NoEnricher;
+
+impl IpEnricher for NoEnricher {
+    fn lookup(&self, ip: Ipv4Address) -> Option<IpInfo> {
+        None
+    }
+}
+
+
+/// Maps address prefixes to their information, using the longest matching prefix.
+#[derive(Debug, Default)]
+pub struct PrefixTable {
+    /// Network, prefix length and information, sorted by descending prefix length.
+    prefixes: Vec<(u32, u8, IpInfo)>,
+}
+
+impl PrefixTable {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn insert(&mut self, network: Ipv4Address, length: u8, info: IpInfo) {
+        let mask = Self::mask(length);
+        let network = be_u32(network.bytes(), 0) & mask;
+        let index = self.prefixes.partition_point(|(_, other, _)| *other >= length);
+        self.prefixes.insert(index, (network, length, info));
+    }
+
+    /// Reads lines of the form `203.0.113.0/24,US,64500`. The country and ASN may be empty and
+    /// lines starting with `#` are ignored.
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let mut table = Self::new();
+        for (number, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+            let line = line?;
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') { continue }
+
+            let invalid = || Error::new(ErrorKind::Other, format!("Invalid prefix on line {} of {:?}: '{}'", number + 1, path, line));
+            let mut fields = line.split(',').map(str::trim);
+            let (network, length) = fields.next().and_then(|x| x.split_once('/')).ok_or_else(invalid)?;
+            let network: Ipv4Address = network.parse().map_err(|_| invalid())?;
+            let length: u8 = length.parse().ok().filter(|x| *x <= 32).ok_or_else(invalid)?;
+            let country = fields.next().filter(|x| !x.is_empty()).map(str::to_string);
+            let asn = match fields.next().filter(|x| !x.is_empty()) {
+                Some(asn) => Some(asn.trim_start_matches("AS").parse().map_err(|_| invalid())?),
+                None => None,
+            };
+            table.insert(network, length, IpInfo { country, asn });
+        }
+        Ok(table)
+    }
+
+    fn mask(length: u8) -> u32 {
+        if length == 0 { 0 } else { u32::MAX << (32 - length as u32) }
+    }
+}
+
+impl IpEnricher for PrefixTable {
+    fn lookup(&self, ip: Ipv4Address) -> Option<IpInfo> {
+        let address = be_u32(ip.bytes(), 0);
+        self.prefixes.iter()
+            .find(|(network, length, _)| address & Self::mask(*length) == *network)
+            .map(|(_, _, info)| info.clone())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::ip;
+
+    fn info(country: Option<&str>, asn: Option<u32>) -> IpInfo {
+        IpInfo { country: country.map(str::to_string), asn }
+    }
+
+    #[test]
+    fn formats_the_known_parts() {
+        assert_eq!(info(Some("US"), Some(64500)).to_string(), "US, AS64500");
+        assert_eq!(info(Some("SE"), None).to_string(), "SE");
+        assert_eq!(info(None, Some(64501)).to_string(), "AS64501");
+    }
+
+    #[test]
+    fn uses_the_longest_matching_prefix() {
+        let mut table = PrefixTable::new();
+        table.insert(ip(203, 0, 0, 0), 8, info(Some("AU"), None));
+        table.insert(ip(203, 0, 113, 0), 24, info(Some("US"), Some(64500)));
+
+        assert_eq!(table.lookup(ip(203, 0, 113, 7)), Some(info(Some("US"), Some(64500))));
+        assert_eq!(table.lookup(ip(203, 1, 2, 3)), Some(info(Some("AU"), None)));
+        assert_eq!(table.lookup(ip(198, 51, 100, 1)), None);
+    }
+
+    #[test]
+    fn loads_a_prefix_file() {
+        let directory = crate::testing::temp_dir("enrich");
+        let path = directory.join("prefixes.csv");
+        std::fs::write(&path, "# network,country,asn\n203.0.113.0/24,US,AS64500\n\n198.51.100.0/25,,64501\n").unwrap();
+        let table = PrefixTable::load(&path).unwrap();
+        std::fs::write(&path, "203.0.113.0/33,US,64500\n").unwrap();
+        let error = PrefixTable::load(&path).unwrap_err();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        assert_eq!(table.lookup(ip(203, 0, 113, 7)), Some(info(Some("US"), Some(64500))));
+        assert_eq!(table.lookup(ip(198, 51, 100, 1)), Some(info(None, Some(64501))));
+        assert!(error.to_string().contains("line 1"), "{}", error);
+    }
+}
@@ -0,0 +1,42 @@
+#[cfg(feature = "std")]
+use std::io::{Error, ErrorKind};
+
+
+/// Why a header couldn't be parsed. Unlike `std::io::Error` with a formatted message, building one doesn't
+/// allocate, which matters on lossy links where truncated packets are common. The message is only rendered
+/// when the error is displayed or converted to an `std::io::Error`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ParseError {
+    /// A length is below the minimum, e.g. `Tcp data too small, expected at least 20, got 12`.
+    TooSmall { layer: &'static str, field: &'static str, expected: usize, got: usize },
+    /// A length exceeds the available data, e.g. `Tcp header size too big, expected at most 20, got 24`.
+    TooBig { layer: &'static str, field: &'static str, expected: usize, got: usize },
+    /// More tunnels than `MAX_ENCAPSULATION_DEPTH`.
+    TooDeep { layer: &'static str, limit: usize },
+    /// A field has a value that isn't decoded, e.g. an unknown next header.
+    Unsupported { layer: &'static str, field: &'static str, value: u32 },
+    /// A field has a value that isn't allowed.
+    Invalid(&'static str),
+}
+
+impl core::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        match self {
+            ParseError::TooSmall { layer, field, expected, got } => write!(f, "{} {} too small, expected at least {}, got {}", layer, field, expected, got),
+            ParseError::TooBig { layer, field, expected, got }   => write!(f, "{} {} too big, expected at most {}, got {}", layer, field, expected, got),
+            ParseError::TooDeep { layer, limit }                 => write!(f, "{} nested too deep, at most {} tunnels are decoded", layer, limit),
+            ParseError::Unsupported { layer, field, value }      => write!(f, "{} {} {} not implemented", layer, field, value),
+            ParseError::Invalid(reason)                          => write!(f, "{}", reason),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for ParseError {}
+
+#[cfg(feature = "std")]
+impl From<ParseError> for Error {
+    fn from(error: ParseError) -> Self {
+        Error::new(ErrorKind::Other, error)
+    }
+}
@@ -3,16 +3,22 @@ https://standards.ieee.org/ieee/802.3/10422/
 */
 
 
-use crate::endian::be_to_fe;
+use alloc::format;
+use alloc::string::String;
+use alloc::vec::Vec;
+use crate::endian::{be_u16, le_u32};
 use crate::shared::*;
 use crate::ipv4;
+use crate::ipv6;
+use crate::pppoe;
+use crate::mpls;
+use crate::names;
+use crate::lldp;
+use crate::eapol;
+use crate::error::ParseError;
 
-use std::fmt::write;
-use std::io::{Error, ErrorKind};
-use std::io::ErrorKind::Other;
-use std::ops::{Index, Range};
-use byteorder::{LittleEndian, WriteBytesExt, ReadBytesExt};
-use crate::ethernet::Payload::Invalid;
+use core::fmt::write;
+use core::ops::{Index, Range};
 
 
 pub const ADDRESS_SIZE     : usize = 6;
@@ -21,8 +27,13 @@ pub const CRC_SIZE         : usize = 4;
 
 pub const HEADER_SIZE      : usize = ADDRESS_SIZE * 2 + ETHER_TYPE_SIZE;
 pub const MIN_TOTAL_SIZE   : usize = 6 + 6 + 2 + 4;
+/// Shorter payloads are padded to this size.
+pub const MIN_PAYLOAD_SIZE : usize = 64 - HEADER_SIZE - CRC_SIZE;
+/// Largest standard frame. Larger frames are still decoded, see `Ethernet::is_jumbo`.
 pub const MAX_TOTAL_SIZE   : usize = 1518;
-pub const MAX_PAYLOAD_SIZE : usize = MAX_TOTAL_SIZE - HEADER_SIZE;
+pub const MAX_PAYLOAD_SIZE : usize = MAX_TOTAL_SIZE - HEADER_SIZE - CRC_SIZE;
+/// Largest frame with the 9000 byte MTU common on data center links.
+pub const JUMBO_MAX_TOTAL_SIZE : usize = 9000 + HEADER_SIZE + CRC_SIZE;
 
 pub const MINIMUM_MAXIMUM_SEGMENT_SIZE: usize = 576;
 
@@ -88,38 +99,103 @@ pub const MINIMUM_MAXIMUM_SEGMENT_SIZE: usize = 576;
 #[derive(Debug)]
 pub enum Payload<'a> {
     IPv4(ipv4::IPv4<'a>),
-    Invalid
+    IPv6(ipv6::IPv6<'a>),
+    PPPoE(pppoe::PPPoE<'a>),
+    Mpls(mpls::Mpls<'a>),
+    Lldp(lldp::Lldp<'a>),
+    Eapol(eapol::Eapol<'a>),
+    Unknown(&'a [u8])
 }
 
 
 #[repr(u16)]
-#[derive(Debug)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum EtherType  {
-    Unknown = 0x0000,
-    IPv4 = 0x0008,   //  0x0800  Internet Protocol version 4 (IPv4)
-    ARP  = 0x0608,   //  0x0806  Address Resolution Protocol (ARP)
-    RARP = 0x3580,   //  0x8035  Reverse Address Resolution Protocol (RARP)
-    SLPP = 0x0281,   //  0x8102  Simple Loop Prevention Protocol (SLPP)
-    IPv6 = 0xDD86,   //  0x86DD  Internet Protocol Version 6 (IPv6)
+    Unknown        = 0x0000,
+    IPv4           = 0x0800,   //  Internet Protocol version 4 (IPv4)
+    ARP            = 0x0806,   //  Address Resolution Protocol (ARP)
+    WakeOnLan      = 0x0842,   //  Wake-on-LAN
+    RARP           = 0x8035,   //  Reverse Address Resolution Protocol (RARP)
+    VLAN           = 0x8100,   //  VLAN-tagged frame (IEEE 802.1Q)
+    SLPP           = 0x8102,   //  Simple Loop Prevention Protocol (SLPP)
+    IPv6           = 0x86DD,   //  Internet Protocol Version 6 (IPv6)
+    FlowControl    = 0x8808,   //  Ethernet flow control
+    MPLSUnicast    = 0x8847,   //  MPLS unicast
+    MPLSMulticast  = 0x8848,   //  MPLS multicast
+    PPPoEDiscovery = 0x8863,   //  PPPoE Discovery Stage
+    PPPoESession   = 0x8864,   //  PPPoE Session Stage
+    EAPOL          = 0x888E,   //  EAP over LAN (IEEE 802.1X)
+    QinQ           = 0x88A8,   //  Service VLAN tag identifier (S-Tag) on Q-in-Q tunnel
+    LLDP           = 0x88CC,   //  Link Layer Discovery Protocol (LLDP)
+    MACsec         = 0x88E5,   //  IEEE 802.1AE MAC security (MACsec)
+    PTP            = 0x88F7,   //  Precision Time Protocol (PTP) over IEEE 802.3 Ethernet
 }
 
 impl EtherType {
+    /// The EtherType of the big endian `code` on the wire, e.g. 0x0800 for IPv4.
     pub fn from_code(code: u16) -> Self {
         match code {
-            0x0008 => Self::IPv4,
-            0x0608 => Self::ARP,
-            0x3580 => Self::RARP,
-            0x0281 => Self::SLPP,
-            0xDD86 => Self::IPv6,
+            0x0800 => Self::IPv4,
+            0x0806 => Self::ARP,
+            0x0842 => Self::WakeOnLan,
+            0x8035 => Self::RARP,
+            0x8100 => Self::VLAN,
+            0x8102 => Self::SLPP,
+            0x86DD => Self::IPv6,
+            0x8808 => Self::FlowControl,
+            0x8847 => Self::MPLSUnicast,
+            0x8848 => Self::MPLSMulticast,
+            0x8863 => Self::PPPoEDiscovery,
+            0x8864 => Self::PPPoESession,
+            0x888E => Self::EAPOL,
+            0x88A8 => Self::QinQ,
+            0x88CC => Self::LLDP,
+            0x88E5 => Self::MACsec,
+            0x88F7 => Self::PTP,
             _ => Self::Unknown,
         }
     }
+
+    /// The EtherType as it's written on the wire, e.g. 0x0800 for IPv4. `Unknown` is 0.
+    pub fn as_u16(&self) -> u16 {
+        *self as u16
+    }
+
+    pub fn name(&self) -> &'static str {
+        names::ethertype_name(self.as_u16())
+    }
+}
+
+
+/// Whether the captured frame ends with the frame check sequence. Most capture setups strip it, but not all.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Fcs {
+    Absent,
+    Present,
+    /// Present if the last four bytes are a valid CRC of the rest of the frame.
+    Auto,
+}
+
+impl core::str::FromStr for Fcs {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "absent"  => Ok(Self::Absent),
+            "present" => Ok(Self::Present),
+            "auto"    => Ok(Self::Auto),
+            _ => Err(format!("Invalid frame check sequence setting '{}', expected absent, present or auto", text)),
+        }
+    }
 }
 
 
 #[derive(Clone)]
 pub struct Ethernet<'a> {
     data: &'a [u8],
+    has_fcs: bool,
+    /// Number of tunnels this frame is nested in, e.g. 1 inside VXLAN.
+    depth: usize,
 }
 
 
@@ -143,41 +219,174 @@ impl<'a> Ethernet<'a> {
     /// Values of 1500 and below mean that it is used to indicate the size of the payload in octets, while values
     /// of 1536 and above indicate that it is used as an EtherType, to indicate which protocol is encapsulated in
     /// the payload of the frame.
-    pub fn ether_type(&self)  -> EtherType { EtherType::from_code((&self.data[Self::ETHER_TYPE_OFFSET]).read_u16::<LittleEndian>().unwrap()) }
-    pub fn raw_payload(&self) -> &'a [u8]  { &self.data[Self::PAYLOAD_OFFSET..self.data.len()-Self::CRC_SIZE] }
+    pub fn ether_type(&self)  -> EtherType { EtherType::from_code(self.ether_type_raw()) }
+    /// The EtherType as it's written on the wire, including the ones `EtherType` doesn't know.
+    pub fn ether_type_raw(&self) -> u16 { be_u16(self.data, Self::ETHER_TYPE_OFFSET.start) }
+    /// The upper layer packet and any VLAN tags before it, without the padding or the frame check sequence.
+    pub fn raw_payload(&self) -> &'a [u8]  { &self.body()[..self.payload_size()] }
+
+    /// The bytes holding the field `name`, as named by its accessor, or `None` for unknown names.
+    pub fn field_bytes(&self, name: &str) -> Option<&'a [u8]> {
+        match name {
+            "destination" => Some(&self.data[Self::DEST_MAC_ADDRESS_OFFSET]),
+            "source"      => Some(&self.data[Self::SRC_MAC_ADDRESS_OFFSET]),
+            "ether_type"  => Some(&self.data[Self::ETHER_TYPE_OFFSET]),
+            "crc"         => self.has_fcs.then(|| &self.data[self.data.len() - Self::CRC_SIZE..]),
+            _ => None,
+        }
+    }
+
+    /// Size of the upper layer packet and any VLAN tags before it, which is `raw_payload().len()`. It excludes
+    /// the frame check sequence, and the padding of frames below the minimum size by taking the length from the
+    /// IP header when there is one.
+    pub fn payload_size(&self) -> usize {
+        let (ether_type, raw) = self.untag(self.body());
+        let tags = self.body().len() - raw.len();
+        let declared = match EtherType::from_code(ether_type) {
+            EtherType::IPv4 if raw.len() >= 4 && raw[0] >> 4 == 4 => Some(be_u16(raw, 2) as usize),
+            // A payload length of zero is a jumbogram, whose length is in an extension header.
+            EtherType::IPv6 if raw.len() >= 6 && be_u16(raw, 4) != 0 => Some(ipv6::IPv6::HEADER_SIZE + be_u16(raw, 4) as usize),
+            _ => None,
+        };
+        tags + declared.map_or(raw.len(), |x| x.min(raw.len()))
+    }
+
+    /// The bytes after the upper layer packet, normally the zeros padding a short frame to the minimum size.
+    pub fn padding(&self) -> &'a [u8] { &self.body()[self.payload_size()..] }
 
-    pub fn payload_size(&self) -> usize { self.data.len() - HEADER_SIZE }
+    /// The frame as it was parsed, including the frame check sequence if there is one.
+    pub fn to_bytes(&self) -> Vec<u8> { self.data.to_vec() }
 
-    pub fn payload(&self) -> Result<Payload<'a>, Error> {
-        match self.ether_type() {
+    /// Whether the payload is larger than a standard frame allows.
+    pub fn is_jumbo(&self) -> bool { self.raw_payload().len() > MAX_PAYLOAD_SIZE }
+
+    /// Checks the length fields of the IPv4 and UDP headers against each other and the captured bytes,
+    /// returning every disagreement. Mismatches point at crafted or corrupt packets.
+    pub fn validate_lengths(&self) -> Vec<ParseError> {
+        let mut errors = Vec::new();
+        let Ok(Payload::IPv4(ip)) = self.payload() else { return errors };
+        if let Err(error) = ip.validate_length() {
+            errors.push(error);
+        }
+        if let Ok(ipv4::Payload::Udp(udp)) = ip.payload() {
+            if let Err(error) = udp.validate_length(ip.declared_payload_size()) {
+                errors.push(error);
+            }
+        }
+        errors
+    }
+
+    /// Everything between the header and the frame check sequence, padding included.
+    fn body(&self) -> &'a [u8] { &self.data[Self::PAYLOAD_OFFSET..self.data.len()-self.fcs_size()] }
+
+    /// The EtherType and bytes after any 802.1Q or 802.1ad tags, which the payload is decoded from.
+    pub fn untagged(&self) -> (u16, &'a [u8]) {
+        self.untag(self.raw_payload())
+    }
+
+    /// Skips the VLAN tags at the start of `raw`, which follows the addresses of this frame.
+    fn untag(&self, raw: &'a [u8]) -> (u16, &'a [u8]) {
+        let (mut ether_type, mut raw) = (self.ether_type_raw(), raw);
+        while matches!(ether_type, 0x8100 | 0x88A8) && raw.len() >= 4 {
+            ether_type = be_u16(raw, 2);
+            raw = &raw[4..];
+        }
+        (ether_type, raw)
+    }
+
+    /// Decodes the payload by the EtherType inside the VLAN tags, if there are any.
+    pub fn payload(&self) -> Result<Payload<'a>, ParseError> {
+        let (ether_type, raw) = self.untagged();
+        let ether_type = EtherType::from_code(ether_type);
+        match ether_type {
             EtherType::IPv4 => {
-                let payload = ipv4::IPv4::from_bytes(self.raw_payload())?;
+                let payload = ipv4::IPv4::from_bytes_nested(raw, self.depth)?;
                 Ok(Payload::IPv4(payload))
             },
-            EtherType::ARP  => Ok(Invalid),
-            EtherType::RARP => Ok(Invalid),
-            EtherType::SLPP => Ok(Invalid),
-            EtherType::IPv6 => Ok(Invalid),
-            EtherType::Unknown => Ok(Invalid),
+            EtherType::IPv6 => {
+                let payload = ipv6::IPv6::from_bytes_nested(raw, self.depth)?;
+                Ok(Payload::IPv6(payload))
+            },
+            EtherType::PPPoEDiscovery | EtherType::PPPoESession => {
+                let payload = pppoe::PPPoE::from_bytes(raw, ether_type == EtherType::PPPoESession)?;
+                Ok(Payload::PPPoE(payload))
+            },
+            EtherType::MPLSUnicast | EtherType::MPLSMulticast => {
+                let payload = mpls::Mpls::from_bytes(raw)?;
+                Ok(Payload::Mpls(payload))
+            },
+            EtherType::LLDP => {
+                let payload = lldp::Lldp::from_bytes(raw)?;
+                Ok(Payload::Lldp(payload))
+            },
+            EtherType::EAPOL => {
+                let payload = eapol::Eapol::from_bytes(raw)?;
+                Ok(Payload::Eapol(payload))
+            },
+            _ => Ok(Payload::Unknown(self.raw_payload())),
+        }
+    }
+
+    /// The tag control information of an 802.1Q or 802.1ad tag right after the addresses, if there is one.
+    pub fn vlan_tag(&self) -> Option<u16> {
+        let raw = self.raw_payload();
+        match self.ether_type_raw() {
+            0x8100 | 0x88A8 if raw.len() >= 2 => Some(be_u16(raw, 0)),
+            _ => None,
         }
     }
 
-    pub fn crc(&self) -> u32 { (&self.data[self.data.len() - Self::CRC_SIZE..]).read_u32::<LittleEndian>().unwrap() }
+    pub fn has_fcs(&self) -> bool { self.has_fcs }
+    pub fn depth(&self) -> usize { self.depth }
+    fn fcs_size(&self) -> usize { if self.has_fcs { Self::CRC_SIZE } else { 0 } }
+
+    /// The frame check sequence, which is sent least significant byte first.
+    pub fn crc(&self) -> Option<u32> {
+        if !self.has_fcs { return None }
+        Some(le_u32(self.data, self.data.len() - Self::CRC_SIZE))
+    }
+
+    /// Whether the frame check sequence matches the CRC-32 of the rest of the frame, or `None` if there's no FCS.
+    pub fn verify_fcs(&self) -> Option<bool> {
+        let crc = self.crc()?;
+        Some(crc32(&self.data[..self.data.len() - Self::CRC_SIZE]) == crc)
+    }
 
-    pub fn from_bytes(data: &'a [u8]) -> Result<Self, Error> {
+    /// Parses a frame, detecting whether it ends with a frame check sequence.
+    pub fn from_bytes(data: &'a [u8]) -> Result<Self, ParseError> {
+        Self::from_bytes_with_fcs(data, Fcs::Auto)
+    }
+
+    pub fn from_bytes_with_fcs(data: &'a [u8], fcs: Fcs) -> Result<Self, ParseError> {
         if data.len() < HEADER_SIZE {
-            return Err(Error::new(Other, format!("Ethernet data too small, expected at least {}, got {}", HEADER_SIZE, data.len())))
+            return Err(ParseError::TooSmall { layer: "Ethernet", field: "data", expected: HEADER_SIZE, got: data.len() })
+        }
+
+        let has_fcs = match fcs {
+            Fcs::Absent  => false,
+            Fcs::Present => true,
+            Fcs::Auto    => data.len() >= HEADER_SIZE + CRC_SIZE && Self { data, has_fcs: true, depth: 0 }.verify_fcs() == Some(true),
+        };
+
+        if has_fcs && data.len() < HEADER_SIZE + CRC_SIZE {
+            return Err(ParseError::TooSmall { layer: "Ethernet", field: "data for a frame check sequence", expected: HEADER_SIZE + CRC_SIZE, got: data.len() })
         }
 
-        let me = Self { data };
+        let me = Self { data, has_fcs, depth: 0 };
         Ok(me)
     }
+
+    /// Parses a frame without frame check sequence that is encapsulated in `depth` tunnels, e.g. by VXLAN.
+    pub fn from_bytes_nested(data: &'a [u8], depth: usize) -> Result<Self, ParseError> {
+        let me = Self::from_bytes_with_fcs(data, Fcs::Absent)?;
+        Ok(Self { depth, ..me })
+    }
 }
 
 
 
-impl<'a> std::fmt::Debug for Ethernet<'a> {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+impl<'a> core::fmt::Debug for Ethernet<'a> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
         write!(f, "Ethernet\n")?;
         write!(f, "    Source:      {:?}\n", self.source())?;
         write!(f, "    Destination: {:?}\n", self.destination())?;
@@ -188,16 +397,206 @@ impl<'a> std::fmt::Debug for Ethernet<'a> {
     }
 }
 
+/// One line, e.g. `Ethernet 0:17:136:1:2:3 > 255:255:255:255:255:255 type=IPv4 len=60`.
+impl<'a> core::fmt::Display for Ethernet<'a> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "Ethernet {:?} > {:?} type={:?} len={}", self.source(), self.destination(), self.ether_type(), self.data.len())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::{Ipv4Builder, TcpBuilder};
+    use crate::testing::{self, ip};
+
+    /// A TCP segment in an Ethernet frame, and the frame with its frame check sequence appended.
+    fn frames() -> (Vec<u8>, Vec<u8>) {
+        let packet = Ipv4Builder::new(ip(10, 0, 0, 1), ip(10, 0, 0, 2)).with_tcp(&TcpBuilder::new(51000, 80)).build();
+        let frame = testing::ethernet(testing::ETHER_TYPE_IPV4, &packet);
+        let mut with_fcs = frame.clone();
+        with_fcs.extend_from_slice(&crc32(&frame).to_le_bytes());
+        (frame, with_fcs)
+    }
+
+    #[test]
+    fn strips_a_valid_fcs() {
+        let (frame, with_fcs) = frames();
+        for fcs in [Fcs::Auto, Fcs::Present] {
+            let packet = Ethernet::from_bytes_with_fcs(&with_fcs, fcs).unwrap();
+            assert!(packet.has_fcs());
+            assert_eq!(packet.raw_payload(), &frame[HEADER_SIZE..]);
+            assert_eq!(packet.verify_fcs(), Some(true));
+        }
+    }
 
+    #[test]
+    fn keeps_the_whole_payload_without_fcs() {
+        let (frame, _) = frames();
+        for fcs in [Fcs::Auto, Fcs::Absent] {
+            let packet = Ethernet::from_bytes_with_fcs(&frame, fcs).unwrap();
+            assert!(!packet.has_fcs());
+            assert_eq!(packet.raw_payload(), &frame[HEADER_SIZE..]);
+            assert_eq!(packet.crc(), None);
+            assert_eq!(packet.verify_fcs(), None);
+        }
+    }
 
+    #[test]
+    fn reports_a_corrupted_fcs_only_when_told_it_is_present() {
+        let (frame, mut with_fcs) = frames();
+        *with_fcs.last_mut().unwrap() ^= 0xFF;
 
+        // Guessing takes the bad CRC for payload.
+        assert!(!Ethernet::from_bytes_with_fcs(&with_fcs, Fcs::Auto).unwrap().has_fcs());
 
+        let packet = Ethernet::from_bytes_with_fcs(&with_fcs, Fcs::Present).unwrap();
+        assert_eq!(packet.verify_fcs(), Some(false));
+        assert_eq!(packet.raw_payload(), &frame[HEADER_SIZE..]);
+    }
 
+    #[test]
+    fn parses_the_fcs_setting() {
+        assert_eq!("present".parse(), Ok(Fcs::Present));
+        assert_eq!("absent".parse(), Ok(Fcs::Absent));
+        assert_eq!("auto".parse(), Ok(Fcs::Auto));
+        assert!("yes".parse::<Fcs>().is_err());
+    }
 
+    #[test]
+    fn names_the_common_ether_types() {
+        for (code, ether_type, name) in [
+            (0x0806, EtherType::ARP,         "ARP"),
+            (0x0842, EtherType::WakeOnLan,   "Wake-on-LAN"),
+            (0x8100, EtherType::VLAN,        "802.1Q VLAN"),
+            (0x88A8, EtherType::QinQ,        "802.1ad QinQ"),
+            (0x8847, EtherType::MPLSUnicast, "MPLS unicast"),
+            (0x8864, EtherType::PPPoESession, "PPPoE Session"),
+            (0x88CC, EtherType::LLDP,        "LLDP"),
+        ] {
+            assert_eq!(EtherType::from_code(code), ether_type);
+            assert_eq!(ether_type.as_u16(), code);
+            assert_eq!(ether_type.name(), name);
+        }
+    }
 
+    #[test]
+    fn keeps_unknown_ether_types_unknown() {
+        assert_eq!(EtherType::from_code(0x1234), EtherType::Unknown);
+        assert_eq!(EtherType::Unknown.name(), "Unknown");
+    }
 
+    #[test]
+    fn displays_one_line_and_debugs_the_tree() {
+        let (frame, _) = frames();
+        let packet = Ethernet::from_bytes(&frame).unwrap();
+        assert_eq!(format!("{}", packet), "Ethernet 2:0:0:0:0:10 > 2:0:0:0:0:11 type=IPv4 len=54");
+        let tree = format!("{:?}", packet);
+        assert!(tree.lines().count() > 4);
+        assert!(tree.contains("    Ether Type:  IPv4\n"));
+        assert!(tree.contains("Tcp"));
+    }
 
+    #[test]
+    fn decodes_a_jumbo_frame_in_full() {
+        let payload = alloc::vec![0x42; 9000 - 40];
+        let packet = Ipv4Builder::new(ip(10, 0, 0, 1), ip(10, 0, 0, 2)).with_tcp(&TcpBuilder::new(51000, 80).with_payload(&payload)).build();
+        let frame = testing::ethernet(testing::ETHER_TYPE_IPV4, &packet);
+        assert!(frame.len() <= JUMBO_MAX_TOTAL_SIZE);
+
+        let ethernet = Ethernet::from_bytes(&frame).unwrap();
+        assert!(ethernet.is_jumbo());
+        assert_eq!(ethernet.payload_size(), 9000);
+        let Ok(Payload::IPv4(ip)) = ethernet.payload() else { panic!("the jumbo frame isn't decoded as IPv4") };
+        assert_eq!(ip.total_length(), 9000);
+        let Ok(crate::ipv4::Payload::Tcp(tcp)) = ip.payload() else { panic!("the jumbo packet isn't decoded as TCP") };
+        assert_eq!(tcp.raw_payload(), payload);
+
+        let (standard, _) = frames();
+        assert!(!Ethernet::from_bytes(&standard).unwrap().is_jumbo());
+    }
 
+    #[test]
+    fn round_trips_through_to_bytes() {
+        let (frame, with_fcs) = frames();
+        assert_eq!(Ethernet::from_bytes(&frame).unwrap().to_bytes(), frame);
+        assert_eq!(Ethernet::from_bytes_with_fcs(&with_fcs, Fcs::Present).unwrap().to_bytes(), with_fcs);
+        let Ok(Payload::IPv4(ip)) = Ethernet::from_bytes(&frame).unwrap().payload() else { panic!("the frame isn't decoded as IPv4") };
+        assert_eq!(ip.to_bytes(), &frame[HEADER_SIZE..]);
+    }
 
+    #[test]
+    fn reports_each_length_that_disagrees_with_the_data() {
+        let datagram = testing::udp(53000, 53, b"query");
+        let packet = Ipv4Builder::new(ip(10, 0, 0, 1), ip(10, 0, 0, 2)).with_payload(crate::ipv4::Protocol::UDP, &datagram).build();
+        let frame = testing::ethernet(testing::ETHER_TYPE_IPV4, &packet);
+        assert_eq!(Ethernet::from_bytes(&frame).unwrap().validate_lengths(), []);
+
+        // The IP total length claims more than was captured, and the UDP length less than the IP payload.
+        let mut frame = frame;
+        frame[HEADER_SIZE + 2..HEADER_SIZE + 4].copy_from_slice(&(packet.len() as u16 + 100).to_be_bytes());
+        let errors = Ethernet::from_bytes(&frame).unwrap().validate_lengths();
+        assert_eq!(errors.len(), 2, "{:?}", errors);
+        assert!(matches!(errors[0], ParseError::TooBig { layer: "Ipv4", .. }));
+        assert!(matches!(errors[1], ParseError::Invalid(_)));
+    }
 
+    #[test]
+    fn excludes_the_padding_of_a_minimum_size_frame() {
+        let (frame, _) = frames();
+        let mut padded = frame.clone();
+        padded.resize(60, 0);
+
+        let ethernet = Ethernet::from_bytes_with_fcs(&padded, Fcs::Absent).unwrap();
+        assert_eq!(ethernet.raw_payload(), &frame[HEADER_SIZE..]);
+        assert_eq!(ethernet.payload_size(), 40);
+        assert_eq!(ethernet.padding(), [0; 6]);
+        // The IP layer only gets the packet, so the padding can't be mistaken for its payload.
+        let Ok(Payload::IPv4(ip)) = ethernet.payload() else { panic!("the padded frame isn't decoded as IPv4") };
+        assert_eq!(ip.to_bytes(), &frame[HEADER_SIZE..]);
+
+        let mut with_fcs = padded.clone();
+        with_fcs.extend_from_slice(&crc32(&padded).to_le_bytes());
+        let ethernet = Ethernet::from_bytes_with_fcs(&with_fcs, Fcs::Present).unwrap();
+        assert_eq!(ethernet.payload_size(), 40);
+        assert_eq!(ethernet.padding(), [0; 6]);
+
+        // Without a length to go by, the payload of an unknown EtherType is all of it but the frame check sequence.
+        with_fcs[12..14].copy_from_slice(&0x88B5u16.to_be_bytes());
+        let ethernet = Ethernet::from_bytes_with_fcs(&with_fcs, Fcs::Present).unwrap();
+        assert_eq!(ethernet.payload_size(), 46);
+        assert!(ethernet.padding().is_empty());
+        assert!(matches!(ethernet.payload(), Ok(Payload::Unknown(x)) if x == &padded[HEADER_SIZE..]));
+    }
 
+    #[test]
+    fn has_no_padding_in_a_full_size_frame() {
+        let payload = alloc::vec![0x42; 1500 - 40];
+        let packet = Ipv4Builder::new(ip(10, 0, 0, 1), ip(10, 0, 0, 2)).with_tcp(&TcpBuilder::new(51000, 80).with_payload(&payload)).build();
+        let frame = testing::ethernet(testing::ETHER_TYPE_IPV4, &packet);
+        assert_eq!(frame.len(), 1514);
+
+        let ethernet = Ethernet::from_bytes_with_fcs(&frame, Fcs::Absent).unwrap();
+        assert_eq!(ethernet.raw_payload().len(), 1500);
+        assert_eq!(ethernet.payload_size(), 1500);
+        assert!(ethernet.padding().is_empty());
+        assert!(!ethernet.is_jumbo());
+    }
+    #[test]
+    fn decodes_the_payload_inside_vlan_tags() {
+        let (frame, _) = frames();
+        // An 802.1ad service tag around an 802.1Q customer tag.
+        let mut tagged = frame[..12].to_vec();
+        tagged.extend_from_slice(&[0x88, 0xA8, 0x00, 0x0A, 0x81, 0x00, 0xA0, 0x64]);
+        tagged.extend_from_slice(&frame[12..]);
+        tagged.resize(14 + 8 + 46, 0);
+
+        let ethernet = Ethernet::from_bytes_with_fcs(&tagged, Fcs::Absent).unwrap();
+        assert_eq!(ethernet.vlan_tag(), Some(0x000A));
+        let Ok(Payload::IPv4(ip)) = ethernet.payload() else { panic!("the tagged payload isn't decoded as IPv4") };
+        assert_eq!(ip.total_length(), 40);
+        assert_eq!(ethernet.payload_size(), 8 + 40);
+        assert_eq!(ethernet.padding(), [0; 6]);
+    }
+}
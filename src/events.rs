@@ -0,0 +1,193 @@
+use alloc::{format, string::{String, ToString}, vec, vec::Vec};
+use crate::error::ParseError;
+use crate::ethernet::{self, Ethernet};
+use crate::ipv4::{self, IPv4};
+use crate::ipv6::{self, IPv6};
+use crate::tcp::Tcp;
+use crate::udp::Udp;
+
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum LayerKind {
+    Ethernet,
+    Ipv4,
+    Ipv6,
+    Tcp,
+    Udp,
+}
+
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum DecodeEvent<'a> {
+    /// A decoded header with its fields in wire order.
+    Layer { kind: LayerKind, fields: Vec<(&'static str, String)> },
+    /// The bytes after the last decoded header.
+    Payload(&'a [u8]),
+    /// The next header couldn't be parsed. This is the last event.
+    Error(ParseError),
+}
+
+
+/// What to decode on the next call to `next`.
+enum Next<'a> {
+    Ethernet(&'a [u8]),
+    FromEthernet(Ethernet<'a>),
+    FromIpv4(IPv4<'a>),
+    FromIpv6(IPv6<'a>),
+    Payload(&'a [u8]),
+    Done,
+}
+
+
+/// Decodes a frame one layer per call to `next`, outermost first, ending with its payload. Unlike a `Visitor`
+/// nothing is parsed before it's asked for, so a consumer can stop after e.g. the IP header and skip the rest.
+pub struct EventDecoder<'a> {
+    next: Next<'a>,
+}
+
+impl<'a> EventDecoder<'a> {
+    /// Decodes `frame` as Ethernet.
+    pub fn new(frame: &'a [u8]) -> Self {
+        Self { next: Next::Ethernet(frame) }
+    }
+
+    fn ethernet(frame: &Ethernet) -> DecodeEvent<'a> {
+        DecodeEvent::Layer { kind: LayerKind::Ethernet, fields: vec![
+            ("Source",      format!("{:?}", frame.source())),
+            ("Destination", format!("{:?}", frame.destination())),
+            ("Ether Type",  format!("{:#06x}", frame.ether_type_raw())),
+        ]}
+    }
+
+    fn ipv4(packet: &IPv4) -> DecodeEvent<'a> {
+        DecodeEvent::Layer { kind: LayerKind::Ipv4, fields: vec![
+            ("Header Length",       packet.header_length().to_string()),
+            ("Total Length",        packet.total_length().to_string()),
+            ("Identification",      packet.identification().to_string()),
+            ("Time To Live",        packet.time_to_live().to_string()),
+            ("Protocol",            packet.protocol_raw().to_string()),
+            ("Header Checksum",     format!("{:#06x}", packet.header_checksum())),
+            ("Source Address",      format!("{:?}", packet.source_address())),
+            ("Destination Address", format!("{:?}", packet.destination_address())),
+        ]}
+    }
+
+    fn ipv6(packet: &IPv6) -> DecodeEvent<'a> {
+        DecodeEvent::Layer { kind: LayerKind::Ipv6, fields: vec![
+            ("Traffic Class",       packet.traffic_class().to_string()),
+            ("Flow Label",          packet.flow_label().to_string()),
+            ("Payload Length",      packet.payload_length().to_string()),
+            ("Next Header",         packet.next_header().to_string()),
+            ("Hop Limit",           packet.hop_limit().to_string()),
+            ("Source Address",      format!("{:?}", packet.source_address())),
+            ("Destination Address", format!("{:?}", packet.destination_address())),
+        ]}
+    }
+
+    fn tcp(packet: &Tcp) -> DecodeEvent<'a> {
+        DecodeEvent::Layer { kind: LayerKind::Tcp, fields: vec![
+            ("Source Port",           packet.source_port().to_string()),
+            ("Destination Port",      packet.destination_port().to_string()),
+            ("Sequence Number",       packet.sequence_number().to_string()),
+            ("Acknowledgment Number", packet.acknowledgment_number().to_string()),
+            ("Flags",                 packet.flag_string()),
+            ("Window Size",           packet.window_size().to_string()),
+        ]}
+    }
+
+    fn udp(packet: &Udp) -> DecodeEvent<'a> {
+        DecodeEvent::Layer { kind: LayerKind::Udp, fields: vec![
+            ("Source Port",      packet.source_port().to_string()),
+            ("Destination Port", packet.destination_port().to_string()),
+            ("Length",           packet.length().to_string()),
+        ]}
+    }
+}
+
+impl<'a> Iterator for EventDecoder<'a> {
+    type Item = DecodeEvent<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (event, next) = match core::mem::replace(&mut self.next, Next::Done) {
+            Next::Ethernet(data) => match Ethernet::from_bytes(data) {
+                Ok(frame) => (Self::ethernet(&frame), Next::FromEthernet(frame)),
+                Err(error) => (DecodeEvent::Error(error), Next::Done),
+            },
+            Next::FromEthernet(frame) => match frame.payload() {
+                Ok(ethernet::Payload::IPv4(packet)) => (Self::ipv4(&packet), Next::FromIpv4(packet)),
+                Ok(ethernet::Payload::IPv6(packet)) => (Self::ipv6(&packet), Next::FromIpv6(packet)),
+                Ok(_) => (DecodeEvent::Payload(frame.raw_payload()), Next::Done),
+                Err(error) => (DecodeEvent::Error(error), Next::Done),
+            },
+            Next::FromIpv4(packet) => match packet.payload() {
+                Ok(ipv4::Payload::Tcp(tcp)) => (Self::tcp(&tcp), Next::Payload(tcp.raw_payload())),
+                Ok(ipv4::Payload::Udp(udp)) => (Self::udp(&udp), Next::Payload(udp.raw_payload())),
+                Ok(_) => (DecodeEvent::Payload(packet.raw_payload()), Next::Done),
+                Err(error) => (DecodeEvent::Error(error), Next::Done),
+            },
+            Next::FromIpv6(packet) => match packet.payload() {
+                Ok(ipv6::Payload::Tcp(tcp)) => (Self::tcp(&tcp), Next::Payload(tcp.raw_payload())),
+                Ok(ipv6::Payload::Udp(udp)) => (Self::udp(&udp), Next::Payload(udp.raw_payload())),
+                Ok(_) => (DecodeEvent::Payload(packet.raw_payload()), Next::Done),
+                Err(error) => (DecodeEvent::Error(error), Next::Done),
+            },
+            Next::Payload(data) => (DecodeEvent::Payload(data), Next::Done),
+            Next::Done => return None,
+        };
+        self.next = next;
+        Some(event)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::{TcpBuilder, TCP_ACK, TCP_PSH};
+    use crate::testing::{self, ip};
+
+    fn kinds(events: &[DecodeEvent]) -> Vec<Option<LayerKind>> {
+        events.iter().map(|x| match x { DecodeEvent::Layer { kind, .. } => Some(*kind), _ => None }).collect()
+    }
+
+    fn field<'e>(event: &'e DecodeEvent, name: &str) -> &'e str {
+        let DecodeEvent::Layer { fields, .. } = event else { panic!("{:?} isn't a layer", event) };
+        &fields.iter().find(|(x, _)| *x == name).unwrap().1
+    }
+
+    #[test]
+    fn yields_the_layers_of_a_tcp_segment_then_its_payload() {
+        let tcp = TcpBuilder::new(51000, 80).with_flags(TCP_ACK | TCP_PSH).with_sequence_number(1000).with_payload(b"GET /");
+        let frame = testing::tcp_frame(ip(10, 0, 0, 1), ip(10, 0, 0, 2), &tcp);
+        let events: Vec<_> = EventDecoder::new(&frame).collect();
+
+        assert_eq!(kinds(&events), [Some(LayerKind::Ethernet), Some(LayerKind::Ipv4), Some(LayerKind::Tcp), None]);
+        assert_eq!(field(&events[0], "Ether Type"), "0x0800");
+        assert_eq!(field(&events[1], "Source Address"), "10.0.0.1");
+        assert_eq!(field(&events[1], "Protocol"), "6");
+        assert_eq!(field(&events[2], "Destination Port"), "80");
+        assert_eq!(field(&events[2], "Sequence Number"), "1000");
+        assert_eq!(field(&events[2], "Flags"), "P.");
+        assert_eq!(events[3], DecodeEvent::Payload(b"GET /"));
+    }
+
+    #[test]
+    fn stops_where_the_consumer_stops() {
+        let frame = testing::udp_frame(ip(10, 0, 0, 1), 53000, ip(10, 0, 0, 2), 53, b"query");
+        let mut decoder = EventDecoder::new(&frame);
+        let events: Vec<_> = decoder.by_ref().take(2).collect();
+        assert_eq!(kinds(&events), [Some(LayerKind::Ethernet), Some(LayerKind::Ipv4)]);
+
+        assert_eq!(field(&decoder.next().unwrap(), "Length"), "13");
+        assert_eq!(decoder.next(), Some(DecodeEvent::Payload(b"query")));
+        assert_eq!(decoder.next(), None);
+    }
+
+    #[test]
+    fn ends_with_the_error_of_a_truncated_header() {
+        let frame = testing::tcp_frame(ip(10, 0, 0, 1), ip(10, 0, 0, 2), &TcpBuilder::new(51000, 80));
+        let events: Vec<_> = EventDecoder::new(&frame[..14 + 10]).collect();
+        assert_eq!(kinds(&events), [Some(LayerKind::Ethernet), None]);
+        assert!(matches!(events[1], DecodeEvent::Error(_)));
+    }
+}
@@ -0,0 +1,121 @@
+use crate::ipv4::{IPv4, Ipv4Address, Protocol};
+use crate::reassembly::StreamKey;
+use crate::tcp::Tcp;
+use crate::udp::Udp;
+
+
+/// The protocol and endpoints identifying one direction of a connection, usable as a map key.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub struct FiveTuple {
+    pub protocol: u8,
+    pub source: Ipv4Address,
+    pub source_port: u16,
+    pub destination: Ipv4Address,
+    pub destination_port: u16,
+}
+
+impl FiveTuple {
+    pub fn from_tcp(ip: &IPv4, tcp: &Tcp) -> Self {
+        Self {
+            protocol: Protocol::TCP.value(),
+            source: ip.source_address(),
+            source_port: tcp.source_port(),
+            destination: ip.destination_address(),
+            destination_port: tcp.destination_port(),
+        }
+    }
+
+    pub fn from_udp(ip: &IPv4, udp: &Udp) -> Self {
+        Self {
+            protocol: Protocol::UDP.value(),
+            source: ip.source_address(),
+            source_port: udp.source_port(),
+            destination: ip.destination_address(),
+            destination_port: udp.destination_port(),
+        }
+    }
+
+    /// The opposite direction of the same connection.
+    pub fn reversed(&self) -> Self {
+        Self {
+            protocol: self.protocol,
+            source: self.destination,
+            source_port: self.destination_port,
+            destination: self.source,
+            destination_port: self.source_port,
+        }
+    }
+
+    /// The same key for both directions of a connection, with the lower endpoint as the source.
+    pub fn normalized(&self) -> Self {
+        if (self.source, self.source_port) <= (self.destination, self.destination_port) { *self } else { self.reversed() }
+    }
+}
+
+impl From<StreamKey> for FiveTuple {
+    fn from(key: StreamKey) -> Self {
+        Self {
+            protocol: Protocol::TCP.value(),
+            source: key.source,
+            source_port: key.source_port,
+            destination: key.destination,
+            destination_port: key.destination_port,
+        }
+    }
+}
+
+impl std::fmt::Display for FiveTuple {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} {:?}:{} > {:?}:{}", crate::names::ip_protocol_name(self.protocol), self.source, self.source_port, self.destination, self.destination_port)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::{Ipv4Builder, TcpBuilder};
+    use crate::testing::{self, ip};
+
+    fn tcp_key(source: Ipv4Address, source_port: u16, destination: Ipv4Address, destination_port: u16) -> FiveTuple {
+        let packet = Ipv4Builder::new(source, destination).with_tcp(&TcpBuilder::new(source_port, destination_port)).build();
+        let ip = IPv4::from_bytes(&packet).unwrap();
+        let Ok(crate::ipv4::Payload::Tcp(tcp)) = ip.payload() else { panic!("not TCP") };
+        FiveTuple::from_tcp(&ip, &tcp)
+    }
+
+    #[test]
+    fn normalizes_both_directions_to_the_same_key() {
+        let forward = tcp_key(ip(10, 0, 0, 2), 51000, ip(10, 0, 0, 1), 80);
+        let reverse = tcp_key(ip(10, 0, 0, 1), 80, ip(10, 0, 0, 2), 51000);
+        assert_ne!(forward, reverse);
+        assert_eq!(forward.reversed(), reverse);
+        assert_eq!(forward.normalized(), reverse.normalized());
+        assert_eq!(forward.normalized().source, ip(10, 0, 0, 1));
+    }
+
+    #[test]
+    fn tells_different_flows_apart() {
+        let key = tcp_key(ip(10, 0, 0, 1), 51000, ip(10, 0, 0, 2), 80);
+        assert_ne!(key.normalized(), tcp_key(ip(10, 0, 0, 1), 51001, ip(10, 0, 0, 2), 80).normalized());
+        assert_ne!(key.normalized(), tcp_key(ip(10, 0, 0, 3), 51000, ip(10, 0, 0, 2), 80).normalized());
+
+        let packet = Ipv4Builder::new(ip(10, 0, 0, 1), ip(10, 0, 0, 2)).with_payload(Protocol::UDP, &testing::udp(51000, 80, &[])).build();
+        let ip = IPv4::from_bytes(&packet).unwrap();
+        let Ok(crate::ipv4::Payload::Udp(udp)) = ip.payload() else { panic!("not UDP") };
+        let udp = FiveTuple::from_udp(&ip, &udp);
+        assert_eq!((udp.source, udp.source_port, udp.destination, udp.destination_port), (key.source, key.source_port, key.destination, key.destination_port));
+        assert_ne!(udp, key);
+
+        let set: std::collections::HashSet<FiveTuple> = [key, key.reversed().normalized(), udp].into_iter().collect();
+        assert_eq!(set.len(), 2);
+    }
+
+    #[test]
+    fn converts_stream_keys() {
+        let key = tcp_key(ip(10, 0, 0, 1), 51000, ip(10, 0, 0, 2), 80);
+        let stream = StreamKey { source: ip(10, 0, 0, 1), source_port: 51000, destination: ip(10, 0, 0, 2), destination_port: 80 };
+        assert_eq!(FiveTuple::from(stream), key);
+        assert_eq!(key.to_string(), "TCP 10.0.0.1:51000 > 10.0.0.2:80");
+    }
+}
@@ -0,0 +1,377 @@
+/*
+https://www.rfc-editor.org/rfc/rfc7011
+https://www.iana.org/assignments/ipfix/ipfix.xhtml
+*/
+
+use std::io::{Error, Write};
+use std::str::FromStr;
+use std::time::Duration;
+use crate::flow::FiveTuple;
+use crate::ipv4::{self, IPv4};
+use crate::limits::{FlowTable, ResourceLimits};
+use crate::time_window;
+use crate::visitor::{Context, Visitor};
+
+
+/// How exported flows are written.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub enum FlowFormat {
+    /// One line per flow after a header line.
+    #[default]
+    Csv,
+    /// IPFIX messages as a collector receives them, with the template in the first message.
+    Ipfix,
+}
+
+impl FromStr for FlowFormat {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "csv"   => Ok(Self::Csv),
+            "ipfix" => Ok(Self::Ipfix),
+            _ => Err(format!("Unknown flow format '{}', expected csv or ipfix", text)),
+        }
+    }
+}
+
+
+/// The traffic of one direction of a connection, as a NetFlow or IPFIX probe reports it.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct FlowRecord {
+    pub key: FiveTuple,
+    /// Capture time of the first and last packet, since the epoch.
+    pub start: Duration,
+    pub end: Duration,
+    pub packets: u64,
+    /// Bytes of the IP packets, headers included.
+    pub bytes: u64,
+    /// The TCP flags of all segments OR'ed together, 0 for other protocols.
+    pub tcp_flags: u8,
+}
+
+
+/// Accumulates flow records of IPv4 traffic. A flow ends when it's idle for `flow_idle_timeout` or evicted to
+/// stay within `max_flows`, and the remaining flows end at shutdown.
+#[derive(Debug, Default)]
+pub struct FlowTracker {
+    flows: FlowTable<FiveTuple, FlowRecord>,
+}
+
+impl FlowTracker {
+    pub fn new(limits: ResourceLimits) -> Self {
+        Self { flows: FlowTable::new(limits) }
+    }
+
+    /// Records a packet of `bytes` captured at `now`, returning the flows that ended before it.
+    pub fn push(&mut self, key: FiveTuple, now: Duration, bytes: u64, tcp_flags: u8) -> Vec<FlowRecord> {
+        let mut ended: Vec<FlowRecord> = self.flows.expire(now).into_iter().map(|(_, flow)| flow).collect();
+        if self.flows.get(&key).is_none() {
+            while self.flows.len() >= self.flows.limits().max_flows.max(1) {
+                let Some((_, flow)) = self.flows.evict_oldest() else { break };
+                ended.push(flow);
+            }
+        }
+
+        let flow = self.flows.get_or_insert_with(key, now, || FlowRecord {
+            key, start: now, end: now, packets: 0, bytes: 0, tcp_flags: 0
+        });
+        flow.start = flow.start.min(now);
+        flow.end = flow.end.max(now);
+        flow.packets += 1;
+        flow.bytes += bytes;
+        flow.tcp_flags |= tcp_flags;
+        ended
+    }
+
+    /// Ends all flows, least recently used first.
+    pub fn finish(&mut self) -> Vec<FlowRecord> {
+        self.flows.drain().into_iter().map(|(_, flow)| flow).collect()
+    }
+}
+
+
+/// Writes flow records in a `FlowFormat`.
+pub struct FlowWriter<W: Write> {
+    writer: W,
+    format: FlowFormat,
+    /// Records written so far, the sequence number of the next IPFIX message.
+    sequence: u32,
+    started: bool,
+}
+
+impl<W: Write> FlowWriter<W> {
+    pub const CSV_HEADER: &'static str = "protocol,source,source_port,destination,destination_port,start,end,packets,bytes,tcp_flags";
+
+    pub const IPFIX_VERSION: u16 = 10;
+    pub const TEMPLATE_SET_ID: u16 = 2;
+    pub const TEMPLATE_ID: u16 = 256;
+    /// The information elements of a record as (id, size), in the order they're written.
+    pub const TEMPLATE: [(u16, u16); 10] = [
+        (8, 4),   // sourceIPv4Address
+        (12, 4),  // destinationIPv4Address
+        (7, 2),   // sourceTransportPort
+        (11, 2),  // destinationTransportPort
+        (4, 1),   // protocolIdentifier
+        (6, 2),   // tcpControlBits
+        (152, 8), // flowStartMilliseconds
+        (153, 8), // flowEndMilliseconds
+        (2, 8),   // packetDeltaCount
+        (1, 8),   // octetDeltaCount
+    ];
+    /// Records per message, keeping messages below their 64 KiB limit.
+    pub const MAX_RECORDS_PER_MESSAGE: usize = 1000;
+
+    pub fn new(writer: W, format: FlowFormat) -> Self {
+        Self { writer, format, sequence: 0, started: false }
+    }
+
+    /// Writes `records`, exported at capture time `now`. The CSV header and the IPFIX template are written
+    /// with the first call even if there are no records.
+    pub fn write(&mut self, records: &[FlowRecord], now: Duration) -> Result<(), Error> {
+        if records.is_empty() && self.started { return Ok(()) }
+        match self.format {
+            FlowFormat::Csv => {
+                if !self.started { writeln!(self.writer, "{}", Self::CSV_HEADER)? }
+                for record in records {
+                    self.write_csv(record)?;
+                }
+            },
+            FlowFormat::Ipfix => {
+                let mut template = !self.started;
+                if records.is_empty() { self.write_ipfix(&[], now, template)? }
+                for chunk in records.chunks(Self::MAX_RECORDS_PER_MESSAGE) {
+                    self.write_ipfix(chunk, now, template)?;
+                    template = false;
+                }
+            },
+        }
+        self.started = true;
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<(), Error> {
+        self.writer.flush()
+    }
+
+    fn write_csv(&mut self, record: &FlowRecord) -> Result<(), Error> {
+        let key = &record.key;
+        writeln!(self.writer, "{},{:?},{},{:?},{},{}.{:06},{}.{:06},{},{},{}",
+            key.protocol, key.source, key.source_port, key.destination, key.destination_port,
+            record.start.as_secs(), record.start.subsec_micros(), record.end.as_secs(), record.end.subsec_micros(),
+            record.packets, record.bytes, record.tcp_flags)
+    }
+
+    fn write_ipfix(&mut self, records: &[FlowRecord], now: Duration, template: bool) -> Result<(), Error> {
+        let mut message = Vec::new();
+        message.extend_from_slice(&Self::IPFIX_VERSION.to_be_bytes());
+        message.extend_from_slice(&[0, 0]);
+        message.extend_from_slice(&(now.as_secs() as u32).to_be_bytes());
+        message.extend_from_slice(&self.sequence.to_be_bytes());
+        // Observation domain.
+        message.extend_from_slice(&0u32.to_be_bytes());
+
+        if template {
+            let length = 4 + 4 + 4 * Self::TEMPLATE.len();
+            message.extend_from_slice(&Self::TEMPLATE_SET_ID.to_be_bytes());
+            message.extend_from_slice(&(length as u16).to_be_bytes());
+            message.extend_from_slice(&Self::TEMPLATE_ID.to_be_bytes());
+            message.extend_from_slice(&(Self::TEMPLATE.len() as u16).to_be_bytes());
+            for (id, size) in Self::TEMPLATE {
+                message.extend_from_slice(&id.to_be_bytes());
+                message.extend_from_slice(&size.to_be_bytes());
+            }
+        }
+
+        if !records.is_empty() {
+            let record_size: u16 = Self::TEMPLATE.iter().map(|(_, size)| size).sum();
+            let length = 4 + records.len() * record_size as usize;
+            message.extend_from_slice(&Self::TEMPLATE_ID.to_be_bytes());
+            message.extend_from_slice(&(length as u16).to_be_bytes());
+            for record in records {
+                message.extend_from_slice(record.key.source.bytes());
+                message.extend_from_slice(record.key.destination.bytes());
+                message.extend_from_slice(&record.key.source_port.to_be_bytes());
+                message.extend_from_slice(&record.key.destination_port.to_be_bytes());
+                message.push(record.key.protocol);
+                message.extend_from_slice(&(record.tcp_flags as u16).to_be_bytes());
+                message.extend_from_slice(&(record.start.as_millis() as u64).to_be_bytes());
+                message.extend_from_slice(&(record.end.as_millis() as u64).to_be_bytes());
+                message.extend_from_slice(&record.packets.to_be_bytes());
+                message.extend_from_slice(&record.bytes.to_be_bytes());
+            }
+        }
+
+        let length = message.len() as u16;
+        message[2..4].copy_from_slice(&length.to_be_bytes());
+        self.sequence = self.sequence.wrapping_add(records.len() as u32);
+        self.writer.write_all(&message)
+    }
+}
+
+
+/// Exports a flow record for each direction of each IPv4 connection as the flow ends, turning the analyser into
+/// a flow probe.
+pub struct FlowExportVisitor<W: Write> {
+    tracker: FlowTracker,
+    writer: FlowWriter<W>,
+    /// Capture time of the latest packet.
+    now: Duration,
+    exported: usize,
+}
+
+impl<W: Write> FlowExportVisitor<W> {
+    pub fn new(writer: FlowWriter<W>, limits: ResourceLimits) -> Self {
+        Self { tracker: FlowTracker::new(limits), writer, now: Duration::ZERO, exported: 0 }
+    }
+
+    fn export(&mut self, records: &[FlowRecord]) -> Result<(), Error> {
+        self.exported += records.len();
+        self.writer.write(records, self.now)
+    }
+}
+
+
+impl<'a, W: Write> Visitor<'a, ()> for FlowExportVisitor<W> {
+    fn visit_ipv4(&mut self, packet: &IPv4<'a>, context: &Context<'a>) -> Result<(), Error> {
+        let now = context.header.map(time_window::timestamp).unwrap_or(self.now);
+        self.now = self.now.max(now);
+
+        // Later fragments don't hold the ports, so they count towards a flow without them.
+        let payload = if packet.fragment_offset() == 0 { packet.payload().ok() } else { None };
+        let (key, tcp_flags) = match payload {
+            Some(ipv4::Payload::Tcp(tcp)) => (FiveTuple::from_tcp(packet, &tcp), tcp.flags()),
+            Some(ipv4::Payload::Udp(udp)) => (FiveTuple::from_udp(packet, &udp), 0),
+            _ => (FiveTuple {
+                protocol: packet.protocol_raw(),
+                source: packet.source_address(),
+                source_port: 0,
+                destination: packet.destination_address(),
+                destination_port: 0,
+            }, 0),
+        };
+
+        let ended = self.tracker.push(key, now, packet.total_length() as u64, tcp_flags);
+        if !ended.is_empty() {
+            self.export(&ended)?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        let ended = self.tracker.finish();
+        self.export(&ended)?;
+        self.writer.flush()?;
+        println!("Exported {} flows", self.exported);
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::{TcpBuilder, TCP_ACK, TCP_FIN, TCP_SYN};
+    use crate::endian::{be_u16, be_u32};
+    use crate::raw::visit_frame;
+    use crate::testing::{self, ip};
+    use crate::tree::SharedBuffer;
+
+    fn key(source_port: u16) -> FiveTuple {
+        FiveTuple { protocol: 6, source: ip(10, 0, 0, 1), source_port, destination: ip(10, 0, 0, 2), destination_port: 80 }
+    }
+
+    fn limits(max_flows: usize) -> ResourceLimits {
+        ResourceLimits { max_flows, flow_idle_timeout: Duration::from_secs(30), ..ResourceLimits::default() }
+    }
+
+    #[test]
+    fn exports_a_flow_when_it_expires() {
+        let mut tracker = FlowTracker::new(limits(10));
+        assert_eq!(tracker.push(key(51000), Duration::from_secs(1), 60, TCP_SYN), vec![]);
+        assert_eq!(tracker.push(key(51000), Duration::from_secs(2), 1500, TCP_ACK), vec![]);
+        assert_eq!(tracker.push(key(51000), Duration::from_secs(3), 40, TCP_FIN | TCP_ACK), vec![]);
+
+        let ended = tracker.push(key(51001), Duration::from_secs(40), 60, TCP_SYN);
+        assert_eq!(ended, vec![FlowRecord {
+            key: key(51000),
+            start: Duration::from_secs(1),
+            end: Duration::from_secs(3),
+            packets: 3,
+            bytes: 1600,
+            tcp_flags: TCP_SYN | TCP_ACK | TCP_FIN,
+        }]);
+        assert_eq!(tracker.finish().iter().map(|x| x.key).collect::<Vec<_>>(), vec![key(51001)]);
+    }
+
+    #[test]
+    fn exports_the_flow_evicted_beyond_max_flows() {
+        let mut tracker = FlowTracker::new(limits(1));
+        assert_eq!(tracker.push(key(51000), Duration::ZERO, 60, TCP_SYN), vec![]);
+        let ended = tracker.push(key(51001), Duration::ZERO, 60, TCP_SYN);
+        assert_eq!(ended.iter().map(|x| (x.key, x.packets)).collect::<Vec<_>>(), vec![(key(51000), 1)]);
+    }
+
+    #[test]
+    fn writes_csv_records_after_a_header() {
+        let record = FlowRecord {
+            key: key(51000), start: Duration::from_micros(1_500_000), end: Duration::from_secs(3), packets: 3, bytes: 1600, tcp_flags: 0x13,
+        };
+        let mut writer = FlowWriter::new(Vec::new(), FlowFormat::Csv);
+        writer.write(&[record], Duration::from_secs(3)).unwrap();
+        writer.write(&[], Duration::from_secs(4)).unwrap();
+        assert_eq!(String::from_utf8(writer.writer).unwrap(), format!("{}\n6,10.0.0.1,51000,10.0.0.2,80,1.500000,3.000000,3,1600,19\n", FlowWriter::<Vec<u8>>::CSV_HEADER));
+    }
+
+    #[test]
+    fn writes_ipfix_messages_with_the_template_first() {
+        let record = FlowRecord {
+            key: key(51000), start: Duration::from_millis(1500), end: Duration::from_secs(3), packets: 3, bytes: 1600, tcp_flags: 0x13,
+        };
+        let mut writer = FlowWriter::new(Vec::new(), FlowFormat::Ipfix);
+        writer.write(&[record], Duration::from_secs(3)).unwrap();
+        let first = writer.writer.len();
+        writer.write(&[record], Duration::from_secs(4)).unwrap();
+        let data = writer.writer;
+        let u16_at = |i: usize| be_u16(&data, i);
+        let u32_at = |i: usize| be_u32(&data, i);
+
+        // Message header, the template set of 10 fields and a data set of one 47 byte record.
+        assert_eq!((u16_at(0), u16_at(2) as usize, u32_at(4), u32_at(8)), (10, first, 3, 0));
+        assert_eq!((u16_at(16), u16_at(18), u16_at(20), u16_at(22)), (2, 48, 256, 10));
+        assert_eq!((u16_at(64), u16_at(66)), (256, 51));
+        assert_eq!(&data[68..72], &[10, 0, 0, 1]);
+        assert_eq!(&data[72..76], &[10, 0, 0, 2]);
+        assert_eq!((u16_at(76), u16_at(78), data[80], u16_at(81)), (51000, 80, 6, 0x13));
+        assert_eq!(first, 64 + 51);
+
+        // The second message has no template and counts the record before it.
+        assert_eq!((u16_at(first + 2), u32_at(first + 4), u32_at(first + 8)), (16 + 51, 4, 1));
+        assert_eq!(u16_at(first + 16), 256);
+    }
+
+    #[test]
+    fn exports_both_directions_of_a_connection_at_shutdown() {
+        let buffer = SharedBuffer::default();
+        let mut visitor = FlowExportVisitor::new(FlowWriter::new(buffer.clone(), FlowFormat::Csv), limits(10));
+        let request = testing::tcp_frame(ip(10, 0, 0, 1), ip(10, 0, 0, 2), &TcpBuilder::new(51000, 80).with_flags(TCP_SYN));
+        let reply = testing::tcp_frame(ip(10, 0, 0, 2), ip(10, 0, 0, 1), &TcpBuilder::new(80, 51000).with_flags(TCP_SYN | TCP_ACK));
+        visit_frame(&mut visitor, &request, Duration::from_secs(1)).unwrap();
+        visit_frame(&mut visitor, &reply, Duration::from_secs(2)).unwrap();
+        visitor.finish().unwrap();
+
+        let text = String::from_utf8(buffer.take()).unwrap();
+        let lines: Vec<_> = text.lines().skip(1).collect();
+        assert_eq!(lines, [
+            "6,10.0.0.1,51000,10.0.0.2,80,1.000000,1.000000,1,40,2",
+            "6,10.0.0.2,80,10.0.0.1,51000,2.000000,2.000000,1,40,18",
+        ]);
+    }
+
+    #[test]
+    fn parses_flow_formats() {
+        assert_eq!("csv".parse::<FlowFormat>(), Ok(FlowFormat::Csv));
+        assert_eq!("ipfix".parse::<FlowFormat>(), Ok(FlowFormat::Ipfix));
+        assert!("netflow".parse::<FlowFormat>().is_err());
+    }
+}
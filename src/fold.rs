@@ -0,0 +1,95 @@
+use std::io::ErrorKind;
+use std::path::Path;
+use pcap::{Capture, Packet};
+use crate::error::ParseError;
+use crate::ethernet::Ethernet;
+use crate::source::{CaptureSource, PacketSource};
+
+
+/// Decodes each frame of `source` and threads `init` through `f`, returning the final value. This is the
+/// shortcut for aggregations that don't need the `Visitor` trait, e.g. summing bytes or counting protocols.
+/// A live capture is folded through `CaptureSource::new(capture)`.
+///
+/// Stops after `limit` packets if given, or when the source ends or fails. Read timeouts of a live capture
+/// are skipped.
+pub fn fold_packets<A, F>(source: &mut dyn PacketSource, limit: Option<usize>, init: A, mut f: F) -> A
+    where F: FnMut(A, &Packet, Result<Ethernet, ParseError>) -> A
+{
+    let mut accumulator = init;
+    let mut count = 0;
+
+    while limit.is_none_or(|limit| count < limit) {
+        let packet = match source.next() {
+            Some(Ok(packet)) => packet,
+            Some(Err(error)) if error.kind() == ErrorKind::TimedOut => continue,
+            Some(Err(_)) | None => break,
+        };
+        count += 1;
+
+        let packet = packet.as_packet();
+        let frame = Ethernet::from_bytes(packet.data);
+        accumulator = f(accumulator, &packet, frame);
+    }
+
+    accumulator
+}
+
+
+/// `fold_packets` over every packet of the capture file at `path`.
+pub fn fold_file<A, F>(path: impl AsRef<Path>, init: A, f: F) -> Result<A, pcap::Error>
+    where F: FnMut(A, &Packet, Result<Ethernet, ParseError>) -> A
+{
+    let mut source = CaptureSource::new(Capture::from_file(path)?);
+    Ok(fold_packets(&mut source, None, init, f))
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::BTreeMap;
+    use std::time::Duration;
+    use crate::builder::TcpBuilder;
+    use crate::ethernet::Payload;
+    use crate::ipv4;
+    use crate::source::MockSource;
+    use crate::testing::{self, ip};
+
+    fn source() -> MockSource {
+        let tcp = testing::tcp_frame(ip(10, 0, 0, 1), ip(10, 0, 0, 2), &TcpBuilder::new(51000, 80).with_payload(&[0; 100]));
+        let udp = testing::udp_frame(ip(10, 0, 0, 1), 53000, ip(10, 0, 0, 2), 53, b"query");
+        let arp = testing::ethernet(0x0806, &[0; 28]);
+        MockSource::new(vec![(Duration::ZERO, tcp.clone()), (Duration::ZERO, udp), (Duration::ZERO, tcp), (Duration::ZERO, arp), (Duration::ZERO, vec![0; 6])])
+    }
+
+    /// The innermost protocol a frame is counted under.
+    fn protocol(frame: Result<Ethernet, ParseError>) -> &'static str {
+        match frame.as_ref().map(|x| x.payload()) {
+            Ok(Ok(Payload::IPv4(ip))) => match ip.payload() {
+                Ok(ipv4::Payload::Tcp(_)) => "tcp",
+                Ok(ipv4::Payload::Udp(_)) => "udp",
+                _ => "ipv4",
+            },
+            Ok(_) => "other",
+            Err(_) => "invalid",
+        }
+    }
+
+    #[test]
+    fn folds_into_a_byte_count_and_a_protocol_histogram() {
+        let bytes = fold_packets(&mut source(), None, 0, |total, packet, _| total + packet.header.len as usize);
+        assert_eq!(bytes, 154 + 47 + 154 + 42 + 6);
+
+        let histogram = fold_packets(&mut source(), None, BTreeMap::new(), |mut histogram, _, frame| {
+            *histogram.entry(protocol(frame)).or_insert(0) += 1;
+            histogram
+        });
+        assert_eq!(histogram, BTreeMap::from([("invalid", 1), ("other", 1), ("tcp", 2), ("udp", 1)]));
+    }
+
+    #[test]
+    fn stops_after_the_limit() {
+        assert_eq!(fold_packets(&mut source(), Some(2), 0, |count, _, _| count + 1), 2);
+        assert_eq!(fold_packets(&mut MockSource::default(), None, 7, |count, _, _| count + 1), 7);
+    }
+}
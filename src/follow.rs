@@ -0,0 +1,162 @@
+use std::io::Error;
+use std::time::Duration;
+use pcap::Packet;
+use crate::ipv4::IPv4;
+use crate::limits::ResourceLimits;
+use crate::radiotap::Radiotap;
+use crate::sll::Sll2;
+use crate::tcp::Tcp;
+use crate::reassembly::{OverlapPolicy, Reassembler, StreamKey};
+use crate::time_window;
+use crate::visitor::{Context, Visitor};
+
+
+/// Prints the reassembled payload of one TCP connection as text, like Wireshark's "Follow TCP Stream".
+pub struct FollowVisitor {
+    selection: StreamKey,
+    separator: String,
+    reassembler: Reassembler,
+    /// Direction of the data printed last.
+    direction: Option<StreamKey>,
+}
+
+impl FollowVisitor {
+    pub const DEFAULT_SEPARATOR: &'static str = "=====";
+
+    /// `selection` matches the connection in either direction.
+    pub fn new(selection: StreamKey, separator: String) -> Self {
+        Self { selection, separator, reassembler: Reassembler::new(), direction: None }
+    }
+
+    pub fn with_overlap_policy(self, policy: OverlapPolicy) -> Self {
+        Self { reassembler: self.reassembler.with_policy(policy), ..self }
+    }
+
+    pub fn with_limits(self, limits: ResourceLimits) -> Self {
+        Self { reassembler: self.reassembler.with_limits(limits), ..self }
+    }
+
+    /// Renders printable ASCII and whitespace as is and everything else as `\xNN`.
+    pub fn escape(data: &[u8]) -> String {
+        let mut text = String::with_capacity(data.len());
+        for byte in data {
+            match *byte {
+                b'\n' | b'\r' | b'\t' | 0x20..=0x7E => text.push(*byte as char),
+                _ => text += &format!("\\x{:02x}", byte),
+            }
+        }
+        text
+    }
+
+    /// Returns the text for a segment captured at `now`, prefixed with a separator line whenever the direction
+    /// changes.
+    pub fn follow(&mut self, ip: &IPv4, tcp: &Tcp, now: Duration) -> Option<String> {
+        let key = StreamKey::new(ip, tcp);
+        if key != self.selection && key != self.selection.reversed() {
+            return None;
+        }
+
+        let conflicts = self.reassembler.conflicts();
+        let data = self.reassembler.push(ip, tcp, now);
+        if self.reassembler.conflicts() > conflicts {
+            eprintln!("[WARNING] {}: segment at sequence number {} overlaps earlier data with different bytes", key, tcp.sequence_number());
+        }
+        if data.is_empty() {
+            return None;
+        }
+
+        let mut text = String::new();
+        if self.direction != Some(key) {
+            if self.direction.is_some() { text.push('\n') }
+            text += &format!("{} {} {}\n", self.separator, key, self.separator);
+            self.direction = Some(key);
+        }
+        text += &Self::escape(&data);
+        Some(text)
+    }
+}
+
+
+impl<'a> Visitor<'a, ()> for FollowVisitor {
+    fn visit_packet(&mut self, packet: &'a Packet) -> Result<(), Error> {
+        // Packets that fail to decode can't belong to the followed stream, so they're not worth reporting.
+        let _ = self.visit_packet_payload(packet);
+        Ok(())
+    }
+
+    fn visit_radiotap_packet(&mut self, packet: &'a Packet) -> Result<(), Error> {
+        if let Ok(radiotap) = Radiotap::from_bytes(packet.data) {
+            let _ = self.visit_radiotap(&radiotap, &Context::new(packet.header));
+        }
+        Ok(())
+    }
+
+    fn visit_sll2_packet(&mut self, packet: &'a Packet) -> Result<(), Error> {
+        if let Ok(sll2) = Sll2::from_bytes(packet.data) {
+            let _ = self.visit_sll2(&sll2, &Context::new(packet.header));
+        }
+        Ok(())
+    }
+
+    fn visit_tcp(&mut self, packet: &Tcp<'a>, context: &Context<'a>) -> Result<(), Error> {
+        if let Some(ip) = &context.ipv4 {
+            let now = context.header.map(time_window::timestamp).unwrap_or_default();
+            if let Some(text) = self.follow(ip, packet, now) {
+                print!("{}", text);
+            }
+        }
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::{Ipv4Builder, TcpBuilder, TCP_ACK, TCP_PSH, TCP_SYN};
+    use crate::ipv4::{self, Ipv4Address};
+    use crate::testing::ip;
+
+    fn segment(follow: &mut FollowVisitor, source: (Ipv4Address, u16), destination: (Ipv4Address, u16), flags: u8, sequence: u32, payload: &[u8]) -> Option<String> {
+        let tcp = TcpBuilder::new(source.1, destination.1).with_flags(flags).with_sequence_number(sequence).with_payload(payload);
+        let data = Ipv4Builder::new(source.0, destination.0).with_tcp(&tcp).build();
+        let packet = IPv4::from_bytes(&data).unwrap();
+        let Ok(ipv4::Payload::Tcp(tcp)) = packet.payload() else { panic!("not a TCP segment") };
+        follow.follow(&packet, &tcp, Duration::ZERO)
+    }
+
+    #[test]
+    fn follows_both_directions_of_an_http_exchange() {
+        let (client, server) = ((ip(10, 0, 0, 1), 51000), (ip(10, 0, 0, 2), 80));
+        // Selected from the server's side, which still matches the client's segments.
+        let mut follow = FollowVisitor::new("10.0.0.2:80-10.0.0.1:51000".parse().unwrap(), FollowVisitor::DEFAULT_SEPARATOR.to_string());
+
+        let mut text = String::new();
+        let exchange = [
+            (client, server, TCP_SYN, 100, &b""[..]),
+            (server, client, TCP_SYN | TCP_ACK, 500, b""),
+            (client, server, TCP_PSH | TCP_ACK, 101, b"GET / HTTP/1.1\r\nHost: example.com\r\n\r\n"),
+            (server, client, TCP_PSH | TCP_ACK, 501, b"HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nhi"),
+        ];
+        for (source, destination, flags, sequence, payload) in exchange {
+            text += &segment(&mut follow, source, destination, flags, sequence, payload).unwrap_or_default();
+        }
+
+        assert_eq!(text, "===== 10.0.0.1:51000 > 10.0.0.2:80 =====\n\
+                          GET / HTTP/1.1\r\nHost: example.com\r\n\r\n\
+                          \n===== 10.0.0.2:80 > 10.0.0.1:51000 =====\n\
+                          HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nhi");
+    }
+
+    #[test]
+    fn ignores_other_connections() {
+        let mut follow = FollowVisitor::new("10.0.0.1:51000-10.0.0.2:80".parse().unwrap(), FollowVisitor::DEFAULT_SEPARATOR.to_string());
+        assert_eq!(segment(&mut follow, (ip(10, 0, 0, 3), 51000), (ip(10, 0, 0, 2), 80), TCP_SYN, 0, b""), None);
+        assert_eq!(segment(&mut follow, (ip(10, 0, 0, 3), 51000), (ip(10, 0, 0, 2), 80), TCP_ACK, 1, b"data"), None);
+    }
+
+    #[test]
+    fn escapes_non_printable_bytes() {
+        assert_eq!(FollowVisitor::escape(b"ok\r\n\x00\xff"), "ok\r\n\\x00\\xff");
+    }
+}
@@ -0,0 +1,448 @@
+/*
+https://www.rfc-editor.org/rfc/rfc791#section-3.2
+*/
+
+use std::collections::{BTreeMap, HashMap};
+use std::io::Error;
+use std::time::Duration;
+use pcap::{Packet, PacketHeader};
+use crate::ethernet::{self, Ethernet};
+use crate::ipv4::{IPv4, Ipv4Address};
+use crate::limits::ResourceLimits;
+use crate::shared::{checksum_add, checksum_finish};
+use crate::visitor::Visitor;
+
+
+/// Identifies the fragments of one datagram.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct FragmentKey {
+    pub source: Ipv4Address,
+    pub destination: Ipv4Address,
+    pub identification: u16,
+    pub protocol: u8,
+}
+
+impl FragmentKey {
+    pub fn new(ip: &IPv4) -> Self {
+        Self {
+            source: ip.source_address(),
+            destination: ip.destination_address(),
+            identification: ip.identification(),
+            protocol: ip.protocol_raw(),
+        }
+    }
+}
+
+
+/// The fragments received so far of one datagram.
+#[derive(Debug)]
+struct Fragments {
+    /// Fragment data keyed by offset in bytes.
+    pieces: BTreeMap<usize, Vec<u8>>,
+    /// Header of the first fragment, which is the one that may carry options to copy.
+    header: Option<Vec<u8>>,
+    /// Payload size, known once the last fragment has arrived.
+    size: Option<usize>,
+    bytes: usize,
+    first_seen: Duration,
+}
+
+impl Fragments {
+    /// The payload, if every byte up to the end of the last fragment has arrived.
+    fn assemble(&self) -> Option<Vec<u8>> {
+        let size = self.size?;
+        let mut covered = 0;
+        for (offset, piece) in &self.pieces {
+            if *offset > covered { return None }
+            covered = covered.max(offset + piece.len());
+        }
+        if covered < size || self.header.is_none() { return None }
+
+        let mut payload = vec![0u8; size];
+        for (offset, piece) in &self.pieces {
+            let end = (offset + piece.len()).min(size);
+            if *offset < end { payload[*offset..end].copy_from_slice(&piece[..end - offset]) }
+        }
+        Some(payload)
+    }
+}
+
+
+/// A datagram reassembled from its fragments.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Datagram {
+    /// The datagram, header included.
+    pub data: Vec<u8>,
+    /// Offsets in bytes of the fragments it was reassembled from, in order.
+    pub offsets: Vec<usize>,
+}
+
+
+/// Buffers IPv4 fragments until their datagram is complete. Incomplete datagrams are dropped after `timeout`,
+/// or oldest first when more than `max_bytes` are buffered or more than `max_datagrams` are pending.
+#[derive(Debug)]
+pub struct FragmentReassembler {
+    sets: HashMap<FragmentKey, Fragments>,
+    timeout: Duration,
+    max_bytes: usize,
+    max_datagrams: usize,
+    bytes: usize,
+    dropped: usize,
+}
+
+impl FragmentReassembler {
+    /// Same as the Linux default for `ipfrag_time`.
+    pub const DEFAULT_TIMEOUT: Duration = Duration::from_secs(30);
+    pub const DEFAULT_MAX_BYTES: usize = 4 << 20;
+    pub const MAX_DATAGRAM_SIZE: usize = 65535;
+
+    pub fn new(timeout: Duration, max_bytes: usize) -> Self {
+        let max_datagrams = ResourceLimits::default().max_flows;
+        Self { sets: HashMap::new(), timeout, max_bytes, max_datagrams, bytes: 0, dropped: 0 }
+    }
+
+    /// Bounds the buffered bytes by `max_reassembly_bytes` and the pending datagrams by `max_flows`.
+    pub fn with_limits(self, limits: ResourceLimits) -> Self {
+        Self { max_bytes: limits.max_reassembly_bytes, max_datagrams: limits.max_flows, ..self }
+    }
+
+    /// Number of incomplete datagrams that were given up on.
+    pub fn dropped(&self) -> usize {
+        self.dropped
+    }
+
+    /// Adds a fragment received at `now` and returns the complete datagram, header included, once all its
+    /// fragments have arrived. The returned header has the fragment fields cleared and its checksum updated.
+    pub fn push(&mut self, ip: &IPv4, now: Duration) -> Option<Datagram> {
+        self.expire(now);
+
+        // Ethernet padding may follow the datagram.
+        let end = (ip.total_length() as usize).clamp(ip.header_size(), ip.header_size() + ip.raw_payload().len());
+        let data = &ip.raw_payload()[..end - ip.header_size()];
+        let offset = ip.fragment_offset_bytes();
+        if offset + data.len() > Self::MAX_DATAGRAM_SIZE - ip.header_size() {
+            return None;
+        }
+
+        let key = FragmentKey::new(ip);
+        while !self.sets.contains_key(&key) && !self.sets.is_empty() && self.sets.len() >= self.max_datagrams {
+            self.drop_oldest();
+        }
+        let set = self.sets.entry(key).or_insert_with(|| Fragments {
+            pieces: BTreeMap::new(), header: None, size: None, bytes: 0, first_seen: now
+        });
+        if offset == 0 {
+            set.header = Some(ip.raw_header().to_vec());
+        }
+        if ip.mf() == 0 {
+            set.size = Some(offset + data.len());
+        }
+        if let Some(old) = set.pieces.insert(offset, data.to_vec()) {
+            set.bytes -= old.len();
+            self.bytes -= old.len();
+        }
+        set.bytes  += data.len();
+        self.bytes += data.len();
+
+        if let Some(payload) = set.assemble() {
+            let set = self.sets.remove(&key).unwrap();
+            self.bytes -= set.bytes;
+            let offsets = set.pieces.keys().copied().collect();
+            return Some(Datagram { data: Self::datagram(set.header.unwrap(), &payload), offsets });
+        }
+
+        while self.bytes > self.max_bytes && !self.sets.is_empty() {
+            self.drop_oldest();
+        }
+        None
+    }
+
+    /// Drops the datagrams still waiting for fragments, e.g. at the end of the capture, counting them as dropped.
+    pub fn drop_pending(&mut self) {
+        let pending: Vec<FragmentKey> = self.sets.keys().copied().collect();
+        for key in pending {
+            self.drop_set(&key);
+        }
+    }
+
+    fn drop_oldest(&mut self) {
+        if let Some(oldest) = self.sets.iter().min_by_key(|(_, set)| set.first_seen).map(|(key, _)| *key) {
+            self.drop_set(&oldest);
+        }
+    }
+
+    /// Drops the datagrams whose first fragment arrived more than `timeout` before `now`.
+    pub fn expire(&mut self, now: Duration) {
+        let expired: Vec<FragmentKey> = self.sets.iter()
+            .filter(|(_, set)| now.saturating_sub(set.first_seen) > self.timeout)
+            .map(|(key, _)| *key)
+            .collect();
+        for key in expired {
+            self.drop_set(&key);
+        }
+    }
+
+    fn drop_set(&mut self, key: &FragmentKey) {
+        if let Some(set) = self.sets.remove(key) {
+            self.bytes -= set.bytes;
+            self.dropped += 1;
+        }
+    }
+
+    fn datagram(mut header: Vec<u8>, payload: &[u8]) -> Vec<u8> {
+        let total_length = (header.len() + payload.len()) as u16;
+        header[2..4].copy_from_slice(&total_length.to_be_bytes());
+        // Keep DF and the reserved bit, clear MF and the offset.
+        header[6] &= 0b1100_0000;
+        header[7] = 0;
+        header[10..12].copy_from_slice(&[0, 0]);
+        let check_sum = checksum_finish(checksum_add(0, &header));
+        header[10..12].copy_from_slice(&check_sum.to_be_bytes());
+
+        header.extend_from_slice(payload);
+        header
+    }
+}
+
+impl Default for FragmentReassembler {
+    fn default() -> Self {
+        Self::new(Self::DEFAULT_TIMEOUT, Self::DEFAULT_MAX_BYTES)
+    }
+}
+
+
+/// Holds back IPv4 fragments over Ethernet and passes each reassembled datagram on to the inner visitor as a
+/// frame of its own, with the Ethernet header and capture time of its last fragment. Packets that aren't
+/// fragments are passed on without being buffered.
+pub struct DefragVisitor {
+    inner: Box<dyn for<'a> Visitor<'a, ()>>,
+    reassembler: FragmentReassembler,
+}
+
+impl DefragVisitor {
+    pub fn new(inner: Box<dyn for<'a> Visitor<'a, ()>>) -> Self {
+        Self { inner, reassembler: FragmentReassembler::default() }
+    }
+
+    pub fn with_limits(self, limits: ResourceLimits) -> Self {
+        Self { reassembler: self.reassembler.with_limits(limits), ..self }
+    }
+}
+
+
+fn timestamp(header: &PacketHeader) -> Duration {
+    Duration::new(header.ts.tv_sec.max(0) as u64, (header.ts.tv_usec.max(0) as u32).min(999_999) * 1000)
+}
+
+
+impl<'a> Visitor<'a, ()> for DefragVisitor {
+    fn visit_packet(&mut self, packet: &'a Packet) -> Result<(), Error> {
+        let Ok(frame) = Ethernet::from_bytes(packet.data) else { return self.inner.visit_packet(packet) };
+        let Ok(ethernet::Payload::IPv4(ip)) = frame.payload() else { return self.inner.visit_packet(packet) };
+        if !ip.is_fragment() {
+            return self.inner.visit_packet(packet);
+        }
+
+        let Some(datagram) = self.reassembler.push(&ip, timestamp(packet.header)) else { return Ok(()) };
+        let mut data = packet.data[..Ethernet::PAYLOAD_OFFSET].to_vec();
+        data.extend_from_slice(&datagram.data);
+        let header = PacketHeader { ts: packet.header.ts, caplen: data.len() as u32, len: data.len() as u32 };
+        self.inner.visit_reassembled_packet(&Packet::new(&header, &data), &datagram.offsets)
+    }
+
+    /// Wireless fragments are passed on as they are.
+    fn visit_radiotap_packet(&mut self, packet: &'a Packet) -> Result<(), Error> {
+        self.inner.visit_radiotap_packet(packet)
+    }
+
+    fn visit_sll2_packet(&mut self, packet: &'a Packet) -> Result<(), Error> {
+        self.inner.visit_sll2_packet(packet)
+    }
+
+    /// Reports the datagrams that never completed, including those still pending, before the inner visitor's
+    /// report so it isn't mixed into it.
+    fn finish(&mut self) -> Result<(), Error> {
+        self.reassembler.drop_pending();
+        if self.reassembler.dropped() > 0 {
+            eprintln!("Dropped {} incomplete fragmented datagrams", self.reassembler.dropped());
+        }
+        self.inner.finish()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::Ipv4Builder;
+    use crate::ipv4::Protocol;
+    use crate::raw::visit_frame;
+    use crate::testing::{self, ip, Recorder};
+    use crate::tree::SharedBuffer;
+    use crate::visitor::Printer;
+
+    /// A UDP datagram with 40 bytes of payload, 48 after the IPv4 header, that may be fragmented.
+    fn datagram() -> Vec<u8> {
+        let payload: Vec<u8> = (0..40).collect();
+        Ipv4Builder::new(ip(10, 0, 0, 1), ip(10, 0, 0, 2))
+            .with_identification(0x4242)
+            .with_dont_fragment(false)
+            .with_payload(Protocol::UDP, &testing::udp(53000, 53, &payload))
+            .build()
+    }
+
+    /// `datagram` in three fragments of 16 bytes of payload each.
+    fn fragments(datagram: &[u8]) -> Vec<Vec<u8>> {
+        vec![
+            testing::ipv4_fragment(datagram, 0, 16, true),
+            testing::ipv4_fragment(datagram, 16, 16, true),
+            testing::ipv4_fragment(datagram, 32, 16, false),
+        ]
+    }
+
+    fn push(reassembler: &mut FragmentReassembler, fragment: &[u8]) -> Option<Datagram> {
+        reassembler.push(&IPv4::from_bytes(fragment).unwrap(), Duration::ZERO)
+    }
+
+    #[test]
+    fn reassembles_three_fragments_in_order() {
+        let datagram = datagram();
+        let fragments = fragments(&datagram);
+        let mut reassembler = FragmentReassembler::default();
+
+        assert_eq!(push(&mut reassembler, &fragments[0]), None);
+        assert_eq!(push(&mut reassembler, &fragments[1]), None);
+        let reassembled = push(&mut reassembler, &fragments[2]).unwrap();
+
+        assert_eq!(reassembled.data, datagram);
+        assert_eq!(reassembled.offsets, [0, 16, 32]);
+        assert!(IPv4::from_bytes(&reassembled.data).unwrap().verify_header_checksum());
+    }
+
+    #[test]
+    fn reassembles_fragments_out_of_order() {
+        let datagram = datagram();
+        let fragments = fragments(&datagram);
+        let mut reassembler = FragmentReassembler::default();
+
+        assert_eq!(push(&mut reassembler, &fragments[2]), None);
+        assert_eq!(push(&mut reassembler, &fragments[0]), None);
+        assert_eq!(push(&mut reassembler, &fragments[1]).unwrap().data, datagram);
+    }
+
+    #[test]
+    fn reassembles_overlapping_fragments() {
+        let datagram = datagram();
+        let mut reassembler = FragmentReassembler::default();
+
+        assert_eq!(push(&mut reassembler, &testing::ipv4_fragment(&datagram, 0, 24, true)), None);
+        assert_eq!(push(&mut reassembler, &testing::ipv4_fragment(&datagram, 16, 16, true)), None);
+        let reassembled = push(&mut reassembler, &testing::ipv4_fragment(&datagram, 24, 24, false)).unwrap();
+        assert_eq!(reassembled.data, datagram);
+        assert_eq!(reassembled.offsets, [0, 16, 24]);
+    }
+
+    #[test]
+    fn waits_for_a_missing_fragment() {
+        let fragments = fragments(&datagram());
+        let mut reassembler = FragmentReassembler::default();
+
+        assert_eq!(push(&mut reassembler, &fragments[0]), None);
+        assert_eq!(push(&mut reassembler, &fragments[2]), None);
+        assert_eq!(reassembler.dropped(), 0);
+
+        reassembler.expire(FragmentReassembler::DEFAULT_TIMEOUT + Duration::from_secs(1));
+        assert_eq!(reassembler.dropped(), 1);
+    }
+
+    #[test]
+    fn visitor_forwards_the_reassembled_frame_and_drops_pending_sets_at_the_end() {
+        let datagram = datagram();
+        let fragments = fragments(&datagram);
+        let incomplete = testing::ipv4_fragment(&Ipv4Builder::new(ip(10, 0, 0, 3), ip(10, 0, 0, 2)).with_dont_fragment(false).with_payload(Protocol::UDP, &[0; 32]).build(), 0, 16, true);
+        let recorder = Recorder::default();
+        let mut visitor = DefragVisitor::new(Box::new(recorder.clone()));
+
+        for fragment in [&fragments[0], &incomplete, &fragments[1], &fragments[2]] {
+            visit_frame(&mut visitor, &testing::ethernet(testing::ETHER_TYPE_IPV4, fragment), Duration::ZERO).unwrap();
+        }
+        visitor.finish().unwrap();
+
+        assert_eq!(recorder.packets(), vec![testing::ethernet(testing::ETHER_TYPE_IPV4, &datagram)]);
+        assert_eq!(visitor.reassembler.dropped(), 1);
+    }
+
+    #[test]
+    fn drops_the_oldest_datagram_beyond_max_flows() {
+        let limits = ResourceLimits { max_flows: 1, ..ResourceLimits::default() };
+        let mut reassembler = FragmentReassembler::default().with_limits(limits);
+        let first = datagram();
+        let mut second = datagram();
+        second[4..6].copy_from_slice(&0x4343u16.to_be_bytes());
+
+        assert_eq!(push(&mut reassembler, &fragments(&first)[0]), None);
+        assert_eq!(push(&mut reassembler, &fragments(&second)[0]), None);
+        assert_eq!(reassembler.dropped(), 1);
+        // The rest of the first datagram can't complete it anymore.
+        assert_eq!(push(&mut reassembler, &fragments(&first)[1]), None);
+        assert_eq!(push(&mut reassembler, &fragments(&first)[2]), None);
+    }
+    /// Collects the name of the first question of every DNS message over UDP. Clones share the names.
+    #[derive(Clone, Default)]
+    struct Questions(std::rc::Rc<std::cell::RefCell<Vec<String>>>);
+
+    impl<'a> Visitor<'a, ()> for Questions {
+        fn visit_udp(&mut self, packet: &crate::udp::Udp<'a>, _context: &crate::visitor::Context<'a>) -> Result<(), Error> {
+            let message = crate::dns::Message::from_bytes(packet.raw_payload())?;
+            self.0.borrow_mut().extend(message.questions.first().map(|x| x.name.clone()));
+            Ok(())
+        }
+    }
+
+    /// A DNS query for the A record of example.com from 10.0.0.1, which may be fragmented.
+    fn dns_datagram() -> Vec<u8> {
+        let mut query = Vec::from([0x12, 0x34, 0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0]);
+        query.extend_from_slice(b"\x07example\x03com\x00");
+        query.extend_from_slice(&[0, 1, 0, 1]);
+        Ipv4Builder::new(ip(10, 0, 0, 1), ip(10, 0, 0, 53))
+            .with_identification(0x4242)
+            .with_dont_fragment(false)
+            .with_payload(Protocol::UDP, &testing::udp(53000, 53, &query))
+            .build()
+    }
+
+    #[test]
+    fn decodes_the_dns_question_of_a_fragmented_query() {
+        let datagram = dns_datagram();
+        // 8 bytes of UDP header and 29 of DNS query, the question is split across the fragments.
+        let fragments = [testing::ipv4_fragment(&datagram, 0, 16, true), testing::ipv4_fragment(&datagram, 16, 21, false)];
+        let questions = Questions::default();
+        let mut visitor = DefragVisitor::new(Box::new(questions.clone()));
+
+        visit_frame(&mut visitor, &testing::ethernet(testing::ETHER_TYPE_IPV4, &fragments[0]), Duration::ZERO).unwrap();
+        assert!(questions.0.borrow().is_empty());
+        visit_frame(&mut visitor, &testing::ethernet(testing::ETHER_TYPE_IPV4, &fragments[1]), Duration::ZERO).unwrap();
+        assert_eq!(*questions.0.borrow(), ["example.com"]);
+
+        // The same query in one piece isn't buffered.
+        visit_frame(&mut visitor, &testing::ethernet(testing::ETHER_TYPE_IPV4, &datagram), Duration::ZERO).unwrap();
+        assert_eq!(*questions.0.borrow(), ["example.com", "example.com"]);
+        assert!(visitor.reassembler.sets.is_empty());
+    }
+
+    #[test]
+    fn printer_notes_the_offsets_of_the_fragments() {
+        let datagram = dns_datagram();
+        let buffer = SharedBuffer::default();
+        let printer = Printer::with_writer(Box::new(buffer.clone())).with_verbosity(Printer::KEY_FIELDS);
+        let mut visitor = DefragVisitor::new(Box::new(printer));
+
+        for (offset, length, more) in [(0, 16, true), (16, 21, false)] {
+            let fragment = testing::ipv4_fragment(&datagram, offset, length, more);
+            visit_frame(&mut visitor, &testing::ethernet(testing::ETHER_TYPE_IPV4, &fragment), Duration::ZERO).unwrap();
+        }
+        let text = String::from_utf8(buffer.take()).unwrap();
+        assert!(text.contains(&format!("Packet [ size {}, reassembled from fragments at offsets 0, 16 ] @", 14 + datagram.len())), "{}", text);
+        assert!(text.contains("|    Destination Port      : 53"), "{}", text);
+    }
+}
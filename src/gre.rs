@@ -0,0 +1,176 @@
+/*
+https://www.rfc-editor.org/rfc/rfc2784
+https://www.rfc-editor.org/rfc/rfc2890
+*/
+
+use crate::shared::MAX_ENCAPSULATION_DEPTH;
+use crate::ethernet::EtherType;
+use crate::ipv4;
+use crate::ipv6;
+use crate::error::ParseError;
+use crate::endian::{be_u16, be_u32};
+
+
+#[derive(Debug)]
+pub enum Payload<'a> {
+    IPv4(ipv4::IPv4<'a>),
+    IPv6(ipv6::IPv6<'a>),
+    Unknown(&'a [u8])
+}
+
+
+#[derive(Clone)]
+pub struct Gre<'a> {
+    data: &'a [u8],
+    /// Number of tunnels this header is nested in.
+    depth: usize,
+}
+
+impl<'a> Gre<'a> {
+    pub const MIN_HEADER_SIZE: usize = 4;
+
+    pub fn checksum_present(&self) -> bool { self.data[0] & 0b1000_0000 != 0 }
+    pub fn key_present(&self)      -> bool { self.data[0] & 0b0010_0000 != 0 }
+    pub fn sequence_present(&self) -> bool { self.data[0] & 0b0001_0000 != 0 }
+    pub fn version(&self)          -> u8   { self.data[1] & 0b0000_0111 }
+
+    /// EtherType of the encapsulated protocol.
+    pub fn protocol_type(&self) -> u16 { be_u16(self.data, 2) }
+
+    pub fn check_sum(&self) -> Option<u16> {
+        if self.checksum_present() { Some(be_u16(self.data, 4)) } else { None }
+    }
+
+    pub fn key(&self) -> Option<u32> {
+        let offset = 4 + if self.checksum_present() { 4 } else { 0 };
+        if self.key_present() { Some(be_u32(self.data, offset)) } else { None }
+    }
+
+    pub fn sequence_number(&self) -> Option<u32> {
+        let offset = 4 + if self.checksum_present() { 4 } else { 0 } + if self.key_present() { 4 } else { 0 };
+        if self.sequence_present() { Some(be_u32(self.data, offset)) } else { None }
+    }
+
+    pub fn header_size(&self) -> usize {
+        let mut size = Self::MIN_HEADER_SIZE;
+        if self.checksum_present() { size += 4 }
+        if self.key_present()      { size += 4 }
+        if self.sequence_present() { size += 4 }
+        size
+    }
+
+    pub fn depth(&self) -> usize { self.depth }
+
+    pub fn raw_payload(&self) -> &'a [u8] {
+        &self.data[self.header_size()..]
+    }
+
+    pub fn payload(&self) -> Result<Payload<'a>, ParseError> {
+        match EtherType::from_code(self.protocol_type()) {
+            EtherType::IPv4 => Ok(Payload::IPv4(ipv4::IPv4::from_bytes_nested(self.raw_payload(), self.depth)?)),
+            EtherType::IPv6 => Ok(Payload::IPv6(ipv6::IPv6::from_bytes_nested(self.raw_payload(), self.depth)?)),
+            _ => Ok(Payload::Unknown(self.raw_payload())),
+        }
+    }
+
+    pub fn from_bytes(data: &'a [u8]) -> Result<Self, ParseError> {
+        Self::from_bytes_nested(data, 1)
+    }
+
+    /// Parses a GRE header that is itself inside `depth - 1` tunnels.
+    pub fn from_bytes_nested(data: &'a [u8], depth: usize) -> Result<Self, ParseError> {
+        if depth > MAX_ENCAPSULATION_DEPTH {
+            return Err(ParseError::TooDeep { layer: "Gre", limit: MAX_ENCAPSULATION_DEPTH });
+        }
+
+        if data.len() < Self::MIN_HEADER_SIZE {
+            return Err(ParseError::TooSmall { layer: "Gre", field: "data", expected: Self::MIN_HEADER_SIZE, got: data.len() });
+        }
+
+        let me = Self { data, depth };
+
+        if me.header_size() > data.len() {
+            return Err(ParseError::TooBig { layer: "Gre", field: "header size", expected: data.len(), got: me.header_size() });
+        }
+
+        Ok(me)
+    }
+}
+
+
+impl<'a> core::fmt::Debug for Gre<'a> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "    Gre\n")?;
+        write!(f, "        version:         {:?}\n", self.version())?;
+        write!(f, "        protocol_type:   {:#06x}\n", self.protocol_type())?;
+        write!(f, "        check_sum:       {:?}\n", self.check_sum())?;
+        write!(f, "        key:             {:?}\n", self.key())?;
+        write!(f, "        sequence_number: {:?}\n", self.sequence_number())?;
+        write!(f, "        payload: {:?}\n",    self.payload())?;
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+    use crate::builder::{Ipv4Builder, TcpBuilder, TCP_SYN};
+    use crate::ipv4::{IPv4, Protocol};
+    use crate::testing::ip;
+
+    /// A GRE header without optional fields followed by `payload` of `protocol_type`.
+    fn gre(protocol_type: u16, payload: &[u8]) -> Vec<u8> {
+        let mut data = Vec::from([0, 0]);
+        data.extend_from_slice(&protocol_type.to_be_bytes());
+        data.extend_from_slice(payload);
+        data
+    }
+
+    #[test]
+    fn decodes_ipv4_in_gre_in_ipv4() {
+        let inner = Ipv4Builder::new(ip(192, 168, 0, 1), ip(192, 168, 0, 2)).with_tcp(&TcpBuilder::new(51000, 22).with_flags(TCP_SYN)).build();
+        let outer = Ipv4Builder::new(ip(10, 0, 0, 1), ip(10, 0, 0, 2)).with_payload(Protocol::GRE, &gre(0x0800, &inner)).build();
+
+        let packet = IPv4::from_bytes(&outer).unwrap();
+        let Ok(ipv4::Payload::Gre(tunnel)) = packet.payload() else { panic!("not a GRE packet") };
+        assert_eq!(tunnel.version(), 0);
+        assert_eq!(tunnel.protocol_type(), 0x0800);
+        assert_eq!(tunnel.key(), None);
+
+        let Ok(Payload::IPv4(inner)) = tunnel.payload() else { panic!("GRE doesn't hold IPv4") };
+        assert_eq!(inner.source_address(), ip(192, 168, 0, 1));
+        assert_eq!(inner.destination_address(), ip(192, 168, 0, 2));
+        let Ok(ipv4::Payload::Tcp(tcp)) = inner.payload() else { panic!("the inner packet isn't TCP") };
+        assert_eq!(tcp.destination_port(), 22);
+    }
+
+    #[test]
+    fn reads_the_optional_fields() {
+        let data = [0b1011_0000, 0, 0x86, 0xDD, 0xAB, 0xCD, 0, 0, 0, 0, 0, 42, 0, 0, 0, 7];
+        let tunnel = Gre::from_bytes(&data).unwrap();
+        assert_eq!(tunnel.check_sum(), Some(0xABCD));
+        assert_eq!(tunnel.key(), Some(42));
+        assert_eq!(tunnel.sequence_number(), Some(7));
+        assert_eq!(tunnel.header_size(), 16);
+        assert!(Gre::from_bytes(&data[..12]).is_err());
+    }
+
+    #[test]
+    fn stops_at_the_maximum_depth() {
+        let mut packet = Ipv4Builder::new(ip(10, 0, 0, 1), ip(10, 0, 0, 2)).with_payload(Protocol::UDP, &[0; 8]).build();
+        for _ in 0..MAX_ENCAPSULATION_DEPTH + 1 {
+            packet = Ipv4Builder::new(ip(10, 0, 0, 1), ip(10, 0, 0, 2)).with_payload(Protocol::GRE, &gre(0x0800, &packet)).build();
+        }
+
+        let mut result = IPv4::from_bytes(&packet).and_then(|x| x.payload());
+        while let Ok(ipv4::Payload::Gre(tunnel)) = result {
+            result = tunnel.payload().and_then(|x| match x {
+                Payload::IPv4(inner) => inner.payload(),
+                _ => panic!("GRE doesn't hold IPv4"),
+            });
+        }
+        assert!(matches!(result, Err(ParseError::TooDeep { layer: "Gre", .. })));
+    }
+}
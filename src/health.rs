@@ -0,0 +1,91 @@
+use std::io::{Error, ErrorKind};
+use crate::error::ParseError;
+use crate::ethernet::{self, Ethernet};
+
+
+/// What became of one packet.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Outcome {
+    Decoded,
+    /// A header was cut off, e.g. by the snaplen.
+    Truncated,
+    /// A header failed validation.
+    Malformed,
+    /// The link type, EtherType or a field value isn't decoded.
+    Unsupported,
+}
+
+impl Outcome {
+    /// Classifies the result of visiting a packet. `frame` is the packet's data if it's an Ethernet frame,
+    /// which is needed to tell an unknown EtherType from a decoded packet.
+    pub fn classify(result: &Result<(), Error>, frame: Option<&[u8]>) -> Self {
+        let error = match result {
+            Ok(()) => {
+                let payload = frame.map(|x| Ethernet::from_bytes(x).and_then(|x| x.payload()));
+                return match payload {
+                    Some(Ok(ethernet::Payload::Unknown(_))) => Self::Unsupported,
+                    _ => Self::Decoded,
+                };
+            },
+            Err(error) => error,
+        };
+
+        if error.kind() == ErrorKind::Unsupported {
+            return Self::Unsupported;
+        }
+        match error.get_ref().and_then(|x| x.downcast_ref::<ParseError>()) {
+            Some(ParseError::TooSmall { .. } | ParseError::TooBig { .. }) => Self::Truncated,
+            Some(ParseError::Unsupported { .. }) => Self::Unsupported,
+            _ => Self::Malformed,
+        }
+    }
+}
+
+
+/// Tally of the outcomes of all packets in a run, e.g.
+/// `12 packets: 9 decoded, 1 truncated, 1 malformed, 1 unsupported`.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub struct Health {
+    pub total: usize,
+    pub decoded: usize,
+    pub truncated: usize,
+    pub malformed: usize,
+    pub unsupported: usize,
+}
+
+impl Health {
+    pub fn record(&mut self, outcome: Outcome) {
+        self.total += 1;
+        match outcome {
+            Outcome::Decoded     => self.decoded += 1,
+            Outcome::Truncated   => self.truncated += 1,
+            Outcome::Malformed   => self.malformed += 1,
+            Outcome::Unsupported => self.unsupported += 1,
+        }
+    }
+}
+
+impl std::fmt::Display for Health {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} packets: {} decoded, {} truncated, {} malformed, {} unsupported",
+            self.total, self.decoded, self.truncated, self.malformed, self.unsupported)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classifies_errors_by_their_parse_error() {
+        let classify = |error: ParseError| Outcome::classify(&Err(error.into()), None);
+        assert_eq!(classify(ParseError::TooSmall { layer: "Tcp", field: "data", expected: 20, got: 12 }), Outcome::Truncated);
+        assert_eq!(classify(ParseError::TooBig { layer: "Tcp", field: "header size", expected: 20, got: 24 }), Outcome::Truncated);
+        assert_eq!(classify(ParseError::Unsupported { layer: "Ipv6", field: "next header", value: 253 }), Outcome::Unsupported);
+        assert_eq!(classify(ParseError::Invalid("Ipv4 version is not 4")), Outcome::Malformed);
+        assert_eq!(Outcome::classify(&Err(Error::new(ErrorKind::Unsupported, "link type")), None), Outcome::Unsupported);
+        assert_eq!(Outcome::classify(&Err(Error::new(ErrorKind::Other, "bad")), None), Outcome::Malformed);
+        assert_eq!(Outcome::classify(&Ok(()), None), Outcome::Decoded);
+    }
+}
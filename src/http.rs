@@ -0,0 +1,295 @@
+/*
+https://www.rfc-editor.org/rfc/rfc9112
+*/
+
+use std::io::{Error, ErrorKind};
+use crate::inflate;
+
+
+/// The ports HTTP is decoded on by default, see `app::AppDispatch`.
+pub const PORTS: [u16; 2] = [80, 8080];
+
+
+/// Header fields in the order they appear. Lookups ignore case, as field names are case-insensitive.
+#[derive(Debug, Default, Clone)]
+pub struct Headers {
+    fields: Vec<(String, String)>,
+}
+
+impl Headers {
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.fields.iter()
+            .find(|(key, _)| key.eq_ignore_ascii_case(name))
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item=&(String, String)> {
+        self.fields.iter()
+    }
+
+    pub fn len(&self) -> usize {
+        self.fields.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty()
+    }
+}
+
+
+#[derive(Debug, Clone)]
+pub struct HttpRequest {
+    pub method: String,
+    pub target: String,
+    pub version: String,
+    pub headers: Headers,
+}
+
+#[derive(Debug, Clone)]
+pub struct HttpResponse {
+    pub version: String,
+    pub status: u16,
+    pub reason: String,
+    pub headers: Headers,
+}
+
+#[derive(Debug, Clone)]
+pub enum Message {
+    Request(HttpRequest),
+    Response(HttpResponse),
+}
+
+impl Message {
+    pub fn headers(&self) -> &Headers {
+        match self {
+            Message::Request(request)   => &request.headers,
+            Message::Response(response) => &response.headers,
+        }
+    }
+}
+
+
+#[derive(Debug)]
+pub enum Parse {
+    /// The message head and the number of bytes it occupies, including the empty line.
+    Complete { message: Message, header_size: usize },
+    /// The empty line ending the head hasn't been seen yet.
+    Incomplete,
+}
+
+
+/// Parses the start line and header fields of a message at the start of `data`.
+pub fn parse(data: &[u8]) -> Result<Parse, Error> {
+    let Some(end) = data.windows(4).position(|x| x == b"\r\n\r\n") else {
+        return Ok(Parse::Incomplete);
+    };
+    let header_size = end + 4;
+
+    let head = std::str::from_utf8(&data[..end])
+        .map_err(|_| Error::new(ErrorKind::Other, "Http head is not valid UTF-8"))?;
+
+    let mut lines = head.split("\r\n");
+    let start_line = lines.next().unwrap_or("");
+
+    let mut headers = Headers::default();
+    for line in lines {
+        let (name, value) = line.split_once(':')
+            .ok_or(Error::new(ErrorKind::Other, format!("Http header line without ':': '{}'", line)))?;
+        headers.fields.push((name.trim().to_string(), value.trim().to_string()));
+    }
+
+    let mut parts = start_line.splitn(3, ' ');
+    let first  = parts.next().unwrap_or("");
+    let second = parts.next().ok_or(Error::new(ErrorKind::Other, format!("Invalid Http start line '{}'", start_line)))?;
+    let third  = parts.next().unwrap_or("");
+
+    let message = if first.starts_with("HTTP/") {
+        let status = second.parse()
+            .map_err(|_| Error::new(ErrorKind::Other, format!("Invalid Http status code '{}'", second)))?;
+        Message::Response(HttpResponse { version: first.to_string(), status, reason: third.to_string(), headers })
+    } else if third.starts_with("HTTP/") {
+        Message::Request(HttpRequest { method: first.to_string(), target: second.to_string(), version: third.to_string(), headers })
+    } else {
+        return Err(Error::new(ErrorKind::Other, format!("Invalid Http start line '{}'", start_line)));
+    };
+
+    Ok(Parse::Complete { message, header_size })
+}
+
+
+/// How the end of a message body is found.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum BodyLength {
+    Length(usize),
+    Chunked,
+    /// The body of a response without a length ends when the connection closes.
+    UntilClose,
+}
+
+impl Message {
+    pub fn body_length(&self) -> BodyLength {
+        let headers = self.headers();
+        let chunked = headers.get("Transfer-Encoding").is_some_and(|x| x.to_ascii_lowercase().contains("chunked"));
+        if chunked { return BodyLength::Chunked }
+        match (headers.get("Content-Length").and_then(|x| x.parse().ok()), self) {
+            (Some(length), _)          => BodyLength::Length(length),
+            (None, Message::Request(_)) => BodyLength::Length(0),
+            (None, Message::Response(response)) => {
+                // These never have a body (RFC 9112, 6.3).
+                if response.status / 100 == 1 || response.status == 204 || response.status == 304 { BodyLength::Length(0) } else { BodyLength::UntilClose }
+            },
+        }
+    }
+}
+
+
+/// Undoes the content codings of a body that was already de-chunked, e.g. `Content-Encoding: gzip`. Codings
+/// are listed in the order they were applied, so they're undone from the last. Transfer codings other than
+/// `chunked` are applied on top of the content codings. Each decoded body is at most `limit` bytes.
+pub fn decode_content(message: &Message, body: &[u8], limit: usize) -> Result<Vec<u8>, String> {
+    let headers = message.headers();
+    let codings = [headers.get("Content-Encoding"), headers.get("Transfer-Encoding")].into_iter()
+        .flatten()
+        .flat_map(|x| x.split(','))
+        .map(|x| x.trim().to_ascii_lowercase())
+        .filter(|x| !x.is_empty() && x != "identity" && x != "chunked");
+
+    let mut body = body.to_vec();
+    for coding in codings.collect::<Vec<String>>().iter().rev() {
+        body = match coding.as_str() {
+            "gzip" | "x-gzip" => inflate::gunzip(&body, limit)?,
+            // Should be zlib, but some servers send raw DEFLATE.
+            "deflate" => inflate::unzlib(&body, limit).or_else(|_| inflate::inflate(&body, limit))?,
+            _ => return Err(format!("Unsupported Http coding '{}'", coding)),
+        };
+    }
+    Ok(body)
+}
+
+
+/// Decodes a chunked body at the start of `data`. Returns the body and the number of bytes it occupied,
+/// including the trailer, or `None` if the last chunk hasn't arrived yet.
+pub fn decode_chunked(data: &[u8]) -> Result<Option<(Vec<u8>, usize)>, Error> {
+    let mut body = Vec::new();
+    let mut index = 0;
+    loop {
+        let Some(end) = data[index..].windows(2).position(|x| x == b"\r\n") else { return Ok(None) };
+        let line = std::str::from_utf8(&data[index..index + end])
+            .map_err(|_| Error::new(ErrorKind::Other, "Http chunk size is not valid UTF-8"))?;
+        // Chunk extensions follow a ';'.
+        let size = line.split(';').next().unwrap_or("").trim();
+        let size = usize::from_str_radix(size, 16)
+            .map_err(|_| Error::new(ErrorKind::Other, format!("Invalid Http chunk size '{}'", size)))?;
+        index += end + 2;
+
+        if size == 0 {
+            // Skip the trailer fields up to the empty line.
+            loop {
+                let Some(end) = data[index..].windows(2).position(|x| x == b"\r\n") else { return Ok(None) };
+                index += end + 2;
+                if end == 0 { return Ok(Some((body, index))) }
+            }
+        }
+
+        if data.len() < index + size + 2 { return Ok(None) }
+        body.extend_from_slice(&data[index..index + size]);
+        index += size + 2;
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_a_get_request() {
+        let data = b"GET /index.html HTTP/1.1\r\nHost: example.com\r\nAccept: */*\r\n\r\n";
+        let Parse::Complete { message: Message::Request(request), header_size } = parse(data).unwrap() else { panic!("not a complete request") };
+        assert_eq!(request.method, "GET");
+        assert_eq!(request.target, "/index.html");
+        assert_eq!(request.version, "HTTP/1.1");
+        assert_eq!(request.headers.len(), 2);
+        assert_eq!(request.headers.get("host"), Some("example.com"));
+        assert_eq!(header_size, data.len());
+
+        let Parse::Complete { message, .. } = parse(b"GET / HTTP/1.0\r\n\r\n").unwrap() else { panic!("not a complete request") };
+        assert!(message.headers().is_empty() && !request.headers.is_empty());
+    }
+
+    #[test]
+    fn parses_a_200_response() {
+        let data = b"HTTP/1.1 200 OK\r\nContent-Type: text/plain\r\nContent-Length: 5\r\n\r\nhello";
+        let Parse::Complete { message, header_size } = parse(data).unwrap() else { panic!("not a complete response") };
+        assert_eq!(&data[header_size..], b"hello");
+        assert_eq!(message.body_length(), BodyLength::Length(5));
+
+        let Message::Response(response) = message else { panic!("not a response") };
+        assert_eq!(response.version, "HTTP/1.1");
+        assert_eq!(response.status, 200);
+        assert_eq!(response.reason, "OK");
+        assert_eq!(response.headers.get("Content-Type"), Some("text/plain"));
+    }
+
+    #[test]
+    fn needs_more_data_without_the_empty_line() {
+        assert!(matches!(parse(b"GET / HTTP/1.1\r\nHost: exam").unwrap(), Parse::Incomplete));
+    }
+
+    #[test]
+    fn rejects_an_invalid_start_line() {
+        assert!(parse(b"HELLO\r\n\r\n").is_err());
+        assert!(parse(b"HTTP/1.1 abc OK\r\n\r\n").is_err());
+    }
+    /// `hello, hello, hello gzip\n` compressed by gzip.
+    const GZIP: [u8; 35] = [
+        0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x03, 0xcb, 0x48, 0xcd, 0xc9, 0xc9, 0xd7, 0x51, 0xc8,
+        0x40, 0xa2, 0x14, 0xd2, 0xab, 0x32, 0x0b, 0xb8, 0x00, 0x2a, 0x08, 0x44, 0xa6, 0x19, 0x00, 0x00, 0x00,
+    ];
+
+    #[test]
+    fn decodes_a_chunked_body_with_a_trailer() {
+        let data = b"5;name=value\r\nhello\r\n7\r\n, world\r\n0\r\nExpires: never\r\n\r\nGET / HTTP/1.1\r\n";
+        let (body, size) = decode_chunked(data).unwrap().unwrap();
+        assert_eq!(body, b"hello, world");
+        assert!(data[size..].starts_with(b"GET /"));
+
+        for end in [0, 3, 10, 30, size - 1] {
+            assert!(decode_chunked(&data[..end]).unwrap().is_none(), "{} bytes", end);
+        }
+        assert!(decode_chunked(b"xyz\r\n").is_err());
+    }
+
+    #[test]
+    fn decodes_a_chunked_gzip_body() {
+        let mut data = b"HTTP/1.1 200 OK\r\nContent-Encoding: gzip\r\nTransfer-Encoding: chunked\r\n\r\n".to_vec();
+        for chunk in GZIP.chunks(20) {
+            data.extend_from_slice(format!("{:x}\r\n", chunk.len()).as_bytes());
+            data.extend_from_slice(chunk);
+            data.extend_from_slice(b"\r\n");
+        }
+        data.extend_from_slice(b"0\r\n\r\n");
+
+        let Parse::Complete { message, header_size } = parse(&data).unwrap() else { panic!("not a complete response") };
+        assert_eq!(message.body_length(), BodyLength::Chunked);
+        let (body, size) = decode_chunked(&data[header_size..]).unwrap().unwrap();
+        assert_eq!(header_size + size, data.len());
+        assert_eq!(body, GZIP);
+        assert_eq!(decode_content(&message, &body, 1 << 20).unwrap(), b"hello, hello, hello gzip\n");
+    }
+
+    #[test]
+    fn undoes_codings_from_the_last() {
+        let response = |encoding: &str| {
+            let data = format!("HTTP/1.1 200 OK\r\nContent-Encoding: {}\r\n\r\n", encoding);
+            let Parse::Complete { message, .. } = parse(data.as_bytes()).unwrap() else { panic!("not a complete response") };
+            message
+        };
+        // zlib, as deflate should be.
+        let deflated = [0x78, 0x9c, 0x4b, 0x49, 0x4d, 0xcb, 0x49, 0x2c, 0x49, 0x4d, 0x51, 0x48, 0x41, 0x67, 0x00, 0x00, 0x85, 0xd0, 0x09, 0xec];
+        assert_eq!(decode_content(&response("deflate"), &deflated, 1 << 20).unwrap(), b"deflated deflated deflated");
+        assert_eq!(decode_content(&response("identity"), b"plain", 1 << 20).unwrap(), b"plain");
+        assert!(decode_content(&response("gzip, deflate"), &GZIP, 1 << 20).is_err());
+        assert!(decode_content(&response("br"), b"", 1 << 20).is_err());
+    }
+}
@@ -0,0 +1,191 @@
+/*
+https://www.rfc-editor.org/rfc/rfc792
+https://www.rfc-editor.org/rfc/rfc1191
+*/
+
+use crate::ipv4::{IPv4, Protocol};
+use crate::shared::{checksum_add, checksum_finish};
+use crate::error::ParseError;
+use crate::endian::be_u16;
+
+
+pub const TYPE_ECHO_REPLY             : u8 = 0;
+pub const TYPE_DESTINATION_UNREACHABLE: u8 = 3;
+pub const TYPE_SOURCE_QUENCH          : u8 = 4;
+pub const TYPE_REDIRECT               : u8 = 5;
+pub const TYPE_ECHO_REQUEST           : u8 = 8;
+pub const TYPE_TIME_EXCEEDED          : u8 = 11;
+pub const TYPE_PARAMETER_PROBLEM      : u8 = 12;
+
+pub const CODE_FRAGMENTATION_NEEDED: u8 = 4;
+
+
+/// What an ICMP message reports, e.g. `Destination Unreachable (fragmentation needed)`.
+pub fn kind_name(kind: u8, code: u8) -> &'static str {
+    match (kind, code) {
+        (TYPE_ECHO_REPLY, _)                    => "Echo Reply",
+        (TYPE_DESTINATION_UNREACHABLE, 0)       => "Destination Unreachable (network)",
+        (TYPE_DESTINATION_UNREACHABLE, 1)       => "Destination Unreachable (host)",
+        (TYPE_DESTINATION_UNREACHABLE, 2)       => "Destination Unreachable (protocol)",
+        (TYPE_DESTINATION_UNREACHABLE, 3)       => "Destination Unreachable (port)",
+        (TYPE_DESTINATION_UNREACHABLE, CODE_FRAGMENTATION_NEEDED) => "Destination Unreachable (fragmentation needed)",
+        (TYPE_DESTINATION_UNREACHABLE, 13)      => "Destination Unreachable (administratively prohibited)",
+        (TYPE_DESTINATION_UNREACHABLE, _)       => "Destination Unreachable",
+        (TYPE_SOURCE_QUENCH, _)                 => "Source Quench",
+        (TYPE_REDIRECT, _)                      => "Redirect",
+        (TYPE_ECHO_REQUEST, _)                  => "Echo Request",
+        (TYPE_TIME_EXCEEDED, 0)                 => "Time Exceeded (TTL)",
+        (TYPE_TIME_EXCEEDED, 1)                 => "Time Exceeded (fragment reassembly)",
+        (TYPE_TIME_EXCEEDED, _)                 => "Time Exceeded",
+        (TYPE_PARAMETER_PROBLEM, _)             => "Parameter Problem",
+        _ => "Unknown",
+    }
+}
+
+
+/// An ICMP message for IPv4.
+#[derive(Clone)]
+pub struct Icmp<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Icmp<'a> {
+    /// Type, code, checksum and the four bytes whose meaning depends on the type.
+    pub const HEADER_SIZE: usize = 8;
+
+    pub fn kind(&self)      -> u8  { self.data[0] }
+    pub fn code(&self)      -> u8  { self.data[1] }
+    pub fn check_sum(&self) -> u16 { be_u16(self.data, 2) }
+
+    pub fn kind_name(&self) -> &'static str {
+        kind_name(self.kind(), self.code())
+    }
+
+    /// The identifier and sequence number of echo requests and replies.
+    pub fn echo(&self) -> Option<(u16, u16)> {
+        match self.kind() {
+            TYPE_ECHO_REQUEST | TYPE_ECHO_REPLY => Some((be_u16(self.data, 4), be_u16(self.data, 6))),
+            _ => None,
+        }
+    }
+
+    /// The MTU of the next hop of a fragmentation needed message, or `None` for routers that predate RFC 1191
+    /// and leave it zero.
+    pub fn next_hop_mtu(&self) -> Option<u16> {
+        let mtu = be_u16(self.data, 6);
+        (self.kind() == TYPE_DESTINATION_UNREACHABLE && self.code() == CODE_FRAGMENTATION_NEEDED && mtu != 0).then_some(mtu)
+    }
+
+    /// Whether the message reports an error with the packet it quotes.
+    pub fn is_error(&self) -> bool {
+        matches!(self.kind(), TYPE_DESTINATION_UNREACHABLE | TYPE_SOURCE_QUENCH | TYPE_REDIRECT | TYPE_TIME_EXCEEDED | TYPE_PARAMETER_PROBLEM)
+    }
+
+    pub fn raw_payload(&self) -> &'a [u8] {
+        &self.data[Self::HEADER_SIZE..]
+    }
+
+    /// The IP header of the packet an error message is about, followed by at least the first 8 bytes of its
+    /// payload. The header's total length is that of the original packet, so it exceeds what is quoted.
+    pub fn quoted(&self) -> Option<Result<IPv4<'a>, ParseError>> {
+        self.is_error().then(|| IPv4::from_bytes(self.raw_payload()))
+    }
+
+    /// Verifies the checksum over the whole message.
+    pub fn verify_checksum(&self) -> bool {
+        checksum_finish(checksum_add(0, self.data)) == 0
+    }
+
+    pub fn from_bytes(data: &'a [u8]) -> Result<Self, ParseError> {
+        if data.len() < Self::HEADER_SIZE {
+            return Err(ParseError::TooSmall { layer: "Icmp", field: "data", expected: Self::HEADER_SIZE, got: data.len() });
+        }
+        Ok(Self { data })
+    }
+}
+
+
+/// The ports of a quoted TCP, UDP or SCTP packet, which all start with them. Only the first 8 bytes of the
+/// payload are quoted, too few to parse the transport header in full.
+pub fn quoted_ports(ip: &IPv4) -> Option<(u16, u16)> {
+    let payload = ip.raw_payload();
+    if ip.fragment_offset() != 0 || payload.len() < 4 { return None }
+    match ip.protocol() {
+        Protocol::TCP | Protocol::UDP | Protocol::SCTP => Some((be_u16(payload, 0), be_u16(payload, 2))),
+        _ => None,
+    }
+}
+
+
+impl<'a> core::fmt::Debug for Icmp<'a> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "    Icmp\n")?;
+        write!(f, "        type:      {:?}\n", self.kind())?;
+        write!(f, "        code:      {:?}\n", self.code())?;
+        write!(f, "        check_sum: {:?}\n", self.check_sum())?;
+        if let Some(Ok(quoted)) = self.quoted() {
+            write!(f, "        quoted:    {}\n", quoted)?;
+        }
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+    use crate::builder::{Ipv4Builder, TcpBuilder, TCP_ACK};
+    use crate::testing::ip;
+
+    /// The IP header and first 8 bytes of a 1500 byte TCP segment from 10.0.0.1:51000 to 10.0.0.2:443.
+    fn quote() -> Vec<u8> {
+        let tcp = TcpBuilder::new(51000, 443).with_flags(TCP_ACK).with_payload(&[0; 1460]);
+        let packet = Ipv4Builder::new(ip(10, 0, 0, 1), ip(10, 0, 0, 2)).with_tcp(&tcp).build();
+        packet[..20 + 8].to_vec()
+    }
+
+    /// An ICMP message of `kind` and `code` with `rest` after its checksum.
+    fn message(kind: u8, code: u8, rest: &[u8]) -> Vec<u8> {
+        let mut data = Vec::from([kind, code, 0, 0]);
+        data.extend_from_slice(rest);
+        let checksum = checksum_finish(checksum_add(0, &data));
+        data[2..4].copy_from_slice(&checksum.to_be_bytes());
+        data
+    }
+
+    #[test]
+    fn decodes_the_ports_quoted_by_a_fragmentation_needed_message() {
+        let mut rest = Vec::from([0, 0, 0x05, 0x78]);
+        rest.extend_from_slice(&quote());
+        let data = message(TYPE_DESTINATION_UNREACHABLE, CODE_FRAGMENTATION_NEEDED, &rest);
+        let packet = Icmp::from_bytes(&data).unwrap();
+
+        assert_eq!(packet.kind_name(), "Destination Unreachable (fragmentation needed)");
+        assert!(packet.verify_checksum());
+        assert_eq!(packet.next_hop_mtu(), Some(1400));
+        let quoted = packet.quoted().unwrap().unwrap();
+        assert_eq!((quoted.source_address(), quoted.destination_address()), (ip(10, 0, 0, 1), ip(10, 0, 0, 2)));
+        // The original length is kept, though only 8 bytes of the segment are quoted.
+        assert_eq!(quoted.total_length(), 1500);
+        assert_eq!(quoted.raw_payload().len(), 8);
+        assert_eq!(quoted_ports(&quoted), Some((51000, 443)));
+    }
+
+    #[test]
+    fn quotes_nothing_in_echo_messages() {
+        let data = message(TYPE_ECHO_REQUEST, 0, &[0x12, 0x34, 0, 7, b'p', b'i', b'n', b'g']);
+        let packet = Icmp::from_bytes(&data).unwrap();
+        assert_eq!(packet.kind_name(), "Echo Request");
+        assert_eq!(packet.echo(), Some((0x1234, 7)));
+        assert_eq!(packet.next_hop_mtu(), None);
+        assert!(packet.quoted().is_none());
+        assert!(Icmp::from_bytes(&data[..7]).is_err());
+    }
+
+    #[test]
+    fn reports_a_malformed_quote() {
+        let data = message(TYPE_TIME_EXCEEDED, 0, &[0, 0, 0, 0, 0x45, 0]);
+        assert!(Icmp::from_bytes(&data).unwrap().quoted().unwrap().is_err());
+    }
+}
@@ -0,0 +1,246 @@
+/*
+https://www.rfc-editor.org/rfc/rfc4443
+https://www.rfc-editor.org/rfc/rfc4861
+*/
+
+use crate::shared::{MacAddress, checksum_add, checksum_finish};
+use crate::ipv6::{Ipv6Address, NEXT_HEADER_ICMPV6};
+use crate::error::ParseError;
+use crate::endian::{be_u16, be_u32};
+
+
+pub const TYPE_DESTINATION_UNREACHABLE : u8 = 1;
+pub const TYPE_PACKET_TOO_BIG          : u8 = 2;
+pub const TYPE_TIME_EXCEEDED           : u8 = 3;
+pub const TYPE_PARAMETER_PROBLEM       : u8 = 4;
+pub const TYPE_ECHO_REQUEST            : u8 = 128;
+pub const TYPE_ECHO_REPLY              : u8 = 129;
+pub const TYPE_ROUTER_SOLICITATION     : u8 = 133;
+pub const TYPE_ROUTER_ADVERTISEMENT    : u8 = 134;
+pub const TYPE_NEIGHBOR_SOLICITATION   : u8 = 135;
+pub const TYPE_NEIGHBOR_ADVERTISEMENT  : u8 = 136;
+pub const TYPE_REDIRECT                : u8 = 137;
+
+
+/// Neighbor Discovery option, as carried by the router and neighbor messages.
+#[derive(Debug)]
+pub enum NdpOption {
+    SourceLinkLayerAddress(MacAddress),
+    TargetLinkLayerAddress(MacAddress),
+    PrefixInformation { prefix_length: u8, on_link: bool, autonomous: bool, valid_lifetime: u32, preferred_lifetime: u32, prefix: Ipv6Address },
+    Mtu(u32),
+    Unknown { kind: u8, length: usize },
+}
+
+
+pub struct NdpOptionIter<'a> {
+    data: &'a [u8],
+    index: usize,
+}
+
+impl<'a> Iterator for NdpOptionIter<'a> {
+    type Item = NdpOption;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let header = self.data.get(self.index..self.index+2)?;
+        let kind   = header[0];
+        // The length is given in units of 8 bytes and includes the type and length fields.
+        let length = header[1] as usize * 8;
+        if length == 0 { return None }
+
+        let option = self.data.get(self.index..self.index+length)?;
+        self.index += length;
+
+        match kind {
+            1 if length >= 8 => Some(NdpOption::SourceLinkLayerAddress(MacAddress::from_bytes(&option[2..8]).ok()?)),
+            2 if length >= 8 => Some(NdpOption::TargetLinkLayerAddress(MacAddress::from_bytes(&option[2..8]).ok()?)),
+            3 if length >= 32 => Some(NdpOption::PrefixInformation {
+                prefix_length:      option[2],
+                on_link:            option[3] & 0b1000_0000 != 0,
+                autonomous:         option[3] & 0b0100_0000 != 0,
+                valid_lifetime:     be_u32(option, 4),
+                preferred_lifetime: be_u32(option, 8),
+                prefix:             Ipv6Address::from_bytes(option[16..32].try_into().unwrap()),
+            }),
+            5 if length >= 8 => Some(NdpOption::Mtu(be_u32(option, 4))),
+            _ => Some(NdpOption::Unknown { kind, length }),
+        }
+    }
+}
+
+
+#[derive(Debug)]
+pub enum Message {
+    RouterSolicitation,
+    RouterAdvertisement { hop_limit: u8, managed: bool, other: bool, router_lifetime: u16, reachable_time: u32, retransmit_timer: u32 },
+    NeighborSolicitation { target: Ipv6Address },
+    NeighborAdvertisement { router: bool, solicited: bool, override_: bool, target: Ipv6Address },
+    EchoRequest { identifier: u16, sequence: u16 },
+    EchoReply { identifier: u16, sequence: u16 },
+    Other,
+}
+
+
+#[derive(Clone)]
+pub struct Icmpv6<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Icmpv6<'a> {
+    pub const HEADER_SIZE: usize = 4;
+
+    pub fn kind(&self)      -> u8  { self.data[0] }
+    pub fn code(&self)      -> u8  { self.data[1] }
+    pub fn check_sum(&self) -> u16 { be_u16(self.data, 2) }
+
+    /// The message body following type, code and checksum.
+    pub fn raw_payload(&self) -> &'a [u8] {
+        &self.data[Self::HEADER_SIZE..]
+    }
+
+    pub fn message(&self) -> Message {
+        let body = self.raw_payload();
+        match self.kind() {
+            TYPE_ROUTER_SOLICITATION => Message::RouterSolicitation,
+            TYPE_ROUTER_ADVERTISEMENT if body.len() >= 12 => Message::RouterAdvertisement {
+                hop_limit:        body[0],
+                managed:          body[1] & 0b1000_0000 != 0,
+                other:            body[1] & 0b0100_0000 != 0,
+                router_lifetime:  be_u16(body, 2),
+                reachable_time:   be_u32(body, 4),
+                retransmit_timer: be_u32(body, 8),
+            },
+            TYPE_NEIGHBOR_SOLICITATION if body.len() >= 20 => Message::NeighborSolicitation {
+                target: Ipv6Address::from_bytes(body[4..20].try_into().unwrap()),
+            },
+            TYPE_NEIGHBOR_ADVERTISEMENT if body.len() >= 20 => Message::NeighborAdvertisement {
+                router:    body[0] & 0b1000_0000 != 0,
+                solicited: body[0] & 0b0100_0000 != 0,
+                override_: body[0] & 0b0010_0000 != 0,
+                target:    Ipv6Address::from_bytes(body[4..20].try_into().unwrap()),
+            },
+            TYPE_ECHO_REQUEST if body.len() >= 4 => Message::EchoRequest { identifier: be_u16(body, 0), sequence: be_u16(body, 2) },
+            TYPE_ECHO_REPLY   if body.len() >= 4 => Message::EchoReply   { identifier: be_u16(body, 0), sequence: be_u16(body, 2) },
+            _ => Message::Other,
+        }
+    }
+
+    /// Neighbor Discovery options. Empty for messages that don't carry any.
+    pub fn options(&self) -> NdpOptionIter<'a> {
+        let offset = match self.kind() {
+            TYPE_ROUTER_SOLICITATION    => 4,
+            TYPE_ROUTER_ADVERTISEMENT   => 12,
+            TYPE_NEIGHBOR_SOLICITATION  => 20,
+            TYPE_NEIGHBOR_ADVERTISEMENT => 20,
+            TYPE_REDIRECT               => 36,
+            _ => usize::MAX,
+        };
+        let body = self.raw_payload();
+        NdpOptionIter {
+            data: body.get(offset..).unwrap_or(&[]),
+            index: 0,
+        }
+    }
+
+    /// Verifies the checksum over the IPv6 pseudo-header and the whole message.
+    pub fn verify_checksum(&self, source: &Ipv6Address, destination: &Ipv6Address) -> bool {
+        let length = self.data.len() as u32;
+
+        let mut sum = 0;
+        sum = checksum_add(sum, source.bytes());
+        sum = checksum_add(sum, destination.bytes());
+        sum = checksum_add(sum, &length.to_be_bytes());
+        sum = checksum_add(sum, &[0, 0, 0, NEXT_HEADER_ICMPV6]);
+        sum = checksum_add(sum, self.data);
+        checksum_finish(sum) == 0
+    }
+
+    pub fn from_bytes(data: &'a [u8]) -> Result<Self, ParseError> {
+        if data.len() < Self::HEADER_SIZE {
+            return Err(ParseError::TooSmall { layer: "Icmpv6", field: "data", expected: Self::HEADER_SIZE, got: data.len() });
+        }
+
+        let me = Self { data };
+        Ok(me)
+    }
+}
+
+
+impl<'a> core::fmt::Debug for Icmpv6<'a> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "    Icmpv6\n")?;
+        write!(f, "        type:      {:?}\n", self.kind())?;
+        write!(f, "        code:      {:?}\n", self.code())?;
+        write!(f, "        check_sum: {:?}\n", self.check_sum())?;
+        write!(f, "        message:   {:?}\n", self.message())?;
+        for (i, option) in self.options().enumerate() {
+            write!(f, "        option[{}]: {:?}\n", i, option)?;
+        }
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+
+    const ROUTER: [u8; 16] = [0xFE, 0x80, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1];
+    const HOST:   [u8; 16] = [0xFE, 0x80, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2];
+
+    /// An ICMPv6 message with a valid checksum from `source` to `destination`.
+    fn message(kind: u8, body: &[u8], source: &Ipv6Address, destination: &Ipv6Address) -> Vec<u8> {
+        let mut data = Vec::from([kind, 0, 0, 0]);
+        data.extend_from_slice(body);
+
+        let mut sum = 0;
+        sum = checksum_add(sum, source.bytes());
+        sum = checksum_add(sum, destination.bytes());
+        sum = checksum_add(sum, &(data.len() as u32).to_be_bytes());
+        sum = checksum_add(sum, &[0, 0, 0, NEXT_HEADER_ICMPV6]);
+        sum = checksum_add(sum, &data);
+        data[2..4].copy_from_slice(&checksum_finish(sum).to_be_bytes());
+        data
+    }
+
+    #[test]
+    fn parses_a_neighbor_solicitation() {
+        let (source, destination) = (Ipv6Address::from_bytes(HOST), Ipv6Address::from_bytes(ROUTER));
+        let mut body = Vec::from([0; 4]);
+        body.extend_from_slice(&ROUTER);
+        body.extend_from_slice(&[1, 1, 0x02, 0, 0, 0, 0, 0x0A]);
+        let data = message(TYPE_NEIGHBOR_SOLICITATION, &body, &source, &destination);
+
+        let packet = Icmpv6::from_bytes(&data).unwrap();
+        assert!(packet.verify_checksum(&source, &destination));
+        assert!(matches!(packet.message(), Message::NeighborSolicitation { target } if target.bytes() == &ROUTER));
+
+        let options: Vec<_> = packet.options().collect();
+        assert_eq!(options.len(), 1);
+        assert!(matches!(&options[0], NdpOption::SourceLinkLayerAddress(mac) if mac.bytes() == &[0x02, 0, 0, 0, 0, 0x0A]));
+    }
+
+    #[test]
+    fn parses_a_router_advertisement() {
+        let (source, destination) = (Ipv6Address::from_bytes(ROUTER), Ipv6Address::from_bytes(HOST));
+        let mut body = Vec::from([64, 0b1000_0000, 0x07, 0x08, 0, 0, 0x75, 0x30, 0, 0, 0x03, 0xE8]);
+        body.extend_from_slice(&[5, 1, 0, 0, 0, 0, 0x05, 0xDC]);
+        let data = message(TYPE_ROUTER_ADVERTISEMENT, &body, &source, &destination);
+
+        let packet = Icmpv6::from_bytes(&data).unwrap();
+        assert!(packet.verify_checksum(&source, &destination));
+        assert!(matches!(packet.message(), Message::RouterAdvertisement {
+            hop_limit: 64, managed: true, other: false, router_lifetime: 1800, reachable_time: 30_000, retransmit_timer: 1000,
+        }));
+        assert!(matches!(packet.options().collect::<Vec<_>>()[..], [NdpOption::Mtu(1500)]));
+    }
+
+    #[test]
+    fn rejects_a_checksum_over_other_addresses() {
+        let (source, destination) = (Ipv6Address::from_bytes(ROUTER), Ipv6Address::from_bytes(HOST));
+        let data = message(TYPE_ROUTER_SOLICITATION, &[0; 4], &source, &destination);
+        let packet = Icmpv6::from_bytes(&data).unwrap();
+        assert!(!packet.verify_checksum(&destination, &destination));
+    }
+}
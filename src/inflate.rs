@@ -0,0 +1,364 @@
+/*
+https://www.rfc-editor.org/rfc/rfc1951
+https://www.rfc-editor.org/rfc/rfc1952
+*/
+
+use crate::endian::{be_u32, le_u16, le_u32};
+use crate::shared::crc32;
+
+
+const MAX_BITS: usize = 15;
+
+const LENGTH_BASE:  [u16; 29] = [3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195, 227, 258];
+const LENGTH_EXTRA: [u8; 29]  = [0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0];
+const DISTANCE_BASE:  [u16; 30] = [1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073, 4097, 6145, 8193, 12289, 16385, 24577];
+const DISTANCE_EXTRA: [u8; 30]  = [0, 0, 0, 0, 1, 1, 2, 2, 3, 3, 4, 4, 5, 5, 6, 6, 7, 7, 8, 8, 9, 9, 10, 10, 11, 11, 12, 12, 13, 13];
+/// Order in which the code length code lengths of a dynamic block are stored.
+const CODE_LENGTH_ORDER: [usize; 19] = [16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15];
+
+const GZIP_MAGIC: [u8; 2] = [0x1F, 0x8B];
+const GZIP_METHOD_DEFLATE: u8 = 8;
+const GZIP_FLAG_HCRC    : u8 = 0x02;
+const GZIP_FLAG_EXTRA   : u8 = 0x04;
+const GZIP_FLAG_NAME    : u8 = 0x08;
+const GZIP_FLAG_COMMENT : u8 = 0x10;
+
+
+/// Reads bits least significant first, as DEFLATE packs them.
+struct Bits<'a> {
+    data: &'a [u8],
+    index: usize,
+    buffer: u32,
+    count: u32,
+}
+
+impl<'a> Bits<'a> {
+    fn bits(&mut self, n: u32) -> Result<u32, String> {
+        while self.count < n {
+            let byte = *self.data.get(self.index).ok_or("Deflate data ends unexpectedly")?;
+            self.buffer |= (byte as u32) << self.count;
+            self.index += 1;
+            self.count += 8;
+        }
+        let value = self.buffer & ((1u64 << n) - 1) as u32;
+        self.buffer >>= n;
+        self.count -= n;
+        Ok(value)
+    }
+
+    /// Drops the bits left of the current byte.
+    fn align(&mut self) {
+        self.buffer = 0;
+        self.count = 0;
+    }
+}
+
+
+/// A canonical Huffman code, stored as the number of codes of each length and the symbols in code order.
+struct Huffman {
+    counts: [u16; MAX_BITS + 1],
+    symbols: Vec<u16>,
+}
+
+impl Huffman {
+    fn new(lengths: &[u8]) -> Result<Self, String> {
+        let mut counts = [0u16; MAX_BITS + 1];
+        for length in lengths {
+            counts[*length as usize] += 1;
+        }
+
+        let mut offsets = [0u16; MAX_BITS + 2];
+        for length in 1..=MAX_BITS {
+            offsets[length + 1] = offsets[length] + counts[length];
+        }
+        let mut symbols = vec![0; lengths.len()];
+        for (symbol, length) in lengths.iter().enumerate() {
+            if *length != 0 {
+                symbols[offsets[*length as usize] as usize] = symbol as u16;
+                offsets[*length as usize] += 1;
+            }
+        }
+
+        counts[0] = 0;
+        Ok(Self { counts, symbols })
+    }
+
+    fn decode(&self, bits: &mut Bits) -> Result<u16, String> {
+        let mut code: i32 = 0;
+        let mut first: i32 = 0;
+        let mut index: i32 = 0;
+        for length in 1..=MAX_BITS {
+            code |= bits.bits(1)? as i32;
+            let count = self.counts[length] as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err("Invalid Huffman code in deflate data".to_string())
+    }
+}
+
+
+/// Decompresses raw DEFLATE data, failing once the output grows beyond `limit` bytes.
+pub fn inflate(data: &[u8], limit: usize) -> Result<Vec<u8>, String> {
+    let mut bits = Bits { data, index: 0, buffer: 0, count: 0 };
+    let mut output = Vec::new();
+
+    loop {
+        let last = bits.bits(1)? == 1;
+        match bits.bits(2)? {
+            0 => stored(&mut bits, &mut output, limit)?,
+            1 => {
+                let mut lengths = [0u8; 288];
+                lengths[..144].fill(8);
+                lengths[144..256].fill(9);
+                lengths[256..280].fill(7);
+                lengths[280..].fill(8);
+                let literals = Huffman::new(&lengths)?;
+                let distances = Huffman::new(&[5; 30])?;
+                codes(&mut bits, &mut output, &literals, &distances, limit)?;
+            },
+            2 => {
+                let (literals, distances) = dynamic(&mut bits)?;
+                codes(&mut bits, &mut output, &literals, &distances, limit)?;
+            },
+            _ => return Err("Invalid deflate block type".to_string()),
+        }
+        if last { break }
+    }
+
+    Ok(output)
+}
+
+fn too_large(limit: usize) -> String {
+    format!("Deflate data inflates beyond the limit of {} bytes", limit)
+}
+
+fn stored(bits: &mut Bits, output: &mut Vec<u8>, limit: usize) -> Result<(), String> {
+    bits.align();
+    let header = bits.data.get(bits.index..bits.index + 4).ok_or("Stored deflate block ends unexpectedly")?;
+    let length = le_u16(header, 0);
+    if length != !le_u16(header, 2) {
+        return Err("Stored deflate block length doesn't match its complement".to_string());
+    }
+    bits.index += 4;
+    let block = bits.data.get(bits.index..bits.index + length as usize).ok_or("Stored deflate block ends unexpectedly")?;
+    if output.len() + block.len() > limit { return Err(too_large(limit)) }
+    output.extend_from_slice(block);
+    bits.index += length as usize;
+    Ok(())
+}
+
+fn dynamic(bits: &mut Bits) -> Result<(Huffman, Huffman), String> {
+    let literal_count  = bits.bits(5)? as usize + 257;
+    let distance_count = bits.bits(5)? as usize + 1;
+    let code_count     = bits.bits(4)? as usize + 4;
+
+    let mut code_lengths = [0u8; 19];
+    for i in 0..code_count {
+        code_lengths[CODE_LENGTH_ORDER[i]] = bits.bits(3)? as u8;
+    }
+    let code_lengths = Huffman::new(&code_lengths)?;
+
+    let mut lengths = vec![0u8; literal_count + distance_count];
+    let mut i = 0;
+    while i < lengths.len() {
+        let symbol = code_lengths.decode(bits)?;
+        let (value, repeat) = match symbol {
+            0..=15 => (symbol as u8, 1),
+            16 => (*lengths.get(i.wrapping_sub(1)).ok_or("Deflate length repeat without a previous length")?, 3 + bits.bits(2)? as usize),
+            17 => (0, 3 + bits.bits(3)? as usize),
+            _  => (0, 11 + bits.bits(7)? as usize),
+        };
+        if i + repeat > lengths.len() { return Err("Deflate code lengths overflow".to_string()) }
+        lengths[i..i + repeat].fill(value);
+        i += repeat;
+    }
+
+    Ok((Huffman::new(&lengths[..literal_count])?, Huffman::new(&lengths[literal_count..])?))
+}
+
+fn codes(bits: &mut Bits, output: &mut Vec<u8>, literals: &Huffman, distances: &Huffman, limit: usize) -> Result<(), String> {
+    loop {
+        let symbol = literals.decode(bits)? as usize;
+        match symbol {
+            0..=255 => {
+                if output.len() >= limit { return Err(too_large(limit)) }
+                output.push(symbol as u8);
+            },
+            256 => return Ok(()),
+            _ => {
+                let index = symbol - 257;
+                if index >= LENGTH_BASE.len() { return Err("Invalid deflate length code".to_string()) }
+                let length = LENGTH_BASE[index] as usize + bits.bits(LENGTH_EXTRA[index] as u32)? as usize;
+
+                let index = distances.decode(bits)? as usize;
+                if index >= DISTANCE_BASE.len() { return Err("Invalid deflate distance code".to_string()) }
+                let distance = DISTANCE_BASE[index] as usize + bits.bits(DISTANCE_EXTRA[index] as u32)? as usize;
+                if distance > output.len() { return Err("Deflate distance reaches before the start of the output".to_string()) }
+                if output.len() + length > limit { return Err(too_large(limit)) }
+
+                // The copy may overlap the bytes it produces.
+                let start = output.len() - distance;
+                for i in 0..length {
+                    output.push(output[start + i]);
+                }
+            },
+        }
+    }
+}
+
+
+/// The Adler-32 checksum of zlib streams (RFC 1950).
+fn adler32(data: &[u8]) -> u32 {
+    let (mut a, mut b) = (1u32, 0u32);
+    for chunk in data.chunks(5552) {
+        for byte in chunk {
+            a += *byte as u32;
+            b += a;
+        }
+        a %= 65521;
+        b %= 65521;
+    }
+    b << 16 | a
+}
+
+
+/// Decompresses a zlib stream of at most `limit` bytes and verifies its checksum.
+pub fn unzlib(data: &[u8], limit: usize) -> Result<Vec<u8>, String> {
+    if data.len() < 6 || data[0] & 0x0F != GZIP_METHOD_DEFLATE || !(data[0] as u16 * 256 + data[1] as u16).is_multiple_of(31) {
+        return Err("Not zlib data".to_string());
+    }
+    if data[1] & 0x20 != 0 {
+        return Err("Zlib data with a preset dictionary is not supported".to_string());
+    }
+
+    let output = inflate(&data[2..data.len() - 4], limit)?;
+    let checksum = be_u32(data, data.len() - 4);
+    if adler32(&output) != checksum {
+        return Err("Zlib data doesn't match its checksum".to_string());
+    }
+    Ok(output)
+}
+
+
+/// Decompresses a gzip member of at most `limit` bytes and verifies its CRC and size.
+pub fn gunzip(data: &[u8], limit: usize) -> Result<Vec<u8>, String> {
+    if data.len() < 18 || data[..2] != GZIP_MAGIC {
+        return Err("Not gzip data".to_string());
+    }
+    if data[2] != GZIP_METHOD_DEFLATE {
+        return Err(format!("Unsupported gzip compression method {}", data[2]));
+    }
+
+    let flags = data[3];
+    let mut index = 10;
+    if flags & GZIP_FLAG_EXTRA != 0 {
+        if index + 2 > data.len() { return Err("Gzip header ends unexpectedly".to_string()) }
+        let length = le_u16(data, index);
+        index += 2 + length as usize;
+    }
+    for flag in [GZIP_FLAG_NAME, GZIP_FLAG_COMMENT] {
+        if flags & flag != 0 {
+            let end = data.get(index..).and_then(|x| x.iter().position(|x| *x == 0)).ok_or("Gzip header ends unexpectedly")?;
+            index += end + 1;
+        }
+    }
+    if flags & GZIP_FLAG_HCRC != 0 {
+        index += 2;
+    }
+    if index + 8 > data.len() {
+        return Err("Gzip header ends unexpectedly".to_string());
+    }
+
+    let output = inflate(&data[index..data.len() - 8], limit)?;
+    let trailer = &data[data.len() - 8..];
+    let crc  = le_u32(trailer, 0);
+    let size = le_u32(trailer, 4);
+    if crc32(&output) != crc || output.len() as u32 != size {
+        return Err("Gzip data doesn't match its checksum".to_string());
+    }
+    Ok(output)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Larger than anything the tests inflate.
+    const LIMIT: usize = 1 << 20;
+
+    /// `hello, hello, hello gzip\n` compressed by gzip.
+    const GZIP: [u8; 35] = [
+        0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x03, 0xcb, 0x48, 0xcd, 0xc9, 0xc9, 0xd7, 0x51, 0xc8,
+        0x40, 0xa2, 0x14, 0xd2, 0xab, 0x32, 0x0b, 0xb8, 0x00, 0x2a, 0x08, 0x44, 0xa6, 0x19, 0x00, 0x00, 0x00,
+    ];
+
+    #[test]
+    fn gunzips_and_checks_the_crc() {
+        assert_eq!(gunzip(&GZIP, LIMIT).unwrap(), b"hello, hello, hello gzip\n");
+
+        let mut corrupt = GZIP;
+        corrupt[27] ^= 1;
+        assert!(gunzip(&corrupt, LIMIT).is_err());
+        assert!(gunzip(&GZIP[..17], LIMIT).is_err());
+        assert!(gunzip(b"not gzip at all, not gzip at all", LIMIT).is_err());
+    }
+
+    #[test]
+    fn unzlibs_and_checks_the_header() {
+        let zlib = [0x78, 0x9c, 0x4b, 0x49, 0x4d, 0xcb, 0x49, 0x2c, 0x49, 0x4d, 0x51, 0x48, 0x41, 0x67, 0x00, 0x00, 0x85, 0xd0, 0x09, 0xec];
+        assert_eq!(unzlib(&zlib, LIMIT).unwrap(), b"deflated deflated deflated");
+        assert_eq!(inflate(&zlib[2..zlib.len() - 4], LIMIT).unwrap(), b"deflated deflated deflated");
+
+        let mut header = zlib;
+        header[1] = 0x9d;
+        assert!(unzlib(&header, LIMIT).is_err());
+        let mut checksum = zlib;
+        checksum[19] ^= 1;
+        assert!(unzlib(&checksum, LIMIT).is_err());
+    }
+
+    /// `Lorem ipsum dolor sit amet, consectetur adipiscing elit.\n` compressed by zlib 1.2.13 at level 9, which
+    /// picks a dynamic Huffman block for it.
+    const LOREM: &[u8] = b"Lorem ipsum dolor sit amet, consectetur adipiscing elit.\n";
+    const LOREM_ZLIB: [u8; 63] = [
+        0x78, 0xda, 0x05, 0xc1, 0xd1, 0x09, 0xc0, 0x20, 0x0c, 0x05, 0xc0, 0xff, 0x4e, 0xf1, 0x06, 0x28, 0x9d, 0xc4,
+        0x25, 0x24, 0x06, 0x79, 0x60, 0x8c, 0x24, 0x71, 0xff, 0xde, 0x35, 0x0f, 0x35, 0xf0, 0xe4, 0x35, 0x0c, 0x5f,
+        0x1e, 0x48, 0x16, 0xba, 0x69, 0xbd, 0x10, 0xdf, 0xa9, 0x52, 0x5a, 0x37, 0xd0, 0x07, 0x0f, 0x53, 0xb8, 0x27,
+        0x74, 0xb1, 0xbe, 0xe7, 0x07, 0x6a, 0x04, 0x15, 0x01,
+    ];
+    /// The same compressed by gzip with no name and a zero time.
+    const LOREM_GZIP: [u8; 75] = [
+        0x1f, 0x8b, 0x08, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x03, 0x05, 0xc1, 0xd1, 0x09, 0xc0, 0x20, 0x0c, 0x05,
+        0xc0, 0xff, 0x4e, 0xf1, 0x06, 0x28, 0x9d, 0xc4, 0x25, 0x24, 0x06, 0x79, 0x60, 0x8c, 0x24, 0x71, 0xff, 0xde,
+        0x35, 0x0f, 0x35, 0xf0, 0xe4, 0x35, 0x0c, 0x5f, 0x1e, 0x48, 0x16, 0xba, 0x69, 0xbd, 0x10, 0xdf, 0xa9, 0x52,
+        0x5a, 0x37, 0xd0, 0x07, 0x0f, 0x53, 0xb8, 0x27, 0x74, 0xb1, 0xbe, 0xe7, 0x07, 0x3a, 0xed, 0x29, 0xfa, 0x39,
+        0x00, 0x00, 0x00,
+    ];
+
+    #[test]
+    fn inflates_a_dynamic_huffman_block() {
+        // BTYPE is in bits 1 and 2 of the first byte of the block.
+        assert_eq!(LOREM_ZLIB[2] >> 1 & 0b11, 2);
+        assert_eq!(unzlib(&LOREM_ZLIB, LIMIT).unwrap(), LOREM);
+        assert_eq!(gunzip(&LOREM_GZIP, LIMIT).unwrap(), LOREM);
+    }
+
+    #[test]
+    fn stops_at_the_output_limit() {
+        assert_eq!(unzlib(&LOREM_ZLIB, LOREM.len()).unwrap(), LOREM);
+        assert_eq!(unzlib(&LOREM_ZLIB, LOREM.len() - 1).unwrap_err(), too_large(LOREM.len() - 1));
+        assert_eq!(gunzip(&LOREM_GZIP, 10).unwrap_err(), too_large(10));
+        assert_eq!(gunzip(&GZIP, 24).unwrap_err(), too_large(24));
+
+        // A final stored block of five bytes.
+        let stored = [0x01, 0x05, 0x00, 0xfa, 0xff, b'h', b'e', b'l', b'l', b'o'];
+        assert_eq!(inflate(&stored, 5).unwrap(), b"hello");
+        assert_eq!(inflate(&stored, 4).unwrap_err(), too_large(4));
+    }
+}
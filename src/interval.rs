@@ -0,0 +1,184 @@
+use std::collections::HashMap;
+use std::io::Error;
+use std::time::Duration;
+use pcap::Packet;
+use crate::count;
+use crate::ethernet::Ethernet;
+use crate::time_window;
+use crate::visitor::Visitor;
+
+
+/// How many source addresses a report lists.
+pub const TOP_TALKERS: usize = 5;
+
+
+/// The traffic of one interval, and of the capture up to its end.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Report {
+    /// Time since the Unix epoch the interval started at.
+    pub start: Duration,
+    pub packets: usize,
+    pub bytes: u64,
+    /// The source addresses that sent the most bytes in the interval, most first.
+    pub top_talkers: Vec<(String, u64)>,
+    pub total_packets: usize,
+    pub total_bytes: u64,
+}
+
+
+/// Counts packets and bytes per interval of capture time, keeping the totals across intervals. The intervals
+/// are aligned to the first packet, and intervals without packets aren't reported.
+#[derive(Debug)]
+pub struct IntervalStats {
+    interval: Duration,
+    start: Option<Duration>,
+    packets: usize,
+    bytes: u64,
+    talkers: HashMap<String, u64>,
+    total_packets: usize,
+    total_bytes: u64,
+}
+
+impl IntervalStats {
+    pub fn new(interval: Duration) -> Self {
+        Self { interval, start: None, packets: 0, bytes: 0, talkers: HashMap::new(), total_packets: 0, total_bytes: 0 }
+    }
+
+    /// Records a packet of `bytes` captured `timestamp` after the epoch, returning the report of the interval
+    /// it closed, if any. Packets timestamped before the current interval are counted in it.
+    pub fn push(&mut self, timestamp: Duration, bytes: u64, source: Option<String>) -> Option<Report> {
+        let start = *self.start.get_or_insert(timestamp);
+        let mut report = None;
+        if timestamp >= start + self.interval {
+            report = self.report();
+            let elapsed = (timestamp - start).as_secs_f64() / self.interval.as_secs_f64();
+            self.start = Some(start + self.interval.mul_f64(elapsed.floor()));
+        }
+
+        self.packets += 1;
+        self.bytes += bytes;
+        self.total_packets += 1;
+        self.total_bytes += bytes;
+        if let Some(source) = source {
+            *self.talkers.entry(source).or_insert(0) += bytes;
+        }
+        report
+    }
+
+    /// The report of the current interval, resetting its counters, or `None` if it has no packets.
+    pub fn report(&mut self) -> Option<Report> {
+        if self.packets == 0 { return None }
+
+        let mut top_talkers: Vec<(String, u64)> = std::mem::take(&mut self.talkers).into_iter().collect();
+        top_talkers.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+        top_talkers.truncate(TOP_TALKERS);
+
+        let report = Report {
+            start: self.start.unwrap_or_default(),
+            packets: self.packets,
+            bytes: self.bytes,
+            top_talkers,
+            total_packets: self.total_packets,
+            total_bytes: self.total_bytes,
+        };
+        self.packets = 0;
+        self.bytes = 0;
+        Some(report)
+    }
+}
+
+
+/// Prints a summary of the traffic every interval of capture time while passing each packet on to the inner
+/// visitor, so a long live capture shows its progress.
+pub struct IntervalVisitor {
+    inner: Box<dyn for<'a> Visitor<'a, ()>>,
+    stats: IntervalStats,
+}
+
+impl IntervalVisitor {
+    pub fn new(inner: Box<dyn for<'a> Visitor<'a, ()>>, interval: Duration) -> Self {
+        Self { inner, stats: IntervalStats::new(interval) }
+    }
+
+    fn push(&mut self, packet: &Packet, source: Option<String>) {
+        let timestamp = time_window::timestamp(packet.header);
+        if let Some(report) = self.stats.push(timestamp, packet.header.len as u64, source) {
+            print_report(&report);
+        }
+    }
+}
+
+fn print_report(report: &Report) {
+    println!("Interval {}.{:06}: {} packets, {} bytes (total {} packets, {} bytes)",
+        report.start.as_secs(), report.start.subsec_micros(), report.packets, report.bytes, report.total_packets, report.total_bytes);
+    for (source, bytes) in &report.top_talkers {
+        println!("    {:<40} {} bytes", source, bytes);
+    }
+}
+
+
+impl<'a> Visitor<'a, ()> for IntervalVisitor {
+    fn visit_packet(&mut self, packet: &'a Packet) -> Result<(), Error> {
+        let source = Ethernet::from_bytes(packet.data).ok()
+            .and_then(|frame| count::addresses(&frame))
+            .map(|(source, _)| source);
+        self.push(packet, source);
+        self.inner.visit_packet(packet)
+    }
+
+    /// The talkers are taken from Ethernet frames, so other frames only count towards the totals.
+    fn visit_radiotap_packet(&mut self, packet: &'a Packet) -> Result<(), Error> {
+        self.push(packet, None);
+        self.inner.visit_radiotap_packet(packet)
+    }
+
+    fn visit_sll2_packet(&mut self, packet: &'a Packet) -> Result<(), Error> {
+        self.push(packet, None);
+        self.inner.visit_sll2_packet(packet)
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        self.inner.finish()?;
+        if let Some(report) = self.stats.report() {
+            print_report(&report);
+        }
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(millis: u64) -> Duration {
+        Duration::from_secs(1_700_000_000) + Duration::from_millis(millis)
+    }
+
+    #[test]
+    fn reports_each_interval_and_the_running_totals() {
+        let mut stats = IntervalStats::new(Duration::from_secs(1));
+        let mut reports = Vec::new();
+        let packets = [(0, 100, "10.0.0.1"), (400, 200, "10.0.0.2"), (900, 300, "10.0.0.2"), (1_100, 50, "10.0.0.3"), (1_999, 60, "10.0.0.1"), (2_000, 70, "10.0.0.1")];
+        for (millis, bytes, source) in packets {
+            reports.extend(stats.push(at(millis), bytes, Some(source.to_string())));
+        }
+
+        assert_eq!(reports, [
+            Report { start: at(0), packets: 3, bytes: 600, top_talkers: vec![("10.0.0.2".to_string(), 500), ("10.0.0.1".to_string(), 100)], total_packets: 3, total_bytes: 600 },
+            Report { start: at(1_000), packets: 2, bytes: 110, top_talkers: vec![("10.0.0.1".to_string(), 60), ("10.0.0.3".to_string(), 50)], total_packets: 5, total_bytes: 710 },
+        ]);
+        let last = stats.report().unwrap();
+        assert_eq!((last.start, last.packets, last.total_packets, last.total_bytes), (at(2_000), 1, 6, 780));
+        assert_eq!(stats.report(), None);
+    }
+
+    #[test]
+    fn skips_intervals_without_packets() {
+        let mut stats = IntervalStats::new(Duration::from_secs(10));
+        assert_eq!(stats.push(at(0), 100, None), None);
+        let report = stats.push(at(35_000), 100, None).unwrap();
+        assert_eq!((report.start, report.packets), (at(0), 1));
+        assert_eq!(stats.report().unwrap().start, at(30_000));
+    }
+}
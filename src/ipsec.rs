@@ -0,0 +1,199 @@
+/*
+https://www.rfc-editor.org/rfc/rfc4302
+https://www.rfc-editor.org/rfc/rfc4303
+*/
+
+use crate::tcp;
+use crate::udp;
+use crate::ipv4;
+use crate::ipv6;
+use crate::error::ParseError;
+use crate::endian::be_u32;
+
+
+pub const NEXT_HEADER_IPV4 : u8 = 4;
+pub const NEXT_HEADER_TCP  : u8 = 6;
+pub const NEXT_HEADER_UDP  : u8 = 17;
+pub const NEXT_HEADER_IPV6 : u8 = 41;
+
+
+#[derive(Debug)]
+pub enum Payload<'a> {
+    Tcp(tcp::Tcp<'a>),
+    Udp(udp::Udp<'a>),
+    /// Tunnel mode.
+    IPv4(ipv4::IPv4<'a>),
+    IPv6(ipv6::IPv6<'a>),
+    Unknown(&'a [u8]),
+}
+
+
+/// An Authentication Header. It signs the packet but leaves it readable, so the protocol it protects can
+/// still be decoded.
+#[derive(Clone)]
+pub struct Ah<'a> {
+    data: &'a [u8],
+    /// Number of tunnels the enclosing IP header is nested in.
+    depth: usize,
+}
+
+impl<'a> Ah<'a> {
+    pub const MIN_HEADER_SIZE: usize = 12;
+
+    pub fn next_header(&self)     -> u8  { self.data[0] }
+    /// Length of the header in 4 byte units, minus 2.
+    pub fn payload_length(&self)  -> u8  { self.data[1] }
+    pub fn spi(&self)             -> u32 { be_u32(self.data, 4) }
+    pub fn sequence_number(&self) -> u32 { be_u32(self.data, 8) }
+
+    pub fn header_size(&self) -> usize { (self.payload_length() as usize + 2) * 4 }
+
+    /// The Integrity Check Value following the fixed fields.
+    pub fn icv(&self) -> &'a [u8] {
+        &self.data[Self::MIN_HEADER_SIZE..self.header_size()]
+    }
+
+    pub fn raw_payload(&self) -> &'a [u8] {
+        &self.data[self.header_size()..]
+    }
+
+    pub fn payload(&self) -> Result<Payload<'a>, ParseError> {
+        let data = self.raw_payload();
+        match self.next_header() {
+            NEXT_HEADER_TCP  => Ok(Payload::Tcp(tcp::Tcp::from_bytes(data)?)),
+            NEXT_HEADER_UDP  => Ok(Payload::Udp(udp::Udp::from_bytes_nested(data, self.depth)?)),
+            NEXT_HEADER_IPV4 => Ok(Payload::IPv4(ipv4::IPv4::from_bytes_nested(data, self.depth + 1)?)),
+            NEXT_HEADER_IPV6 => Ok(Payload::IPv6(ipv6::IPv6::from_bytes_nested(data, self.depth + 1)?)),
+            _ => Ok(Payload::Unknown(data)),
+        }
+    }
+
+    pub fn from_bytes(data: &'a [u8]) -> Result<Self, ParseError> {
+        Self::from_bytes_nested(data, 0)
+    }
+
+    /// Parses a header whose IP header is encapsulated in `depth` tunnels.
+    pub fn from_bytes_nested(data: &'a [u8], depth: usize) -> Result<Self, ParseError> {
+        if data.len() < Self::MIN_HEADER_SIZE {
+            return Err(ParseError::TooSmall { layer: "Ah", field: "data", expected: Self::MIN_HEADER_SIZE, got: data.len() });
+        }
+
+        let me = Self { data, depth };
+        if me.header_size() < Self::MIN_HEADER_SIZE {
+            return Err(ParseError::TooSmall { layer: "Ah", field: "payload length", expected: Self::MIN_HEADER_SIZE, got: me.header_size() });
+        }
+        if me.header_size() > data.len() {
+            return Err(ParseError::TooBig { layer: "Ah", field: "payload length", expected: data.len(), got: me.header_size() });
+        }
+
+        Ok(me)
+    }
+}
+
+
+impl<'a> core::fmt::Debug for Ah<'a> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "    Ah\n")?;
+        write!(f, "        next_header:     {:?}\n", self.next_header())?;
+        write!(f, "        payload_length:  {:?}\n", self.payload_length())?;
+        write!(f, "        spi:             {:#010x}\n", self.spi())?;
+        write!(f, "        sequence_number: {:?}\n", self.sequence_number())?;
+        write!(f, "        payload:         {:?}\n", self.payload())?;
+        Ok(())
+    }
+}
+
+
+/// An Encapsulating Security Payload. Everything after the sequence number is encrypted, so decoding stops here.
+#[derive(Clone)]
+pub struct Esp<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Esp<'a> {
+    pub const HEADER_SIZE: usize = 8;
+
+    pub fn spi(&self)             -> u32 { be_u32(self.data, 0) }
+    pub fn sequence_number(&self) -> u32 { be_u32(self.data, 4) }
+
+    /// The encrypted payload, padding, trailer and Integrity Check Value.
+    pub fn raw_payload(&self) -> &'a [u8] {
+        &self.data[Self::HEADER_SIZE..]
+    }
+
+    pub fn from_bytes(data: &'a [u8]) -> Result<Self, ParseError> {
+        if data.len() < Self::HEADER_SIZE {
+            return Err(ParseError::TooSmall { layer: "Esp", field: "data", expected: Self::HEADER_SIZE, got: data.len() });
+        }
+
+        Ok(Self { data })
+    }
+}
+
+
+impl<'a> core::fmt::Debug for Esp<'a> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "    Esp\n")?;
+        write!(f, "        spi:             {:#010x}\n", self.spi())?;
+        write!(f, "        sequence_number: {:?}\n", self.sequence_number())?;
+        write!(f, "        encrypted:       {} bytes\n", self.raw_payload().len())?;
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+    use crate::builder::{Ipv4Builder, TcpBuilder};
+    use crate::ipv4::{IPv4, Protocol};
+    use crate::testing::ip;
+
+    /// An AH with SPI 0x1000, sequence number 7 and a 12 byte ICV in front of `payload`.
+    fn ah(next_header: u8, payload: &[u8]) -> Vec<u8> {
+        let mut data = Vec::from([next_header, 4, 0, 0, 0, 0, 0x10, 0, 0, 0, 0, 7]);
+        data.extend_from_slice(&[0xAA; 12]);
+        data.extend_from_slice(payload);
+        data
+    }
+
+    #[test]
+    fn decodes_the_ipv4_packet_protected_by_an_ah() {
+        let inner = Ipv4Builder::new(ip(192, 168, 0, 1), ip(192, 168, 0, 2)).with_tcp(&TcpBuilder::new(51000, 22)).build();
+        let outer = Ipv4Builder::new(ip(10, 0, 0, 1), ip(10, 0, 0, 2)).with_payload(Protocol::AH, &ah(NEXT_HEADER_IPV4, &inner)).build();
+        let outer = IPv4::from_bytes(&outer).unwrap();
+
+        let Ok(ipv4::Payload::Ah(ah)) = outer.payload() else { panic!("protocol 51 isn't decoded as AH") };
+        assert_eq!(ah.spi(), 0x1000);
+        assert_eq!(ah.sequence_number(), 7);
+        assert_eq!(ah.header_size(), 24);
+        assert_eq!(ah.icv(), [0xAA; 12]);
+
+        let Ok(Payload::IPv4(inner)) = ah.payload() else { panic!("the AH doesn't lead to IPv4") };
+        assert_eq!(inner.source_address(), ip(192, 168, 0, 1));
+        let Ok(ipv4::Payload::Tcp(tcp)) = inner.payload() else { panic!("the inner packet isn't TCP") };
+        assert_eq!(tcp.destination_port(), 22);
+    }
+
+    #[test]
+    fn stops_at_the_encrypted_payload_of_an_esp() {
+        let mut esp = Vec::from([0xC0, 0xFF, 0xEE, 0x01, 0, 0, 0, 3]);
+        esp.extend_from_slice(&[0x5A; 48]);
+        let packet = Ipv4Builder::new(ip(10, 0, 0, 1), ip(10, 0, 0, 2)).with_payload(Protocol::ESP, &esp).build();
+        let packet = IPv4::from_bytes(&packet).unwrap();
+
+        let Ok(ipv4::Payload::Esp(esp)) = packet.payload() else { panic!("protocol 50 isn't decoded as ESP") };
+        assert_eq!(esp.spi(), 0xC0FFEE01);
+        assert_eq!(esp.sequence_number(), 3);
+        assert_eq!(esp.raw_payload().len(), 48);
+    }
+
+    #[test]
+    fn rejects_an_ah_longer_than_its_packet() {
+        let data = ah(NEXT_HEADER_TCP, &[]);
+        assert!(matches!(Ah::from_bytes(&data[..20]), Err(ParseError::TooBig { layer: "Ah", expected: 20, got: 24, .. })));
+        assert!(matches!(Ah::from_bytes(&data[..8]), Err(ParseError::TooSmall { layer: "Ah", .. })));
+        assert!(matches!(Esp::from_bytes(&[0; 7]), Err(ParseError::TooSmall { layer: "Esp", .. })));
+    }
+}
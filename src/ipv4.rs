@@ -1,19 +1,18 @@
-use std::io::{Error, ErrorKind};
-use std::ops::{Index, Range};
-use std::path::Iter;
+use alloc::{format, string::String, vec::Vec};
+use core::ops::{Index, Range};
 use crate::tcp;
-
-
-
-// @NOTE(ted): Assuming big endian (network endian) to little endian (hardware endian).
-fn u8(data: &[u8],  i: usize) -> u8  { unsafe { (*data.get_unchecked(i+0)) } }
-fn u16(data: &[u8], i: usize) -> u16 { unsafe { (*data.get_unchecked(i+1) as u16) << 8  | (*data.get_unchecked(i+0) as u16) << 0 } }
-fn u32(data: &[u8], i: usize) -> u32 { unsafe { (*data.get_unchecked(i+3) as u32) << 24 | (*data.get_unchecked(i+2) as u32) << 16 | (*data.get_unchecked(i+1) as u32) << 8 | (*data.get_unchecked(i) as u32) << 0 } }
-fn u64(data: &[u8], i: usize) -> u64 { unsafe { (*data.get_unchecked(i+7) as u64) << 56 | (*data.get_unchecked(i+6) as u64) << 48 | (*data.get_unchecked(i+5) as u64) << 40 | (*data.get_unchecked(i+4) as u64) << 32 | (*data.get_unchecked(i+3) as u64) << 24 | (*data.get_unchecked(i+2) as u64) << 16 | (*data.get_unchecked(i+1) as u64) << 8 | (*data.get_unchecked(i) as u64) << 0 } }
-
-
-
-#[derive(Copy, Clone)]
+use crate::udp;
+use crate::gre;
+use crate::sctp;
+use crate::icmp;
+use crate::ipsec;
+use crate::ethernet;
+use crate::error::ParseError;
+use crate::shared::{bytes_of_bits, checksum_add, checksum_finish};
+use crate::endian::{be_u16, be_u32};
+
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash, Ord, PartialOrd)]
 pub struct Ipv4Address {
     data: [u8; 4]
 }
@@ -22,10 +21,32 @@ impl Ipv4Address {
     pub fn from_bytes(bytes: [u8; 4]) -> Self {
         Self { data: bytes }
     }
+
+    pub fn bytes(&self) -> &[u8; 4] {
+        &self.data
+    }
 }
 
-impl std::fmt::Debug for Ipv4Address {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+impl core::str::FromStr for Ipv4Address {
+    type Err = String;
+
+    /// Parses dotted decimal notation, e.g. `10.0.0.1`.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut data = [0; 4];
+        let mut parts = text.split('.');
+        for byte in data.iter_mut() {
+            let part = parts.next().ok_or(format!("Invalid Ipv4 address '{}'", text))?;
+            *byte = part.parse().map_err(|_| format!("Invalid Ipv4 address '{}'", text))?;
+        }
+        if parts.next().is_some() {
+            return Err(format!("Invalid Ipv4 address '{}'", text));
+        }
+        Ok(Self { data })
+    }
+}
+
+impl core::fmt::Debug for Ipv4Address {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
         write!(f, "{}.{}.{}.{}", self.data[0], self.data[1], self.data[2], self.data[3])?;
         Ok(())
     }
@@ -34,19 +55,46 @@ impl std::fmt::Debug for Ipv4Address {
 
 
 // https://en.wikipedia.org/wiki/List_of_IP_protocol_numbers
-#[derive(Debug)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
 pub enum Protocol {
-    Unknown = 0x92,  // Unassigned
-    TCP = 6,
-    UDP = 17
+    ICMP,
+    IGMP,
+    TCP,
+    UDP,
+    GRE,
+    ESP,
+    AH,
+    SCTP,
+    /// Any other protocol number.
+    Other(u8),
 }
 
 impl Protocol {
-    pub fn from_value(value: u32) -> Self {
+    pub fn from_value(value: u8) -> Self {
         match value {
-            6  => Self::TCP,
-            17 => Self::UDP,
-            _  => Self::Unknown
+            1   => Self::ICMP,
+            2   => Self::IGMP,
+            6   => Self::TCP,
+            17  => Self::UDP,
+            47  => Self::GRE,
+            50  => Self::ESP,
+            51  => Self::AH,
+            132 => Self::SCTP,
+            _   => Self::Other(value),
+        }
+    }
+
+    pub fn value(&self) -> u8 {
+        match self {
+            Self::ICMP  => 1,
+            Self::IGMP  => 2,
+            Self::TCP   => 6,
+            Self::UDP   => 17,
+            Self::GRE   => 47,
+            Self::ESP   => 50,
+            Self::AH    => 51,
+            Self::SCTP  => 132,
+            Self::Other(value) => *value,
         }
     }
 }
@@ -54,10 +102,163 @@ impl Protocol {
 #[derive(Debug)]
 pub enum Payload<'a> {
     Tcp(tcp::Tcp<'a>),
+    Udp(udp::Udp<'a>),
+    Gre(gre::Gre<'a>),
+    Sctp(sctp::Sctp<'a>),
+    Ah(ipsec::Ah<'a>),
+    Esp(ipsec::Esp<'a>),
+    Icmp(icmp::Icmp<'a>),
+    Unknown(&'a [u8]),
 }
 
 
 
+/// A decoded Ipv4 option, https://www.iana.org/assignments/ip-parameters.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Ipv4Option<'a> {
+    NoOperation,
+    /// The addresses recorded so far, `pointer` is the 1-based offset of the next free slot.
+    RecordRoute { pointer: u8, route: Vec<Ipv4Address> },
+    LooseSourceRoute { pointer: u8, route: Vec<Ipv4Address> },
+    StrictSourceRoute { pointer: u8, route: Vec<Ipv4Address> },
+    /// `flags` 0 means bare timestamps, 1 and 3 mean each timestamp follows an address.
+    Timestamp { pointer: u8, overflow: u8, flags: u8, entries: Vec<(Option<Ipv4Address>, u32)> },
+    /// Asks routers to look at the packet, e.g. for IGMP. A value of 0 means that.
+    RouterAlert { value: u16 },
+    Security { data: &'a [u8] },
+    StreamId { id: u16 },
+    Unknown { kind: u8, data: &'a [u8] },
+    /// A known option whose length doesn't fit its format.
+    Malformed { kind: u8, length: usize },
+}
+
+impl<'a> Ipv4Option<'a> {
+    pub const END_OF_OPTIONS: u8 = 0;
+    pub const NO_OPERATION: u8 = 1;
+    pub const RECORD_ROUTE: u8 = 7;
+    pub const TIMESTAMP: u8 = 68;
+    pub const SECURITY: u8 = 130;
+    pub const LOOSE_SOURCE_ROUTE: u8 = 131;
+    pub const STREAM_ID: u8 = 136;
+    pub const STRICT_SOURCE_ROUTE: u8 = 137;
+    pub const ROUTER_ALERT: u8 = 148;
+
+    pub fn name(kind: u8) -> &'static str {
+        match kind {
+            Self::END_OF_OPTIONS      => "End of Options",
+            Self::NO_OPERATION        => "No Operation",
+            Self::RECORD_ROUTE        => "Record Route",
+            Self::TIMESTAMP           => "Timestamp",
+            Self::SECURITY            => "Security",
+            Self::LOOSE_SOURCE_ROUTE  => "Loose Source Route",
+            Self::STREAM_ID           => "Stream Id",
+            Self::STRICT_SOURCE_ROUTE => "Strict Source Route",
+            Self::ROUTER_ALERT        => "Router Alert",
+            _ => "Unknown",
+        }
+    }
+
+    /// Decodes the option `kind` from its `data`, the bytes after the kind and length.
+    fn parse(kind: u8, data: &'a [u8]) -> Self {
+        let address = |i: usize| Ipv4Address::from_bytes(data[i..i + 4].try_into().unwrap());
+        let malformed = Self::Malformed { kind, length: data.len() + 2 };
+
+        match kind {
+            Self::RECORD_ROUTE | Self::LOOSE_SOURCE_ROUTE | Self::STRICT_SOURCE_ROUTE => {
+                if data.is_empty() || !(data.len() - 1).is_multiple_of(4) { return malformed }
+                let pointer = data[0];
+                // Only the slots before the pointer are filled in.
+                let filled = ((pointer as usize).saturating_sub(4) / 4).min((data.len() - 1) / 4);
+                let route = (0..filled).map(|i| address(1 + i * 4)).collect();
+                match kind {
+                    Self::RECORD_ROUTE       => Self::RecordRoute { pointer, route },
+                    Self::LOOSE_SOURCE_ROUTE => Self::LooseSourceRoute { pointer, route },
+                    _                        => Self::StrictSourceRoute { pointer, route },
+                }
+            },
+            Self::TIMESTAMP => {
+                if data.len() < 2 { return malformed }
+                let (pointer, overflow, flags) = (data[0], data[1] >> 4, data[1] & 0x0F);
+                let entry_size = if flags == 0 { 4 } else { 8 };
+                if !(data.len() - 2).is_multiple_of(entry_size) || !matches!(flags, 0 | 1 | 3) { return malformed }
+                let filled = ((pointer as usize).saturating_sub(5) / entry_size).min((data.len() - 2) / entry_size);
+                let entries = (0..filled).map(|i| {
+                    let offset = 2 + i * entry_size;
+                    if flags == 0 { (None, be_u32(data, offset)) } else { (Some(address(offset)), be_u32(data, offset + 4)) }
+                }).collect();
+                Self::Timestamp { pointer, overflow, flags, entries }
+            },
+            Self::ROUTER_ALERT if data.len() == 2 => Self::RouterAlert { value: be_u16(data, 0) },
+            Self::STREAM_ID    if data.len() == 2 => Self::StreamId { id: be_u16(data, 0) },
+            Self::SECURITY     if data.len() == 9 => Self::Security { data },
+            Self::ROUTER_ALERT | Self::STREAM_ID | Self::SECURITY => malformed,
+            _ => Self::Unknown { kind, data },
+        }
+    }
+}
+
+impl<'a> core::fmt::Display for Ipv4Option<'a> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        let route = |f: &mut core::fmt::Formatter, name: &str, pointer: &u8, route: &Vec<Ipv4Address>| {
+            write!(f, "{} (pointer {}):", name, pointer)?;
+            for address in route { write!(f, " {:?}", address)? }
+            Ok(())
+        };
+        match self {
+            Ipv4Option::NoOperation                          => write!(f, "No Operation"),
+            Ipv4Option::RecordRoute { pointer, route: r }       => route(f, "Record Route", pointer, r),
+            Ipv4Option::LooseSourceRoute { pointer, route: r }  => route(f, "Loose Source Route", pointer, r),
+            Ipv4Option::StrictSourceRoute { pointer, route: r } => route(f, "Strict Source Route", pointer, r),
+            Ipv4Option::Timestamp { pointer, overflow, flags, entries } => {
+                write!(f, "Timestamp (pointer {}, overflow {}, flags {}):", pointer, overflow, flags)?;
+                for (address, timestamp) in entries {
+                    match address {
+                        Some(address) => write!(f, " {:?}={}", address, timestamp)?,
+                        None          => write!(f, " {}", timestamp)?,
+                    }
+                }
+                Ok(())
+            },
+            Ipv4Option::RouterAlert { value }   => write!(f, "Router Alert ({})", value),
+            Ipv4Option::Security { data }       => write!(f, "Security ({} bytes)", data.len()),
+            Ipv4Option::StreamId { id }         => write!(f, "Stream Id ({})", id),
+            Ipv4Option::Unknown { kind, data }  => write!(f, "Option {} ({} bytes)", kind, data.len()),
+            Ipv4Option::Malformed { kind, length } => write!(f, "{} ({}) with invalid length {}", Ipv4Option::name(*kind), kind, length),
+        }
+    }
+}
+
+
+/// Iterates over the options of an Ipv4 header. Stops at End of Options or at an option whose length
+/// doesn't fit, which `IPv4::validate_options` reports.
+pub struct Ipv4OptionIter<'a> {
+    data: &'a [u8],
+    index: usize,
+}
+
+impl<'a> Iterator for Ipv4OptionIter<'a> {
+    type Item = Ipv4Option<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let start = self.index;
+        let kind = *self.data.get(start)?;
+        match kind {
+            Ipv4Option::END_OF_OPTIONS => return None,
+            Ipv4Option::NO_OPERATION => {
+                self.index += 1;
+                return Some(Ipv4Option::NoOperation);
+            },
+            _ => {},
+        }
+
+        let length = *self.data.get(start + 1)? as usize;
+        if length < 2 || start + length > self.data.len() { return None }
+        self.index = start + length;
+        Some(Ipv4Option::parse(kind, &self.data[start + 2..start + length]))
+    }
+}
+
+
 struct BitArray<'a> {
     data: &'a [u8],
 }
@@ -67,20 +268,31 @@ impl<'a> BitArray<'a> {
         Self { data }
     }
 
-    pub fn at(&self, range: Range<usize>) -> Result<usize, Error> {
-        let mut result = match range.len() {
-            0..=8 => u8(&self.data, range.start / 8) as usize,
-            9..=16 => u16(&self.data, range.start / 8) as usize,
-            17..=32 => u32(&self.data, range.start / 8) as usize,
-            33..=64 => u64(&self.data, range.start / 8) as usize,
-            _ => return Err(Error::new(ErrorKind::Other, format!("Invalid range, must be 1-64, got {}", range.len())))
-        };
+    /// The bits in `range`, counting from the most significant bit of the first byte like the `*_BITS`
+    /// constants. `None` if the range is empty, wider than 64 bits or reaches past the data.
+    pub fn get(&self, range: Range<usize>) -> Option<u64> {
+        if range.is_empty() || range.len() > 64 || range.end > self.data.len() * 8 {
+            return None;
+        }
+
+        // A 64 bit range that isn't byte aligned spans 9 bytes, so collect them in a u128 to keep every shift
+        // below the width.
+        let first = range.start / 8;
+        let last  = (range.end - 1) / 8;
+        let mut value = 0u128;
+        for byte in &self.data[first..=last] {
+            value = value << 8 | *byte as u128;
+        }
 
-        let start = range.start % 8;
-        let end = range.end % 8;
+        let trailing = (last + 1) * 8 - range.end;
+        let mask = (1u128 << range.len()) - 1;
+        Some(((value >> trailing) & mask) as u64)
+    }
 
-        result = result >> start & 0xFFFF_FFFF_FFFF_FFFF >> (8 - end);
-        Ok(result)
+    pub fn at(&self, range: Range<usize>) -> Result<usize, ParseError> {
+        self.get(range)
+            .map(|x| x as usize)
+            .ok_or(ParseError::Invalid("Bit range must be 1-64 bits within the data"))
     }
 }
 
@@ -88,6 +300,8 @@ impl<'a> BitArray<'a> {
 #[derive(Clone)]
 pub struct IPv4<'a> {
     data: &'a [u8],
+    /// Number of tunnels this header is nested in.
+    depth: usize,
 }
 
 impl<'a> IPv4<'a> {
@@ -106,10 +320,9 @@ impl<'a> IPv4<'a> {
     pub const DESTINATION_ADDRESS_BITS : Range<usize> = 128..160;
 
 
-    // @NOTE(ted): Assuming big endian (network endian) to little endian (hardware endian).
-    fn u8(&self,  i: usize) -> u8  { unsafe { (*self.data.get_unchecked(i+0)) } }
-    fn u16(&self, i: usize) -> u16 { unsafe { (*self.data.get_unchecked(i+1) as u16) << 8  | (*self.data.get_unchecked(i+0) as u16) << 0 } }
-    fn u32(&self, i: usize) -> u32 { unsafe { (*self.data.get_unchecked(i+3) as u32) << 24 | (*self.data.get_unchecked(i+2) as u32) << 16 | (*self.data.get_unchecked(i+1) as u32) << 8 | (*self.data.get_unchecked(i) as u32) << 0 } }
+    fn u8(&self,  i: usize) -> u8  { self.data[i] }
+    fn u16(&self, i: usize) -> u16 { be_u16(self.data, i) }
+    fn address(&self, i: usize) -> Ipv4Address { Ipv4Address::from_bytes(self.data[i..i + 4].try_into().unwrap()) }
 
     /// Version is always 4.
     pub fn version(&self) -> u8 { 4 }
@@ -117,7 +330,11 @@ impl<'a> IPv4<'a> {
     pub fn version_raw(&self)    -> u8 { (self.u8(0) & 0b1111_0000) >> 4 }
     pub fn header_length(&self)  -> u8 { (self.u8(0) & 0b0000_1111) >> 0 }
 
-    // TODO: Check DSCP/ECN
+    /// Differentiated Services code point, RFC 2474. It replaced the type of service bits below.
+    pub fn dscp(&self) -> u8 { (self.u8(1) & 0b1111_1100) >> 2 }
+    /// Explicit Congestion Notification, RFC 3168.
+    pub fn ecn(&self)  -> u8 { (self.u8(1) & 0b0000_0011) >> 0 }
+
     pub fn reserved1(&self)     -> u8 { (self.u8(1) & 0b0000_0001) >> 0 }
     pub fn cost(&self)          -> u8 { (self.u8(1) & 0b0000_0010) >> 1 }
     pub fn reliability(&self)   -> u8 { (self.u8(1) & 0b0000_0100) >> 2 }
@@ -128,51 +345,167 @@ impl<'a> IPv4<'a> {
     pub fn total_length(&self)   -> u16 { self.u16(2) }
     pub fn identification(&self) -> u16 { self.u16(4) }
 
-    pub fn reserved2(&self) -> u8 { (self.u8(6) & 0b0000_0001) >> 0 }
-    pub fn df(&self)        -> u8 { (self.u8(6) & 0b0000_0001) >> 0 }
-    pub fn mf(&self)        -> u8 { (self.u8(6) & 0b0000_0001) >> 0 }
-    pub fn fragment_offset(&self) -> u16 { (self.u8(7) as u16) | (self.u8(6) as u16 & 0b0001_1111) }
+    pub fn reserved2(&self) -> u8 { (self.u8(6) & 0b1000_0000) >> 7 }
+    pub fn df(&self)        -> u8 { (self.u8(6) & 0b0100_0000) >> 6 }
+    pub fn mf(&self)        -> u8 { (self.u8(6) & 0b0010_0000) >> 5 }
+    /// Offset of this fragment in units of 8 bytes.
+    pub fn fragment_offset(&self) -> u16 { self.u16(6) & 0b0001_1111_1111_1111 }
+    pub fn fragment_offset_bytes(&self) -> usize { self.fragment_offset() as usize * 8 }
+
+    /// Whether this is one fragment of a larger datagram.
+    pub fn is_fragment(&self) -> bool { self.mf() != 0 || self.fragment_offset() != 0 }
 
     pub fn time_to_live(&self)        -> u8       { (self.u8(8))   }
-    pub fn protocol(&self)            -> Protocol { Protocol::from_value(self.u8(9) as u32) }
+    pub fn protocol(&self)            -> Protocol { Protocol::from_value(self.u8(9)) }
+    pub fn protocol_raw(&self)        -> u8       { self.u8(9) }
     pub fn header_checksum(&self)     -> u16      { (self.u16(10)) }
-    pub fn source_address(&self)      -> Ipv4Address { Ipv4Address::from_bytes(unsafe { std::mem::transmute(self.u32(12)) }) }
-    pub fn destination_address(&self) -> Ipv4Address { Ipv4Address::from_bytes(unsafe { std::mem::transmute(self.u32(16)) }) }
+    pub fn source_address(&self)      -> Ipv4Address { self.address(12) }
+    pub fn destination_address(&self) -> Ipv4Address { self.address(16) }
+
+
+    pub fn has_options(&self) -> bool { self.header_length() > 5 }
+
+    /// The option bytes between the fixed 20 byte header and the payload, including any padding.
+    pub fn raw_options(&self) -> &'a [u8] {
+        self.data.get(20..self.header_size()).unwrap_or(&[])
+    }
+
+    pub fn options(&self) -> Ipv4OptionIter<'a> {
+        Ipv4OptionIter { data: self.raw_options(), index: 0 }
+    }
+
+    /// Checks that the options exactly fill the space declared by the header length. Everything after an
+    /// End of Options List is padding.
+    pub fn validate_options(&self) -> Result<(), ParseError> {
+        let options = self.raw_options();
+        if options.len() + 20 != self.header_size() {
+            return Err(ParseError::TooSmall { layer: "Ipv4", field: "captured options", expected: self.header_size() - 20, got: options.len() });
+        }
+
+        let mut index = 0;
+        while index < options.len() {
+            match options[index] {
+                0 => return Ok(()),   // End of Options List.
+                1 => index += 1,      // No Operation.
+                kind => {
+                    let length = *options.get(index + 1)
+                        .ok_or(ParseError::Invalid("Ipv4 option is missing its length"))? as usize;
+                    if length < 2 {
+                        return Err(ParseError::TooSmall { layer: "Ipv4 option", field: "length", expected: 2, got: length });
+                    }
+                    if index + length > options.len() {
+                        return Err(ParseError::TooBig { layer: "Ipv4 option", field: "length", expected: options.len() - index, got: length });
+                    }
+                    index += length;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Size of the header in bytes, including options.
+    pub fn header_size(&self) -> usize { self.header_length() as usize * 4 }
+
+    /// Size of the payload according to `total_length`, which may differ from what was captured.
+    pub fn declared_payload_size(&self) -> usize { (self.total_length() as usize).saturating_sub(self.header_size()) }
+
+    /// Checks `total_length` against the bytes after the start of the header. Extra bytes are only allowed up
+    /// to the minimum Ethernet payload, since short frames are padded.
+    pub fn validate_length(&self) -> Result<(), ParseError> {
+        let total = self.total_length() as usize;
+        if total < self.header_size() {
+            return Err(ParseError::TooSmall { layer: "Ipv4", field: "total length", expected: self.header_size(), got: total });
+        }
+        if total > self.data.len() {
+            return Err(ParseError::TooBig { layer: "Ipv4", field: "total length", expected: self.data.len(), got: total });
+        }
+        if total < self.data.len() && self.data.len() > ethernet::MIN_PAYLOAD_SIZE {
+            return Err(ParseError::Invalid("Ipv4 total length is shorter than the payload it's carried in"));
+        }
+        Ok(())
+    }
+
+    /// The header including options.
+    pub fn raw_header(&self) -> &'a [u8] {
+        &self.data[..self.header_size().min(self.data.len())]
+    }
 
+    /// Whether the header checksum is correct. Headers that weren't fully captured are never correct.
+    pub fn verify_header_checksum(&self) -> bool {
+        let header = self.raw_header();
+        header.len() == self.header_size() && checksum_finish(checksum_add(0, header)) == 0
+    }
 
-    // pub fn has_options(&self) -> bool { self.header_length() > 5 }
-    // pub fn options(&self) -> OptionIter<'a> {
-    //     let total_header_length = self.header_length() * 4;
-    //
-    //     OptionIter {
-    //         data: self.data[total_header_length..],
-    //         index: 0
-    //     }
-    // }
+    /// The bytes holding the field `name`, as named by its accessor, or `None` for unknown names.
+    pub fn field_bytes(&self, name: &str) -> Option<&'a [u8]> {
+        let bits = match name {
+            "version"             => Self::VERSION_BITS,
+            "header_length"       => Self::HEADER_LENGTH_BITS,
+            "dscp"                => Self::DSCP_BITS,
+            "ecn"                 => Self::ECN_BITS,
+            "total_length"        => Self::TOTAL_LENGTH_BITS,
+            "identification"      => Self::IDENTIFICATION_BITS,
+            "flags"               => Self::FLAGS_BITS,
+            "fragment_offset"     => Self::FRAGMENT_OFFSET_BITS,
+            "time_to_live"        => Self::TTL_BITS,
+            "protocol"            => Self::PROTOCOL_BITS,
+            "header_checksum"     => Self::HEADER_CHECKSUM_BITS,
+            "source_address"      => Self::SOURCE_ADDRESS_BITS,
+            "destination_address" => Self::DESTINATION_ADDRESS_BITS,
+            "options"             => return Some(self.raw_options()),
+            _ => return None,
+        };
+        Some(bytes_of_bits(self.data, bits))
+    }
 
+    /// The bytes after the header up to `total_length`, so the padding of short Ethernet frames is left out.
+    /// Packets cut short by the capture, or quoted in ICMP errors, end with the data.
     pub fn raw_payload(&self) -> &'a [u8] {
-        &self.data[14..self.data.len()-4]
+        let end = (self.total_length() as usize).min(self.data.len());
+        &self.data[self.header_size()..end]
+    }
+
+    /// The header and payload as they were parsed.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.data.to_vec()
     }
 
-    pub fn payload(&self) -> Result<Payload<'a>, Error> {
+    pub fn payload(&self) -> Result<Payload<'a>, ParseError> {
+        if self.is_fragment() { return Err(ParseError::Invalid("Ipv4 fragment can't be decoded without reassembly")) }
         match self.protocol() {
             Protocol::TCP => Ok(Payload::Tcp(tcp::Tcp::from_bytes(self.raw_payload())?)),
-            Protocol::UDP => Err(Error::new(ErrorKind::Other, "UDP not implemented")),
-            _ => Err(Error::new(ErrorKind::Other, "Unknown protocol")),
+            Protocol::UDP => Ok(Payload::Udp(udp::Udp::from_bytes_nested(self.raw_payload(), self.depth)?)),
+            Protocol::GRE => Ok(Payload::Gre(gre::Gre::from_bytes_nested(self.raw_payload(), self.depth + 1)?)),
+            Protocol::SCTP => Ok(Payload::Sctp(sctp::Sctp::from_bytes(self.raw_payload())?)),
+            Protocol::AH   => Ok(Payload::Ah(ipsec::Ah::from_bytes_nested(self.raw_payload(), self.depth)?)),
+            Protocol::ESP  => Ok(Payload::Esp(ipsec::Esp::from_bytes(self.raw_payload())?)),
+            Protocol::ICMP => Ok(Payload::Icmp(icmp::Icmp::from_bytes(self.raw_payload())?)),
+            _ => Ok(Payload::Unknown(self.raw_payload())),
         }
     }
 
-    pub fn from_bytes(data: &'a [u8]) -> Result<Self, Error> {
+    pub fn from_bytes(data: &'a [u8]) -> Result<Self, ParseError> {
+        Self::from_bytes_nested(data, 0)
+    }
+
+    /// Parses a header that is encapsulated in `depth` tunnels.
+    pub fn from_bytes_nested(data: &'a [u8], depth: usize) -> Result<Self, ParseError> {
         if data.len() < 20 {
-            return Err(Error::new(ErrorKind::Other, format!("Ipv4 data too small, expected at least 20, got {}", data.len())));
+            return Err(ParseError::TooSmall { layer: "Ipv4", field: "data", expected: 20, got: data.len() });
         } else {
-            let me = Self { data };
+            let me = Self { data, depth };
 
             // TODO: Verify all data.
-            if me.version_raw()   != 4  { return Err(Error::new(ErrorKind::Other, "Version must be 4")) }
-            if me.header_length()  < 5  { return Err(Error::new(ErrorKind::Other, "Header length too small")) }
-            if me.header_length()  > 20 { return Err(Error::new(ErrorKind::Other, "Header length too big")) }
-            if me.reserved1()     != 0  { return Err(Error::new(ErrorKind::Other, "Reserved flag is not 0")) }
+            if me.version_raw()   != 4  { return Err(ParseError::Invalid("Version must be 4")) }
+            if me.header_length()  < 5  { return Err(ParseError::Invalid("Header length too small")) }
+            if me.header_length()  > 20 { return Err(ParseError::Invalid("Header length too big")) }
+            if data.len() < me.header_size() {
+                return Err(ParseError::TooSmall { layer: "Ipv4", field: "data for header with options", expected: me.header_size(), got: data.len() });
+            }
+            if (me.total_length() as usize) < me.header_size() {
+                return Err(ParseError::TooSmall { layer: "Ipv4", field: "total length", expected: me.header_size(), got: me.total_length() as usize });
+            }
 
             Ok(me)
         }
@@ -180,8 +513,8 @@ impl<'a> IPv4<'a> {
 }
 
 
-impl<'a> std::fmt::Debug for IPv4<'a> {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+impl<'a> core::fmt::Debug for IPv4<'a> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
         write!(f, "    Ipv4\n")?;
         write!(f, "        header_length:       {:?}\n", self.header_length())?;
         write!(f, "        version:             {:?}\n", self.version())?;
@@ -205,4 +538,227 @@ impl<'a> std::fmt::Debug for IPv4<'a> {
         write!(f, "        payload: {:?}\n",    self.payload())?;
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+/// One line, e.g. `IPv4 10.0.0.1 > 10.0.0.2 proto=TCP len=40`.
+impl<'a> core::fmt::Display for IPv4<'a> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "IPv4 {:?} > {:?} proto={:?} len={}", self.source_address(), self.destination_address(), self.protocol(), self.total_length())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::{Ipv4Builder, TcpBuilder, TCP_ACK};
+    use crate::testing;
+
+    /// Splitmix64, so the property tests are reproducible without a dependency.
+    fn random(state: &mut u64) -> u64 {
+        *state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = *state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Reads `range` one bit at a time.
+    fn reference(data: &[u8], range: Range<usize>) -> u64 {
+        range.fold(0, |value, bit| value << 1 | (data[bit / 8] >> (7 - bit % 8) & 1) as u64)
+    }
+
+    #[test]
+    fn bit_array_matches_bit_by_bit_extraction() {
+        let mut state = 825;
+        for _ in 0..10_000 {
+            let data: [u8; 12] = core::array::from_fn(|_| random(&mut state) as u8);
+            let start = (random(&mut state) % 96) as usize;
+            let end = start + 1 + (random(&mut state) % 64) as usize;
+            let bits = BitArray::new(&data);
+            if end <= 96 {
+                assert_eq!(bits.get(start..end), Some(reference(&data, start..end)), "{:?} {}..{}", data, start, end);
+            } else {
+                assert_eq!(bits.get(start..end), None);
+            }
+        }
+    }
+
+    #[test]
+    fn bit_array_rejects_bad_ranges() {
+        let data = [0xFF; 9];
+        let bits = BitArray::new(&data);
+        assert_eq!(bits.get(3..3), None);
+        assert_eq!(bits.get(0..65), None);
+        assert_eq!(bits.get(70..73), None);
+        assert_eq!(bits.get(8..72), Some(u64::MAX));
+        assert_eq!(bits.get(4..68), Some(u64::MAX));
+        assert!(bits.at(0..0).is_err());
+    }
+
+    #[test]
+    fn maps_protocol_numbers() {
+        let protocols = [(1, Protocol::ICMP), (2, Protocol::IGMP), (6, Protocol::TCP), (17, Protocol::UDP), (47, Protocol::GRE), (50, Protocol::ESP), (51, Protocol::AH), (132, Protocol::SCTP), (0x92, Protocol::Other(0x92)), (253, Protocol::Other(253))];
+        for (value, protocol) in protocols {
+            assert_eq!(Protocol::from_value(value), protocol);
+            assert_eq!(protocol.value(), value);
+        }
+        assert_eq!(alloc::format!("{:?}", Protocol::Other(253)), "Other(253)");
+    }
+
+    #[test]
+    fn accepts_options_that_fill_the_header() {
+        // Router Alert, then No Operation and End of Options List padding.
+        let data = testing::ipv4_with_options(&[148, 4, 0, 0, 1, 0, 0, 0]);
+        let packet = IPv4::from_bytes(&data).unwrap();
+        assert_eq!(packet.header_size(), 28);
+        assert!(packet.validate_options().is_ok());
+        assert!(packet.verify_header_checksum());
+    }
+
+    #[test]
+    fn rejects_an_option_overrunning_the_header() {
+        // A Record Route declaring 12 bytes in a header with room for 8.
+        let data = testing::ipv4_with_options(&[7, 12, 4, 0, 0, 0, 0, 0]);
+        let packet = IPv4::from_bytes(&data).unwrap();
+        assert!(matches!(packet.validate_options(), Err(ParseError::TooBig { field: "length", expected: 8, got: 12, .. })));
+    }
+
+    #[test]
+    fn rejects_an_option_length_below_two() {
+        let data = testing::ipv4_with_options(&[148, 1, 0, 0]);
+        assert!(IPv4::from_bytes(&data).unwrap().validate_options().is_err());
+    }
+
+    #[test]
+    fn rejects_an_option_without_its_length() {
+        let data = testing::ipv4_with_options(&[1, 1, 1, 148]);
+        assert!(matches!(IPv4::from_bytes(&data).unwrap().validate_options(), Err(ParseError::Invalid(_))));
+    }
+
+    #[test]
+    fn displays_one_line_and_debugs_the_tree() {
+        let data = Ipv4Builder::new(testing::ip(10, 0, 0, 1), testing::ip(10, 0, 0, 2)).with_tcp(&TcpBuilder::new(51000, 80)).build();
+        let packet = IPv4::from_bytes(&data).unwrap();
+        assert_eq!(alloc::format!("{}", packet), "IPv4 10.0.0.1 > 10.0.0.2 proto=TCP len=40");
+        let tree = alloc::format!("{:?}", packet);
+        assert!(tree.starts_with("    Ipv4\n"));
+        assert!(tree.contains("        source_address:      10.0.0.1\n"));
+        assert!(tree.contains("        destination_port:      80\n"));
+    }
+
+    #[test]
+    fn round_trips_through_to_bytes() {
+        let data = testing::ipv4_with_options(&[7, 7, 4, 0, 0, 0, 0, 0]);
+        assert_eq!(IPv4::from_bytes(&data).unwrap().to_bytes(), data);
+    }
+
+    #[test]
+    fn validates_the_total_length_against_the_data() {
+        let data = Ipv4Builder::new(testing::ip(10, 0, 0, 1), testing::ip(10, 0, 0, 2)).with_tcp(&TcpBuilder::new(51000, 80).with_payload(&[0; 100])).build();
+        assert_eq!(IPv4::from_bytes(&data).unwrap().validate_length(), Ok(()));
+
+        // Claims 20 bytes more than were captured.
+        let mut longer = data.clone();
+        longer[2..4].copy_from_slice(&160u16.to_be_bytes());
+        let ip = IPv4::from_bytes(&longer).unwrap();
+        assert_eq!(ip.validate_length(), Err(ParseError::TooBig { layer: "Ipv4", field: "total length", expected: 140, got: 160 }));
+        assert_eq!(ip.declared_payload_size(), 140);
+
+        let mut shorter = data.clone();
+        shorter[2..4].copy_from_slice(&100u16.to_be_bytes());
+        assert!(matches!(IPv4::from_bytes(&shorter).unwrap().validate_length(), Err(ParseError::Invalid(_))));
+
+        // A short packet padded to the minimum Ethernet payload is fine.
+        let mut padded = Ipv4Builder::new(testing::ip(10, 0, 0, 1), testing::ip(10, 0, 0, 2)).with_tcp(&TcpBuilder::new(51000, 80)).build();
+        padded.resize(crate::ethernet::MIN_PAYLOAD_SIZE, 0);
+        assert_eq!(IPv4::from_bytes(&padded).unwrap().validate_length(), Ok(()));
+    }
+
+    #[test]
+    fn accepts_every_ecn_code_point() {
+        let mut data = Ipv4Builder::new(testing::ip(10, 0, 0, 1), testing::ip(10, 0, 0, 2)).with_tcp(&TcpBuilder::new(51000, 80)).build();
+        for ecn in 0..4 {
+            data[1] = 46 << 2 | ecn;
+            let packet = IPv4::from_bytes(&data).unwrap();
+            assert_eq!((packet.dscp(), packet.ecn()), (46, ecn));
+        }
+    }
+    #[test]
+    fn decodes_the_router_alert_of_an_igmp_report() {
+        // IGMPv2 membership reports carry a Router Alert so routers look at them.
+        let mut data = testing::ipv4_with_options(&[148, 4, 0, 0]);
+        data[9] = 2;
+        let packet = IPv4::from_bytes(&data).unwrap();
+        assert_eq!(packet.header_size(), 24);
+        assert_eq!(packet.options().collect::<Vec<_>>(), [Ipv4Option::RouterAlert { value: 0 }]);
+        assert_eq!(alloc::format!("{}", Ipv4Option::RouterAlert { value: 0 }), "Router Alert (0)");
+
+        // A Router Alert must be 4 bytes long.
+        let data = testing::ipv4_with_options(&[148, 3, 0, 0]);
+        let option = IPv4::from_bytes(&data).unwrap().options().next().unwrap();
+        assert_eq!(option, Ipv4Option::Malformed { kind: 148, length: 3 });
+        assert_eq!(alloc::format!("{}", option), "Router Alert (148) with invalid length 3");
+    }
+
+    #[test]
+    fn decodes_the_filled_slots_of_a_record_route() {
+        // Room for three addresses of which two are recorded, then End of Options List.
+        let data = testing::ipv4_with_options(&[7, 15, 12, 192, 168, 0, 1, 10, 0, 0, 254, 0, 0, 0, 0, 0]);
+        let packet = IPv4::from_bytes(&data).unwrap();
+        assert!(packet.validate_options().is_ok());
+        let route = alloc::vec![testing::ip(192, 168, 0, 1), testing::ip(10, 0, 0, 254)];
+        let options: Vec<_> = packet.options().collect();
+        assert_eq!(options, [Ipv4Option::RecordRoute { pointer: 12, route }]);
+        assert_eq!(alloc::format!("{}", options[0]), "Record Route (pointer 12): 192.168.0.1 10.0.0.254");
+
+        // The addresses don't fill the option.
+        let data = testing::ipv4_with_options(&[7, 6, 4, 0, 0, 0, 0, 0]);
+        assert_eq!(IPv4::from_bytes(&data).unwrap().options().next(), Some(Ipv4Option::Malformed { kind: 7, length: 6 }));
+    }
+    #[test]
+    fn rejects_a_header_claiming_options_beyond_the_data() {
+        // A header length of 15 words claims 40 bytes of options that a bare 20-byte header doesn't have.
+        let mut data = Ipv4Builder::new(testing::ip(10, 0, 0, 1), testing::ip(10, 0, 0, 2)).with_tcp(&TcpBuilder::new(51000, 80)).build();
+        data.truncate(20);
+        data[0] = 0x4F;
+        data[2..4].copy_from_slice(&60u16.to_be_bytes());
+        assert_eq!(IPv4::from_bytes(&data).unwrap_err(), ParseError::TooSmall { layer: "Ipv4", field: "data for header with options", expected: 60, got: 20 });
+
+        // The header fits but the total length doesn't even cover it.
+        let mut data = testing::ipv4_with_options(&[148, 4, 0, 0]);
+        data[2..4].copy_from_slice(&20u16.to_be_bytes());
+        assert_eq!(IPv4::from_bytes(&data).unwrap_err(), ParseError::TooSmall { layer: "Ipv4", field: "total length", expected: 24, got: 20 });
+
+        assert!(matches!(IPv4::from_bytes(&[0x45; 19]), Err(ParseError::TooSmall { expected: 20, got: 19, .. })));
+        let mut short = testing::ipv4_with_options(&[]);
+        short[0] = 0x44;
+        assert_eq!(IPv4::from_bytes(&short).unwrap_err(), ParseError::Invalid("Header length too small"));
+    }
+    #[test]
+    fn returns_the_bytes_behind_a_field() {
+        let data = testing::ipv4_with_options(&[148, 4, 0, 0]);
+        let packet = IPv4::from_bytes(&data).unwrap();
+        assert_eq!(packet.field_bytes("source_address"), Some(&[10, 0, 0, 1][..]));
+        assert_eq!(packet.field_bytes("destination_address"), Some(&data[16..20]));
+        assert_eq!(packet.field_bytes("total_length"), Some(&(data.len() as u16).to_be_bytes()[..]));
+        // Fields sharing bytes get the bytes they overlap.
+        assert_eq!(packet.field_bytes("version"), Some(&[0x46][..]));
+        assert_eq!(packet.field_bytes("fragment_offset"), Some(&data[6..8]));
+        assert_eq!(packet.field_bytes("options"), Some(&[148, 4, 0, 0][..]));
+        assert_eq!(packet.field_bytes("source"), None);
+    }
+
+    #[test]
+    fn leaves_the_padding_of_a_minimum_size_frame_out_of_the_payload() {
+        let ack = TcpBuilder::new(51000, 80).with_flags(TCP_ACK);
+        let mut frame = testing::tcp_frame(testing::ip(10, 0, 0, 1), testing::ip(10, 0, 0, 2), &ack);
+        assert_eq!(frame.len(), 54);
+        frame.resize(60, 0);
+
+        let packet = IPv4::from_bytes(&frame[14..]).unwrap();
+        assert_eq!(packet.raw_payload().len(), 20);
+        let Ok(Payload::Tcp(tcp)) = packet.payload() else { panic!("the frame doesn't hold TCP") };
+        assert!(tcp.raw_payload().is_empty());
+    }
+}
@@ -0,0 +1,399 @@
+/*
+https://www.rfc-editor.org/rfc/rfc8200
+*/
+
+use alloc::{format, string::String, vec::Vec};
+use core::ops::Range;
+use crate::tcp;
+use crate::udp;
+use crate::icmpv6;
+use crate::gre;
+use crate::sctp;
+use crate::ipsec;
+use crate::error::ParseError;
+use crate::shared::bytes_of_bits;
+use crate::endian::{be_u16, be_u32};
+
+
+pub const NEXT_HEADER_TCP    : u8 = 6;
+pub const NEXT_HEADER_UDP    : u8 = 17;
+pub const NEXT_HEADER_GRE    : u8 = 47;
+pub const NEXT_HEADER_ICMPV6 : u8 = 58;
+pub const NEXT_HEADER_SCTP   : u8 = 132;
+pub const NEXT_HEADER_ESP    : u8 = 50;
+
+pub const NEXT_HEADER_HOP_BY_HOP          : u8 = 0;
+pub const NEXT_HEADER_ROUTING             : u8 = 43;
+pub const NEXT_HEADER_FRAGMENT            : u8 = 44;
+pub const NEXT_HEADER_AUTHENTICATION      : u8 = 51;
+pub const NEXT_HEADER_NO_NEXT_HEADER      : u8 = 59;
+pub const NEXT_HEADER_DESTINATION_OPTIONS : u8 = 60;
+pub const NEXT_HEADER_MOBILITY            : u8 = 135;
+
+/// Whether `next_header` is an extension header rather than an upper-layer protocol.
+pub fn is_extension_header(next_header: u8) -> bool {
+    matches!(next_header,
+        NEXT_HEADER_HOP_BY_HOP | NEXT_HEADER_ROUTING | NEXT_HEADER_FRAGMENT |
+        NEXT_HEADER_AUTHENTICATION | NEXT_HEADER_DESTINATION_OPTIONS | NEXT_HEADER_MOBILITY)
+}
+
+
+#[derive(Copy, Clone, Eq, PartialEq, Hash)]
+pub struct Ipv6Address {
+    data: [u8; 16]
+}
+
+impl Ipv6Address {
+    pub fn from_bytes(bytes: [u8; 16]) -> Self {
+        Self { data: bytes }
+    }
+
+    pub fn bytes(&self) -> &[u8; 16] {
+        &self.data
+    }
+
+    pub fn segments(&self) -> [u16; 8] {
+        let mut segments = [0; 8];
+        for (i, segment) in segments.iter_mut().enumerate() {
+            *segment = be_u16(&self.data, i * 2);
+        }
+        segments
+    }
+}
+
+impl core::fmt::Debug for Ipv6Address {
+    /// Formats the address as described in RFC 5952, compressing the longest run of zero groups.
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        let segments = self.segments();
+
+        let mut longest = 0..0;
+        let mut start = 0;
+        for i in 0..=segments.len() {
+            if i < segments.len() && segments[i] == 0 { continue }
+            if i - start > longest.len() && i - start > 1 { longest = start..i }
+            start = i + 1;
+        }
+
+        if longest.is_empty() {
+            let parts: Vec<String> = segments.iter().map(|x| format!("{:x}", x)).collect();
+            return write!(f, "{}", parts.join(":"));
+        }
+
+        let head: Vec<String> = segments[..longest.start].iter().map(|x| format!("{:x}", x)).collect();
+        let tail: Vec<String> = segments[longest.end..].iter().map(|x| format!("{:x}", x)).collect();
+        write!(f, "{}::{}", head.join(":"), tail.join(":"))
+    }
+}
+
+
+/// One extension header of the chain between the fixed header and the upper-layer protocol.
+#[derive(Debug, Copy, Clone)]
+pub struct ExtensionHeader<'a> {
+    /// The next header value that announced this header, e.g. 44 for a Fragment header.
+    pub kind: u8,
+    /// The whole header, including its next header and length fields.
+    pub data: &'a [u8],
+}
+
+impl<'a> ExtensionHeader<'a> {
+    pub fn next_header(&self) -> u8 { self.data[0] }
+}
+
+
+/// The Fragment extension header of a packet that is one fragment of a larger one.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Fragment {
+    /// Offset of this fragment in bytes.
+    pub offset: usize,
+    pub more_fragments: bool,
+    pub identification: u32,
+}
+
+
+/// Walks the extension headers, honoring each header's length, and stops at the first upper-layer protocol.
+/// Ends early at a header that isn't fully captured, see `IPv6::upper_layer`.
+pub struct ExtensionHeaderIter<'a> {
+    next_header: u8,
+    data: &'a [u8],
+    offset: usize,
+}
+
+impl<'a> Iterator for ExtensionHeaderIter<'a> {
+    type Item = ExtensionHeader<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if !is_extension_header(self.next_header) { return None }
+        let rest = &self.data[self.offset..];
+        let length = match self.next_header {
+            NEXT_HEADER_FRAGMENT       => 8,
+            // The Authentication Header counts 4 byte units, excluding the first 2.
+            NEXT_HEADER_AUTHENTICATION => (*rest.get(1)? as usize + 2) * 4,
+            // The others count 8 byte units, excluding the first.
+            _                          => (*rest.get(1)? as usize + 1) * 8,
+        };
+        let header = ExtensionHeader { kind: self.next_header, data: rest.get(..length)? };
+        self.next_header = header.next_header();
+        self.offset += length;
+        Some(header)
+    }
+}
+
+
+#[derive(Debug)]
+pub enum Payload<'a> {
+    Tcp(tcp::Tcp<'a>),
+    Udp(udp::Udp<'a>),
+    Icmpv6(icmpv6::Icmpv6<'a>),
+    Gre(gre::Gre<'a>),
+    Sctp(sctp::Sctp<'a>),
+    /// The Authentication Header is skipped like the other extension headers.
+    Esp(ipsec::Esp<'a>),
+    Unknown(&'a [u8]),
+}
+
+
+#[derive(Clone)]
+pub struct IPv6<'a> {
+    data: &'a [u8],
+    /// Number of tunnels this header is nested in.
+    depth: usize,
+}
+
+impl<'a> IPv6<'a> {
+    pub const HEADER_SIZE: usize = 40;
+
+    pub const VERSION_BITS             : Range<usize> = 0..4;
+    pub const TRAFFIC_CLASS_BITS       : Range<usize> = 4..12;
+    pub const FLOW_LABEL_BITS          : Range<usize> = 12..32;
+    pub const PAYLOAD_LENGTH_BITS      : Range<usize> = 32..48;
+    pub const NEXT_HEADER_BITS         : Range<usize> = 48..56;
+    pub const HOP_LIMIT_BITS           : Range<usize> = 56..64;
+    pub const SOURCE_ADDRESS_BITS      : Range<usize> = 64..192;
+    pub const DESTINATION_ADDRESS_BITS : Range<usize> = 192..320;
+
+    pub fn version(&self)        -> u8  { (self.data[0] & 0b1111_0000) >> 4 }
+    pub fn traffic_class(&self)  -> u8  { ((be_u16(self.data, 0) & 0x0FF0) >> 4) as u8 }
+    /// The traffic class is split like the IPv4 type of service byte.
+    pub fn dscp(&self)           -> u8  { self.traffic_class() >> 2 }
+    pub fn ecn(&self)            -> u8  { self.traffic_class() & 0b11 }
+    pub fn flow_label(&self)     -> u32 { be_u32(self.data, 0) & 0x000F_FFFF }
+    pub fn payload_length(&self) -> u16 { be_u16(self.data, 4) }
+    pub fn next_header(&self)    -> u8  { self.data[6] }
+    pub fn hop_limit(&self)      -> u8  { self.data[7] }
+
+    pub fn source_address(&self) -> Ipv6Address {
+        Ipv6Address::from_bytes(self.data[8..24].try_into().unwrap())
+    }
+    pub fn destination_address(&self) -> Ipv6Address {
+        Ipv6Address::from_bytes(self.data[24..40].try_into().unwrap())
+    }
+
+    /// The bytes holding the field `name`, as named by its accessor, or `None` for unknown names.
+    pub fn field_bytes(&self, name: &str) -> Option<&'a [u8]> {
+        let bits = match name {
+            "version"             => Self::VERSION_BITS,
+            "traffic_class"       => Self::TRAFFIC_CLASS_BITS,
+            "flow_label"          => Self::FLOW_LABEL_BITS,
+            "payload_length"      => Self::PAYLOAD_LENGTH_BITS,
+            "next_header"         => Self::NEXT_HEADER_BITS,
+            "hop_limit"           => Self::HOP_LIMIT_BITS,
+            "source_address"      => Self::SOURCE_ADDRESS_BITS,
+            "destination_address" => Self::DESTINATION_ADDRESS_BITS,
+            _ => return None,
+        };
+        Some(bytes_of_bits(self.data, bits))
+    }
+
+    /// Everything after the fixed header up to `payload_length`, including any extension headers. A payload
+    /// length of zero is that of a jumbogram (RFC 2675), which runs to the end of the data.
+    pub fn raw_payload(&self) -> &'a [u8] {
+        let end = match self.payload_length() {
+            0      => self.data.len(),
+            length => (Self::HEADER_SIZE + length as usize).min(self.data.len()),
+        };
+        &self.data[Self::HEADER_SIZE..end]
+    }
+
+    pub fn extension_headers(&self) -> ExtensionHeaderIter<'a> {
+        ExtensionHeaderIter { next_header: self.next_header(), data: self.raw_payload(), offset: 0 }
+    }
+
+    /// The upper-layer protocol after the extension headers, and the bytes it starts at.
+    pub fn upper_layer(&self) -> Result<(u8, &'a [u8]), ParseError> {
+        let mut headers = self.extension_headers();
+        for _ in &mut headers {}
+        if is_extension_header(headers.next_header) {
+            return Err(ParseError::TooSmall { layer: "Ipv6", field: "extension header", expected: headers.offset + 8, got: self.raw_payload().len() });
+        }
+        Ok((headers.next_header, &self.raw_payload()[headers.offset..]))
+    }
+
+    /// The upper-layer protocol, e.g. TCP for a segment behind a Hop-by-Hop header.
+    pub fn upper_layer_protocol(&self) -> u8 {
+        self.upper_layer().map(|(protocol, _)| protocol).unwrap_or(self.next_header())
+    }
+
+    /// The Fragment header, if this packet is part of a fragmented one.
+    pub fn fragment(&self) -> Option<Fragment> {
+        let header = self.extension_headers().find(|x| x.kind == NEXT_HEADER_FRAGMENT)?;
+        let offset = be_u16(header.data, 2);
+        Some(Fragment {
+            offset: (offset & 0xFFF8) as usize,
+            more_fragments: offset & 1 != 0,
+            identification: be_u32(header.data, 4),
+        })
+    }
+
+    /// Whether this packet must be reassembled with other fragments before the upper layer can be decoded.
+    pub fn is_fragment(&self) -> bool {
+        self.fragment().is_some_and(|x| x.offset != 0 || x.more_fragments)
+    }
+
+    pub fn payload(&self) -> Result<Payload<'a>, ParseError> {
+        if self.is_fragment() { return Err(ParseError::Invalid("Ipv6 fragment can't be decoded without reassembly")) }
+        let (protocol, data) = self.upper_layer()?;
+        match protocol {
+            NEXT_HEADER_TCP    => Ok(Payload::Tcp(tcp::Tcp::from_bytes(data)?)),
+            NEXT_HEADER_UDP    => Ok(Payload::Udp(udp::Udp::from_bytes_nested(data, self.depth)?)),
+            NEXT_HEADER_ICMPV6 => Ok(Payload::Icmpv6(icmpv6::Icmpv6::from_bytes(data)?)),
+            NEXT_HEADER_GRE    => Ok(Payload::Gre(gre::Gre::from_bytes_nested(data, self.depth + 1)?)),
+            NEXT_HEADER_SCTP   => Ok(Payload::Sctp(sctp::Sctp::from_bytes(data)?)),
+            NEXT_HEADER_ESP    => Ok(Payload::Esp(ipsec::Esp::from_bytes(data)?)),
+            _ => Ok(Payload::Unknown(data)),
+        }
+    }
+
+    pub fn from_bytes(data: &'a [u8]) -> Result<Self, ParseError> {
+        Self::from_bytes_nested(data, 0)
+    }
+
+    /// Parses a header that is encapsulated in `depth` tunnels.
+    pub fn from_bytes_nested(data: &'a [u8], depth: usize) -> Result<Self, ParseError> {
+        if data.len() < Self::HEADER_SIZE {
+            return Err(ParseError::TooSmall { layer: "Ipv6", field: "data", expected: Self::HEADER_SIZE, got: data.len() });
+        }
+
+        let me = Self { data, depth };
+        if me.version() != 6 { return Err(ParseError::Invalid("Version must be 6")) }
+
+        Ok(me)
+    }
+}
+
+
+impl<'a> core::fmt::Debug for IPv6<'a> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "    Ipv6\n")?;
+        write!(f, "        version:             {:?}\n", self.version())?;
+        write!(f, "        traffic_class:       {:?}\n", self.traffic_class())?;
+        write!(f, "        flow_label:          {:?}\n", self.flow_label())?;
+        write!(f, "        payload_length:      {:?}\n", self.payload_length())?;
+        write!(f, "        next_header:         {:?}\n", self.next_header())?;
+        write!(f, "        hop_limit:           {:?}\n", self.hop_limit())?;
+        write!(f, "        source_address:      {:?}\n", self.source_address())?;
+        write!(f, "        destination_address: {:?}\n", self.destination_address())?;
+        write!(f, "        payload: {:?}\n",    self.payload())?;
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::{TcpBuilder, TCP_SYN};
+    use crate::testing;
+
+    /// An IPv6 packet from 2001:db8::1 to 2001:db8::2 whose payload, starting with `next_header`, is `payload`.
+    fn build(next_header: u8, payload: &[u8]) -> Vec<u8> {
+        let mut data = Vec::from([0x60, 0, 0, 0]);
+        data.extend_from_slice(&(payload.len() as u16).to_be_bytes());
+        data.extend_from_slice(&[next_header, 64]);
+        data.extend_from_slice(&[0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]);
+        data.extend_from_slice(&[0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2]);
+        data.extend_from_slice(payload);
+        data
+    }
+
+    /// A Hop-by-Hop header with a Router Alert option, followed by `next_header`.
+    fn hop_by_hop(next_header: u8) -> [u8; 8] {
+        [next_header, 0, 5, 2, 0, 0, 1, 0]
+    }
+
+    fn syn() -> Vec<u8> {
+        testing::tcp_with_options(&TcpBuilder::new(51000, 443).with_flags(TCP_SYN), &[])
+    }
+
+    #[test]
+    fn walks_a_hop_by_hop_header_to_tcp() {
+        let mut payload = Vec::from(hop_by_hop(NEXT_HEADER_TCP));
+        payload.extend_from_slice(&syn());
+        let data = build(NEXT_HEADER_HOP_BY_HOP, &payload);
+        let packet = IPv6::from_bytes(&data).unwrap();
+
+        let headers: Vec<(u8, usize)> = packet.extension_headers().map(|x| (x.kind, x.data.len())).collect();
+        assert_eq!(headers, [(NEXT_HEADER_HOP_BY_HOP, 8)]);
+        assert_eq!(packet.next_header(), NEXT_HEADER_HOP_BY_HOP);
+        assert_eq!(packet.upper_layer_protocol(), NEXT_HEADER_TCP);
+        let Ok(Payload::Tcp(tcp)) = packet.payload() else { panic!("the Hop-by-Hop header doesn't lead to TCP") };
+        assert_eq!(tcp.destination_port(), 443);
+        assert_eq!(tcp.syn(), 1);
+    }
+
+    #[test]
+    fn honors_the_length_of_each_header() {
+        // A Destination Options header of 16 bytes, with a PadN option filling it.
+        let mut payload = Vec::from(hop_by_hop(NEXT_HEADER_DESTINATION_OPTIONS));
+        payload.extend_from_slice(&[NEXT_HEADER_UDP, 1, 1, 12, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        payload.extend_from_slice(&testing::udp(53000, 53, b"query"));
+        let data = build(NEXT_HEADER_HOP_BY_HOP, &payload);
+        let packet = IPv6::from_bytes(&data).unwrap();
+
+        let headers: Vec<(u8, usize)> = packet.extension_headers().map(|x| (x.kind, x.data.len())).collect();
+        assert_eq!(headers, [(NEXT_HEADER_HOP_BY_HOP, 8), (NEXT_HEADER_DESTINATION_OPTIONS, 16)]);
+        let Ok(Payload::Udp(udp)) = packet.payload() else { panic!("the headers don't lead to UDP") };
+        assert_eq!(udp.raw_payload(), b"query");
+    }
+
+    #[test]
+    fn marks_fragments_for_reassembly() {
+        let mut first = Vec::from([NEXT_HEADER_TCP, 0, 0, 1, 0, 0, 0, 42]);
+        first.extend_from_slice(&syn());
+        let data = build(NEXT_HEADER_FRAGMENT, &first);
+        let packet = IPv6::from_bytes(&data).unwrap();
+        assert_eq!(packet.fragment(), Some(Fragment { offset: 0, more_fragments: true, identification: 42 }));
+        assert!(packet.is_fragment());
+        assert!(matches!(packet.payload(), Err(ParseError::Invalid(_))));
+
+        // An atomic fragment is a whole packet.
+        let mut atomic = Vec::from([NEXT_HEADER_TCP, 0, 0, 0, 0, 0, 0, 43]);
+        atomic.extend_from_slice(&syn());
+        let data = build(NEXT_HEADER_FRAGMENT, &atomic);
+        let packet = IPv6::from_bytes(&data).unwrap();
+        assert!(!packet.is_fragment());
+        assert!(matches!(packet.payload(), Ok(Payload::Tcp(_))));
+    }
+
+    #[test]
+    fn rejects_a_truncated_extension_header() {
+        let data = build(NEXT_HEADER_HOP_BY_HOP, &[NEXT_HEADER_TCP, 1, 0, 0, 0, 0, 0, 0]);
+        let packet = IPv6::from_bytes(&data).unwrap();
+        assert!(matches!(packet.payload(), Err(ParseError::TooSmall { layer: "Ipv6", field: "extension header", .. })));
+        assert_eq!(packet.upper_layer_protocol(), NEXT_HEADER_HOP_BY_HOP);
+    }
+
+    #[test]
+    fn leaves_trailing_padding_out_of_the_payload() {
+        let mut data = build(NEXT_HEADER_TCP, &syn());
+        let size = data.len();
+        data.resize(size + 6, 0);
+        let packet = IPv6::from_bytes(&data).unwrap();
+        assert_eq!(packet.raw_payload().len(), size - IPv6::HEADER_SIZE);
+        let Ok(Payload::Tcp(tcp)) = packet.payload() else { panic!("the packet doesn't hold TCP") };
+        assert!(tcp.raw_payload().is_empty());
+
+        // A jumbogram's length isn't in the fixed header, so its payload runs to the end.
+        data[4..6].copy_from_slice(&[0, 0]);
+        assert_eq!(IPv6::from_bytes(&data).unwrap().raw_payload().len(), size + 6 - IPv6::HEADER_SIZE);
+    }
+}
@@ -0,0 +1,115 @@
+//! The protocol parsers of the analyser, which only need byte slices. Without the default `std` feature this
+//! builds with `no_std` and `alloc`, so the dissectors can run on embedded or WASM targets:
+//! `cargo build --lib --no-default-features`. With `std` it also holds the visitors, reassembly and capture
+//! sources the `packet-analyser` binary is built from.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+#![allow(unused)]
+
+extern crate alloc;
+
+pub mod shared;
+pub mod endian;
+pub mod error;
+pub mod names;
+pub mod ethernet;
+pub mod ipv4;
+pub mod ipv6;
+pub mod pppoe;
+pub mod mpls;
+pub mod lldp;
+pub mod eapol;
+pub mod tcp;
+pub mod udp;
+pub mod gre;
+pub mod sctp;
+pub mod ipsec;
+pub mod icmp;
+pub mod icmpv6;
+pub mod vxlan;
+pub mod radiotap;
+pub mod sll;
+pub mod parse_only;
+pub mod builder;
+pub mod events;
+
+// Everything below needs `std`, most of it for `pcap` packets or I/O.
+#[cfg(feature = "std")] pub mod visitor;
+#[cfg(feature = "std")] pub mod summary;
+#[cfg(feature = "std")] pub mod reassembly;
+#[cfg(feature = "std")] pub mod follow;
+#[cfg(feature = "std")] pub mod payload_dump;
+#[cfg(feature = "std")] pub mod http;
+#[cfg(feature = "std")] pub mod device;
+#[cfg(feature = "std")] pub mod pcapng;
+#[cfg(feature = "std")] pub mod pcap_writer;
+#[cfg(feature = "std")] pub mod rewrite;
+#[cfg(feature = "std")] pub mod ring_buffer;
+#[cfg(feature = "std")] pub mod debug_file;
+#[cfg(feature = "std")] pub mod dedup;
+#[cfg(feature = "std")] pub mod tree;
+#[cfg(feature = "std")] pub mod sample;
+#[cfg(feature = "std")] pub mod timing;
+#[cfg(feature = "std")] pub mod interval;
+#[cfg(feature = "std")] pub mod fragment;
+#[cfg(feature = "std")] pub mod count;
+#[cfg(feature = "std")] pub mod raw;
+#[cfg(feature = "std")] pub mod dns;
+#[cfg(feature = "std")] pub mod dns_report;
+#[cfg(feature = "std")] pub mod anomaly;
+#[cfg(feature = "std")] pub mod ndjson;
+#[cfg(feature = "std")] pub mod enrich;
+#[cfg(feature = "std")] pub mod time_window;
+#[cfg(feature = "std")] pub mod checksum;
+#[cfg(feature = "std")] pub mod flow;
+#[cfg(feature = "std")] pub mod limits;
+#[cfg(feature = "std")] pub mod flow_export;
+#[cfg(feature = "std")] pub mod digest;
+#[cfg(feature = "std")] pub mod diff;
+#[cfg(feature = "std")] pub mod app;
+#[cfg(feature = "std")] pub mod inflate;
+#[cfg(feature = "std")] pub mod carve;
+#[cfg(feature = "std")] pub mod replay;
+#[cfg(feature = "std")] pub mod connection;
+#[cfg(feature = "std")] pub mod health;
+#[cfg(feature = "std")] pub mod drops;
+#[cfg(feature = "std")] pub mod registry;
+#[cfg(feature = "std")] pub mod pdml;
+#[cfg(feature = "std")] pub mod qos;
+#[cfg(feature = "std")] pub mod protocol_filter;
+#[cfg(feature = "std")] pub mod fold;
+#[cfg(feature = "std")] pub mod rtt;
+#[cfg(feature = "std")] pub mod mss;
+#[cfg(feature = "std")] pub mod ecn;
+#[cfg(feature = "std")] pub mod scan;
+#[cfg(feature = "std")] pub mod websocket;
+#[cfg(feature = "std")] pub mod source;
+#[cfg(all(feature = "std", feature = "log"))] pub mod log_visitor;
+
+#[cfg(test)] mod testing;
+
+
+// `cargo test --lib --no-default-features` runs these with the parsers built as `no_std`.
+#[cfg(test)]
+mod tests {
+    use crate::builder::{Ipv4Builder, TcpBuilder, TCP_SYN};
+    use crate::ethernet::{self, Ethernet, Fcs};
+    use crate::ipv4::{self, Ipv4Address};
+
+    #[test]
+    fn parses_a_frame_with_the_core_parsers() {
+        let packet = Ipv4Builder::new(Ipv4Address::from_bytes([10, 0, 0, 1]), Ipv4Address::from_bytes([10, 0, 0, 2]))
+            .with_tcp(&TcpBuilder::new(51000, 80).with_flags(TCP_SYN))
+            .build();
+        let mut frame = alloc::vec![0xAA; 6];
+        frame.extend_from_slice(&[0xBB; 6]);
+        frame.extend_from_slice(&[0x08, 0x00]);
+        frame.extend_from_slice(&packet);
+
+        let frame = Ethernet::from_bytes_with_fcs(&frame, Fcs::Absent).unwrap();
+        let ethernet::Payload::IPv4(ip) = frame.payload().unwrap() else { panic!("Expected IPv4") };
+        let ipv4::Payload::Tcp(tcp) = ip.payload().unwrap() else { panic!("Expected TCP") };
+        assert_eq!((tcp.source_port(), tcp.destination_port()), (51000, 80));
+        assert_eq!(tcp.syn(), 1);
+    }
+}
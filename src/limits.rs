@@ -0,0 +1,210 @@
+use std::collections::{BTreeMap, HashMap};
+use std::hash::Hash;
+use std::time::Duration;
+
+
+/// Bounds on the state kept for long or adversarial captures, shared by connection tracking, TCP reassembly
+/// and fragment reassembly.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct ResourceLimits {
+    /// Flows kept at a time, the least recently used is evicted to make room for a new one.
+    pub max_flows: usize,
+    /// Bytes buffered out of order across all reassembled streams or fragmented datagrams.
+    pub max_reassembly_bytes: usize,
+    /// Capture time after which a flow without packets is dropped.
+    pub flow_idle_timeout: Duration,
+    /// Bytes a compressed body may inflate to before it's given up on as a decompression bomb.
+    pub max_decompressed_bytes: usize,
+}
+
+impl Default for ResourceLimits {
+    fn default() -> Self {
+        Self { max_flows: 100_000, max_reassembly_bytes: 64 << 20, flow_idle_timeout: Duration::from_secs(300), max_decompressed_bytes: 64 << 20 }
+    }
+}
+
+
+#[derive(Debug)]
+struct Entry<V> {
+    value: V,
+    last_seen: Duration,
+    /// Position in `FlowTable::order`.
+    tick: u64,
+}
+
+
+/// Per-flow state bounded by `ResourceLimits::max_flows` and `flow_idle_timeout`, with counts of the flows
+/// dropped for each. Flows are timed out by capture time, in the order they were last used.
+#[derive(Debug)]
+pub struct FlowTable<K, V> {
+    entries: HashMap<K, Entry<V>>,
+    /// The keys from least to most recently used.
+    order: BTreeMap<u64, K>,
+    next_tick: u64,
+    limits: ResourceLimits,
+    evicted: usize,
+    expired: usize,
+}
+
+impl<K: Copy + Eq + Hash, V> FlowTable<K, V> {
+    pub fn new(limits: ResourceLimits) -> Self {
+        Self { entries: HashMap::new(), order: BTreeMap::new(), next_tick: 0, limits, evicted: 0, expired: 0 }
+    }
+
+    pub fn limits(&self) -> ResourceLimits {
+        self.limits
+    }
+
+    /// Number of flows evicted to stay within the limits.
+    pub fn evicted(&self) -> usize {
+        self.evicted
+    }
+
+    /// Number of flows dropped after being idle for `flow_idle_timeout`.
+    pub fn expired(&self) -> usize {
+        self.expired
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    pub fn get(&self, key: &K) -> Option<&V> {
+        self.entries.get(key).map(|x| &x.value)
+    }
+
+    /// The flow of `key` seen at `now`, if it's kept. Idle flows are timed out first.
+    pub fn get_mut(&mut self, key: &K, now: Duration) -> Option<&mut V> {
+        self.expire(now);
+        if !self.entries.contains_key(key) { return None }
+        Some(self.get_or_insert_with(*key, now, || unreachable!("the flow was just found")))
+    }
+
+    /// The flow of `key` seen at `now`, created with `default` if it's new. Idle flows are timed out first, and
+    /// the least recently used flow is evicted if a new one doesn't fit.
+    pub fn get_or_insert_with(&mut self, key: K, now: Duration, default: impl FnOnce() -> V) -> &mut V {
+        self.expire(now);
+        match self.entries.get_mut(&key) {
+            Some(entry) => {
+                self.order.remove(&entry.tick);
+                entry.last_seen = entry.last_seen.max(now);
+                entry.tick = self.next_tick;
+            },
+            None => {
+                while self.entries.len() >= self.limits.max_flows.max(1) {
+                    if self.pop_oldest().is_none() { break }
+                    self.evicted += 1;
+                }
+                self.entries.insert(key, Entry { value: default(), last_seen: now, tick: self.next_tick });
+            },
+        }
+        self.order.insert(self.next_tick, key);
+        self.next_tick += 1;
+        &mut self.entries.get_mut(&key).unwrap().value
+    }
+
+    /// Sets the flow of `key` seen at `now`, as with `get_or_insert_with`.
+    pub fn insert(&mut self, key: K, now: Duration, value: V) {
+        let mut value = Some(value);
+        let slot = self.get_or_insert_with(key, now, || value.take().unwrap());
+        if let Some(value) = value { *slot = value }
+    }
+
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let entry = self.entries.remove(key)?;
+        self.order.remove(&entry.tick);
+        Some(entry.value)
+    }
+
+    /// Evicts the least recently used flow, counting it as evicted.
+    pub fn evict_oldest(&mut self) -> Option<(K, V)> {
+        let oldest = self.pop_oldest()?;
+        self.evicted += 1;
+        Some(oldest)
+    }
+
+    /// Drops and returns the flows that saw no packet within `flow_idle_timeout` before `now`, oldest first.
+    pub fn expire(&mut self, now: Duration) -> Vec<(K, V)> {
+        let mut expired = Vec::new();
+        while let Some((_, key)) = self.order.first_key_value() {
+            let key = *key;
+            if now.saturating_sub(self.entries[&key].last_seen) <= self.limits.flow_idle_timeout { break }
+            let Some((key, value)) = self.pop_oldest() else { break };
+            expired.push((key, value));
+            self.expired += 1;
+        }
+        expired
+    }
+
+    /// Removes and returns all flows, least recently used first.
+    pub fn drain(&mut self) -> Vec<(K, V)> {
+        let mut flows = Vec::with_capacity(self.entries.len());
+        while let Some(flow) = self.pop_oldest() {
+            flows.push(flow);
+        }
+        flows
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item=(&K, &V)> {
+        self.entries.iter().map(|(key, entry)| (key, &entry.value))
+    }
+
+    pub fn values(&self) -> impl Iterator<Item=&V> {
+        self.entries.values().map(|x| &x.value)
+    }
+
+    fn pop_oldest(&mut self) -> Option<(K, V)> {
+        let (_, key) = self.order.pop_first()?;
+        let entry = self.entries.remove(&key)?;
+        Some((key, entry.value))
+    }
+}
+
+impl<K: Copy + Eq + Hash, V> Default for FlowTable<K, V> {
+    fn default() -> Self {
+        Self::new(ResourceLimits::default())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(max_flows: usize, idle_seconds: u64) -> FlowTable<u32, &'static str> {
+        FlowTable::new(ResourceLimits { max_flows, flow_idle_timeout: Duration::from_secs(idle_seconds), ..ResourceLimits::default() })
+    }
+
+    #[test]
+    fn evicts_the_least_recently_used_flow_beyond_max_flows() {
+        let mut flows = table(2, 300);
+        flows.insert(1, Duration::ZERO, "a");
+        flows.insert(2, Duration::ZERO, "b");
+        // Using the first flow again leaves the second as the least recently used.
+        assert_eq!(flows.get_mut(&1, Duration::ZERO), Some(&mut "a"));
+        flows.insert(3, Duration::ZERO, "c");
+
+        assert_eq!(flows.len(), 2);
+        assert_eq!(flows.get(&2), None);
+        assert_eq!((flows.get(&1), flows.get(&3)), (Some(&"a"), Some(&"c")));
+        assert_eq!((flows.evicted(), flows.expired()), (1, 0));
+        assert_eq!(flows.drain(), vec![(1, "a"), (3, "c")]);
+    }
+
+    #[test]
+    fn times_out_idle_flows_by_capture_time() {
+        let mut flows = table(10, 10);
+        flows.insert(1, Duration::ZERO, "a");
+        flows.insert(2, Duration::from_secs(5), "b");
+        flows.insert(3, Duration::from_secs(12), "c");
+
+        assert_eq!(flows.get(&1), None);
+        assert_eq!(flows.expired(), 1);
+        assert_eq!(flows.expire(Duration::from_secs(20)), vec![(2, "b")]);
+        assert_eq!((flows.len(), flows.evicted(), flows.expired()), (1, 0, 2));
+    }
+}
@@ -0,0 +1,183 @@
+/*
+https://standards.ieee.org/ieee/802.1AB/6047/
+*/
+
+use alloc::{format, string::String, vec::Vec};
+use crate::error::ParseError;
+use crate::shared::MacAddress;
+use crate::endian::be_u16;
+
+
+pub const TLV_END_OF_LLDPDU      : u8 = 0;
+pub const TLV_CHASSIS_ID         : u8 = 1;
+pub const TLV_PORT_ID            : u8 = 2;
+pub const TLV_TIME_TO_LIVE       : u8 = 3;
+pub const TLV_PORT_DESCRIPTION   : u8 = 4;
+pub const TLV_SYSTEM_NAME        : u8 = 5;
+pub const TLV_SYSTEM_DESCRIPTION : u8 = 6;
+
+/// Chassis ID subtype 4 and port ID subtype 3 carry a MAC address.
+const CHASSIS_ID_MAC_ADDRESS : u8 = 4;
+const PORT_ID_MAC_ADDRESS    : u8 = 3;
+
+
+#[derive(Debug, Copy, Clone)]
+pub struct Tlv<'a> {
+    pub kind: u8,
+    pub value: &'a [u8],
+}
+
+
+/// Iterates the TLVs up to, but not including, the End of LLDPDU TLV.
+pub struct TlvIter<'a> {
+    data: &'a [u8],
+    index: usize,
+}
+
+impl<'a> Iterator for TlvIter<'a> {
+    type Item = Tlv<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let header = self.data.get(self.index..self.index + 2)?;
+        let kind   = header[0] >> 1;
+        let length = ((header[0] as usize & 1) << 8) | header[1] as usize;
+        if kind == TLV_END_OF_LLDPDU { return None }
+
+        let value = self.data.get(self.index + 2..self.index + 2 + length)?;
+        self.index += 2 + length;
+        Some(Tlv { kind, value })
+    }
+}
+
+
+#[derive(Clone)]
+pub struct Lldp<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Lldp<'a> {
+    pub fn tlvs(&self) -> TlvIter<'a> {
+        TlvIter { data: self.data, index: 0 }
+    }
+
+    fn value(&self, kind: u8) -> Option<&'a [u8]> {
+        self.tlvs().find(|x| x.kind == kind).map(|x| x.value)
+    }
+
+    pub fn chassis_id(&self) -> Option<String> {
+        let value = self.value(TLV_CHASSIS_ID)?;
+        Some(identifier(value, CHASSIS_ID_MAC_ADDRESS))
+    }
+
+    pub fn port_id(&self) -> Option<String> {
+        let value = self.value(TLV_PORT_ID)?;
+        Some(identifier(value, PORT_ID_MAC_ADDRESS))
+    }
+
+    /// Seconds the receiver should keep the information.
+    pub fn time_to_live(&self) -> Option<u16> {
+        let value = self.value(TLV_TIME_TO_LIVE)?;
+        if value.len() < 2 { return None }
+        Some(be_u16(value, 0))
+    }
+
+    pub fn port_description(&self)   -> Option<String> { self.value(TLV_PORT_DESCRIPTION).map(text) }
+    pub fn system_name(&self)        -> Option<String> { self.value(TLV_SYSTEM_NAME).map(text) }
+    pub fn system_description(&self) -> Option<String> { self.value(TLV_SYSTEM_DESCRIPTION).map(text) }
+
+    /// Requires the mandatory Chassis ID, Port ID and Time To Live TLVs, in that order.
+    pub fn from_bytes(data: &'a [u8]) -> Result<Self, ParseError> {
+        let me = Self { data };
+        let kinds: Vec<u8> = me.tlvs().take(3).map(|x| x.kind).collect();
+        if kinds != [TLV_CHASSIS_ID, TLV_PORT_ID, TLV_TIME_TO_LIVE] {
+            return Err(ParseError::Invalid("Lldp must start with the Chassis ID, Port ID and Time To Live TLVs"));
+        }
+        Ok(me)
+    }
+}
+
+
+fn text(value: &[u8]) -> String {
+    String::from_utf8_lossy(value).into_owned()
+}
+
+/// Renders an ID TLV, which starts with a subtype byte.
+fn identifier(value: &[u8], mac_subtype: u8) -> String {
+    match value.split_first() {
+        Some((subtype, id)) if *subtype == mac_subtype && id.len() == 6 => format!("{:?}", MacAddress::from_bytes(id).unwrap()),
+        Some((_, id)) => text(id),
+        None => String::new(),
+    }
+}
+
+
+impl<'a> core::fmt::Debug for Lldp<'a> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "    Lldp\n")?;
+        write!(f, "        chassis_id:         {:?}\n", self.chassis_id())?;
+        write!(f, "        port_id:            {:?}\n", self.port_id())?;
+        write!(f, "        time_to_live:       {:?}\n", self.time_to_live())?;
+        write!(f, "        port_description:   {:?}\n", self.port_description())?;
+        write!(f, "        system_name:        {:?}\n", self.system_name())?;
+        write!(f, "        system_description: {:?}\n", self.system_description())?;
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ethernet::{self, Ethernet};
+    use crate::testing::{self, MAC_A};
+
+    fn tlv(kind: u8, value: &[u8]) -> Vec<u8> {
+        let header = (kind as u16) << 9 | value.len() as u16;
+        let mut data = Vec::from(header.to_be_bytes());
+        data.extend_from_slice(value);
+        data
+    }
+
+    /// The mandatory TLVs of a switch port, then `rest`.
+    fn lldpdu(rest: &[u8]) -> Vec<u8> {
+        let mut chassis_id = Vec::from([CHASSIS_ID_MAC_ADDRESS]);
+        chassis_id.extend_from_slice(&MAC_A);
+        let mut data = tlv(TLV_CHASSIS_ID, &chassis_id);
+        data.extend_from_slice(&tlv(TLV_PORT_ID, b"\x05Gi1/0/7"));
+        data.extend_from_slice(&tlv(TLV_TIME_TO_LIVE, &120u16.to_be_bytes()));
+        data.extend_from_slice(rest);
+        data
+    }
+
+    #[test]
+    fn decodes_a_minimal_frame() {
+        let mut rest = tlv(TLV_SYSTEM_NAME, b"switch-1");
+        rest.extend_from_slice(&tlv(TLV_END_OF_LLDPDU, &[]));
+        let frame = testing::ethernet(0x88CC, &lldpdu(&rest));
+
+        let Ok(ethernet::Payload::Lldp(lldp)) = Ethernet::from_bytes(&frame).unwrap().payload() else { panic!("not an LLDP frame") };
+        assert_eq!(lldp.chassis_id().as_deref(), Some("2:0:0:0:0:10"));
+        assert_eq!(lldp.port_id().as_deref(), Some("Gi1/0/7"));
+        assert_eq!(lldp.time_to_live(), Some(120));
+        assert_eq!(lldp.system_name().as_deref(), Some("switch-1"));
+        assert_eq!(lldp.system_description(), None);
+    }
+
+    #[test]
+    fn stops_at_the_end_of_lldpdu() {
+        let mut rest = tlv(TLV_END_OF_LLDPDU, &[]);
+        rest.extend_from_slice(&tlv(TLV_SYSTEM_NAME, b"after the end"));
+        // Ethernet padding after the end.
+        rest.extend_from_slice(&[0; 6]);
+        let data = lldpdu(&rest);
+        let lldp = Lldp::from_bytes(&data).unwrap();
+        assert_eq!(lldp.tlvs().count(), 3);
+        assert_eq!(lldp.system_name(), None);
+    }
+
+    #[test]
+    fn requires_the_mandatory_tlvs() {
+        let data = tlv(TLV_SYSTEM_NAME, b"switch-1");
+        assert!(matches!(Lldp::from_bytes(&data), Err(ParseError::Invalid(_))));
+    }
+}
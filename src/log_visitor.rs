@@ -0,0 +1,123 @@
+use std::io::Error;
+use log::{debug, info, Log, Metadata, Record};
+use pcap::Packet;
+use crate::ethernet::Ethernet;
+use crate::ipv4::IPv4;
+use crate::ipv6::IPv6;
+use crate::tcp::Tcp;
+use crate::udp::Udp;
+use crate::visitor::{self, Context, Visitor};
+
+
+/// Target of every record, so loggers can filter the decoded packets apart from other output.
+pub const TARGET: &str = "packet";
+
+
+/// Emits an `info` record per packet and a `debug` record per decoded layer through the `log` facade, with
+/// the fields as `key=value` pairs. Failures to decode are logged as warnings.
+pub struct LogVisitor {
+    index: usize,
+}
+
+impl LogVisitor {
+    pub fn new() -> Self {
+        Self { index: 0 }
+    }
+}
+
+
+impl<'a> Visitor<'a, ()> for LogVisitor {
+    fn visit_packet(&mut self, packet: &'a Packet) -> Result<(), Error> {
+        self.index += 1;
+        info!(target: TARGET, "packet index={} time={} length={}", self.index, visitor::format_timestamp(packet.header), packet.header.len);
+        if let Err(error) = self.visit_packet_payload(packet) {
+            log::warn!(target: TARGET, "decode index={} error=\"{}\"", self.index, error);
+        }
+        Ok(())
+    }
+
+    fn visit_ethernet(&mut self, packet: &Ethernet<'a>, context: &Context<'a>) -> Result<(), Error> {
+        debug!(target: TARGET, "ethernet source={:?} destination={:?} ether_type={:#06x}", packet.source(), packet.destination(), packet.ether_type().as_u16());
+        self.visit_ethernet_payload(&packet.payload()?, &context.with_ethernet(packet))
+    }
+
+    fn visit_ipv4(&mut self, packet: &IPv4<'a>, context: &Context<'a>) -> Result<(), Error> {
+        debug!(target: TARGET, "ipv4 source={:?} destination={:?} protocol={} ttl={}", packet.source_address(), packet.destination_address(), packet.protocol_raw(), packet.time_to_live());
+        self.visit_ipv4_payload(&packet.payload()?, &context.with_ipv4(packet))
+    }
+
+    fn visit_ipv6(&mut self, packet: &IPv6<'a>, context: &Context<'a>) -> Result<(), Error> {
+        debug!(target: TARGET, "ipv6 source={:?} destination={:?} next_header={} hop_limit={}", packet.source_address(), packet.destination_address(), packet.next_header(), packet.hop_limit());
+        self.visit_ipv6_payload(&packet.payload()?, &context.with_ipv6(packet))
+    }
+
+    fn visit_tcp(&mut self, packet: &Tcp<'a>, context: &Context<'a>) -> Result<(), Error> {
+        debug!(target: TARGET, "tcp source_port={} destination_port={} seq={} ack={} flags={:#04x} length={}", packet.source_port(), packet.destination_port(), packet.sequence_number(), packet.acknowledgment_number(), packet.flags(), packet.raw_payload().len());
+        Ok(())
+    }
+
+    fn visit_udp(&mut self, packet: &Udp<'a>, context: &Context<'a>) -> Result<(), Error> {
+        debug!(target: TARGET, "udp source_port={} destination_port={} length={}", packet.source_port(), packet.destination_port(), packet.raw_payload().len());
+        Ok(())
+    }
+}
+
+
+/// Writes every record to stderr, for when the binary runs without another logger installed.
+pub struct StderrLogger;
+
+impl Log for StderrLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        true
+    }
+
+    fn log(&self, record: &Record) {
+        eprintln!("[{}] {}: {}", record.level(), record.target(), record.args());
+    }
+
+    fn flush(&self) {}
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use std::time::Duration;
+    use crate::builder::TcpBuilder;
+    use crate::raw::visit_frame;
+    use crate::testing::{self, ip};
+
+    /// Keeps the records of `TARGET`. The logger is global, so every test shares it.
+    struct Capture(Mutex<Vec<(log::Level, String)>>);
+
+    impl Log for Capture {
+        fn enabled(&self, metadata: &Metadata) -> bool {
+            metadata.target() == TARGET
+        }
+
+        fn log(&self, record: &Record) {
+            if self.enabled(record.metadata()) {
+                self.0.lock().unwrap().push((record.level(), record.args().to_string()));
+            }
+        }
+
+        fn flush(&self) {}
+    }
+
+    static CAPTURE: Capture = Capture(Mutex::new(Vec::new()));
+
+    #[test]
+    fn logs_a_record_per_packet_and_layer() {
+        let _ = log::set_logger(&CAPTURE);
+        log::set_max_level(log::LevelFilter::Debug);
+
+        let frame = testing::tcp_frame(ip(10, 0, 0, 1), ip(10, 0, 0, 2), &TcpBuilder::new(51000, 80));
+        visit_frame(&mut LogVisitor::new(), &frame, Duration::ZERO).unwrap();
+
+        let records = CAPTURE.0.lock().unwrap().clone();
+        assert!(records.contains(&(log::Level::Info, format!("packet index=1 time=00:00:00.000000 length={}", frame.len()))), "{:?}", records);
+        assert!(records.iter().any(|(level, text)| *level == log::Level::Debug && text.starts_with("ipv4 source=10.0.0.1 destination=10.0.0.2 protocol=6")), "{:?}", records);
+        assert!(records.iter().any(|(level, text)| *level == log::Level::Debug && text.starts_with("tcp source_port=51000 destination_port=80")), "{:?}", records);
+    }
+}
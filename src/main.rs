@@ -1,48 +1,567 @@
 #![allow(unused)]
 
-mod shared;
-mod endian;
-mod ipv4;
-mod tcp;
-mod ethernet;
-mod visitor;
+mod arguments;
+
+use std::io::{BufWriter, Error, ErrorKind, Read, Write};
+use std::fs::File;
+use std::path::Path;
+use std::time::Instant;
+use pcap::{self, Device, Capture, Packet, PacketHeader, Activated};
+use packet_analyser::{device, diff, pcapng, pdml, visitor};
+#[cfg(feature = "log")]
+use packet_analyser::log_visitor;
+use packet_analyser::ethernet::Ethernet;
+use packet_analyser::visitor::Visitor;
+use packet_analyser::summary::SummaryVisitor;
+use packet_analyser::follow::FollowVisitor;
+use packet_analyser::payload_dump::TcpPayloadVisitor;
+use packet_analyser::pcapng::PcapngReader;
+use packet_analyser::ring_buffer::RingBuffer;
+use packet_analyser::debug_file::DebugFileVisitor;
+use packet_analyser::dedup::DedupVisitor;
+use packet_analyser::sample::SampleVisitor;
+use packet_analyser::protocol_filter::ProtocolFilter;
+use packet_analyser::registry::Registry;
+use packet_analyser::timing::TimingVisitor;
+use packet_analyser::interval::IntervalVisitor;
+use packet_analyser::fragment::DefragVisitor;
+use packet_analyser::count::CountVisitor;
+use packet_analyser::raw::RawReader;
+use packet_analyser::dns_report::DnsVisitor;
+use packet_analyser::anomaly::{AnomalyVisitor, StallVisitor};
+use packet_analyser::connection::ConnectionVisitor;
+use packet_analyser::qos::QosVisitor;
+use packet_analyser::rtt::RttVisitor;
+use packet_analyser::mss::MssVisitor;
+use packet_analyser::ecn::EcnVisitor;
+use packet_analyser::scan::ScanVisitor;
+use packet_analyser::health::{Health, Outcome};
+use packet_analyser::drops::DropMonitor;
+use packet_analyser::ndjson::NdjsonVisitor;
+use packet_analyser::pdml::PdmlVisitor;
+use packet_analyser::enrich::{IpEnricher, NoEnricher, PrefixTable};
+use packet_analyser::checksum::ChecksumVerifier;
+use packet_analyser::carve::CarveVisitor;
+use packet_analyser::rewrite::{Rewriter, RewriteVisitor};
+use packet_analyser::pcap_writer::PcapWriter;
+use packet_analyser::flow_export::{FlowExportVisitor, FlowWriter};
+use packet_analyser::digest::HashVisitor;
+use packet_analyser::diff::FlowCollector;
+use packet_analyser::source::{CaptureSource, PacketSource, PcapngSource, RawSource, LINK_TYPE_ETHERNET, LINK_TYPE_IEEE802_11_RADIOTAP, LINK_TYPE_LINUX_SLL2};
+use crate::arguments::Arguments;
+
+
+const CAPTURE_TIMEOUT_MS: i32 = 100;
+
+/// Bytes captured per packet with `--headers-only`, enough for Ethernet, IPv4 and TCP headers with a few options.
+const HEADERS_ONLY_SNAPLEN: i32 = 96;
+
+/// Interface name that reads length-prefixed raw frames from stdin instead, see `RawReader`.
+const STDIN: &str = "-";
 
-use std::io::Error;
-use pcap::{self, Device, Capture, Packet};
-use crate::ethernet::Ethernet;
-use crate::visitor::Visitor;
 
 
 fn main() {
-    let mut printer = visitor::Printer::new();
-
-    // Fetch the network interface from the command line or use the default one.
-    let interface = std::env::args().nth(1).unwrap_or("en0".to_string());
-
-    // Select the network interface if present.
-    let mut device = Device::list()
-        .expect("Device lookup failed")
-        .into_iter()
-        .find(|x| x.name == interface)
-        .unwrap_or(
-            Device::lookup()
-                .expect("Device lookup failed")
-                .expect("No devices found")
-        );
-
-    println!("Using device {}", device.name);
-
-    let mut cap = Capture::from_device(device)
-        .expect("Failed to open device")
-        .promisc(true)
-        .immediate_mode(true)
-        .open()
-        .expect("Failed to open device");
-
-    println!("Waiting...");
-    while let Ok(packet) = cap.next_packet() {
-        if let Err(error) = printer.visit_packet(&packet) {
-            println!("[ERROR]: {}", error);
-        }
-    }
-}
\ No newline at end of file
+    let arguments = match Arguments::from_env() {
+        Ok(arguments) => arguments,
+        Err(error) => {
+            eprintln!("[ERROR]: {}", error);
+            std::process::exit(2);
+        }
+    };
+
+    if arguments.list {
+        device::print_list(&Device::list().expect("Device lookup failed"));
+        return;
+    }
+
+    if let (Some(path), Some(other)) = (&arguments.interface, &arguments.diff) {
+        diff_files(path, other, &arguments);
+        return;
+    }
+
+    let mut visitor: Box<dyn for<'a> Visitor<'a, ()>> = if let Some(selection) = arguments.follow {
+        let separator = arguments.separator.clone().unwrap_or(FollowVisitor::DEFAULT_SEPARATOR.to_string());
+        Box::new(FollowVisitor::new(selection, separator)
+            .with_overlap_policy(arguments.overlap_policy.unwrap_or_default())
+            .with_limits(arguments.resource_limits()))
+    } else if arguments.tcp_payload_only {
+        let writer: Box<dyn Write> = match &arguments.payload_out {
+            Some(path) => Box::new(BufWriter::new(File::create(path).expect("Failed to create the payload output file"))),
+            None => Box::new(BufWriter::new(std::io::stdout())),
+        };
+        let mut visitor = TcpPayloadVisitor::new(writer);
+        if let Some(selection) = arguments.payload_flow {
+            visitor = visitor.with_selection(selection).with_direction(arguments.payload_direction.unwrap_or_default());
+        }
+        Box::new(visitor)
+    } else if let Some(path) = &arguments.rewrite_out {
+        let mut rewriter = Rewriter::new();
+        for (old, new) in &arguments.rewrite_macs {
+            rewriter = rewriter.with_mac(*old, *new);
+        }
+        for (old, new) in &arguments.rewrite_ips {
+            rewriter = rewriter.with_ip(*old, *new);
+        }
+        let file = File::create(path).expect("Failed to create the rewritten capture");
+        let writer = PcapWriter::new(BufWriter::new(file), LINK_TYPE_ETHERNET as u32).expect("Failed to write the rewritten capture");
+        Box::new(RewriteVisitor::new(rewriter, writer))
+    } else if let Some(path) = &arguments.debug_out {
+        let file = File::create(path).expect("Failed to create debug output file");
+        Box::new(DebugFileVisitor::new(BufWriter::new(file)))
+    } else if let Some(directory) = &arguments.assemble_files {
+        std::fs::create_dir_all(directory).expect("Failed to create the directory for assembled files");
+        Box::new(CarveVisitor::new(directory.clone())
+            .with_overlap_policy(arguments.overlap_policy.unwrap_or_default())
+            .with_limits(arguments.resource_limits())
+            .with_app_dispatch(arguments.app_dispatch()))
+    } else if let Some(path) = &arguments.flow_export {
+        let file = File::create(path).expect("Failed to create the flow export file");
+        let writer = FlowWriter::new(BufWriter::new(file), arguments.flow_format.unwrap_or_default());
+        Box::new(FlowExportVisitor::new(writer, arguments.resource_limits()))
+    } else if arguments.hash {
+        let visitor = HashVisitor::new(arguments.hash_options);
+        if arguments.hash_flows {
+            Box::new(visitor.with_flows(arguments.resource_limits()))
+        } else {
+            Box::new(visitor)
+        }
+    } else if arguments.log {
+        log_visitor()
+    } else if arguments.ndjson {
+        Box::new(NdjsonVisitor::new(std::io::stdout()))
+    } else if arguments.xml {
+        Box::new(PdmlVisitor::new(BufWriter::new(std::io::stdout())))
+    } else if arguments.json {
+        Box::new(PdmlVisitor::new(BufWriter::new(std::io::stdout())).with_format(pdml::Format::Json))
+    } else if arguments.anomalies {
+        Box::new(AnomalyVisitor::new())
+    } else if arguments.connections {
+        Box::new(ConnectionVisitor::new().with_limits(arguments.resource_limits()))
+    } else if arguments.stalls {
+        Box::new(StallVisitor::new())
+    } else if arguments.rtt {
+        Box::new(RttVisitor::new())
+    } else if arguments.mss {
+        Box::new(MssVisitor::new())
+    } else if arguments.ecn {
+        Box::new(EcnVisitor::new())
+    } else if arguments.scans {
+        Box::new(ScanVisitor::new(arguments.scan_thresholds()))
+    } else if arguments.qos {
+        Box::new(QosVisitor::new())
+    } else if arguments.dns {
+        Box::new(DnsVisitor::new(DnsVisitor::DEFAULT_TIMEOUT))
+    } else if let Some(count_by) = arguments.count_by {
+        Box::new(CountVisitor::new(count_by.key()))
+    } else if arguments.summary {
+        Box::new(SummaryVisitor::new())
+    } else {
+        let enricher: Box<dyn IpEnricher> = match &arguments.ip_prefixes {
+            Some(path) => Box::new(PrefixTable::load(path).expect("Failed to load the IP prefix table")),
+            None       => Box::new(NoEnricher),
+        };
+        let mut checksums = ChecksumVerifier::new(arguments.checksums.unwrap_or_default());
+        for address in &arguments.local_ips {
+            checksums.add_local_ip(*address);
+        }
+        for address in &arguments.local_macs {
+            checksums.add_local_mac(*address);
+        }
+        let mut printer = visitor::Printer::new()
+            .with_verbosity(arguments.verbose.unwrap_or(visitor::Printer::FULL))
+            .with_enricher(enricher)
+            .with_checksums(checksums)
+            .with_app_dispatch(arguments.app_dispatch())
+            .with_registry(Registry::new());
+        if let Some(limit) = arguments.max_payload_bytes {
+            printer = printer.with_max_payload(limit);
+        }
+        if arguments.relative_seq {
+            printer = printer.with_relative_sequence_numbers();
+        }
+        if arguments.pretty {
+            printer = printer.with_tree();
+        }
+        if let Some(mtu) = arguments.mtu {
+            printer = printer.with_mtu(mtu);
+        }
+        if let Some(fcs) = arguments.fcs {
+            printer = printer.with_fcs(fcs);
+        }
+        if arguments.headers_only {
+            Box::new(printer.without_hex_dump())
+        } else {
+            Box::new(printer)
+        }
+    };
+
+    if arguments.only.is_some() || arguments.exclude.is_some() {
+        visitor = Box::new(ProtocolFilter::new(visitor, arguments.only.clone(), arguments.exclude.clone()));
+    }
+
+    if !arguments.no_defrag {
+        visitor = Box::new(DefragVisitor::new(visitor).with_limits(arguments.resource_limits()));
+    }
+
+    if arguments.timing {
+        visitor = Box::new(TimingVisitor::new(visitor));
+    }
+
+    if let Some(interval) = arguments.interval {
+        visitor = Box::new(IntervalVisitor::new(visitor, interval));
+    }
+
+    if arguments.dedup {
+        visitor = Box::new(DedupVisitor::new(visitor, arguments.dedup_window.unwrap_or(DedupVisitor::DEFAULT_WINDOW)));
+    }
+
+    if let Some(sampling) = arguments.sampling() {
+        visitor = Box::new(SampleVisitor::new(visitor, sampling));
+    }
+
+    if let Some((count, size_limit)) = arguments.ring_buffer {
+        visitor = Box::new(RingBuffer::new(visitor, Path::new("."), LINK_TYPE_ETHERNET as u32, count, size_limit));
+    }
+
+    let is_file = arguments.interface.as_ref().is_some_and(|x| x != STDIN && Path::new(x).is_file());
+    if arguments.replay && !is_file {
+        eprintln!("[ERROR]: --replay only works when reading a capture file");
+        std::process::exit(2);
+    }
+
+    let start = Instant::now();
+    let health = if arguments.interface.as_deref() == Some(STDIN) {
+        let mut source = RawSource::new(RawReader::new(std::io::stdin().lock()));
+        capture_loop(&mut source, visitor.as_mut(), &arguments, None)
+    } else if let Some(path) = arguments.interface.as_ref().filter(|x| is_fifo(Path::new(x))) {
+        eprintln!("Reading raw frames from {}", path);
+        let mut source = RawSource::new(RawReader::new(File::open(path).expect("Failed to open pipe")));
+        capture_loop(&mut source, visitor.as_mut(), &arguments, None)
+    } else if let Some(path) = arguments.interface.as_ref().filter(|x| Path::new(x).is_file()) {
+        eprintln!("Reading file {}", path);
+        capture_loop(open_file(path).as_mut(), visitor.as_mut(), &arguments, None)
+    } else {
+        // Select the requested network interface or use the default one.
+        let device = match device::choose(&device::PcapLister, arguments.interface.as_deref()) {
+            Ok(device) => device,
+            Err(error) => {
+                eprintln!("[ERROR]: {}", error);
+                std::process::exit(1);
+            }
+        };
+
+        eprintln!("Using device {}", device.name);
+
+        // The timeout makes `next_packet` return periodically so the duration limit is checked even on a quiet link.
+        let mut cap = Capture::from_device(device)
+            .expect("Failed to open device")
+            .promisc(true)
+            .immediate_mode(true)
+            .timeout(CAPTURE_TIMEOUT_MS);
+        if arguments.headers_only {
+            cap = cap.snaplen(HEADERS_ONLY_SNAPLEN);
+        } else if let Some(size) = arguments.max_frame_size {
+            cap = cap.snaplen(size as i32);
+        }
+        let cap = cap.open()
+            .expect("Failed to open device");
+
+        eprintln!("Waiting...");
+        let drops = arguments.show_drops.then(|| DropMonitor::new(DropMonitor::DEFAULT_INTERVAL));
+        capture_loop(&mut CaptureSource::new(cap), visitor.as_mut(), &arguments, drops)
+    };
+
+    if let Err(error) = visitor.finish() {
+        eprintln!("[ERROR]: {}", error);
+    }
+    eprintln!("Processed {} packets in {:.3} seconds", health.total, start.elapsed().as_secs_f64());
+    eprintln!("{}", health);
+}
+
+
+/// Logs the decoded packets to stderr through the `log` facade.
+#[cfg(feature = "log")]
+fn log_visitor() -> Box<dyn for<'a> Visitor<'a, ()>> {
+    if log::set_logger(&log_visitor::StderrLogger).is_ok() {
+        log::set_max_level(log::LevelFilter::Debug);
+    }
+    Box::new(log_visitor::LogVisitor::new())
+}
+
+#[cfg(not(feature = "log"))]
+fn log_visitor() -> Box<dyn for<'a> Visitor<'a, ()>> {
+    eprintln!("[ERROR]: --log needs the program to be built with the `log` feature");
+    std::process::exit(2);
+}
+
+
+/// Opens a pcap or pcapng file, told apart by its magic number.
+fn open_file(path: &str) -> Box<dyn PacketSource> {
+    let mut magic = [0u8; 4];
+    let mut file = File::open(path).expect("Failed to open file");
+    let is_pcapng = file.read_exact(&mut magic).is_ok() && pcapng::is_pcapng(&magic);
+
+    if is_pcapng {
+        Box::new(PcapngSource::new(PcapngReader::new(File::open(path).expect("Failed to open file"))))
+    } else {
+        let cap = Capture::from_file(path)
+            .expect("Failed to open file");
+        Box::new(CaptureSource::new(cap))
+    }
+}
+
+
+/// Compares the flows of two capture files, honouring the filters and limits of `arguments` for both.
+fn diff_files(a: &str, b: &str, arguments: &Arguments) {
+    let mut flows = Vec::new();
+    for path in [a, b] {
+        if !Path::new(path).is_file() {
+            eprintln!("[ERROR]: --diff compares capture files, '{}' is not one", path);
+            std::process::exit(2);
+        }
+        let mut collector = FlowCollector::new();
+        capture_loop(open_file(path).as_mut(), &mut collector, arguments, None);
+        flows.push(collector.flows);
+    }
+    diff::print_diff((a, b), &flows[0], &flows[1]);
+}
+
+
+/// Feeds packets from the source to the visitor until the source ends or the count/duration limit is reached.
+/// `drops` reports the kernel's drop counters of a live capture along the way and at the end.
+/// Returns how the processed packets fared.
+fn capture_loop(source: &mut dyn PacketSource, visitor: &mut dyn for<'a> Visitor<'a, ()>, arguments: &Arguments, mut drops: Option<DropMonitor>) -> Health {
+    let start = Instant::now();
+    let mut health = Health::default();
+    let window = arguments.time_window();
+    let mut pacer = arguments.pacer();
+
+    loop {
+        if arguments.count.is_some_and(|limit| health.total >= limit) { break }
+        if arguments.duration.is_some_and(|limit| start.elapsed() >= limit) { break }
+        if let Some(drops) = &mut drops { drops.poll(source) }
+
+        let packet = match source.next() {
+            Some(Ok(packet)) => packet,
+            Some(Err(error)) if error.kind() == ErrorKind::TimedOut => continue,
+            Some(Err(error)) => {
+                eprintln!("[ERROR]: {}", error);
+                break;
+            },
+            None => break,
+        };
+        if !window.contains(&packet.header) { continue }
+        if let Some(pacer) = &mut pacer { pacer.wait(&packet.header) }
+
+        let result = visit(visitor, &packet.as_packet(), packet.link_type);
+        if let Err(error) = &result {
+            eprintln!("[ERROR]: {}", error);
+        }
+        health.record(Outcome::classify(&result, (packet.link_type == LINK_TYPE_ETHERNET).then_some(&packet.data[..])));
+    }
+
+    if let Some(drops) = &mut drops { drops.report(source) }
+    health
+}
+
+
+/// Runs a packet through the visitor method for its link type.
+fn visit<'a>(visitor: &mut dyn for<'b> Visitor<'b, ()>, packet: &'a Packet<'a>, link_type: u16) -> Result<(), Error> {
+    match link_type {
+        LINK_TYPE_ETHERNET            => visitor.visit_packet(packet),
+        LINK_TYPE_IEEE802_11_RADIOTAP => visitor.visit_radiotap_packet(packet),
+        LINK_TYPE_LINUX_SLL2          => visitor.visit_sll2_packet(packet),
+        _ => Err(Error::new(ErrorKind::Unsupported, format!("Link type {} is not supported", link_type))),
+    }
+}
+
+
+/// Whether `path` is a named pipe, which is read like stdin rather than as a capture file.
+fn is_fifo(path: &Path) -> bool {
+    use std::os::unix::fs::FileTypeExt;
+    std::fs::metadata(path).is_ok_and(|x| x.file_type().is_fifo())
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    /// Counts the Ethernet frames it's given and keeps their timestamps in microseconds.
+    #[derive(Default)]
+    struct Counter {
+        packets: usize,
+        timestamps: Vec<i64>,
+    }
+
+    impl<'a> Visitor<'a, ()> for Counter {
+        fn visit_packet(&mut self, packet: &'a Packet) -> Result<(), Error> {
+            self.packets += 1;
+            self.timestamps.push(packet.header.ts.tv_sec as i64 * 1_000_000 + packet.header.ts.tv_usec as i64);
+            Ok(())
+        }
+    }
+
+    fn block(kind: u32, body: &[u8]) -> Vec<u8> {
+        let length = 12 + body.len().next_multiple_of(4) as u32;
+        let mut data = Vec::new();
+        data.extend_from_slice(&kind.to_le_bytes());
+        data.extend_from_slice(&length.to_le_bytes());
+        data.extend_from_slice(body);
+        data.resize(length as usize - 4, 0);
+        data.extend_from_slice(&length.to_le_bytes());
+        data
+    }
+
+    /// A little endian pcapng file with one Ethernet interface holding `frames`, a millisecond apart.
+    fn pcapng(frames: &[Vec<u8>]) -> Vec<u8> {
+        let frames: Vec<(u64, Vec<u8>)> = frames.iter().enumerate().map(|(i, frame)| (i as u64 * 1000, frame.clone())).collect();
+        pcapng_at(&frames)
+    }
+
+    /// A little endian pcapng file with one Ethernet interface holding the frames, each captured the given
+    /// number of microseconds after the epoch.
+    fn pcapng_at(frames: &[(u64, Vec<u8>)]) -> Vec<u8> {
+        let mut data = block(pcapng::BLOCK_SECTION_HEADER, &[0x4D, 0x3C, 0x2B, 0x1A, 1, 0, 0, 0, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]);
+        data.extend(block(pcapng::BLOCK_INTERFACE_DESCRIPTION, &[1, 0, 0, 0, 0xFF, 0xFF, 0, 0]));
+        for (micros, frame) in frames {
+            let mut body = Vec::new();
+            body.extend_from_slice(&0u32.to_le_bytes());
+            body.extend_from_slice(&((micros >> 32) as u32).to_le_bytes());
+            body.extend_from_slice(&(*micros as u32).to_le_bytes());
+            body.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+            body.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+            body.extend_from_slice(frame);
+            data.extend(block(pcapng::BLOCK_ENHANCED_PACKET, &body));
+        }
+        data
+    }
+
+    #[test]
+    fn count_stops_after_that_many_packets() {
+        let path = std::env::temp_dir().join(format!("packet-analyser-count-{}.pcapng", std::process::id()));
+        std::fs::write(&path, pcapng(&vec![vec![0; 60]; 5])).unwrap();
+
+        let arguments = Arguments::parse(["--count", "3"].map(String::from)).unwrap();
+        let mut counter = Counter::default();
+        let health = capture_loop(open_file(path.to_str().unwrap()).as_mut(), &mut counter, &arguments, None);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(health.total, 3);
+        assert_eq!(counter.packets, 3);
+    }
+
+    #[test]
+    fn skips_packets_outside_the_time_window() {
+        // Out of order around the window, from 1700000010 to 1700000020 seconds.
+        let seconds = [1_700_000_005, 1_700_000_012, 1_700_000_025, 1_700_000_010, 1_700_000_020, 1_700_000_021, 1_700_000_015];
+        let frames: Vec<(u64, Vec<u8>)> = seconds.iter().map(|x| (x * 1_000_000, vec![0; 60])).collect();
+        let path = std::env::temp_dir().join(format!("packet-analyser-window-{}.pcapng", std::process::id()));
+        std::fs::write(&path, pcapng_at(&frames)).unwrap();
+
+        let arguments = Arguments::parse(["--since", "1700000010", "--until", "1700000020"].map(String::from)).unwrap();
+        let mut counter = Counter::default();
+        capture_loop(open_file(path.to_str().unwrap()).as_mut(), &mut counter, &arguments, None);
+        std::fs::remove_file(&path).unwrap();
+
+        let seconds: Vec<i64> = counter.timestamps.iter().map(|x| x / 1_000_000).collect();
+        assert_eq!(seconds, [1_700_000_012, 1_700_000_010, 1_700_000_020, 1_700_000_015]);
+    }
+
+    #[test]
+    fn replays_a_file_with_its_gaps_scaled_by_the_speed() {
+        // Three seconds of capture, replayed twenty times faster.
+        let micros = [1_700_000_000_000_000u64, 1_700_000_001_000_000, 1_700_000_002_000_000, 1_700_000_002_500_000, 1_700_000_003_000_000];
+        let frames: Vec<(u64, Vec<u8>)> = micros.iter().map(|x| (*x, vec![0; 60])).collect();
+        let path = std::env::temp_dir().join(format!("packet-analyser-replay-{}.pcapng", std::process::id()));
+        std::fs::write(&path, pcapng_at(&frames)).unwrap();
+
+        let arguments = Arguments::parse(["--replay", "--speed", "20"].map(String::from)).unwrap();
+        let mut counter = Counter::default();
+        let start = Instant::now();
+        capture_loop(open_file(path.to_str().unwrap()).as_mut(), &mut counter, &arguments, None);
+        let elapsed = start.elapsed();
+        std::fs::remove_file(&path).unwrap();
+
+        let timestamps: Vec<u64> = counter.timestamps.iter().map(|x| *x as u64).collect();
+        assert_eq!(timestamps, micros);
+        assert!(elapsed >= Duration::from_millis(140), "{:?}", elapsed);
+        assert!(elapsed < Duration::from_secs(2), "{:?}", elapsed);
+    }
+
+    #[test]
+    fn tallies_decoded_truncated_and_unsupported_packets() {
+        use packet_analyser::builder::{Ipv4Builder, TcpBuilder};
+        let ethernet = |ether_type: u16, payload: &[u8]| {
+            let mut frame = vec![0x02, 0, 0, 0, 0, 0x0B, 0x02, 0, 0, 0, 0, 0x0A];
+            frame.extend_from_slice(&ether_type.to_be_bytes());
+            frame.extend_from_slice(payload);
+            frame
+        };
+        let packet = Ipv4Builder::new("10.0.0.1".parse().unwrap(), "10.0.0.2".parse().unwrap()).with_tcp(&TcpBuilder::new(51000, 80)).build();
+        let good = ethernet(0x0800, &packet);
+        let truncated = good[..24].to_vec();
+        let unsupported = ethernet(0x88B5, b"local experimental");
+        let path = std::env::temp_dir().join(format!("packet-analyser-health-{}.pcapng", std::process::id()));
+        std::fs::write(&path, pcapng(&[good, truncated, unsupported])).unwrap();
+
+        let arguments = Arguments::parse(Vec::<String>::new()).unwrap();
+        let mut printer = visitor::Printer::with_writer(Box::new(std::io::sink()));
+        let health = capture_loop(open_file(path.to_str().unwrap()).as_mut(), &mut printer, &arguments, None);
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(health, Health { total: 3, decoded: 1, truncated: 1, malformed: 0, unsupported: 1 });
+        assert_eq!(health.to_string(), "3 packets: 1 decoded, 1 truncated, 0 malformed, 1 unsupported");
+    }
+
+    #[test]
+    fn drives_the_loop_from_memory() {
+        use packet_analyser::source::MockSource;
+        let frames: Vec<(Duration, Vec<u8>)> = (0..4).map(|i| (Duration::from_secs(1_700_000_000 + i), vec![0; 60])).collect();
+
+        let arguments = Arguments::parse(Vec::<String>::new()).unwrap();
+        let mut counter = Counter::default();
+        let health = capture_loop(&mut MockSource::new(frames.clone()), &mut counter, &arguments, None);
+        assert_eq!(health.total, 4);
+        assert_eq!(counter.timestamps, [1_700_000_000_000_000, 1_700_000_001_000_000, 1_700_000_002_000_000, 1_700_000_003_000_000]);
+
+        let arguments = Arguments::parse(["--count", "2"].map(String::from)).unwrap();
+        let mut counter = Counter::default();
+        capture_loop(&mut MockSource::new(frames), &mut counter, &arguments, None);
+        assert_eq!(counter.packets, 2);
+    }
+
+    #[test]
+    fn skips_timeouts_and_stops_at_an_error() {
+        use packet_analyser::source::OwnedPacket;
+
+        /// Times out, yields a frame, times out again, fails, and would yield another frame after that.
+        struct Flaky(Vec<Result<OwnedPacket, Error>>);
+        impl PacketSource for Flaky {
+            fn next(&mut self) -> Option<Result<OwnedPacket, Error>> {
+                (!self.0.is_empty()).then(|| self.0.remove(0))
+            }
+        }
+        let frame = || Ok(OwnedPacket::new(LINK_TYPE_ETHERNET, Duration::ZERO, 60, vec![0; 60]));
+        let timeout = || Err(Error::new(ErrorKind::TimedOut, "Capture timed out"));
+        let mut source = Flaky(vec![timeout(), frame(), timeout(), Err(Error::other("Device went away")), frame()]);
+
+        let arguments = Arguments::parse(Vec::<String>::new()).unwrap();
+        let mut counter = Counter::default();
+        let health = capture_loop(&mut source, &mut counter, &arguments, None);
+        assert_eq!(health.total, 1);
+        assert_eq!(counter.packets, 1);
+        assert_eq!(source.0.len(), 1);
+    }
+
+    #[test]
+    fn parses_the_local_addresses_for_checksums() {
+        let arguments = Arguments::parse(["--checksums", "inbound", "--local-mac", "02:00:00:00:00:0a", "--local-ip", "10.0.0.1"].map(String::from)).unwrap();
+        assert_eq!(arguments.checksums, Some(packet_analyser::checksum::ChecksumPolicy::VerifyInbound));
+        assert_eq!(arguments.local_macs, ["02:00:00:00:00:0a".parse().unwrap()]);
+        assert_eq!(arguments.local_ips, ["10.0.0.1".parse().unwrap()]);
+        assert!(Arguments::parse(["--local-mac", "02:00:00:00:00"].map(String::from)).is_err());
+    }
+}
@@ -0,0 +1,141 @@
+/*
+https://www.rfc-editor.org/rfc/rfc3032
+*/
+
+use crate::error::ParseError;
+use crate::ipv4;
+use crate::ipv6;
+use crate::endian::be_u32;
+
+
+#[derive(Debug)]
+pub enum Payload<'a> {
+    IPv4(ipv4::IPv4<'a>),
+    IPv6(ipv6::IPv6<'a>),
+    /// Neither IPv4 nor IPv6 judging by the first nibble, e.g. a pseudowire.
+    Unknown(&'a [u8])
+}
+
+
+/// One entry of the label stack.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Label {
+    pub label: u32,
+    pub traffic_class: u8,
+    pub bottom_of_stack: bool,
+    pub ttl: u8,
+}
+
+impl Label {
+    pub const SIZE: usize = 4;
+
+    pub fn from_u32(entry: u32) -> Self {
+        Self {
+            label: entry >> 12,
+            traffic_class: ((entry >> 9) & 0b111) as u8,
+            bottom_of_stack: (entry >> 8) & 1 != 0,
+            ttl: (entry & 0xFF) as u8,
+        }
+    }
+}
+
+
+#[derive(Clone)]
+pub struct Mpls<'a> {
+    data: &'a [u8],
+    /// Number of labels up to and including the one with the bottom of stack bit.
+    count: usize,
+}
+
+impl<'a> Mpls<'a> {
+    pub fn labels(&self) -> impl Iterator<Item=Label> + 'a {
+        let data = self.data;
+        (0..self.count).map(move |i| Label::from_u32(be_u32(data, i * Label::SIZE)))
+    }
+
+    pub fn header_size(&self) -> usize { self.count * Label::SIZE }
+
+    pub fn raw_payload(&self) -> &'a [u8] {
+        &self.data[self.header_size()..]
+    }
+
+    /// The stack doesn't say what it carries, so the payload is guessed from the IP version nibble.
+    pub fn payload(&self) -> Result<Payload<'a>, ParseError> {
+        match self.raw_payload().first().map(|x| x >> 4) {
+            Some(4) => Ok(Payload::IPv4(ipv4::IPv4::from_bytes(self.raw_payload())?)),
+            Some(6) => Ok(Payload::IPv6(ipv6::IPv6::from_bytes(self.raw_payload())?)),
+            _ => Ok(Payload::Unknown(self.raw_payload())),
+        }
+    }
+
+    pub fn from_bytes(data: &'a [u8]) -> Result<Self, ParseError> {
+        let mut count = 0;
+        loop {
+            if data.len() < (count + 1) * Label::SIZE {
+                return Err(ParseError::TooSmall { layer: "Mpls", field: "label stack", expected: (count + 1) * Label::SIZE, got: data.len() });
+            }
+            count += 1;
+            if Label::from_u32(be_u32(data, (count - 1) * Label::SIZE)).bottom_of_stack { break }
+        }
+        Ok(Self { data, count })
+    }
+}
+
+
+impl<'a> core::fmt::Debug for Mpls<'a> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "    Mpls\n")?;
+        for (i, label) in self.labels().enumerate() {
+            write!(f, "        label[{}]: {:?}\n", i, label)?;
+        }
+        write!(f, "        payload: {:?}\n", self.payload())?;
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+    use crate::builder::{Ipv4Builder, TcpBuilder, TCP_SYN};
+    use crate::testing::ip;
+
+    fn entry(label: u32, traffic_class: u8, bottom_of_stack: bool, ttl: u8) -> [u8; 4] {
+        (label << 12 | (traffic_class as u32) << 9 | (bottom_of_stack as u32) << 8 | ttl as u32).to_be_bytes()
+    }
+
+    #[test]
+    fn decodes_a_two_label_stack_over_ipv4() {
+        let inner = Ipv4Builder::new(ip(10, 0, 0, 1), ip(10, 0, 0, 2)).with_tcp(&TcpBuilder::new(51000, 80).with_flags(TCP_SYN)).build();
+        let mut data = Vec::new();
+        data.extend_from_slice(&entry(16, 5, false, 64));
+        data.extend_from_slice(&entry(1000, 0, true, 63));
+        data.extend_from_slice(&inner);
+
+        let stack = Mpls::from_bytes(&data).unwrap();
+        assert_eq!(stack.header_size(), 8);
+        assert_eq!(stack.labels().collect::<Vec<_>>(), [
+            Label { label: 16, traffic_class: 5, bottom_of_stack: false, ttl: 64 },
+            Label { label: 1000, traffic_class: 0, bottom_of_stack: true, ttl: 63 },
+        ]);
+        let Ok(Payload::IPv4(packet)) = stack.payload() else { panic!("the stack doesn't hold IPv4") };
+        assert_eq!(packet.source_address(), ip(10, 0, 0, 1));
+        assert_eq!(packet.destination_address(), ip(10, 0, 0, 2));
+    }
+
+    #[test]
+    fn guesses_unknown_payloads_from_the_first_nibble() {
+        let mut data = Vec::from(entry(3, 0, true, 1));
+        data.extend_from_slice(&[0x00, 0x01, 0x02]);
+        let stack = Mpls::from_bytes(&data).unwrap();
+        assert!(matches!(stack.payload(), Ok(Payload::Unknown([0x00, 0x01, 0x02]))));
+    }
+
+    #[test]
+    fn rejects_a_stack_without_a_bottom() {
+        let mut data = Vec::from(entry(16, 0, false, 64));
+        data.extend_from_slice(&entry(17, 0, false, 64));
+        assert!(matches!(Mpls::from_bytes(&data), Err(ParseError::TooSmall { layer: "Mpls", expected: 12, got: 8, .. })));
+    }
+}
@@ -0,0 +1,186 @@
+use std::collections::HashMap;
+use std::io::Error;
+use crate::reassembly::StreamKey;
+use crate::tcp::{self, Tcp};
+use crate::time_window;
+use crate::visitor::{Context, Visitor};
+
+
+/// The MSS of IPv4 over standard Ethernet, 1500 bytes minus the IPv4 and TCP headers. Smaller values usually
+/// mean a tunnel or PPPoE on the path eats into the MTU.
+pub const ETHERNET_MSS: u16 = 1460;
+
+
+/// What a segment revealed about the maximum segment size.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum MssEvent {
+    /// A SYN advertised less than `ETHERNET_MSS`.
+    Reduced { mss: u16 },
+    /// The payload is larger than the MSS the receiver advertised, e.g. a misconfigured or crafted sender, or
+    /// segments the capturing host's NIC coalesced.
+    Oversize { payload: usize, mss: u16 },
+}
+
+
+/// The MSS of one direction of a connection.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub struct FlowMss {
+    /// The MSS this side advertised in its SYN.
+    pub advertised: Option<u16>,
+    /// Segments this side sent above the MSS the other side advertised.
+    pub oversize: usize,
+    pub largest_payload: usize,
+}
+
+
+/// Remembers the MSS each side of a connection advertised and checks the segments sent against the MSS of
+/// the receiving side.
+#[derive(Debug, Default)]
+pub struct MssTracker {
+    directions: HashMap<StreamKey, FlowMss>,
+}
+
+impl MssTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records the MSS option of a SYN, and checks the payload of other segments.
+    pub fn push(&mut self, key: StreamKey, tcp: &Tcp) -> Vec<MssEvent> {
+        let mut events = Vec::new();
+        let payload = tcp.raw_payload().len();
+
+        if tcp.syn() != 0 {
+            let mss = tcp.options().find_map(|option| match option {
+                tcp::Option::MaximumSegmentSize { size } => Some(size),
+                _ => None,
+            });
+            if let Some(mss) = mss.filter(|x| *x < ETHERNET_MSS) {
+                events.push(MssEvent::Reduced { mss });
+            }
+            self.directions.entry(key).or_default().advertised = mss;
+        }
+
+        let limit = self.directions.get(&key.reversed()).and_then(|x| x.advertised);
+        let direction = self.directions.entry(key).or_default();
+        direction.largest_payload = direction.largest_payload.max(payload);
+        if let Some(mss) = limit.filter(|x| payload > *x as usize) {
+            direction.oversize += 1;
+            events.push(MssEvent::Oversize { payload, mss });
+        }
+        events
+    }
+
+    /// The directions seen, in address order.
+    pub fn report(&self) -> Vec<(StreamKey, FlowMss)> {
+        let mut report: Vec<(StreamKey, FlowMss)> = self.directions.iter().map(|(key, mss)| (*key, *mss)).collect();
+        report.sort_by_key(|(key, _)| (key.source, key.source_port, key.destination, key.destination_port));
+        report
+    }
+}
+
+
+/// Prints MSS violations as they happen and the MSS of each direction at the end.
+pub struct MssVisitor {
+    tracker: MssTracker,
+}
+
+impl MssVisitor {
+    pub fn new() -> Self {
+        Self { tracker: MssTracker::new() }
+    }
+}
+
+impl Default for MssVisitor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+impl<'a> Visitor<'a, ()> for MssVisitor {
+    fn visit_tcp(&mut self, packet: &Tcp<'a>, context: &Context<'a>) -> Result<(), Error> {
+        let Some(ip) = &context.ipv4 else { return Ok(()) };
+        let key = StreamKey::new(ip, packet);
+        let time = context.header.map(time_window::timestamp).unwrap_or_default();
+
+        for event in self.tracker.push(key, packet) {
+            match event {
+                MssEvent::Reduced { mss } => {
+                    println!("{}.{:06} {}: MSS {}, {} bytes of overhead on the path, e.g. a tunnel", time.as_secs(), time.subsec_micros(), key, mss, ETHERNET_MSS - mss);
+                },
+                MssEvent::Oversize { payload, mss } => {
+                    println!("[WARNING] {}.{:06} {}: {} byte payload exceeds the MSS of {}", time.as_secs(), time.subsec_micros(), key, payload, mss);
+                },
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        for (key, mss) in self.tracker.report() {
+            let advertised = match mss.advertised {
+                Some(advertised) => advertised.to_string(),
+                None             => "unknown".to_string(),
+            };
+            println!("{}: MSS {}, largest payload {}, {} oversize segments", key, advertised, mss.largest_payload, mss.oversize);
+        }
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::{TcpBuilder, TCP_ACK, TCP_SYN};
+    use crate::testing::{self, ip};
+
+    fn client() -> StreamKey {
+        StreamKey { source: ip(10, 0, 0, 1), source_port: 51000, destination: ip(10, 0, 0, 2), destination_port: 80 }
+    }
+
+    /// A SYN in the direction of `key` advertising `mss`.
+    fn syn(tracker: &mut MssTracker, key: StreamKey, mss: u16, flags: u8) -> Vec<MssEvent> {
+        let [high, low] = mss.to_be_bytes();
+        let segment = testing::tcp_with_options(&TcpBuilder::new(key.source_port, key.destination_port).with_flags(flags), &[2, 4, high, low]);
+        tracker.push(key, &Tcp::from_bytes(&segment).unwrap())
+    }
+
+    fn data(tracker: &mut MssTracker, key: StreamKey, length: usize) -> Vec<MssEvent> {
+        let segment = TcpBuilder::new(key.source_port, key.destination_port).with_flags(TCP_ACK).with_payload(&vec![0; length]).build(key.source, key.destination);
+        tracker.push(key, &Tcp::from_bytes(&segment).unwrap())
+    }
+
+    #[test]
+    fn flags_a_segment_above_the_advertised_mss() {
+        let mut tracker = MssTracker::new();
+        assert_eq!(syn(&mut tracker, client(), 1460, TCP_SYN), []);
+        assert_eq!(syn(&mut tracker, client().reversed(), 1460, TCP_SYN | TCP_ACK), []);
+
+        assert_eq!(data(&mut tracker, client(), 1460), []);
+        assert_eq!(data(&mut tracker, client(), 2920), [MssEvent::Oversize { payload: 2920, mss: 1460 }]);
+        assert_eq!(data(&mut tracker, client().reversed(), 1000), []);
+
+        assert_eq!(tracker.report(), [
+            (client(), FlowMss { advertised: Some(1460), oversize: 1, largest_payload: 2920 }),
+            (client().reversed(), FlowMss { advertised: Some(1460), oversize: 0, largest_payload: 1000 }),
+        ]);
+    }
+
+    #[test]
+    fn notes_an_mss_reduced_by_tunnel_overhead() {
+        let mut tracker = MssTracker::new();
+        assert_eq!(syn(&mut tracker, client(), 1460, TCP_SYN), []);
+        // The server is behind PPPoE.
+        assert_eq!(syn(&mut tracker, client().reversed(), 1452, TCP_SYN | TCP_ACK), [MssEvent::Reduced { mss: 1452 }]);
+        assert_eq!(data(&mut tracker, client(), 1460), [MssEvent::Oversize { payload: 1460, mss: 1452 }]);
+    }
+
+    #[test]
+    fn checks_nothing_without_the_handshake() {
+        let mut tracker = MssTracker::new();
+        assert_eq!(data(&mut tracker, client(), 9000), []);
+        assert_eq!(tracker.report(), [(client(), FlowMss { advertised: None, oversize: 0, largest_payload: 9000 })]);
+    }
+}
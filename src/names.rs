@@ -0,0 +1,205 @@
+/*
+https://www.iana.org/assignments/ieee-802-numbers
+https://en.wikipedia.org/wiki/IEEE_P802.1p
+https://www.iana.org/assignments/protocol-numbers
+https://www.iana.org/assignments/service-names-port-numbers
+*/
+
+
+static ETHER_TYPES: &[(u16, &str)] = &[
+    (0x0800, "IPv4"),
+    (0x0806, "ARP"),
+    (0x0842, "Wake-on-LAN"),
+    (0x22F0, "AVTP"),
+    (0x22F3, "TRILL"),
+    (0x22EA, "SRP"),
+    (0x6002, "DEC MOP RC"),
+    (0x6003, "DECnet Phase IV"),
+    (0x6004, "DEC LAT"),
+    (0x8035, "RARP"),
+    (0x809B, "AppleTalk"),
+    (0x80F3, "AARP"),
+    (0x8100, "802.1Q VLAN"),
+    (0x8102, "SLPP"),
+    (0x8103, "VLACP"),
+    (0x8137, "IPX"),
+    (0x8204, "QNX Qnet"),
+    (0x86DD, "IPv6"),
+    (0x8808, "Ethernet flow control"),
+    (0x8809, "Slow Protocols"),
+    (0x8819, "CobraNet"),
+    (0x8847, "MPLS unicast"),
+    (0x8848, "MPLS multicast"),
+    (0x8863, "PPPoE Discovery"),
+    (0x8864, "PPPoE Session"),
+    (0x887B, "HomePlug 1.0 MME"),
+    (0x888E, "EAPOL"),
+    (0x8892, "PROFINET"),
+    (0x889A, "HyperSCSI"),
+    (0x88A2, "ATA over Ethernet"),
+    (0x88A4, "EtherCAT"),
+    (0x88A8, "802.1ad QinQ"),
+    (0x88AB, "Ethernet Powerlink"),
+    (0x88B8, "GOOSE"),
+    (0x88B9, "GSE"),
+    (0x88BA, "SV"),
+    (0x88BF, "MikroTik RoMON"),
+    (0x88CC, "LLDP"),
+    (0x88CD, "SERCOS III"),
+    (0x88E1, "HomePlug Green PHY"),
+    (0x88E3, "MRP"),
+    (0x88E5, "MACsec"),
+    (0x88E7, "PBB"),
+    (0x88F7, "PTP"),
+    (0x88F8, "NC-SI"),
+    (0x88FB, "PRP"),
+    (0x8902, "CFM"),
+    (0x8906, "FCoE"),
+    (0x8914, "FIP"),
+    (0x8915, "RoCE"),
+    (0x891D, "TTE"),
+    (0x893A, "IEEE 1905.1"),
+    (0x892F, "HSR"),
+    (0x9000, "Ethernet Configuration Testing Protocol"),
+    (0xF1C1, "802.1CB Redundancy Tag"),
+];
+
+static IP_PROTOCOLS: &[(u8, &str)] = &[
+    (0,   "HOPOPT"),
+    (1,   "ICMP"),
+    (2,   "IGMP"),
+    (4,   "IPv4"),
+    (6,   "TCP"),
+    (17,  "UDP"),
+    (41,  "IPv6"),
+    (43,  "IPv6-Route"),
+    (44,  "IPv6-Frag"),
+    (47,  "GRE"),
+    (50,  "ESP"),
+    (51,  "AH"),
+    (58,  "ICMPv6"),
+    (59,  "IPv6-NoNxt"),
+    (60,  "IPv6-Opts"),
+    (88,  "EIGRP"),
+    (89,  "OSPF"),
+    (103, "PIM"),
+    (112, "VRRP"),
+    (115, "L2TP"),
+    (132, "SCTP"),
+    (136, "UDPLite"),
+    (137, "MPLS-in-IP"),
+];
+
+static TCP_SERVICES: &[(u16, &str)] = &[
+    (20,    "ftp-data"),
+    (21,    "ftp"),
+    (22,    "ssh"),
+    (23,    "telnet"),
+    (25,    "smtp"),
+    (53,    "domain"),
+    (80,    "http"),
+    (110,   "pop3"),
+    (111,   "sunrpc"),
+    (119,   "nntp"),
+    (135,   "msrpc"),
+    (139,   "netbios-ssn"),
+    (143,   "imap"),
+    (179,   "bgp"),
+    (389,   "ldap"),
+    (443,   "https"),
+    (445,   "microsoft-ds"),
+    (465,   "smtps"),
+    (587,   "submission"),
+    (636,   "ldaps"),
+    (993,   "imaps"),
+    (995,   "pop3s"),
+    (1433,  "ms-sql-s"),
+    (1521,  "oracle"),
+    (3306,  "mysql"),
+    (3389,  "ms-wbt-server"),
+    (5432,  "postgresql"),
+    (5900,  "vnc"),
+    (6379,  "redis"),
+    (8080,  "http-alt"),
+    (8443,  "https-alt"),
+];
+
+
+fn lookup<K: PartialEq + Copy>(table: &'static [(K, &'static str)], key: K) -> Option<&'static str> {
+    table.iter().find(|(x, _)| *x == key).map(|(_, name)| *name)
+}
+
+
+/// The name of an EtherType as written on the wire, e.g. `IPv4` for 0x0800, or `Unknown`.
+pub fn ethertype_name(code: u16) -> &'static str {
+    lookup(ETHER_TYPES, code).unwrap_or("Unknown")
+}
+
+/// The name of an IPv4 protocol or IPv6 next header number, e.g. `TCP` for 6, or `Unknown`.
+pub fn ip_protocol_name(protocol: u8) -> &'static str {
+    lookup(IP_PROTOCOLS, protocol).unwrap_or("Unknown")
+}
+
+/// The well-known service on a TCP port, e.g. `https` for 443.
+pub fn tcp_port_service(port: u16) -> Option<&'static str> {
+    lookup(TCP_SERVICES, port)
+}
+
+/// The IEEE 802.1p traffic class of a VLAN priority code point, e.g. `Voice` for 5. Note that 0 is the
+/// default and ranks above 1.
+pub fn pcp_traffic_class(pcp: u8) -> &'static str {
+    match pcp & 0b111 {
+        0 => "Best Effort",
+        1 => "Background",
+        2 => "Excellent Effort",
+        3 => "Critical Applications",
+        4 => "Video",
+        5 => "Voice",
+        6 => "Internetwork Control",
+        _ => "Network Control",
+    }
+}
+
+/// What the drop eligible indicator of a VLAN tag means for switches under congestion.
+pub fn dei_meaning(dei: u8) -> &'static str {
+    if dei & 1 != 0 { "may be dropped first" } else { "not drop eligible" }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_ether_types() {
+        assert_eq!(ethertype_name(0x0800), "IPv4");
+        assert_eq!(ethertype_name(0x0806), "ARP");
+        assert_eq!(ethertype_name(0x88B5), "Unknown");
+    }
+
+    #[test]
+    fn names_ip_protocols() {
+        assert_eq!(ip_protocol_name(6), "TCP");
+        assert_eq!(ip_protocol_name(17), "UDP");
+        assert_eq!(ip_protocol_name(58), "ICMPv6");
+        assert_eq!(ip_protocol_name(253), "Unknown");
+    }
+
+    #[test]
+    fn names_well_known_tcp_ports() {
+        assert_eq!(tcp_port_service(22), Some("ssh"));
+        assert_eq!(tcp_port_service(443), Some("https"));
+        assert_eq!(tcp_port_service(8080), Some("http-alt"));
+        assert_eq!(tcp_port_service(51000), None);
+    }
+
+    #[test]
+    fn names_vlan_priorities() {
+        assert_eq!(pcp_traffic_class(5), "Voice");
+        assert_eq!(pcp_traffic_class(0), "Best Effort");
+        assert_eq!(pcp_traffic_class(1), "Background");
+        assert_eq!(pcp_traffic_class(7), "Network Control");
+        assert_eq!(dei_meaning(1), "may be dropped first");
+        assert_eq!(dei_meaning(0), "not drop eligible");
+    }
+}
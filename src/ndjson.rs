@@ -0,0 +1,241 @@
+use std::io::{Error, Write};
+use pcap::Packet;
+use crate::ethernet::Ethernet;
+use crate::ipv4::IPv4;
+use crate::ipv6::IPv6;
+use crate::radiotap::{Radiotap, Ieee80211};
+use crate::sll::Sll2;
+use crate::tcp::Tcp;
+use crate::udp::Udp;
+use crate::visitor::{Context, Visitor};
+
+
+/// Renders `text` as a JSON string literal.
+pub fn json_string(text: &str) -> String {
+    let mut out = String::with_capacity(text.len() + 2);
+    out.push('"');
+    for c in text.chars() {
+        match c {
+            '"'  => out += "\\\"",
+            '\\' => out += "\\\\",
+            '\n' => out += "\\n",
+            '\r' => out += "\\r",
+            '\t' => out += "\\t",
+            c if (c as u32) < 0x20 => out += &format!("\\u{:04x}", c as u32),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+
+/// Fields of a JSON object whose values are already rendered.
+#[derive(Debug, Default)]
+struct Object {
+    fields: Vec<(&'static str, String)>,
+}
+
+impl Object {
+    fn number(mut self, key: &'static str, value: impl std::fmt::Display) -> Self {
+        self.fields.push((key, value.to_string()));
+        self
+    }
+
+    fn string(mut self, key: &'static str, value: &str) -> Self {
+        self.fields.push((key, json_string(value)));
+        self
+    }
+
+    fn object(mut self, key: &'static str, value: Object) -> Self {
+        self.fields.push((key, value.render()));
+        self
+    }
+
+    fn render(&self) -> String {
+        let fields: Vec<String> = self.fields.iter().map(|(key, value)| format!("{}:{}", json_string(key), value)).collect();
+        format!("{{{}}}", fields.join(","))
+    }
+}
+
+
+/// Writes one compact JSON object per packet and line, flushed right away so it can be piped into e.g. `jq`.
+///
+/// Every line has `schema_version`, `index` (counting from 1), `timestamp` (seconds since the Unix epoch) and
+/// `length`. Decoded layers follow as objects named `ethernet`, `ipv4`, `ipv6`, `tcp` and `udp`, and a packet
+/// that fails to decode has an `error` string instead of the layers.
+pub struct NdjsonVisitor<W: Write> {
+    out: W,
+    index: usize,
+    layers: Object,
+}
+
+impl<W: Write> NdjsonVisitor<W> {
+    pub const SCHEMA_VERSION: u32 = 1;
+
+    pub fn new(out: W) -> Self {
+        Self { out, index: 0, layers: Object::default() }
+    }
+
+    /// Writes the line of one packet, with the layers collected while visiting it or the error.
+    fn write_line(&mut self, packet: &Packet, result: Result<(), Error>) -> Result<(), Error> {
+        self.index += 1;
+        let mut line = Object::default()
+            .number("schema_version", Self::SCHEMA_VERSION)
+            .number("index", self.index)
+            .number("timestamp", format!("{}.{:06}", packet.header.ts.tv_sec, packet.header.ts.tv_usec))
+            .number("length", packet.header.len);
+        match result {
+            Ok(())     => line.fields.append(&mut std::mem::take(&mut self.layers).fields),
+            Err(error) => line = line.string("error", &error.to_string()),
+        }
+
+        writeln!(self.out, "{}", line.render())?;
+        self.out.flush()
+    }
+}
+
+
+impl<'a, W: Write> Visitor<'a, ()> for NdjsonVisitor<W> {
+    fn visit_packet(&mut self, packet: &'a Packet) -> Result<(), Error> {
+        self.layers = Object::default();
+        let result = self.visit_packet_payload(packet);
+        self.write_line(packet, result)
+    }
+
+    fn visit_radiotap_packet(&mut self, packet: &'a Packet) -> Result<(), Error> {
+        self.layers = Object::default();
+        let context = Context::new(packet.header);
+        let result = Radiotap::from_bytes(packet.data).map_err(Error::from).and_then(|radiotap| self.visit_radiotap(&radiotap, &context));
+        self.write_line(packet, result)
+    }
+
+    fn visit_sll2_packet(&mut self, packet: &'a Packet) -> Result<(), Error> {
+        self.layers = Object::default();
+        let context = Context::new(packet.header);
+        let result = Sll2::from_bytes(packet.data).map_err(Error::from).and_then(|sll2| self.visit_sll2(&sll2, &context));
+        self.write_line(packet, result)
+    }
+
+    fn visit_ethernet(&mut self, packet: &Ethernet<'a>, context: &Context<'a>) -> Result<(), Error> {
+        let layer = Object::default()
+            .string("source", &format!("{:?}", packet.source()))
+            .string("destination", &format!("{:?}", packet.destination()))
+            .number("ether_type", packet.ether_type().as_u16());
+        self.layers = std::mem::take(&mut self.layers).object("ethernet", layer);
+        self.visit_ethernet_payload(&packet.payload()?, &context.with_ethernet(packet))
+    }
+
+    fn visit_ieee80211(&mut self, packet: &Ieee80211<'a>, context: &Context<'a>) -> Result<(), Error> {
+        let mut layer = Object::default()
+            .number("type", packet.kind())
+            .number("subtype", packet.subtype());
+        if let Some(source) = packet.source() { layer = layer.string("source", &format!("{:?}", source)) }
+        if let Some(destination) = packet.destination() { layer = layer.string("destination", &format!("{:?}", destination)) }
+        self.layers = std::mem::take(&mut self.layers).object("ieee80211", layer);
+        self.visit_ieee80211_payload(&packet.payload()?, context)
+    }
+
+    fn visit_sll2(&mut self, packet: &Sll2<'a>, context: &Context<'a>) -> Result<(), Error> {
+        let layer = Object::default()
+            .number("protocol_type", packet.protocol_type())
+            .number("interface_index", packet.interface_index())
+            .number("packet_type", packet.packet_type());
+        self.layers = std::mem::take(&mut self.layers).object("sll2", layer);
+        self.visit_sll2_payload(&packet.payload()?, context)
+    }
+
+    fn visit_ipv4(&mut self, packet: &IPv4<'a>, context: &Context<'a>) -> Result<(), Error> {
+        let layer = Object::default()
+            .string("source", &format!("{:?}", packet.source_address()))
+            .string("destination", &format!("{:?}", packet.destination_address()))
+            .number("protocol", packet.protocol_raw())
+            .number("ttl", packet.time_to_live());
+        self.layers = std::mem::take(&mut self.layers).object("ipv4", layer);
+        self.visit_ipv4_payload(&packet.payload()?, &context.with_ipv4(packet))
+    }
+
+    fn visit_ipv6(&mut self, packet: &IPv6<'a>, context: &Context<'a>) -> Result<(), Error> {
+        let layer = Object::default()
+            .string("source", &format!("{:?}", packet.source_address()))
+            .string("destination", &format!("{:?}", packet.destination_address()))
+            .number("next_header", packet.next_header())
+            .number("hop_limit", packet.hop_limit());
+        self.layers = std::mem::take(&mut self.layers).object("ipv6", layer);
+        self.visit_ipv6_payload(&packet.payload()?, &context.with_ipv6(packet))
+    }
+
+    fn visit_tcp(&mut self, packet: &Tcp<'a>, context: &Context<'a>) -> Result<(), Error> {
+        let layer = Object::default()
+            .number("source_port", packet.source_port())
+            .number("destination_port", packet.destination_port())
+            .number("sequence_number", packet.sequence_number())
+            .number("acknowledgment_number", packet.acknowledgment_number())
+            .number("flags", packet.flags())
+            .number("window_size", packet.window_size())
+            .number("payload_length", packet.raw_payload().len());
+        self.layers = std::mem::take(&mut self.layers).object("tcp", layer);
+        Ok(())
+    }
+
+    fn visit_udp(&mut self, packet: &Udp<'a>, context: &Context<'a>) -> Result<(), Error> {
+        let layer = Object::default()
+            .number("source_port", packet.source_port())
+            .number("destination_port", packet.destination_port())
+            .number("payload_length", packet.raw_payload().len());
+        self.layers = std::mem::take(&mut self.layers).object("udp", layer);
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+    use crate::builder::{TcpBuilder, TCP_SYN};
+    use crate::raw::{visit_frame, RawReader};
+    use crate::testing::{self, ip, Json};
+
+    #[test]
+    fn writes_one_valid_json_object_per_line() {
+        let frames = [
+            testing::tcp_frame(ip(10, 0, 0, 1), ip(10, 0, 0, 2), &TcpBuilder::new(51000, 80).with_flags(TCP_SYN)),
+            testing::udp_frame(ip(10, 0, 0, 1), 53000, ip(10, 0, 0, 53), 53, b"\"query\"\n"),
+            // An IPv4 header cut short.
+            testing::ethernet(testing::ETHER_TYPE_IPV4, &[0x45, 0, 0]),
+        ];
+        let mut file = Vec::new();
+        for frame in &frames {
+            file.extend_from_slice(&(frame.len() as u32).to_be_bytes());
+            file.extend_from_slice(frame);
+        }
+
+        let mut out = Vec::new();
+        let mut visitor = NdjsonVisitor::new(&mut out);
+        let mut reader = RawReader::new(Cursor::new(file));
+        while let Some(frame) = reader.next_frame() {
+            let (data, timestamp) = frame.unwrap();
+            visit_frame(&mut visitor, &data, timestamp).unwrap();
+        }
+
+        let text = String::from_utf8(out).unwrap();
+        let lines: Vec<Json> = text.lines().map(|line| Json::parse(line).unwrap_or_else(|error| panic!("{}: {}", error, line))).collect();
+        assert_eq!(lines.len(), 3);
+        for (i, line) in lines.iter().enumerate() {
+            assert_eq!(line.get("schema_version"), Some(&Json::Number(1.0)));
+            assert_eq!(line.get("index"), Some(&Json::Number(i as f64 + 1.0)));
+        }
+        assert_eq!(lines[0].get("ipv4").and_then(|x| x.get("source")), Some(&Json::String("10.0.0.1".to_string())));
+        assert_eq!(lines[0].get("tcp").and_then(|x| x.get("destination_port")), Some(&Json::Number(80.0)));
+        assert_eq!(lines[1].get("udp").and_then(|x| x.get("payload_length")), Some(&Json::Number(8.0)));
+        assert!(matches!(lines[2].get("error"), Some(Json::String(_))), "{}", text);
+        assert_eq!(lines[2].get("ipv4"), None);
+    }
+
+    #[test]
+    fn escapes_strings() {
+        assert_eq!(json_string("a\"b\\c\nd\u{1}"), r#""a\"b\\c\nd\u0001""#);
+        assert_eq!(Json::parse(&json_string("tab\there")), Ok(Json::String("tab\there".to_string())));
+    }
+}
@@ -0,0 +1,236 @@
+use crate::error::ParseError;
+use crate::ethernet::{self, Ethernet, Fcs};
+use crate::ipv4::{self, IPv4};
+use crate::ipv6::{self, IPv6};
+use crate::{endian, mpls, pppoe};
+
+
+/// What `parse_only` found out about a frame.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub struct Layers {
+    pub ether_type: u16,
+    /// The IPv4 protocol or IPv6 next header, if the frame carries IP.
+    pub ip_protocol: Option<u8>,
+    /// Source and destination port, if the frame carries TCP, UDP or SCTP.
+    pub ports: Option<(u16, u16)>,
+    /// Size of the innermost payload that was reached.
+    pub payload_size: usize,
+}
+
+
+/// Validates the headers of a frame down to the transport layer without building any of the printable
+/// representations. Ip and TCP options are only checked if `options` is set.
+pub fn parse_only(data: &[u8], fcs: Fcs, options: bool) -> Result<Layers, ParseError> {
+    let frame = Ethernet::from_bytes_with_fcs(data, fcs)?;
+    let mut layers = Layers { ether_type: frame.ether_type().as_u16(), payload_size: frame.raw_payload().len(), ..Layers::default() };
+
+    match frame.payload()? {
+        ethernet::Payload::IPv4(ip) => ipv4_only(&ip, options, &mut layers)?,
+        ethernet::Payload::IPv6(ip) => ipv6_only(&ip, options, &mut layers)?,
+        ethernet::Payload::PPPoE(pppoe) => match pppoe.payload()? {
+            pppoe::Payload::IPv4(ip) => ipv4_only(&ip, options, &mut layers)?,
+            pppoe::Payload::IPv6(ip) => ipv6_only(&ip, options, &mut layers)?,
+            pppoe::Payload::Unknown(raw) => layers.payload_size = raw.len(),
+        },
+        ethernet::Payload::Mpls(mpls) => match mpls.payload()? {
+            mpls::Payload::IPv4(ip) => ipv4_only(&ip, options, &mut layers)?,
+            mpls::Payload::IPv6(ip) => ipv6_only(&ip, options, &mut layers)?,
+            mpls::Payload::Unknown(raw) => layers.payload_size = raw.len(),
+        },
+        ethernet::Payload::Lldp(_) | ethernet::Payload::Eapol(_) | ethernet::Payload::Unknown(_) => {},
+    }
+
+    Ok(layers)
+}
+
+fn ipv4_only(ip: &IPv4, options: bool, layers: &mut Layers) -> Result<(), ParseError> {
+    if options && ip.validate_options().is_err() {
+        return Err(ParseError::Invalid("Malformed Ipv4 options"));
+    }
+    layers.ip_protocol  = Some(ip.protocol_raw());
+    layers.payload_size = ip.raw_payload().len();
+
+    match ip.payload()? {
+        ipv4::Payload::Tcp(tcp) => {
+            if options { tcp.options().for_each(drop) }
+            layers.ports = Some((tcp.source_port(), tcp.destination_port()));
+            layers.payload_size = tcp.raw_payload().len();
+        },
+        ipv4::Payload::Udp(udp) => {
+            layers.ports = Some((udp.source_port(), udp.destination_port()));
+            layers.payload_size = udp.raw_payload().len();
+        },
+        ipv4::Payload::Sctp(sctp) => layers.ports = Some((sctp.source_port(), sctp.destination_port())),
+        ipv4::Payload::Gre(_) | ipv4::Payload::Ah(_) | ipv4::Payload::Esp(_) | ipv4::Payload::Icmp(_) | ipv4::Payload::Unknown(_) => {},
+    }
+    Ok(())
+}
+
+fn ipv6_only(ip: &IPv6, options: bool, layers: &mut Layers) -> Result<(), ParseError> {
+    layers.ip_protocol  = Some(ip.upper_layer_protocol());
+    layers.payload_size = ip.raw_payload().len();
+
+    match ip.payload()? {
+        ipv6::Payload::Tcp(tcp) => {
+            if options { tcp.options().for_each(drop) }
+            layers.ports = Some((tcp.source_port(), tcp.destination_port()));
+            layers.payload_size = tcp.raw_payload().len();
+        },
+        ipv6::Payload::Udp(udp) => {
+            layers.ports = Some((udp.source_port(), udp.destination_port()));
+            layers.payload_size = udp.raw_payload().len();
+        },
+        ipv6::Payload::Sctp(sctp) => layers.ports = Some((sctp.source_port(), sctp.destination_port())),
+        ipv6::Payload::Icmpv6(_) | ipv6::Payload::Gre(_) | ipv6::Payload::Esp(_) | ipv6::Payload::Unknown(_) => {},
+    }
+    Ok(())
+}
+
+
+/// What `peek` found out about a frame.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub struct PacketSummary {
+    /// The pcap link type the frame was read as, always Ethernet (1) for now.
+    pub link_type: u16,
+    pub ether_type: Option<u16>,
+    pub ip_protocol: Option<u8>,
+    pub ports: Option<(u16, u16)>,
+    /// Size of the whole frame.
+    pub length: usize,
+    layers: [&'static str; PacketSummary::MAX_LAYERS],
+    layer_count: usize,
+}
+
+impl PacketSummary {
+    pub const MAX_LAYERS: usize = 6;
+
+    /// The protocols found, outermost first, e.g. `["Ethernet", "IPv4", "TCP"]`.
+    pub fn layers(&self) -> &[&'static str] {
+        &self.layers[..self.layer_count]
+    }
+
+    fn push(&mut self, layer: &'static str) {
+        if self.layer_count < Self::MAX_LAYERS {
+            self.layers[self.layer_count] = layer;
+            self.layer_count += 1;
+        }
+    }
+}
+
+
+/// Reads the protocol stack of an Ethernet frame straight from the bytes, without validating the headers.
+/// Stops at the first layer that is truncated or not understood.
+pub fn peek(frame: &[u8]) -> PacketSummary {
+    let be_u16 = |i: usize| frame.get(i..i + 2).map(|x| endian::be_u16(x, 0));
+
+    let mut summary = PacketSummary { link_type: 1, length: frame.len(), ..PacketSummary::default() };
+    let Some(mut ether_type) = be_u16(12) else { return summary };
+    summary.push("Ethernet");
+
+    // Skip VLAN tags down to the EtherType of the payload.
+    let mut offset = 14;
+    while ether_type == 0x8100 || ether_type == 0x88A8 {
+        summary.push("VLAN");
+        let Some(inner) = be_u16(offset + 2) else { return summary };
+        ether_type = inner;
+        offset += 4;
+    }
+    summary.ether_type = Some(ether_type);
+
+    let protocol = match ether_type {
+        0x0800 => {
+            let Some(&first) = frame.get(offset) else { return summary };
+            let Some(&protocol) = frame.get(offset + 9) else { return summary };
+            summary.push("IPv4");
+            offset += (first & 0x0F) as usize * 4;
+            protocol
+        },
+        0x86DD => {
+            let Some(&protocol) = frame.get(offset + 6) else { return summary };
+            summary.push("IPv6");
+            offset += 40;
+            protocol
+        },
+        0x0806 => { summary.push("ARP");   return summary },
+        0x8863 | 0x8864 => { summary.push("PPPoE"); return summary },
+        0x8847 | 0x8848 => { summary.push("MPLS");  return summary },
+        0x888E => { summary.push("EAPOL"); return summary },
+        0x88CC => { summary.push("LLDP");  return summary },
+        _ => return summary,
+    };
+    summary.ip_protocol = Some(protocol);
+
+    let layer = match protocol {
+        1   => "ICMP",
+        6   => "TCP",
+        17  => "UDP",
+        47  => "GRE",
+        50  => "ESP",
+        51  => "AH",
+        58  => "ICMPv6",
+        132 => "SCTP",
+        _   => return summary,
+    };
+    if offset > frame.len() { return summary }
+    summary.push(layer);
+
+    if matches!(protocol, 6 | 17 | 132) {
+        if let (Some(source), Some(destination)) = (be_u16(offset), be_u16(offset + 2)) {
+            summary.ports = Some((source, destination));
+        }
+    }
+    summary
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+    use crate::builder::{Ipv4Builder, TcpBuilder, TCP_SYN};
+    use crate::testing::{self, ip};
+
+    /// Checks that `peek` agrees with a full decode of `frame` and returns its layers.
+    fn peek_and_decode(frame: &[u8]) -> Vec<&'static str> {
+        let summary = peek(frame);
+        let layers = parse_only(frame, Fcs::Absent, true).unwrap();
+        assert_eq!(summary.ether_type, Some(layers.ether_type));
+        assert_eq!(summary.ip_protocol, layers.ip_protocol);
+        assert_eq!(summary.ports, layers.ports);
+        assert_eq!(summary.length, frame.len());
+        summary.layers().to_vec()
+    }
+
+    #[test]
+    fn agrees_with_a_full_decode() {
+        let tcp = testing::tcp_frame(ip(10, 0, 0, 1), ip(10, 0, 0, 2), &TcpBuilder::new(51000, 443).with_flags(TCP_SYN));
+        assert_eq!(peek_and_decode(&tcp), ["Ethernet", "IPv4", "TCP"]);
+
+        let udp = testing::udp_frame(ip(10, 0, 0, 1), 53000, ip(10, 0, 0, 53), 53, b"query");
+        assert_eq!(peek_and_decode(&udp), ["Ethernet", "IPv4", "UDP"]);
+
+        let options = testing::ethernet(testing::ETHER_TYPE_IPV4, &testing::ipv4_with_options(&[148, 4, 0, 0]));
+        assert_eq!(peek_and_decode(&options), ["Ethernet", "IPv4", "UDP"]);
+
+        let segment = testing::tcp_with_options(&TcpBuilder::new(51000, 80), &[]);
+        let ipv6 = testing::ipv6_tcp_frame([0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1], [0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2], &segment);
+        assert_eq!(peek_and_decode(&ipv6), ["Ethernet", "IPv6", "TCP"]);
+
+        let gre = Ipv4Builder::new(ip(10, 0, 0, 1), ip(10, 0, 0, 2)).with_payload(ipv4::Protocol::GRE, &[0, 0, 0x86, 0xDD]).build();
+        assert_eq!(peek_and_decode(&testing::ethernet(testing::ETHER_TYPE_IPV4, &gre)), ["Ethernet", "IPv4", "GRE"]);
+
+        let arp = testing::ethernet(0x0806, &[0; 28]);
+        assert_eq!(peek_and_decode(&arp), ["Ethernet", "ARP"]);
+    }
+
+    #[test]
+    fn stops_at_a_truncated_layer() {
+        let frame = testing::tcp_frame(ip(10, 0, 0, 1), ip(10, 0, 0, 2), &TcpBuilder::new(51000, 443));
+        let summary = peek(&frame[..14 + 5]);
+        assert_eq!(summary.layers(), ["Ethernet"]);
+        assert_eq!(summary.ip_protocol, None);
+        assert!(parse_only(&frame[..14 + 5], Fcs::Absent, false).is_err());
+
+        assert_eq!(peek(&frame[..10]).layers(), [] as [&str; 0]);
+    }
+}
@@ -0,0 +1,168 @@
+use std::io::{Error, Write};
+use std::str::FromStr;
+use pcap::Packet;
+use crate::radiotap::Radiotap;
+use crate::reassembly::StreamKey;
+use crate::sll::Sll2;
+use crate::tcp::Tcp;
+use crate::visitor::{Context, Visitor};
+
+
+/// Which direction of a selected connection to write.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub enum PayloadDirection {
+    #[default]
+    Both,
+    /// From the source to the destination of the selection.
+    Forward,
+    /// From the destination to the source of the selection.
+    Reverse,
+}
+
+impl FromStr for PayloadDirection {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "both"    => Ok(Self::Both),
+            "forward" => Ok(Self::Forward),
+            "reverse" => Ok(Self::Reverse),
+            _ => Err(format!("Unknown direction '{}', expected both, forward or reverse", text)),
+        }
+    }
+}
+
+
+/// Writes the payload of each TCP segment as it was captured, without reassembly. Retransmitted and out of
+/// order segments are written as they come, which is good enough to pull the bytes of a binary protocol out
+/// of a clean capture. Use `FollowVisitor` when the order matters.
+pub struct TcpPayloadVisitor<W: Write> {
+    writer: W,
+    selection: Option<StreamKey>,
+    direction: PayloadDirection,
+    segments: usize,
+    bytes: usize,
+}
+
+impl<W: Write> TcpPayloadVisitor<W> {
+    pub fn new(writer: W) -> Self {
+        Self { writer, selection: None, direction: PayloadDirection::Both, segments: 0, bytes: 0 }
+    }
+
+    /// Only writes the connection `selection`, in either direction.
+    pub fn with_selection(self, selection: StreamKey) -> Self {
+        Self { selection: Some(selection), ..self }
+    }
+
+    /// Only writes one direction of the selected connection.
+    pub fn with_direction(self, direction: PayloadDirection) -> Self {
+        Self { direction, ..self }
+    }
+
+    fn is_selected(&self, key: StreamKey) -> bool {
+        let Some(selection) = self.selection else { return true };
+        match self.direction {
+            PayloadDirection::Both    => key == selection || key == selection.reversed(),
+            PayloadDirection::Forward => key == selection,
+            PayloadDirection::Reverse => key == selection.reversed(),
+        }
+    }
+}
+
+
+impl<'a, W: Write> Visitor<'a, ()> for TcpPayloadVisitor<W> {
+    fn visit_packet(&mut self, packet: &'a Packet) -> Result<(), Error> {
+        // Packets that fail to decode hold no payload to write, and reporting them would mix with it on stdout.
+        let _ = self.visit_packet_payload(packet);
+        Ok(())
+    }
+
+    fn visit_radiotap_packet(&mut self, packet: &'a Packet) -> Result<(), Error> {
+        if let Ok(radiotap) = Radiotap::from_bytes(packet.data) {
+            let _ = self.visit_radiotap(&radiotap, &Context::new(packet.header));
+        }
+        Ok(())
+    }
+
+    fn visit_sll2_packet(&mut self, packet: &'a Packet) -> Result<(), Error> {
+        if let Ok(sll2) = Sll2::from_bytes(packet.data) {
+            let _ = self.visit_sll2(&sll2, &Context::new(packet.header));
+        }
+        Ok(())
+    }
+
+    fn visit_tcp(&mut self, packet: &Tcp<'a>, context: &Context<'a>) -> Result<(), Error> {
+        let Some(ip) = &context.ipv4 else { return Ok(()) };
+        let payload = packet.raw_payload();
+        if payload.is_empty() || !self.is_selected(StreamKey::new(ip, packet)) {
+            return Ok(());
+        }
+        self.segments += 1;
+        self.bytes += payload.len();
+        self.writer.write_all(payload)
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        self.writer.flush()?;
+        eprintln!("Wrote {} bytes from {} segments", self.bytes, self.segments);
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use crate::builder::{TcpBuilder, TCP_ACK, TCP_PSH};
+    use crate::raw::visit_frame;
+    use crate::testing::{self, ip};
+    use crate::tree::SharedBuffer;
+
+    fn selection() -> StreamKey {
+        StreamKey { source: ip(10, 0, 0, 1), source_port: 51000, destination: ip(10, 0, 0, 2), destination_port: 6379 }
+    }
+
+    /// A segment with `payload` from the client (10.0.0.1:51000) or the server (10.0.0.2:6379).
+    fn segment(from_client: bool, payload: &[u8]) -> Vec<u8> {
+        let (source, destination, ports) = if from_client {
+            (ip(10, 0, 0, 1), ip(10, 0, 0, 2), (51000, 6379))
+        } else {
+            (ip(10, 0, 0, 2), ip(10, 0, 0, 1), (6379, 51000))
+        };
+        testing::tcp_frame(source, destination, &TcpBuilder::new(ports.0, ports.1).with_flags(TCP_ACK | TCP_PSH).with_payload(payload))
+    }
+
+    /// What the visitor writes for `frames`.
+    fn dump(visitor: impl FnOnce(SharedBuffer) -> TcpPayloadVisitor<SharedBuffer>, frames: &[Vec<u8>]) -> Vec<u8> {
+        let buffer = SharedBuffer::default();
+        let mut visitor = visitor(buffer.clone());
+        for frame in frames {
+            visit_frame(&mut visitor, frame, Duration::ZERO).unwrap();
+        }
+        visitor.finish().unwrap();
+        buffer.take()
+    }
+
+    #[test]
+    fn concatenates_the_payloads_of_a_flow() {
+        let frames = [segment(true, b"*1\r\n$4\r\n"), segment(true, b"PING\r\n")];
+        assert_eq!(dump(TcpPayloadVisitor::new, &frames), b"*1\r\n$4\r\nPING\r\n");
+    }
+
+    #[test]
+    fn writes_only_the_selected_flow_and_direction() {
+        let other = testing::tcp_frame(ip(10, 0, 0, 3), ip(10, 0, 0, 2), &TcpBuilder::new(52000, 6379).with_flags(TCP_ACK).with_payload(b"other"));
+        let frames = [segment(true, b"PING\r\n"), other, segment(false, b"+PONG\r\n"), segment(true, b"")];
+
+        assert_eq!(dump(|x| TcpPayloadVisitor::new(x).with_selection(selection()), &frames), b"PING\r\n+PONG\r\n");
+        assert_eq!(dump(|x| TcpPayloadVisitor::new(x).with_selection(selection()).with_direction(PayloadDirection::Forward), &frames), b"PING\r\n");
+        assert_eq!(dump(|x| TcpPayloadVisitor::new(x).with_selection(selection()).with_direction(PayloadDirection::Reverse), &frames), b"+PONG\r\n");
+    }
+
+    #[test]
+    fn parses_directions() {
+        assert_eq!("reverse".parse::<PayloadDirection>(), Ok(PayloadDirection::Reverse));
+        assert!("sideways".parse::<PayloadDirection>().is_err());
+    }
+}
@@ -0,0 +1,53 @@
+/*
+https://wiki.wireshark.org/Development/LibpcapFileFormat
+*/
+
+use std::io::{Error, Write};
+use pcap::PacketHeader;
+
+
+pub const MAGIC: u32 = 0xA1B2C3D4;
+pub const VERSION_MAJOR: u16 = 2;
+pub const VERSION_MINOR: u16 = 4;
+pub const DEFAULT_SNAP_LENGTH: u32 = 65535;
+
+pub const FILE_HEADER_SIZE  : usize = 24;
+pub const RECORD_HEADER_SIZE: usize = 16;
+
+
+/// Writes packets in the classic pcap format, in native byte order like libpcap does.
+pub struct PcapWriter<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> PcapWriter<W> {
+    /// Writes the file header, so the output is a valid capture even if no packets follow.
+    pub fn new(mut writer: W, link_type: u32) -> Result<Self, Error> {
+        writer.write_all(&MAGIC.to_ne_bytes())?;
+        writer.write_all(&VERSION_MAJOR.to_ne_bytes())?;
+        writer.write_all(&VERSION_MINOR.to_ne_bytes())?;
+        writer.write_all(&0i32.to_ne_bytes())?;  // Time zone offset, always 0.
+        writer.write_all(&0u32.to_ne_bytes())?;  // Timestamp accuracy, always 0.
+        writer.write_all(&DEFAULT_SNAP_LENGTH.to_ne_bytes())?;
+        writer.write_all(&link_type.to_ne_bytes())?;
+        Ok(Self { writer })
+    }
+
+    /// Writes one record and returns the number of bytes it took.
+    pub fn write_packet(&mut self, header: &PacketHeader, data: &[u8]) -> Result<usize, Error> {
+        self.writer.write_all(&(header.ts.tv_sec as u32).to_ne_bytes())?;
+        self.writer.write_all(&(header.ts.tv_usec as u32).to_ne_bytes())?;
+        self.writer.write_all(&(data.len() as u32).to_ne_bytes())?;
+        self.writer.write_all(&header.len.to_ne_bytes())?;
+        self.writer.write_all(data)?;
+        Ok(RECORD_HEADER_SIZE + data.len())
+    }
+
+    pub fn flush(&mut self) -> Result<(), Error> {
+        self.writer.flush()
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
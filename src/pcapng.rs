@@ -0,0 +1,299 @@
+/*
+https://www.ietf.org/archive/id/draft-ietf-opsawg-pcapng-02.html
+*/
+
+use std::io::{Error, ErrorKind, Read};
+use std::time::Duration;
+use crate::endian::{be_u16, be_u32, le_u16, le_u32};
+
+
+pub const MAGIC: [u8; 4] = [0x0A, 0x0D, 0x0D, 0x0A];
+
+pub const BLOCK_SECTION_HEADER       : u32 = 0x0A0D0D0A;
+pub const BLOCK_INTERFACE_DESCRIPTION: u32 = 0x00000001;
+pub const BLOCK_SIMPLE_PACKET        : u32 = 0x00000003;
+pub const BLOCK_ENHANCED_PACKET      : u32 = 0x00000006;
+
+const BYTE_ORDER_MAGIC: u32 = 0x1A2B3C4D;
+const OPTION_END_OF_OPTIONS: u16 = 0;
+const OPTION_IF_TSRESOL: u16 = 9;
+
+
+/// Whether the file starts with a pcapng Section Header Block.
+pub fn is_pcapng(header: &[u8]) -> bool {
+    header.starts_with(&MAGIC)
+}
+
+
+#[derive(Debug, Clone)]
+pub struct Interface {
+    pub link_type: u16,
+    pub snap_length: u32,
+    /// Number of timestamp units per second.
+    pub units_per_second: u64,
+}
+
+
+#[derive(Debug, Clone)]
+pub struct PcapngPacket {
+    /// Index of the Interface Description Block in the current section.
+    pub interface: usize,
+    pub link_type: u16,
+    /// Time since the Unix epoch.
+    pub timestamp: Duration,
+    pub original_length: u32,
+    pub data: Vec<u8>,
+}
+
+
+/// Reads the packets of a pcapng file, keeping track of the interfaces of each section.
+pub struct PcapngReader<R: Read> {
+    reader: R,
+    big_endian: bool,
+    interfaces: Vec<Interface>,
+}
+
+impl<R: Read> PcapngReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self { reader, big_endian: false, interfaces: Vec::new() }
+    }
+
+    pub fn interfaces(&self) -> &[Interface] {
+        &self.interfaces
+    }
+
+    /// Returns the next packet, or `None` at the end of the file.
+    pub fn next_packet(&mut self) -> Option<Result<PcapngPacket, Error>> {
+        loop {
+            let (kind, body) = match self.next_block()? {
+                Ok(block) => block,
+                Err(error) => return Some(Err(error)),
+            };
+
+            match kind {
+                BLOCK_INTERFACE_DESCRIPTION => {
+                    if let Err(error) = self.read_interface(&body) { return Some(Err(error)) }
+                },
+                BLOCK_ENHANCED_PACKET => return Some(self.read_enhanced_packet(&body)),
+                BLOCK_SIMPLE_PACKET   => return Some(self.read_simple_packet(&body)),
+                // Name resolution, statistics and custom blocks carry no packets.
+                _ => continue,
+            }
+        }
+    }
+
+    /// Reads the next block and returns its type and body, without the type and length fields.
+    fn next_block(&mut self) -> Option<Result<(u32, Vec<u8>), Error>> {
+        let mut header = [0u8; 8];
+        match self.reader.read_exact(&mut header[..4]) {
+            Ok(()) => {},
+            Err(error) if error.kind() == ErrorKind::UnexpectedEof => return None,
+            Err(error) => return Some(Err(error)),
+        }
+        if let Err(error) = self.reader.read_exact(&mut header[4..]) { return Some(Err(error)) }
+
+        // A new section may switch byte order and always starts without interfaces.
+        if header[..4] == MAGIC {
+            let mut magic = [0u8; 4];
+            if let Err(error) = self.reader.read_exact(&mut magic) { return Some(Err(error)) }
+            self.big_endian = be_u32(&magic, 0) == BYTE_ORDER_MAGIC;
+            self.interfaces.clear();
+
+            let length = self.u32(&header, 4) as usize;
+            if length < 12 || !length.is_multiple_of(4) {
+                return Some(Err(Error::new(ErrorKind::Other, format!("Invalid pcapng section header length {}", length))));
+            }
+            let mut body = vec![0u8; length - 12];
+            if let Err(error) = self.reader.read_exact(&mut body) { return Some(Err(error)) }
+            return Some(Ok((BLOCK_SECTION_HEADER, body)));
+        }
+
+        let kind   = self.u32(&header, 0);
+        let length = self.u32(&header, 4) as usize;
+        if length < 12 || !length.is_multiple_of(4) {
+            return Some(Err(Error::new(ErrorKind::Other, format!("Invalid pcapng block length {}", length))));
+        }
+
+        // The body is followed by a copy of the block length.
+        let mut body = vec![0u8; length - 8];
+        if let Err(error) = self.reader.read_exact(&mut body) { return Some(Err(error)) }
+        body.truncate(length - 12);
+        Some(Ok((kind, body)))
+    }
+
+    fn read_interface(&mut self, body: &[u8]) -> Result<(), Error> {
+        if body.len() < 8 {
+            return Err(Error::new(ErrorKind::Other, format!("Pcapng interface block too small, expected at least 8, got {}", body.len())));
+        }
+
+        let mut interface = Interface {
+            link_type: self.u16(body, 0),
+            snap_length: self.u32(body, 4),
+            units_per_second: 1_000_000,
+        };
+
+        let mut index = 8;
+        while index + 4 <= body.len() {
+            let code   = self.u16(body, index);
+            let length = self.u16(body, index + 2) as usize;
+            index += 4;
+            if code == OPTION_END_OF_OPTIONS || index + length > body.len() { break }
+
+            if code == OPTION_IF_TSRESOL && length >= 1 {
+                // The most significant bit selects a power of two instead of a power of ten.
+                let resolution = body[index];
+                let exponent = (resolution & 0x7F) as u32;
+                interface.units_per_second = if resolution & 0x80 == 0 {
+                    10u64.checked_pow(exponent).unwrap_or(u64::MAX)
+                } else {
+                    2u64.checked_pow(exponent).unwrap_or(u64::MAX)
+                };
+            }
+
+            // Option values are padded to 32 bits.
+            index += (length + 3) & !3;
+        }
+
+        self.interfaces.push(interface);
+        Ok(())
+    }
+
+    fn read_enhanced_packet(&self, body: &[u8]) -> Result<PcapngPacket, Error> {
+        if body.len() < 20 {
+            return Err(Error::new(ErrorKind::Other, format!("Pcapng packet block too small, expected at least 20, got {}", body.len())));
+        }
+
+        let interface = self.u32(body, 0) as usize;
+        let units     = (self.u32(body, 4) as u64) << 32 | self.u32(body, 8) as u64;
+        let captured  = self.u32(body, 12) as usize;
+        let original  = self.u32(body, 16);
+
+        let description = self.interfaces.get(interface)
+            .ok_or(Error::new(ErrorKind::Other, format!("Pcapng packet refers to unknown interface {}", interface)))?;
+        let data = body.get(20..20 + captured)
+            .ok_or(Error::new(ErrorKind::Other, format!("Pcapng packet data truncated, expected {} bytes", captured)))?;
+
+        Ok(PcapngPacket {
+            interface,
+            link_type: description.link_type,
+            timestamp: to_duration(units, description.units_per_second),
+            original_length: original,
+            data: data.to_vec(),
+        })
+    }
+
+    fn read_simple_packet(&self, body: &[u8]) -> Result<PcapngPacket, Error> {
+        if body.len() < 4 {
+            return Err(Error::new(ErrorKind::Other, format!("Pcapng simple packet block too small, expected at least 4, got {}", body.len())));
+        }
+
+        // Simple packets always belong to the first interface and have no timestamp.
+        let description = self.interfaces.first()
+            .ok_or(Error::new(ErrorKind::Other, "Pcapng simple packet without an interface"))?;
+        let original = self.u32(body, 0);
+        let captured = (original as usize).min(description.snap_length as usize).min(body.len() - 4);
+
+        Ok(PcapngPacket {
+            interface: 0,
+            link_type: description.link_type,
+            timestamp: Duration::ZERO,
+            original_length: original,
+            data: body[4..4 + captured].to_vec(),
+        })
+    }
+
+    fn u16(&self, data: &[u8], i: usize) -> u16 {
+        if self.big_endian { be_u16(data, i) } else { le_u16(data, i) }
+    }
+
+    fn u32(&self, data: &[u8], i: usize) -> u32 {
+        if self.big_endian { be_u32(data, i) } else { le_u32(data, i) }
+    }
+}
+
+
+fn to_duration(units: u64, units_per_second: u64) -> Duration {
+    let seconds   = units / units_per_second;
+    let remainder = units % units_per_second;
+    let nanos     = (remainder as u128 * 1_000_000_000 / units_per_second as u128) as u32;
+    Duration::new(seconds, nanos)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LINK_TYPE_ETHERNET: u16 = 1;
+    const LINK_TYPE_IEEE802_11_RADIOTAP: u16 = 127;
+
+    fn block(kind: u32, body: &[u8]) -> Vec<u8> {
+        let length = 12 + body.len().next_multiple_of(4) as u32;
+        let mut data = Vec::new();
+        data.extend_from_slice(&kind.to_le_bytes());
+        data.extend_from_slice(&length.to_le_bytes());
+        data.extend_from_slice(body);
+        data.resize(length as usize - 4, 0);
+        data.extend_from_slice(&length.to_le_bytes());
+        data
+    }
+
+    fn section() -> Vec<u8> {
+        let mut body = BYTE_ORDER_MAGIC.to_le_bytes().to_vec();
+        body.extend_from_slice(&[1, 0, 0, 0]);
+        body.extend_from_slice(&u64::MAX.to_le_bytes());
+        block(BLOCK_SECTION_HEADER, &body)
+    }
+
+    fn interface(link_type: u16, options: &[u8]) -> Vec<u8> {
+        let mut body = link_type.to_le_bytes().to_vec();
+        body.extend_from_slice(&[0, 0]);
+        body.extend_from_slice(&65535u32.to_le_bytes());
+        body.extend_from_slice(options);
+        block(BLOCK_INTERFACE_DESCRIPTION, &body)
+    }
+
+    fn enhanced_packet(interface: u32, units: u64, data: &[u8]) -> Vec<u8> {
+        let mut body = interface.to_le_bytes().to_vec();
+        body.extend_from_slice(&((units >> 32) as u32).to_le_bytes());
+        body.extend_from_slice(&(units as u32).to_le_bytes());
+        body.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        body.extend_from_slice(&(data.len() as u32 + 4).to_le_bytes());
+        body.extend_from_slice(data);
+        block(BLOCK_ENHANCED_PACKET, &body)
+    }
+
+    #[test]
+    fn reads_packets_of_two_interfaces() {
+        let mut file = section();
+        file.extend(interface(LINK_TYPE_ETHERNET, &[]));
+        // Nanosecond timestamps, then the end of options.
+        file.extend(interface(LINK_TYPE_IEEE802_11_RADIOTAP, &[9, 0, 1, 0, 9, 0, 0, 0, 0, 0, 0, 0]));
+        file.extend(enhanced_packet(0, 1_500_000, &[0xAA; 60]));
+        file.extend(enhanced_packet(1, 2_000_000_123, &[0xBB; 30]));
+        assert!(is_pcapng(&file));
+
+        let mut reader = PcapngReader::new(&file[..]);
+        let first = reader.next_packet().unwrap().unwrap();
+        assert_eq!((first.interface, first.link_type), (0, LINK_TYPE_ETHERNET));
+        assert_eq!(first.timestamp, Duration::from_micros(1_500_000));
+        assert_eq!(first.data, [0xAA; 60]);
+        assert_eq!(first.original_length, 64);
+
+        let second = reader.next_packet().unwrap().unwrap();
+        assert_eq!((second.interface, second.link_type), (1, LINK_TYPE_IEEE802_11_RADIOTAP));
+        assert_eq!(second.timestamp, Duration::from_nanos(2_000_000_123));
+        assert_eq!(second.data, [0xBB; 30]);
+
+        assert_eq!(reader.interfaces().len(), 2);
+        assert!(reader.next_packet().is_none());
+    }
+
+    #[test]
+    fn rejects_a_packet_of_an_unknown_interface() {
+        let mut file = section();
+        file.extend(interface(LINK_TYPE_ETHERNET, &[]));
+        file.extend(enhanced_packet(1, 0, &[0; 60]));
+        assert!(PcapngReader::new(&file[..]).next_packet().unwrap().is_err());
+    }
+}
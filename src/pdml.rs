@@ -0,0 +1,325 @@
+use std::io::{Error, Write};
+use pcap::Packet;
+use crate::ethernet::Ethernet;
+use crate::ipv4::IPv4;
+use crate::ipv6::IPv6;
+use crate::ndjson::json_string;
+use crate::tcp::Tcp;
+use crate::udp::Udp;
+use crate::visitor::{Context, Visitor};
+
+
+/// Escapes the characters that can't appear literally in an XML attribute value.
+pub fn xml_escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&'  => out += "&amp;",
+            '<'  => out += "&lt;",
+            '>'  => out += "&gt;",
+            '"'  => out += "&quot;",
+            '\'' => out += "&apos;",
+            c if (c as u32) < 0x20 && c != '\t' => out += &format!("&#x{:x};", c as u32),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+
+/// One decoded layer with its fields named like Wireshark's, e.g. `ip.src`.
+#[derive(Debug)]
+struct Proto {
+    name: &'static str,
+    fields: Vec<(&'static str, String)>,
+}
+
+impl Proto {
+    fn new(name: &'static str) -> Self {
+        Self { name, fields: Vec::new() }
+    }
+
+    fn field(mut self, name: &'static str, value: impl std::fmt::Display) -> Self {
+        self.fields.push((name, value.to_string()));
+        self
+    }
+}
+
+
+/// The document formats of `tshark -T`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Format {
+    Pdml,
+    Json,
+}
+
+
+/// Writes a simplified PDML document, the XML Wireshark exports with `tshark -T pdml`: one `<packet>` per
+/// frame with a `<proto>` per decoded layer, each holding `<field name=".." value=".."/>` elements.
+///
+/// With `Format::Json` it writes the same fields like `tshark -T json` instead: an array with an object per
+/// frame, whose `_source.layers` maps each protocol to its fields, e.g. `layers.ip["ip.src"]`, all as strings.
+pub struct PdmlVisitor<W: Write> {
+    out: W,
+    format: Format,
+    index: usize,
+    started: bool,
+    protos: Vec<Proto>,
+}
+
+impl<W: Write> PdmlVisitor<W> {
+    pub fn new(out: W) -> Self {
+        Self { out, format: Format::Pdml, index: 0, started: false, protos: Vec::new() }
+    }
+
+    pub fn with_format(self, format: Format) -> Self {
+        Self { format, ..self }
+    }
+
+    /// Opens the document, with the XML declaration for PDML, once.
+    fn start(&mut self) -> Result<(), Error> {
+        if !self.started {
+            match self.format {
+                Format::Pdml => {
+                    writeln!(self.out, "<?xml version=\"1.0\"?>")?;
+                    writeln!(self.out, "<pdml version=\"0\" creator=\"packet-analyser\">")?;
+                },
+                Format::Json => writeln!(self.out, "[")?,
+            }
+            self.started = true;
+        }
+        Ok(())
+    }
+
+    fn write_packet(&mut self, packet: &Packet, result: Result<(), Error>) -> Result<(), Error> {
+        self.start()?;
+        let first = self.index == 0;
+
+        self.index += 1;
+        let mut frame = Proto::new("frame")
+            .field("frame.number", self.index)
+            .field("frame.time_epoch", format!("{}.{:06}", packet.header.ts.tv_sec, packet.header.ts.tv_usec))
+            .field("frame.len", packet.header.len)
+            .field("frame.cap_len", packet.header.caplen);
+        if let Err(error) = result {
+            frame = frame.field("frame.error", error);
+        }
+
+        let protos: Vec<Proto> = std::iter::once(frame).chain(std::mem::take(&mut self.protos)).collect();
+        match self.format {
+            Format::Pdml => self.write_pdml(&protos),
+            Format::Json => self.write_json(&protos, first),
+        }
+    }
+
+    fn write_pdml(&mut self, protos: &[Proto]) -> Result<(), Error> {
+        writeln!(self.out, "<packet>")?;
+        for proto in protos {
+            writeln!(self.out, "  <proto name=\"{}\">", proto.name)?;
+            for (name, value) in &proto.fields {
+                writeln!(self.out, "    <field name=\"{}\" value=\"{}\"/>", name, xml_escape(value))?;
+            }
+            writeln!(self.out, "  </proto>")?;
+        }
+        writeln!(self.out, "</packet>")
+    }
+
+    /// Writes one element of the array, preceded by a comma unless it's the `first`.
+    fn write_json(&mut self, protos: &[Proto], first: bool) -> Result<(), Error> {
+        if !first { writeln!(self.out, "  ,")? }
+        writeln!(self.out, "  {{")?;
+        writeln!(self.out, "    \"_index\": \"packets\",")?;
+        writeln!(self.out, "    \"_type\": \"doc\",")?;
+        writeln!(self.out, "    \"_score\": null,")?;
+        writeln!(self.out, "    \"_source\": {{")?;
+        writeln!(self.out, "      \"layers\": {{")?;
+        for (i, proto) in protos.iter().enumerate() {
+            writeln!(self.out, "        {}: {{", json_string(proto.name))?;
+            for (j, (name, value)) in proto.fields.iter().enumerate() {
+                let comma = if j + 1 < proto.fields.len() { "," } else { "" };
+                writeln!(self.out, "          {}: {}{}", json_string(name), json_string(value), comma)?;
+            }
+            writeln!(self.out, "        }}{}", if i + 1 < protos.len() { "," } else { "" })?;
+        }
+        writeln!(self.out, "      }}")?;
+        writeln!(self.out, "    }}")?;
+        writeln!(self.out, "  }}")
+    }
+}
+
+
+impl<'a, W: Write> Visitor<'a, ()> for PdmlVisitor<W> {
+    fn visit_packet(&mut self, packet: &'a Packet) -> Result<(), Error> {
+        self.protos.clear();
+        let result = self.visit_packet_payload(packet);
+        self.write_packet(packet, result)
+    }
+
+    fn visit_ethernet(&mut self, packet: &Ethernet<'a>, context: &Context<'a>) -> Result<(), Error> {
+        self.protos.push(Proto::new("eth")
+            .field("eth.src", format!("{:?}", packet.source()))
+            .field("eth.dst", format!("{:?}", packet.destination()))
+            .field("eth.type", format!("{:#06x}", packet.ether_type_raw())));
+        self.visit_ethernet_payload(&packet.payload()?, &context.with_ethernet(packet))
+    }
+
+    fn visit_ipv4(&mut self, packet: &IPv4<'a>, context: &Context<'a>) -> Result<(), Error> {
+        self.protos.push(Proto::new("ip")
+            .field("ip.src", format!("{:?}", packet.source_address()))
+            .field("ip.dst", format!("{:?}", packet.destination_address()))
+            .field("ip.proto", packet.protocol_raw())
+            .field("ip.ttl", packet.time_to_live())
+            .field("ip.len", packet.total_length()));
+        self.visit_ipv4_payload(&packet.payload()?, &context.with_ipv4(packet))
+    }
+
+    fn visit_ipv6(&mut self, packet: &IPv6<'a>, context: &Context<'a>) -> Result<(), Error> {
+        self.protos.push(Proto::new("ipv6")
+            .field("ipv6.src", format!("{:?}", packet.source_address()))
+            .field("ipv6.dst", format!("{:?}", packet.destination_address()))
+            .field("ipv6.nxt", packet.next_header())
+            .field("ipv6.hlim", packet.hop_limit())
+            .field("ipv6.plen", packet.payload_length()));
+        self.visit_ipv6_payload(&packet.payload()?, &context.with_ipv6(packet))
+    }
+
+    fn visit_tcp(&mut self, packet: &Tcp<'a>, context: &Context<'a>) -> Result<(), Error> {
+        self.protos.push(Proto::new("tcp")
+            .field("tcp.srcport", packet.source_port())
+            .field("tcp.dstport", packet.destination_port())
+            .field("tcp.seq", packet.sequence_number())
+            .field("tcp.ack", packet.acknowledgment_number())
+            .field("tcp.flags", format!("{:#04x}", packet.flags()))
+            .field("tcp.window_size", packet.window_size())
+            .field("tcp.len", packet.raw_payload().len()));
+        Ok(())
+    }
+
+    fn visit_udp(&mut self, packet: &Udp<'a>, context: &Context<'a>) -> Result<(), Error> {
+        self.protos.push(Proto::new("udp")
+            .field("udp.srcport", packet.source_port())
+            .field("udp.dstport", packet.destination_port())
+            .field("udp.length", packet.length()));
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        self.start()?;
+        match self.format {
+            Format::Pdml => writeln!(self.out, "</pdml>")?,
+            Format::Json => writeln!(self.out, "]")?,
+        }
+        self.out.flush()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use crate::builder::{TcpBuilder, TCP_SYN};
+    use crate::raw::visit_frame;
+    use crate::testing::{self, ip, Json};
+
+    /// Checks that `xml` is one well-formed element after the declaration: tags nest, attribute values are
+    /// quoted without `<` or stray `&`, and only whitespace sits between elements. Returns the names of the
+    /// elements in document order.
+    fn elements(xml: &str) -> Result<Vec<String>, String> {
+        let xml = xml.strip_prefix("<?xml version=\"1.0\"?>").ok_or("missing declaration")?;
+        let mut open: Vec<String> = Vec::new();
+        let mut names = Vec::new();
+        let mut rest = xml;
+        while let Some(start) = rest.find('<') {
+            if !rest[..start].trim().is_empty() { return Err(format!("text outside a tag: {:?}", &rest[..start])) }
+            let end = rest[start..].find('>').ok_or("unclosed tag")? + start;
+            let tag = &rest[start + 1..end];
+            rest = &rest[end + 1..];
+
+            if let Some(name) = tag.strip_prefix('/') {
+                if open.pop().as_deref() != Some(name) { return Err(format!("mismatched </{}>", name)) }
+                if open.is_empty() && !rest.trim().is_empty() { return Err("content after the root".to_string()) }
+                continue;
+            }
+            let (tag, closed) = match tag.strip_suffix('/') { Some(x) => (x, true), None => (tag, false) };
+            let name = tag.split_whitespace().next().ok_or("empty tag")?.to_string();
+            let attributes = tag[name.len()..].trim();
+            let mut quotes = attributes.split('"');
+            while let Some(key) = quotes.next() {
+                let key = key.trim();
+                if key.is_empty() { break }
+                if !key.ends_with('=') { return Err(format!("unquoted attribute {:?}", key)) }
+                let value = quotes.next().ok_or("unterminated attribute")?;
+                if value.contains('<') { return Err(format!("< in attribute {:?}", value)) }
+                for entity in value.split('&').skip(1) {
+                    if !["amp;", "lt;", "gt;", "quot;", "apos;", "#x"].iter().any(|x| entity.starts_with(x)) {
+                        return Err(format!("stray & in attribute {:?}", value));
+                    }
+                }
+            }
+            if open.is_empty() && !names.is_empty() { return Err("more than one root".to_string()) }
+            names.push(name.clone());
+            if !closed { open.push(name) }
+        }
+        if !open.is_empty() || !rest.trim().is_empty() { return Err(format!("unclosed {:?}", open)) }
+        Ok(names)
+    }
+
+    fn document(format: Format) -> String {
+        let mut out = Vec::new();
+        let mut visitor = PdmlVisitor::new(&mut out).with_format(format);
+        let tcp = testing::tcp_frame(ip(10, 0, 0, 1), ip(10, 0, 0, 2), &TcpBuilder::new(51000, 80).with_flags(TCP_SYN));
+        visit_frame(&mut visitor, &tcp, Duration::from_secs(1)).unwrap();
+        visit_frame(&mut visitor, &testing::udp_frame(ip(10, 0, 0, 3), 53000, ip(10, 0, 0, 4), 53, b"<&>"), Duration::from_secs(2)).unwrap();
+        // A truncated IPv4 header, reported in the frame.
+        visit_frame(&mut visitor, &testing::ethernet(testing::ETHER_TYPE_IPV4, &[0x45, 0, 0, 20]), Duration::from_secs(3)).unwrap();
+        visitor.finish().unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn writes_well_formed_pdml() {
+        let xml = document(Format::Pdml);
+        let names = elements(&xml).unwrap_or_else(|error| panic!("{}: {}", error, xml));
+        assert_eq!(names.iter().filter(|x| *x == "packet").count(), 3);
+        let protos: Vec<&str> = xml.lines().filter_map(|x| x.trim().strip_prefix("<proto name=\"")).map(|x| x.trim_end_matches("\">")).collect();
+        assert_eq!(protos, ["frame", "eth", "ip", "tcp", "frame", "eth", "ip", "udp", "frame", "eth"]);
+        assert!(xml.contains("<field name=\"ip.src\" value=\"10.0.0.1\"/>"), "{}", xml);
+        assert!(xml.contains("<field name=\"tcp.flags\" value=\"0x02\"/>"), "{}", xml);
+        assert!(xml.contains("<field name=\"frame.error\" value=\"Ipv4"), "{}", xml);
+    }
+
+    #[test]
+    fn an_empty_capture_is_still_a_document() {
+        let mut out = Vec::new();
+        PdmlVisitor::new(&mut out).finish().unwrap();
+        assert_eq!(elements(&String::from_utf8(out).unwrap()).unwrap(), ["pdml"]);
+    }
+
+    #[test]
+    fn escapes_attribute_values() {
+        assert_eq!(xml_escape("a<b>&\"c'\u{1}\td"), "a&lt;b&gt;&amp;&quot;c&apos;&#x1;\td");
+        assert!(elements(&format!("<?xml version=\"1.0\"?><a b=\"{}\"/>", xml_escape("<&>"))).is_ok());
+        assert!(elements("<?xml version=\"1.0\"?><a b=\"<&>\"/>").is_err());
+        assert!(elements("<?xml version=\"1.0\"?><a><b></a></b>").is_err());
+    }
+    #[test]
+    fn writes_tshark_json() {
+        let text = document(Format::Json);
+        let Ok(Json::Array(packets)) = Json::parse(&text) else { panic!("not a JSON array: {}", text) };
+        assert_eq!(packets.len(), 3);
+
+        let layers = |i: usize| packets[i].get("_source").and_then(|x| x.get("layers")).unwrap();
+        let field = |i: usize, proto: &str, name: &str| layers(i).get(proto).and_then(|x| x.get(name)).cloned();
+        assert_eq!(field(0, "ip", "ip.src"), Some(Json::String("10.0.0.1".to_string())));
+        assert_eq!(field(0, "tcp", "tcp.dstport"), Some(Json::String("80".to_string())));
+        assert_eq!(field(1, "frame", "frame.number"), Some(Json::String("2".to_string())));
+        assert_eq!(field(1, "udp", "udp.srcport"), Some(Json::String("53000".to_string())));
+        assert_eq!(field(2, "ip", "ip.src"), None);
+        assert!(matches!(field(2, "frame", "frame.error"), Some(Json::String(x)) if x.starts_with("Ipv4")));
+
+        let mut out = Vec::new();
+        PdmlVisitor::new(&mut out).with_format(Format::Json).finish().unwrap();
+        assert_eq!(Json::parse(&String::from_utf8(out).unwrap()), Ok(Json::Array(Vec::new())));
+    }
+}
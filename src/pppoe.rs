@@ -0,0 +1,161 @@
+/*
+https://www.rfc-editor.org/rfc/rfc2516
+https://www.rfc-editor.org/rfc/rfc1661
+*/
+
+use crate::ipv4;
+use crate::ipv6;
+use crate::error::ParseError;
+use crate::endian::be_u16;
+
+
+pub const CODE_SESSION_DATA : u8 = 0x00;
+pub const CODE_PADI         : u8 = 0x09;
+pub const CODE_PADO         : u8 = 0x07;
+pub const CODE_PADR         : u8 = 0x19;
+pub const CODE_PADS         : u8 = 0x65;
+pub const CODE_PADT         : u8 = 0xA7;
+
+pub const PPP_PROTOCOL_IPV4 : u16 = 0x0021;
+pub const PPP_PROTOCOL_IPV6 : u16 = 0x0057;
+
+
+#[derive(Debug)]
+pub enum Payload<'a> {
+    IPv4(ipv4::IPv4<'a>),
+    IPv6(ipv6::IPv6<'a>),
+    /// Discovery frames, and session frames carrying a PPP protocol other than IP (e.g. LCP).
+    Unknown(&'a [u8])
+}
+
+
+#[derive(Clone)]
+pub struct PPPoE<'a> {
+    data: &'a [u8],
+    /// Whether this is a session stage frame, which carries a PPP protocol field.
+    session: bool,
+}
+
+impl<'a> PPPoE<'a> {
+    pub const HEADER_SIZE: usize = 6;
+    pub const PPP_PROTOCOL_SIZE: usize = 2;
+
+    pub fn version(&self)    -> u8  { (self.data[0] & 0b1111_0000) >> 4 }
+    pub fn kind(&self)       -> u8  { self.data[0] & 0b0000_1111 }
+    pub fn code(&self)       -> u8  { self.data[1] }
+    pub fn session_id(&self) -> u16 { be_u16(self.data, 2) }
+    pub fn length(&self)     -> u16 { be_u16(self.data, 4) }
+
+    pub fn is_session(&self) -> bool { self.session }
+
+    /// The PPP protocol of a session frame.
+    pub fn ppp_protocol(&self) -> Option<u16> {
+        if self.session { Some(be_u16(self.data, Self::HEADER_SIZE)) } else { None }
+    }
+
+    /// The PPPoE payload, without any Ethernet padding. For session frames it starts with the PPP protocol.
+    pub fn raw_payload(&self) -> &'a [u8] {
+        let end = (Self::HEADER_SIZE + self.length() as usize).min(self.data.len());
+        &self.data[Self::HEADER_SIZE..end]
+    }
+
+    pub fn payload(&self) -> Result<Payload<'a>, ParseError> {
+        let data = &self.raw_payload()[Self::PPP_PROTOCOL_SIZE.min(self.raw_payload().len())..];
+        match self.ppp_protocol() {
+            Some(PPP_PROTOCOL_IPV4) => Ok(Payload::IPv4(ipv4::IPv4::from_bytes(data)?)),
+            Some(PPP_PROTOCOL_IPV6) => Ok(Payload::IPv6(ipv6::IPv6::from_bytes(data)?)),
+            _ => Ok(Payload::Unknown(data)),
+        }
+    }
+
+    /// Parses a discovery (`session == false`) or session stage frame.
+    pub fn from_bytes(data: &'a [u8], session: bool) -> Result<Self, ParseError> {
+        let minimum = Self::HEADER_SIZE + if session { Self::PPP_PROTOCOL_SIZE } else { 0 };
+        if data.len() < minimum {
+            return Err(ParseError::TooSmall { layer: "PPPoE", field: "data", expected: minimum, got: data.len() });
+        }
+
+        let me = Self { data, session };
+        if me.version() != 1 || me.kind() != 1 {
+            return Err(ParseError::Invalid("PPPoE version and type must be 1"));
+        }
+        if session && me.code() != CODE_SESSION_DATA {
+            return Err(ParseError::Invalid("PPPoE session frame must have code 0"));
+        }
+        if (me.length() as usize) < minimum - Self::HEADER_SIZE {
+            return Err(ParseError::TooSmall { layer: "PPPoE", field: "length", expected: minimum - Self::HEADER_SIZE, got: me.length() as usize });
+        }
+        if Self::HEADER_SIZE + me.length() as usize > data.len() {
+            return Err(ParseError::TooBig { layer: "PPPoE", field: "length", expected: data.len() - Self::HEADER_SIZE, got: me.length() as usize });
+        }
+
+        Ok(me)
+    }
+}
+
+
+impl<'a> core::fmt::Debug for PPPoE<'a> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "    PPPoE\n")?;
+        write!(f, "        version:      {:?}\n", self.version())?;
+        write!(f, "        type:         {:?}\n", self.kind())?;
+        write!(f, "        code:         {:#04x}\n", self.code())?;
+        write!(f, "        session_id:   {:#06x}\n", self.session_id())?;
+        write!(f, "        length:       {:?}\n", self.length())?;
+        write!(f, "        ppp_protocol: {:?}\n", self.ppp_protocol())?;
+        write!(f, "        payload: {:?}\n",    self.payload())?;
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+    use crate::builder::Ipv4Builder;
+    use crate::ethernet::{self, Ethernet};
+    use crate::ipv4::Protocol;
+    use crate::testing::{self, ip};
+
+    const ETHER_TYPE_PPPOE_SESSION: u16 = 0x8864;
+
+    /// A PPPoE session frame of session 0x1234 carrying `payload` of `ppp_protocol`.
+    fn session(ppp_protocol: u16, payload: &[u8]) -> Vec<u8> {
+        let mut data = Vec::from([0x11, CODE_SESSION_DATA, 0x12, 0x34]);
+        data.extend_from_slice(&((PPPoE::PPP_PROTOCOL_SIZE + payload.len()) as u16).to_be_bytes());
+        data.extend_from_slice(&ppp_protocol.to_be_bytes());
+        data.extend_from_slice(payload);
+        testing::ethernet(ETHER_TYPE_PPPOE_SESSION, &data)
+    }
+
+    #[test]
+    fn decodes_ipv4_over_a_pppoe_session() {
+        let packet = Ipv4Builder::new(ip(10, 0, 0, 1), ip(10, 0, 0, 2)).with_payload(Protocol::UDP, &testing::udp(53000, 53, b"query")).build();
+        let frame = session(PPP_PROTOCOL_IPV4, &packet);
+
+        let Ok(ethernet::Payload::PPPoE(pppoe)) = Ethernet::from_bytes(&frame).unwrap().payload() else { panic!("not a PPPoE frame") };
+        assert!(pppoe.is_session());
+        assert_eq!((pppoe.version(), pppoe.kind(), pppoe.code()), (1, 1, CODE_SESSION_DATA));
+        assert_eq!(pppoe.session_id(), 0x1234);
+        assert_eq!(pppoe.length() as usize, 2 + packet.len());
+        assert_eq!(pppoe.ppp_protocol(), Some(PPP_PROTOCOL_IPV4));
+
+        let Ok(Payload::IPv4(inner)) = pppoe.payload() else { panic!("PPPoE doesn't hold IPv4") };
+        assert_eq!(inner.source_address(), ip(10, 0, 0, 1));
+    }
+
+    #[test]
+    fn leaves_other_ppp_protocols_undecoded() {
+        // An LCP Echo-Request.
+        let frame = session(0xC021, &[9, 1, 0, 8, 0, 0, 0, 0]);
+        let Ok(ethernet::Payload::PPPoE(pppoe)) = Ethernet::from_bytes(&frame).unwrap().payload() else { panic!("not a PPPoE frame") };
+        assert!(matches!(pppoe.payload(), Ok(Payload::Unknown([9, 1, 0, 8, 0, 0, 0, 0]))));
+    }
+
+    #[test]
+    fn rejects_a_length_beyond_the_frame() {
+        let data = [0x11, CODE_SESSION_DATA, 0x12, 0x34, 0, 10, 0x00, 0x21];
+        assert!(matches!(PPPoE::from_bytes(&data, true), Err(ParseError::TooBig { .. })));
+    }
+}
@@ -0,0 +1,317 @@
+use std::io::Error;
+use pcap::Packet;
+use crate::dns;
+use crate::ethernet::Ethernet;
+use crate::http;
+use crate::ipv4::IPv4;
+use crate::ipv6::IPv6;
+use crate::icmp::Icmp;
+use crate::icmpv6::Icmpv6;
+use crate::gre::Gre;
+use crate::sctp::Sctp;
+use crate::ipsec::{Ah, Esp};
+use crate::pppoe::PPPoE;
+use crate::mpls::Mpls;
+use crate::lldp::Lldp;
+use crate::eapol::Eapol;
+use crate::radiotap::{Ieee80211, Radiotap};
+use crate::sll::Sll2;
+use crate::source::{LINK_TYPE_ETHERNET, LINK_TYPE_IEEE802_11_RADIOTAP, LINK_TYPE_LINUX_SLL2};
+use crate::registry::{Dissection, Registry};
+use crate::tcp::Tcp;
+use crate::udp::Udp;
+use crate::visitor::{Context, Visitor};
+
+
+/// The protocol names `--only` and `--exclude` accept.
+pub const PROTOCOLS: [&str; 22] = [
+    "eth", "arp", "ipv4", "ipv6", "pppoe", "mpls", "lldp", "eapol", "radiotap", "wlan", "sll2",
+    "tcp", "udp", "sctp", "gre", "icmp", "icmpv6", "ah", "esp", "dns", "http", "tls",
+];
+
+const DNS_PORT: u16 = 53;
+
+
+/// Whether `data` starts like a TLS record: a known content type followed by major version 3.
+pub fn is_tls_record(data: &[u8]) -> bool {
+    data.len() >= 5 && (20..=23).contains(&data[0]) && data[1] == 3
+}
+
+
+/// A comma separated list of protocol names, e.g. `tcp,dns`.
+#[derive(Debug, Clone, Default, Eq, PartialEq)]
+pub struct ProtocolSet {
+    names: Vec<&'static str>,
+}
+
+impl ProtocolSet {
+    pub fn contains(&self, name: &str) -> bool {
+        self.names.iter().any(|x| x.eq_ignore_ascii_case(name))
+    }
+}
+
+impl std::str::FromStr for ProtocolSet {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut names = Vec::new();
+        for name in text.split(',').map(|x| x.trim()).filter(|x| !x.is_empty()) {
+            match PROTOCOLS.iter().find(|x| x.eq_ignore_ascii_case(name)) {
+                Some(known) => names.push(*known),
+                None => return Err(format!("Unknown protocol '{}', expected one of {}", name, PROTOCOLS.join(", "))),
+            }
+        }
+        if names.is_empty() { return Err("Expected at least one protocol".to_string()) }
+        Ok(Self { names })
+    }
+}
+
+
+/// Finds the innermost protocol of a packet the analyser decodes, including the ones BPF can't express
+/// like DNS, HTTP and TLS. Layers that fail to parse end the search, so a truncated TCP segment is `tcp`.
+pub struct Classifier {
+    registry: Registry,
+    top: &'static str,
+}
+
+impl Classifier {
+    pub fn new() -> Self {
+        Self { registry: Registry::new(), top: "" }
+    }
+
+    /// The innermost protocol of a frame of the given link type.
+    pub fn classify(&mut self, packet: &Packet, link_type: u16) -> &'static str {
+        self.top = "";
+        let _ = match link_type {
+            LINK_TYPE_IEEE802_11_RADIOTAP => self.visit_radiotap_packet(packet),
+            LINK_TYPE_LINUX_SLL2          => self.visit_sll2_packet(packet),
+            _ => self.visit_packet(packet),
+        };
+        self.top
+    }
+}
+
+impl Default for Classifier {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+impl<'a> Visitor<'a, ()> for Classifier {
+    fn visit_ethernet(&mut self, packet: &Ethernet<'a>, context: &Context<'a>) -> Result<(), Error> {
+        self.top = "eth";
+        self.visit_ethernet_payload(&packet.payload()?, &context.with_ethernet(packet))
+    }
+    fn visit_ipv4(&mut self, packet: &IPv4<'a>, context: &Context<'a>) -> Result<(), Error> {
+        self.top = "ipv4";
+        self.visit_ipv4_payload(&packet.payload()?, &context.with_ipv4(packet))
+    }
+    fn visit_ipv6(&mut self, packet: &IPv6<'a>, context: &Context<'a>) -> Result<(), Error> {
+        self.top = "ipv6";
+        self.visit_ipv6_payload(&packet.payload()?, &context.with_ipv6(packet))
+    }
+    fn visit_pppoe(&mut self, packet: &PPPoE<'a>, context: &Context<'a>) -> Result<(), Error> {
+        self.top = "pppoe";
+        self.visit_pppoe_payload(&packet.payload()?, context)
+    }
+    fn visit_mpls(&mut self, packet: &Mpls<'a>, context: &Context<'a>) -> Result<(), Error> {
+        self.top = "mpls";
+        self.visit_mpls_payload(&packet.payload()?, context)
+    }
+    fn visit_lldp(&mut self, packet: &Lldp<'a>, context: &Context<'a>) -> Result<(), Error> {
+        self.top = "lldp";
+        Ok(())
+    }
+    fn visit_eapol(&mut self, packet: &Eapol<'a>, context: &Context<'a>) -> Result<(), Error> {
+        self.top = "eapol";
+        Ok(())
+    }
+    fn visit_radiotap(&mut self, packet: &Radiotap<'a>, context: &Context<'a>) -> Result<(), Error> {
+        self.top = "radiotap";
+        self.visit_ieee80211(&packet.payload()?, context)
+    }
+    fn visit_ieee80211(&mut self, packet: &Ieee80211<'a>, context: &Context<'a>) -> Result<(), Error> {
+        self.top = "wlan";
+        self.visit_ieee80211_payload(&packet.payload()?, context)
+    }
+    fn visit_sll2(&mut self, packet: &Sll2<'a>, context: &Context<'a>) -> Result<(), Error> {
+        self.top = "sll2";
+        self.visit_sll2_payload(&packet.payload()?, context)
+    }
+    fn visit_tcp(&mut self, packet: &Tcp<'a>, context: &Context<'a>) -> Result<(), Error> {
+        self.top = "tcp";
+        let payload = packet.raw_payload();
+        let is_dns = packet.source_port() == DNS_PORT || packet.destination_port() == DNS_PORT;
+        // DNS over TCP prefixes each message with its length.
+        if is_dns && payload.len() > 2 && dns::Message::from_bytes(&payload[2..]).is_ok() {
+            self.top = "dns";
+        } else if is_tls_record(payload) {
+            self.top = "tls";
+        }
+        self.visit_tcp_payload(packet, context)
+    }
+    fn visit_udp(&mut self, packet: &Udp<'a>, context: &Context<'a>) -> Result<(), Error> {
+        self.top = "udp";
+        let is_dns = packet.source_port() == DNS_PORT || packet.destination_port() == DNS_PORT;
+        if is_dns && dns::Message::from_bytes(packet.raw_payload()).is_ok() {
+            self.top = "dns";
+        }
+        Ok(())
+    }
+    fn visit_icmp(&mut self, packet: &Icmp<'a>, context: &Context<'a>) -> Result<(), Error> {
+        self.top = "icmp";
+        Ok(())
+    }
+    fn visit_icmpv6(&mut self, packet: &Icmpv6<'a>, context: &Context<'a>) -> Result<(), Error> {
+        self.top = "icmpv6";
+        Ok(())
+    }
+    fn visit_gre(&mut self, packet: &Gre<'a>, context: &Context<'a>) -> Result<(), Error> {
+        self.top = "gre";
+        self.visit_gre_payload(&packet.payload()?, context)
+    }
+    fn visit_sctp(&mut self, packet: &Sctp<'a>, context: &Context<'a>) -> Result<(), Error> {
+        self.top = "sctp";
+        Ok(())
+    }
+    fn visit_ah(&mut self, packet: &Ah<'a>, context: &Context<'a>) -> Result<(), Error> {
+        self.top = "ah";
+        self.visit_ah_payload(&packet.payload()?, context)
+    }
+    fn visit_esp(&mut self, packet: &Esp<'a>, context: &Context<'a>) -> Result<(), Error> {
+        self.top = "esp";
+        Ok(())
+    }
+    fn visit_http(&mut self, message: &http::Message, body: &'a [u8], context: &Context<'a>) -> Result<(), Error> {
+        self.top = "http";
+        Ok(())
+    }
+
+    fn registry(&self) -> Option<&Registry> {
+        Some(&self.registry)
+    }
+    fn visit_dissection(&mut self, dissection: &Dissection, context: &Context<'a>) -> Result<(), Error> {
+        if let Some(name) = PROTOCOLS.iter().find(|x| x.eq_ignore_ascii_case(dissection.name)) {
+            self.top = name;
+        }
+        Ok(())
+    }
+}
+
+
+/// Passes on the packets whose innermost protocol is in `only`, if given, and not in `exclude`. Unlike a BPF
+/// filter this runs after decoding, so it can select on protocols above the transport layer.
+pub struct ProtocolFilter {
+    inner: Box<dyn for<'a> Visitor<'a, ()>>,
+    classifier: Classifier,
+    only: Option<ProtocolSet>,
+    exclude: ProtocolSet,
+}
+
+impl ProtocolFilter {
+    pub fn new(inner: Box<dyn for<'a> Visitor<'a, ()>>, only: Option<ProtocolSet>, exclude: Option<ProtocolSet>) -> Self {
+        Self { inner, classifier: Classifier::new(), only, exclude: exclude.unwrap_or_default() }
+    }
+
+    pub fn passes(&self, protocol: &str) -> bool {
+        self.only.as_ref().is_none_or(|x| x.contains(protocol)) && !self.exclude.contains(protocol)
+    }
+}
+
+
+impl<'a> Visitor<'a, ()> for ProtocolFilter {
+    fn visit_packet(&mut self, packet: &'a Packet) -> Result<(), Error> {
+        let protocol = self.classifier.classify(packet, LINK_TYPE_ETHERNET);
+        if !self.passes(protocol) { return Ok(()) }
+        self.inner.visit_packet(packet)
+    }
+
+    fn visit_reassembled_packet(&mut self, packet: &'a Packet, offsets: &[usize]) -> Result<(), Error> {
+        let protocol = self.classifier.classify(packet, LINK_TYPE_ETHERNET);
+        if !self.passes(protocol) { return Ok(()) }
+        self.inner.visit_reassembled_packet(packet, offsets)
+    }
+
+    fn visit_radiotap_packet(&mut self, packet: &'a Packet) -> Result<(), Error> {
+        let protocol = self.classifier.classify(packet, LINK_TYPE_IEEE802_11_RADIOTAP);
+        if !self.passes(protocol) { return Ok(()) }
+        self.inner.visit_radiotap_packet(packet)
+    }
+
+    fn visit_sll2_packet(&mut self, packet: &'a Packet) -> Result<(), Error> {
+        let protocol = self.classifier.classify(packet, LINK_TYPE_LINUX_SLL2);
+        if !self.passes(protocol) { return Ok(()) }
+        self.inner.visit_sll2_packet(packet)
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        self.inner.finish()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use crate::builder::{TcpBuilder, TCP_ACK};
+    use crate::raw::visit_frame;
+    use crate::testing::{self, ip, Recorder};
+
+    /// A DNS query for the A record of example.com.
+    fn dns_query() -> Vec<u8> {
+        let mut data = Vec::from([0x12, 0x34, 0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0]);
+        data.extend_from_slice(b"\x07example\x03com\x00");
+        data.extend_from_slice(&[0, 1, 0, 1]);
+        data
+    }
+
+    fn frames() -> (Vec<u8>, Vec<u8>) {
+        let dns = testing::udp_frame(ip(10, 0, 0, 1), 53000, ip(10, 0, 0, 53), 53, &dns_query());
+        let ack = testing::tcp_frame(ip(10, 0, 0, 1), ip(10, 0, 0, 2), &TcpBuilder::new(51000, 80).with_flags(TCP_ACK));
+        (dns, ack)
+    }
+
+    /// The frames of `frames` that pass a filter with `only` and `exclude`.
+    fn passed(only: Option<&str>, exclude: Option<&str>, frames: &[&[u8]]) -> Vec<Vec<u8>> {
+        let recorder = Recorder::default();
+        let mut filter = ProtocolFilter::new(Box::new(recorder.clone()), only.map(|x| x.parse().unwrap()), exclude.map(|x| x.parse().unwrap()));
+        for frame in frames {
+            visit_frame(&mut filter, frame, Duration::ZERO).unwrap();
+        }
+        recorder.packets()
+    }
+
+    #[test]
+    fn only_dns_passes_a_query_and_drops_a_tcp_ack() {
+        let (dns, ack) = frames();
+        assert_eq!(passed(Some("dns"), None, &[&dns, &ack]), [dns.clone()]);
+        assert_eq!(passed(Some("tcp,dns"), None, &[&dns, &ack]), [dns.clone(), ack.clone()]);
+        assert_eq!(passed(None, Some("dns"), &[&dns, &ack]), [ack]);
+    }
+
+    #[test]
+    fn classifies_the_innermost_protocol() {
+        let (dns, ack) = frames();
+        let arp = testing::ethernet(0x0806, &[0, 1, 8, 0, 6, 4, 0, 1, 2, 0, 0, 0, 0, 0x0A, 10, 0, 0, 1, 0, 0, 0, 0, 0, 0, 10, 0, 0, 2]);
+        let udp = testing::udp_frame(ip(10, 0, 0, 1), 53000, ip(10, 0, 0, 2), 9999, b"hi");
+        let mut classifier = Classifier::new();
+        let mut classify = |frame: &[u8]| {
+            let header = testing::header(0, frame.len());
+            classifier.classify(&Packet::new(&header, frame), LINK_TYPE_ETHERNET)
+        };
+        assert_eq!(classify(&dns), "dns");
+        assert_eq!(classify(&ack), "tcp");
+        assert_eq!(classify(&arp), "arp");
+        assert_eq!(classify(&udp), "udp");
+    }
+
+    #[test]
+    fn parses_protocol_sets() {
+        let set: ProtocolSet = "TCP, dns".parse().unwrap();
+        assert!(set.contains("tcp") && set.contains("DNS") && !set.contains("udp"));
+        assert!("tcp,bogus".parse::<ProtocolSet>().unwrap_err().starts_with("Unknown protocol 'bogus'"));
+        assert!(",".parse::<ProtocolSet>().is_err());
+    }
+}
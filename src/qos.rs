@@ -0,0 +1,161 @@
+use std::collections::BTreeMap;
+use std::io::Error;
+use crate::ipv4::IPv4;
+use crate::ipv6::IPv6;
+use crate::visitor::{Context, Visitor};
+
+
+pub const ECN_NOT_ECT: u8 = 0b00;
+pub const ECN_ECT1:    u8 = 0b01;
+pub const ECN_ECT0:    u8 = 0b10;
+/// Congestion Experienced, set by a router instead of dropping the packet.
+pub const ECN_CE:      u8 = 0b11;
+
+
+/// The name of a standard per-hop behaviour, e.g. `AF21` or `EF`.
+pub fn dscp_name(dscp: u8) -> Option<&'static str> {
+    let name = match dscp {
+        0  => "CS0",
+        8  => "CS1",  10 => "AF11", 12 => "AF12", 14 => "AF13",
+        16 => "CS2",  18 => "AF21", 20 => "AF22", 22 => "AF23",
+        24 => "CS3",  26 => "AF31", 28 => "AF32", 30 => "AF33",
+        32 => "CS4",  34 => "AF41", 36 => "AF42", 38 => "AF43",
+        40 => "CS5",  44 => "VA",   46 => "EF",
+        48 => "CS6",
+        56 => "CS7",
+        _  => return None,
+    };
+    Some(name)
+}
+
+pub fn ecn_name(ecn: u8) -> &'static str {
+    match ecn & 0b11 {
+        ECN_NOT_ECT => "Not-ECT",
+        ECN_ECT1    => "ECT(1)",
+        ECN_ECT0    => "ECT(0)",
+        _           => "CE",
+    }
+}
+
+
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub struct ClassTotals {
+    pub packets: usize,
+    pub bytes: usize,
+}
+
+impl ClassTotals {
+    fn add(&mut self, bytes: usize) {
+        self.packets += 1;
+        self.bytes += bytes;
+    }
+}
+
+
+/// Tallies IP packets and their bytes, as given by the IP header, per DSCP and per ECN code point, and prints
+/// the distribution at the end. Only the outermost IP header of a packet is counted.
+#[derive(Debug, Default)]
+pub struct QosVisitor {
+    dscp: BTreeMap<u8, ClassTotals>,
+    ecn: [ClassTotals; 4],
+}
+
+impl QosVisitor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn record(&mut self, dscp: u8, ecn: u8, bytes: usize) {
+        self.dscp.entry(dscp).or_default().add(bytes);
+        self.ecn[(ecn & 0b11) as usize].add(bytes);
+    }
+
+    /// Totals per DSCP value, in ascending order.
+    pub fn dscp_totals(&self) -> impl Iterator<Item=(u8, ClassTotals)> + '_ {
+        self.dscp.iter().map(|(dscp, totals)| (*dscp, *totals))
+    }
+
+    pub fn ecn_totals(&self, ecn: u8) -> ClassTotals {
+        self.ecn[(ecn & 0b11) as usize]
+    }
+}
+
+
+impl<'a> Visitor<'a, ()> for QosVisitor {
+    fn visit_ipv4(&mut self, packet: &IPv4<'a>, context: &Context<'a>) -> Result<(), Error> {
+        self.record(packet.dscp(), packet.ecn(), packet.total_length() as usize);
+        Ok(())
+    }
+
+    fn visit_ipv6(&mut self, packet: &IPv6<'a>, context: &Context<'a>) -> Result<(), Error> {
+        self.record(packet.dscp(), packet.ecn(), IPv6::HEADER_SIZE + packet.payload_length() as usize);
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        let total: usize = self.ecn.iter().map(|x| x.bytes).sum();
+        let share = |bytes: usize| if total == 0 { 0.0 } else { bytes as f64 * 100.0 / total as f64 };
+
+        println!("{:<12} {:>10} {:>14} {:>7}", "DSCP", "Packets", "Bytes", "Share");
+        for (dscp, totals) in self.dscp_totals() {
+            let name = format!("{} ({})", dscp_name(dscp).unwrap_or("-"), dscp);
+            println!("{:<12} {:>10} {:>14} {:>6.1}%", name, totals.packets, totals.bytes, share(totals.bytes));
+        }
+
+        println!();
+        println!("{:<12} {:>10} {:>14} {:>7}", "ECN", "Packets", "Bytes", "Share");
+        for ecn in [ECN_NOT_ECT, ECN_ECT0, ECN_ECT1, ECN_CE] {
+            let totals = self.ecn_totals(ecn);
+            println!("{:<12} {:>10} {:>14} {:>6.1}%", ecn_name(ecn), totals.packets, totals.bytes, share(totals.bytes));
+        }
+
+        let congested = self.ecn_totals(ECN_CE);
+        if congested.packets > 0 {
+            println!("[WARNING] {} packets ({} bytes) were marked Congestion Experienced", congested.packets, congested.bytes);
+        }
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use crate::raw::visit_frame;
+    use crate::testing::{self, ip};
+
+    /// A UDP frame with `payload` bytes whose traffic class byte holds `dscp` and `ecn`.
+    fn frame(dscp: u8, ecn: u8, payload: usize) -> Vec<u8> {
+        let mut frame = testing::udp_frame(ip(10, 0, 0, 1), 5004, ip(10, 0, 0, 2), 5004, &vec![0; payload]);
+        frame[15] = dscp << 2 | ecn;
+        frame
+    }
+
+    #[test]
+    fn totals_bytes_per_dscp_and_ecn() {
+        let mut visitor = QosVisitor::new();
+        for frame in [frame(46, ECN_ECT0, 172), frame(46, ECN_ECT0, 172), frame(18, ECN_CE, 1000), frame(0, ECN_NOT_ECT, 12)] {
+            visit_frame(&mut visitor, &frame, Duration::ZERO).unwrap();
+        }
+
+        let totals: Vec<(u8, ClassTotals)> = visitor.dscp_totals().collect();
+        assert_eq!(totals, [
+            (0,  ClassTotals { packets: 1, bytes: 40 }),
+            (18, ClassTotals { packets: 1, bytes: 1028 }),
+            (46, ClassTotals { packets: 2, bytes: 400 }),
+        ]);
+        assert_eq!(visitor.ecn_totals(ECN_ECT0), ClassTotals { packets: 2, bytes: 400 });
+        assert_eq!(visitor.ecn_totals(ECN_CE), ClassTotals { packets: 1, bytes: 1028 });
+        assert_eq!(visitor.ecn_totals(ECN_ECT1), ClassTotals::default());
+    }
+
+    #[test]
+    fn names_the_code_points() {
+        assert_eq!(dscp_name(46), Some("EF"));
+        assert_eq!(dscp_name(18), Some("AF21"));
+        assert_eq!(dscp_name(1), None);
+        assert_eq!(ecn_name(ECN_CE), "CE");
+        assert_eq!(ecn_name(ECN_ECT0), "ECT(0)");
+    }
+}
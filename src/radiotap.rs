@@ -0,0 +1,430 @@
+/*
+https://www.radiotap.org
+IEEE 802.11-2020, 9.2 MAC frame formats
+*/
+
+use crate::ipv4;
+use crate::ipv6;
+use crate::ethernet::EtherType;
+use crate::shared::MacAddress;
+use crate::error::ParseError;
+use crate::endian::{le_u16, le_u32, le_u64, be_u16};
+
+
+pub const PRESENT_TSFT          : u32 = 0;
+pub const PRESENT_FLAGS         : u32 = 1;
+pub const PRESENT_RATE          : u32 = 2;
+pub const PRESENT_CHANNEL       : u32 = 3;
+pub const PRESENT_ANTENNA_SIGNAL: u32 = 5;
+pub const PRESENT_ANTENNA_NOISE : u32 = 6;
+pub const PRESENT_ANTENNA       : u32 = 11;
+pub const PRESENT_EXTENDED      : u32 = 31;
+
+/// The frame ends with the 802.11 frame check sequence.
+pub const FLAG_FCS: u8 = 0x10;
+
+/// Alignment and size of the fields in the first present word, up to RX flags. Later fields aren't decoded.
+const FIELDS: [(usize, usize); 15] = [
+    (8, 8),  // TSFT
+    (1, 1),  // Flags
+    (1, 1),  // Rate
+    (2, 4),  // Channel
+    (1, 2),  // FHSS
+    (1, 1),  // Antenna signal
+    (1, 1),  // Antenna noise
+    (2, 2),  // Lock quality
+    (2, 2),  // TX attenuation
+    (2, 2),  // dB TX attenuation
+    (1, 1),  // dBm TX power
+    (1, 1),  // Antenna
+    (1, 1),  // dB antenna signal
+    (1, 1),  // dB antenna noise
+    (2, 2),  // RX flags
+];
+
+
+/// The radiotap header that monitor mode captures (link type 127) put in front of each 802.11 frame.
+#[derive(Clone)]
+pub struct Radiotap<'a> {
+    data: &'a [u8],
+    /// Offset of each decodable field of the first present word, if present.
+    offsets: [Option<usize>; FIELDS.len()],
+}
+
+impl<'a> Radiotap<'a> {
+    pub const HEADER_SIZE: usize = 8;
+
+    pub fn version(&self) -> u8  { self.data[0] }
+    pub fn length(&self)  -> u16 { le_u16(self.data, 2) }
+    pub fn present(&self) -> u32 { le_u32(self.data, 4) }
+
+    pub fn has_field(&self, bit: u32) -> bool { self.present() & (1 << bit) != 0 }
+
+    fn field(&self, bit: u32) -> Option<&'a [u8]> {
+        let offset = self.offsets.get(bit as usize).copied().flatten()?;
+        Some(&self.data[offset..offset + FIELDS[bit as usize].1])
+    }
+
+    /// Microseconds on the receiver's timer when the first bit of the frame arrived.
+    pub fn tsft(&self)           -> Option<u64> { self.field(PRESENT_TSFT).map(|x| le_u64(x, 0)) }
+    pub fn flags(&self)          -> Option<u8>  { self.field(PRESENT_FLAGS).map(|x| x[0]) }
+    /// Data rate in units of 500 kbps.
+    pub fn rate(&self)           -> Option<u8>  { self.field(PRESENT_RATE).map(|x| x[0]) }
+    /// Frequency in MHz and channel flags.
+    pub fn channel(&self)        -> Option<(u16, u16)> { self.field(PRESENT_CHANNEL).map(|x| (le_u16(x, 0), le_u16(x, 2))) }
+    /// Signal power in dBm.
+    pub fn antenna_signal(&self) -> Option<i8>  { self.field(PRESENT_ANTENNA_SIGNAL).map(|x| x[0] as i8) }
+    /// Noise power in dBm.
+    pub fn antenna_noise(&self)  -> Option<i8>  { self.field(PRESENT_ANTENNA_NOISE).map(|x| x[0] as i8) }
+    pub fn antenna(&self)        -> Option<u8>  { self.field(PRESENT_ANTENNA).map(|x| x[0]) }
+
+    pub fn has_fcs(&self) -> bool {
+        self.flags().is_some_and(|x| x & FLAG_FCS != 0)
+    }
+
+    /// The 802.11 frame, without the frame check sequence.
+    pub fn raw_payload(&self) -> &'a [u8] {
+        let end = if self.has_fcs() { self.data.len().saturating_sub(4) } else { self.data.len() };
+        &self.data[(self.length() as usize).min(end)..end]
+    }
+
+    pub fn payload(&self) -> Result<Ieee80211<'a>, ParseError> {
+        Ieee80211::from_bytes(self.raw_payload())
+    }
+
+    pub fn from_bytes(data: &'a [u8]) -> Result<Self, ParseError> {
+        if data.len() < Self::HEADER_SIZE {
+            return Err(ParseError::TooSmall { layer: "Radiotap", field: "data", expected: Self::HEADER_SIZE, got: data.len() });
+        }
+
+        let mut me = Self { data, offsets: [None; FIELDS.len()] };
+        if me.version() != 0 { return Err(ParseError::Invalid("Radiotap version must be 0")) }
+
+        let length = me.length() as usize;
+        if length < Self::HEADER_SIZE || length > data.len() {
+            return Err(ParseError::TooBig { layer: "Radiotap", field: "length", expected: data.len(), got: length });
+        }
+
+        // Extended bitmaps follow the first one, and the fields follow the last bitmap.
+        let mut offset = 4;
+        loop {
+            if offset + 4 > length {
+                return Err(ParseError::TooSmall { layer: "Radiotap", field: "present bitmaps", expected: offset + 4, got: length });
+            }
+            let present = le_u32(data, offset);
+            offset += 4;
+            if present & (1 << PRESENT_EXTENDED) == 0 { break }
+        }
+
+        // Each field is aligned to its natural size relative to the start of the header.
+        let present = me.present();
+        for (bit, (alignment, size)) in FIELDS.iter().enumerate() {
+            if present & (1 << bit) == 0 { continue }
+            offset = (offset + alignment - 1) & !(alignment - 1);
+            if offset + size > length {
+                return Err(ParseError::TooSmall { layer: "Radiotap", field: "fields", expected: offset + size, got: length });
+            }
+            me.offsets[bit] = Some(offset);
+            offset += size;
+        }
+
+        Ok(me)
+    }
+}
+
+
+impl<'a> core::fmt::Debug for Radiotap<'a> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "Radiotap\n")?;
+        write!(f, "    version:        {:?}\n", self.version())?;
+        write!(f, "    length:         {:?}\n", self.length())?;
+        write!(f, "    present:        {:#010x}\n", self.present())?;
+        write!(f, "    tsft:           {:?}\n", self.tsft())?;
+        write!(f, "    flags:          {:?}\n", self.flags())?;
+        write!(f, "    rate:           {:?}\n", self.rate())?;
+        write!(f, "    channel:        {:?}\n", self.channel())?;
+        write!(f, "    antenna_signal: {:?}\n", self.antenna_signal())?;
+        write!(f, "    antenna_noise:  {:?}\n", self.antenna_noise())?;
+        write!(f, "    antenna:        {:?}\n", self.antenna())?;
+        write!(f, "    payload: {:?}\n", self.payload())?;
+        Ok(())
+    }
+}
+
+
+pub const TYPE_MANAGEMENT : u8 = 0;
+pub const TYPE_CONTROL    : u8 = 1;
+pub const TYPE_DATA       : u8 = 2;
+
+pub const SUBTYPE_BEACON  : u8 = 8;
+pub const SUBTYPE_CTS     : u8 = 12;
+pub const SUBTYPE_ACK     : u8 = 13;
+
+const FLAG_TO_DS     : u8 = 0x01;
+const FLAG_FROM_DS   : u8 = 0x02;
+const FLAG_PROTECTED : u8 = 0x40;
+const FLAG_ORDER     : u8 = 0x80;
+
+const LLC_SNAP: [u8; 6] = [0xAA, 0xAA, 0x03, 0x00, 0x00, 0x00];
+
+
+#[derive(Debug)]
+pub enum Payload<'a> {
+    IPv4(ipv4::IPv4<'a>),
+    IPv6(ipv6::IPv6<'a>),
+    /// Management and control frames, encrypted data, and data that isn't IP.
+    Unknown(&'a [u8]),
+}
+
+
+/// An 802.11 MAC frame.
+#[derive(Clone)]
+pub struct Ieee80211<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Ieee80211<'a> {
+    /// Frame control and duration, the least a frame has.
+    pub const MIN_HEADER_SIZE: usize = 4;
+
+    pub fn frame_control(&self) -> u16 { le_u16(self.data, 0) }
+    pub fn protocol_version(&self) -> u8 { self.data[0] & 0b0000_0011 }
+    pub fn kind(&self)             -> u8 { (self.data[0] & 0b0000_1100) >> 2 }
+    pub fn subtype(&self)          -> u8 { (self.data[0] & 0b1111_0000) >> 4 }
+    pub fn flags(&self)            -> u8 { self.data[1] }
+    pub fn duration(&self)         -> u16 { le_u16(self.data, 2) }
+
+    pub fn to_ds(&self)     -> bool { self.flags() & FLAG_TO_DS != 0 }
+    pub fn from_ds(&self)   -> bool { self.flags() & FLAG_FROM_DS != 0 }
+    pub fn protected(&self) -> bool { self.flags() & FLAG_PROTECTED != 0 }
+
+    pub fn kind_name(&self) -> &'static str {
+        match (self.kind(), self.subtype()) {
+            (TYPE_MANAGEMENT, 0)  => "Association Request",
+            (TYPE_MANAGEMENT, 1)  => "Association Response",
+            (TYPE_MANAGEMENT, 4)  => "Probe Request",
+            (TYPE_MANAGEMENT, 5)  => "Probe Response",
+            (TYPE_MANAGEMENT, SUBTYPE_BEACON) => "Beacon",
+            (TYPE_MANAGEMENT, 10) => "Disassociation",
+            (TYPE_MANAGEMENT, 11) => "Authentication",
+            (TYPE_MANAGEMENT, 12) => "Deauthentication",
+            (TYPE_MANAGEMENT, 13) => "Action",
+            (TYPE_MANAGEMENT, _)  => "Management",
+            (TYPE_CONTROL, 11)    => "RTS",
+            (TYPE_CONTROL, SUBTYPE_CTS) => "CTS",
+            (TYPE_CONTROL, SUBTYPE_ACK) => "ACK",
+            (TYPE_CONTROL, _)     => "Control",
+            (TYPE_DATA, 4) | (TYPE_DATA, 12) => "Null",
+            (TYPE_DATA, s) if s & 0b1000 != 0 => "QoS Data",
+            (TYPE_DATA, _)        => "Data",
+            _                     => "Extension",
+        }
+    }
+
+    /// Whether this is a data frame with a QoS control field.
+    fn is_qos(&self) -> bool { self.kind() == TYPE_DATA && self.subtype() & 0b1000 != 0 }
+
+    /// The number of address fields in the header.
+    pub fn address_count(&self) -> usize {
+        match (self.kind(), self.subtype()) {
+            (TYPE_CONTROL, SUBTYPE_CTS) | (TYPE_CONTROL, SUBTYPE_ACK) => 1,
+            (TYPE_CONTROL, _) => 2,
+            (TYPE_DATA, _) if self.to_ds() && self.from_ds() => 4,
+            _ => 3,
+        }
+    }
+
+    pub fn header_size(&self) -> usize {
+        match self.kind() {
+            TYPE_CONTROL => Self::MIN_HEADER_SIZE + 6 * self.address_count(),
+            _ => {
+                // Addresses 1 to 3 and the sequence control come first, then the optional fourth address.
+                let mut size = Self::MIN_HEADER_SIZE + 6 * 3 + 2;
+                if self.address_count() == 4 { size += 6 }
+                if self.is_qos() {
+                    size += 2;
+                    if self.flags() & FLAG_ORDER != 0 { size += 4 }
+                }
+                size
+            }
+        }
+    }
+
+    /// Address field `n`, counting from 1 like the standard.
+    pub fn address(&self, n: usize) -> Option<MacAddress> {
+        if n == 0 || n > self.address_count() { return None }
+        let offset = if n == 4 { 24 } else { 4 + 6 * (n - 1) };
+        MacAddress::from_bytes(&self.data[offset..offset + 6]).ok()
+    }
+
+    pub fn sequence_number(&self) -> Option<u16> {
+        if self.kind() == TYPE_CONTROL { return None }
+        Some(le_u16(self.data, 22) >> 4)
+    }
+
+    /// The address the frame is finally going to, which depends on the distribution system bits.
+    pub fn destination(&self) -> Option<MacAddress> {
+        if self.kind() == TYPE_DATA && self.to_ds() { self.address(3) } else { self.address(1) }
+    }
+
+    /// The address the frame originally came from, which depends on the distribution system bits.
+    pub fn source(&self) -> Option<MacAddress> {
+        match (self.kind(), self.to_ds(), self.from_ds()) {
+            (TYPE_DATA, true, true)  => self.address(4),
+            (TYPE_DATA, false, true) => self.address(3),
+            _                        => self.address(2),
+        }
+    }
+
+    /// The access point the frame belongs to, if it can be told from the header.
+    pub fn bssid(&self) -> Option<MacAddress> {
+        match (self.kind(), self.to_ds(), self.from_ds()) {
+            (TYPE_MANAGEMENT, _, _)      => self.address(3),
+            (TYPE_DATA, false, false)    => self.address(3),
+            (TYPE_DATA, true, false)     => self.address(1),
+            (TYPE_DATA, false, true)     => self.address(2),
+            _                            => None,
+        }
+    }
+
+    pub fn raw_payload(&self) -> &'a [u8] {
+        &self.data[self.header_size()..]
+    }
+
+    /// The EtherType of an unencrypted data frame's LLC/SNAP header.
+    pub fn ether_type(&self) -> Option<u16> {
+        let payload = self.raw_payload();
+        if self.kind() != TYPE_DATA || self.protected() || payload.len() < LLC_SNAP.len() + 2 { return None }
+        if payload[..LLC_SNAP.len()] != LLC_SNAP { return None }
+        Some(be_u16(payload, LLC_SNAP.len()))
+    }
+
+    pub fn payload(&self) -> Result<Payload<'a>, ParseError> {
+        let data = self.raw_payload();
+        match self.ether_type().map(EtherType::from_code) {
+            Some(EtherType::IPv4) => Ok(Payload::IPv4(ipv4::IPv4::from_bytes(&data[LLC_SNAP.len() + 2..])?)),
+            Some(EtherType::IPv6) => Ok(Payload::IPv6(ipv6::IPv6::from_bytes(&data[LLC_SNAP.len() + 2..])?)),
+            _ => Ok(Payload::Unknown(data)),
+        }
+    }
+
+    pub fn from_bytes(data: &'a [u8]) -> Result<Self, ParseError> {
+        if data.len() < Self::MIN_HEADER_SIZE {
+            return Err(ParseError::TooSmall { layer: "Ieee80211", field: "data", expected: Self::MIN_HEADER_SIZE, got: data.len() });
+        }
+
+        let me = Self { data };
+        if me.protocol_version() != 0 { return Err(ParseError::Invalid("802.11 protocol version must be 0")) }
+        if data.len() < me.header_size() {
+            return Err(ParseError::TooSmall { layer: "Ieee80211", field: "header", expected: me.header_size(), got: data.len() });
+        }
+
+        Ok(me)
+    }
+}
+
+
+impl<'a> core::fmt::Debug for Ieee80211<'a> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "    Ieee80211\n")?;
+        write!(f, "        kind:        {} ({}/{})\n", self.kind_name(), self.kind(), self.subtype())?;
+        write!(f, "        flags:       {:#04x}\n", self.flags())?;
+        write!(f, "        duration:    {:?}\n", self.duration())?;
+        write!(f, "        source:      {:?}\n", self.source())?;
+        write!(f, "        destination: {:?}\n", self.destination())?;
+        write!(f, "        bssid:       {:?}\n", self.bssid())?;
+        write!(f, "        payload: {:?}\n", self.payload())?;
+        Ok(())
+    }
+}
+
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+    use crate::builder::{Ipv4Builder, TcpBuilder};
+    use crate::testing::{ip, MAC_A, MAC_B};
+
+    const AP: [u8; 6] = [0x02, 0x00, 0x00, 0x00, 0x00, 0x01];
+
+    /// A radiotap header with flags, a 24 Mbps rate, channel 6 and a signal of -42 dBm, then `frame`.
+    fn radiotap(frame: &[u8]) -> Vec<u8> {
+        let present = 1u32 << PRESENT_FLAGS | 1 << PRESENT_RATE | 1 << PRESENT_CHANNEL | 1 << PRESENT_ANTENNA_SIGNAL;
+        let mut data = Vec::from([0, 0, 15, 0]);
+        data.extend_from_slice(&present.to_le_bytes());
+        data.extend_from_slice(&[0, 48]);
+        data.extend_from_slice(&2437u16.to_le_bytes());
+        data.extend_from_slice(&0x00A0u16.to_le_bytes());
+        data.push(-42i8 as u8);
+        data.extend_from_slice(frame);
+        data
+    }
+
+    /// A MAC header of `frame_control` with the three addresses and sequence number 1.
+    fn mac_header(frame_control: [u8; 2], addresses: [[u8; 6]; 3]) -> Vec<u8> {
+        let mut data = Vec::from(frame_control);
+        data.extend_from_slice(&[0, 0]);
+        for address in addresses {
+            data.extend_from_slice(&address);
+        }
+        data.extend_from_slice(&[0x10, 0x00]);
+        data
+    }
+
+    #[test]
+    fn decodes_a_beacon() {
+        let mut frame = mac_header([0x80, 0x00], [[0xFF; 6], AP, AP]);
+        // Timestamp, beacon interval, capabilities and the SSID "lab".
+        frame.extend_from_slice(&[0; 8]);
+        frame.extend_from_slice(&[0x64, 0x00, 0x01, 0x04, 0x00, 0x03]);
+        frame.extend_from_slice(b"lab");
+        let data = radiotap(&frame);
+
+        let header = Radiotap::from_bytes(&data).unwrap();
+        assert_eq!(header.length(), 15);
+        assert_eq!(header.flags(), Some(0));
+        assert_eq!(header.rate(), Some(48));
+        assert_eq!(header.channel(), Some((2437, 0x00A0)));
+        assert_eq!(header.antenna_signal(), Some(-42));
+        assert_eq!(header.tsft(), None);
+
+        let beacon = header.payload().unwrap();
+        assert_eq!(beacon.kind_name(), "Beacon");
+        assert_eq!(beacon.sequence_number(), Some(1));
+        assert_eq!(beacon.destination().unwrap().bytes(), &[0xFF; 6]);
+        assert_eq!(beacon.bssid().unwrap().bytes(), &AP);
+        assert!(matches!(beacon.payload(), Ok(Payload::Unknown(body)) if body.ends_with(b"lab")));
+    }
+
+    #[test]
+    fn decodes_ipv4_in_a_data_frame() {
+        let packet = Ipv4Builder::new(ip(192, 168, 1, 10), ip(192, 168, 1, 1)).with_tcp(&TcpBuilder::new(51000, 80)).build();
+        // From a station to the access point, on to MAC_B.
+        let mut frame = mac_header([0x08, 0x01], [AP, MAC_A, MAC_B]);
+        frame.extend_from_slice(&LLC_SNAP);
+        frame.extend_from_slice(&0x0800u16.to_be_bytes());
+        frame.extend_from_slice(&packet);
+        let data = radiotap(&frame);
+
+        let frame = Radiotap::from_bytes(&data).unwrap().payload().unwrap();
+        assert_eq!(frame.kind_name(), "Data");
+        assert_eq!(frame.source().unwrap().bytes(), &MAC_A);
+        assert_eq!(frame.destination().unwrap().bytes(), &MAC_B);
+        assert_eq!(frame.bssid().unwrap().bytes(), &AP);
+        assert_eq!(frame.ether_type(), Some(0x0800));
+        let Ok(Payload::IPv4(packet)) = frame.payload() else { panic!("the data frame doesn't hold IPv4") };
+        assert_eq!(packet.source_address(), ip(192, 168, 1, 10));
+    }
+
+    #[test]
+    fn leaves_protected_data_undecoded() {
+        let mut frame = mac_header([0x08, 0x41], [AP, MAC_A, MAC_B]);
+        frame.extend_from_slice(&[0x5A; 32]);
+        let data = radiotap(&frame);
+        let frame = Radiotap::from_bytes(&data).unwrap().payload().unwrap();
+        assert_eq!(frame.ether_type(), None);
+        assert!(matches!(frame.payload(), Ok(Payload::Unknown(_))));
+    }
+}
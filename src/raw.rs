@@ -0,0 +1,144 @@
+use std::io::{Error, ErrorKind, Read};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use pcap::{Packet, PacketHeader};
+use crate::endian::be_u32;
+use crate::visitor::Visitor;
+
+
+/// Runs one Ethernet frame through the visitor as if it had been captured at `timestamp`, the time since the
+/// Unix epoch. This needs neither libpcap nor a capture file.
+pub fn visit_frame(visitor: &mut dyn for<'a> Visitor<'a, ()>, data: &[u8], timestamp: Duration) -> Result<(), Error> {
+    let header = PacketHeader {
+        ts: libc::timeval {
+            tv_sec:  timestamp.as_secs() as libc::time_t,
+            tv_usec: timestamp.subsec_micros() as libc::suseconds_t,
+        },
+        caplen: data.len() as u32,
+        len: data.len() as u32,
+    };
+    visitor.visit_packet(&Packet::new(&header, data))
+}
+
+
+/// Reads Ethernet frames that are each prefixed by their length as a 32 bit big endian integer, e.g. the bytes
+/// `00 00 00 3c` followed by a 60 byte frame. There's no file header, and frames are timestamped when read.
+pub struct RawReader<R: Read> {
+    reader: R,
+}
+
+impl<R: Read> RawReader<R> {
+    /// Frames above this size are treated as corrupt framing rather than allocated.
+    pub const MAX_FRAME_SIZE: usize = 1 << 18;
+
+    pub fn new(reader: R) -> Self {
+        Self { reader }
+    }
+
+    /// Returns the next frame and the time it was read, or `None` at the end of the input.
+    pub fn next_frame(&mut self) -> Option<Result<(Vec<u8>, Duration), Error>> {
+        let mut length = [0u8; 4];
+        match self.reader.read_exact(&mut length) {
+            Ok(()) => {},
+            Err(error) if error.kind() == ErrorKind::UnexpectedEof => return None,
+            Err(error) => return Some(Err(error)),
+        }
+
+        let length = be_u32(&length, 0) as usize;
+        if length > Self::MAX_FRAME_SIZE {
+            return Some(Err(Error::new(ErrorKind::Other, format!("Raw frame too big, expected at most {}, got {}", Self::MAX_FRAME_SIZE, length))));
+        }
+
+        let mut data = vec![0u8; length];
+        if let Err(error) = self.reader.read_exact(&mut data) { return Some(Err(error)) }
+
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+        Some(Ok((data, timestamp)))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{self, ip, Recorder};
+    use crate::tree::SharedBuffer;
+    use crate::visitor::Printer;
+
+    #[test]
+    fn hands_the_frame_and_timestamp_to_the_visitor() {
+        struct Timestamp(Option<(i64, i64, u32)>);
+        impl<'a> Visitor<'a, ()> for Timestamp {
+            fn visit_packet(&mut self, packet: &'a Packet) -> Result<(), Error> {
+                self.0 = Some((packet.header.ts.tv_sec as i64, packet.header.ts.tv_usec as i64, packet.header.len));
+                Ok(())
+            }
+        }
+
+        let frame = testing::udp_frame(ip(10, 0, 0, 1), 53000, ip(10, 0, 0, 2), 53, b"query");
+        let mut visitor = Timestamp(None);
+        visit_frame(&mut visitor, &frame, Duration::new(1_700_000_000, 250_000_000)).unwrap();
+        assert_eq!(visitor.0, Some((1_700_000_000, 250_000, frame.len() as u32)));
+
+        let mut recorder = Recorder::default();
+        visit_frame(&mut recorder, &frame, Duration::ZERO).unwrap();
+        assert_eq!(recorder.packets(), [frame]);
+    }
+
+    #[test]
+    fn decodes_a_hand_built_frame() {
+        let frame = testing::udp_frame(ip(10, 0, 0, 1), 53000, ip(10, 0, 0, 2), 53, b"query");
+        let buffer = SharedBuffer::default();
+        let mut printer = Printer::with_writer(Box::new(buffer.clone()));
+        visit_frame(&mut printer, &frame, Duration::ZERO).unwrap();
+        let text = String::from_utf8(buffer.take()).unwrap();
+        assert!(text.contains("10.0.0.1"), "{}", text);
+        assert!(text.contains("- Udp"), "{}", text);
+        assert!(!text.contains("[ERROR]"), "{}", text);
+    }
+
+    /// The frames, each prefixed by its length as a 32 bit big endian integer.
+    fn framed(frames: &[&[u8]]) -> Vec<u8> {
+        let mut data = Vec::new();
+        for frame in frames {
+            data.extend_from_slice(&(frame.len() as u32).to_be_bytes());
+            data.extend_from_slice(frame);
+        }
+        data
+    }
+
+    #[test]
+    fn reads_length_prefixed_frames_from_a_stream() {
+        let udp = testing::udp_frame(ip(10, 0, 0, 1), 53000, ip(10, 0, 0, 2), 53, b"query");
+        let tcp = testing::tcp_frame(ip(10, 0, 0, 3), ip(10, 0, 0, 4), &crate::builder::TcpBuilder::new(51000, 80));
+        let mut reader = RawReader::new(std::io::Cursor::new(framed(&[&udp, &tcp])));
+
+        let buffer = SharedBuffer::default();
+        let mut printer = Printer::with_writer(Box::new(buffer.clone()));
+        let mut frames = Vec::new();
+        while let Some(frame) = reader.next_frame() {
+            let (frame, timestamp) = frame.unwrap();
+            visit_frame(&mut printer, &frame, timestamp).unwrap();
+            frames.push(frame);
+        }
+        assert_eq!(frames, [udp, tcp]);
+
+        let text = String::from_utf8(buffer.take()).unwrap();
+        assert_eq!(text.matches("---------- Packet").count(), 2, "{}", text);
+        assert!(text.contains("- Udp"), "{}", text);
+        assert!(text.contains("- Tcp"), "{}", text);
+    }
+
+    #[test]
+    fn reports_broken_framing() {
+        let mut data = framed(&[&[0xAB; 60]]);
+        data.truncate(40);
+        let mut reader = RawReader::new(std::io::Cursor::new(data));
+        assert_eq!(reader.next_frame().unwrap().unwrap_err().kind(), ErrorKind::UnexpectedEof);
+
+        let size = (RawReader::<std::io::Empty>::MAX_FRAME_SIZE as u32 + 1).to_be_bytes();
+        let mut reader = RawReader::new(std::io::Cursor::new(size));
+        assert!(reader.next_frame().unwrap().unwrap_err().to_string().starts_with("Raw frame too big"));
+
+        assert!(RawReader::new(std::io::empty()).next_frame().is_none());
+    }
+}
@@ -0,0 +1,558 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::str::FromStr;
+use std::time::Duration;
+use crate::ipv4::{IPv4, Ipv4Address};
+use crate::ipv6::{IPv6, Ipv6Address};
+use crate::limits::{FlowTable, ResourceLimits};
+use crate::tcp::{self, Tcp};
+
+
+/// One direction of a TCP connection.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct StreamKey {
+    pub source: Ipv4Address,
+    pub source_port: u16,
+    pub destination: Ipv4Address,
+    pub destination_port: u16,
+}
+
+impl StreamKey {
+    pub fn new(ip: &IPv4, tcp: &Tcp) -> Self {
+        Self {
+            source: ip.source_address(),
+            source_port: tcp.source_port(),
+            destination: ip.destination_address(),
+            destination_port: tcp.destination_port(),
+        }
+    }
+
+    /// The opposite direction of the same connection.
+    pub fn reversed(&self) -> Self {
+        Self {
+            source: self.destination,
+            source_port: self.destination_port,
+            destination: self.source,
+            destination_port: self.source_port,
+        }
+    }
+}
+
+impl std::fmt::Display for StreamKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{:?}:{} > {:?}:{}", self.source, self.source_port, self.destination, self.destination_port)
+    }
+}
+
+impl std::str::FromStr for StreamKey {
+    type Err = String;
+
+    /// Parses `SRC:PORT-DST:PORT`, e.g. `10.0.0.1:51000-10.0.0.2:80`.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        fn endpoint(text: &str) -> Result<(Ipv4Address, u16), String> {
+            let (address, port) = text.split_once(':').ok_or(format!("Expected ADDRESS:PORT, got '{}'", text))?;
+            let port = port.parse().map_err(|_| format!("Invalid port '{}'", port))?;
+            Ok((address.parse()?, port))
+        }
+
+        let (source, destination) = text.split_once('-').ok_or(format!("Expected SRC:PORT-DST:PORT, got '{}'", text))?;
+        let (source, source_port) = endpoint(source)?;
+        let (destination, destination_port) = endpoint(destination)?;
+        Ok(Self { source, source_port, destination, destination_port })
+    }
+}
+
+
+/// One direction of a TCP connection over IPv4 or IPv6, for state kept per flow regardless of the IP version.
+/// IPv4 addresses are stored IPv4-mapped (`::ffff:a.b.c.d`).
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct FlowKey {
+    pub source: Ipv6Address,
+    pub source_port: u16,
+    pub destination: Ipv6Address,
+    pub destination_port: u16,
+}
+
+impl FlowKey {
+    pub fn from_ipv4(ip: &IPv4, tcp: &Tcp) -> Self {
+        Self::from(StreamKey::new(ip, tcp))
+    }
+
+    pub fn from_ipv6(ip: &IPv6, tcp: &Tcp) -> Self {
+        Self {
+            source: ip.source_address(),
+            source_port: tcp.source_port(),
+            destination: ip.destination_address(),
+            destination_port: tcp.destination_port(),
+        }
+    }
+
+    /// The opposite direction of the same connection.
+    pub fn reversed(&self) -> Self {
+        Self {
+            source: self.destination,
+            source_port: self.destination_port,
+            destination: self.source,
+            destination_port: self.source_port,
+        }
+    }
+}
+
+impl From<StreamKey> for FlowKey {
+    fn from(key: StreamKey) -> Self {
+        fn mapped(address: Ipv4Address) -> Ipv6Address {
+            let mut bytes = [0; 16];
+            bytes[10..12].copy_from_slice(&[0xFF, 0xFF]);
+            bytes[12..].copy_from_slice(address.bytes());
+            Ipv6Address::from_bytes(bytes)
+        }
+        Self {
+            source: mapped(key.source),
+            source_port: key.source_port,
+            destination: mapped(key.destination),
+            destination_port: key.destination_port,
+        }
+    }
+}
+
+
+/// Which copy wins when segments overlap with different bytes, which attackers use to make an IDS see other
+/// data than the receiving host. The operating systems differ, so the policy should match the receiver.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Default)]
+pub enum OverlapPolicy {
+    /// The bytes received first.
+    #[default]
+    First,
+    /// The bytes received last.
+    Last,
+    /// The bytes received first, unless the new segment starts before the old one, or at the same sequence
+    /// number and ends after it.
+    Linux,
+    /// The bytes received first, unless the new segment starts before the old one.
+    Bsd,
+}
+
+impl OverlapPolicy {
+    /// Whether the new segment wins over the old one it overlaps, both given as sequence number and length.
+    fn new_wins(&self, old: (u32, usize), new: (u32, usize)) -> bool {
+        let end = |(sequence, length): (u32, usize)| sequence.wrapping_add(length as u32);
+        match self {
+            Self::First => false,
+            Self::Last  => true,
+            Self::Linux => is_before(new.0, old.0) || (new.0 == old.0 && is_before(end(old), end(new))),
+            Self::Bsd   => is_before(new.0, old.0),
+        }
+    }
+}
+
+impl FromStr for OverlapPolicy {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        match text {
+            "first" => Ok(Self::First),
+            "last"  => Ok(Self::Last),
+            "linux" => Ok(Self::Linux),
+            "bsd"   => Ok(Self::Bsd),
+            _ => Err(format!("Invalid overlap policy '{}', expected first, last, linux or bsd", text)),
+        }
+    }
+}
+
+
+/// Reorders the segments of one direction into a contiguous byte stream.
+#[derive(Debug, Default)]
+pub struct Stream {
+    /// Sequence number of the next byte expected in order.
+    next_sequence: Option<u32>,
+    /// Segments received ahead of `next_sequence`, keyed by sequence number. Where they overlap their bytes
+    /// agree, as decided by `policy`.
+    pending: BTreeMap<u32, Vec<u8>>,
+    policy: OverlapPolicy,
+    /// The last bytes delivered, ending at `next_sequence`, to compare retransmissions with.
+    delivered: VecDeque<u8>,
+    conflicts: usize,
+}
+
+impl Stream {
+    /// Maximum number of bytes buffered out of order before they are dropped.
+    pub const MAX_PENDING_BYTES: usize = 1 << 20;
+    /// Number of delivered bytes kept to compare retransmissions with.
+    pub const HISTORY_SIZE: usize = 64 << 10;
+
+    pub fn with_policy(policy: OverlapPolicy) -> Self {
+        Self { policy, ..Self::default() }
+    }
+
+    /// Number of bytes buffered out of order.
+    pub fn pending_bytes(&self) -> usize {
+        self.pending.values().map(|x| x.len()).sum()
+    }
+
+    /// Number of segments that overlapped earlier data with different bytes. Bytes that were already
+    /// delivered can't be replaced, whatever the policy.
+    pub fn conflicts(&self) -> usize {
+        self.conflicts
+    }
+
+    /// Adds a segment and returns the bytes that became contiguous because of it.
+    pub fn push(&mut self, sequence: u32, syn: bool, payload: &[u8]) -> Vec<u8> {
+        // The SYN consumes one sequence number before the first data byte.
+        let sequence = if syn { sequence.wrapping_add(1) } else { sequence };
+        let next = *self.next_sequence.get_or_insert(sequence);
+
+        if !payload.is_empty() {
+            let mut differs = self.differs_from_delivered(sequence, payload);
+
+            // Segments that end before the next expected byte have already been delivered.
+            if is_before(next, sequence.wrapping_add(payload.len() as u32)) {
+                let mut segment = payload.to_vec();
+                differs |= self.resolve_overlaps(sequence, &mut segment);
+                if self.pending.get(&sequence).is_none_or(|old| old.len() < segment.len()) {
+                    self.pending.insert(sequence, segment);
+                }
+            }
+            if differs { self.conflicts += 1 }
+        }
+
+        let mut output = Vec::new();
+        loop {
+            let next = self.next_sequence.unwrap();
+            let Some(sequence) = self.pending.keys().copied().find(|x| !is_before(next, *x)) else { break };
+
+            // Skip the part of the segment that was already delivered.
+            let segment = self.pending.remove(&sequence).unwrap();
+            let skip = next.wrapping_sub(sequence) as usize;
+            if skip < segment.len() {
+                output.extend_from_slice(&segment[skip..]);
+                self.next_sequence = Some(next.wrapping_add((segment.len() - skip) as u32));
+            }
+        }
+
+        if self.pending_bytes() > Self::MAX_PENDING_BYTES {
+            self.pending.clear();
+        }
+
+        self.delivered.extend(&output);
+        let excess = self.delivered.len().saturating_sub(Self::HISTORY_SIZE);
+        self.delivered.drain(..excess);
+
+        output
+    }
+
+    /// Whether the part of a segment before `next_sequence` differs from the bytes delivered there, as far
+    /// as they're still kept.
+    fn differs_from_delivered(&self, sequence: u32, data: &[u8]) -> bool {
+        let Some(next) = self.next_sequence else { return false };
+        if !is_before(sequence, next) { return false }
+
+        let start = next.wrapping_sub(self.delivered.len() as u32);
+        let length = (next.wrapping_sub(sequence) as usize).min(data.len());
+        data[..length].iter().enumerate().any(|(i, byte)| {
+            let position = sequence.wrapping_add(i as u32);
+            !is_before(position, start) && self.delivered[position.wrapping_sub(start) as usize] != *byte
+        })
+    }
+
+    /// Makes the bytes of `segment` and the pending segments agree where they overlap, keeping the copy the
+    /// policy prefers. Returns whether any of them differed.
+    fn resolve_overlaps(&mut self, sequence: u32, segment: &mut [u8]) -> bool {
+        let policy = self.policy;
+        let end = sequence.wrapping_add(segment.len() as u32);
+        let mut differs = false;
+
+        for (start, old) in self.pending.iter_mut() {
+            let old_end = start.wrapping_add(old.len() as u32);
+            let from = if is_before(*start, sequence) { sequence } else { *start };
+            let to = if is_before(old_end, end) { old_end } else { end };
+            if !is_before(from, to) { continue }
+
+            let length = to.wrapping_sub(from) as usize;
+            let old_range = from.wrapping_sub(*start) as usize..from.wrapping_sub(*start) as usize + length;
+            let new_range = from.wrapping_sub(sequence) as usize..from.wrapping_sub(sequence) as usize + length;
+            if old[old_range.clone()] == segment[new_range.clone()] { continue }
+
+            differs = true;
+            if policy.new_wins((*start, old.len()), (sequence, segment.len())) {
+                old[old_range].copy_from_slice(&segment[new_range]);
+            } else {
+                segment[new_range].copy_from_slice(&old[old_range]);
+            }
+        }
+        differs
+    }
+}
+
+
+/// Whether sequence number `a` comes before `b`, taking wrap-around into account.
+pub fn is_before(a: u32, b: u32) -> bool {
+    (a.wrapping_sub(b) as i32) < 0
+}
+
+
+/// Reassembles the byte streams of all TCP connections over IPv4, within `ResourceLimits`.
+#[derive(Debug, Default)]
+pub struct Reassembler {
+    streams: FlowTable<StreamKey, Stream>,
+    policy: OverlapPolicy,
+    /// At least the bytes buffered out of order across all streams. Streams that are dropped aren't
+    /// subtracted, so it's recounted when it exceeds the limit.
+    pending_bytes: usize,
+}
+
+impl Reassembler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_policy(self, policy: OverlapPolicy) -> Self {
+        Self { policy, ..self }
+    }
+
+    pub fn with_limits(self, limits: ResourceLimits) -> Self {
+        Self { streams: FlowTable::new(limits), ..self }
+    }
+
+    /// Adds a segment captured at `now` and returns the bytes that became contiguous in its direction.
+    pub fn push(&mut self, ip: &IPv4, tcp: &Tcp, now: Duration) -> Vec<u8> {
+        let policy = self.policy;
+        let stream = self.streams.get_or_insert_with(StreamKey::new(ip, tcp), now, || Stream::with_policy(policy));
+        let before = stream.pending_bytes();
+        let output = stream.push(tcp.sequence_number(), tcp.syn() != 0, tcp.raw_payload());
+        self.pending_bytes = (self.pending_bytes + stream.pending_bytes()).saturating_sub(before);
+
+        let limit = self.streams.limits().max_reassembly_bytes;
+        if self.pending_bytes > limit {
+            self.pending_bytes = self.streams.values().map(|x| x.pending_bytes()).sum();
+            while self.pending_bytes > limit {
+                let Some((_, stream)) = self.streams.evict_oldest() else { break };
+                self.pending_bytes -= stream.pending_bytes();
+            }
+        }
+        output
+    }
+
+    pub fn stream(&self, key: &StreamKey) -> Option<&Stream> {
+        self.streams.get(key)
+    }
+
+    /// Number of streams evicted to stay within the limits, and dropped after being idle.
+    pub fn evicted(&self) -> usize {
+        self.streams.evicted()
+    }
+
+    pub fn expired(&self) -> usize {
+        self.streams.expired()
+    }
+
+    /// Number of conflicting overlaps in all streams, see `Stream::conflicts`.
+    pub fn conflicts(&self) -> usize {
+        self.streams.values().map(|x| x.conflicts()).sum()
+    }
+}
+
+
+/// Remembers the window scale each side announced in its SYN, which applies to every later window it sends
+/// but only if both sides announced one (RFC 7323).
+#[derive(Debug, Default)]
+pub struct WindowTracker {
+    scales: HashMap<FlowKey, Option<u8>>,
+}
+
+impl WindowTracker {
+    /// Largest shift allowed, larger announced values are treated as this.
+    pub const MAX_SCALE: u8 = 14;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records the scale of a SYN segment, and returns the shift that applies to the window of the segment or
+    /// `None` if it isn't scaled.
+    pub fn push(&mut self, key: FlowKey, tcp: &Tcp) -> Option<u8> {
+        if tcp.syn() != 0 {
+            let scale = tcp.options().find_map(|option| match option {
+                tcp::Option::WindowScale { scale } => Some(scale.min(Self::MAX_SCALE)),
+                _ => None,
+            });
+            self.scales.insert(key, scale);
+            // The window of a SYN segment is never scaled.
+            return None;
+        }
+
+        let scale = (*self.scales.get(&key)?)?;
+        (*self.scales.get(&key.reversed())?)?;
+        Some(scale)
+    }
+
+    /// The window of the segment in bytes, taking the negotiated scale into account.
+    pub fn scaled_window(&mut self, key: FlowKey, tcp: &Tcp) -> u32 {
+        let window = tcp.window_size() as u32;
+        match self.push(key, tcp) {
+            Some(scale) => window << scale,
+            None => window,
+        }
+    }
+}
+
+
+/// Remembers the initial sequence number each side sent in its SYN, to show sequence and acknowledgment
+/// numbers relative to it like Wireshark does. The SYN itself has relative sequence number 0.
+#[derive(Debug, Default)]
+pub struct SequenceTracker {
+    initial: HashMap<FlowKey, u32>,
+}
+
+impl SequenceTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records the ISN of a SYN segment, and returns the relative sequence and acknowledgment numbers of the
+    /// segment. Either is `None` if the SYN of its direction wasn't captured, or for the acknowledgment number
+    /// if the ACK flag isn't set.
+    pub fn push(&mut self, key: FlowKey, tcp: &Tcp) -> (Option<u32>, Option<u32>) {
+        if tcp.syn() != 0 {
+            self.initial.insert(key, tcp.sequence_number());
+        }
+
+        let sequence = self.initial.get(&key).map(|isn| tcp.sequence_number().wrapping_sub(*isn));
+        let acknowledgment = match tcp.ack() {
+            0 => None,
+            _ => self.initial.get(&key.reversed()).map(|isn| tcp.acknowledgment_number().wrapping_sub(*isn)),
+        };
+        (sequence, acknowledgment)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::{TcpBuilder, TCP_ACK, TCP_SYN};
+    use crate::testing::{self, ip};
+
+    const CLIENT: [u8; 16] = [0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1];
+    const SERVER: [u8; 16] = [0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2];
+
+    fn client() -> FlowKey {
+        FlowKey { source: Ipv6Address::from_bytes(CLIENT), source_port: 51000, destination: Ipv6Address::from_bytes(SERVER), destination_port: 80 }
+    }
+
+    /// A segment announcing a window scale of `scale` if any.
+    fn segment(tcp: TcpBuilder, scale: Option<u8>) -> Vec<u8> {
+        match scale {
+            Some(scale) => testing::tcp_with_options(&tcp, &[1, 3, 3, scale]),
+            None => testing::tcp_with_options(&tcp, &[]),
+        }
+    }
+
+    #[test]
+    fn scales_windows_after_both_sides_announce_a_scale() {
+        let mut windows = WindowTracker::new();
+        let syn = segment(TcpBuilder::new(51000, 80).with_flags(TCP_SYN).with_window_size(512), Some(7));
+        let syn_ack = segment(TcpBuilder::new(80, 51000).with_flags(TCP_SYN | TCP_ACK).with_window_size(1024), Some(2));
+        let ack = segment(TcpBuilder::new(51000, 80).with_flags(TCP_ACK).with_window_size(512), None);
+        let reply = segment(TcpBuilder::new(80, 51000).with_flags(TCP_ACK).with_window_size(1024), None);
+
+        assert_eq!(windows.scaled_window(client(), &Tcp::from_bytes(&syn).unwrap()), 512);
+        assert_eq!(windows.scaled_window(client().reversed(), &Tcp::from_bytes(&syn_ack).unwrap()), 1024);
+        assert_eq!(windows.scaled_window(client(), &Tcp::from_bytes(&ack).unwrap()), 65536);
+        assert_eq!(windows.scaled_window(client().reversed(), &Tcp::from_bytes(&reply).unwrap()), 4096);
+    }
+
+    #[test]
+    fn leaves_windows_unscaled_unless_both_sides_announce_a_scale() {
+        let mut windows = WindowTracker::new();
+        let syn = segment(TcpBuilder::new(51000, 80).with_flags(TCP_SYN), Some(7));
+        let syn_ack = segment(TcpBuilder::new(80, 51000).with_flags(TCP_SYN | TCP_ACK), None);
+        let ack = segment(TcpBuilder::new(51000, 80).with_flags(TCP_ACK).with_window_size(512), None);
+
+        windows.push(client(), &Tcp::from_bytes(&syn).unwrap());
+        windows.push(client().reversed(), &Tcp::from_bytes(&syn_ack).unwrap());
+        assert_eq!(windows.scaled_window(client(), &Tcp::from_bytes(&ack).unwrap()), 512);
+        // Nor without the handshake.
+        assert_eq!(WindowTracker::new().scaled_window(client(), &Tcp::from_bytes(&ack).unwrap()), 512);
+    }
+
+    #[test]
+    fn numbers_sequences_relative_to_the_syn_of_each_direction() {
+        let mut sequences = SequenceTracker::new();
+        let syn = segment(TcpBuilder::new(51000, 80).with_flags(TCP_SYN).with_sequence_number(1000), None);
+        let syn_ack = segment(TcpBuilder::new(80, 51000).with_flags(TCP_SYN | TCP_ACK).with_sequence_number(5000).with_acknowledgment_number(1001), None);
+        let ack = segment(TcpBuilder::new(51000, 80).with_flags(TCP_ACK).with_sequence_number(1001).with_acknowledgment_number(5001), None);
+
+        assert_eq!(sequences.push(client(), &Tcp::from_bytes(&syn).unwrap()), (Some(0), None));
+        assert_eq!(sequences.push(client().reversed(), &Tcp::from_bytes(&syn_ack).unwrap()), (Some(0), Some(1)));
+        assert_eq!(sequences.push(client(), &Tcp::from_bytes(&ack).unwrap()), (Some(1), Some(1)));
+        // Without the handshake the numbers stay absolute.
+        assert_eq!(SequenceTracker::new().push(client(), &Tcp::from_bytes(&ack).unwrap()), (None, None));
+    }
+
+    #[test]
+    fn maps_ipv4_streams_into_flow_keys() {
+        let key = FlowKey::from(StreamKey { source: ip(10, 0, 0, 1), source_port: 51000, destination: ip(10, 0, 0, 2), destination_port: 80 });
+        assert_eq!(key.source.bytes(), &[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xFF, 0xFF, 10, 0, 0, 1]);
+        assert_eq!(key.destination.bytes(), &[0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0xFF, 0xFF, 10, 0, 0, 2]);
+        assert_eq!(key.reversed().source_port, 80);
+        assert_eq!(key.reversed().reversed(), key);
+    }
+    /// The bytes delivered once the gap before `segments` is filled, and the number of conflicts seen.
+    fn reassemble(policy: OverlapPolicy, segments: &[(u32, &[u8])]) -> (Vec<u8>, usize) {
+        let mut stream = Stream::with_policy(policy);
+        assert_eq!(stream.push(100, false, b"xx"), b"xx");
+        for (sequence, payload) in segments {
+            assert_eq!(stream.push(*sequence, false, payload), b"");
+        }
+        (stream.push(102, false, b"-+"), stream.conflicts())
+    }
+
+    #[test]
+    fn resolves_overlaps_with_the_same_start_by_policy() {
+        let segments: &[(u32, &[u8])] = &[(104, b"abcd"), (104, b"WXYZEF")];
+        assert_eq!(reassemble(OverlapPolicy::First, segments), (b"-+abcdEF".to_vec(), 1));
+        assert_eq!(reassemble(OverlapPolicy::Last, segments), (b"-+WXYZEF".to_vec(), 1));
+        assert_eq!(reassemble(OverlapPolicy::Linux, segments), (b"-+WXYZEF".to_vec(), 1));
+        assert_eq!(reassemble(OverlapPolicy::Bsd, segments), (b"-+abcdEF".to_vec(), 1));
+    }
+
+    #[test]
+    fn resolves_overlaps_starting_earlier_by_policy() {
+        let segments: &[(u32, &[u8])] = &[(105, b"abcd"), (104, b"WXY")];
+        assert_eq!(reassemble(OverlapPolicy::First, segments), (b"-+Wabcd".to_vec(), 1));
+        assert_eq!(reassemble(OverlapPolicy::Last, segments), (b"-+WXYcd".to_vec(), 1));
+        assert_eq!(reassemble(OverlapPolicy::Linux, segments), (b"-+WXYcd".to_vec(), 1));
+        assert_eq!(reassemble(OverlapPolicy::Bsd, segments), (b"-+WXYcd".to_vec(), 1));
+    }
+
+    #[test]
+    fn resolves_overlaps_starting_later_by_policy() {
+        let segments: &[(u32, &[u8])] = &[(104, b"abcd"), (106, b"WXYZ")];
+        assert_eq!(reassemble(OverlapPolicy::First, segments), (b"-+abcdYZ".to_vec(), 1));
+        assert_eq!(reassemble(OverlapPolicy::Last, segments), (b"-+abWXYZ".to_vec(), 1));
+        assert_eq!(reassemble(OverlapPolicy::Linux, segments), (b"-+abcdYZ".to_vec(), 1));
+        assert_eq!(reassemble(OverlapPolicy::Bsd, segments), (b"-+abcdYZ".to_vec(), 1));
+    }
+
+    #[test]
+    fn counts_only_overlaps_with_different_bytes() {
+        assert_eq!(reassemble(OverlapPolicy::Last, &[(104, b"abcd"), (106, b"cdef")]), (b"-+abcdef".to_vec(), 0));
+
+        // Delivered bytes stay as they were, but a retransmission changing them is still reported.
+        let mut stream = Stream::with_policy(OverlapPolicy::Last);
+        assert_eq!(stream.push(100, false, b"abcd"), b"abcd");
+        assert_eq!(stream.push(100, false, b"abcd"), b"");
+        assert_eq!(stream.conflicts(), 0);
+        assert_eq!(stream.push(102, false, b"XYef"), b"ef");
+        assert_eq!(stream.conflicts(), 1);
+    }
+
+    #[test]
+    fn parses_overlap_policies() {
+        assert_eq!("first".parse::<OverlapPolicy>(), Ok(OverlapPolicy::First));
+        assert_eq!("last".parse::<OverlapPolicy>(), Ok(OverlapPolicy::Last));
+        assert_eq!("linux".parse::<OverlapPolicy>(), Ok(OverlapPolicy::Linux));
+        assert_eq!("bsd".parse::<OverlapPolicy>(), Ok(OverlapPolicy::Bsd));
+        assert!("windows".parse::<OverlapPolicy>().is_err());
+        assert_eq!(OverlapPolicy::default(), OverlapPolicy::First);
+    }
+}
@@ -0,0 +1,161 @@
+use std::collections::HashMap;
+use crate::error::ParseError;
+use crate::shared::MacAddress;
+use crate::ipv4::Ipv4Address;
+use crate::endian::be_u16;
+
+
+/// Where a protocol is found: behind an EtherType or an IP protocol number.
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum ProtocolKey {
+    EtherType(u16),
+    IpProtocol(u8),
+}
+
+
+/// The fields a `ProtocolParser` decoded, in the order they should be shown.
+#[derive(Debug, Clone, Default)]
+pub struct Dissection {
+    pub name: &'static str,
+    pub fields: Vec<(&'static str, String)>,
+}
+
+
+/// Decodes a protocol that the built-in payload enums don't know.
+pub trait ProtocolParser {
+    fn name(&self) -> &'static str;
+    fn parse(&self, data: &[u8]) -> Result<Dissection, ParseError>;
+}
+
+
+/// The parsers consulted for payloads the built-in dispatch leaves as unknown, so dissectors can be added
+/// without touching the `Payload` enums or the visitors' match arms.
+pub struct Registry {
+    parsers: HashMap<ProtocolKey, Box<dyn ProtocolParser>>,
+}
+
+impl Registry {
+    /// A registry with the built-in dissectors.
+    pub fn new() -> Self {
+        let mut registry = Self::empty();
+        registry.register(ProtocolKey::EtherType(0x0806), Box::new(ArpParser));
+        registry
+    }
+
+    pub fn empty() -> Self {
+        Self { parsers: HashMap::new() }
+    }
+
+    /// Adds `parser` for `key`, returning the parser it replaces.
+    pub fn register(&mut self, key: ProtocolKey, parser: Box<dyn ProtocolParser>) -> Option<Box<dyn ProtocolParser>> {
+        self.parsers.insert(key, parser)
+    }
+
+    pub fn get(&self, key: ProtocolKey) -> Option<&dyn ProtocolParser> {
+        self.parsers.get(&key).map(|x| x.as_ref())
+    }
+
+    /// Decodes `data` with the parser for `key`, or `None` if there is none.
+    pub fn dissect(&self, key: ProtocolKey, data: &[u8]) -> Option<Result<Dissection, ParseError>> {
+        self.get(key).map(|parser| parser.parse(data))
+    }
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+/// ARP for IPv4 over Ethernet, https://www.rfc-editor.org/rfc/rfc826.
+pub struct ArpParser;
+
+impl ArpParser {
+    pub const SIZE: usize = 28;
+}
+
+impl ProtocolParser for ArpParser {
+    fn name(&self) -> &'static str { "Arp" }
+
+    fn parse(&self, data: &[u8]) -> Result<Dissection, ParseError> {
+        if data.len() < Self::SIZE {
+            return Err(ParseError::TooSmall { layer: "Arp", field: "data", expected: Self::SIZE, got: data.len() });
+        }
+        if be_u16(data, 0) != 1 || be_u16(data, 2) != 0x0800 || data[4] != 6 || data[5] != 4 {
+            return Err(ParseError::Unsupported { layer: "Arp", field: "hardware/protocol type", value: (be_u16(data, 0) as u32) << 16 | be_u16(data, 2) as u32 });
+        }
+
+        let operation = match be_u16(data, 6) {
+            1 => "Request".to_string(),
+            2 => "Reply".to_string(),
+            x => x.to_string(),
+        };
+        let mac = |i: usize| format!("{:?}", MacAddress::from_bytes(&data[i..i + 6]).unwrap());
+        let ip  = |i: usize| format!("{:?}", Ipv4Address::from_bytes(data[i..i + 4].try_into().unwrap()));
+
+        Ok(Dissection {
+            name: self.name(),
+            fields: vec![
+                ("Operation", operation),
+                ("Sender MAC", mac(8)),
+                ("Sender IP", ip(14)),
+                ("Target MAC", mac(18)),
+                ("Target IP", ip(24)),
+            ],
+        })
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reads a made up protocol whose first byte is a version and the rest a name.
+    struct Dummy;
+
+    impl ProtocolParser for Dummy {
+        fn name(&self) -> &'static str { "Dummy" }
+
+        fn parse(&self, data: &[u8]) -> Result<Dissection, ParseError> {
+            let (version, name) = data.split_first().ok_or(ParseError::TooSmall { layer: "Dummy", field: "data", expected: 1, got: 0 })?;
+            Ok(Dissection { name: self.name(), fields: vec![("Version", version.to_string()), ("Name", String::from_utf8_lossy(name).into_owned())] })
+        }
+    }
+
+    #[test]
+    fn dissects_with_a_registered_parser() {
+        let mut registry = Registry::empty();
+        assert!(registry.dissect(ProtocolKey::IpProtocol(253), b"\x01hi").is_none());
+        assert!(registry.register(ProtocolKey::IpProtocol(253), Box::new(Dummy)).is_none());
+
+        let dissection = registry.dissect(ProtocolKey::IpProtocol(253), b"\x01hi").unwrap().unwrap();
+        assert_eq!(dissection.name, "Dummy");
+        assert_eq!(dissection.fields, [("Version", "1".to_string()), ("Name", "hi".to_string())]);
+        assert!(matches!(registry.dissect(ProtocolKey::IpProtocol(253), b""), Some(Err(ParseError::TooSmall { .. }))));
+        // The same number behind another layer is another protocol.
+        assert!(registry.get(ProtocolKey::EtherType(253)).is_none());
+    }
+
+    #[test]
+    fn replaces_a_built_in_parser() {
+        let mut registry = Registry::new();
+        assert_eq!(registry.get(ProtocolKey::EtherType(0x0806)).map(|x| x.name()), Some("Arp"));
+        let old = registry.register(ProtocolKey::EtherType(0x0806), Box::new(Dummy));
+        assert_eq!(old.map(|x| x.name()), Some("Arp"));
+        assert_eq!(registry.get(ProtocolKey::EtherType(0x0806)).map(|x| x.name()), Some("Dummy"));
+    }
+
+    #[test]
+    fn parses_an_arp_request() {
+        let mut data = vec![0, 1, 8, 0, 6, 4, 0, 1];
+        data.extend_from_slice(&[2, 0, 0, 0, 0, 0x0A, 10, 0, 0, 1]);
+        data.extend_from_slice(&[0, 0, 0, 0, 0, 0, 10, 0, 0, 2]);
+        let dissection = ArpParser.parse(&data).unwrap();
+        assert_eq!(dissection.fields[0], ("Operation", "Request".to_string()));
+        assert_eq!(dissection.fields[2], ("Sender IP", "10.0.0.1".to_string()));
+        assert_eq!(dissection.fields[4], ("Target IP", "10.0.0.2".to_string()));
+        assert!(matches!(ArpParser.parse(&data[..27]), Err(ParseError::TooSmall { layer: "Arp", .. })));
+    }
+}
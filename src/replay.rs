@@ -0,0 +1,41 @@
+use std::time::{Duration, Instant};
+use pcap::PacketHeader;
+use crate::time_window;
+
+
+/// Sleeps between the packets of a capture file so they are visited with the gaps they were captured with,
+/// divided by `speed`.
+pub struct Pacer {
+    speed: f64,
+    /// Timestamp of the previous packet and when it was released.
+    previous: Option<(Duration, Instant)>,
+}
+
+impl Pacer {
+    /// Longest sleep between two packets, so an idle hour in the capture doesn't stall the replay.
+    pub const MAX_SLEEP: Duration = Duration::from_secs(5);
+
+    pub fn new(speed: f64) -> Self {
+        Self { speed, previous: None }
+    }
+
+    /// Blocks until the packet is due. Packets with a timestamp before the previous one are released at once.
+    pub fn wait(&mut self, header: &PacketHeader) {
+        let timestamp = time_window::timestamp(header);
+        let now = Instant::now();
+
+        let release = match self.previous {
+            Some((previous, released)) => {
+                let gap = timestamp.saturating_sub(previous).div_f64(self.speed).min(Self::MAX_SLEEP);
+                released + gap
+            },
+            None => now,
+        };
+        if release > now {
+            std::thread::sleep(release - now);
+        }
+
+        // Time lost to slow visitors isn't made up for by skipping later sleeps.
+        self.previous = Some((timestamp, release.max(now)));
+    }
+}
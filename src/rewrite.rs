@@ -0,0 +1,252 @@
+use std::collections::HashMap;
+use std::io::{Error, Write};
+use std::str::FromStr;
+use pcap::Packet;
+use crate::endian::be_u16;
+use crate::ethernet::Ethernet;
+use crate::ipv4::{IPv4, Ipv4Address, Protocol};
+use crate::pcap_writer::PcapWriter;
+use crate::shared::{checksum_add, checksum_adjust, checksum_finish, crc32, MacAddress, NETWORK_PROTOCOL_IPv4};
+use crate::visitor::Visitor;
+
+
+/// Parses `OLD=NEW`, e.g. `10.0.0.1=192.168.1.1`.
+pub fn parse_mapping<T: FromStr<Err=String>>(text: &str) -> Result<(T, T), String> {
+    let (old, new) = text.split_once('=').ok_or(format!("Expected OLD=NEW, got '{}'", text))?;
+    Ok((old.parse()?, new.parse()?))
+}
+
+
+/// Replaces MAC and IPv4 addresses in Ethernet frames. Addresses without a mapping are left as they are, and
+/// the checksums covering a replaced address are updated: the IPv4 header checksum is recomputed, the TCP and
+/// UDP checksums are adjusted incrementally so that they stay valid for segments that weren't fully captured.
+#[derive(Debug, Default, Clone)]
+pub struct Rewriter {
+    macs: HashMap<MacAddress, MacAddress>,
+    ips: HashMap<Ipv4Address, Ipv4Address>,
+}
+
+impl Rewriter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_mac(mut self, old: MacAddress, new: MacAddress) -> Self {
+        self.macs.insert(old, new);
+        self
+    }
+
+    pub fn with_ip(mut self, old: Ipv4Address, new: Ipv4Address) -> Self {
+        self.ips.insert(old, new);
+        self
+    }
+
+    /// The frame with its addresses replaced, or `None` if nothing matched.
+    pub fn rewrite(&self, data: &[u8]) -> Option<Vec<u8>> {
+        let frame = Ethernet::from_bytes(data).ok()?;
+        let mut out = data.to_vec();
+        let mut changed = false;
+
+        for range in [Ethernet::DEST_MAC_ADDRESS_OFFSET, Ethernet::SRC_MAC_ADDRESS_OFFSET] {
+            let old = MacAddress::from_bytes(&data[range.clone()]).ok()?;
+            if let Some(new) = self.macs.get(&old) {
+                out[range].copy_from_slice(new.bytes());
+                changed = true;
+            }
+        }
+
+        if frame.ether_type_raw() == NETWORK_PROTOCOL_IPv4 {
+            changed |= self.rewrite_ipv4(&mut out[Ethernet::PAYLOAD_OFFSET..]);
+        }
+
+        if !changed { return None }
+        if frame.has_fcs() {
+            let end = out.len() - Ethernet::CRC_SIZE;
+            let crc = crc32(&out[..end]);
+            out[end..].copy_from_slice(&crc.to_le_bytes());
+        }
+        Some(out)
+    }
+
+    /// Replaces the addresses of the IPv4 packet at the start of `data`, returning whether any matched.
+    fn rewrite_ipv4(&self, data: &mut [u8]) -> bool {
+        let Ok(ip) = IPv4::from_bytes(data) else { return false };
+        let header_size = ip.header_size();
+        let protocol = ip.protocol();
+        let first_fragment = ip.fragment_offset() == 0;
+        let (source, destination) = (ip.source_address(), ip.destination_address());
+
+        let mut old = [0u8; 8];
+        old[..4].copy_from_slice(source.bytes());
+        old[4..].copy_from_slice(destination.bytes());
+        let mut new = old;
+        if let Some(address) = self.ips.get(&source) { new[..4].copy_from_slice(address.bytes()) }
+        if let Some(address) = self.ips.get(&destination) { new[4..].copy_from_slice(address.bytes()) }
+        if new == old { return false }
+
+        data[12..20].copy_from_slice(&new);
+        data[10..12].fill(0);
+        let checksum = checksum_finish(checksum_add(0, &data[..header_size]));
+        data[10..12].copy_from_slice(&checksum.to_be_bytes());
+
+        // Later fragments don't hold the transport header, and the pseudo-header covers the addresses.
+        let offset = match protocol {
+            Protocol::TCP => 16,
+            Protocol::UDP => 6,
+            _ => return true,
+        };
+        let segment = &mut data[header_size..];
+        if !first_fragment || segment.len() < offset + 2 { return true }
+
+        let checksum = be_u16(segment, offset);
+        // A UDP checksum of zero means the sender didn't compute one.
+        if protocol == Protocol::UDP && checksum == 0 { return true }
+        let mut checksum = checksum_adjust(checksum, &old, &new);
+        if protocol == Protocol::UDP && checksum == 0 { checksum = 0xFFFF }
+        segment[offset..offset + 2].copy_from_slice(&checksum.to_be_bytes());
+        true
+    }
+}
+
+
+/// Writes every Ethernet frame to a pcap file with its addresses replaced by a `Rewriter`.
+pub struct RewriteVisitor<W: Write> {
+    rewriter: Rewriter,
+    writer: PcapWriter<W>,
+    rewritten: usize,
+}
+
+impl<W: Write> RewriteVisitor<W> {
+    pub fn new(rewriter: Rewriter, writer: PcapWriter<W>) -> Self {
+        Self { rewriter, writer, rewritten: 0 }
+    }
+}
+
+
+impl<'a, W: Write> Visitor<'a, ()> for RewriteVisitor<W> {
+    fn visit_packet(&mut self, packet: &'a Packet) -> Result<(), Error> {
+        match self.rewriter.rewrite(packet.data) {
+            Some(data) => {
+                self.rewritten += 1;
+                self.writer.write_packet(packet.header, &data)?;
+            },
+            None => {
+                self.writer.write_packet(packet.header, packet.data)?;
+            },
+        }
+        Ok(())
+    }
+
+    /// The file is written with the Ethernet link type, so wireless and cooked frames aren't saved.
+    fn visit_radiotap_packet(&mut self, packet: &'a Packet) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn visit_sll2_packet(&mut self, packet: &'a Packet) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        self.writer.flush()?;
+        println!("Rewrote addresses in {} packets", self.rewritten);
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::{TcpBuilder, TCP_ACK};
+    use crate::ethernet::{self, Ethernet};
+    use crate::testing::{self, ip, MAC_A, MAC_B};
+    use crate::tree::SharedBuffer;
+
+    /// The IPv4 packet of an Ethernet `frame`.
+    fn ipv4(frame: &[u8]) -> IPv4<'_> {
+        let Ok(ethernet::Payload::IPv4(packet)) = Ethernet::from_bytes(frame).unwrap().payload() else { panic!("not IPv4") };
+        packet
+    }
+
+    /// Whether the TCP segment of `ip` sums to zero with its pseudo-header.
+    fn tcp_checksum_is_valid(ip: &IPv4) -> bool {
+        let segment = ip.raw_payload();
+        let mut sum = checksum_add(0, ip.source_address().bytes());
+        sum = checksum_add(sum, ip.destination_address().bytes());
+        sum = checksum_add(sum, &[0, Protocol::TCP.value()]);
+        sum = checksum_add(sum, &(segment.len() as u16).to_be_bytes());
+        checksum_finish(checksum_add(sum, segment)) == 0
+    }
+
+    #[test]
+    fn rewrites_a_source_ip_with_valid_checksums() {
+        let tcp = TcpBuilder::new(51000, 80).with_flags(TCP_ACK).with_payload(b"GET / HTTP/1.1\r\n\r\n");
+        let frame = testing::tcp_frame(ip(10, 0, 0, 1), ip(10, 0, 0, 2), &tcp);
+        let rewriter = Rewriter::new().with_ip(ip(10, 0, 0, 1), ip(192, 168, 1, 1));
+
+        let out = rewriter.rewrite(&frame).unwrap();
+        let packet = ipv4(&out);
+        assert_eq!(packet.source_address(), ip(192, 168, 1, 1));
+        assert_eq!(packet.destination_address(), ip(10, 0, 0, 2));
+        assert!(packet.verify_header_checksum());
+        assert!(tcp_checksum_is_valid(&packet));
+        assert_eq!(&out[..Ethernet::PAYLOAD_OFFSET], &frame[..Ethernet::PAYLOAD_OFFSET]);
+    }
+
+    #[test]
+    fn leaves_frames_without_a_matching_address_alone() {
+        let frame = testing::tcp_frame(ip(10, 0, 0, 3), ip(10, 0, 0, 2), &TcpBuilder::new(51000, 80));
+        let rewriter = Rewriter::new().with_ip(ip(10, 0, 0, 1), ip(192, 168, 1, 1)).with_mac(MacAddress::from_bytes(&[2, 0, 0, 0, 0, 0x0C]).unwrap(), MacAddress::from_bytes(&MAC_A).unwrap());
+        assert_eq!(rewriter.rewrite(&frame), None);
+    }
+
+    #[test]
+    fn rewrites_only_the_matching_mac() {
+        let frame = testing::udp_frame(ip(10, 0, 0, 1), 53000, ip(10, 0, 0, 2), 53, b"query");
+        let new = [2, 0, 0, 0, 0, 0x0C];
+        let rewriter = Rewriter::new().with_mac(MacAddress::from_bytes(&MAC_A).unwrap(), MacAddress::from_bytes(&new).unwrap());
+
+        let out = rewriter.rewrite(&frame).unwrap();
+        assert_eq!(&out[Ethernet::DEST_MAC_ADDRESS_OFFSET], &MAC_B);
+        assert_eq!(&out[Ethernet::SRC_MAC_ADDRESS_OFFSET], &new);
+        assert_eq!(&out[Ethernet::PAYLOAD_OFFSET..], &frame[Ethernet::PAYLOAD_OFFSET..]);
+    }
+
+    #[test]
+    fn keeps_an_absent_udp_checksum_absent() {
+        let frame = testing::udp_frame(ip(10, 0, 0, 1), 53000, ip(10, 0, 0, 2), 53, b"query");
+        let out = Rewriter::new().with_ip(ip(10, 0, 0, 2), ip(10, 0, 0, 9)).rewrite(&frame).unwrap();
+        let packet = ipv4(&out);
+        assert_eq!(packet.destination_address(), ip(10, 0, 0, 9));
+        assert!(packet.verify_header_checksum());
+        assert_eq!(&packet.raw_payload()[6..8], &[0, 0]);
+    }
+
+    #[test]
+    fn writes_every_frame_with_the_rewritten_ones_replaced() {
+        let buffer = SharedBuffer::default();
+        let rewriter = Rewriter::new().with_ip(ip(10, 0, 0, 1), ip(192, 168, 1, 1));
+        let mut visitor = RewriteVisitor::new(rewriter, PcapWriter::new(buffer.clone(), 1).unwrap());
+        let frames = [
+            testing::tcp_frame(ip(10, 0, 0, 1), ip(10, 0, 0, 2), &TcpBuilder::new(51000, 80)),
+            testing::tcp_frame(ip(10, 0, 0, 3), ip(10, 0, 0, 2), &TcpBuilder::new(51000, 80)),
+        ];
+        for (i, frame) in frames.iter().enumerate() {
+            let header = testing::header(i as u64, frame.len());
+            visitor.visit_packet(&Packet::new(&header, frame)).unwrap();
+        }
+        visitor.finish().unwrap();
+
+        let written = testing::read_pcap(&buffer.take());
+        assert_eq!(visitor.rewritten, 1);
+        assert_eq!(ipv4(&written[0]).source_address(), ip(192, 168, 1, 1));
+        assert_eq!(written[1], frames[1]);
+    }
+
+    #[test]
+    fn parses_mappings() {
+        assert_eq!(parse_mapping::<Ipv4Address>("10.0.0.1=192.168.1.1"), Ok((ip(10, 0, 0, 1), ip(192, 168, 1, 1))));
+        assert!(parse_mapping::<Ipv4Address>("10.0.0.1").is_err());
+        assert!(parse_mapping::<Ipv4Address>("10.0.0.1=nowhere").is_err());
+    }
+}
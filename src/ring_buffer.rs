@@ -0,0 +1,146 @@
+use std::fs::File;
+use std::io::{BufWriter, Error};
+use std::path::{Path, PathBuf};
+use pcap::Packet;
+use crate::pcap_writer::{self, PcapWriter};
+use crate::visitor::Visitor;
+
+
+/// Writes every packet to a ring of `count` pcap files named `capN.pcap`, moving on to the next file when the
+/// current one reaches `size_limit` bytes and overwriting the oldest once all are used. Packets are then passed on
+/// to the inner visitor.
+pub struct RingBuffer {
+    inner: Box<dyn for<'a> Visitor<'a, ()>>,
+    directory: PathBuf,
+    link_type: u32,
+    count: usize,
+    size_limit: usize,
+    /// Index of the file currently written to.
+    index: usize,
+    /// Bytes written to the current file.
+    size: usize,
+    writer: Option<PcapWriter<BufWriter<File>>>,
+}
+
+impl RingBuffer {
+    pub fn new(inner: Box<dyn for<'a> Visitor<'a, ()>>, directory: &Path, link_type: u32, count: usize, size_limit: usize) -> Self {
+        Self {
+            inner,
+            directory: directory.to_path_buf(),
+            link_type,
+            count: count.max(1),
+            size_limit,
+            index: 0,
+            size: 0,
+            writer: None,
+        }
+    }
+
+    pub fn path(&self, index: usize) -> PathBuf {
+        self.directory.join(format!("cap{}.pcap", index))
+    }
+
+    /// Parses `<count>:<size_mb>`.
+    pub fn parse_spec(text: &str) -> Result<(usize, usize), String> {
+        let (count, size) = text.split_once(':').ok_or(format!("Expected <count>:<size_mb>, got '{}'", text))?;
+        let count: usize = count.parse().map_err(|_| format!("Invalid ring buffer file count '{}'", count))?;
+        let size: f64 = size.parse().map_err(|_| format!("Invalid ring buffer file size '{}'", size))?;
+        if count == 0 || size <= 0.0 {
+            return Err(format!("Ring buffer needs at least one file of non-zero size, got '{}'", text));
+        }
+        Ok((count, (size * 1024.0 * 1024.0) as usize))
+    }
+
+    fn write(&mut self, packet: &Packet) -> Result<(), Error> {
+        let record_size = pcap_writer::RECORD_HEADER_SIZE + packet.data.len();
+        // Rotate before a packet would push the file past the limit, but never leave a file without packets.
+        if self.writer.is_some() && self.size + record_size > self.size_limit && self.size > pcap_writer::FILE_HEADER_SIZE {
+            if let Some(mut writer) = self.writer.take() {
+                writer.flush()?;
+            }
+            self.index = (self.index + 1) % self.count;
+        }
+
+        if self.writer.is_none() {
+            let file = File::create(self.path(self.index))?;
+            self.writer = Some(PcapWriter::new(BufWriter::new(file), self.link_type)?);
+            self.size = pcap_writer::FILE_HEADER_SIZE;
+        }
+
+        let writer = self.writer.as_mut().unwrap();
+        self.size += writer.write_packet(packet.header, packet.data)?;
+        Ok(())
+    }
+}
+
+impl Drop for RingBuffer {
+    fn drop(&mut self) {
+        if let Some(writer) = &mut self.writer {
+            let _ = writer.flush();
+        }
+    }
+}
+
+
+impl<'a> Visitor<'a, ()> for RingBuffer {
+    fn visit_packet(&mut self, packet: &'a Packet) -> Result<(), Error> {
+        self.write(packet)?;
+        self.inner.visit_packet(packet)
+    }
+
+    /// The files are written with the Ethernet link type, so wireless and cooked frames aren't saved.
+    fn visit_radiotap_packet(&mut self, packet: &'a Packet) -> Result<(), Error> {
+        self.inner.visit_radiotap_packet(packet)
+    }
+
+    fn visit_sll2_packet(&mut self, packet: &'a Packet) -> Result<(), Error> {
+        self.inner.visit_sll2_packet(packet)
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        if let Some(writer) = &mut self.writer {
+            writer.flush()?;
+        }
+        self.inner.finish()
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use crate::raw::visit_frame;
+    use crate::testing::{self, Recorder};
+
+    #[test]
+    fn rotates_through_the_files() {
+        let directory = testing::temp_dir("ring-buffer");
+        let recorder = Recorder::default();
+        // Room for two 60 byte frames per file.
+        let size_limit = pcap_writer::FILE_HEADER_SIZE + 2 * (pcap_writer::RECORD_HEADER_SIZE + 60);
+        let mut ring = RingBuffer::new(Box::new(recorder.clone()), &directory, 1, 3, size_limit);
+
+        for i in 0..7u8 {
+            visit_frame(&mut ring, &[i; 60], Duration::from_secs(i as u64)).unwrap();
+        }
+        ring.finish().unwrap();
+
+        let files: Vec<_> = (0..4).map(|i| std::fs::read(ring.path(i)).ok().map(|x| testing::read_pcap(&x))).collect();
+        std::fs::remove_dir_all(&directory).unwrap();
+
+        // The seventh frame overwrote the first file.
+        assert_eq!(files[0], Some(vec![vec![6; 60]]));
+        assert_eq!(files[1], Some(vec![vec![2; 60], vec![3; 60]]));
+        assert_eq!(files[2], Some(vec![vec![4; 60], vec![5; 60]]));
+        assert_eq!(files[3], None);
+        assert_eq!(recorder.packets().len(), 7);
+    }
+
+    #[test]
+    fn parses_the_spec() {
+        assert_eq!(RingBuffer::parse_spec("4:0.5").unwrap(), (4, 512 * 1024));
+        assert!(RingBuffer::parse_spec("0:1").is_err());
+        assert!(RingBuffer::parse_spec("4").is_err());
+    }
+}
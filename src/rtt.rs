@@ -0,0 +1,200 @@
+use std::collections::{HashMap, VecDeque};
+use std::io::Error;
+use std::time::Duration;
+use crate::reassembly::{is_before, StreamKey};
+use crate::tcp::{self, Tcp};
+use crate::time_window;
+use crate::visitor::{Context, Visitor};
+
+
+/// What a segment's timestamp option revealed.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum TimestampEvent {
+    /// The segment echoed a TSval of the other direction, this long after it was captured.
+    Rtt(Duration),
+    /// The TSval went backwards, which PAWS (RFC 7323) drops as an old duplicate.
+    PawsViolation { previous: u32, current: u32 },
+}
+
+
+/// The timing of one direction of a connection.
+#[derive(Debug, Default, Copy, Clone, Eq, PartialEq)]
+pub struct FlowTiming {
+    /// Smoothed round trip time from the capture point, as in RFC 6298.
+    pub srtt: Option<Duration>,
+    pub samples: usize,
+    pub paws_violations: usize,
+}
+
+
+#[derive(Debug, Default)]
+struct Direction {
+    /// TSvals not yet echoed with the time they were first captured, oldest first.
+    sent: VecDeque<(u32, Duration)>,
+    last_tsval: Option<u32>,
+    timing: FlowTiming,
+}
+
+
+/// Estimates the round trip time of each direction of a TCP connection from the timestamp option: when one
+/// side echoes a TSval in its TSecr, the time since the TSval was captured is a sample.
+#[derive(Debug, Default)]
+pub struct TimestampTracker {
+    directions: HashMap<StreamKey, Direction>,
+}
+
+impl TimestampTracker {
+    /// Number of unechoed TSvals remembered per direction.
+    pub const MAX_SENT: usize = 64;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records the timestamp option of a segment captured at `time`, if it has one.
+    pub fn push(&mut self, key: StreamKey, tcp: &Tcp, time: Duration) -> Vec<TimestampEvent> {
+        let mut events = Vec::new();
+        let Some((tsval, tsecr)) = tcp.options().find_map(|option| match option {
+            tcp::Option::Timestamp { timestamp, echo } => Some((timestamp, echo)),
+            _ => None,
+        }) else { return events };
+
+        let direction = self.directions.entry(key).or_default();
+        match direction.last_tsval {
+            Some(previous) if is_before(tsval, previous) && tcp.rst() == 0 => {
+                direction.timing.paws_violations += 1;
+                events.push(TimestampEvent::PawsViolation { previous, current: tsval });
+            },
+            _ => direction.last_tsval = Some(tsval),
+        }
+        if !direction.sent.iter().any(|(sent, _)| *sent == tsval) {
+            direction.sent.push_back((tsval, time));
+            if direction.sent.len() > Self::MAX_SENT {
+                direction.sent.pop_front();
+            }
+        }
+
+        // TSecr is only valid on segments with ACK set.
+        if tcp.ack() == 0 { return events }
+        let Some(reverse) = self.directions.get_mut(&key.reversed()) else { return events };
+        if let Some(index) = reverse.sent.iter().position(|(sent, _)| *sent == tsecr) {
+            let sample = time.saturating_sub(reverse.sent[index].1);
+            // Older TSvals can only be echoed by retransmissions, which would give too large samples.
+            reverse.sent.drain(..=index);
+
+            let timing = &mut reverse.timing;
+            timing.srtt = Some(match timing.srtt {
+                Some(srtt) => srtt * 7 / 8 + sample / 8,
+                None => sample,
+            });
+            timing.samples += 1;
+            events.push(TimestampEvent::Rtt(sample));
+        }
+        events
+    }
+
+    pub fn timing(&self, key: &StreamKey) -> FlowTiming {
+        self.directions.get(key).map(|x| x.timing).unwrap_or_default()
+    }
+
+    /// The directions that sent timestamps, in address order.
+    pub fn report(&self) -> Vec<(StreamKey, FlowTiming)> {
+        let mut report: Vec<(StreamKey, FlowTiming)> = self.directions.iter()
+            .map(|(key, direction)| (*key, direction.timing))
+            .collect();
+        report.sort_by_key(|(key, _)| (key.source, key.source_port, key.destination, key.destination_port));
+        report
+    }
+}
+
+
+/// Prints PAWS violations as they happen and the smoothed round trip time of each direction at the end.
+pub struct RttVisitor {
+    tracker: TimestampTracker,
+}
+
+impl RttVisitor {
+    pub fn new() -> Self {
+        Self { tracker: TimestampTracker::new() }
+    }
+}
+
+impl Default for RttVisitor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+impl<'a> Visitor<'a, ()> for RttVisitor {
+    fn visit_tcp(&mut self, packet: &Tcp<'a>, context: &Context<'a>) -> Result<(), Error> {
+        let (Some(ip), Some(header)) = (&context.ipv4, context.header) else { return Ok(()) };
+        let key = StreamKey::new(ip, packet);
+        let time = time_window::timestamp(header);
+
+        for event in self.tracker.push(key, packet, time) {
+            if let TimestampEvent::PawsViolation { previous, current } = event {
+                println!("[WARNING] {}.{:06} {}: PAWS violation, TSval {} after {}", time.as_secs(), time.subsec_micros(), key, current, previous);
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        for (key, timing) in self.tracker.report() {
+            let srtt = match timing.srtt {
+                Some(srtt) => format!("{:.3} ms", srtt.as_secs_f64() * 1000.0),
+                None       => "unknown".to_string(),
+            };
+            println!("{}: srtt {} over {} samples, {} PAWS violations", key, srtt, timing.samples, timing.paws_violations);
+        }
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::{TcpBuilder, TCP_ACK};
+    use crate::testing::{self, ip};
+
+    fn client() -> StreamKey {
+        StreamKey { source: ip(10, 0, 0, 1), source_port: 51000, destination: ip(10, 0, 0, 2), destination_port: 80 }
+    }
+
+    /// Pushes an ACK with a timestamp option from the client or the server, captured at `millis`.
+    fn push(tracker: &mut TimestampTracker, from_client: bool, tsval: u32, tsecr: u32, millis: u64) -> Vec<TimestampEvent> {
+        let (key, ports) = if from_client { (client(), (51000, 80)) } else { (client().reversed(), (80, 51000)) };
+        let mut option = Vec::from([1, 1, 8, 10]);
+        option.extend_from_slice(&tsval.to_be_bytes());
+        option.extend_from_slice(&tsecr.to_be_bytes());
+        let data = testing::tcp_with_options(&TcpBuilder::new(ports.0, ports.1).with_flags(TCP_ACK), &option);
+        tracker.push(key, &Tcp::from_bytes(&data).unwrap(), Duration::from_millis(millis))
+    }
+
+    #[test]
+    fn measures_the_rtt_of_echoed_timestamps() {
+        let mut tracker = TimestampTracker::new();
+        assert_eq!(push(&mut tracker, true,  100, 0,   0),   []);
+        assert_eq!(push(&mut tracker, false, 500, 100, 20),  [TimestampEvent::Rtt(Duration::from_millis(20))]);
+        assert_eq!(push(&mut tracker, true,  101, 500, 25),  [TimestampEvent::Rtt(Duration::from_millis(5))]);
+        assert_eq!(push(&mut tracker, true,  102, 500, 100), []);
+        assert_eq!(push(&mut tracker, false, 501, 102, 140), [TimestampEvent::Rtt(Duration::from_millis(40))]);
+
+        let timing = tracker.timing(&client());
+        assert_eq!(timing.samples, 2);
+        // 7/8 of the first sample and 1/8 of the second.
+        assert_eq!(timing.srtt, Some(Duration::from_micros(22_500)));
+        assert_eq!(tracker.timing(&client().reversed()).srtt, Some(Duration::from_millis(5)));
+    }
+
+    #[test]
+    fn flags_a_tsval_going_backwards() {
+        let mut tracker = TimestampTracker::new();
+        push(&mut tracker, true, 1000, 0, 0);
+        assert_eq!(push(&mut tracker, true, 900, 0, 10), [TimestampEvent::PawsViolation { previous: 1000, current: 900 }]);
+        assert_eq!(push(&mut tracker, true, 1001, 0, 20), []);
+        assert_eq!(tracker.timing(&client()).paws_violations, 1);
+    }
+}
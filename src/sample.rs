@@ -0,0 +1,163 @@
+use std::io::Error;
+use pcap::Packet;
+use crate::visitor::Visitor;
+
+
+/// Which packets to keep, one in `one_in`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Sampling {
+    /// The first packet and every `one_in`th after it.
+    Every { one_in: u64 },
+    /// Each packet with probability 1/`one_in`, from a generator seeded with `seed` so runs are reproducible.
+    Random { one_in: u64, seed: u64 },
+}
+
+impl Sampling {
+    /// Parses `1/N` as every Nth packet.
+    pub fn parse_rate(text: &str) -> Result<u64, String> {
+        let one_in = text.strip_prefix("1/").and_then(|x| x.parse::<u64>().ok());
+        match one_in {
+            Some(one_in) if one_in > 0 => Ok(one_in),
+            _ => Err(format!("Invalid sample rate '{}', expected 1/N with N at least 1", text)),
+        }
+    }
+}
+
+
+/// Decides which packets are sampled.
+#[derive(Debug)]
+pub struct Sampler {
+    sampling: Sampling,
+    state: u64,
+    seen: u64,
+    sampled: u64,
+}
+
+impl Sampler {
+    pub fn new(sampling: Sampling) -> Self {
+        let state = match sampling {
+            Sampling::Every { .. } => 0,
+            Sampling::Random { seed, .. } => seed,
+        };
+        Self { sampling, state, seen: 0, sampled: 0 }
+    }
+
+    /// Counts a packet and returns whether it's sampled.
+    pub fn sample(&mut self) -> bool {
+        self.seen += 1;
+        let sampled = match self.sampling {
+            Sampling::Every { one_in } => (self.seen - 1).is_multiple_of(one_in),
+            Sampling::Random { one_in, .. } => self.next_random().is_multiple_of(one_in),
+        };
+        if sampled {
+            self.sampled += 1;
+        }
+        sampled
+    }
+
+    pub fn seen(&self) -> u64 {
+        self.seen
+    }
+
+    pub fn sampled(&self) -> u64 {
+        self.sampled
+    }
+
+    /// SplitMix64, small and good enough to pick packets.
+    fn next_random(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+}
+
+
+/// Passes only the sampled packets on to the inner visitor, to get a statistical view of a high-rate capture
+/// without decoding everything.
+pub struct SampleVisitor {
+    inner: Box<dyn for<'a> Visitor<'a, ()>>,
+    sampler: Sampler,
+}
+
+impl SampleVisitor {
+    pub fn new(inner: Box<dyn for<'a> Visitor<'a, ()>>, sampling: Sampling) -> Self {
+        Self { inner, sampler: Sampler::new(sampling) }
+    }
+}
+
+
+impl<'a> Visitor<'a, ()> for SampleVisitor {
+    fn visit_packet(&mut self, packet: &'a Packet) -> Result<(), Error> {
+        if !self.sampler.sample() {
+            return Ok(());
+        }
+        self.inner.visit_packet(packet)
+    }
+
+    fn visit_radiotap_packet(&mut self, packet: &'a Packet) -> Result<(), Error> {
+        if !self.sampler.sample() {
+            return Ok(());
+        }
+        self.inner.visit_radiotap_packet(packet)
+    }
+
+    fn visit_sll2_packet(&mut self, packet: &'a Packet) -> Result<(), Error> {
+        if !self.sampler.sample() {
+            return Ok(());
+        }
+        self.inner.visit_sll2_packet(packet)
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        self.inner.finish()?;
+        println!("Sampled {} of {} packets", self.sampler.sampled(), self.sampler.seen());
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use crate::raw::visit_frame;
+    use crate::testing::{self, ip, Recorder};
+
+    fn frame(port: u16) -> Vec<u8> {
+        testing::udp_frame(ip(10, 0, 0, 1), port, ip(10, 0, 0, 2), 53, b"query")
+    }
+
+    #[test]
+    fn forwards_one_in_three_packets() {
+        let recorder = Recorder::default();
+        let mut visitor = SampleVisitor::new(Box::new(recorder.clone()), Sampling::Every { one_in: Sampling::parse_rate("1/3").unwrap() });
+        for port in 0..9 {
+            visit_frame(&mut visitor, &frame(port), Duration::ZERO).unwrap();
+        }
+        assert_eq!(recorder.packets(), [frame(0), frame(3), frame(6)]);
+        assert_eq!((visitor.sampler.sampled(), visitor.sampler.seen()), (3, 9));
+    }
+
+    #[test]
+    fn samples_randomly_but_reproducibly() {
+        let picks = |seed| {
+            let mut sampler = Sampler::new(Sampling::Random { one_in: 4, seed });
+            (0..10_000).map(|_| sampler.sample()).collect::<Vec<_>>()
+        };
+        assert_eq!(picks(7), picks(7));
+        assert_ne!(picks(7), picks(8));
+        let sampled = picks(7).iter().filter(|x| **x).count();
+        assert!((2_300..2_700).contains(&sampled), "{}", sampled);
+    }
+
+    #[test]
+    fn parses_a_rate() {
+        assert_eq!(Sampling::parse_rate("1/1"), Ok(1));
+        assert_eq!(Sampling::parse_rate("1/100"), Ok(100));
+        for text in ["1/0", "2/3", "3", "1/x"] {
+            assert!(Sampling::parse_rate(text).is_err(), "{}", text);
+        }
+    }
+}
@@ -0,0 +1,194 @@
+use std::collections::{HashMap, VecDeque};
+use std::io::Error;
+use std::time::Duration;
+use crate::ipv4::Ipv4Address;
+use crate::reassembly::StreamKey;
+use crate::tcp::Tcp;
+use crate::time_window;
+use crate::visitor::{Context, Visitor};
+
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Thresholds {
+    /// Distinct destination ports a source may send SYNs to within `window`.
+    pub ports: usize,
+    /// SYNs a source may leave without a completed handshake within `window`.
+    pub half_open: usize,
+    pub window: Duration,
+}
+
+impl Default for Thresholds {
+    fn default() -> Self {
+        Self { ports: 100, half_open: 200, window: Duration::from_secs(10) }
+    }
+}
+
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Alert {
+    PortScan { source: Ipv4Address, ports: usize },
+    SynFlood { source: Ipv4Address, half_open: usize },
+}
+
+impl std::fmt::Display for Alert {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Alert::PortScan { source, ports }     => write!(f, "Port scan from {:?}: SYNs to {} distinct ports", source, ports),
+            Alert::SynFlood { source, half_open } => write!(f, "SYN flood from {:?}: {} handshakes not completed", source, half_open),
+        }
+    }
+}
+
+
+/// The SYNs one source sent within the window.
+#[derive(Debug, Default)]
+struct Source {
+    syns: VecDeque<(Duration, StreamKey)>,
+    /// Number of SYNs in the window per destination port.
+    ports: HashMap<u16, usize>,
+    /// Connections in the window whose handshake isn't completed, with their number of SYNs in the window.
+    half_open: HashMap<StreamKey, usize>,
+    scanning: bool,
+    flooding: bool,
+}
+
+
+/// Watches the SYNs of each source IP over a sliding window for port scans, many distinct destination ports,
+/// and SYN floods, many handshakes that the source never completes. Each alert fires once when a threshold is
+/// crossed and again only after the source dropped below it.
+#[derive(Debug)]
+pub struct ScanDetector {
+    thresholds: Thresholds,
+    sources: HashMap<Ipv4Address, Source>,
+}
+
+impl ScanDetector {
+    pub fn new(thresholds: Thresholds) -> Self {
+        Self { thresholds, sources: HashMap::new() }
+    }
+
+    /// Records a segment captured at `time` and returns the alerts it triggered.
+    pub fn push(&mut self, key: StreamKey, tcp: &Tcp, time: Duration) -> Vec<Alert> {
+        let mut alerts = Vec::new();
+        if tcp.syn() == 0 && tcp.ack() != 0 {
+            // The ACK completing the handshake comes from the source of the SYN.
+            if let Some(source) = self.sources.get_mut(&key.source) {
+                source.half_open.remove(&key);
+            }
+        }
+        if tcp.syn() == 0 || tcp.ack() != 0 {
+            return alerts;
+        }
+
+        let source = self.sources.entry(key.source).or_default();
+        source.syns.push_back((time, key));
+        *source.ports.entry(key.destination_port).or_insert(0) += 1;
+        *source.half_open.entry(key).or_insert(0) += 1;
+
+        while source.syns.front().is_some_and(|(sent, _)| time.saturating_sub(*sent) > self.thresholds.window) {
+            let (_, old) = source.syns.pop_front().unwrap();
+            if let Some(count) = source.ports.get_mut(&old.destination_port) {
+                *count -= 1;
+                if *count == 0 { source.ports.remove(&old.destination_port); }
+            }
+            // A retransmitted SYN keeps the connection in the window.
+            if let Some(count) = source.half_open.get_mut(&old) {
+                *count -= 1;
+                if *count == 0 { source.half_open.remove(&old); }
+            }
+        }
+
+        let scanning = source.ports.len() >= self.thresholds.ports;
+        if scanning && !source.scanning {
+            alerts.push(Alert::PortScan { source: key.source, ports: source.ports.len() });
+        }
+        source.scanning = scanning;
+
+        let flooding = source.half_open.len() >= self.thresholds.half_open;
+        if flooding && !source.flooding {
+            alerts.push(Alert::SynFlood { source: key.source, half_open: source.half_open.len() });
+        }
+        source.flooding = flooding;
+
+        alerts
+    }
+}
+
+
+/// Prints port scan and SYN flood alerts as they happen.
+pub struct ScanVisitor {
+    detector: ScanDetector,
+    alerts: usize,
+}
+
+impl ScanVisitor {
+    pub fn new(thresholds: Thresholds) -> Self {
+        Self { detector: ScanDetector::new(thresholds), alerts: 0 }
+    }
+}
+
+
+impl<'a> Visitor<'a, ()> for ScanVisitor {
+    fn visit_tcp(&mut self, packet: &Tcp<'a>, context: &Context<'a>) -> Result<(), Error> {
+        let (Some(ip), Some(header)) = (&context.ipv4, context.header) else { return Ok(()) };
+        let time = time_window::timestamp(header);
+
+        for alert in self.detector.push(StreamKey::new(ip, packet), packet, time) {
+            self.alerts += 1;
+            println!("[ALERT] {}.{:06} {}", time.as_secs(), time.subsec_micros(), alert);
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        println!("{} alerts", self.alerts);
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::{TcpBuilder, TCP_ACK, TCP_SYN};
+    use crate::testing::ip;
+
+    fn key(source_port: u16, destination_port: u16) -> StreamKey {
+        StreamKey { source: ip(10, 0, 0, 1), source_port, destination: ip(10, 0, 0, 2), destination_port }
+    }
+
+    /// The alerts of a segment from 10.0.0.1 with `flags` captured at `millis`.
+    fn push(detector: &mut ScanDetector, key: StreamKey, flags: u8, millis: u64) -> Vec<Alert> {
+        let segment = TcpBuilder::new(key.source_port, key.destination_port).with_flags(flags).build(key.source, key.destination);
+        detector.push(key, &Tcp::from_bytes(&segment).unwrap(), Duration::from_millis(millis))
+    }
+
+    #[test]
+    fn alerts_once_about_syns_to_many_ports() {
+        let mut detector = ScanDetector::new(Thresholds { ports: 20, half_open: 1000, window: Duration::from_secs(1) });
+        let mut alerts = Vec::new();
+        for port in 1..=50 {
+            alerts.extend(push(&mut detector, key(40000, port), TCP_SYN, port as u64));
+        }
+        assert_eq!(alerts, [Alert::PortScan { source: ip(10, 0, 0, 1), ports: 20 }]);
+
+        // Once the window slid past the scan the source may scan again.
+        assert_eq!(push(&mut detector, key(40000, 1), TCP_SYN, 5000), []);
+        let alerts: Vec<_> = (2..=20).flat_map(|port| push(&mut detector, key(40000, port), TCP_SYN, 5000)).collect();
+        assert_eq!(alerts, [Alert::PortScan { source: ip(10, 0, 0, 1), ports: 20 }]);
+    }
+
+    #[test]
+    fn alerts_about_handshakes_that_are_never_completed() {
+        let mut detector = ScanDetector::new(Thresholds { ports: 1000, half_open: 10, window: Duration::from_secs(1) });
+        // Completed handshakes don't count.
+        for port in 40000..40020 {
+            assert_eq!(push(&mut detector, key(port, 80), TCP_SYN, 0), []);
+            assert_eq!(push(&mut detector, key(port, 80), TCP_ACK, 1), []);
+        }
+
+        let alerts: Vec<_> = (50000..50020).flat_map(|port| push(&mut detector, key(port, 80), TCP_SYN, 2)).collect();
+        assert_eq!(alerts, [Alert::SynFlood { source: ip(10, 0, 0, 1), half_open: 10 }]);
+        assert_eq!(alerts[0].to_string(), "SYN flood from 10.0.0.1: 10 handshakes not completed");
+    }
+}
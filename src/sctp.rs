@@ -0,0 +1,206 @@
+/*
+https://www.rfc-editor.org/rfc/rfc9260
+*/
+
+use alloc::vec::Vec;
+use core::ops::Range;
+use crate::shared::{bytes_of_bits, crc32c};
+use crate::error::ParseError;
+use crate::endian::{be_u16, be_u32, le_u32};
+
+
+pub const CHUNK_DATA     : u8 = 0;
+pub const CHUNK_INIT     : u8 = 1;
+pub const CHUNK_INIT_ACK : u8 = 2;
+pub const CHUNK_SACK     : u8 = 3;
+pub const CHUNK_ABORT    : u8 = 6;
+pub const CHUNK_SHUTDOWN : u8 = 7;
+
+
+#[derive(Debug)]
+pub enum ChunkValue<'a> {
+    Data { unordered: bool, beginning: bool, ending: bool, tsn: u32, stream_identifier: u16, stream_sequence_number: u16, payload_protocol_identifier: u32, user_data: &'a [u8] },
+    Init { initiate_tag: u32, advertised_receiver_window_credit: u32, outbound_streams: u16, inbound_streams: u16, initial_tsn: u32 },
+    Other(&'a [u8]),
+}
+
+
+#[derive(Debug)]
+pub struct Chunk<'a> {
+    pub kind: u8,
+    pub flags: u8,
+    /// Length of the chunk including its header, excluding the padding.
+    pub length: u16,
+    pub value: ChunkValue<'a>,
+}
+
+
+pub struct ChunkIter<'a> {
+    data: &'a [u8],
+    index: usize,
+}
+
+impl<'a> Iterator for ChunkIter<'a> {
+    type Item = Chunk<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let header = self.data.get(self.index..self.index+4)?;
+        let kind   = header[0];
+        let flags  = header[1];
+        let length = be_u16(header, 2);
+        if length < 4 { return None }
+
+        let value = self.data.get(self.index+4..self.index+length as usize)?;
+        // Chunks are padded to a multiple of four bytes.
+        self.index += (length as usize + 3) & !3;
+
+        let value = match kind {
+            CHUNK_DATA if value.len() >= 12 => ChunkValue::Data {
+                unordered: flags & 0b100 != 0,
+                beginning: flags & 0b010 != 0,
+                ending:    flags & 0b001 != 0,
+                tsn:                         be_u32(value, 0),
+                stream_identifier:           be_u16(value, 4),
+                stream_sequence_number:      be_u16(value, 6),
+                payload_protocol_identifier: be_u32(value, 8),
+                user_data:                   &value[12..],
+            },
+            CHUNK_INIT | CHUNK_INIT_ACK if value.len() >= 16 => ChunkValue::Init {
+                initiate_tag:                      be_u32(value, 0),
+                advertised_receiver_window_credit: be_u32(value, 4),
+                outbound_streams:                  be_u16(value, 8),
+                inbound_streams:                   be_u16(value, 10),
+                initial_tsn:                       be_u32(value, 12),
+            },
+            _ => ChunkValue::Other(value),
+        };
+
+        Some(Chunk { kind, flags, length, value })
+    }
+}
+
+
+#[derive(Clone)]
+pub struct Sctp<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Sctp<'a> {
+    pub const HEADER_SIZE: usize = 12;
+
+    pub const SOURCE_PORT_BITS:       Range<usize> = 0..16;
+    pub const DESTINATION_PORT_BITS:  Range<usize> = 16..32;
+    pub const VERIFICATION_TAG_BITS:  Range<usize> = 32..64;
+    pub const CHECK_SUM_BITS:         Range<usize> = 64..96;
+
+    pub fn source_port(&self)      -> u16 { be_u16(self.data, 0) }
+    pub fn destination_port(&self) -> u16 { be_u16(self.data, 2) }
+    pub fn verification_tag(&self) -> u32 { be_u32(self.data, 4) }
+
+    /// The CRC-32C is stored least significant byte first.
+    pub fn check_sum(&self) -> u32 { le_u32(self.data, 8) }
+
+    /// Verifies the CRC-32C over the whole packet, computed with the checksum field set to zero.
+    pub fn verify_checksum(&self) -> bool {
+        let mut data = self.data.to_vec();
+        data[8..12].fill(0);
+        crc32c(&data) == self.check_sum()
+    }
+
+    /// The bytes holding the field `name`, as named by its accessor, or `None` for unknown names.
+    pub fn field_bytes(&self, name: &str) -> Option<&'a [u8]> {
+        let bits = match name {
+            "source_port"      => Self::SOURCE_PORT_BITS,
+            "destination_port" => Self::DESTINATION_PORT_BITS,
+            "verification_tag" => Self::VERIFICATION_TAG_BITS,
+            "check_sum"        => Self::CHECK_SUM_BITS,
+            _ => return None,
+        };
+        Some(bytes_of_bits(self.data, bits))
+    }
+
+    pub fn raw_payload(&self) -> &'a [u8] {
+        &self.data[Self::HEADER_SIZE..]
+    }
+
+    pub fn chunks(&self) -> ChunkIter<'a> {
+        ChunkIter { data: self.raw_payload(), index: 0 }
+    }
+
+    pub fn from_bytes(data: &'a [u8]) -> Result<Self, ParseError> {
+        if data.len() < Self::HEADER_SIZE {
+            return Err(ParseError::TooSmall { layer: "Sctp", field: "data", expected: Self::HEADER_SIZE, got: data.len() });
+        }
+
+        let me = Self { data };
+        Ok(me)
+    }
+}
+
+
+impl<'a> core::fmt::Debug for Sctp<'a> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "    Sctp\n")?;
+        write!(f, "        source_port:      {:?}\n", self.source_port())?;
+        write!(f, "        destination_port: {:?}\n", self.destination_port())?;
+        write!(f, "        verification_tag: {:?}\n", self.verification_tag())?;
+        write!(f, "        check_sum:        {:?}\n", self.check_sum())?;
+        for (i, chunk) in self.chunks().enumerate() {
+            write!(f, "        chunk[{}]:         {:?}\n", i, chunk)?;
+        }
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::Ipv4Builder;
+    use crate::ipv4::{self, IPv4, Protocol};
+    use crate::testing::ip;
+
+    /// An SCTP packet from port 5000 to 3868 holding an INIT chunk, with a valid checksum.
+    fn init() -> Vec<u8> {
+        let mut data = Vec::from([0x13, 0x88, 0x0F, 0x1C, 0, 0, 0, 0, 0, 0, 0, 0]);
+        data.extend_from_slice(&[CHUNK_INIT, 0, 0, 20]);
+        data.extend_from_slice(&0x1234_5678u32.to_be_bytes());
+        data.extend_from_slice(&65536u32.to_be_bytes());
+        data.extend_from_slice(&[0, 10, 0, 5]);
+        data.extend_from_slice(&1000u32.to_be_bytes());
+        let crc = crc32c(&data);
+        data[8..12].copy_from_slice(&crc.to_le_bytes());
+        data
+    }
+
+    #[test]
+    fn parses_an_init_chunk() {
+        let packet = Ipv4Builder::new(ip(10, 0, 0, 1), ip(10, 0, 0, 2)).with_payload(Protocol::SCTP, &init()).build();
+        let packet = IPv4::from_bytes(&packet).unwrap();
+        let Ok(ipv4::Payload::Sctp(sctp)) = packet.payload() else { panic!("not an SCTP packet") };
+
+        assert_eq!(sctp.source_port(), 5000);
+        assert_eq!(sctp.destination_port(), 3868);
+        assert_eq!(sctp.verification_tag(), 0);
+        assert!(sctp.verify_checksum());
+
+        let chunks: Vec<_> = sctp.chunks().collect();
+        assert_eq!(chunks.len(), 1);
+        assert_eq!((chunks[0].kind, chunks[0].length), (CHUNK_INIT, 20));
+        assert!(matches!(chunks[0].value, ChunkValue::Init {
+            initiate_tag: 0x1234_5678, advertised_receiver_window_credit: 65536, outbound_streams: 10, inbound_streams: 5, initial_tsn: 1000,
+        }));
+    }
+
+    #[test]
+    fn rejects_a_corrupted_checksum() {
+        let mut data = init();
+        data[20] ^= 1;
+        assert!(!Sctp::from_bytes(&data).unwrap().verify_checksum());
+    }
+
+    #[test]
+    fn computes_the_crc32c_check_value() {
+        assert_eq!(crc32c(b"123456789"), 0xE306_9283);
+    }
+}
@@ -1,5 +1,6 @@
-use std::io::{Error, ErrorKind};
-use std::io::ErrorKind::Other;
+use core::ops::Range;
+use alloc::{format, string::String};
+use crate::error::ParseError;
 
 
 #[allow(non_upper_case_globals)]
@@ -11,9 +12,12 @@ pub const NETWORK_PROTOCOL_ARP  : u16 = 0x0806;
 pub const TRANSPORT_PROTOCOL_UDP : u8 = 0x11;
 pub const TRANSPORT_PROTOCOL_TCP : u8 = 0x06;
 
+/// Maximum number of nested tunnels (GRE, ...) that are decoded before giving up.
+pub const MAX_ENCAPSULATION_DEPTH : usize = 8;
 
 
-#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq)]
+
+#[derive(Copy, Clone, Ord, PartialOrd, Eq, PartialEq, Hash)]
 pub struct MacAddress {
     data: [u8; 6]
 }
@@ -25,20 +29,136 @@ impl MacAddress {
         Self { data: *array }
     }
 
-    pub fn from_bytes(data: &[u8]) -> Result<Self, Error> {
+    pub fn from_bytes(data: &[u8]) -> Result<Self, ParseError> {
         if data.len() < 6 {
-            return Err(Error::new(Other, format!("MacAddress needs 6 bytes, got {}", data.len())))
+            return Err(ParseError::TooSmall { layer: "MacAddress", field: "data", expected: 6, got: data.len() })
         } else {
             let mut x: [u8; 6] = [0; 6];
             x.copy_from_slice(&data[0..6]);
             Ok(Self { data: x })
         }
     }
+
+    pub fn bytes(&self) -> &[u8; 6] {
+        &self.data
+    }
 }
 
 
-impl std::fmt::Debug for MacAddress {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+impl core::str::FromStr for MacAddress {
+    type Err = String;
+
+    /// Parses six hexadecimal bytes separated by colons, e.g. `00:1b:21:3a:4f:5e`.
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let mut data = [0; 6];
+        let mut parts = text.split(':');
+        for byte in data.iter_mut() {
+            let part = parts.next().filter(|x| x.len() == 2).ok_or(format!("Invalid MAC address '{}'", text))?;
+            *byte = u8::from_str_radix(part, 16).map_err(|_| format!("Invalid MAC address '{}'", text))?;
+        }
+        if parts.next().is_some() {
+            return Err(format!("Invalid MAC address '{}'", text));
+        }
+        Ok(Self { data })
+    }
+}
+
+
+impl core::fmt::Debug for MacAddress {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
         write!(f, "{}:{}:{}:{}:{}:{}", self.data[0], self.data[1], self.data[2], self.data[3], self.data[4], self.data[5])
     }
-}
\ No newline at end of file
+}
+
+/// The bytes of `data` that hold the bit range `bits`, e.g. the whole first byte for the 4 bits at 0..4.
+pub fn bytes_of_bits(data: &[u8], bits: Range<usize>) -> &[u8] {
+    &data[bits.start / 8..bits.end.div_ceil(8)]
+}
+
+/// Adds `data` as big endian 16-bit words to a running one's complement sum (RFC 1071).
+/// An odd trailing byte is padded with zero.
+pub fn checksum_add(mut sum: u32, data: &[u8]) -> u32 {
+    let mut chunks = data.chunks_exact(2);
+    for word in &mut chunks {
+        sum += (word[0] as u32) << 8 | word[1] as u32;
+    }
+    if let [last] = chunks.remainder() {
+        sum += (*last as u32) << 8;
+    }
+    sum
+}
+
+/// Folds the carries of a running sum and returns its one's complement.
+/// Summing over data that includes a valid checksum yields 0.
+pub fn checksum_finish(mut sum: u32) -> u16 {
+    while sum > 0xFFFF {
+        sum = (sum & 0xFFFF) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+/// Updates `checksum` for covered bytes that changed from `old` to `new`, both starting at an even offset,
+/// without summing the rest of the data again (RFC 1624). A checksum that was wrong stays wrong.
+pub fn checksum_adjust(checksum: u16, old: &[u8], new: &[u8]) -> u16 {
+    let mut sum = !checksum as u32;
+    for (old, new) in old.chunks(2).zip(new.chunks(2)) {
+        let old = (old[0] as u32) << 8 | old.get(1).copied().unwrap_or(0) as u32;
+        sum += !old & 0xFFFF;
+        sum = checksum_add(sum, new);
+    }
+    checksum_finish(sum)
+}
+
+
+const fn crc32_table(polynomial: u32) -> [u32; 256] {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ polynomial } else { crc >> 1 };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+}
+
+static CRC32_TABLE:  [u32; 256] = crc32_table(0xEDB8_8320);
+static CRC32C_TABLE: [u32; 256] = crc32_table(0x82F6_3B78);
+
+fn crc32_with_table(table: &[u32; 256], data: &[u8]) -> u32 {
+    let mut crc = 0xFFFF_FFFF;
+    for byte in data {
+        crc = table[((crc ^ *byte as u32) & 0xFF) as usize] ^ (crc >> 8);
+    }
+    !crc
+}
+
+/// CRC-32 as used by the Ethernet frame check sequence (IEEE 802.3).
+pub fn crc32(data: &[u8]) -> u32 {
+    crc32_with_table(&CRC32_TABLE, data)
+}
+
+/// CRC-32C (Castagnoli) as used by the SCTP checksum.
+pub fn crc32c(data: &[u8]) -> u32 {
+    crc32_with_table(&CRC32C_TABLE, data)
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn covers_a_bit_range_with_whole_bytes() {
+        let data = [0x45, 0x00, 0x00, 0x28, 0x12, 0x34];
+        assert_eq!(bytes_of_bits(&data, 0..4), [0x45]);
+        assert_eq!(bytes_of_bits(&data, 4..8), [0x45]);
+        assert_eq!(bytes_of_bits(&data, 16..32), [0x00, 0x28]);
+        assert_eq!(bytes_of_bits(&data, 35..48), [0x12, 0x34]);
+        assert_eq!(bytes_of_bits(&data, 7..9), [0x45, 0x00]);
+    }
+}
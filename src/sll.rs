@@ -0,0 +1,143 @@
+/*
+https://www.tcpdump.org/linktypes/LINKTYPE_LINUX_SLL2.html
+*/
+
+use crate::ethernet::EtherType;
+use crate::ipv4;
+use crate::ipv6;
+use crate::error::ParseError;
+use crate::endian::{be_u16, be_u32};
+
+
+pub const PACKET_HOST     : u8 = 0;
+pub const PACKET_BROADCAST: u8 = 1;
+pub const PACKET_MULTICAST: u8 = 2;
+pub const PACKET_OTHERHOST: u8 = 3;
+pub const PACKET_OUTGOING : u8 = 4;
+
+/// Who a packet was addressed to, as the kernel saw it.
+pub fn packet_type_name(kind: u8) -> &'static str {
+    match kind {
+        PACKET_HOST      => "Unicast to us",
+        PACKET_BROADCAST => "Broadcast",
+        PACKET_MULTICAST => "Multicast",
+        PACKET_OTHERHOST => "Unicast to another host",
+        PACKET_OUTGOING  => "Sent by us",
+        _ => "Unknown",
+    }
+}
+
+
+#[derive(Debug)]
+pub enum Payload<'a> {
+    IPv4(ipv4::IPv4<'a>),
+    IPv6(ipv6::IPv6<'a>),
+    /// ARP and other protocols, by their EtherType.
+    Unknown(&'a [u8]),
+}
+
+
+/// The Linux cooked capture v2 header (link type 276), which captures on the `any` pseudo-interface put in
+/// front of each packet instead of the link layer header.
+#[derive(Clone)]
+pub struct Sll2<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Sll2<'a> {
+    pub const HEADER_SIZE: usize = 20;
+    pub const MAX_ADDRESS_SIZE: usize = 8;
+
+    /// EtherType of the payload.
+    pub fn protocol_type(&self)   -> u16 { be_u16(self.data, 0) }
+    pub fn reserved(&self)        -> u16 { be_u16(self.data, 2) }
+    /// Index of the interface the packet was captured on, as in `ip link`.
+    pub fn interface_index(&self) -> u32 { be_u32(self.data, 4) }
+    /// The ARPHRD_ type of the interface, e.g. 1 for Ethernet.
+    pub fn arphrd_type(&self)     -> u16 { be_u16(self.data, 8) }
+    pub fn packet_type(&self)     -> u8  { self.data[10] }
+
+    /// The link layer source address, e.g. a MAC address.
+    pub fn address(&self) -> &'a [u8] {
+        let length = (self.data[11] as usize).min(Self::MAX_ADDRESS_SIZE);
+        &self.data[12..12 + length]
+    }
+
+    pub fn raw_payload(&self) -> &'a [u8] {
+        &self.data[Self::HEADER_SIZE..]
+    }
+
+    pub fn payload(&self) -> Result<Payload<'a>, ParseError> {
+        match EtherType::from_code(self.protocol_type()) {
+            EtherType::IPv4 => Ok(Payload::IPv4(ipv4::IPv4::from_bytes(self.raw_payload())?)),
+            EtherType::IPv6 => Ok(Payload::IPv6(ipv6::IPv6::from_bytes(self.raw_payload())?)),
+            _ => Ok(Payload::Unknown(self.raw_payload())),
+        }
+    }
+
+    pub fn from_bytes(data: &'a [u8]) -> Result<Self, ParseError> {
+        if data.len() < Self::HEADER_SIZE {
+            return Err(ParseError::TooSmall { layer: "Sll2", field: "data", expected: Self::HEADER_SIZE, got: data.len() });
+        }
+        Ok(Self { data })
+    }
+}
+
+
+impl<'a> core::fmt::Debug for Sll2<'a> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "Sll2\n")?;
+        write!(f, "    protocol_type:   {:#06x}\n", self.protocol_type())?;
+        write!(f, "    interface_index: {:?}\n",   self.interface_index())?;
+        write!(f, "    arphrd_type:     {:?}\n",   self.arphrd_type())?;
+        write!(f, "    packet_type:     {:?}\n",   self.packet_type())?;
+        write!(f, "    address:         {:02x?}\n", self.address())?;
+        write!(f, "    payload:         {:?}\n",   self.payload())?;
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+    use crate::builder::{Ipv4Builder, TcpBuilder, TCP_SYN};
+    use crate::testing::{self, ip};
+
+    /// `payload` of EtherType `protocol` captured on interface 3, sent by us from `testing::MAC_A`.
+    fn sll2(protocol: u16, payload: &[u8]) -> Vec<u8> {
+        let mut data = Vec::new();
+        data.extend_from_slice(&protocol.to_be_bytes());
+        data.extend_from_slice(&[0, 0, 0, 0, 0, 3, 0, 1, PACKET_OUTGOING, 6]);
+        data.extend_from_slice(&testing::MAC_A);
+        data.extend_from_slice(&[0, 0]);
+        data.extend_from_slice(payload);
+        data
+    }
+
+    #[test]
+    fn decodes_the_header_and_the_ipv4_packet() {
+        let packet = Ipv4Builder::new(ip(10, 0, 0, 1), ip(10, 0, 0, 2)).with_tcp(&TcpBuilder::new(51000, 443).with_flags(TCP_SYN)).build();
+        let data = sll2(testing::ETHER_TYPE_IPV4, &packet);
+        let sll = Sll2::from_bytes(&data).unwrap();
+
+        assert_eq!(sll.protocol_type(), 0x0800);
+        assert_eq!(sll.interface_index(), 3);
+        assert_eq!(sll.arphrd_type(), 1);
+        assert_eq!(packet_type_name(sll.packet_type()), "Sent by us");
+        assert_eq!(sll.address(), testing::MAC_A);
+
+        let Ok(Payload::IPv4(ip)) = sll.payload() else { panic!("the payload isn't IPv4") };
+        assert_eq!(ip.destination_address(), testing::ip(10, 0, 0, 2));
+        let Ok(ipv4::Payload::Tcp(tcp)) = ip.payload() else { panic!("the packet isn't TCP") };
+        assert_eq!(tcp.destination_port(), 443);
+    }
+
+    #[test]
+    fn leaves_arp_undecoded() {
+        let data = sll2(0x0806, &[0; 28]);
+        assert!(matches!(Sll2::from_bytes(&data).unwrap().payload(), Ok(Payload::Unknown(raw)) if raw.len() == 28));
+        assert!(matches!(Sll2::from_bytes(&data[..19]), Err(ParseError::TooSmall { expected: 20, got: 19, .. })));
+    }
+}
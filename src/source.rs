@@ -0,0 +1,151 @@
+use std::collections::VecDeque;
+use std::io::{Error, ErrorKind, Read};
+use std::time::Duration;
+use pcap::{Activated, Capture, Packet, PacketHeader, Stat};
+use crate::drops::StatsSource;
+use crate::pcapng::PcapngReader;
+use crate::raw::RawReader;
+
+
+pub const LINK_TYPE_ETHERNET: u16 = 1;
+/// 802.11 frames with a radiotap header, as captured in monitor mode.
+pub const LINK_TYPE_IEEE802_11_RADIOTAP: u16 = 127;
+/// Linux cooked capture v2, as captured on the `any` interface.
+pub const LINK_TYPE_LINUX_SLL2: u16 = 276;
+
+
+/// A captured frame that owns its bytes, so sources don't have to keep a buffer alive while it's visited.
+#[derive(Debug, Clone)]
+pub struct OwnedPacket {
+    pub header: PacketHeader,
+    pub link_type: u16,
+    pub data: Vec<u8>,
+}
+
+impl OwnedPacket {
+    /// A frame captured `timestamp` after the Unix epoch that was `original_length` bytes on the wire.
+    pub fn new(link_type: u16, timestamp: Duration, original_length: u32, data: Vec<u8>) -> Self {
+        let header = PacketHeader {
+            ts: libc::timeval {
+                tv_sec:  timestamp.as_secs() as libc::time_t,
+                tv_usec: timestamp.subsec_micros() as libc::suseconds_t,
+            },
+            caplen: data.len() as u32,
+            len: original_length,
+        };
+        Self { header, link_type, data }
+    }
+
+    pub fn as_packet(&self) -> Packet<'_> {
+        Packet::new(&self.header, &self.data)
+    }
+}
+
+
+/// Where the capture loop gets its packets from: a live device, a capture file, raw frames or memory.
+pub trait PacketSource {
+    /// The next packet, or `None` once the source is exhausted. A live source returns an error of kind
+    /// `TimedOut` when no packet arrived in time, so the caller gets to check its limits.
+    fn next(&mut self) -> Option<Result<OwnedPacket, Error>>;
+
+    /// The kernel's packet counters, if the source keeps any.
+    fn stats(&mut self) -> Option<Stat> {
+        None
+    }
+}
+
+impl<'a> StatsSource for dyn PacketSource + 'a {
+    fn stats(&mut self) -> Option<Stat> {
+        PacketSource::stats(self)
+    }
+}
+
+
+/// A live capture or a pcap file opened with libpcap.
+pub struct CaptureSource<T: Activated + ?Sized> {
+    capture: Capture<T>,
+    link_type: u16,
+}
+
+impl<T: Activated + ?Sized> CaptureSource<T> {
+    pub fn new(capture: Capture<T>) -> Self {
+        let link_type = capture.get_datalink().0 as u16;
+        Self { capture, link_type }
+    }
+}
+
+impl<T: Activated + ?Sized> PacketSource for CaptureSource<T> {
+    fn next(&mut self) -> Option<Result<OwnedPacket, Error>> {
+        match self.capture.next_packet() {
+            Ok(packet) => Some(Ok(OwnedPacket { header: *packet.header, link_type: self.link_type, data: packet.data.to_vec() })),
+            Err(pcap::Error::TimeoutExpired) => Some(Err(Error::new(ErrorKind::TimedOut, "Capture timed out"))),
+            Err(pcap::Error::NoMorePackets) => None,
+            Err(error) => Some(Err(Error::other(error.to_string()))),
+        }
+    }
+
+    fn stats(&mut self) -> Option<Stat> {
+        self.capture.stats().ok()
+    }
+}
+
+
+/// A pcapng file, whose interfaces may each have their own link type.
+pub struct PcapngSource<R: Read> {
+    reader: PcapngReader<R>,
+}
+
+impl<R: Read> PcapngSource<R> {
+    pub fn new(reader: PcapngReader<R>) -> Self {
+        Self { reader }
+    }
+}
+
+impl<R: Read> PacketSource for PcapngSource<R> {
+    fn next(&mut self) -> Option<Result<OwnedPacket, Error>> {
+        self.reader.next_packet().map(|x| x.map(|packet| {
+            OwnedPacket::new(packet.link_type, packet.timestamp, packet.original_length, packet.data)
+        }))
+    }
+}
+
+
+/// Length-prefixed Ethernet frames, see `RawReader`.
+pub struct RawSource<R: Read> {
+    reader: RawReader<R>,
+}
+
+impl<R: Read> RawSource<R> {
+    pub fn new(reader: RawReader<R>) -> Self {
+        Self { reader }
+    }
+}
+
+impl<R: Read> PacketSource for RawSource<R> {
+    fn next(&mut self) -> Option<Result<OwnedPacket, Error>> {
+        self.reader.next_frame().map(|x| x.map(|(data, timestamp)| {
+            OwnedPacket::new(LINK_TYPE_ETHERNET, timestamp, data.len() as u32, data)
+        }))
+    }
+}
+
+
+/// Ethernet frames from memory with the time since the Unix epoch they were captured at, to drive the
+/// capture loop deterministically.
+#[derive(Debug, Default)]
+pub struct MockSource {
+    frames: VecDeque<(Duration, Vec<u8>)>,
+}
+
+impl MockSource {
+    pub fn new(frames: Vec<(Duration, Vec<u8>)>) -> Self {
+        Self { frames: frames.into() }
+    }
+}
+
+impl PacketSource for MockSource {
+    fn next(&mut self) -> Option<Result<OwnedPacket, Error>> {
+        let (timestamp, data) = self.frames.pop_front()?;
+        Some(Ok(OwnedPacket::new(LINK_TYPE_ETHERNET, timestamp, data.len() as u32, data)))
+    }
+}
@@ -0,0 +1,156 @@
+use std::io::{Error, ErrorKind, Write};
+use crate::ipv4::{self, IPv4};
+use crate::icmp;
+use crate::tcp::Tcp;
+use crate::visitor::{self, Context, Visitor};
+
+
+/// Prints one tcpdump-style line per packet, e.g.
+/// `12:00:01.123456 IP 10.0.0.1.443 > 10.0.0.2.51000: Flags [S.], seq 1, ack 2, length 0`.
+pub struct SummaryVisitor {
+    out: Box<dyn Write>,
+}
+
+impl SummaryVisitor {
+    /// Prints to stdout.
+    pub fn new() -> Self {
+        Self::with_writer(Box::new(std::io::stdout()))
+    }
+
+    pub fn with_writer(out: Box<dyn Write>) -> Self {
+        Self { out }
+    }
+
+    /// Renders the flags in the same order and notation as tcpdump, with `.` for ACK.
+    pub fn tcp_flags(packet: &Tcp) -> String {
+        packet.flag_string()
+    }
+
+    /// Builds the summary line for an IPv4 packet, without the leading timestamp.
+    pub fn summarize(packet: &IPv4) -> Result<String, Error> {
+        let source      = packet.source_address();
+        let destination = packet.destination_address();
+
+        match packet.payload()? {
+            ipv4::Payload::Tcp(tcp) => {
+                let mut line = format!(
+                    "IP {:?}.{} > {:?}.{}: Flags [{}], seq {}",
+                    source, tcp.source_port(), destination, tcp.destination_port(),
+                    Self::tcp_flags(&tcp), tcp.sequence_number()
+                );
+                if tcp.ack() != 0 {
+                    line += &format!(", ack {}", tcp.acknowledgment_number());
+                }
+                line += &format!(", length {}", tcp.raw_payload().len());
+                Ok(line)
+            },
+            ipv4::Payload::Udp(udp) => {
+                Ok(format!(
+                    "IP {:?}.{} > {:?}.{}: UDP, length {}",
+                    source, udp.source_port(), destination, udp.destination_port(),
+                    udp.raw_payload().len()
+                ))
+            },
+            ipv4::Payload::Sctp(sctp) => {
+                Ok(format!(
+                    "IP {:?}.{} > {:?}.{}: sctp, {} chunks",
+                    source, sctp.source_port(), destination, sctp.destination_port(), sctp.chunks().count()
+                ))
+            },
+            ipv4::Payload::Gre(gre) => {
+                Ok(format!(
+                    "IP {:?} > {:?}: GREv{}, proto {:#06x}, length {}",
+                    source, destination, gre.version(), gre.protocol_type(), gre.raw_payload().len()
+                ))
+            },
+            ipv4::Payload::Ah(ah) => {
+                Ok(format!(
+                    "IP {:?} > {:?}: AH(spi={:#010x},seq={:#x}), length {}",
+                    source, destination, ah.spi(), ah.sequence_number(), ah.raw_payload().len()
+                ))
+            },
+            ipv4::Payload::Esp(esp) => {
+                Ok(format!(
+                    "IP {:?} > {:?}: ESP(spi={:#010x},seq={:#x}), length {}",
+                    source, destination, esp.spi(), esp.sequence_number(), esp.raw_payload().len()
+                ))
+            },
+            ipv4::Payload::Icmp(icmp) => {
+                let quoted = match icmp.quoted() {
+                    Some(Ok(ip)) => match icmp::quoted_ports(&ip) {
+                        Some((source_port, destination_port)) => format!(" for {:?}.{} > {:?}.{}", ip.source_address(), source_port, ip.destination_address(), destination_port),
+                        None => format!(" for {:?} > {:?}", ip.source_address(), ip.destination_address()),
+                    },
+                    _ => String::new(),
+                };
+                Ok(format!("IP {:?} > {:?}: ICMP {}{}, length {}", source, destination, icmp.kind_name(), quoted, icmp.raw_payload().len()))
+            },
+            ipv4::Payload::Unknown(raw) => {
+                Ok(format!("IP {:?} > {:?}: ip-proto-{} {}", source, destination, packet.protocol_raw(), raw.len()))
+            },
+        }
+    }
+}
+
+impl Default for SummaryVisitor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+impl<'a> Visitor<'a, ()> for SummaryVisitor {
+    fn visit_ipv4(&mut self, packet: &IPv4<'a>, context: &Context<'a>) -> Result<(), Error> {
+        let line = Self::summarize(packet)?;
+        let timestamp = context.header.map(visitor::format_timestamp).unwrap_or_default();
+        writeln!(self.out, "{} {}", timestamp, line)
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::{Ipv4Builder, TcpBuilder, TCP_ACK, TCP_SYN};
+    use std::time::Duration;
+    use crate::ipv4::Protocol;
+    use crate::raw::visit_frame;
+    use crate::testing::{self, ip};
+    use crate::tree::SharedBuffer;
+
+    #[test]
+    fn summarizes_a_syn_ack() {
+        let tcp = TcpBuilder::new(443, 51000).with_flags(TCP_SYN | TCP_ACK).with_sequence_number(1000).with_acknowledgment_number(2001);
+        let data = Ipv4Builder::new(ip(10, 0, 0, 1), ip(10, 0, 0, 2)).with_tcp(&tcp).build();
+        let packet = IPv4::from_bytes(&data).unwrap();
+        assert_eq!(SummaryVisitor::summarize(&packet).unwrap(), "IP 10.0.0.1.443 > 10.0.0.2.51000: Flags [S.], seq 1000, ack 2001, length 0");
+    }
+
+    #[test]
+    fn summarizes_a_udp_datagram() {
+        let data = Ipv4Builder::new(ip(192, 168, 1, 5), ip(8, 8, 8, 8))
+            .with_payload(Protocol::UDP, &testing::udp(53000, 53, &[0; 12]))
+            .build();
+        let packet = IPv4::from_bytes(&data).unwrap();
+        assert_eq!(SummaryVisitor::summarize(&packet).unwrap(), "IP 192.168.1.5.53000 > 8.8.8.8.53: UDP, length 12");
+    }
+
+    #[test]
+    fn formats_the_timestamp_of_the_day() {
+        let header = testing::header(3_600_000_000 + 61_123_456, 60);
+        assert_eq!(visitor::format_timestamp(&header), "01:01:01.123456");
+    }
+
+    #[test]
+    fn writes_a_timestamped_line_per_packet() {
+        let buffer = SharedBuffer::default();
+        let mut visitor = SummaryVisitor::with_writer(Box::new(buffer.clone()));
+        let frame = testing::udp_frame(ip(192, 168, 1, 5), 53000, ip(8, 8, 8, 8), 53, b"query");
+        visit_frame(&mut visitor, &frame, Duration::from_micros(3_600_000_000 + 61_123_456)).unwrap();
+        visit_frame(&mut visitor, &frame, Duration::from_secs(7200)).unwrap();
+        assert_eq!(String::from_utf8(buffer.take()).unwrap(), concat!(
+            "01:01:01.123456 IP 192.168.1.5.53000 > 8.8.8.8.53: UDP, length 5\n",
+            "02:00:00.000000 IP 192.168.1.5.53000 > 8.8.8.8.53: UDP, length 5\n",
+        ));
+    }
+}
@@ -1,14 +1,11 @@
-use std::io::{Error, ErrorKind};
-use std::ops::Range;
-use chrono::format::format;
+use alloc::{string::String, vec::Vec};
+use core::ops::Range;
+use crate::error::ParseError;
+use crate::shared::bytes_of_bits;
+use crate::endian::{be_u16, be_u32};
 use crate::tcp::Option::{MaximumSegmentSize, NoOperation, Sack, SackPermitted, Timestamp, WindowScale};
 
 
-// @NOTE(ted): Assuming big endian (network endian) to little endian (hardware endian).
-fn be2leu8(data: &[u8],  i: usize) -> u8  { unsafe { (*data.get_unchecked(i+0)) } }
-fn be2leu16(data: &[u8], i: usize) -> u16 { unsafe { (*data.get_unchecked(i+1) as u16) << 8  | (*data.get_unchecked(i+0) as u16) << 0 } }
-fn be2leu32(data: &[u8], i: usize) -> u32 { unsafe { (*data.get_unchecked(i+3) as u32) << 24 | (*data.get_unchecked(i+2) as u32) << 16 | (*data.get_unchecked(i+1) as u32) << 8 | (*data.get_unchecked(i) as u32) << 0 } }
-
 
 #[derive(Debug)]
 pub enum Option {
@@ -32,46 +29,36 @@ pub struct OptionIter<'a> {
 impl<'a> Iterator for OptionIter<'a> {
     type Item = Option;
 
-    fn next(&mut self) -> std::option::Option<Self::Item> {
-        let kind = self.data.get(self.index)?;
-        self.index += 1;
-
-        match kind {
-            0 => None,  // EndOfOptions
-            1 => Some(NoOperation),
-            2 => {
-                if self.data.len() < self.index + 2 { return None; }
-                let size = be2leu16(&self.data, self.index);
-                self.index += 2;
-                Some(MaximumSegmentSize { size })
-            },
-            3 => {
-                if self.data.len() < self.index + 1 { return None; }
-                let scale = be2leu8(&self.data, self.index);
-                self.index += 1;
-                Some(WindowScale { scale })
-            },
-            4 => Some(SackPermitted),
-            5 => {
-                if self.data.len() < self.index + 4 { return None; }
-                let begin = be2leu32(&self.data, self.index);
-                self.index += 4;
-                if self.data.len() < self.index + 4 { return None; }
-                let end = be2leu32(&self.data, self.index);
-                self.index += 4;
-                Some(Sack { begin, end })
-            },
-            6..=7 => None,
-            8 => {
-                if self.data.len() < self.index + 4 { return None; }
-                let timestamp = be2leu32(&self.data, self.index);
-                self.index += 4;
-                if self.data.len() < self.index + 4 { return None; }
-                let echo = be2leu32(&self.data, self.index);
-                self.index += 4;
-                Some(Timestamp { timestamp, echo })
+    fn next(&mut self) -> core::option::Option<Self::Item> {
+        loop {
+            let start = self.index;
+            let kind = *self.data.get(start)?;
+
+            match kind {
+                0 => return None,  // EndOfOptions
+                1 => {
+                    self.index += 1;
+                    return Some(NoOperation);
+                },
+                _ => {}
+            }
+
+            // Every other option has a length byte that counts the kind and length bytes too.
+            let length = *self.data.get(start + 1)? as usize;
+            if length < 2 || start + length > self.data.len() { return None; }
+            self.index = start + length;
+            let value = &self.data[start + 2..start + length];
+
+            match (kind, value.len()) {
+                (2, 2) => return Some(MaximumSegmentSize { size: be_u16(value, 0) }),
+                (3, 1) => return Some(WindowScale { scale: value[0] }),
+                (4, 0) => return Some(SackPermitted),
+                // Only the first of up to four blocks is kept.
+                (5, n) if n >= 8 && n % 8 == 0 => return Some(Sack { begin: be_u32(value, 0), end: be_u32(value, 4) }),
+                (8, 8) => return Some(Timestamp { timestamp: be_u32(value, 0), echo: be_u32(value, 4) }),
+                // Skip options that are unknown or have the wrong length.
+                _ => continue,
             }
-            _ => None
         }
     }
 }
@@ -103,51 +90,125 @@ impl<'a> Tcp<'a> {
 
 
     /// Version is always 4.
-    pub fn source_port(&self)      -> u16 { be2leu16(&self.data, 0) }
-    pub fn destination_port(&self) -> u16 { be2leu16(&self.data, 2) }
+    pub fn source_port(&self)      -> u16 { be_u16(&self.data, 0) }
+    pub fn destination_port(&self) -> u16 { be_u16(&self.data, 2) }
 
-    pub fn sequence_number(&self)        -> u32 { be2leu32(&self.data, 4) }
-    pub fn acknowledgment_number(&self)  -> u32 { be2leu32(&self.data, 8) }
+    pub fn sequence_number(&self)        -> u32 { be_u32(&self.data, 4) }
+    pub fn acknowledgment_number(&self)  -> u32 { be_u32(&self.data, 8) }
 
-    pub fn reserved(&self)    -> u8 { (be2leu8(&self.data, 12) & 0b1111_0000) >> 4 }
-    pub fn data_offset(&self) -> u8 { (be2leu8(&self.data, 12) & 0b0000_1111) >> 0 }
+    pub fn data_offset(&self) -> u8 { (self.data[12] & 0b1111_0000) >> 4 }
+    pub fn reserved(&self)    -> u8 { (self.data[12] & 0b0000_1111) >> 0 }
 
-    pub fn cwr(&self) -> u8 { (be2leu8(&self.data, 13) & 0b0000_0001) >> 0 }
-    pub fn ece(&self) -> u8 { (be2leu8(&self.data, 13) & 0b0000_0010) >> 1 }
-    pub fn urg(&self) -> u8 { (be2leu8(&self.data, 13) & 0b0000_0100) >> 2 }
-    pub fn ack(&self) -> u8 { (be2leu8(&self.data, 13) & 0b0000_1000) >> 3 }
-    pub fn psh(&self) -> u8 { (be2leu8(&self.data, 13) & 0b0001_0000) >> 4 }
-    pub fn rst(&self) -> u8 { (be2leu8(&self.data, 13) & 0b0010_0000) >> 5 }
-    pub fn syn(&self) -> u8 { (be2leu8(&self.data, 13) & 0b0100_0000) >> 6 }
-    pub fn fin(&self) -> u8 { (be2leu8(&self.data, 13) & 0b1000_0000) >> 7 }
+    /// All eight flags, CWR in the most significant bit.
+    pub fn flags(&self) -> u8 { self.data[13] }
 
-    pub fn window_size(&self)    -> u16 { be2leu16(&self.data, 14) }
-    pub fn check_sum(&self)      -> u16 { be2leu16(&self.data, 16) }
-    pub fn urgent_pointer(&self) -> u16 { be2leu16(&self.data, 18) }
+    pub fn cwr(&self) -> u8 { (self.data[13] & 0b1000_0000) >> 7 }
+    pub fn ece(&self) -> u8 { (self.data[13] & 0b0100_0000) >> 6 }
+    pub fn urg(&self) -> u8 { (self.data[13] & 0b0010_0000) >> 5 }
+    pub fn ack(&self) -> u8 { (self.data[13] & 0b0001_0000) >> 4 }
+    pub fn psh(&self) -> u8 { (self.data[13] & 0b0000_1000) >> 3 }
+    pub fn rst(&self) -> u8 { (self.data[13] & 0b0000_0100) >> 2 }
+    pub fn syn(&self) -> u8 { (self.data[13] & 0b0000_0010) >> 1 }
+    pub fn fin(&self) -> u8 { (self.data[13] & 0b0000_0001) >> 0 }
+
+    /// The set flags in the same order and notation as tcpdump, with `.` for ACK, e.g. `S.`.
+    pub fn flag_string(&self) -> String {
+        let mut flags = String::new();
+        if self.fin() != 0 { flags.push('F') }
+        if self.syn() != 0 { flags.push('S') }
+        if self.rst() != 0 { flags.push('R') }
+        if self.psh() != 0 { flags.push('P') }
+        if self.ack() != 0 { flags.push('.') }
+        if self.urg() != 0 { flags.push('U') }
+        if self.ece() != 0 { flags.push('E') }
+        if self.cwr() != 0 { flags.push('W') }
+        flags
+    }
+
+    pub fn window_size(&self)    -> u16 { be_u16(&self.data, 14) }
+    pub fn check_sum(&self)      -> u16 { be_u16(&self.data, 16) }
+    pub fn urgent_pointer(&self) -> u16 { be_u16(&self.data, 18) }
+
+    /// The sequence numbers of the urgent data when URG is set, from the start of the segment up to but not
+    /// including `sequence_number + urgent_pointer` (RFC 6093).
+    pub fn urgent_range(&self) -> core::option::Option<(u32, u32)> {
+        if self.urg() == 0 { return None }
+        Some((self.sequence_number(), self.sequence_number().wrapping_add(self.urgent_pointer() as u32)))
+    }
+
+    /// The part of the payload that is urgent data when URG is set. Its last byte is the one sockets deliver
+    /// out of band.
+    pub fn urgent_data(&self) -> core::option::Option<&'a [u8]> {
+        if self.urg() == 0 { return None }
+        let payload = self.raw_payload();
+        Some(&payload[..(self.urgent_pointer() as usize).min(payload.len())])
+    }
+
+    /// Whether the urgent pointer is non-zero without URG. Receivers ignore it, which makes it a known way to
+    /// evade inspection that interprets it anyway.
+    pub fn has_stray_urgent_pointer(&self) -> bool {
+        self.urg() == 0 && self.urgent_pointer() != 0
+    }
 
     pub fn header_size(&self) -> usize { self.data_offset() as usize * 4 }
 
-    pub fn raw_payload(&self) -> &[u8] {
+    /// The bytes holding the field `name`, as named by its accessor, or `None` for unknown names.
+    pub fn field_bytes(&self, name: &str) -> core::option::Option<&'a [u8]> {
+        let bits = match name {
+            "source_port"           => Self::SOURCE_PORT_BITS,
+            "destination_port"      => Self::DESTINATION_PORT_BITS,
+            "sequence_number"       => Self::SEQUENCE_NUMBER_BITS,
+            "acknowledgment_number" => Self::ACKNOWLEDGMENT_NUMBER_BITS,
+            "reserved"              => Self::RESERVED_BITS,
+            "data_offset"           => Self::DATA_OFFSET_BITS,
+            "cwr"                   => Self::CWR_BITS,
+            "ece"                   => Self::ECE_BITS,
+            "urg"                   => Self::URG_BITS,
+            "ack"                   => Self::ACK_BITS,
+            "psh"                   => Self::PSH_BITS,
+            "rst"                   => Self::RST_BITS,
+            "syn"                   => Self::SYN_BITS,
+            "fin"                   => Self::FIN_BITS,
+            "flags"                 => Self::CWR_BITS.start..Self::FIN_BITS.end,
+            "window_size"           => Self::WINDOW_SIZE_BITS,
+            "check_sum"             => Self::CHECK_SUM_BITS,
+            "urgent_pointer"        => Self::URGENT_POINTER_BITS,
+            "options"               => return self.data.get(20..self.header_size()),
+            _ => return None,
+        };
+        Some(bytes_of_bits(self.data, bits))
+    }
+
+    pub fn raw_payload(&self) -> &'a [u8] {
         &self.data[self.header_size()..]
     }
 
+    /// The header and payload as they were parsed.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.data.to_vec()
+    }
+
     pub fn options(&self) -> OptionIter<'a> {
-        let options = &self.data[20..];
+        let options = &self.data[20..self.header_size()];
         OptionIter {
             data: options,
             index: 0
         }
     }
 
-    pub fn from_bytes(data: &'a [u8]) -> Result<Self, Error> {
+    pub fn from_bytes(data: &'a [u8]) -> Result<Self, ParseError> {
         if data.len() < 20 {
-            return Err(Error::new(ErrorKind::Other, format!("Tcp data too small, expected at least 20, got {}", data.len())));
+            return Err(ParseError::TooSmall { layer: "Tcp", field: "data", expected: 20, got: data.len() });
         }
 
         let me = Self { data };
 
+        if me.header_size() < 20 {
+            return Err(ParseError::TooSmall { layer: "Tcp", field: "header size", expected: 20, got: me.header_size() });
+        }
+
         if me.header_size() > data.len() {
-            return Err(Error::new(ErrorKind::Other, format!("Tcp header size too big, expected at most {}, got {}", data.len(), me.header_size())));
+            return Err(ParseError::TooBig { layer: "Tcp", field: "header size", expected: data.len(), got: me.header_size() });
         }
 
         // TODO: Verify all data.
@@ -156,8 +217,8 @@ impl<'a> Tcp<'a> {
 }
 
 
-impl<'a> std::fmt::Debug for Tcp<'a> {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+impl<'a> core::fmt::Debug for Tcp<'a> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
         write!(f, "    Tcp\n")?;
         write!(f, "        source_port:           {:?}\n", self.source_port())?;
         write!(f, "        destination_port:      {:?}\n", self.destination_port())?;
@@ -181,4 +242,76 @@ impl<'a> std::fmt::Debug for Tcp<'a> {
         }
         Ok(())
     }
-}
\ No newline at end of file
+}
+
+/// One line, e.g. `Tcp 51000 > 80 [S.] seq=1 ack=2 win=65535 len=0`.
+impl<'a> core::fmt::Display for Tcp<'a> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "Tcp {} > {} [{}] seq={} ack={} win={} len={}",
+            self.source_port(), self.destination_port(), self.flag_string(), self.sequence_number(),
+            self.acknowledgment_number(), self.window_size(), self.raw_payload().len())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::format;
+    use crate::builder::{TcpBuilder, TCP_ACK, TCP_SYN, TCP_URG};
+    use crate::testing::ip;
+
+    #[test]
+    fn displays_one_line_and_debugs_the_tree() {
+        let data = TcpBuilder::new(51000, 80).with_flags(TCP_SYN | TCP_ACK).with_sequence_number(1).with_acknowledgment_number(2)
+            .with_window_size(65535).with_payload(b"abc").build(ip(10, 0, 0, 1), ip(10, 0, 0, 2));
+        let packet = Tcp::from_bytes(&data).unwrap();
+        assert_eq!(format!("{}", packet), "Tcp 51000 > 80 [S.] seq=1 ack=2 win=65535 len=3");
+        let tree = format!("{:?}", packet);
+        assert!(tree.starts_with("    Tcp\n"));
+        assert!(tree.contains("        sequence_number:       1\n"));
+        assert!(tree.contains("        window_size:           65535\n"));
+    }
+
+    #[test]
+    fn round_trips_through_to_bytes() {
+        let data = TcpBuilder::new(51000, 80).with_flags(TCP_ACK).with_payload(b"GET / HTTP/1.1\r\n\r\n").build(ip(10, 0, 0, 1), ip(10, 0, 0, 2));
+        assert_eq!(Tcp::from_bytes(&data).unwrap().to_bytes(), data);
+        let data = crate::testing::tcp_with_options(&TcpBuilder::new(51000, 80).with_flags(TCP_SYN), &[2, 4, 5, 0xB4, 1, 3, 3, 7]);
+        assert_eq!(Tcp::from_bytes(&data).unwrap().to_bytes(), data);
+    }
+    #[test]
+    fn returns_the_bytes_behind_a_field() {
+        let data = TcpBuilder::new(51000, 80).with_flags(TCP_SYN).with_sequence_number(0x0102_0304).build(ip(10, 0, 0, 1), ip(10, 0, 0, 2));
+        let packet = Tcp::from_bytes(&data).unwrap();
+        assert_eq!(packet.field_bytes("source_port"), Some(&51000u16.to_be_bytes()[..]));
+        assert_eq!(packet.field_bytes("sequence_number"), Some(&[1, 2, 3, 4][..]));
+        assert_eq!(packet.field_bytes("syn"), Some(&[TCP_SYN][..]));
+        assert_eq!(packet.field_bytes("flags"), Some(&[TCP_SYN][..]));
+        assert_eq!(packet.field_bytes("window"), None);
+    }
+    #[test]
+    fn locates_the_urgent_data_of_a_urg_segment() {
+        let data = TcpBuilder::new(51000, 23).with_flags(TCP_ACK | TCP_URG).with_sequence_number(1000).with_urgent_pointer(3)
+            .with_payload(b"ab\xFFcd").build(ip(10, 0, 0, 1), ip(10, 0, 0, 2));
+        let packet = Tcp::from_bytes(&data).unwrap();
+        assert_eq!(packet.urgent_range(), Some((1000, 1003)));
+        assert_eq!(packet.urgent_data(), Some(&b"ab\xFF"[..]));
+        assert!(!packet.has_stray_urgent_pointer());
+
+        // A pointer beyond the payload is cut off at its end.
+        let data = TcpBuilder::new(51000, 23).with_flags(TCP_ACK | TCP_URG).with_urgent_pointer(10).with_payload(b"ab").build(ip(10, 0, 0, 1), ip(10, 0, 0, 2));
+        assert_eq!(Tcp::from_bytes(&data).unwrap().urgent_data(), Some(&b"ab"[..]));
+    }
+
+    #[test]
+    fn flags_an_urgent_pointer_without_urg() {
+        let data = TcpBuilder::new(51000, 23).with_flags(TCP_ACK).with_urgent_pointer(3).with_payload(b"abc").build(ip(10, 0, 0, 1), ip(10, 0, 0, 2));
+        let packet = Tcp::from_bytes(&data).unwrap();
+        assert!(packet.has_stray_urgent_pointer());
+        assert_eq!((packet.urgent_range(), packet.urgent_data()), (None, None));
+
+        let data = TcpBuilder::new(51000, 23).with_flags(TCP_ACK).with_payload(b"abc").build(ip(10, 0, 0, 1), ip(10, 0, 0, 2));
+        assert!(!Tcp::from_bytes(&data).unwrap().has_stray_urgent_pointer());
+    }
+}
@@ -0,0 +1,356 @@
+//! Frames for the unit tests, built with `builder` and wrapped in the link layers the parsers expect.
+
+use alloc::vec::Vec;
+use crate::builder::{Ipv4Builder, TcpBuilder};
+use crate::ipv4::{Ipv4Address, Protocol};
+use crate::shared::{checksum_add, checksum_finish};
+
+
+pub const MAC_A: [u8; 6] = [0x02, 0x00, 0x00, 0x00, 0x00, 0x0A];
+pub const MAC_B: [u8; 6] = [0x02, 0x00, 0x00, 0x00, 0x00, 0x0B];
+
+pub const ETHER_TYPE_IPV4: u16 = 0x0800;
+pub const ETHER_TYPE_IPV6: u16 = 0x86DD;
+
+
+pub fn ip(a: u8, b: u8, c: u8, d: u8) -> Ipv4Address {
+    Ipv4Address::from_bytes([a, b, c, d])
+}
+
+
+/// An Ethernet frame from `MAC_A` to `MAC_B` without frame check sequence.
+pub fn ethernet(ether_type: u16, payload: &[u8]) -> Vec<u8> {
+    let mut frame = Vec::with_capacity(14 + payload.len());
+    frame.extend_from_slice(&MAC_B);
+    frame.extend_from_slice(&MAC_A);
+    frame.extend_from_slice(&ether_type.to_be_bytes());
+    frame.extend_from_slice(payload);
+    frame
+}
+
+
+/// A UDP datagram without checksum, which IPv4 allows.
+pub fn udp(source_port: u16, destination_port: u16, payload: &[u8]) -> Vec<u8> {
+    let mut data = Vec::with_capacity(8 + payload.len());
+    data.extend_from_slice(&source_port.to_be_bytes());
+    data.extend_from_slice(&destination_port.to_be_bytes());
+    data.extend_from_slice(&((8 + payload.len()) as u16).to_be_bytes());
+    data.extend_from_slice(&[0, 0]);
+    data.extend_from_slice(payload);
+    data
+}
+
+
+/// An Ethernet frame holding `tcp` between `source` and `destination`.
+pub fn tcp_frame(source: Ipv4Address, destination: Ipv4Address, tcp: &TcpBuilder) -> Vec<u8> {
+    ethernet(ETHER_TYPE_IPV4, &Ipv4Builder::new(source, destination).with_tcp(tcp).build())
+}
+
+
+/// The segment `tcp` builds with `options`, which must be a multiple of four bytes, in its header. The checksum
+/// isn't updated.
+pub fn tcp_with_options(tcp: &TcpBuilder, options: &[u8]) -> Vec<u8> {
+    let segment = tcp.build(ip(0, 0, 0, 0), ip(0, 0, 0, 0));
+    let mut data = segment[..TcpBuilder::HEADER_SIZE].to_vec();
+    data.extend_from_slice(options);
+    data.extend_from_slice(&segment[TcpBuilder::HEADER_SIZE..]);
+    data[12] = (((TcpBuilder::HEADER_SIZE + options.len()) / 4) as u8) << 4;
+    data
+}
+
+
+/// An Ethernet frame holding an IPv6 packet without extension headers around the TCP `segment`.
+pub fn ipv6_tcp_frame(source: [u8; 16], destination: [u8; 16], segment: &[u8]) -> Vec<u8> {
+    let mut packet = Vec::from([0x60, 0, 0, 0]);
+    packet.extend_from_slice(&(segment.len() as u16).to_be_bytes());
+    packet.extend_from_slice(&[Protocol::TCP.value(), 64]);
+    packet.extend_from_slice(&source);
+    packet.extend_from_slice(&destination);
+    packet.extend_from_slice(segment);
+    ethernet(ETHER_TYPE_IPV6, &packet)
+}
+
+/// An Ethernet frame holding a UDP datagram between `source` and `destination`.
+pub fn udp_frame(source: Ipv4Address, source_port: u16, destination: Ipv4Address, destination_port: u16, payload: &[u8]) -> Vec<u8> {
+    let packet = Ipv4Builder::new(source, destination)
+        .with_payload(Protocol::UDP, &udp(source_port, destination_port, payload))
+        .build();
+    ethernet(ETHER_TYPE_IPV4, &packet)
+}
+
+
+/// An IPv4 header with `options`, which must be a multiple of four bytes, followed by a UDP datagram.
+pub fn ipv4_with_options(options: &[u8]) -> Vec<u8> {
+    let packet = Ipv4Builder::new(ip(10, 0, 0, 1), ip(10, 0, 0, 2))
+        .with_payload(Protocol::UDP, &udp(53000, 53, &[]))
+        .build();
+    let mut data = packet[..20].to_vec();
+    data.extend_from_slice(options);
+    data.extend_from_slice(&packet[20..]);
+
+    data[0] = 0x40 | (data.len() - packet.len() + 20) as u8 / 4;
+    let total_length = data.len() as u16;
+    data[2..4].copy_from_slice(&total_length.to_be_bytes());
+    data[10..12].fill(0);
+    let checksum = checksum_finish(checksum_add(0, &data[..20 + options.len()]));
+    data[10..12].copy_from_slice(&checksum.to_be_bytes());
+    data
+}
+
+
+/// The fragment of the 20 byte header `datagram` holding `length` payload bytes from `offset`, a multiple of 8.
+pub fn ipv4_fragment(datagram: &[u8], offset: usize, length: usize, more: bool) -> Vec<u8> {
+    let payload = &datagram[20..];
+    let end = (offset + length).min(payload.len());
+    let mut data = datagram[..20].to_vec();
+    data.extend_from_slice(&payload[offset..end]);
+
+    let total_length = data.len() as u16;
+    data[2..4].copy_from_slice(&total_length.to_be_bytes());
+    let flags = if more { 0x2000 } else { 0 } | (offset / 8) as u16;
+    data[6..8].copy_from_slice(&flags.to_be_bytes());
+    data[10..12].fill(0);
+    let checksum = checksum_finish(checksum_add(0, &data[..20]));
+    data[10..12].copy_from_slice(&checksum.to_be_bytes());
+    data
+}
+
+
+/// A pcap header for a frame of `length` bytes captured `micros` microseconds after the epoch.
+#[cfg(feature = "std")]
+pub fn header(micros: u64, length: usize) -> pcap::PacketHeader {
+    pcap::PacketHeader {
+        ts: libc::timeval {
+            tv_sec:  (micros / 1_000_000) as libc::time_t,
+            tv_usec: (micros % 1_000_000) as libc::suseconds_t,
+        },
+        caplen: length as u32,
+        len: length as u32,
+    }
+}
+
+
+/// Keeps the bytes of every packet it's given, to check what a wrapping visitor forwards. Clones share the
+/// packets, so a test can keep one while the wrapper owns another.
+#[cfg(feature = "std")]
+#[derive(Clone, Default)]
+pub struct Recorder {
+    packets: std::rc::Rc<std::cell::RefCell<Vec<Vec<u8>>>>,
+}
+
+#[cfg(feature = "std")]
+impl Recorder {
+    pub fn packets(&self) -> Vec<Vec<u8>> {
+        self.packets.borrow().clone()
+    }
+}
+
+#[cfg(feature = "std")]
+impl<'a> crate::visitor::Visitor<'a, ()> for Recorder {
+    fn visit_packet(&mut self, packet: &'a pcap::Packet) -> Result<(), std::io::Error> {
+        self.packets.borrow_mut().push(packet.data.to_vec());
+        Ok(())
+    }
+}
+
+
+/// A fresh directory for the files of one test, removed by `remove_dir_all` at its end.
+#[cfg(feature = "std")]
+pub fn temp_dir(name: &str) -> std::path::PathBuf {
+    let path = std::env::temp_dir().join(format!("packet-analyser-{}-{}", name, std::process::id()));
+    let _ = std::fs::remove_dir_all(&path);
+    std::fs::create_dir_all(&path).unwrap();
+    path
+}
+
+
+/// The frames of a classic pcap file as written by `PcapWriter`, checking the file header on the way.
+#[cfg(feature = "std")]
+pub fn read_pcap(data: &[u8]) -> Vec<Vec<u8>> {
+    use crate::pcap_writer::{FILE_HEADER_SIZE, MAGIC, RECORD_HEADER_SIZE};
+    let u32_at = |i: usize| u32::from_ne_bytes(data[i..i+4].try_into().unwrap());
+
+    assert!(data.len() >= FILE_HEADER_SIZE, "no pcap file header");
+    assert_eq!(u32_at(0), MAGIC);
+
+    let mut frames = Vec::new();
+    let mut index = FILE_HEADER_SIZE;
+    while index < data.len() {
+        let length = u32_at(index + 8) as usize;
+        index += RECORD_HEADER_SIZE;
+        frames.push(data[index..index + length].to_vec());
+        index += length;
+    }
+    assert_eq!(index, data.len(), "truncated pcap record");
+    frames
+}
+
+
+/// A little endian pcapng file with one Ethernet interface holding the frames, each captured the given number
+/// of microseconds after the epoch.
+#[cfg(feature = "std")]
+pub fn pcapng(frames: &[(u64, Vec<u8>)]) -> Vec<u8> {
+    use crate::pcapng::{BLOCK_ENHANCED_PACKET, BLOCK_INTERFACE_DESCRIPTION, BLOCK_SECTION_HEADER};
+    let block = |kind: u32, body: &[u8]| {
+        let length = 12 + body.len().next_multiple_of(4) as u32;
+        let mut data = Vec::from(kind.to_le_bytes());
+        data.extend_from_slice(&length.to_le_bytes());
+        data.extend_from_slice(body);
+        data.resize(length as usize - 4, 0);
+        data.extend_from_slice(&length.to_le_bytes());
+        data
+    };
+
+    let mut data = block(BLOCK_SECTION_HEADER, &[0x4D, 0x3C, 0x2B, 0x1A, 1, 0, 0, 0, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF]);
+    data.extend(block(BLOCK_INTERFACE_DESCRIPTION, &[1, 0, 0, 0, 0xFF, 0xFF, 0, 0]));
+    for (micros, frame) in frames {
+        let mut body = Vec::from(0u32.to_le_bytes());
+        body.extend_from_slice(&((micros >> 32) as u32).to_le_bytes());
+        body.extend_from_slice(&(*micros as u32).to_le_bytes());
+        body.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+        body.extend_from_slice(&(frame.len() as u32).to_le_bytes());
+        body.extend_from_slice(frame);
+        data.extend(block(BLOCK_ENHANCED_PACKET, &body));
+    }
+    data
+}
+
+
+/// A parsed JSON value, to check what the JSON writers produce without a JSON dependency.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(std::string::String),
+    Array(Vec<Json>),
+    Object(Vec<(std::string::String, Json)>),
+}
+
+#[cfg(feature = "std")]
+impl Json {
+    /// The value of `key` if this is an object that has it.
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(fields) => fields.iter().find(|(x, _)| x == key).map(|(_, value)| value),
+            _ => None,
+        }
+    }
+
+    /// Parses `text` as exactly one JSON value, surrounded by nothing but whitespace.
+    pub fn parse(text: &str) -> Result<Json, std::string::String> {
+        let mut parser = JsonParser { data: text.as_bytes(), index: 0 };
+        let value = parser.value()?;
+        parser.whitespace();
+        match parser.index == parser.data.len() {
+            true  => Ok(value),
+            false => Err(format!("Trailing characters at {}", parser.index)),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+struct JsonParser<'a> {
+    data: &'a [u8],
+    index: usize,
+}
+
+#[cfg(feature = "std")]
+impl<'a> JsonParser<'a> {
+    fn whitespace(&mut self) {
+        while matches!(self.data.get(self.index), Some(b' ' | b'\t' | b'\n' | b'\r')) {
+            self.index += 1;
+        }
+    }
+
+    fn expect(&mut self, text: &str) -> Result<(), std::string::String> {
+        match self.data[self.index..].starts_with(text.as_bytes()) {
+            true  => { self.index += text.len(); Ok(()) },
+            false => Err(format!("Expected '{}' at {}", text, self.index)),
+        }
+    }
+
+    fn value(&mut self) -> Result<Json, std::string::String> {
+        self.whitespace();
+        match self.data.get(self.index) {
+            Some(b'n') => self.expect("null").map(|_| Json::Null),
+            Some(b't') => self.expect("true").map(|_| Json::Bool(true)),
+            Some(b'f') => self.expect("false").map(|_| Json::Bool(false)),
+            Some(b'"') => self.string().map(Json::String),
+            Some(b'[') => {
+                self.index += 1;
+                let mut values = Vec::new();
+                self.whitespace();
+                if self.expect("]").is_ok() { return Ok(Json::Array(values)) }
+                loop {
+                    values.push(self.value()?);
+                    self.whitespace();
+                    if self.expect(",").is_err() { break }
+                }
+                self.expect("]").map(|_| Json::Array(values))
+            },
+            Some(b'{') => {
+                self.index += 1;
+                let mut fields = Vec::new();
+                self.whitespace();
+                if self.expect("}").is_ok() { return Ok(Json::Object(fields)) }
+                loop {
+                    self.whitespace();
+                    let key = self.string()?;
+                    self.whitespace();
+                    self.expect(":")?;
+                    fields.push((key, self.value()?));
+                    self.whitespace();
+                    if self.expect(",").is_err() { break }
+                }
+                self.expect("}").map(|_| Json::Object(fields))
+            },
+            Some(b'-' | b'0'..=b'9') => {
+                let start = self.index;
+                while matches!(self.data.get(self.index), Some(b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9')) {
+                    self.index += 1;
+                }
+                let text = std::str::from_utf8(&self.data[start..self.index]).unwrap();
+                text.parse().map(Json::Number).map_err(|_| format!("Invalid number '{}'", text))
+            },
+            _ => Err(format!("Expected a value at {}", self.index)),
+        }
+    }
+
+    fn string(&mut self) -> Result<std::string::String, std::string::String> {
+        self.expect("\"")?;
+        let mut text = std::string::String::new();
+        loop {
+            let rest = std::str::from_utf8(&self.data[self.index..]).map_err(|x| x.to_string())?;
+            let c = rest.chars().next().ok_or("Unterminated string")?;
+            self.index += c.len_utf8();
+            match c {
+                '"' => return Ok(text),
+                '\\' => {
+                    let escape = *self.data.get(self.index).ok_or("Unterminated escape")?;
+                    self.index += 1;
+                    match escape {
+                        b'"' => text.push('"'),
+                        b'\\' => text.push('\\'),
+                        b'/' => text.push('/'),
+                        b'b' => text.push('\u{8}'),
+                        b'f' => text.push('\u{c}'),
+                        b'n' => text.push('\n'),
+                        b'r' => text.push('\r'),
+                        b't' => text.push('\t'),
+                        b'u' => {
+                            let hex = self.data.get(self.index..self.index + 4).ok_or("Truncated \\u escape")?;
+                            let code = u32::from_str_radix(std::str::from_utf8(hex).unwrap(), 16).map_err(|x| x.to_string())?;
+                            text.push(char::from_u32(code).ok_or("Invalid \\u escape")?);
+                            self.index += 4;
+                        },
+                        _ => return Err(format!("Invalid escape at {}", self.index)),
+                    }
+                },
+                c if (c as u32) < 0x20 => return Err(format!("Control character in string at {}", self.index)),
+                c => text.push(c),
+            }
+        }
+    }
+}
@@ -0,0 +1,125 @@
+use std::str::FromStr;
+use std::time::Duration;
+use pcap::PacketHeader;
+
+
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+
+/// Time since the epoch at which the packet was captured.
+pub fn timestamp(header: &PacketHeader) -> Duration {
+    Duration::new(header.ts.tv_sec.max(0) as u64, (header.ts.tv_usec.max(0) as u32).min(999_999) * 1000)
+}
+
+
+/// A point in time accepted by `--since` and `--until`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum TimeBound {
+    /// Time since the Unix epoch, e.g. `1700000000` or `1700000000.25`.
+    Epoch(Duration),
+    /// Time of day in UTC like the printed timestamps, e.g. `13:05:00`. It matches that time on every day.
+    TimeOfDay(Duration),
+}
+
+impl FromStr for TimeBound {
+    type Err = String;
+
+    fn from_str(text: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("Invalid time '{}', expected HH:MM:SS or seconds since the epoch", text);
+
+        if !text.contains(':') {
+            let seconds: f64 = text.parse().map_err(|_| invalid())?;
+            return Duration::try_from_secs_f64(seconds).map(Self::Epoch).map_err(|_| invalid());
+        }
+
+        let parts: Vec<&str> = text.split(':').collect();
+        let [hours, minutes, seconds] = parts[..] else { return Err(invalid()) };
+        let hours: u64 = hours.parse().ok().filter(|x| *x < 24).ok_or_else(invalid)?;
+        let minutes: u64 = minutes.parse().ok().filter(|x| *x < 60).ok_or_else(invalid)?;
+        let seconds: f64 = seconds.parse().ok().filter(|x| (0.0..60.0).contains(x)).ok_or_else(invalid)?;
+        Ok(Self::TimeOfDay(Duration::from_secs(hours * 3600 + minutes * 60) + Duration::from_secs_f64(seconds)))
+    }
+}
+
+impl TimeBound {
+    /// Converts `timestamp`, the time since the epoch, to the same kind of time as this bound.
+    fn project(&self, timestamp: Duration) -> Duration {
+        match self {
+            Self::Epoch(_)     => timestamp,
+            Self::TimeOfDay(_) => Duration::new(timestamp.as_secs() % SECONDS_PER_DAY, timestamp.subsec_nanos()),
+        }
+    }
+
+    fn value(&self) -> Duration {
+        match self {
+            Self::Epoch(value) | Self::TimeOfDay(value) => *value,
+        }
+    }
+}
+
+
+/// Selects the packets captured between `since` and `until`, both inclusive. Each packet is checked on its
+/// own, so captures with out of order timestamps are handled.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct TimeWindow {
+    pub since: Option<TimeBound>,
+    pub until: Option<TimeBound>,
+}
+
+impl TimeWindow {
+    /// Whether the packet was captured inside the window. Only looks at the header.
+    pub fn contains(&self, header: &PacketHeader) -> bool {
+        let timestamp = timestamp(header);
+
+        // A window like 23:00:00 to 01:00:00 spans midnight.
+        if let (Some(since @ TimeBound::TimeOfDay(start)), Some(TimeBound::TimeOfDay(end))) = (self.since, self.until) {
+            if start > end {
+                let time = since.project(timestamp);
+                return time >= start || time <= end;
+            }
+        }
+
+        if let Some(since) = self.since {
+            if since.project(timestamp) < since.value() { return false }
+        }
+        if let Some(until) = self.until {
+            if until.project(timestamp) > until.value() { return false }
+        }
+        true
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::header;
+
+    #[test]
+    fn parses_epoch_seconds_and_times_of_day() {
+        assert_eq!("1700000000.25".parse(), Ok(TimeBound::Epoch(Duration::from_millis(1_700_000_000_250))));
+        assert_eq!("13:05:30".parse(), Ok(TimeBound::TimeOfDay(Duration::from_secs(13 * 3600 + 5 * 60 + 30))));
+        assert!("24:00:00".parse::<TimeBound>().is_err());
+        assert!("13:05".parse::<TimeBound>().is_err());
+        assert!("soon".parse::<TimeBound>().is_err());
+    }
+
+    #[test]
+    fn includes_both_bounds() {
+        let window = TimeWindow { since: Some(TimeBound::Epoch(Duration::from_secs(10))), until: Some(TimeBound::Epoch(Duration::from_secs(20))) };
+        assert!(!window.contains(&header(9_999_999, 60)));
+        assert!(window.contains(&header(10_000_000, 60)));
+        assert!(window.contains(&header(20_000_000, 60)));
+        assert!(!window.contains(&header(20_000_001, 60)));
+    }
+
+    #[test]
+    fn matches_a_time_of_day_on_every_day_across_midnight() {
+        let window = TimeWindow { since: Some("23:00:00".parse().unwrap()), until: Some("01:00:00".parse().unwrap()) };
+        let day = SECONDS_PER_DAY * 1_000_000;
+        let hour = 3600 * 1_000_000;
+        assert!(window.contains(&header(3 * day + 23 * hour + 1, 60)));
+        assert!(window.contains(&header(4 * day + hour / 2, 60)));
+        assert!(!window.contains(&header(4 * day + 12 * hour, 60)));
+    }
+}
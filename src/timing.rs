@@ -0,0 +1,174 @@
+use std::io::Error;
+use std::time::Duration;
+use pcap::{Packet, PacketHeader};
+use crate::visitor::{self, Visitor};
+
+
+/// Tracks the capture's time span and the gaps between consecutive packets, then passes each packet on to the
+/// inner visitor. Packets timestamped before an earlier one are counted as out of order instead of making a gap.
+pub struct TimingVisitor {
+    inner: Box<dyn for<'a> Visitor<'a, ()>>,
+    timing: Timing,
+}
+
+impl TimingVisitor {
+    pub fn new(inner: Box<dyn for<'a> Visitor<'a, ()>>) -> Self {
+        Self { inner, timing: Timing::default() }
+    }
+}
+
+
+/// The gap between packets `index - 1` and `index`, counting from 1.
+#[derive(Debug, Copy, Clone, Default, PartialEq)]
+pub struct Gap {
+    pub index: usize,
+    pub length: Duration,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Timing {
+    pub count: usize,
+    pub first: Option<Duration>,
+    pub last: Option<Duration>,
+    pub largest_gap: Option<Gap>,
+    pub out_of_order: usize,
+    total_gap: Duration,
+    gaps: usize,
+}
+
+impl Timing {
+    /// Records the timestamp of the next packet, as time since the Unix epoch.
+    pub fn push(&mut self, timestamp: Duration) {
+        self.count += 1;
+
+        match self.last {
+            None => {
+                self.first = Some(timestamp);
+                self.last  = Some(timestamp);
+            },
+            Some(last) if timestamp < last => {
+                self.out_of_order += 1;
+                if self.first.is_some_and(|first| timestamp < first) { self.first = Some(timestamp) }
+            },
+            Some(last) => {
+                let gap = Gap { index: self.count, length: timestamp - last };
+                if self.largest_gap.is_none_or(|largest| gap.length > largest.length) { self.largest_gap = Some(gap) }
+                self.total_gap += gap.length;
+                self.gaps += 1;
+                self.last = Some(timestamp);
+            },
+        }
+    }
+
+    pub fn duration(&self) -> Duration {
+        match (self.first, self.last) {
+            (Some(first), Some(last)) => last - first,
+            _ => Duration::ZERO,
+        }
+    }
+
+    pub fn mean_gap(&self) -> Option<Duration> {
+        if self.gaps == 0 { None } else { Some(self.total_gap / self.gaps as u32) }
+    }
+}
+
+
+fn timestamp(header: &PacketHeader) -> Duration {
+    Duration::new(header.ts.tv_sec.max(0) as u64, (header.ts.tv_usec.max(0) as u32).min(999_999) * 1000)
+}
+
+fn format_time(time: Duration) -> String {
+    let header = PacketHeader {
+        ts: libc::timeval { tv_sec: time.as_secs() as libc::time_t, tv_usec: time.subsec_micros() as libc::suseconds_t },
+        caplen: 0,
+        len: 0,
+    };
+    visitor::format_timestamp(&header)
+}
+
+
+impl<'a> Visitor<'a, ()> for TimingVisitor {
+    fn visit_packet(&mut self, packet: &'a Packet) -> Result<(), Error> {
+        self.timing.push(timestamp(packet.header));
+        self.inner.visit_packet(packet)
+    }
+
+    fn visit_radiotap_packet(&mut self, packet: &'a Packet) -> Result<(), Error> {
+        self.timing.push(timestamp(packet.header));
+        self.inner.visit_radiotap_packet(packet)
+    }
+
+    fn visit_sll2_packet(&mut self, packet: &'a Packet) -> Result<(), Error> {
+        self.timing.push(timestamp(packet.header));
+        self.inner.visit_sll2_packet(packet)
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        self.inner.finish()?;
+
+        let timing = &self.timing;
+        let (Some(first), Some(last)) = (timing.first, timing.last) else {
+            println!("No packets to time");
+            return Ok(());
+        };
+        println!("First packet             : {}", format_time(first));
+        println!("Last packet              : {}", format_time(last));
+        println!("Duration                 : {:.6} seconds", timing.duration().as_secs_f64());
+        if let Some(mean) = timing.mean_gap() {
+            println!("Mean gap                 : {:.6} seconds", mean.as_secs_f64());
+        }
+        if let Some(gap) = timing.largest_gap {
+            println!("Largest gap              : {:.6} seconds, between packets {} and {}", gap.length.as_secs_f64(), gap.index - 1, gap.index);
+        }
+        if timing.out_of_order > 0 {
+            println!("[WARNING] {} packets were timestamped before an earlier packet", timing.out_of_order);
+        }
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::raw::visit_frame;
+    use crate::testing::Recorder;
+
+    #[test]
+    fn measures_the_duration_and_largest_gap() {
+        let mut timing = Timing::default();
+        for millis in [1000, 1100, 1150, 1900, 2000] {
+            timing.push(Duration::from_millis(millis));
+        }
+
+        assert_eq!(timing.count, 5);
+        assert_eq!(timing.duration(), Duration::from_secs(1));
+        assert_eq!(timing.largest_gap, Some(Gap { index: 4, length: Duration::from_millis(750) }));
+        assert_eq!(timing.mean_gap(), Some(Duration::from_millis(250)));
+        assert_eq!(timing.out_of_order, 0);
+    }
+
+    #[test]
+    fn counts_out_of_order_timestamps_instead_of_negative_gaps() {
+        let mut timing = Timing::default();
+        for millis in [1000, 1200, 900, 1300] {
+            timing.push(Duration::from_millis(millis));
+        }
+
+        assert_eq!(timing.out_of_order, 1);
+        assert_eq!(timing.first, Some(Duration::from_millis(900)));
+        assert_eq!(timing.duration(), Duration::from_millis(400));
+        assert_eq!(timing.largest_gap, Some(Gap { index: 2, length: Duration::from_millis(200) }));
+    }
+
+    #[test]
+    fn times_the_packets_it_forwards() {
+        let recorder = Recorder::default();
+        let mut visitor = TimingVisitor::new(Box::new(recorder.clone()));
+        visit_frame(&mut visitor, &[0; 60], Duration::from_micros(5_000_000)).unwrap();
+        visit_frame(&mut visitor, &[1; 60], Duration::from_micros(7_500_000)).unwrap();
+
+        assert_eq!(visitor.timing.duration(), Duration::from_millis(2500));
+        assert_eq!(recorder.packets().len(), 2);
+    }
+}
@@ -0,0 +1,83 @@
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::rc::Rc;
+
+
+/// A line and the lines nested under it.
+#[derive(Debug, Default)]
+struct Node {
+    label: String,
+    children: Vec<Node>,
+}
+
+
+/// Renders the flat `|`-prefixed lines the `Printer` writes for one packet as a tree under `root`. A line like
+/// `| - Tcp [ ... ]` starts a layer nested under the previous layer, and a line like `|    Source Port : 80`
+/// is a field of the current layer, so the encapsulation depth shows as indentation:
+///
+/// ```text
+/// Packet [ size 74 ]
+/// └─ Ethernet [ payload size 60 ]
+///    ├─ Source                : 00:11:22:33:44:55
+///    └─ Ipv4 [ payload size 40 ]
+///       └─ ...
+/// ```
+pub fn render(root: &str, flat: &str) -> String {
+    // The innermost layer is last, so the chain of layers is built outside in and nested at the end.
+    let mut layers: Vec<Node> = Vec::new();
+    let mut top = Vec::new();
+    for line in flat.lines() {
+        if let Some(label) = line.strip_prefix("| - ").or_else(|| line.strip_prefix("|- ")) {
+            layers.push(Node { label: label.trim().to_string(), children: Vec::new() });
+        } else {
+            let label = line.strip_prefix('|').unwrap_or(line).trim().to_string();
+            match layers.last_mut() {
+                Some(layer) => layer.children.push(Node { label, children: Vec::new() }),
+                None => top.push(Node { label, children: Vec::new() }),
+            }
+        }
+    }
+    let innermost = layers.into_iter().rev().reduce(|inner, mut outer| {
+        outer.children.push(inner);
+        outer
+    });
+    top.extend(innermost);
+
+    let mut text = format!("{}\n", root);
+    write_children(&mut text, &top, "");
+    text
+}
+
+fn write_children(text: &mut String, children: &[Node], prefix: &str) {
+    for (i, child) in children.iter().enumerate() {
+        let last = i + 1 == children.len();
+        text.push_str(prefix);
+        text.push_str(if last { "└─ " } else { "├─ " });
+        text.push_str(&child.label);
+        text.push('\n');
+        write_children(text, &child.children, &format!("{}{}", prefix, if last { "   " } else { "│  " }));
+    }
+}
+
+
+/// A writer whose bytes can be taken out while a clone of it is boxed elsewhere, to collect the lines of one
+/// packet before they're rendered as a tree.
+#[derive(Clone, Default)]
+pub struct SharedBuffer(Rc<RefCell<Vec<u8>>>);
+
+impl SharedBuffer {
+    pub fn take(&self) -> Vec<u8> {
+        std::mem::take(&mut self.0.borrow_mut())
+    }
+}
+
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.borrow_mut().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
@@ -0,0 +1,146 @@
+use alloc::vec::Vec;
+use core::ops::Range;
+use crate::error::ParseError;
+use crate::shared::bytes_of_bits;
+use crate::vxlan;
+use crate::endian::be_u16;
+
+
+#[derive(Debug)]
+pub enum Payload<'a> {
+    Vxlan(vxlan::Vxlan<'a>),
+    Unknown(&'a [u8]),
+}
+
+
+#[derive(Clone)]
+pub struct Udp<'a> {
+    data: &'a [u8],
+    /// Number of tunnels the enclosing IP header is nested in.
+    depth: usize,
+}
+
+impl<'a> Udp<'a> {
+    pub const HEADER_SIZE: usize = 8;
+
+    pub const SOURCE_PORT_BITS:      Range<usize> = 0..16;
+    pub const DESTINATION_PORT_BITS: Range<usize> = 16..32;
+    pub const LENGTH_BITS:           Range<usize> = 32..48;
+    pub const CHECK_SUM_BITS:        Range<usize> = 48..64;
+
+    pub fn source_port(&self)      -> u16 { be_u16(self.data, 0) }
+    pub fn destination_port(&self) -> u16 { be_u16(self.data, 2) }
+
+    /// Length in bytes of the header and the payload.
+    pub fn length(&self)    -> u16 { be_u16(self.data, 4) }
+    pub fn check_sum(&self) -> u16 { be_u16(self.data, 6) }
+
+    /// The bytes holding the field `name`, as named by its accessor, or `None` for unknown names.
+    pub fn field_bytes(&self, name: &str) -> Option<&'a [u8]> {
+        let bits = match name {
+            "source_port"      => Self::SOURCE_PORT_BITS,
+            "destination_port" => Self::DESTINATION_PORT_BITS,
+            "length"           => Self::LENGTH_BITS,
+            "check_sum"        => Self::CHECK_SUM_BITS,
+            _ => return None,
+        };
+        Some(bytes_of_bits(self.data, bits))
+    }
+
+    pub fn raw_payload(&self) -> &'a [u8] {
+        &self.data[Self::HEADER_SIZE..]
+    }
+
+    /// Decodes tunnels by their destination port. Other payloads, including DNS, are left to the caller.
+    pub fn payload(&self) -> Result<Payload<'a>, ParseError> {
+        match self.destination_port() {
+            vxlan::PORT => Ok(Payload::Vxlan(vxlan::Vxlan::from_bytes_nested(self.raw_payload(), self.depth + 1)?)),
+            _ => Ok(Payload::Unknown(self.raw_payload())),
+        }
+    }
+
+    /// Checks `length` against `available`, the size of the datagram according to the IP header.
+    pub fn validate_length(&self, available: usize) -> Result<(), ParseError> {
+        let length = self.length() as usize;
+        if length < Self::HEADER_SIZE {
+            return Err(ParseError::TooSmall { layer: "Udp", field: "length", expected: Self::HEADER_SIZE, got: length });
+        }
+        if length > available {
+            return Err(ParseError::TooBig { layer: "Udp", field: "length", expected: available, got: length });
+        }
+        if length < available {
+            return Err(ParseError::Invalid("Udp length is shorter than the Ip payload"));
+        }
+        Ok(())
+    }
+
+    /// The header and payload as they were parsed.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        self.data.to_vec()
+    }
+
+    pub fn from_bytes(data: &'a [u8]) -> Result<Self, ParseError> {
+        Self::from_bytes_nested(data, 0)
+    }
+
+    /// Parses a header whose IP header is encapsulated in `depth` tunnels.
+    pub fn from_bytes_nested(data: &'a [u8], depth: usize) -> Result<Self, ParseError> {
+        if data.len() < Self::HEADER_SIZE {
+            return Err(ParseError::TooSmall { layer: "Udp", field: "data", expected: Self::HEADER_SIZE, got: data.len() });
+        }
+
+        let me = Self { data, depth };
+        Ok(me)
+    }
+}
+
+
+impl<'a> core::fmt::Debug for Udp<'a> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "    Udp\n")?;
+        write!(f, "        source_port:      {:?}\n", self.source_port())?;
+        write!(f, "        destination_port: {:?}\n", self.destination_port())?;
+        write!(f, "        length:           {:?}\n", self.length())?;
+        write!(f, "        check_sum:        {:?}\n", self.check_sum())?;
+        Ok(())
+    }
+}
+
+/// One line, e.g. `Udp 51000 > 53 len=40`.
+impl<'a> core::fmt::Display for Udp<'a> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "Udp {} > {} len={}", self.source_port(), self.destination_port(), self.length())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::format;
+    use crate::testing;
+
+    #[test]
+    fn displays_one_line_and_debugs_the_tree() {
+        let data = testing::udp(51000, 53, b"query");
+        let packet = Udp::from_bytes(&data).unwrap();
+        assert_eq!(format!("{}", packet), "Udp 51000 > 53 len=13");
+        assert_eq!(format!("{:?}", packet), format!(
+            "    Udp\n        source_port:      51000\n        destination_port: 53\n        length:           13\n        check_sum:        {}\n",
+            packet.check_sum()));
+    }
+
+    #[test]
+    fn round_trips_through_to_bytes() {
+        let data = testing::udp(51000, 53, b"query");
+        assert_eq!(Udp::from_bytes(&data).unwrap().to_bytes(), data);
+    }
+    #[test]
+    fn returns_the_bytes_behind_a_field() {
+        let data = testing::udp(53000, 53, b"query");
+        let packet = Udp::from_bytes(&data).unwrap();
+        assert_eq!(packet.field_bytes("destination_port"), Some(&[0, 53][..]));
+        assert_eq!(packet.field_bytes("length"), Some(&[0, 13][..]));
+        assert_eq!(packet.field_bytes("payload"), None);
+    }
+}
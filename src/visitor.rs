@@ -4,161 +4,1056 @@ use chrono::{NaiveDateTime, Utc};
 use std::time::{SystemTime, UNIX_EPOCH, Duration};
 
 
-use std::io::{Error, ErrorKind};
+use std::io::{Error, ErrorKind, Write};
 use chrono::format::format;
-use pcap::Packet;
-use crate::ethernet::{self, Ethernet};
-use crate::ipv4::{self, IPv4};
+use pcap::{Packet, PacketHeader};
+use crate::ethernet::{self, Ethernet, Fcs};
+use crate::ipv4::{self, IPv4, Ipv4Address, Ipv4Option};
+use crate::ipv6::{self, IPv6};
+use crate::icmp::{self, Icmp};
+use crate::icmpv6::Icmpv6;
+use crate::gre::{self, Gre};
 use crate::tcp;
 use crate::tcp::Tcp;
+use crate::udp::{self, Udp};
+use crate::vxlan::Vxlan;
+use crate::http;
+use crate::websocket::{self, WebSocketTracker};
+use crate::app::{AppDispatch, AppProtocol, Transport};
+use crate::sctp::Sctp;
+use crate::ipsec::{self, Ah, Esp};
+use crate::pppoe::{self, PPPoE};
+use crate::mpls::{self, Mpls};
+use crate::lldp::Lldp;
+use crate::eapol::Eapol;
+use crate::radiotap::{self, Radiotap, Ieee80211};
+use crate::sll::{self, Sll2};
+use crate::reassembly::{FlowKey, SequenceTracker, WindowTracker};
+use crate::names;
+use crate::summary::SummaryVisitor;
+use crate::enrich::{IpEnricher, NoEnricher};
+use crate::checksum::{ChecksumStatus, ChecksumVerifier};
+use crate::registry::{Dissection, ProtocolKey, Registry};
+use crate::tree::{self, SharedBuffer};
+
+
+/// The layers enclosing the one being visited, so e.g. `visit_tcp` can see the IP addresses.
+/// For tunneled packets the innermost enclosing layer of each kind is kept.
+#[derive(Clone, Default)]
+pub struct Context<'a> {
+    pub header:   Option<&'a PacketHeader>,
+    pub ethernet: Option<Ethernet<'a>>,
+    pub ipv4:     Option<IPv4<'a>>,
+    pub ipv6:     Option<IPv6<'a>>,
+}
+
+impl<'a> Context<'a> {
+    pub fn new(header: &'a PacketHeader) -> Self {
+        Self { header: Some(header), ..Self::default() }
+    }
+
+    pub fn with_ethernet(&self, packet: &Ethernet<'a>) -> Self {
+        Self { ethernet: Some(packet.clone()), ..self.clone() }
+    }
+
+    pub fn with_ipv4(&self, packet: &IPv4<'a>) -> Self {
+        Self { ipv4: Some(packet.clone()), ipv6: None, ..self.clone() }
+    }
+
+    pub fn with_ipv6(&self, packet: &IPv6<'a>) -> Self {
+        Self { ipv6: Some(packet.clone()), ipv4: None, ..self.clone() }
+    }
+
+    /// The direction of the TCP connection `tcp` belongs to, from the innermost IP layer of either version.
+    pub fn flow_key(&self, tcp: &Tcp) -> Option<FlowKey> {
+        match (&self.ipv4, &self.ipv6) {
+            (Some(ip), _) => Some(FlowKey::from_ipv4(ip, tcp)),
+            (_, Some(ip)) => Some(FlowKey::from_ipv6(ip, tcp)),
+            _ => None,
+        }
+    }
+}
 
 
 pub trait Visitor<'a, T> where T: Default {
     fn visit_packet(&mut self, packet: &'a Packet) -> Result<T, Error> {
         self.visit_packet_payload(packet)
     }
-    fn visit_ethernet(&mut self, packet: &Ethernet<'a>) -> Result<T, Error> {
-        self.visit_ethernet_payload(&packet.payload()?)
+    /// Like `visit_packet`, for an Ethernet frame holding an IPv4 datagram reassembled from fragments at `offsets`.
+    fn visit_reassembled_packet(&mut self, packet: &'a Packet, offsets: &[usize]) -> Result<T, Error> {
+        self.visit_packet(packet)
+    }
+    /// Like `visit_packet`, for frames captured in monitor mode (link type 127).
+    fn visit_radiotap_packet(&mut self, packet: &'a Packet) -> Result<T, Error> {
+        let context = Context::new(packet.header);
+        self.visit_radiotap(&Radiotap::from_bytes(packet.data)?, &context)
+    }
+    /// Like `visit_packet`, for Linux cooked captures on the `any` interface (link type 276).
+    fn visit_sll2_packet(&mut self, packet: &'a Packet) -> Result<T, Error> {
+        let context = Context::new(packet.header);
+        self.visit_sll2(&Sll2::from_bytes(packet.data)?, &context)
+    }
+    fn visit_ethernet(&mut self, packet: &Ethernet<'a>, context: &Context<'a>) -> Result<T, Error> {
+        self.visit_ethernet_payload(&packet.payload()?, &context.with_ethernet(packet))
+    }
+    fn visit_ipv4(&mut self, packet: &IPv4<'a>, context: &Context<'a>) -> Result<T, Error> {
+        self.visit_ipv4_payload(&packet.payload()?, &context.with_ipv4(packet))
+    }
+    fn visit_ipv6(&mut self, packet: &IPv6<'a>, context: &Context<'a>) -> Result<T, Error> {
+        self.visit_ipv6_payload(&packet.payload()?, &context.with_ipv6(packet))
+    }
+    fn visit_pppoe(&mut self, packet: &PPPoE<'a>, context: &Context<'a>) -> Result<T, Error> {
+        self.visit_pppoe_payload(&packet.payload()?, context)
+    }
+    fn visit_mpls(&mut self, packet: &Mpls<'a>, context: &Context<'a>) -> Result<T, Error> {
+        self.visit_mpls_payload(&packet.payload()?, context)
+    }
+    fn visit_lldp(&mut self, packet: &Lldp<'a>, context: &Context<'a>) -> Result<T, Error> {
+        Ok(T::default())
+    }
+    fn visit_eapol(&mut self, packet: &Eapol<'a>, context: &Context<'a>) -> Result<T, Error> {
+        Ok(T::default())
+    }
+    fn visit_radiotap(&mut self, packet: &Radiotap<'a>, context: &Context<'a>) -> Result<T, Error> {
+        self.visit_ieee80211(&packet.payload()?, context)
+    }
+    fn visit_ieee80211(&mut self, packet: &Ieee80211<'a>, context: &Context<'a>) -> Result<T, Error> {
+        self.visit_ieee80211_payload(&packet.payload()?, context)
+    }
+    fn visit_sll2(&mut self, packet: &Sll2<'a>, context: &Context<'a>) -> Result<T, Error> {
+        self.visit_sll2_payload(&packet.payload()?, context)
+    }
+    fn visit_tcp(&mut self, packet: &Tcp<'a>, context: &Context<'a>) -> Result<T, Error> {
+        self.visit_tcp_payload(packet, context)
+    }
+    fn visit_udp(&mut self, packet: &Udp<'a>, context: &Context<'a>) -> Result<T, Error> {
+        self.visit_udp_ports(packet, context)
+    }
+    fn visit_vxlan(&mut self, packet: &Vxlan<'a>, context: &Context<'a>) -> Result<T, Error> {
+        self.visit_ethernet(&packet.payload()?, context)
     }
-    fn visit_ipv4(&mut self, packet: &IPv4<'a>) -> Result<T, Error> {
-        self.visit_ipv4_payload(&packet.payload()?)
+    fn visit_icmp(&mut self, packet: &Icmp<'a>, context: &Context<'a>) -> Result<T, Error> {
+        Ok(T::default())
+    }
+    fn visit_icmpv6(&mut self, packet: &Icmpv6<'a>, context: &Context<'a>) -> Result<T, Error> {
+        Ok(T::default())
+    }
+    fn visit_gre(&mut self, packet: &Gre<'a>, context: &Context<'a>) -> Result<T, Error> {
+        self.visit_gre_payload(&packet.payload()?, context)
+    }
+    fn visit_sctp(&mut self, packet: &Sctp<'a>, context: &Context<'a>) -> Result<T, Error> {
+        Ok(T::default())
     }
-    fn visit_tcp(&mut self, packet: &Tcp<'a>) -> Result<T, Error> {
-        todo!("Not implemented")
+    fn visit_ah(&mut self, packet: &Ah<'a>, context: &Context<'a>) -> Result<T, Error> {
+        self.visit_ah_payload(&packet.payload()?, context)
+    }
+    fn visit_esp(&mut self, packet: &Esp<'a>, context: &Context<'a>) -> Result<T, Error> {
+        Ok(T::default())
+    }
+    fn visit_http(&mut self, message: &http::Message, body: &'a [u8], context: &Context<'a>) -> Result<T, Error> {
+        self.visit_raw_payload(body)
+    }
+    /// Called with each frame completed by a segment of a connection that switched to WebSocket.
+    fn visit_websocket(&mut self, frame: &websocket::Frame, context: &Context<'a>) -> Result<T, Error> {
+        Ok(T::default())
     }
 
     fn visit_packet_payload(&mut self, packet: &'a Packet) -> Result<T, Error> {
-        match Ethernet::from_bytes(packet.data) {
-            Ok(payload) => self.visit_ethernet(&payload),
-            Err(error) => Err(error)
+        let context = Context::new(packet.header);
+        match Ethernet::from_bytes_with_fcs(packet.data, self.fcs()) {
+            Ok(payload) => self.visit_ethernet(&payload, &context),
+            Err(error) => Err(error.into())
+        }
+    }
+
+    fn visit_ethernet_payload(&mut self, payload: &ethernet::Payload<'a>, context: &Context<'a>) -> Result<T, Error> {
+        match payload {
+            ethernet::Payload::IPv4(payload) => self.visit_ipv4(&payload, context),
+            ethernet::Payload::IPv6(payload) => self.visit_ipv6(&payload, context),
+            ethernet::Payload::PPPoE(payload) => self.visit_pppoe(&payload, context),
+            ethernet::Payload::Mpls(payload)  => self.visit_mpls(&payload, context),
+            ethernet::Payload::Lldp(payload)  => self.visit_lldp(&payload, context),
+            ethernet::Payload::Eapol(payload) => self.visit_eapol(&payload, context),
+            ethernet::Payload::Unknown(raw)   => {
+                let key = context.ethernet.as_ref().map(|x| ProtocolKey::EtherType(x.ether_type_raw()));
+                self.visit_registered(key, "Ethernet", raw, context)
+            },
         }
     }
 
-    fn visit_ethernet_payload(&mut self, payload: &ethernet::Payload<'a>) -> Result<T, Error> {
+    fn visit_pppoe_payload(&mut self, payload: &pppoe::Payload<'a>, context: &Context<'a>) -> Result<T, Error> {
         match payload {
-            ethernet::Payload::IPv4(payload) => self.visit_ipv4(&payload),
-            ethernet::Payload::Invalid => Err(Error::new(ErrorKind::Other, format!("Ethernet payload is not implemented"))),
+            pppoe::Payload::IPv4(payload) => self.visit_ipv4(&payload, context),
+            pppoe::Payload::IPv6(payload) => self.visit_ipv6(&payload, context),
+            pppoe::Payload::Unknown(raw) => self.visit_unknown("PPPoE", raw),
         }
     }
 
-    fn visit_ipv4_payload(&mut self, payload: &ipv4::Payload<'a>) -> Result<T, Error> {
+    fn visit_mpls_payload(&mut self, payload: &mpls::Payload<'a>, context: &Context<'a>) -> Result<T, Error> {
         match payload {
-            ipv4::Payload::Tcp(payload) => self.visit_tcp(&payload),
-            _ => Err(Error::new(ErrorKind::Other, format!("Ipv4 payload is not implemented"))),
+            mpls::Payload::IPv4(payload) => self.visit_ipv4(&payload, context),
+            mpls::Payload::IPv6(payload) => self.visit_ipv6(&payload, context),
+            mpls::Payload::Unknown(raw) => self.visit_unknown("Mpls", raw),
         }
     }
 
+    fn visit_ieee80211_payload(&mut self, payload: &radiotap::Payload<'a>, context: &Context<'a>) -> Result<T, Error> {
+        match payload {
+            radiotap::Payload::IPv4(payload) => self.visit_ipv4(&payload, context),
+            radiotap::Payload::IPv6(payload) => self.visit_ipv6(&payload, context),
+            radiotap::Payload::Unknown(raw) => self.visit_unknown("Ieee80211", raw),
+        }
+    }
+
+    fn visit_sll2_payload(&mut self, payload: &sll::Payload<'a>, context: &Context<'a>) -> Result<T, Error> {
+        match payload {
+            sll::Payload::IPv4(payload) => self.visit_ipv4(&payload, context),
+            sll::Payload::IPv6(payload) => self.visit_ipv6(&payload, context),
+            sll::Payload::Unknown(raw) => self.visit_unknown("Sll2", raw),
+        }
+    }
+
+    fn visit_ipv4_payload(&mut self, payload: &ipv4::Payload<'a>, context: &Context<'a>) -> Result<T, Error> {
+        match payload {
+            ipv4::Payload::Tcp(payload) => self.visit_tcp(&payload, context),
+            ipv4::Payload::Udp(payload) => self.visit_udp(&payload, context),
+            ipv4::Payload::Gre(payload) => self.visit_gre(&payload, context),
+            ipv4::Payload::Sctp(payload) => self.visit_sctp(&payload, context),
+            ipv4::Payload::Ah(payload) => self.visit_ah(&payload, context),
+            ipv4::Payload::Esp(payload) => self.visit_esp(&payload, context),
+            ipv4::Payload::Icmp(payload) => self.visit_icmp(&payload, context),
+            ipv4::Payload::Unknown(raw) => {
+                let key = context.ipv4.as_ref().map(|x| ProtocolKey::IpProtocol(x.protocol_raw()));
+                self.visit_registered(key, "Ipv4", raw, context)
+            },
+        }
+    }
+
+    fn visit_ipv6_payload(&mut self, payload: &ipv6::Payload<'a>, context: &Context<'a>) -> Result<T, Error> {
+        match payload {
+            ipv6::Payload::Tcp(payload)    => self.visit_tcp(&payload, context),
+            ipv6::Payload::Udp(payload)    => self.visit_udp(&payload, context),
+            ipv6::Payload::Icmpv6(payload) => self.visit_icmpv6(&payload, context),
+            ipv6::Payload::Gre(payload)    => self.visit_gre(&payload, context),
+            ipv6::Payload::Sctp(payload)   => self.visit_sctp(&payload, context),
+            ipv6::Payload::Esp(payload)    => self.visit_esp(&payload, context),
+            ipv6::Payload::Unknown(raw)    => {
+                let key = context.ipv6.as_ref().map(|x| ProtocolKey::IpProtocol(x.upper_layer_protocol()));
+                self.visit_registered(key, "Ipv6", raw, context)
+            },
+        }
+    }
+
+    fn visit_gre_payload(&mut self, payload: &gre::Payload<'a>, context: &Context<'a>) -> Result<T, Error> {
+        match payload {
+            gre::Payload::IPv4(payload) => self.visit_ipv4(&payload, context),
+            gre::Payload::IPv6(payload) => self.visit_ipv6(&payload, context),
+            gre::Payload::Unknown(raw) => self.visit_unknown("Gre", raw),
+        }
+    }
+
+    fn visit_udp_payload(&mut self, payload: &udp::Payload<'a>, context: &Context<'a>) -> Result<T, Error> {
+        match payload {
+            udp::Payload::Vxlan(payload) => self.visit_vxlan(&payload, context),
+            udp::Payload::Unknown(raw)   => self.visit_raw_payload(raw),
+        }
+    }
+
+    fn visit_ah_payload(&mut self, payload: &ipsec::Payload<'a>, context: &Context<'a>) -> Result<T, Error> {
+        match payload {
+            ipsec::Payload::Tcp(payload)  => self.visit_tcp(&payload, context),
+            ipsec::Payload::Udp(payload)  => self.visit_udp(&payload, context),
+            ipsec::Payload::IPv4(payload) => self.visit_ipv4(&payload, context),
+            ipsec::Payload::IPv6(payload) => self.visit_ipv6(&payload, context),
+            ipsec::Payload::Unknown(raw)  => self.visit_unknown("Ah", raw),
+        }
+    }
+
+    fn visit_tcp_payload(&mut self, packet: &Tcp<'a>, context: &Context<'a>) -> Result<T, Error> {
+        let payload = packet.raw_payload();
+        if let (Some(key), Some(websockets)) = (context.flow_key(packet), self.websockets()) {
+            if let Some(frames) = websockets.push(key, payload) {
+                let mut result = T::default();
+                for frame in frames? {
+                    result = self.visit_websocket(&frame, context)?;
+                }
+                return Ok(result);
+            }
+        }
+        match self.app_dispatch().protocol(Transport::Tcp, packet.source_port(), packet.destination_port()) {
+            Some(protocol) => self.visit_application(protocol, payload, context),
+            None => self.visit_raw_payload(payload),
+        }
+    }
+
+    /// Hands the payload of `packet` to `visit_udp_payload`, or to `visit_application` if it isn't a tunnel
+    /// and its ports carry a known application protocol.
+    fn visit_udp_ports(&mut self, packet: &Udp<'a>, context: &Context<'a>) -> Result<T, Error> {
+        let payload = packet.payload()?;
+        if let udp::Payload::Unknown(raw) = payload {
+            if let Some(protocol) = self.app_dispatch().protocol(Transport::Udp, packet.source_port(), packet.destination_port()) {
+                return self.visit_application(protocol, raw, context);
+            }
+        }
+        self.visit_udp_payload(&payload, context)
+    }
+
+    /// The connections that switched to WebSocket. Without a tracker their frames are decoded like any other
+    /// payload on their ports.
+    fn websockets(&mut self) -> Option<&mut WebSocketTracker> {
+        None
+    }
+
+    /// Which application protocol each port carries.
+    fn app_dispatch(&self) -> &AppDispatch {
+        AppDispatch::builtin()
+    }
+
+    /// Whether captured Ethernet frames end with the frame check sequence, which depends on the capture setup.
+    fn fcs(&self) -> Fcs {
+        Fcs::Auto
+    }
+
+    /// Decodes the payload of a TCP segment or UDP datagram as `protocol`. Protocols without a dissector, and
+    /// payloads that don't parse, go to `visit_raw_payload`.
+    fn visit_application(&mut self, protocol: AppProtocol, payload: &'a [u8], context: &Context<'a>) -> Result<T, Error> {
+        if protocol == AppProtocol::Http {
+            if let Ok(http::Parse::Complete { message, header_size }) = http::parse(payload) {
+                return self.visit_http(&message, &payload[header_size..], context);
+            }
+        }
+        self.visit_raw_payload(payload)
+    }
+
     fn visit_raw_payload(&mut self, payload: &[u8]) -> Result<T, Error> {
         Ok(T::default())
     }
+
+    /// Called with the payload of `layer` when it carries a protocol that isn't decoded, e.g. ARP over Ethernet.
+    fn visit_unknown(&mut self, layer: &str, raw: &[u8]) -> Result<T, Error> {
+        Ok(T::default())
+    }
+
+    /// The parsers for protocols the built-in dispatch doesn't know. Without one those go to `visit_unknown`.
+    fn registry(&self) -> Option<&Registry> {
+        None
+    }
+
+    /// Called with what a parser from `registry` decoded.
+    fn visit_dissection(&mut self, dissection: &Dissection, context: &Context<'a>) -> Result<T, Error> {
+        Ok(T::default())
+    }
+
+    /// Decodes `raw` with the parser registered for `key`, or hands it to `visit_unknown` if there is none.
+    fn visit_registered(&mut self, key: Option<ProtocolKey>, layer: &str, raw: &'a [u8], context: &Context<'a>) -> Result<T, Error> {
+        let dissection = match (self.registry(), key) {
+            (Some(registry), Some(key)) => registry.dissect(key, raw),
+            _ => None,
+        };
+        match dissection {
+            Some(dissection) => self.visit_dissection(&dissection?, context),
+            None => self.visit_unknown(layer, raw),
+        }
+    }
+
+    /// Called once after the last packet, e.g. to print a report.
+    fn finish(&mut self) -> Result<T, Error> {
+        Ok(T::default())
+    }
+}
+
+
+/// Formats the capture time of the packet as `HH:MM:SS.ffffff`.
+pub fn format_timestamp(header: &PacketHeader) -> String {
+    NaiveDateTime::from_timestamp_opt(header.ts.tv_sec as i64, header.ts.tv_usec as u32 * 1000)
+        .map(|time| time.format("%H:%M:%S%.6f").to_string())
+        .unwrap_or("<invalid timestamp>".to_string())
+}
+
+
+/// Renders a TCP port with its well-known service, e.g. `443 (https)`.
+fn port(port: u16) -> String {
+    match names::tcp_port_service(port) {
+        Some(service) => format!("{} ({})", port, service),
+        None          => format!("{}", port),
+    }
 }
 
 
 pub struct Printer {
     indentation: usize,
+    windows: WindowTracker,
+    /// Set to show TCP sequence numbers relative to the ISN of their direction.
+    sequences: Option<SequenceTracker>,
+    out: Box<dyn Write>,
+    verbosity: u8,
+    enricher: Box<dyn IpEnricher>,
+    checksums: ChecksumVerifier,
+    /// Whether payloads are hex dumped at full verbosity.
+    hex_dump: bool,
+    /// Most payload bytes hex dumped per payload.
+    max_payload: Option<usize>,
+    registry: Registry,
+    /// Whether each packet is drawn as a tree of layers instead of flat lines.
+    tree: bool,
+    /// IP packets larger than this are flagged.
+    mtu: Option<usize>,
+    apps: AppDispatch,
+    fcs: Fcs,
+    websockets: WebSocketTracker,
 }
 
 impl Printer {
+    /// One line per packet.
+    pub const SUMMARY: u8 = 0;
+    /// The addresses, ports and other key fields of each layer.
+    pub const KEY_FIELDS: u8 = 1;
+    /// Every field of every layer and a hex dump of the payload.
+    pub const FULL: u8 = 2;
+
+    /// Prints everything to stdout.
     pub fn new() -> Self {
-        Self { indentation: 0 }
+        Self::with_writer(Box::new(std::io::stdout()))
+    }
+
+    pub fn with_writer(out: Box<dyn Write>) -> Self {
+        Self { indentation: 0, windows: WindowTracker::new(), sequences: None, out, verbosity: Self::FULL, enricher: Box::new(NoEnricher), checksums: ChecksumVerifier::default(), hex_dump: true, max_payload: None, registry: Registry::empty(), tree: false, mtu: None, apps: AppDispatch::new(), fcs: Fcs::Auto, websockets: WebSocketTracker::new() }
+    }
+
+    pub fn with_verbosity(self, verbosity: u8) -> Self {
+        Self { verbosity, ..self }
+    }
+
+    /// Annotates IPv4 addresses with what `enricher` knows about them.
+    pub fn with_enricher(self, enricher: Box<dyn IpEnricher>) -> Self {
+        Self { enricher, ..self }
+    }
+
+    pub fn with_checksums(self, checksums: ChecksumVerifier) -> Self {
+        Self { checksums, ..self }
+    }
+
+    /// Only prints the size of payloads, e.g. because the capture cut them off.
+    pub fn without_hex_dump(self) -> Self {
+        Self { hex_dump: false, ..self }
+    }
+
+    /// Hex dumps at most `limit` bytes of each payload, followed by how many were left out.
+    pub fn with_max_payload(self, limit: usize) -> Self {
+        Self { max_payload: Some(limit), ..self }
+    }
+
+    /// Shows TCP sequence and acknowledgment numbers relative to the SYN of their direction, with the absolute
+    /// number next to it. Falls back to absolute numbers for connections whose handshake wasn't captured.
+    pub fn with_relative_sequence_numbers(self) -> Self {
+        Self { sequences: Some(SequenceTracker::new()), ..self }
+    }
+
+    /// Decodes the protocols in `registry`, e.g. `Registry::new()` for the built-in dissectors. Without one,
+    /// protocols the payload enums don't know are only hex dumped.
+    pub fn with_registry(self, registry: Registry) -> Self {
+        Self { registry, ..self }
+    }
+
+    /// Decodes application protocols by the ports in `apps`, e.g. HTTP on a non-standard port.
+    pub fn with_app_dispatch(self, apps: AppDispatch) -> Self {
+        Self { apps, ..self }
+    }
+
+    /// Draws each packet as a tree with box-drawing characters, indenting each layer under the one that
+    /// encapsulates it, which is easier to follow for tunneled packets.
+    pub fn with_tree(self) -> Self {
+        Self { tree: true, ..self }
+    }
+
+    /// Warns about IP packets longer than `mtu`, which the wire can't carry. Captured on the host they're
+    /// usually TCP segments the NIC coalesced with large receive offload.
+    pub fn with_mtu(self, mtu: usize) -> Self {
+        Self { mtu: Some(mtu), ..self }
+    }
+
+    /// Decodes frames as ending with a frame check sequence or not instead of guessing from its CRC, so frames
+    /// with a corrupted FCS are reported as such rather than decoded with four bytes of trailing payload.
+    pub fn with_fcs(self, fcs: Fcs) -> Self {
+        Self { fcs, ..self }
+    }
+
+    pub fn into_inner(self) -> Box<dyn Write> {
+        self.out
+    }
+
+    /// Formats an address as e.g. `203.0.113.7 (US, AS64500)`.
+    fn address(&self, ip: Ipv4Address) -> String {
+        match self.enricher.lookup(ip) {
+            Some(info) => format!("{:?} ({})", ip, info),
+            None       => format!("{:?}", ip),
+        }
+    }
+
+    /// Writes the checksum line, and a warning at every verbosity if it's invalid.
+    fn checksum(&mut self, value: u16, status: ChecksumStatus) -> Result<(), Error> {
+        self.detail(format_args!("|    Check Sum             : {:#06x} ({})", value, status.name()))?;
+        if status == ChecksumStatus::Invalid {
+            writeln!(self.out, "|    [WARNING] Invalid checksum {:#06x}", value)?;
+        }
+        Ok(())
+    }
+
+    /// Writes a line that is only shown at full verbosity.
+    fn detail(&mut self, line: std::fmt::Arguments) -> Result<(), Error> {
+        if self.verbosity >= Self::FULL {
+            writeln!(self.out, "{}", line)?;
+        }
+        Ok(())
+    }
+
+    /// Writes the packet line `root` and the layers `visit` prints, between separator lines or as a tree.
+    fn framed(&mut self, root: String, visit: impl FnOnce(&mut Self) -> Result<(), Error>) -> Result<(), Error> {
+        if !self.tree {
+            writeln!(self.out, "---------- {} -----------------------------", root)?;
+            let result = visit(self);
+            writeln!(self.out, "---------------------------------------------------------------------------------")?;
+            return result;
+        }
+
+        let buffer = SharedBuffer::default();
+        let out = std::mem::replace(&mut self.out, Box::new(buffer.clone()));
+        let result = visit(self);
+        self.out = out;
+        write!(self.out, "{}", tree::render(&root, &String::from_utf8_lossy(&buffer.take())))?;
+        result
+    }
+
+    /// Writes a warning if an IP packet of `size` bytes exceeds the MTU.
+    fn oversize(&mut self, size: usize, is_tcp: bool) -> Result<(), Error> {
+        match self.mtu {
+            Some(mtu) if size > mtu && is_tcp => writeln!(self.out, "|    [WARNING] Length {} exceeds the MTU of {}, likely segments coalesced by LRO/GRO", size, mtu),
+            Some(mtu) if size > mtu           => writeln!(self.out, "|    [WARNING] Length {} exceeds the MTU of {}", size, mtu),
+            _ => Ok(()),
+        }
+    }
+
+    /// Whether the packet was cut off by the snaplen, in which case length fields are expected to exceed the data.
+    fn truncated(&self, context: &Context) -> bool {
+        context.header.is_some_and(|x| x.caplen < x.len)
+    }
+
+    /// The line printed per packet at `SUMMARY` verbosity, without the timestamp.
+    fn summarize(packet: &Packet) -> String {
+        let Ok(frame) = Ethernet::from_bytes(packet.data) else { return format!("Invalid frame, length {}", packet.header.len) };
+        match frame.payload() {
+            Ok(ethernet::Payload::IPv4(ip)) => SummaryVisitor::summarize(&ip).unwrap_or_else(|_| {
+                format!("IP {:?} > {:?}: {}, length {}", ip.source_address(), ip.destination_address(), names::ip_protocol_name(ip.protocol_raw()), ip.raw_payload().len())
+            }),
+            Ok(ethernet::Payload::IPv6(ip)) => {
+                format!("IP6 {:?} > {:?}: {}, length {}", ip.source_address(), ip.destination_address(), names::ip_protocol_name(ip.upper_layer_protocol()), ip.raw_payload().len())
+            },
+            _ => format!("{:?} > {:?}, {}, length {}", frame.source(), frame.destination(), frame.ether_type().name(), packet.header.len),
+        }
+    }
+}
+
+impl Default for Printer {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 
 impl<'a> Visitor<'a, ()> for Printer {
     fn visit_packet(&mut self, packet: &Packet) -> Result<(), Error> {
-        let timestamp = NaiveDateTime::from_timestamp_opt(packet.header.ts.tv_sec as i64, packet.header.ts.tv_usec as u32 * 1000)
-            .map(|time| time.format("%H:%M:%S%.6f"));
+        let time = format_timestamp(packet.header);
+        if self.verbosity == Self::SUMMARY {
+            return writeln!(self.out, "{} {}", time, Self::summarize(packet));
+        }
 
-        let time = timestamp.map(|x| x.to_string())
-            .unwrap_or("<invalid timestamp>".to_string());
+        self.framed(format!("Packet [ size {} ] @ {}", packet.header.len, time), |printer| printer.visit_packet_payload(packet))
+    }
 
-        println!("---------- Packet [ size {} ] @ {} -----------------------------", packet.header.len, time);
-        let result = self.visit_packet_payload(packet);
-        println!("---------------------------------------------------------------------------------");
-        result
+    fn visit_reassembled_packet(&mut self, packet: &'a Packet, offsets: &[usize]) -> Result<(), Error> {
+        let time = format_timestamp(packet.header);
+        let offsets = offsets.iter().map(|x| x.to_string()).collect::<Vec<String>>().join(", ");
+        if self.verbosity == Self::SUMMARY {
+            return writeln!(self.out, "{} {} (reassembled from fragments at offsets {})", time, Self::summarize(packet), offsets);
+        }
+
+        let root = format!("Packet [ size {}, reassembled from fragments at offsets {} ] @ {}", packet.header.len, offsets, time);
+        self.framed(root, |printer| printer.visit_packet_payload(packet))
+    }
+
+    fn visit_radiotap_packet(&mut self, packet: &'a Packet) -> Result<(), Error> {
+        let time = format_timestamp(packet.header);
+        if self.verbosity == Self::SUMMARY {
+            let line = match Radiotap::from_bytes(packet.data).and_then(|x| x.payload()) {
+                Ok(frame) => format!("802.11 {:?} > {:?}: {}, length {}", frame.source(), frame.destination(), frame.kind_name(), packet.header.len),
+                Err(_) => format!("Invalid 802.11 frame, length {}", packet.header.len),
+            };
+            return writeln!(self.out, "{} {}", time, line);
+        }
+
+        let context = Context::new(packet.header);
+        self.framed(format!("Packet [ size {} ] @ {}", packet.header.len, time), |printer| {
+            Radiotap::from_bytes(packet.data).map_err(Error::from).and_then(|radiotap| printer.visit_radiotap(&radiotap, &context))
+        })
+    }
+
+    fn visit_sll2_packet(&mut self, packet: &'a Packet) -> Result<(), Error> {
+        let time = format_timestamp(packet.header);
+        if self.verbosity == Self::SUMMARY {
+            let line = match Sll2::from_bytes(packet.data) {
+                Ok(frame) => format!("{} {}, ifindex {}, length {}", sll::packet_type_name(frame.packet_type()), names::ethertype_name(frame.protocol_type()), frame.interface_index(), packet.header.len),
+                Err(_) => format!("Invalid cooked frame, length {}", packet.header.len),
+            };
+            return writeln!(self.out, "{} {}", time, line);
+        }
+
+        let context = Context::new(packet.header);
+        self.framed(format!("Packet [ size {} ] @ {}", packet.header.len, time), |printer| {
+            Sll2::from_bytes(packet.data).map_err(Error::from).and_then(|sll2| printer.visit_sll2(&sll2, &context))
+        })
+    }
+
+    fn visit_sll2(&mut self, packet: &Sll2<'a>, context: &Context<'a>) -> Result<(), Error> {
+        writeln!(self.out, "| - Sll2 [ payload size {} ]", packet.raw_payload().len())?;
+        writeln!(self.out, "|    Protocol Type         : {} (0x{:04X})", names::ethertype_name(packet.protocol_type()), packet.protocol_type())?;
+        writeln!(self.out, "|    Interface Index       : {}", packet.interface_index())?;
+        writeln!(self.out, "|    Packet Type           : {} ({})", sll::packet_type_name(packet.packet_type()), packet.packet_type())?;
+        self.detail(format_args!("|    Address               : {:02x?}", packet.address()))?;
+        self.detail(format_args!("|    Arphrd Type           : {}", packet.arphrd_type()))?;
+        self.visit_sll2_payload(&packet.payload()?, context)
+    }
+
+    fn visit_radiotap(&mut self, packet: &Radiotap<'a>, context: &Context<'a>) -> Result<(), Error> {
+        writeln!(self.out, "| - Radiotap [ length {} ]", packet.length())?;
+        if let Some((frequency, _)) = packet.channel() {
+            writeln!(self.out, "|    Channel               : {} MHz", frequency)?;
+        }
+        if let Some(signal) = packet.antenna_signal() {
+            writeln!(self.out, "|    Signal                : {} dBm", signal)?;
+        }
+        if let Some(noise) = packet.antenna_noise() {
+            self.detail(format_args!("|    Noise                 : {} dBm", noise))?;
+        }
+        if let Some(rate) = packet.rate() {
+            self.detail(format_args!("|    Rate                  : {:.1} Mbps", rate as f64 / 2.0))?;
+        }
+        if let Some(tsft) = packet.tsft() {
+            self.detail(format_args!("|    Tsft                  : {}", tsft))?;
+        }
+        self.detail(format_args!("|    Present               : {:#010x}", packet.present()))?;
+        self.visit_ieee80211(&packet.payload()?, context)
     }
 
-    fn visit_ethernet(&mut self, packet: &Ethernet<'a>) -> Result<(), Error> {
-        println!("| - Ethernet [ payload size {} ]",  packet.raw_payload().len());
-        println!("|    Source                : {:?}", packet.source());
-        println!("|    Destination           : {:?}", packet.destination());
-        println!("|    Ether Type            : {:?}", packet.ether_type());
-        println!("|    Crc                   : {:?}", packet.crc());
-        self.visit_ethernet_payload(&packet.payload()?)
-    }
-
-    fn visit_ipv4(&mut self, packet: &IPv4<'a>) -> Result<(), Error> {
-        println!("|- Ipv4 [ payload size {} ]", packet.raw_payload().len());
-        println!("|    Header Length         : {:?}", packet.header_length());
-        println!("|    Version               : {:?}", packet.version());
-        println!("|    Reserved 1            : {:?}", packet.reserved1());
-        println!("|    Cost                  : {:?}", packet.cost());
-        println!("|    Reliability           : {:?}", packet.reliability());
-        println!("|    Throughput            : {:?}", packet.throughput());
-        println!("|    Delay                 : {:?}", packet.delay());
-        println!("|    Precedence            : {:?}", packet.precedence());
-        println!("|    Total Length          : {:?}", packet.total_length());
-        println!("|    Identification        : {:?}", packet.identification());
-        println!("|    Reserved 2            : {:?}", packet.reserved2());
-        println!("|    Df                    : {:?}", packet.df());
-        println!("|    Mf                    : {:?}", packet.mf());
-        println!("|    Fragment_offset       : {:?}", packet.fragment_offset());
-        println!("|    Time To Live          : {:?}", packet.time_to_live());
-        println!("|    Protocol              : {:?}", packet.protocol());
-        println!("|    Header Checksum       : {:?}", packet.header_checksum());
-        println!("|    Source Address        : {:?}", packet.source_address());
-        println!("|    Destination Address   : {:?}", packet.destination_address());
-        self.visit_ipv4_payload(&packet.payload()?)
-    }
-
-    fn visit_tcp(&mut self, packet: &Tcp<'a>) -> Result<(), Error> {
-        println!("| - Tcp [ payload size {} ]", packet.raw_payload().len());
-        println!("|    Source Port           : {:?}", packet.source_port());
-        println!("|    Destination Port      : {:?}", packet.destination_port());
-        println!("|    Sequence Number       : {:?}", packet.sequence_number());
-        println!("|    Acknowledgment Number : {:?}", packet.acknowledgment_number());
-        println!("|    Reserved              : {:?}", packet.reserved());
-        println!("|    Data Offset           : {:?}", packet.data_offset());
-        println!("|    Cwr                   : {:?}", packet.cwr());
-        println!("|    Ece                   : {:?}", packet.ece());
-        println!("|    Urg                   : {:?}", packet.urg());
-        println!("|    Ack                   : {:?}", packet.ack());
-        println!("|    Psh                   : {:?}", packet.psh());
-        println!("|    Rst                   : {:?}", packet.rst());
-        println!("|    Syn                   : {:?}", packet.syn());
-        println!("|    Fin                   : {:?}", packet.fin());
-        println!("|    Window Size           : {:?}", packet.window_size());
-        println!("|    Check Sum             : {:?}", packet.check_sum());
-        println!("|    Urgent Pointer        : {:?}", packet.urgent_pointer());
+    fn visit_ieee80211(&mut self, packet: &Ieee80211<'a>, context: &Context<'a>) -> Result<(), Error> {
+        writeln!(self.out, "| - Ieee80211 [ payload size {} ]", packet.raw_payload().len())?;
+        writeln!(self.out, "|    Frame Type            : {} ({}/{})", packet.kind_name(), packet.kind(), packet.subtype())?;
+        if let Some(source) = packet.source() {
+            writeln!(self.out, "|    Source                : {:?}", source)?;
+        }
+        if let Some(destination) = packet.destination() {
+            writeln!(self.out, "|    Destination           : {:?}", destination)?;
+        }
+        if let Some(bssid) = packet.bssid() {
+            writeln!(self.out, "|    Bssid                 : {:?}", bssid)?;
+        }
+        self.detail(format_args!("|    Flags                 : {:#04x}", packet.flags()))?;
+        self.detail(format_args!("|    Duration              : {:?}", packet.duration()))?;
+        if let Some(sequence) = packet.sequence_number() {
+            self.detail(format_args!("|    Sequence Number       : {:?}", sequence))?;
+        }
+        if let Some(ether_type) = packet.ether_type() {
+            writeln!(self.out, "|    Ether Type            : {} (0x{:04X})", names::ethertype_name(ether_type), ether_type)?;
+        }
+        self.visit_ieee80211_payload(&packet.payload()?, context)
+    }
+
+    fn visit_ethernet(&mut self, packet: &Ethernet<'a>, context: &Context<'a>) -> Result<(), Error> {
+        writeln!(self.out, "| - Ethernet [ payload size {} ]",  packet.raw_payload().len())?;
+        writeln!(self.out, "|    Source                : {:?}", packet.source())?;
+        writeln!(self.out, "|    Destination           : {:?}", packet.destination())?;
+        writeln!(self.out, "|    Ether Type            : {} (0x{:04X})", packet.ether_type().name(), packet.ether_type().as_u16())?;
+        if let Some(tag) = packet.vlan_tag() {
+            let (pcp, dei) = ((tag >> 13) as u8, (tag >> 12 & 1) as u8);
+            writeln!(self.out, "|    Vlan Id               : {}", tag & 0x0FFF)?;
+            writeln!(self.out, "|    Priority              : {} ({})", pcp, names::pcp_traffic_class(pcp))?;
+            self.detail(format_args!("|    Drop Eligible         : {} ({})", dei, names::dei_meaning(dei)))?;
+        }
+        match (packet.crc(), packet.verify_fcs()) {
+            (Some(crc), Some(valid)) => self.detail(format_args!("|    Crc                   : {:#010x} ({})", crc, if valid { "valid" } else { "invalid" }))?,
+            _                        => self.detail(format_args!("|    Crc                   : <absent>"))?,
+        }
+        if packet.is_jumbo() {
+            self.detail(format_args!("|    Jumbo Frame           : {} bytes above the standard maximum", packet.raw_payload().len() - ethernet::MAX_PAYLOAD_SIZE))?;
+        }
+        if !packet.padding().is_empty() {
+            self.detail(format_args!("|    Padding               : {} bytes", packet.padding().len()))?;
+        }
+        self.visit_ethernet_payload(&packet.payload()?, &context.with_ethernet(packet))
+    }
+
+    fn visit_ipv4(&mut self, packet: &IPv4<'a>, context: &Context<'a>) -> Result<(), Error> {
+        writeln!(self.out, "|- Ipv4 [ payload size {} ]", packet.raw_payload().len())?;
+        self.detail(format_args!("|    Header Length         : {:?}", packet.header_length()))?;
+        self.detail(format_args!("|    Version               : {:?}", packet.version()))?;
+        self.detail(format_args!("|    Reserved 1            : {:?}", packet.reserved1()))?;
+        self.detail(format_args!("|    Cost                  : {:?}", packet.cost()))?;
+        self.detail(format_args!("|    Reliability           : {:?}", packet.reliability()))?;
+        self.detail(format_args!("|    Throughput            : {:?}", packet.throughput()))?;
+        self.detail(format_args!("|    Delay                 : {:?}", packet.delay()))?;
+        self.detail(format_args!("|    Precedence            : {:?}", packet.precedence()))?;
+        self.detail(format_args!("|    Total Length          : {:?}", packet.total_length()))?;
+        self.detail(format_args!("|    Identification        : {:?}", packet.identification()))?;
+        self.detail(format_args!("|    Reserved 2            : {:?}", packet.reserved2()))?;
+        self.detail(format_args!("|    Df                    : {:?}", packet.df()))?;
+        self.detail(format_args!("|    Mf                    : {:?}", packet.mf()))?;
+        self.detail(format_args!("|    Fragment_offset       : {:?}", packet.fragment_offset()))?;
+        self.detail(format_args!("|    Time To Live          : {:?}", packet.time_to_live()))?;
+        writeln!(self.out, "|    Protocol              : {} ({})", names::ip_protocol_name(packet.protocol_raw()), packet.protocol_raw())?;
+        let status = self.checksums.ipv4(packet, context);
+        self.checksum(packet.header_checksum(), status)?;
+        writeln!(self.out, "|    Source Address        : {}", self.address(packet.source_address()))?;
+        writeln!(self.out, "|    Destination Address   : {}", self.address(packet.destination_address()))?;
+        for option in packet.options().filter(|x| *x != Ipv4Option::NoOperation) {
+            writeln!(self.out, "|    Option                : {}", option)?;
+        }
+        if let Err(error) = packet.validate_options() {
+            writeln!(self.out, "|    [WARNING] Malformed options: {}", error)?;
+        }
+        if let (Err(error), false) = (packet.validate_length(), self.truncated(context)) {
+            writeln!(self.out, "|    [WARNING] Length mismatch: {}", error)?;
+        }
+        self.oversize(packet.total_length() as usize, packet.protocol() == ipv4::Protocol::TCP)?;
+        self.visit_ipv4_payload(&packet.payload()?, &context.with_ipv4(packet))
+    }
+
+    fn visit_ipv6(&mut self, packet: &IPv6<'a>, context: &Context<'a>) -> Result<(), Error> {
+        writeln!(self.out, "|- Ipv6 [ payload size {} ]", packet.raw_payload().len())?;
+        self.detail(format_args!("|    Version               : {:?}", packet.version()))?;
+        self.detail(format_args!("|    Traffic Class         : {:?}", packet.traffic_class()))?;
+        self.detail(format_args!("|    Flow Label            : {:?}", packet.flow_label()))?;
+        self.detail(format_args!("|    Payload Length        : {:?}", packet.payload_length()))?;
+        writeln!(self.out, "|    Next Header           : {} ({})", names::ip_protocol_name(packet.next_header()), packet.next_header())?;
+        for header in packet.extension_headers() {
+            self.detail(format_args!("|    Extension Header      : {} ({}), {} bytes", names::ip_protocol_name(header.kind), header.kind, header.data.len()))?;
+        }
+        if let Some(fragment) = packet.fragment() {
+            writeln!(self.out, "|    Fragment              : id {:#010x}, offset {}{}", fragment.identification, fragment.offset, if fragment.more_fragments { ", more" } else { "" })?;
+        }
+        self.detail(format_args!("|    Hop Limit             : {:?}", packet.hop_limit()))?;
+        writeln!(self.out, "|    Source Address        : {:?}", packet.source_address())?;
+        writeln!(self.out, "|    Destination Address   : {:?}", packet.destination_address())?;
+        self.oversize(IPv6::HEADER_SIZE + packet.payload_length() as usize, packet.upper_layer_protocol() == ipv6::NEXT_HEADER_TCP)?;
+        self.visit_ipv6_payload(&packet.payload()?, &context.with_ipv6(packet))
+    }
+
+    fn visit_pppoe(&mut self, packet: &PPPoE<'a>, context: &Context<'a>) -> Result<(), Error> {
+        writeln!(self.out, "| - PPPoE [ payload size {} ]", packet.raw_payload().len())?;
+        self.detail(format_args!("|    Version               : {:?}", packet.version()))?;
+        self.detail(format_args!("|    Type                  : {:?}", packet.kind()))?;
+        self.detail(format_args!("|    Code                  : {:#04x}", packet.code()))?;
+        writeln!(self.out, "|    Session Id            : {:#06x}", packet.session_id())?;
+        self.detail(format_args!("|    Length                : {:?}", packet.length()))?;
+        if let Some(protocol) = packet.ppp_protocol() {
+            self.detail(format_args!("|    PPP Protocol          : {:#06x}", protocol))?;
+        }
+        self.visit_pppoe_payload(&packet.payload()?, context)
+    }
+
+    fn visit_mpls(&mut self, packet: &Mpls<'a>, context: &Context<'a>) -> Result<(), Error> {
+        writeln!(self.out, "| - Mpls [ payload size {} ]", packet.raw_payload().len())?;
+        for (i, label) in packet.labels().enumerate() {
+            writeln!(self.out, "|    Label[{}]              : {} (tc {}, ttl {}{})", i, label.label, label.traffic_class, label.ttl, if label.bottom_of_stack { ", bottom" } else { "" })?;
+        }
+        self.visit_mpls_payload(&packet.payload()?, context)
+    }
+
+    fn visit_lldp(&mut self, packet: &Lldp<'a>, context: &Context<'a>) -> Result<(), Error> {
+        writeln!(self.out, "| - Lldp")?;
+        writeln!(self.out, "|    Chassis Id            : {}", packet.chassis_id().unwrap_or_default())?;
+        writeln!(self.out, "|    Port Id               : {}", packet.port_id().unwrap_or_default())?;
+        self.detail(format_args!("|    Time To Live          : {:?}", packet.time_to_live()))?;
+        if let Some(description) = packet.port_description() {
+            self.detail(format_args!("|    Port Description      : {}", description))?;
+        }
+        if let Some(name) = packet.system_name() {
+            writeln!(self.out, "|    System Name           : {}", name)?;
+        }
+        if let Some(description) = packet.system_description() {
+            self.detail(format_args!("|    System Description    : {}", description))?;
+        }
+        Ok(())
+    }
+
+    fn visit_eapol(&mut self, packet: &Eapol<'a>, context: &Context<'a>) -> Result<(), Error> {
+        writeln!(self.out, "| - Eapol [ length {} ]", packet.length())?;
+        self.detail(format_args!("|    Version               : {:?}", packet.version()))?;
+        writeln!(self.out, "|    Type                  : {} ({})", packet.kind_name(), packet.kind())?;
+        let Some(eap) = packet.eap() else { return Ok(()) };
+        let eap = eap?;
+        writeln!(self.out, "|    Eap Code              : {} ({})", eap.code_name(), eap.code())?;
+        writeln!(self.out, "|    Eap Identifier        : {:?}", eap.identifier())?;
+        self.detail(format_args!("|    Eap Length            : {:?}", eap.length()))?;
+        if let Some(kind) = eap.kind() {
+            writeln!(self.out, "|    Eap Type              : {:?}", kind)?;
+        }
+        if let Some(identity) = eap.identity() {
+            writeln!(self.out, "|    Identity              : {}", identity)?;
+        }
+        Ok(())
+    }
+
+    fn visit_tcp(&mut self, packet: &Tcp<'a>, context: &Context<'a>) -> Result<(), Error> {
+        writeln!(self.out, "| - Tcp [ payload size {} ]", packet.raw_payload().len())?;
+        writeln!(self.out, "|    Source Port           : {}", port(packet.source_port()))?;
+        writeln!(self.out, "|    Destination Port      : {}", port(packet.destination_port()))?;
+        let key = context.flow_key(packet);
+        let (sequence, acknowledgment) = match (&mut self.sequences, key) {
+            (Some(sequences), Some(key)) => sequences.push(key, packet),
+            _ => (None, None),
+        };
+        match sequence {
+            Some(relative) => writeln!(self.out, "|    Sequence Number       : {} (relative, absolute {})", relative, packet.sequence_number())?,
+            None           => writeln!(self.out, "|    Sequence Number       : {:?}", packet.sequence_number())?,
+        }
+        match acknowledgment {
+            Some(relative) => writeln!(self.out, "|    Acknowledgment Number : {} (relative, absolute {})", relative, packet.acknowledgment_number())?,
+            None           => writeln!(self.out, "|    Acknowledgment Number : {:?}", packet.acknowledgment_number())?,
+        }
+        self.detail(format_args!("|    Reserved              : {:?}", packet.reserved()))?;
+        self.detail(format_args!("|    Data Offset           : {:?}", packet.data_offset()))?;
+        self.detail(format_args!("|    Cwr                   : {:?}", packet.cwr()))?;
+        self.detail(format_args!("|    Ece                   : {:?}", packet.ece()))?;
+        self.detail(format_args!("|    Urg                   : {:?}", packet.urg()))?;
+        self.detail(format_args!("|    Ack                   : {:?}", packet.ack()))?;
+        self.detail(format_args!("|    Psh                   : {:?}", packet.psh()))?;
+        self.detail(format_args!("|    Rst                   : {:?}", packet.rst()))?;
+        self.detail(format_args!("|    Syn                   : {:?}", packet.syn()))?;
+        self.detail(format_args!("|    Fin                   : {:?}", packet.fin()))?;
+        let window = packet.window_size() as u32;
+        match key.map(|key| self.windows.scaled_window(key, packet)) {
+            Some(scaled) if scaled != window => writeln!(self.out, "|    Window Size           : {:?} (<<{} = {})", window, (scaled / window).trailing_zeros(), scaled)?,
+            _ => writeln!(self.out, "|    Window Size           : {:?}", window)?,
+        }
+        let status = self.checksums.tcp(context);
+        self.checksum(packet.check_sum(), status)?;
+        match (packet.urgent_range(), packet.urgent_data()) {
+            (Some((start, end)), Some(data)) => {
+                writeln!(self.out, "|    Urgent Pointer        : {:?} (urgent data {}..{})", packet.urgent_pointer(), start, end)?;
+                match data.last() {
+                    Some(byte) => writeln!(self.out, "|    Urgent Byte           : {:#04x}", byte)?,
+                    None       => writeln!(self.out, "|    [WARNING] Urgent pointer doesn't point into the payload")?,
+                }
+            },
+            _ => self.detail(format_args!("|    Urgent Pointer        : {:?}", packet.urgent_pointer()))?,
+        }
+        if packet.has_stray_urgent_pointer() {
+            writeln!(self.out, "|    [WARNING] Urgent pointer {} without URG set", packet.urgent_pointer())?;
+        }
         for (i, option) in packet.options().enumerate() {
-            println!("|    Option[{}]             : {:?}", i, option);
+            self.detail(format_args!("|    Option[{}]             : {:?}", i, option))?;
         }
-        self.visit_raw_payload(packet.raw_payload())
+        self.visit_tcp_payload(packet, context)
     }
 
-    fn visit_raw_payload(&mut self, payload: &[u8]) -> Result<(), Error> {
-        println!("| - Payload  [ size {} ]", payload.len());
+    fn visit_udp(&mut self, packet: &Udp<'a>, context: &Context<'a>) -> Result<(), Error> {
+        writeln!(self.out, "| - Udp [ payload size {} ]", packet.raw_payload().len())?;
+        writeln!(self.out, "|    Source Port           : {:?}", packet.source_port())?;
+        writeln!(self.out, "|    Destination Port      : {:?}", packet.destination_port())?;
+        self.detail(format_args!("|    Length                : {:?}", packet.length()))?;
+        let status = self.checksums.udp(packet.check_sum(), context);
+        self.checksum(packet.check_sum(), status)?;
+        if let Some(ip) = &context.ipv4 {
+            if let (Err(error), false) = (packet.validate_length(ip.declared_payload_size()), self.truncated(context)) {
+                writeln!(self.out, "|    [WARNING] Length mismatch: {}", error)?;
+            }
+        }
+        self.visit_udp_ports(packet, context)
+    }
+
+    fn visit_vxlan(&mut self, packet: &Vxlan<'a>, context: &Context<'a>) -> Result<(), Error> {
+        writeln!(self.out, "| - Vxlan [ payload size {} ]", packet.raw_payload().len())?;
+        self.detail(format_args!("|    Flags                 : {:#04x}", packet.flags()))?;
+        writeln!(self.out, "|    VNI                   : {:?}", packet.vni())?;
+        self.visit_ethernet(&packet.payload()?, context)
+    }
 
+    fn visit_gre(&mut self, packet: &Gre<'a>, context: &Context<'a>) -> Result<(), Error> {
+        writeln!(self.out, "| - Gre [ payload size {} ]", packet.raw_payload().len())?;
+        self.detail(format_args!("|    Version               : {:?}", packet.version()))?;
+        writeln!(self.out, "|    Protocol Type         : {:#06x}", packet.protocol_type())?;
+        self.detail(format_args!("|    Check Sum             : {:?}", packet.check_sum()))?;
+        self.detail(format_args!("|    Key                   : {:?}", packet.key()))?;
+        self.detail(format_args!("|    Sequence Number       : {:?}", packet.sequence_number()))?;
+        self.visit_gre_payload(&packet.payload()?, context)
+    }
 
-        for chunk in payload.chunks(16) {
-            print!("|    ");
+    fn visit_sctp(&mut self, packet: &Sctp<'a>, context: &Context<'a>) -> Result<(), Error> {
+        writeln!(self.out, "| - Sctp [ payload size {} ]", packet.raw_payload().len())?;
+        writeln!(self.out, "|    Source Port           : {:?}", packet.source_port())?;
+        writeln!(self.out, "|    Destination Port      : {:?}", packet.destination_port())?;
+        self.detail(format_args!("|    Verification Tag      : {:#010x}", packet.verification_tag()))?;
+        self.detail(format_args!("|    Check Sum             : {:#010x} ({})", packet.check_sum(), if packet.verify_checksum() { "valid" } else { "invalid" }))?;
+        for (i, chunk) in packet.chunks().enumerate() {
+            self.detail(format_args!("|    Chunk[{}]              : {:?}", i, chunk))?;
+        }
+        Ok(())
+    }
+
+    fn visit_ah(&mut self, packet: &Ah<'a>, context: &Context<'a>) -> Result<(), Error> {
+        writeln!(self.out, "| - Ah [ payload size {} ]", packet.raw_payload().len())?;
+        writeln!(self.out, "|    Next Header           : {}", names::ip_protocol_name(packet.next_header()))?;
+        writeln!(self.out, "|    SPI                   : {:#010x}", packet.spi())?;
+        self.detail(format_args!("|    Sequence Number       : {:?}", packet.sequence_number()))?;
+        self.detail(format_args!("|    ICV size              : {:?}", packet.icv().len()))?;
+        self.visit_ah_payload(&packet.payload()?, context)
+    }
+
+    fn visit_esp(&mut self, packet: &Esp<'a>, context: &Context<'a>) -> Result<(), Error> {
+        writeln!(self.out, "| - Esp [ encrypted size {} ]", packet.raw_payload().len())?;
+        writeln!(self.out, "|    SPI                   : {:#010x}", packet.spi())?;
+        self.detail(format_args!("|    Sequence Number       : {:?}", packet.sequence_number()))?;
+        Ok(())
+    }
+
+    fn visit_http(&mut self, message: &http::Message, body: &'a [u8], context: &Context<'a>) -> Result<(), Error> {
+        writeln!(self.out, "| - Http [ body size {} ]", body.len())?;
+        match message {
+            http::Message::Request(request) => {
+                writeln!(self.out, "|    Method                : {}", request.method)?;
+                writeln!(self.out, "|    Target                : {}", request.target)?;
+                self.detail(format_args!("|    Version               : {}", request.version))?;
+            },
+            http::Message::Response(response) => {
+                self.detail(format_args!("|    Version               : {}", response.version))?;
+                writeln!(self.out, "|    Status                : {} {}", response.status, response.reason)?;
+            },
+        }
+        for (name, value) in message.headers().iter() {
+            self.detail(format_args!("|    {:<22}: {}", name, value))?;
+        }
+        self.visit_raw_payload(body)
+    }
+
+    fn visit_websocket(&mut self, frame: &websocket::Frame, context: &Context<'a>) -> Result<(), Error> {
+        writeln!(self.out, "| - WebSocket [ payload size {} ]", frame.payload.len())?;
+        writeln!(self.out, "|    Opcode                : {:?}", frame.opcode)?;
+        self.detail(format_args!("|    Fin                   : {:?}", frame.fin))?;
+        self.detail(format_args!("|    Masking Key           : {:02x?}", frame.masking_key))?;
+        if let Some(code) = frame.close_code() {
+            writeln!(self.out, "|    Close Code            : {}", code)?;
+        }
+        if frame.opcode == websocket::Opcode::Text {
+            writeln!(self.out, "|    Text                  : {}", String::from_utf8_lossy(&frame.payload))?;
+            return Ok(());
+        }
+        self.visit_raw_payload(&frame.payload)
+    }
+
+    fn visit_icmp(&mut self, packet: &Icmp<'a>, context: &Context<'a>) -> Result<(), Error> {
+        writeln!(self.out, "| - Icmp [ payload size {} ]", packet.raw_payload().len())?;
+        writeln!(self.out, "|    Type                  : {} ({}/{})", packet.kind_name(), packet.kind(), packet.code())?;
+        self.detail(format_args!("|    Check Sum             : {:#06x} (valid {:?})", packet.check_sum(), packet.verify_checksum()))?;
+        if let Some((identifier, sequence)) = packet.echo() {
+            writeln!(self.out, "|    Identifier            : {:?}", identifier)?;
+            writeln!(self.out, "|    Sequence Number       : {:?}", sequence)?;
+        }
+        if let Some(mtu) = packet.next_hop_mtu() {
+            writeln!(self.out, "|    Next Hop MTU          : {:?}", mtu)?;
+        }
+        match packet.quoted() {
+            Some(Ok(ip)) => {
+                writeln!(self.out, "|    Quoted Source         : {}", self.address(ip.source_address()))?;
+                writeln!(self.out, "|    Quoted Destination    : {}", self.address(ip.destination_address()))?;
+                writeln!(self.out, "|    Quoted Protocol       : {} ({})", names::ip_protocol_name(ip.protocol_raw()), ip.protocol_raw())?;
+                if let Some((source_port, destination_port)) = icmp::quoted_ports(&ip) {
+                    writeln!(self.out, "|    Quoted Source Port    : {}", port(source_port))?;
+                    writeln!(self.out, "|    Quoted Dest Port      : {}", port(destination_port))?;
+                }
+                self.detail(format_args!("|    Quoted Length         : {:?}", ip.total_length()))?;
+            },
+            Some(Err(error)) => writeln!(self.out, "|    [WARNING] Malformed quoted packet: {}", error)?,
+            None => {},
+        }
+        Ok(())
+    }
+
+    fn visit_icmpv6(&mut self, packet: &Icmpv6<'a>, context: &Context<'a>) -> Result<(), Error> {
+        writeln!(self.out, "| - Icmpv6 [ payload size {} ]", packet.raw_payload().len())?;
+        writeln!(self.out, "|    Type                  : {:?}", packet.kind())?;
+        writeln!(self.out, "|    Code                  : {:?}", packet.code())?;
+        self.detail(format_args!("|    Check Sum             : {:?}", packet.check_sum()))?;
+        if let Some(ipv6) = &context.ipv6 {
+            self.detail(format_args!("|    Check Sum Valid       : {:?}", packet.verify_checksum(&ipv6.source_address(), &ipv6.destination_address())))?;
+        }
+        writeln!(self.out, "|    Message               : {:?}", packet.message())?;
+        for (i, option) in packet.options().enumerate() {
+            self.detail(format_args!("|    Option[{}]             : {:?}", i, option))?;
+        }
+        Ok(())
+    }
+
+    fn finish(&mut self) -> Result<(), Error> {
+        self.out.flush()
+    }
+
+    fn registry(&self) -> Option<&Registry> {
+        Some(&self.registry)
+    }
+
+    fn app_dispatch(&self) -> &AppDispatch {
+        &self.apps
+    }
+
+    fn fcs(&self) -> Fcs {
+        self.fcs
+    }
+
+    fn websockets(&mut self) -> Option<&mut WebSocketTracker> {
+        Some(&mut self.websockets)
+    }
+
+    fn visit_dissection(&mut self, dissection: &Dissection, context: &Context<'a>) -> Result<(), Error> {
+        writeln!(self.out, "| - {}", dissection.name)?;
+        for (name, value) in &dissection.fields {
+            writeln!(self.out, "|    {:<22}: {}", name, value)?;
+        }
+        Ok(())
+    }
+
+    fn visit_unknown(&mut self, layer: &str, raw: &[u8]) -> Result<(), Error> {
+        writeln!(self.out, "| - Unknown {} payload", layer)?;
+        self.visit_raw_payload(raw)
+    }
+
+    fn visit_raw_payload(&mut self, payload: &[u8]) -> Result<(), Error> {
+        writeln!(self.out, "| - Payload  [ size {} ]", payload.len())?;
+        if self.verbosity < Self::FULL || !self.hex_dump {
+            return Ok(());
+        }
+
+        let shown = payload.len().min(self.max_payload.unwrap_or(usize::MAX));
+        for chunk in payload[..shown].chunks(16) {
+            write!(self.out, "|    ")?;
 
             for bytes in chunk.chunks(4) {
                 for byte in bytes {
-                    print!("{:02x} ", *byte);
+                    write!(self.out, "{:02x} ", *byte)?;
                 }
-                print!(" ")
+                write!(self.out, " ")?;
             }
 
-            print!("    ");
+            write!(self.out, "    ")?;
 
             for byte in chunk {
                 let character = *byte as char;
                 let character = if character.is_whitespace() { '.' } else { character };
-                print!("{}", character);
+                write!(self.out, "{}", character)?;
             }
-            println!();
+            writeln!(self.out)?;
+        }
+
+        if shown < payload.len() {
+            writeln!(self.out, "|    ... ({} more bytes)", payload.len() - shown)?;
         }
 
         Ok(())
@@ -166,3 +1061,479 @@ impl<'a> Visitor<'a, ()> for Printer {
 }
 
 
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::{Ipv4Builder, TcpBuilder, TCP_ACK, TCP_SYN, TCP_URG};
+    use crate::ipv4::Protocol;
+    use crate::raw::visit_frame;
+    use crate::shared::{checksum_add, checksum_finish};
+    use crate::testing::{self, ip, MAC_A};
+
+    /// Keeps what `visit_tcp` sees of the enclosing layers.
+    #[derive(Default)]
+    struct Addresses {
+        mac: Option<[u8; 6]>,
+        ip: Option<(Ipv4Address, Ipv4Address)>,
+        timestamp: Option<i64>,
+    }
+
+    impl<'a> Visitor<'a, ()> for Addresses {
+        fn visit_tcp(&mut self, _packet: &Tcp<'a>, context: &Context<'a>) -> Result<(), Error> {
+            self.mac = context.ethernet.as_ref().map(|x| *x.source().bytes());
+            self.ip = context.ipv4.as_ref().map(|x| (x.source_address(), x.destination_address()));
+            self.timestamp = context.header.map(|x| x.ts.tv_sec as i64);
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn visit_tcp_sees_the_enclosing_layers() {
+        let frame = testing::tcp_frame(ip(10, 0, 0, 1), ip(10, 0, 0, 2), &TcpBuilder::new(51000, 80).with_flags(TCP_SYN));
+        let mut visitor = Addresses::default();
+        visit_frame(&mut visitor, &frame, Duration::from_secs(42)).unwrap();
+
+        assert_eq!(visitor.mac, Some(MAC_A));
+        assert_eq!(visitor.ip, Some((ip(10, 0, 0, 1), ip(10, 0, 0, 2))));
+        assert_eq!(visitor.timestamp, Some(42));
+    }
+
+    #[test]
+    fn context_keeps_the_innermost_ip_of_a_tunnel() {
+        let inner = Ipv4Builder::new(ip(192, 168, 0, 1), ip(192, 168, 0, 2)).with_tcp(&TcpBuilder::new(51000, 22)).build();
+        let mut gre = Vec::from([0, 0, 0x08, 0x00]);
+        gre.extend_from_slice(&inner);
+        let outer = Ipv4Builder::new(ip(10, 0, 0, 1), ip(10, 0, 0, 2)).with_payload(ipv4::Protocol::GRE, &gre).build();
+
+        let mut visitor = Addresses::default();
+        visit_frame(&mut visitor, &testing::ethernet(testing::ETHER_TYPE_IPV4, &outer), Duration::ZERO).unwrap();
+        assert_eq!(visitor.ip, Some((ip(192, 168, 0, 1), ip(192, 168, 0, 2))));
+    }
+
+    #[test]
+    fn printer_decodes_frames_with_the_configured_fcs() {
+        let frame = testing::tcp_frame(ip(10, 0, 0, 1), ip(10, 0, 0, 2), &TcpBuilder::new(51000, 80));
+        let mut with_fcs = frame.clone();
+        with_fcs.extend_from_slice(&(crate::shared::crc32(&frame) ^ 1).to_le_bytes());
+
+        let buffer = SharedBuffer::default();
+        let mut printer = Printer::with_writer(Box::new(buffer.clone())).with_fcs(Fcs::Present);
+        visit_frame(&mut printer, &with_fcs, Duration::ZERO).unwrap();
+        let text = String::from_utf8(buffer.take()).unwrap();
+        assert!(text.contains("(invalid)"), "{}", text);
+        assert!(text.contains(&format!("payload size {}", frame.len() - ethernet::HEADER_SIZE)), "{}", text);
+    }
+
+    #[test]
+    fn printer_warns_about_malformed_options() {
+        let packet = testing::ipv4_with_options(&[7, 12, 4, 0, 0, 0, 0, 0]);
+        let buffer = SharedBuffer::default();
+        let mut printer = Printer::with_writer(Box::new(buffer.clone()));
+        visit_frame(&mut printer, &testing::ethernet(testing::ETHER_TYPE_IPV4, &packet), Duration::ZERO).unwrap();
+        let text = String::from_utf8(buffer.take()).unwrap();
+        assert!(text.contains("[WARNING] Malformed options"), "{}", text);
+    }
+
+    #[test]
+    fn printer_shows_the_mpls_labels() {
+        let inner = Ipv4Builder::new(ip(10, 0, 0, 1), ip(10, 0, 0, 2)).with_tcp(&TcpBuilder::new(51000, 80)).build();
+        let mut stack = Vec::new();
+        stack.extend_from_slice(&(16u32 << 12 | 64).to_be_bytes());
+        stack.extend_from_slice(&(1000u32 << 12 | 1 << 8 | 63).to_be_bytes());
+        stack.extend_from_slice(&inner);
+
+        let buffer = SharedBuffer::default();
+        let mut printer = Printer::with_writer(Box::new(buffer.clone()));
+        visit_frame(&mut printer, &testing::ethernet(0x8847, &stack), Duration::ZERO).unwrap();
+        let text = String::from_utf8(buffer.take()).unwrap();
+        assert!(text.contains("Label[0]              : 16 (tc 0, ttl 64)"), "{}", text);
+        assert!(text.contains("Label[1]              : 1000 (tc 0, ttl 63, bottom)"), "{}", text);
+        assert!(text.contains("- Tcp"), "{}", text);
+    }
+
+    /// Keeps the layers whose payload reached `visit_unknown`.
+    #[derive(Default)]
+    struct Unknowns {
+        layers: Vec<(String, usize)>,
+    }
+
+    impl<'a> Visitor<'a, ()> for Unknowns {
+        fn visit_unknown(&mut self, layer: &str, raw: &[u8]) -> Result<(), Error> {
+            self.layers.push((layer.to_string(), raw.len()));
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn arp_reaches_visit_unknown_instead_of_an_error() {
+        let frame = testing::ethernet(0x0806, &[0; 28]);
+        let mut visitor = Unknowns::default();
+        visit_frame(&mut visitor, &frame, Duration::ZERO).unwrap();
+        assert_eq!(visitor.layers, [("Ethernet".to_string(), 28)]);
+    }
+
+    #[test]
+    fn an_unknown_ip_protocol_reaches_visit_unknown() {
+        let packet = Ipv4Builder::new(ip(10, 0, 0, 1), ip(10, 0, 0, 2)).with_payload(ipv4::Protocol::Other(253), &[1, 2, 3]).build();
+        let mut visitor = Unknowns::default();
+        visit_frame(&mut visitor, &testing::ethernet(testing::ETHER_TYPE_IPV4, &packet), Duration::ZERO).unwrap();
+        assert_eq!(visitor.layers, [("Ipv4".to_string(), 3)]);
+    }
+
+    #[test]
+    fn printer_decodes_protocols_from_its_registry() {
+        use crate::error::ParseError;
+        use crate::registry::{ProtocolParser, ProtocolKey};
+
+        struct Dummy;
+        impl ProtocolParser for Dummy {
+            fn name(&self) -> &'static str { "Dummy" }
+            fn parse(&self, data: &[u8]) -> Result<Dissection, ParseError> {
+                Ok(Dissection { name: self.name(), fields: vec![("Length", data.len().to_string())] })
+            }
+        }
+
+        let print = |registry: Registry| {
+            let buffer = SharedBuffer::default();
+            let mut printer = Printer::with_writer(Box::new(buffer.clone())).with_verbosity(Printer::KEY_FIELDS).with_registry(registry);
+            let packet = Ipv4Builder::new(ip(10, 0, 0, 1), ip(10, 0, 0, 2)).with_payload(ipv4::Protocol::Other(253), &[1, 2, 3]).build();
+            visit_frame(&mut printer, &testing::ethernet(testing::ETHER_TYPE_IPV4, &packet), Duration::ZERO).unwrap();
+            String::from_utf8(buffer.take()).unwrap()
+        };
+
+        let mut registry = Registry::empty();
+        registry.register(ProtocolKey::IpProtocol(253), Box::new(Dummy));
+        let text = print(registry);
+        assert!(text.contains("| - Dummy\n|    Length                : 3\n"), "{}", text);
+
+        let text = print(Registry::empty());
+        assert!(text.contains("| - Unknown Ipv4 payload\n"), "{}", text);
+    }
+
+    const CLIENT: [u8; 16] = [0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1];
+    const SERVER: [u8; 16] = [0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2];
+
+    /// Prints a handshake over IPv6 where both sides announce a window scale, then one more segment.
+    fn print_ipv6_handshake(relative: bool) -> String {
+        let buffer = SharedBuffer::default();
+        let mut printer = Printer::with_writer(Box::new(buffer.clone()));
+        if relative {
+            printer = printer.with_relative_sequence_numbers();
+        }
+        let segments = [
+            (CLIENT, SERVER, testing::tcp_with_options(&TcpBuilder::new(51000, 80).with_flags(TCP_SYN).with_sequence_number(1000).with_window_size(64240), &[1, 3, 3, 7])),
+            (SERVER, CLIENT, testing::tcp_with_options(&TcpBuilder::new(80, 51000).with_flags(TCP_SYN | TCP_ACK).with_sequence_number(5000).with_acknowledgment_number(1001).with_window_size(65160), &[1, 3, 3, 7])),
+            (CLIENT, SERVER, testing::tcp_with_options(&TcpBuilder::new(51000, 80).with_flags(TCP_ACK).with_sequence_number(1001).with_acknowledgment_number(5001).with_window_size(512), &[])),
+        ];
+        for (source, destination, segment) in segments {
+            buffer.take();
+            visit_frame(&mut printer, &testing::ipv6_tcp_frame(source, destination, &segment), Duration::ZERO).unwrap();
+        }
+        String::from_utf8(buffer.take()).unwrap()
+    }
+
+    #[test]
+    fn printer_scales_windows_over_ipv6() {
+        let text = print_ipv6_handshake(false);
+        assert!(text.contains("Window Size           : 512 (<<7 = 65536)"), "{}", text);
+    }
+
+    #[test]
+    fn printer_shows_relative_sequence_numbers_over_ipv6() {
+        let text = print_ipv6_handshake(true);
+        assert!(text.contains("Sequence Number       : 1 (relative, absolute 1001)"), "{}", text);
+        assert!(text.contains("Acknowledgment Number : 1 (relative, absolute 5001)"), "{}", text);
+    }
+
+    #[test]
+    fn printer_renders_into_a_buffer() {
+        let buffer = SharedBuffer::default();
+        let mut printer = Printer::with_writer(Box::new(buffer.clone())).with_verbosity(Printer::KEY_FIELDS).without_hex_dump();
+        let frame = testing::udp_frame(ip(10, 0, 0, 1), 53000, ip(10, 0, 0, 2), 9999, b"hi");
+        visit_frame(&mut printer, &frame, Duration::ZERO).unwrap();
+        let text = String::from_utf8(buffer.take()).unwrap();
+        assert_eq!(text, concat!(
+            "---------- Packet [ size 44 ] @ 00:00:00.000000 -----------------------------\n",
+            "| - Ethernet [ payload size 30 ]\n",
+            "|    Source                : 2:0:0:0:0:10\n",
+            "|    Destination           : 2:0:0:0:0:11\n",
+            "|    Ether Type            : IPv4 (0x0800)\n",
+            "|- Ipv4 [ payload size 10 ]\n",
+            "|    Protocol              : UDP (17)\n",
+            "|    Source Address        : 10.0.0.1\n",
+            "|    Destination Address   : 10.0.0.2\n",
+            "| - Udp [ payload size 2 ]\n",
+            "|    Source Port           : 53000\n",
+            "|    Destination Port      : 9999\n",
+            "| - Payload  [ size 2 ]\n",
+            "---------------------------------------------------------------------------------\n",
+        ));
+    }
+
+    #[test]
+    fn printer_decodes_the_headers_of_a_frame_cut_off_by_the_snaplen() {
+        let payload = vec![b'x'; 1400];
+        let frame = testing::tcp_frame(ip(10, 0, 0, 1), ip(10, 0, 0, 2), &TcpBuilder::new(51000, 80).with_flags(TCP_ACK).with_payload(&payload));
+        let mut header = testing::header(0, frame.len());
+        header.caplen = 96;
+
+        let buffer = SharedBuffer::default();
+        let mut printer = Printer::with_writer(Box::new(buffer.clone())).without_hex_dump();
+        printer.visit_packet(&Packet::new(&header, &frame[..96])).unwrap();
+        let text = String::from_utf8(buffer.take()).unwrap();
+
+        assert!(text.contains("|    Destination Port      : 80 (http)\n"), "{}", text);
+        assert!(text.contains("(not verified)"), "{}", text);
+        assert!(text.contains("| - Payload  [ size 42 ]\n"), "{}", text);
+        assert!(!text.contains("WARNING"), "{}", text);
+        assert!(!text.contains("78 78 78 78"), "{}", text);
+    }
+
+    #[test]
+    fn printer_caps_the_payload_hex_dump() {
+        let dump = |size: usize| {
+            let buffer = SharedBuffer::default();
+            let mut printer = Printer::with_writer(Box::new(buffer.clone())).with_max_payload(32);
+            let frame = testing::udp_frame(ip(10, 0, 0, 1), 53000, ip(10, 0, 0, 2), 9999, &vec![0xAB; size]);
+            visit_frame(&mut printer, &frame, Duration::ZERO).unwrap();
+            String::from_utf8(buffer.take()).unwrap()
+        };
+
+        let large = dump(100);
+        assert_eq!(large.lines().filter(|x| x.starts_with("|    ab ")).count(), 2, "{}", large);
+        assert!(large.contains("|    ... (68 more bytes)\n"), "{}", large);
+
+        let small = dump(20);
+        assert_eq!(small.lines().filter(|x| x.starts_with("|    ab ")).count(), 2, "{}", small);
+        assert!(!small.contains("more bytes"), "{}", small);
+    }
+
+    #[test]
+    fn printer_warns_about_a_total_length_beyond_the_captured_data() {
+        let print = |frame: &[u8]| {
+            let buffer = SharedBuffer::default();
+            let mut printer = Printer::with_writer(Box::new(buffer.clone())).with_verbosity(Printer::KEY_FIELDS);
+            visit_frame(&mut printer, frame, Duration::ZERO).unwrap();
+            String::from_utf8(buffer.take()).unwrap()
+        };
+        let mut frame = testing::udp_frame(ip(10, 0, 0, 1), 53000, ip(10, 0, 0, 2), 53, b"query");
+        assert!(!print(&frame).contains("Length mismatch"));
+
+        frame[16..18].copy_from_slice(&533u16.to_be_bytes());
+        let text = print(&frame);
+        assert!(text.contains("|    [WARNING] Length mismatch: Ipv4 total length too big, expected at most 33, got 533\n"), "{}", text);
+    }
+
+    #[test]
+    fn printer_decodes_websocket_frames_after_the_upgrade() {
+        let buffer = SharedBuffer::default();
+        let mut printer = Printer::with_writer(Box::new(buffer.clone())).with_verbosity(Printer::KEY_FIELDS);
+        let (client, server) = (ip(10, 0, 0, 1), ip(10, 0, 0, 2));
+        let mut segment = |from_client: bool, payload: &[u8]| {
+            let frame = match from_client {
+                true  => testing::tcp_frame(client, server, &TcpBuilder::new(51000, 80).with_flags(TCP_ACK).with_payload(payload)),
+                false => testing::tcp_frame(server, client, &TcpBuilder::new(80, 51000).with_flags(TCP_ACK).with_payload(payload)),
+            };
+            visit_frame(&mut printer, &frame, Duration::ZERO).unwrap();
+            String::from_utf8(buffer.take()).unwrap()
+        };
+
+        let text = segment(true, b"GET /chat HTTP/1.1\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\r\n");
+        assert!(text.contains("|    Method                : GET\n"), "{}", text);
+        let text = segment(false, b"HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\r\n");
+        assert!(text.contains("|    Status                : 101 Switching Protocols\n"), "{}", text);
+
+        // A masked "Hello" split across two segments.
+        let hello = [0x81, 0x85, 0x37, 0xfa, 0x21, 0x3d, 0x7f, 0x9f, 0x4d, 0x51, 0x58];
+        let text = segment(true, &hello[..5]);
+        assert!(!text.contains("WebSocket") && !text.contains("Http"), "{}", text);
+        let text = segment(true, &hello[5..]);
+        assert!(text.contains("| - WebSocket [ payload size 5 ]\n|    Opcode                : Text\n|    Text                  : Hello\n"), "{}", text);
+
+        let text = segment(false, &[0x88, 0x02, 0x03, 0xE8]);
+        assert!(text.contains("|    Opcode                : Close\n|    Close Code            : 1000\n"), "{}", text);
+    }
+
+    #[test]
+    fn printer_names_the_priority_and_drop_eligibility_of_a_vlan_tag() {
+        let packet = Ipv4Builder::new(ip(10, 0, 0, 1), ip(10, 0, 0, 2)).with_tcp(&TcpBuilder::new(51000, 80).with_flags(TCP_SYN)).build();
+        // Priority 5 and drop eligible in VLAN 100.
+        let mut tagged = vec![0xB0, 0x64, 0x08, 0x00];
+        tagged.extend_from_slice(&packet);
+        let frame = testing::ethernet(0x8100, &tagged);
+
+        let buffer = SharedBuffer::default();
+        let mut printer = Printer::with_writer(Box::new(buffer.clone())).with_verbosity(Printer::FULL);
+        visit_frame(&mut printer, &frame, Duration::ZERO).unwrap();
+        let text = String::from_utf8(buffer.take()).unwrap();
+        assert!(text.contains("|    Vlan Id               : 100\n|    Priority              : 5 (Voice)\n|    Drop Eligible         : 1 (may be dropped first)\n"), "{}", text);
+    }
+
+    #[test]
+    fn printer_draws_a_vlan_tagged_segment_as_a_tree() {
+        let packet = Ipv4Builder::new(ip(10, 0, 0, 1), ip(10, 0, 0, 2)).with_tcp(&TcpBuilder::new(51000, 80).with_flags(TCP_SYN)).build();
+        // Priority 5 in VLAN 100.
+        let mut tagged = vec![0xA0, 0x64, 0x08, 0x00];
+        tagged.extend_from_slice(&packet);
+        let frame = testing::ethernet(0x8100, &tagged);
+
+        let buffer = SharedBuffer::default();
+        let mut printer = Printer::with_writer(Box::new(buffer.clone())).with_verbosity(Printer::KEY_FIELDS).with_tree();
+        visit_frame(&mut printer, &frame, Duration::ZERO).unwrap();
+        assert_eq!(String::from_utf8(buffer.take()).unwrap(), "\
+Packet [ size 58 ] @ 00:00:00.000000
+└─ Ethernet [ payload size 44 ]
+   ├─ Source                : 2:0:0:0:0:10
+   ├─ Destination           : 2:0:0:0:0:11
+   ├─ Ether Type            : 802.1Q VLAN (0x8100)
+   ├─ Vlan Id               : 100
+   ├─ Priority              : 5 (Voice)
+   └─ Ipv4 [ payload size 20 ]
+      ├─ Protocol              : TCP (6)
+      ├─ Source Address        : 10.0.0.1
+      ├─ Destination Address   : 10.0.0.2
+      └─ Tcp [ payload size 0 ]
+         ├─ Source Port           : 51000
+         ├─ Destination Port      : 80 (http)
+         ├─ Sequence Number       : 0
+         ├─ Acknowledgment Number : 0
+         ├─ Window Size           : 65535
+         └─ Payload  [ size 0 ]
+");
+    }
+
+    #[test]
+    fn printer_flags_packets_beyond_the_mtu() {
+        let print = |frame: &[u8], mtu: Option<usize>| {
+            let buffer = SharedBuffer::default();
+            let mut printer = Printer::with_writer(Box::new(buffer.clone())).with_verbosity(Printer::KEY_FIELDS);
+            if let Some(mtu) = mtu {
+                printer = printer.with_mtu(mtu);
+            }
+            visit_frame(&mut printer, frame, Duration::ZERO).unwrap();
+            String::from_utf8(buffer.take()).unwrap()
+        };
+        let segment = |size: usize| testing::tcp_frame(ip(10, 0, 0, 1), ip(10, 0, 0, 2), &TcpBuilder::new(51000, 80).with_flags(TCP_ACK).with_payload(&vec![0; size - 40]));
+
+        let text = print(&segment(9000), Some(1500));
+        assert!(text.contains("|    [WARNING] Length 9000 exceeds the MTU of 1500, likely segments coalesced by LRO/GRO\n"), "{}", text);
+        assert!(!print(&segment(1500), Some(1500)).contains("WARNING"));
+        assert!(!print(&segment(9000), Some(9000)).contains("WARNING"));
+        assert!(!print(&segment(9000), None).contains("WARNING"));
+
+        let datagram = testing::udp_frame(ip(10, 0, 0, 1), 53000, ip(10, 0, 0, 2), 4000, &vec![0; 2000 - 28]);
+        let text = print(&datagram, Some(1500));
+        assert!(text.contains("|    [WARNING] Length 2000 exceeds the MTU of 1500\n"), "{}", text);
+    }
+
+    #[test]
+    fn printer_shows_urgent_data_and_warns_about_a_stray_urgent_pointer() {
+        let print = |tcp: TcpBuilder| {
+            let buffer = SharedBuffer::default();
+            let mut printer = Printer::with_writer(Box::new(buffer.clone())).with_verbosity(Printer::KEY_FIELDS);
+            visit_frame(&mut printer, &testing::tcp_frame(ip(10, 0, 0, 1), ip(10, 0, 0, 2), &tcp), Duration::ZERO).unwrap();
+            String::from_utf8(buffer.take()).unwrap()
+        };
+
+        let text = print(TcpBuilder::new(51000, 23).with_flags(TCP_ACK | TCP_URG).with_sequence_number(1000).with_urgent_pointer(3).with_payload(b"ab\xFFcd"));
+        assert!(text.contains("|    Urgent Pointer        : 3 (urgent data 1000..1003)\n|    Urgent Byte           : 0xff\n"), "{}", text);
+        assert!(!text.contains("[WARNING]"), "{}", text);
+
+        let text = print(TcpBuilder::new(51000, 23).with_flags(TCP_ACK).with_urgent_pointer(3).with_payload(b"abc"));
+        assert!(text.contains("|    [WARNING] Urgent pointer 3 without URG set\n"), "{}", text);
+        assert!(!text.contains("Urgent Byte"), "{}", text);
+    }
+
+    #[test]
+    fn printer_decodes_http_on_the_ports_of_its_app_dispatch() {
+        let print = |apps: AppDispatch| {
+            let tcp = TcpBuilder::new(51000, 8080).with_flags(TCP_ACK).with_payload(b"GET /index.html HTTP/1.1\r\nHost: example.com\r\n\r\n");
+            let buffer = SharedBuffer::default();
+            let mut printer = Printer::with_writer(Box::new(buffer.clone())).with_verbosity(Printer::KEY_FIELDS).with_app_dispatch(apps);
+            visit_frame(&mut printer, &testing::tcp_frame(ip(10, 0, 0, 1), ip(10, 0, 0, 2), &tcp), Duration::ZERO).unwrap();
+            String::from_utf8(buffer.take()).unwrap()
+        };
+
+        let text = print(AppDispatch::new().without_port(Transport::Tcp, 8080));
+        assert!(!text.contains("Http"), "{}", text);
+        let text = print(AppDispatch::empty().with_port(AppProtocol::Http, 8080));
+        assert!(text.contains("| - Http [ body size 0 ]\n|    Method                : GET\n|    Target                : /index.html\n"), "{}", text);
+    }
+
+    #[test]
+    fn printer_shows_the_ports_quoted_by_a_fragmentation_needed_message() {
+        let tcp = TcpBuilder::new(51000, 443).with_flags(TCP_ACK).with_payload(&[0; 1460]);
+        let original = Ipv4Builder::new(ip(10, 0, 0, 1), ip(10, 0, 0, 2)).with_tcp(&tcp).build();
+        // Destination unreachable, fragmentation needed with a next hop MTU of 1400.
+        let mut message = vec![3, 4, 0, 0, 0, 0, 0x05, 0x78];
+        message.extend_from_slice(&original[..20 + 8]);
+        let checksum = checksum_finish(checksum_add(0, &message));
+        message[2..4].copy_from_slice(&checksum.to_be_bytes());
+        let packet = Ipv4Builder::new(ip(10, 0, 0, 254), ip(10, 0, 0, 1)).with_payload(Protocol::ICMP, &message).build();
+
+        let buffer = SharedBuffer::default();
+        let mut printer = Printer::with_writer(Box::new(buffer.clone())).with_verbosity(Printer::KEY_FIELDS);
+        visit_frame(&mut printer, &testing::ethernet(testing::ETHER_TYPE_IPV4, &packet), Duration::ZERO).unwrap();
+        let text = String::from_utf8(buffer.take()).unwrap();
+        assert!(text.contains("\
+|    Type                  : Destination Unreachable (fragmentation needed) (3/4)
+|    Next Hop MTU          : 1400
+|    Quoted Source         : 10.0.0.1
+|    Quoted Destination    : 10.0.0.2
+|    Quoted Protocol       : TCP (6)
+|    Quoted Source Port    : 51000
+|    Quoted Dest Port      : 443 (https)
+"), "{}", text);
+    }
+
+    /// What the printer renders for a UDP datagram holding `hello` at `verbosity`.
+    fn print_at(verbosity: u8) -> String {
+        let buffer = SharedBuffer::default();
+        let mut printer = Printer::with_writer(Box::new(buffer.clone())).with_verbosity(verbosity);
+        let frame = testing::udp_frame(ip(10, 0, 0, 1), 53000, ip(10, 0, 0, 2), 9999, b"hello");
+        visit_frame(&mut printer, &frame, Duration::ZERO).unwrap();
+        String::from_utf8(buffer.take()).unwrap()
+    }
+
+    #[test]
+    fn printer_summarizes_a_packet_in_one_line() {
+        let text = print_at(Printer::SUMMARY);
+        assert_eq!(text.lines().count(), 1, "{}", text);
+        assert!(text.contains("10.0.0.1"), "{}", text);
+    }
+
+    #[test]
+    fn printer_hex_dumps_the_payload_only_at_full_verbosity() {
+        let full = print_at(Printer::FULL);
+        assert!(full.contains("68 65 6c 6c  6f"), "{}", full);
+        assert!(full.contains("Time To Live"), "{}", full);
+
+        let key_fields = print_at(Printer::KEY_FIELDS);
+        assert!(!key_fields.contains("68 65 6c 6c"), "{}", key_fields);
+        assert!(key_fields.lines().count() < full.lines().count());
+    }
+
+    /// Knows the addresses in its map.
+    struct FakeEnricher(std::collections::HashMap<Ipv4Address, crate::enrich::IpInfo>);
+
+    impl IpEnricher for FakeEnricher {
+        fn lookup(&self, ip: Ipv4Address) -> Option<crate::enrich::IpInfo> {
+            self.0.get(&ip).cloned()
+        }
+    }
+
+    #[test]
+    fn printer_annotates_addresses_from_the_enricher() {
+        let info = crate::enrich::IpInfo { country: Some("US".to_string()), asn: Some(64500) };
+        let enricher = FakeEnricher([(ip(203, 0, 113, 7), info)].into_iter().collect());
+        let buffer = SharedBuffer::default();
+        let mut printer = Printer::with_writer(Box::new(buffer.clone())).with_enricher(Box::new(enricher));
+        let frame = testing::tcp_frame(ip(203, 0, 113, 7), ip(10, 0, 0, 2), &TcpBuilder::new(443, 51000));
+        visit_frame(&mut printer, &frame, Duration::ZERO).unwrap();
+        let text = String::from_utf8(buffer.take()).unwrap();
+        assert!(text.contains("Source Address        : 203.0.113.7 (US, AS64500)"), "{}", text);
+        assert!(text.contains("Destination Address   : 10.0.0.2\n"), "{}", text);
+    }
+}
@@ -0,0 +1,156 @@
+/*
+https://www.rfc-editor.org/rfc/rfc7348
+*/
+
+use core::ops::Range;
+use crate::shared::{bytes_of_bits, MAX_ENCAPSULATION_DEPTH};
+use crate::ethernet;
+use crate::endian::be_u24;
+use crate::error::ParseError;
+
+
+/// The UDP destination port assigned to VXLAN.
+pub const PORT: u16 = 4789;
+
+
+#[derive(Clone)]
+pub struct Vxlan<'a> {
+    data: &'a [u8],
+    /// Number of tunnels this header is nested in.
+    depth: usize,
+}
+
+impl<'a> Vxlan<'a> {
+    pub const HEADER_SIZE: usize = 8;
+
+    pub const FLAGS_BITS: Range<usize> = 0..8;
+    pub const VNI_BITS:   Range<usize> = 32..56;
+
+    pub fn flags(&self) -> u8 { self.data[0] }
+    /// Whether the VNI is valid, which the RFC requires.
+    pub fn vni_present(&self) -> bool { self.data[0] & 0b0000_1000 != 0 }
+    /// VXLAN network identifier, the overlay segment the inner frame belongs to.
+    pub fn vni(&self) -> u32 { be_u24(self.data, 4) }
+
+    pub fn depth(&self) -> usize { self.depth }
+
+    /// The bytes holding the field `name`, as named by its accessor, or `None` for unknown names.
+    pub fn field_bytes(&self, name: &str) -> Option<&'a [u8]> {
+        let bits = match name {
+            "flags" => Self::FLAGS_BITS,
+            "vni"   => Self::VNI_BITS,
+            _ => return None,
+        };
+        Some(bytes_of_bits(self.data, bits))
+    }
+
+    pub fn raw_payload(&self) -> &'a [u8] {
+        &self.data[Self::HEADER_SIZE..]
+    }
+
+    /// The encapsulated frame, which has no frame check sequence.
+    pub fn payload(&self) -> Result<ethernet::Ethernet<'a>, ParseError> {
+        ethernet::Ethernet::from_bytes_nested(self.raw_payload(), self.depth)
+    }
+
+    pub fn from_bytes(data: &'a [u8]) -> Result<Self, ParseError> {
+        Self::from_bytes_nested(data, 1)
+    }
+
+    /// Parses a VXLAN header that is itself inside `depth - 1` tunnels.
+    pub fn from_bytes_nested(data: &'a [u8], depth: usize) -> Result<Self, ParseError> {
+        if depth > MAX_ENCAPSULATION_DEPTH {
+            return Err(ParseError::TooDeep { layer: "Vxlan", limit: MAX_ENCAPSULATION_DEPTH });
+        }
+
+        if data.len() < Self::HEADER_SIZE {
+            return Err(ParseError::TooSmall { layer: "Vxlan", field: "data", expected: Self::HEADER_SIZE, got: data.len() });
+        }
+
+        let me = Self { data, depth };
+        if !me.vni_present() { return Err(ParseError::Invalid("Vxlan VNI flag is not set")) }
+
+        Ok(me)
+    }
+}
+
+
+impl<'a> core::fmt::Debug for Vxlan<'a> {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "    Vxlan\n")?;
+        write!(f, "        flags:   {:#04x}\n", self.flags())?;
+        write!(f, "        vni:     {:?}\n",    self.vni())?;
+        write!(f, "        payload: {:?}\n",    self.payload())?;
+        Ok(())
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec::Vec;
+    use crate::builder::{TcpBuilder, TCP_SYN};
+    use crate::ethernet::{Ethernet, Payload};
+    use crate::{ipv4, udp};
+    use crate::testing::{self, ip};
+
+    /// `inner` in a VXLAN header for network `vni`.
+    fn vxlan(vni: u32, inner: &[u8]) -> Vec<u8> {
+        let mut data = alloc::vec![0x08, 0, 0, 0];
+        data.extend_from_slice(&(vni << 8).to_be_bytes());
+        data.extend_from_slice(inner);
+        data
+    }
+
+    #[test]
+    fn decodes_the_inner_tcp_segment() {
+        let inner = testing::tcp_frame(ip(192, 168, 1, 10), ip(192, 168, 1, 20), &TcpBuilder::new(51000, 443).with_flags(TCP_SYN));
+        let outer = testing::udp_frame(ip(10, 0, 0, 1), 49152, ip(10, 0, 0, 2), PORT, &vxlan(0x12_3456, &inner));
+
+        let Ok(Payload::IPv4(ip)) = Ethernet::from_bytes(&outer).unwrap().payload() else { panic!("the outer frame isn't IPv4") };
+        let Ok(ipv4::Payload::Udp(udp)) = ip.payload() else { panic!("the outer packet isn't UDP") };
+        let Ok(udp::Payload::Vxlan(vxlan)) = udp.payload() else { panic!("port {} isn't decoded as VXLAN", PORT) };
+        assert_eq!(vxlan.vni(), 0x12_3456);
+        assert_eq!(vxlan.field_bytes("vni"), Some(&[0x12, 0x34, 0x56][..]));
+
+        let Ok(Payload::IPv4(ip)) = vxlan.payload().unwrap().payload() else { panic!("the inner frame isn't IPv4") };
+        assert_eq!(ip.source_address(), testing::ip(192, 168, 1, 10));
+        let Ok(ipv4::Payload::Tcp(tcp)) = ip.payload() else { panic!("the inner packet isn't TCP") };
+        assert_eq!((tcp.source_port(), tcp.destination_port(), tcp.syn()), (51000, 443, 1));
+    }
+
+    #[test]
+    fn requires_the_vni_flag_and_a_full_header() {
+        let mut data = vxlan(1, &[]);
+        assert!(Vxlan::from_bytes(&data).is_ok());
+        assert!(matches!(Vxlan::from_bytes(&data[..7]), Err(ParseError::TooSmall { expected: 8, got: 7, .. })));
+        data[0] = 0;
+        assert!(matches!(Vxlan::from_bytes(&data), Err(ParseError::Invalid(_))));
+    }
+
+    #[test]
+    fn stops_at_the_encapsulation_limit() {
+        let mut frame = testing::tcp_frame(ip(192, 168, 1, 10), ip(192, 168, 1, 20), &TcpBuilder::new(51000, 443));
+        for _ in 0..MAX_ENCAPSULATION_DEPTH + 1 {
+            frame = testing::udp_frame(ip(10, 0, 0, 1), 49152, ip(10, 0, 0, 2), PORT, &vxlan(1, &frame));
+        }
+
+        let mut depth = 0;
+        let mut frame = Ethernet::from_bytes(&frame).unwrap();
+        let error = loop {
+            let Ok(Payload::IPv4(ip)) = frame.payload() else { panic!("the frame isn't IPv4") };
+            let Ok(ipv4::Payload::Udp(udp)) = ip.payload() else { panic!("the packet isn't UDP") };
+            match udp.payload() {
+                Ok(udp::Payload::Vxlan(vxlan)) => {
+                    depth += 1;
+                    frame = vxlan.payload().unwrap();
+                },
+                Ok(udp::Payload::Unknown(_)) => panic!("the tunnels ended"),
+                Err(error) => break error,
+            }
+        };
+        assert_eq!(depth, MAX_ENCAPSULATION_DEPTH);
+        assert_eq!(error, ParseError::TooDeep { layer: "Vxlan", limit: MAX_ENCAPSULATION_DEPTH });
+    }
+}
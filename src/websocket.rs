@@ -0,0 +1,250 @@
+use std::collections::HashMap;
+use crate::error::ParseError;
+use crate::http;
+use crate::reassembly::FlowKey;
+use crate::endian::{be_u16, be_u64};
+
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Opcode {
+    Continuation,
+    Text,
+    Binary,
+    Close,
+    Ping,
+    Pong,
+    Other(u8),
+}
+
+impl Opcode {
+    pub fn from_value(value: u8) -> Self {
+        match value {
+            0x0 => Opcode::Continuation,
+            0x1 => Opcode::Text,
+            0x2 => Opcode::Binary,
+            0x8 => Opcode::Close,
+            0x9 => Opcode::Ping,
+            0xA => Opcode::Pong,
+            x   => Opcode::Other(x),
+        }
+    }
+
+    /// Close, ping and pong control the connection and can't be fragmented.
+    pub fn is_control(&self) -> bool {
+        matches!(self, Opcode::Close | Opcode::Ping | Opcode::Pong)
+    }
+}
+
+
+/// A WebSocket frame, https://www.rfc-editor.org/rfc/rfc6455#section-5.2.
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Frame {
+    /// Whether this is the last frame of a message.
+    pub fin: bool,
+    /// The RSV1-3 bits, only set by extensions like permessage-deflate.
+    pub reserved: u8,
+    pub opcode: Opcode,
+    /// The key the payload was masked with, which clients always do.
+    pub masking_key: Option<[u8; 4]>,
+    /// The payload, already unmasked.
+    pub payload: Vec<u8>,
+}
+
+impl Frame {
+    /// The status code of a close frame, if it has one.
+    pub fn close_code(&self) -> Option<u16> {
+        if self.opcode != Opcode::Close || self.payload.len() < 2 { return None }
+        Some(be_u16(&self.payload, 0))
+    }
+}
+
+
+#[derive(Debug)]
+pub enum Parse {
+    /// The frame and the number of bytes it occupies.
+    Complete { frame: Frame, size: usize },
+    /// The frame continues in a later segment, which needs at least `needed` more bytes.
+    Incomplete { needed: usize },
+}
+
+
+/// Whether `message` is the request or response that switches the connection to WebSocket.
+pub fn is_upgrade(message: &http::Message) -> bool {
+    message.headers().get("Upgrade").is_some_and(|x| x.eq_ignore_ascii_case("websocket"))
+}
+
+
+/// Parses the frame at the start of `data`, the reassembled payload of an upgraded connection.
+pub fn parse(data: &[u8]) -> Result<Parse, ParseError> {
+    if data.len() < 2 {
+        return Ok(Parse::Incomplete { needed: 2 - data.len() });
+    }
+
+    let fin      = data[0] & 0x80 != 0;
+    let reserved = (data[0] & 0x70) >> 4;
+    let opcode   = Opcode::from_value(data[0] & 0x0F);
+    let masked   = data[1] & 0x80 != 0;
+
+    let (length, mut offset) = match data[1] & 0x7F {
+        126 => {
+            if data.len() < 4 { return Ok(Parse::Incomplete { needed: 4 - data.len() }) }
+            (be_u16(data, 2) as u64, 4)
+        },
+        127 => {
+            if data.len() < 10 { return Ok(Parse::Incomplete { needed: 10 - data.len() }) }
+            let length = be_u64(data, 2);
+            if length >> 63 != 0 { return Err(ParseError::Invalid("Websocket payload length has the most significant bit set")) }
+            (length, 10)
+        },
+        length => (length as u64, 2),
+    };
+
+    if opcode.is_control() && (length > 125 || !fin) {
+        return Err(ParseError::Invalid("Websocket control frame is fragmented or longer than 125 bytes"));
+    }
+
+    let masking_key = if masked {
+        if data.len() < offset + 4 { return Ok(Parse::Incomplete { needed: offset + 4 - data.len() }) }
+        let key: [u8; 4] = data[offset..offset + 4].try_into().unwrap();
+        offset += 4;
+        Some(key)
+    } else {
+        None
+    };
+
+    let length = usize::try_from(length).map_err(|_| ParseError::Invalid("Websocket payload length doesn't fit in memory"))?;
+    let size = offset.checked_add(length).ok_or(ParseError::Invalid("Websocket payload length doesn't fit in memory"))?;
+    if data.len() < size {
+        return Ok(Parse::Incomplete { needed: size - data.len() });
+    }
+
+    let mut payload = data[offset..size].to_vec();
+    if let Some(key) = masking_key {
+        for (i, byte) in payload.iter_mut().enumerate() {
+            *byte ^= key[i % 4];
+        }
+    }
+
+    Ok(Parse::Complete { frame: Frame { fin, reserved, opcode, masking_key, payload }, size })
+}
+
+
+/// Follows TCP connections that switched to WebSocket. A connection switches when the server answers an
+/// upgrade request with `101 Switching Protocols`, after which its segments carry frames in both directions.
+/// Frames split across segments are buffered until the rest arrives.
+#[derive(Debug, Default)]
+pub struct WebSocketTracker {
+    /// The bytes received so far of the frame each direction is in.
+    buffers: HashMap<FlowKey, Vec<u8>>,
+}
+
+impl WebSocketTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_websocket(&self, key: &FlowKey) -> bool {
+        self.buffers.contains_key(key)
+    }
+
+    /// Returns the frames that `payload` completes on a WebSocket connection, or `None` if `key` isn't one.
+    /// A `101` upgrade response switches its connection, and is left to be decoded as HTTP.
+    pub fn push(&mut self, key: FlowKey, payload: &[u8]) -> Option<Result<Vec<Frame>, ParseError>> {
+        let Some(buffer) = self.buffers.get_mut(&key) else {
+            if let Ok(http::Parse::Complete { message, header_size }) = http::parse(payload) {
+                if matches!(&message, http::Message::Response(x) if x.status == 101) && is_upgrade(&message) {
+                    self.buffers.insert(key, payload[header_size..].to_vec());
+                    self.buffers.insert(key.reversed(), Vec::new());
+                }
+            }
+            return None;
+        };
+
+        buffer.extend_from_slice(payload);
+        let mut frames = Vec::new();
+        let mut offset = 0;
+        loop {
+            match parse(&buffer[offset..]) {
+                Ok(Parse::Complete { frame, size }) => {
+                    frames.push(frame);
+                    offset += size;
+                },
+                Ok(Parse::Incomplete { .. }) => break,
+                Err(error) => {
+                    // There's no telling where the next frame starts.
+                    buffer.clear();
+                    return Some(Err(error));
+                },
+            }
+        }
+        buffer.drain(..offset);
+        Some(Ok(frames))
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::ip;
+
+    /// "Hello" from a client, masked, from https://www.rfc-editor.org/rfc/rfc6455#section-5.7.
+    const MASKED_HELLO: [u8; 11] = [0x81, 0x85, 0x37, 0xfa, 0x21, 0x3d, 0x7f, 0x9f, 0x4d, 0x51, 0x58];
+
+    fn client() -> FlowKey {
+        FlowKey::from(crate::reassembly::StreamKey { source: ip(10, 0, 0, 1), source_port: 51000, destination: ip(10, 0, 0, 2), destination_port: 80 })
+    }
+
+    #[test]
+    fn unmasks_a_text_frame() {
+        let Parse::Complete { frame, size } = parse(&MASKED_HELLO).unwrap() else { panic!("the frame is complete") };
+        assert_eq!(size, 11);
+        assert_eq!(frame, Frame { fin: true, reserved: 0, opcode: Opcode::Text, masking_key: Some([0x37, 0xfa, 0x21, 0x3d]), payload: b"Hello".to_vec() });
+    }
+
+    #[test]
+    fn asks_for_the_rest_of_a_split_frame() {
+        for (length, needed) in [(0, 2), (1, 1), (4, 2), (6, 5), (10, 1)] {
+            let Parse::Incomplete { needed: got } = parse(&MASKED_HELLO[..length]).unwrap() else { panic!("{} bytes aren't a frame", length) };
+            assert_eq!(got, needed, "after {} bytes", length);
+        }
+
+        // A 16-bit extended length, with the payload cut off.
+        let mut binary = vec![0x82, 126, 0x01, 0x00];
+        binary.extend_from_slice(&[0xAB; 200]);
+        assert!(matches!(parse(&binary[..3]).unwrap(), Parse::Incomplete { needed: 1 }));
+        assert!(matches!(parse(&binary).unwrap(), Parse::Incomplete { needed: 56 }));
+        binary.extend_from_slice(&[0xAB; 56]);
+        let Parse::Complete { frame, size } = parse(&binary).unwrap() else { panic!("the frame is complete") };
+        assert_eq!((frame.opcode, frame.payload.len(), size), (Opcode::Binary, 256, 260));
+    }
+
+    #[test]
+    fn rejects_a_long_control_frame() {
+        assert!(parse(&[0x89, 126, 0x00, 0x80]).is_err());
+        assert!(parse(&[0x09, 0x00]).is_err());
+    }
+
+    #[test]
+    fn follows_a_connection_after_the_upgrade() {
+        let mut tracker = WebSocketTracker::new();
+        let request = b"GET /chat HTTP/1.1\r\nHost: example.com\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\r\n";
+        assert!(tracker.push(client(), request).is_none());
+        assert!(!tracker.is_websocket(&client()));
+
+        let response = b"HTTP/1.1 101 Switching Protocols\r\nUpgrade: websocket\r\nConnection: Upgrade\r\n\r\n";
+        assert!(tracker.push(client().reversed(), response).is_none());
+        assert!(tracker.is_websocket(&client()) && tracker.is_websocket(&client().reversed()));
+
+        // The client's frame arrives in two segments.
+        assert_eq!(tracker.push(client(), &MASKED_HELLO[..7]).unwrap().unwrap(), []);
+        let frames = tracker.push(client(), &MASKED_HELLO[7..]).unwrap().unwrap();
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].payload, b"Hello");
+
+        // The server answers with an unmasked ping and a close in one segment.
+        let frames = tracker.push(client().reversed(), &[0x89, 0x00, 0x88, 0x02, 0x03, 0xE8]).unwrap().unwrap();
+        assert_eq!(frames.iter().map(|x| x.opcode).collect::<Vec<_>>(), [Opcode::Ping, Opcode::Close]);
+        assert_eq!(frames[1].close_code(), Some(1000));
+    }
+}